@@ -0,0 +1,267 @@
+//! Images pre-compressed by `wlrs bake` into GPU texture blocks
+//!
+//! A baked texture sits next to its image, once for each block format GPUs
+//! sample: `sky.png` gets `sky.png.bc7.zst` and `sky.png.astc.zst`. The file
+//! is a small header followed by the zstd compressed blocks of every mip
+//! level, largest first:
+//!
+//! ```text
+//! magic "WLRSBC7\0" or "WLRSASTC" | version u32 | width u32 | height u32 | levels u32 | sha256 of the image
+//! ```
+//!
+//! Integers are little endian. The width and height are multiples of the
+//! 4×4 block size, images of another size are padded with their last row and
+//! column. The hash ties the texture to the exact image it was baked from,
+//! an edited image is loaded from the original again.
+//!
+//! Both formats take 16 bytes for a block of 4×4 pixels. `wlrs bake` encodes
+//! them with basis universal.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 8 + 4 * 4 + 32;
+
+/// Size in bytes of an encoded 4×4 block
+pub const BLOCK_SIZE: usize = 16;
+
+/// zstd level of baked files, they are written once and read often
+const COMPRESSION_LEVEL: i32 = 19;
+
+/// Block compression of a baked texture, desktop GPUs sample BC7 and mobile
+/// ones ASTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockFormat {
+    Bc7,
+    Astc,
+}
+
+impl BlockFormat {
+    pub const ALL: [Self; 2] = [Self::Bc7, Self::Astc];
+
+    /// Appended to the name of the image a texture was baked from
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Bc7 => "bc7.zst",
+            Self::Astc => "astc.zst",
+        }
+    }
+
+    fn magic(self) -> &'static [u8; 8] {
+        match self {
+            Self::Bc7 => b"WLRSBC7\0",
+            Self::Astc => b"WLRSASTC",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BakeError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("Not a baked texture")]
+    NotBaked,
+
+    #[error("Baked with an unsupported version {0}")]
+    Version(u32),
+
+    #[error("Baked from another version of the image")]
+    Stale,
+
+    #[error("Image size {0}x{1} is not a multiple of 4")]
+    Size(u32, u32),
+
+    #[error("Baked texture is truncated")]
+    Truncated,
+
+    #[error("Mip level {0} has the wrong number of blocks")]
+    Level(usize),
+}
+
+/// Where the texture of an image baked in `format` is stored
+pub fn baked_path(image: &Path, format: BlockFormat) -> PathBuf {
+    let mut name = image.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(format.extension());
+    image.with_file_name(name)
+}
+
+/// Compressed blocks of an image and its mip chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct BakedTexture {
+    pub format: BlockFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Encoded blocks of each mip level, row by row, the full size first
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl BakedTexture {
+    /// Texture of encoded mip levels, the largest `width` by `height`
+    ///
+    /// The size must be a multiple of 4, GPUs need whole blocks for the
+    /// largest level.
+    pub fn new(
+        format: BlockFormat,
+        width: u32,
+        height: u32,
+        levels: Vec<Vec<u8>>,
+    ) -> Result<Self, BakeError> {
+        if width == 0 || height == 0 || width % 4 != 0 || height % 4 != 0 {
+            return Err(BakeError::Size(width, height));
+        }
+        let texture = Self {
+            format,
+            width,
+            height,
+            levels,
+        };
+        for (level, blocks) in texture.levels.iter().enumerate() {
+            if blocks.len() != texture.level_len(level) {
+                return Err(BakeError::Level(level));
+            }
+        }
+        Ok(texture)
+    }
+
+    /// Width and height of a mip level
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// Size in bytes of the blocks of a mip level, levels smaller than a
+    /// block still take a whole one
+    fn level_len(&self, level: usize) -> usize {
+        let (width, height) = self.level_size(level);
+        width.div_ceil(4) as usize * height.div_ceil(4) as usize * BLOCK_SIZE
+    }
+
+    /// Write the texture baked from `source`, the bytes of the image file
+    pub fn write(&self, path: &Path, source: &[u8]) -> Result<(), BakeError> {
+        let mut data = Vec::with_capacity(HEADER_SIZE);
+        data.extend_from_slice(self.format.magic());
+        for value in [VERSION, self.width, self.height, self.levels.len() as u32] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&Sha256::digest(source));
+        let blocks = self.levels.concat();
+        data.extend(zstd::encode_all(blocks.as_slice(), COMPRESSION_LEVEL)?);
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Read the texture baked from the image at `source`, failing if the
+    /// image changed since
+    pub fn read(path: &Path, source: &Path) -> Result<Self, BakeError> {
+        let data = fs::read(path)?;
+        let format = BlockFormat::ALL
+            .into_iter()
+            .find(|format| data.starts_with(format.magic()))
+            .filter(|_| data.len() >= HEADER_SIZE)
+            .ok_or(BakeError::NotBaked)?;
+        let field = |index: usize| {
+            let start = format.magic().len() + index * 4;
+            u32::from_le_bytes(data[start..start + 4].try_into().unwrap())
+        };
+        let version = field(0);
+        if version != VERSION {
+            return Err(BakeError::Version(version));
+        }
+        let (width, height, level_count) = (field(1), field(2), field(3) as usize);
+        if width == 0 || height == 0 || width % 4 != 0 || height % 4 != 0 {
+            return Err(BakeError::Size(width, height));
+        }
+        let hash = &data[HEADER_SIZE - 32..HEADER_SIZE];
+        if Sha256::digest(fs::read(source)?).as_slice() != hash {
+            return Err(BakeError::Stale);
+        }
+
+        let blocks = zstd::decode_all(&data[HEADER_SIZE..])?;
+        let mut texture = Self {
+            format,
+            width,
+            height,
+            levels: Vec::with_capacity(level_count),
+        };
+        let mut offset = 0;
+        for level in 0..level_count {
+            let end = offset + texture.level_len(level);
+            let level = blocks.get(offset..end).ok_or(BakeError::Truncated)?;
+            texture.levels.push(level.to_vec());
+            offset = end;
+        }
+        Ok(texture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blocks of a mip chain, each level filled with its index
+    fn levels(sizes: &[usize]) -> Vec<Vec<u8>> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(level, &blocks)| vec![level as u8; blocks * BLOCK_SIZE])
+            .collect()
+    }
+
+    #[test]
+    fn test_mip_chain() {
+        let texture = BakedTexture::new(BlockFormat::Bc7, 8, 4, levels(&[2, 1, 1, 1])).unwrap();
+        let sizes: Vec<_> = (0..texture.levels.len())
+            .map(|level| texture.level_size(level))
+            .collect();
+        assert_eq!(sizes, [(8, 4), (4, 2), (2, 1), (1, 1)]);
+
+        assert!(matches!(
+            BakedTexture::new(BlockFormat::Bc7, 8, 4, levels(&[2, 2])),
+            Err(BakeError::Level(1))
+        ));
+        assert!(matches!(
+            BakedTexture::new(BlockFormat::Astc, 6, 4, levels(&[2])),
+            Err(BakeError::Size(6, 4))
+        ));
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("sky.png");
+        fs::write(&image, b"original").unwrap();
+        assert_eq!(
+            baked_path(&image, BlockFormat::Bc7),
+            dir.path().join("sky.png.bc7.zst")
+        );
+        assert_eq!(
+            baked_path(&image, BlockFormat::Astc),
+            dir.path().join("sky.png.astc.zst")
+        );
+
+        for format in BlockFormat::ALL {
+            let path = baked_path(&image, format);
+            let texture = BakedTexture::new(format, 16, 8, levels(&[8, 2, 1, 1, 1])).unwrap();
+            texture.write(&path, b"original").unwrap();
+            assert_eq!(BakedTexture::read(&path, &image).unwrap(), texture);
+        }
+
+        let path = baked_path(&image, BlockFormat::Bc7);
+        fs::write(&image, b"edited").unwrap();
+        assert!(matches!(
+            BakedTexture::read(&path, &image),
+            Err(BakeError::Stale)
+        ));
+        assert!(matches!(
+            BakedTexture::read(&image, &image),
+            Err(BakeError::NotBaked)
+        ));
+    }
+}
@@ -0,0 +1,487 @@
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// Errors that can occur when parsing a color
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ColorError {
+    #[error("invalid hex color '{0}', expected #RGB, #RGBA, #RRGGBB or #RRGGBBAA")]
+    InvalidHex(String),
+
+    #[error("invalid color function '{0}': {1}")]
+    InvalidFunction(String, String),
+
+    #[error("unknown color '{0}'")]
+    Unknown(String),
+}
+
+/// An sRGB-encoded color with straight alpha, every channel from 0.0 to 1.0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb8(0, 0, 0);
+    pub const TRANSPARENT: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.0,
+    };
+
+    /// Create an opaque color from 8-bit channels
+    pub const fn rgb8(r: u8, g: u8, b: u8) -> Self {
+        Self {
+            r: r as f32 / 255.0,
+            g: g as f32 / 255.0,
+            b: b as f32 / 255.0,
+            a: 1.0,
+        }
+    }
+
+    /// Parse a CSS color: hex (`#RGB`, `#RGBA`, `#RRGGBB`, `#RRGGBBAA`),
+    /// `rgb()`, `rgba()`, `hsl()`, `hsla()` or a named color
+    pub fn parse(value: &str) -> Result<Self, ColorError> {
+        let value = value.trim();
+        if let Some(hex) = value.strip_prefix('#') {
+            return parse_hex(hex).ok_or_else(|| ColorError::InvalidHex(value.to_string()));
+        }
+
+        let lower = value.to_ascii_lowercase();
+        if let Some((name, args)) = split_function(&lower) {
+            return parse_function(name, args)
+                .map_err(|reason| ColorError::InvalidFunction(value.to_string(), reason));
+        }
+
+        named(&lower).ok_or_else(|| ColorError::Unknown(value.to_string()))
+    }
+
+    /// Whether a manifest value is meant as a color rather than a file path
+    ///
+    /// This is looser than [`Color::parse`] so that a mistyped color is
+    /// reported as an invalid color instead of a missing image.
+    pub fn looks_like_color(value: &str) -> bool {
+        let value = value.trim().to_ascii_lowercase();
+        value.starts_with('#')
+            || split_function(&value)
+                .is_some_and(|(name, _)| matches!(name, "rgb" | "rgba" | "hsl" | "hsla"))
+            || named(&value).is_some()
+    }
+
+    /// The channels as `[r, g, b, a]`
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+}
+
+impl FromStr for Color {
+    type Err = ColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::parse(s)
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self
+            .to_array()
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        if a == 255 {
+            write!(f, "#{r:02x}{g:02x}{b:02x}")
+        } else {
+            write!(f, "#{r:02x}{g:02x}{b:02x}{a:02x}")
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let digits: Vec<u8> = match hex.len() {
+        // Short forms repeat each digit, #f80 is #ff8800
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8 * 17))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+
+    let mut color = Color::rgb8(digits[0], digits[1], digits[2]);
+    if let Some(&alpha) = digits.get(3) {
+        color.a = alpha as f32 / 255.0;
+    }
+    Some(color)
+}
+
+/// Split `name(args)` into its name and arguments
+fn split_function(value: &str) -> Option<(&str, &str)> {
+    let (name, rest) = value.split_once('(')?;
+    let args = rest.strip_suffix(')')?;
+    Some((name.trim(), args))
+}
+
+fn parse_function(name: &str, args: &str) -> Result<Color, String> {
+    // Both `rgb(1, 2, 3, 0.5)` and `rgb(1 2 3 / 0.5)` are valid CSS
+    let (channels, alpha) = match args.split_once('/') {
+        Some((channels, alpha)) => (channels, Some(alpha.trim())),
+        None => (args, None),
+    };
+    let mut parts: Vec<&str> = channels
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .collect();
+    let alpha = match alpha {
+        Some(alpha) => Some(alpha),
+        None if parts.len() == 4 => parts.pop(),
+        None => None,
+    };
+    if parts.len() != 3 {
+        return Err(format!("expected 3 channels, found {}", parts.len()));
+    }
+
+    let mut color = match name {
+        "rgb" | "rgba" => Color {
+            r: parse_rgb_channel(parts[0])?,
+            g: parse_rgb_channel(parts[1])?,
+            b: parse_rgb_channel(parts[2])?,
+            a: 1.0,
+        },
+        "hsl" | "hsla" => hsl_to_rgb(
+            parse_hue(parts[0])?,
+            parse_percentage(parts[1])?,
+            parse_percentage(parts[2])?,
+        ),
+        _ => return Err(format!("unknown function '{name}'")),
+    };
+    if let Some(alpha) = alpha {
+        color.a = parse_alpha(alpha)?;
+    }
+    Ok(color)
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("'{value}' is not a number"))
+}
+
+/// A red, green or blue channel, `0` to `255` or `0%` to `100%`
+fn parse_rgb_channel(value: &str) -> Result<f32, String> {
+    let channel = match value.strip_suffix('%') {
+        Some(percentage) => parse_number(percentage)? / 100.0,
+        None => parse_number(value)? / 255.0,
+    };
+    Ok(channel.clamp(0.0, 1.0))
+}
+
+/// Saturation or lightness, `%` is optional like in CSS Color 4
+fn parse_percentage(value: &str) -> Result<f32, String> {
+    let number = parse_number(value.strip_suffix('%').unwrap_or(value))?;
+    Ok((number / 100.0).clamp(0.0, 1.0))
+}
+
+/// Opacity, `0` to `1` or `0%` to `100%`
+fn parse_alpha(value: &str) -> Result<f32, String> {
+    let alpha = match value.strip_suffix('%') {
+        Some(percentage) => parse_number(percentage)? / 100.0,
+        None => parse_number(value)?,
+    };
+    Ok(alpha.clamp(0.0, 1.0))
+}
+
+/// Hue in degrees, also accepts `deg`, `rad`, `grad` and `turn` units
+fn parse_hue(value: &str) -> Result<f32, String> {
+    let degrees = if let Some(number) = value.strip_suffix("deg") {
+        parse_number(number)?
+    } else if let Some(number) = value.strip_suffix("grad") {
+        parse_number(number)? * 0.9
+    } else if let Some(number) = value.strip_suffix("rad") {
+        parse_number(number)?.to_degrees()
+    } else if let Some(number) = value.strip_suffix("turn") {
+        parse_number(number)? * 360.0
+    } else {
+        parse_number(value)?
+    };
+    Ok(degrees.rem_euclid(360.0))
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> Color {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let channel = |n: f32| {
+        let k = (n + hue / 30.0) % 12.0;
+        lightness - chroma / 2.0 * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    Color {
+        r: channel(0.0),
+        g: channel(8.0),
+        b: channel(4.0),
+        a: 1.0,
+    }
+}
+
+/// Look up a CSS named color, `name` must be lowercase
+fn named(name: &str) -> Option<Color> {
+    if name == "transparent" {
+        return Some(Color::TRANSPARENT);
+    }
+
+    NAMED_COLORS
+        .binary_search_by_key(&name, |(color_name, _)| color_name)
+        .ok()
+        .map(|index| {
+            let [r, g, b] = NAMED_COLORS[index].1;
+            Color::rgb8(r, g, b)
+        })
+}
+
+/// The CSS named colors, sorted by name
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("aliceblue", [240, 248, 255]),
+    ("antiquewhite", [250, 235, 215]),
+    ("aqua", [0, 255, 255]),
+    ("aquamarine", [127, 255, 212]),
+    ("azure", [240, 255, 255]),
+    ("beige", [245, 245, 220]),
+    ("bisque", [255, 228, 196]),
+    ("black", [0, 0, 0]),
+    ("blanchedalmond", [255, 235, 205]),
+    ("blue", [0, 0, 255]),
+    ("blueviolet", [138, 43, 226]),
+    ("brown", [165, 42, 42]),
+    ("burlywood", [222, 184, 135]),
+    ("cadetblue", [95, 158, 160]),
+    ("chartreuse", [127, 255, 0]),
+    ("chocolate", [210, 105, 30]),
+    ("coral", [255, 127, 80]),
+    ("cornflowerblue", [100, 149, 237]),
+    ("cornsilk", [255, 248, 220]),
+    ("crimson", [220, 20, 60]),
+    ("cyan", [0, 255, 255]),
+    ("darkblue", [0, 0, 139]),
+    ("darkcyan", [0, 139, 139]),
+    ("darkgoldenrod", [184, 134, 11]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgreen", [0, 100, 0]),
+    ("darkgrey", [169, 169, 169]),
+    ("darkkhaki", [189, 183, 107]),
+    ("darkmagenta", [139, 0, 139]),
+    ("darkolivegreen", [85, 107, 47]),
+    ("darkorange", [255, 140, 0]),
+    ("darkorchid", [153, 50, 204]),
+    ("darkred", [139, 0, 0]),
+    ("darksalmon", [233, 150, 122]),
+    ("darkseagreen", [143, 188, 143]),
+    ("darkslateblue", [72, 61, 139]),
+    ("darkslategray", [47, 79, 79]),
+    ("darkslategrey", [47, 79, 79]),
+    ("darkturquoise", [0, 206, 209]),
+    ("darkviolet", [148, 0, 211]),
+    ("deeppink", [255, 20, 147]),
+    ("deepskyblue", [0, 191, 255]),
+    ("dimgray", [105, 105, 105]),
+    ("dimgrey", [105, 105, 105]),
+    ("dodgerblue", [30, 144, 255]),
+    ("firebrick", [178, 34, 34]),
+    ("floralwhite", [255, 250, 240]),
+    ("forestgreen", [34, 139, 34]),
+    ("fuchsia", [255, 0, 255]),
+    ("gainsboro", [220, 220, 220]),
+    ("ghostwhite", [248, 248, 255]),
+    ("gold", [255, 215, 0]),
+    ("goldenrod", [218, 165, 32]),
+    ("gray", [128, 128, 128]),
+    ("green", [0, 128, 0]),
+    ("greenyellow", [173, 255, 47]),
+    ("grey", [128, 128, 128]),
+    ("honeydew", [240, 255, 240]),
+    ("hotpink", [255, 105, 180]),
+    ("indianred", [205, 92, 92]),
+    ("indigo", [75, 0, 130]),
+    ("ivory", [255, 255, 240]),
+    ("khaki", [240, 230, 140]),
+    ("lavender", [230, 230, 250]),
+    ("lavenderblush", [255, 240, 245]),
+    ("lawngreen", [124, 252, 0]),
+    ("lemonchiffon", [255, 250, 205]),
+    ("lightblue", [173, 216, 230]),
+    ("lightcoral", [240, 128, 128]),
+    ("lightcyan", [224, 255, 255]),
+    ("lightgoldenrodyellow", [250, 250, 210]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgreen", [144, 238, 144]),
+    ("lightgrey", [211, 211, 211]),
+    ("lightpink", [255, 182, 193]),
+    ("lightsalmon", [255, 160, 122]),
+    ("lightseagreen", [32, 178, 170]),
+    ("lightskyblue", [135, 206, 250]),
+    ("lightslategray", [119, 136, 153]),
+    ("lightslategrey", [119, 136, 153]),
+    ("lightsteelblue", [176, 196, 222]),
+    ("lightyellow", [255, 255, 224]),
+    ("lime", [0, 255, 0]),
+    ("limegreen", [50, 205, 50]),
+    ("linen", [250, 240, 230]),
+    ("magenta", [255, 0, 255]),
+    ("maroon", [128, 0, 0]),
+    ("mediumaquamarine", [102, 205, 170]),
+    ("mediumblue", [0, 0, 205]),
+    ("mediumorchid", [186, 85, 211]),
+    ("mediumpurple", [147, 112, 219]),
+    ("mediumseagreen", [60, 179, 113]),
+    ("mediumslateblue", [123, 104, 238]),
+    ("mediumspringgreen", [0, 250, 154]),
+    ("mediumturquoise", [72, 209, 204]),
+    ("mediumvioletred", [199, 21, 133]),
+    ("midnightblue", [25, 25, 112]),
+    ("mintcream", [245, 255, 250]),
+    ("mistyrose", [255, 228, 225]),
+    ("moccasin", [255, 228, 181]),
+    ("navajowhite", [255, 222, 173]),
+    ("navy", [0, 0, 128]),
+    ("oldlace", [253, 245, 230]),
+    ("olive", [128, 128, 0]),
+    ("olivedrab", [107, 142, 35]),
+    ("orange", [255, 165, 0]),
+    ("orangered", [255, 69, 0]),
+    ("orchid", [218, 112, 214]),
+    ("palegoldenrod", [238, 232, 170]),
+    ("palegreen", [152, 251, 152]),
+    ("paleturquoise", [175, 238, 238]),
+    ("palevioletred", [219, 112, 147]),
+    ("papayawhip", [255, 239, 213]),
+    ("peachpuff", [255, 218, 185]),
+    ("peru", [205, 133, 63]),
+    ("pink", [255, 192, 203]),
+    ("plum", [221, 160, 221]),
+    ("powderblue", [176, 224, 230]),
+    ("purple", [128, 0, 128]),
+    ("rebeccapurple", [102, 51, 153]),
+    ("red", [255, 0, 0]),
+    ("rosybrown", [188, 143, 143]),
+    ("royalblue", [65, 105, 225]),
+    ("saddlebrown", [139, 69, 19]),
+    ("salmon", [250, 128, 114]),
+    ("sandybrown", [244, 164, 96]),
+    ("seagreen", [46, 139, 87]),
+    ("seashell", [255, 245, 238]),
+    ("sienna", [160, 82, 45]),
+    ("silver", [192, 192, 192]),
+    ("skyblue", [135, 206, 235]),
+    ("slateblue", [106, 90, 205]),
+    ("slategray", [112, 128, 144]),
+    ("slategrey", [112, 128, 144]),
+    ("snow", [255, 250, 250]),
+    ("springgreen", [0, 255, 127]),
+    ("steelblue", [70, 130, 180]),
+    ("tan", [210, 180, 140]),
+    ("teal", [0, 128, 128]),
+    ("thistle", [216, 191, 216]),
+    ("tomato", [255, 99, 71]),
+    ("turquoise", [64, 224, 208]),
+    ("violet", [238, 130, 238]),
+    ("wheat", [245, 222, 179]),
+    ("white", [255, 255, 255]),
+    ("whitesmoke", [245, 245, 245]),
+    ("yellow", [255, 255, 0]),
+    ("yellowgreen", [154, 205, 50]),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color(value: &str, expected: [f32; 4]) {
+        let color = Color::parse(value).unwrap_or_else(|e| panic!("{value}: {e}"));
+        for (channel, expected) in color.to_array().iter().zip(expected) {
+            assert!(
+                (channel - expected).abs() < 0.002,
+                "{value} parsed as {color:?}, expected {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_color("#ff8800", [1.0, 0.533, 0.0, 1.0]);
+        assert_color("#F80", [1.0, 0.533, 0.0, 1.0]);
+        assert_color("#ff880080", [1.0, 0.533, 0.0, 0.502]);
+        assert_color("#f808", [1.0, 0.533, 0.0, 0.533]);
+
+        assert!(matches!(
+            Color::parse("#ff880"),
+            Err(ColorError::InvalidHex(_))
+        ));
+        assert!(matches!(
+            Color::parse("#gg0000"),
+            Err(ColorError::InvalidHex(_))
+        ));
+        assert!(matches!(Color::parse("#"), Err(ColorError::InvalidHex(_))));
+    }
+
+    #[test]
+    fn test_parse_functions() {
+        assert_color("rgb(255, 0, 0)", [1.0, 0.0, 0.0, 1.0]);
+        assert_color("rgba(0, 0, 255, 0.5)", [0.0, 0.0, 1.0, 0.5]);
+        assert_color("rgb(0 255 0 / 25%)", [0.0, 1.0, 0.0, 0.25]);
+        assert_color("RGB(100%, 50%, 0%)", [1.0, 0.5, 0.0, 1.0]);
+        assert_color("hsl(120, 100%, 50%)", [0.0, 1.0, 0.0, 1.0]);
+        assert_color("hsla(240deg 100% 50% / 0.5)", [0.0, 0.0, 1.0, 0.5]);
+        assert_color("hsl(0.5turn, 100%, 25%)", [0.0, 0.5, 0.5, 1.0]);
+        assert_color("hsl(0, 0%, 100%)", [1.0, 1.0, 1.0, 1.0]);
+
+        for invalid in [
+            "rgb(1, 2)",
+            "rgb(a, b, c)",
+            "cmyk(0, 0, 0, 0)",
+            "rgba(1, 2, 3, 4, 5)",
+        ] {
+            assert!(
+                matches!(Color::parse(invalid), Err(ColorError::InvalidFunction(..))),
+                "{invalid} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_named() {
+        assert_color("rebeccapurple", [0.4, 0.2, 0.6, 1.0]);
+        assert_color("White", [1.0, 1.0, 1.0, 1.0]);
+        assert_color("transparent", [0.0, 0.0, 0.0, 0.0]);
+        assert!(matches!(
+            Color::parse("blurple"),
+            Err(ColorError::Unknown(_))
+        ));
+
+        assert!(NAMED_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_looks_like_color() {
+        assert!(Color::looks_like_color("#12345"));
+        assert!(Color::looks_like_color("rgba(1, 2)"));
+        assert!(Color::looks_like_color("navy"));
+        assert!(!Color::looks_like_color("assets/navy.png"));
+        assert!(!Color::looks_like_color("background"));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Color::parse("#F80").unwrap().to_string(), "#ff8800");
+        assert_eq!(
+            Color::parse("rgba(255, 0, 0, 0.5)").unwrap().to_string(),
+            "#ff000080"
+        );
+    }
+}
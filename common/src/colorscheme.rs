@@ -0,0 +1,457 @@
+//! Color schemes of the user's theme, as written by pywal or matugen, and
+//! palettes computed from wallpapers
+//!
+//! Manifest color fields can name a color of the scheme, `{color0}` to
+//! `{color15}`, `{background}` or `{foreground}`, so wallpapers follow the
+//! theme.
+
+use std::borrow::Cow;
+
+use thiserror::Error;
+
+use serde_json::{Map, Value, json};
+
+use crate::color::Color;
+
+/// Number of numbered colors in a scheme, like a terminal palette
+pub const SCHEME_COLORS: usize = 16;
+
+/// Errors that can occur when reading a color scheme
+#[derive(Error, Debug)]
+pub enum ColorSchemeError {
+    #[error("Failed to parse color scheme: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Color scheme is neither pywal's colors.json nor matugen's JSON output")]
+    UnknownFormat,
+
+    #[error("Color scheme has an invalid {0}: {1}")]
+    InvalidColor(String, crate::color::ColorError),
+}
+
+/// Which variant of a matugen scheme to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemeMode {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl SchemeMode {
+    fn name(self) -> &'static str {
+        match self {
+            SchemeMode::Dark => "dark",
+            SchemeMode::Light => "light",
+        }
+    }
+}
+
+/// Material roles of a matugen scheme that stand in for `color0` to
+/// `color7`, the bright colors 8 to 15 are derived from them like pywal does
+const MATUGEN_ROLES: [&str; 8] = [
+    "surface",
+    "error",
+    "tertiary",
+    "secondary",
+    "primary",
+    "primary_container",
+    "secondary_container",
+    "on_surface",
+];
+
+/// A background, a foreground and 16 numbered colors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorScheme {
+    pub background: Color,
+    pub foreground: Color,
+    pub colors: [Color; SCHEME_COLORS],
+}
+
+impl Default for ColorScheme {
+    /// The xterm palette, used until a scheme is loaded
+    fn default() -> Self {
+        let colors = [
+            Color::rgb8(0x00, 0x00, 0x00),
+            Color::rgb8(0xcd, 0x00, 0x00),
+            Color::rgb8(0x00, 0xcd, 0x00),
+            Color::rgb8(0xcd, 0xcd, 0x00),
+            Color::rgb8(0x00, 0x00, 0xee),
+            Color::rgb8(0xcd, 0x00, 0xcd),
+            Color::rgb8(0x00, 0xcd, 0xcd),
+            Color::rgb8(0xe5, 0xe5, 0xe5),
+            Color::rgb8(0x7f, 0x7f, 0x7f),
+            Color::rgb8(0xff, 0x00, 0x00),
+            Color::rgb8(0x00, 0xff, 0x00),
+            Color::rgb8(0xff, 0xff, 0x00),
+            Color::rgb8(0x5c, 0x5c, 0xff),
+            Color::rgb8(0xff, 0x00, 0xff),
+            Color::rgb8(0x00, 0xff, 0xff),
+            Color::rgb8(0xff, 0xff, 0xff),
+        ];
+        Self {
+            background: colors[0],
+            foreground: colors[7],
+            colors,
+        }
+    }
+}
+
+impl ColorScheme {
+    /// Read pywal's `colors.json` or the output of `matugen --json hex`,
+    /// telling them apart by their layout
+    pub fn from_json(text: &str, mode: SchemeMode) -> Result<Self, ColorSchemeError> {
+        let json: Value = serde_json::from_str(text)?;
+        if json.get("special").is_some() {
+            Self::from_pywal(&json)
+        } else if json.get("colors").is_some() {
+            Self::from_matugen(&json, mode)
+        } else {
+            Err(ColorSchemeError::UnknownFormat)
+        }
+    }
+
+    fn from_pywal(json: &Value) -> Result<Self, ColorSchemeError> {
+        let color = |path: &str| -> Result<Color, ColorSchemeError> {
+            let value = json
+                .pointer(&format!("/{}", path.replace('.', "/")))
+                .and_then(Value::as_str)
+                .ok_or(ColorSchemeError::UnknownFormat)?;
+            Color::parse(value).map_err(|e| ColorSchemeError::InvalidColor(path.to_string(), e))
+        };
+
+        let mut colors = [Color::BLACK; SCHEME_COLORS];
+        for (index, slot) in colors.iter_mut().enumerate() {
+            *slot = color(&format!("colors.color{index}"))?;
+        }
+        Ok(Self {
+            background: color("special.background")?,
+            foreground: color("special.foreground")?,
+            colors,
+        })
+    }
+
+    /// Older matugen writes `colors.<mode>.<role>`, newer `colors.<role>.<mode>`
+    fn from_matugen(json: &Value, mode: SchemeMode) -> Result<Self, ColorSchemeError> {
+        let role = |role: &str| -> Result<Color, ColorSchemeError> {
+            let value = json
+                .pointer(&format!("/colors/{}/{role}", mode.name()))
+                .or_else(|| json.pointer(&format!("/colors/{role}/{}", mode.name())))
+                .and_then(Value::as_str)
+                .ok_or(ColorSchemeError::UnknownFormat)?;
+            Color::parse(value).map_err(|e| ColorSchemeError::InvalidColor(role.to_string(), e))
+        };
+
+        let mut base = [Color::BLACK; 8];
+        for (slot, name) in base.iter_mut().zip(MATUGEN_ROLES) {
+            *slot = role(name)?;
+        }
+        Ok(Self::from_base(base))
+    }
+
+    /// Complete a scheme from its first 8 colors, darkest first and lightest
+    /// last, with brighter copies as colors 8 to 15
+    fn from_base(base: [Color; 8]) -> Self {
+        let mut colors = [Color::BLACK; SCHEME_COLORS];
+        for (index, color) in base.into_iter().enumerate() {
+            colors[index] = color;
+            colors[index + 8] = mix(color, Color::rgb8(255, 255, 255), 0.25);
+        }
+        Self {
+            background: colors[0],
+            foreground: colors[7],
+            colors,
+        }
+    }
+
+    /// Compute a scheme from the pixels of an image: its 8 dominant colors
+    /// sorted by lightness, with the darkest darkened into the background
+    /// and the lightest lightened into the foreground
+    pub fn from_pixels(pixels: &[[u8; 3]]) -> Option<Self> {
+        if pixels.is_empty() {
+            return None;
+        }
+
+        let mut dominant = median_cut(pixels.to_vec(), 8);
+        dominant.sort_by(|a, b| luminance(*a).total_cmp(&luminance(*b)));
+        // Images with few colors give fewer boxes, repeat the lightest
+        while dominant.len() < 8 {
+            dominant.push(*dominant.last()?);
+        }
+
+        let mut base = [Color::BLACK; 8];
+        for (slot, color) in base.iter_mut().zip(dominant) {
+            *slot = color;
+        }
+        base[0] = mix(base[0], Color::BLACK, 0.6);
+        base[7] = mix(base[7], Color::rgb8(255, 255, 255), 0.6);
+        Some(Self::from_base(base))
+    }
+
+    /// The color a placeholder like `color4` or `background` names
+    pub fn get(&self, name: &str) -> Option<Color> {
+        match name {
+            "background" => Some(self.background),
+            "foreground" => Some(self.foreground),
+            _ => {
+                let index: usize = name.strip_prefix("color")?.parse().ok()?;
+                self.colors.get(index).copied()
+            }
+        }
+    }
+
+    /// Whether a manifest value is a placeholder of a scheme color, like
+    /// `{color4}`
+    pub fn is_placeholder(value: &str) -> bool {
+        placeholder_name(value).is_some_and(|name| Self::default().get(name).is_some())
+    }
+
+    /// Replace a placeholder with the color it names, other values are kept
+    pub fn resolve<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        match placeholder_name(value).and_then(|name| self.get(name)) {
+            Some(color) => Cow::Owned(color.to_string()),
+            None => Cow::Borrowed(value),
+        }
+    }
+
+    /// The scheme in pywal's `colors.json` layout
+    pub fn to_json(&self) -> Value {
+        let colors: Map<String, Value> = self
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (format!("color{index}"), value.to_string().into()))
+            .collect();
+        json!({
+            "special": {
+                "background": self.background.to_string(),
+                "foreground": self.foreground.to_string(),
+                "cursor": self.foreground.to_string(),
+            },
+            "colors": colors,
+        })
+    }
+
+    /// The colors as a flat list: background, foreground, then color0 to
+    /// color15, as sent over IPC
+    pub fn to_list(&self) -> Vec<String> {
+        [self.background, self.foreground]
+            .iter()
+            .chain(&self.colors)
+            .map(Color::to_string)
+            .collect()
+    }
+
+    /// Read a list written by [`ColorScheme::to_list`]
+    pub fn from_list(list: &[String]) -> Result<Self, ColorSchemeError> {
+        if list.len() != SCHEME_COLORS + 2 {
+            return Err(ColorSchemeError::UnknownFormat);
+        }
+        let parse = |index: usize| {
+            Color::parse(&list[index])
+                .map_err(|e| ColorSchemeError::InvalidColor(format!("color {index}"), e))
+        };
+        let mut colors = [Color::BLACK; SCHEME_COLORS];
+        for (index, slot) in colors.iter_mut().enumerate() {
+            *slot = parse(index + 2)?;
+        }
+        Ok(Self {
+            background: parse(0)?,
+            foreground: parse(1)?,
+            colors,
+        })
+    }
+
+    /// The colors for shaders, sRGB `[r, g, b, a]`: background, foreground,
+    /// then color0 to color15
+    pub fn to_uniform(&self) -> [[f32; 4]; SCHEME_COLORS + 2] {
+        let mut uniform = [[0.0; 4]; SCHEME_COLORS + 2];
+        uniform[0] = self.background.to_array();
+        uniform[1] = self.foreground.to_array();
+        for (slot, color) in uniform[2..].iter_mut().zip(self.colors) {
+            *slot = color.to_array();
+        }
+        uniform
+    }
+}
+
+/// The name inside `{name}`, surrounding whitespace is allowed
+fn placeholder_name(value: &str) -> Option<&str> {
+    value
+        .trim()
+        .strip_prefix('{')?
+        .strip_suffix('}')
+        .map(str::trim)
+}
+
+fn mix(a: Color, b: Color, amount: f32) -> Color {
+    let channel = |a: f32, b: f32| a + (b - a) * amount;
+    Color {
+        r: channel(a.r, b.r),
+        g: channel(a.g, b.g),
+        b: channel(a.b, b.b),
+        a: a.a,
+    }
+}
+
+/// Relative luminance of an sRGB color, good enough to order a palette
+fn luminance(color: Color) -> f32 {
+    0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b
+}
+
+/// Split the pixels into up to `count` boxes along their widest channel and
+/// average each box
+fn median_cut(pixels: Vec<[u8; 3]>, count: usize) -> Vec<Color> {
+    /// Channel with the largest range and that range
+    fn widest(pixels: &[[u8; 3]]) -> (usize, u8) {
+        (0..3)
+            .map(|channel| {
+                let (min, max) = pixels.iter().fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                    (min.min(pixel[channel]), max.max(pixel[channel]))
+                });
+                (channel, max.saturating_sub(min))
+            })
+            .max_by_key(|(_, range)| *range)
+            .unwrap_or((0, 0))
+    }
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < count {
+        let Some((index, channel)) = boxes
+            .iter()
+            .enumerate()
+            .map(|(index, pixels)| (index, widest(pixels)))
+            .filter(|(_, (_, range))| *range > 0)
+            .max_by_key(|(_, (_, range))| *range)
+            .map(|(index, (channel, _))| (index, channel))
+        else {
+            break;
+        };
+
+        let mut pixels = boxes.swap_remove(index);
+        pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .filter(|pixels| !pixels.is_empty())
+        .map(|pixels| {
+            let mut sum = [0u64; 3];
+            for pixel in pixels {
+                for (total, value) in sum.iter_mut().zip(pixel) {
+                    *total += *value as u64;
+                }
+            }
+            let [r, g, b] = sum.map(|total| (total / pixels.len() as u64) as u8);
+            Color::rgb8(r, g, b)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pywal_scheme() {
+        let colors: Vec<String> = (0..16)
+            .map(|index| format!("\"color{index}\": \"#0000{index:02x}\""))
+            .collect();
+        let text = format!(
+            r##"{{
+                "wallpaper": "/home/user/photo.jpg",
+                "alpha": "100",
+                "special": {{ "background": "#101010", "foreground": "#f0f0f0", "cursor": "#f0f0f0" }},
+                "colors": {{ {} }}
+            }}"##,
+            colors.join(", ")
+        );
+
+        let scheme = ColorScheme::from_json(&text, SchemeMode::Dark).unwrap();
+        assert_eq!(scheme.background, Color::rgb8(0x10, 0x10, 0x10));
+        assert_eq!(scheme.foreground, Color::rgb8(0xf0, 0xf0, 0xf0));
+        assert_eq!(scheme.colors[15], Color::rgb8(0, 0, 15));
+
+        // Round trips through its own JSON and the IPC list
+        let written = scheme.to_json().to_string();
+        assert_eq!(
+            ColorScheme::from_json(&written, SchemeMode::Dark).unwrap(),
+            scheme
+        );
+        assert_eq!(ColorScheme::from_list(&scheme.to_list()).unwrap(), scheme);
+    }
+
+    #[test]
+    fn test_matugen_scheme() {
+        let roles = |mode: &str, shade: u8| {
+            MATUGEN_ROLES
+                .iter()
+                .map(|role| format!("\"{role}\": \"#{shade:02x}0000\""))
+                .collect::<Vec<_>>()
+                .join(", ")
+                + &format!(", \"{mode}_only\": \"#000000\"")
+        };
+        let old = format!(
+            r#"{{ "colors": {{ "dark": {{ {} }}, "light": {{ {} }} }} }}"#,
+            roles("dark", 0x20),
+            roles("light", 0xe0)
+        );
+        let dark = ColorScheme::from_json(&old, SchemeMode::Dark).unwrap();
+        assert_eq!(dark.background, Color::rgb8(0x20, 0, 0));
+        let light = ColorScheme::from_json(&old, SchemeMode::Light).unwrap();
+        assert_eq!(light.colors[4], Color::rgb8(0xe0, 0, 0));
+        // Bright colors are lighter copies
+        assert!(light.colors[12].g > light.colors[4].g);
+
+        let new = format!(
+            r#"{{ "colors": {{ {} }} }}"#,
+            MATUGEN_ROLES
+                .iter()
+                .map(|role| format!(r##""{role}": {{ "dark": "#102030", "light": "#fefefe" }}"##))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let dark = ColorScheme::from_json(&new, SchemeMode::Dark).unwrap();
+        assert_eq!(dark.foreground, Color::rgb8(0x10, 0x20, 0x30));
+
+        assert!(matches!(
+            ColorScheme::from_json(r#"{ "palette": [] }"#, SchemeMode::Dark),
+            Err(ColorSchemeError::UnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let scheme = ColorScheme::default();
+        assert!(ColorScheme::is_placeholder("{color4}"));
+        assert!(ColorScheme::is_placeholder(" { background } "));
+        assert!(!ColorScheme::is_placeholder("{color16}"));
+        assert!(!ColorScheme::is_placeholder("{time}"));
+        assert!(!ColorScheme::is_placeholder("#ff0000"));
+
+        assert_eq!(scheme.resolve("{color4}"), "#0000ee");
+        assert_eq!(scheme.resolve("{foreground}"), "#e5e5e5");
+        assert_eq!(scheme.resolve("navy"), "navy");
+        assert_eq!(scheme.resolve("{color99}"), "{color99}");
+    }
+
+    #[test]
+    fn test_palette_from_pixels() {
+        assert!(ColorScheme::from_pixels(&[]).is_none());
+
+        // Half dark blue, half light orange
+        let mut pixels = vec![[10, 20, 90]; 500];
+        pixels.extend(vec![[250, 180, 60]; 500]);
+        let scheme = ColorScheme::from_pixels(&pixels).unwrap();
+        assert!(luminance(scheme.background) < luminance(Color::rgb8(10, 20, 90)));
+        assert!(luminance(scheme.foreground) > luminance(Color::rgb8(250, 180, 60)));
+        for pair in scheme.colors[1..7].windows(2) {
+            assert!(luminance(pair[0]) <= luminance(pair[1]));
+        }
+
+        let single = ColorScheme::from_pixels(&[[128, 128, 128]]).unwrap();
+        assert_eq!(single.colors.len(), SCHEME_COLORS);
+    }
+}
@@ -0,0 +1,628 @@
+//! Import of the wallpaper set in GNOME or KDE Plasma
+//!
+//! GNOME keeps it in gsettings, as an image or a slideshow XML file, Plasma
+//! in `plasma-org.kde.plasma.desktop-appletsrc`. Every image becomes a
+//! wallpaper of its own and a slideshow becomes a playlist: wallpapers that
+//! share a tag, for `wlrs random --tag` and the idle slideshow.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::manifest::{Layer, ScaleMode, WallpaperManifest};
+use crate::wallpaper_engine::Conversion;
+
+/// Image formats looked for in slideshow and wallpaper package directories
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "avif", "jxl", "heic", "svg"];
+
+/// Seconds per slide of a Plasma slideshow that does not set its own
+const DEFAULT_SLIDE_INTERVAL: u64 = 600;
+
+#[derive(Debug, Error)]
+pub enum DesktopError {
+    #[error("Failed to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// A wallpaper setting of a desktop environment
+#[derive(Debug, Clone, PartialEq)]
+pub enum DesktopWallpaper {
+    Image {
+        path: PathBuf,
+        scale_mode: ScaleMode,
+    },
+    /// A plain color, e.g. with GNOME's `picture-options` set to `none`
+    Color(String),
+    Slideshow {
+        name: String,
+        slides: Vec<Slide>,
+    },
+}
+
+/// An image of a slideshow and how long it is shown, in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slide {
+    pub path: PathBuf,
+    pub duration: f64,
+}
+
+/// The wallpapers converted from a desktop's settings
+#[derive(Debug)]
+pub struct Import {
+    pub wallpapers: Vec<Conversion>,
+    /// The tag the wallpapers of a slideshow share
+    pub playlist: Option<Playlist>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playlist {
+    pub tag: String,
+    /// Average seconds per slide, for `slideshow_interval`
+    pub interval: u64,
+}
+
+/// The wallpaper GNOME shows, from the values `gsettings get` prints for
+/// `picture-uri` (or `picture-uri-dark`), `picture-options` and
+/// `primary-color` of `org.gnome.desktop.background`
+pub fn gnome_wallpaper(
+    uri: &str,
+    options: &str,
+    color: &str,
+) -> Result<DesktopWallpaper, DesktopError> {
+    let uri = gsettings_string(uri);
+    let options = gsettings_string(options);
+    if options == "none" || uri.is_empty() {
+        return Ok(DesktopWallpaper::Color(gsettings_string(color)));
+    }
+
+    let path = file_uri_path(&uri)
+        .ok_or_else(|| DesktopError::Invalid(format!("{uri} is not a local file")))?;
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"))
+    {
+        let xml = read(&path)?;
+        let dir = path.parent().unwrap_or(Path::new("/"));
+        return Ok(DesktopWallpaper::Slideshow {
+            name: file_stem(&path),
+            slides: parse_slideshow(&xml, dir)?,
+        });
+    }
+
+    let scale_mode = match options.as_str() {
+        "scaled" => ScaleMode::Fit,
+        "stretched" => ScaleMode::Stretch,
+        "centered" => ScaleMode::Center,
+        "wallpaper" => ScaleMode::Tile,
+        // "zoom" and "spanned"
+        _ => ScaleMode::Fill,
+    };
+    Ok(DesktopWallpaper::Image { path, scale_mode })
+}
+
+/// The wallpapers of the Plasma desktops in an `appletsrc` config, one per
+/// containment that sets one, without duplicates
+pub fn plasma_wallpapers(config: &str) -> Vec<DesktopWallpaper> {
+    let sections = ini_sections(config);
+    let mut wallpapers = Vec::new();
+
+    for (section, keys) in &sections {
+        let Some(plugin) = keys.get("wallpaperplugin") else {
+            continue;
+        };
+        let general =
+            |plugin: &str| sections.get(&format!("{section}[Wallpaper][{plugin}][General]"));
+
+        let wallpaper = match plugin.as_str() {
+            "org.kde.slideshow" => {
+                let Some(general) = general(plugin) else {
+                    continue;
+                };
+                let interval = general
+                    .get("SlideInterval")
+                    .and_then(|interval| interval.parse().ok())
+                    .unwrap_or(DEFAULT_SLIDE_INTERVAL);
+                let slides = general
+                    .get("SlidePaths")
+                    .map(String::as_str)
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter(|dir| !dir.is_empty())
+                    .flat_map(|dir| images_in(&file_uri_path(dir).unwrap_or(dir.into())))
+                    .map(|path| Slide {
+                        path,
+                        duration: interval as f64,
+                    })
+                    .collect::<Vec<_>>();
+                if slides.is_empty() {
+                    continue;
+                }
+                DesktopWallpaper::Slideshow {
+                    name: "plasma-slideshow".to_string(),
+                    slides,
+                }
+            }
+            "org.kde.color" => {
+                let Some(color) = general(plugin).and_then(|general| general.get("Color")) else {
+                    continue;
+                };
+                DesktopWallpaper::Color(plasma_color(color))
+            }
+            "org.kde.image" => {
+                let Some(general) = general(plugin) else {
+                    continue;
+                };
+                let Some(path) = general
+                    .get("Image")
+                    .and_then(|image| file_uri_path(image))
+                    .and_then(|path| package_image(&path))
+                else {
+                    continue;
+                };
+                let scale_mode = match general.get("FillMode").map(String::as_str) {
+                    Some("0") => ScaleMode::Stretch,
+                    Some("1") => ScaleMode::Fit,
+                    Some("3") => ScaleMode::Tile,
+                    Some("6") => ScaleMode::Center,
+                    _ => ScaleMode::Fill,
+                };
+                DesktopWallpaper::Image { path, scale_mode }
+            }
+            _ => continue,
+        };
+        if !wallpapers.contains(&wallpaper) {
+            wallpapers.push(wallpaper);
+        }
+    }
+    wallpapers
+}
+
+/// Convert a desktop wallpaper into wlrs wallpapers, a playlist for a
+/// slideshow
+pub fn convert(wallpaper: &DesktopWallpaper) -> Result<Import, DesktopError> {
+    match wallpaper {
+        DesktopWallpaper::Image { path, scale_mode } => Ok(Import {
+            wallpapers: vec![convert_image(path, scale_mode.clone(), None)?],
+            playlist: None,
+        }),
+        DesktopWallpaper::Color(color) => {
+            let mut manifest = blank_manifest()?;
+            manifest.name = format!("color-{}", color.trim_start_matches('#'));
+            manifest.layers.push(Layer::new_background_color(color));
+            Ok(Import {
+                wallpapers: vec![Conversion {
+                    manifest,
+                    files: BTreeMap::new(),
+                    unsupported: Vec::new(),
+                }],
+                playlist: None,
+            })
+        }
+        DesktopWallpaper::Slideshow { name, slides } => {
+            if slides.is_empty() {
+                return Err(DesktopError::Invalid(format!(
+                    "The slideshow {name} has no images"
+                )));
+            }
+            let tag = name.clone();
+            let mut names = HashSet::new();
+            let mut wallpapers = Vec::new();
+            for slide in slides {
+                let mut conversion = convert_image(&slide.path, ScaleMode::Fill, Some(&tag))?;
+                // A slide may come back later in the slideshow
+                if !names.insert(conversion.manifest.name.clone()) {
+                    continue;
+                }
+                conversion.manifest.name = format!("{name}-{}", conversion.manifest.name);
+                wallpapers.push(conversion);
+            }
+            let total: f64 = slides.iter().map(|slide| slide.duration).sum();
+            Ok(Import {
+                wallpapers,
+                playlist: Some(Playlist {
+                    tag,
+                    interval: (total / slides.len() as f64).round().max(1.0) as u64,
+                }),
+            })
+        }
+    }
+}
+
+/// Parse a GNOME slideshow, the images it shows and for how long
+///
+/// Transitions count towards the image shown before them. Of files given in
+/// several sizes, the largest is used.
+pub fn parse_slideshow(xml: &str, dir: &Path) -> Result<Vec<Slide>, DesktopError> {
+    let background = elements(xml, "background")
+        .into_iter()
+        .next()
+        .ok_or_else(|| DesktopError::Invalid("No <background> in the slideshow".to_string()))?
+        .1;
+
+    let mut slides: Vec<Slide> = Vec::new();
+    for (tag, _, content) in children(background) {
+        let duration = elements(content, "duration")
+            .first()
+            .and_then(|(_, duration)| duration.trim().parse::<f64>().ok())
+            .unwrap_or(0.0);
+        match tag {
+            "static" => {
+                let Some((_, file)) = elements(content, "file").into_iter().next() else {
+                    continue;
+                };
+                let file = largest_size(file);
+                let path = PathBuf::from(unescape(file.trim()));
+                slides.push(Slide {
+                    path: dir.join(path),
+                    duration,
+                });
+            }
+            "transition" => {
+                if let Some(last) = slides.last_mut() {
+                    last.duration += duration;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(slides)
+}
+
+/// The text of a gsettings value, without the quotes of strings
+fn gsettings_string(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('\'')
+        .and_then(|value| value.strip_suffix('\''))
+        .map(|value| value.replace("\\'", "'"))
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Path of a `file://` URI, or of a plain absolute path
+fn file_uri_path(uri: &str) -> Option<PathBuf> {
+    let path = match uri.strip_prefix("file://") {
+        Some(path) => percent_decode(path),
+        None if uri.starts_with('/') => uri.to_string(),
+        None => return None,
+    };
+    Some(PathBuf::from(path))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// The image of a Plasma wallpaper package, the largest one of its
+/// `contents/images`, or `path` itself if it is not a package
+fn package_image(path: &Path) -> Option<PathBuf> {
+    if !path.is_dir() {
+        return Some(path.to_path_buf());
+    }
+    images_in(&path.join("contents").join("images"))
+        .into_iter()
+        .max_by_key(|image| {
+            // Named after their size, like 1920x1080.png
+            let stem = file_stem(image);
+            let mut size = stem.split('x').map(|side| side.parse::<u64>().unwrap_or(0));
+            size.next().unwrap_or(0) * size.next().unwrap_or(0)
+        })
+}
+
+/// The images in `dir` and its subdirectories, sorted by path
+fn images_in(dir: &Path) -> Vec<PathBuf> {
+    let mut images = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return images;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            images.extend(images_in(&path));
+        } else if path.extension().is_some_and(|ext| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|image| ext.eq_ignore_ascii_case(image))
+        }) {
+            images.push(path);
+        }
+    }
+    images.sort();
+    images
+}
+
+/// A Plasma color, `r,g,b` with 0-255 components, as a hex color
+fn plasma_color(color: &str) -> String {
+    let components: Vec<u8> = color
+        .split(',')
+        .filter_map(|component| component.trim().parse().ok())
+        .collect();
+    match components[..] {
+        [r, g, b, ..] => format!("#{r:02x}{g:02x}{b:02x}"),
+        _ => color.to_string(),
+    }
+}
+
+fn convert_image(
+    path: &Path,
+    scale_mode: ScaleMode,
+    tag: Option<&str>,
+) -> Result<Conversion, DesktopError> {
+    if !path.is_file() {
+        return Err(DesktopError::Invalid(format!(
+            "{} does not exist",
+            path.display()
+        )));
+    }
+    let file = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "wallpaper".to_string());
+
+    let mut manifest = blank_manifest()?;
+    manifest.name = file_stem(path);
+    manifest.scale_mode = scale_mode;
+    manifest.tags = tag.map(str::to_string).into_iter().collect();
+    manifest.layers.push(Layer::new_background_image(&file));
+    Ok(Conversion {
+        manifest,
+        files: BTreeMap::from([(file, path.to_path_buf())]),
+        unsupported: Vec::new(),
+    })
+}
+
+/// A manifest without layers that never redraws
+fn blank_manifest() -> Result<WallpaperManifest, DesktopError> {
+    let mut manifest = WallpaperManifest::parse("name = \"imported\"")
+        .map_err(|e| DesktopError::Invalid(e.to_string()))?;
+    manifest.framerate = 0;
+    manifest.tickrate = 0;
+    Ok(manifest)
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn read(path: &Path) -> Result<String, DesktopError> {
+    fs::read_to_string(path).map_err(|source| DesktopError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Sections of an INI file like Plasma's, by their full `[a][b]` header
+fn ini_sections(text: &str) -> BTreeMap<String, BTreeMap<String, String>> {
+    let mut sections: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    let mut current = String::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            current = line.to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            sections
+                .entry(current.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    sections
+}
+
+/// Attributes and content of every `<tag>` element among the top-level
+/// elements of `xml`
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<(&'a str, &'a str)> {
+    children(xml)
+        .into_iter()
+        .filter(|(name, _, _)| *name == tag)
+        .map(|(_, attributes, content)| (attributes, content))
+        .collect()
+}
+
+/// Name, attributes and content of the top-level elements in `xml`,
+/// skipping declarations, comments and empty elements
+fn children(xml: &str) -> Vec<(&str, &str, &str)> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let after = &rest[start + 1..];
+        if after.starts_with("!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+            continue;
+        }
+        let Some(end_of_tag) = after.find('>') else {
+            break;
+        };
+        let tag = &after[..end_of_tag];
+        let body = &after[end_of_tag + 1..];
+        if tag.starts_with(['?', '!', '/']) || tag.ends_with('/') {
+            rest = body;
+            continue;
+        }
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        let close = format!("</{name}>");
+        let Some(end) = body.find(&close) else {
+            rest = body;
+            continue;
+        };
+        found.push((name, attributes, &body[..end]));
+        rest = &body[end + close.len()..];
+    }
+    found
+}
+
+/// The path of the largest `<size>` of a `<file>`, or its text if it has none
+fn largest_size(file: &str) -> &str {
+    let attribute = |attributes: &str, name: &str| -> u64 {
+        attributes
+            .split_once(&format!("{name}=\""))
+            .and_then(|(_, value)| value.split('"').next())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0)
+    };
+    elements(file, "size")
+        .into_iter()
+        .max_by_key(|(attributes, _)| {
+            attribute(attributes, "width") * attribute(attributes, "height")
+        })
+        .map(|(_, path)| path)
+        .unwrap_or(file)
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_gnome_slideshow() {
+        let dir = tempdir().unwrap();
+        let xml = r#"<?xml version="1.0"?>
+<!-- A day in three pictures -->
+<background>
+  <starttime><year>2024</year><hour>6</hour></starttime>
+  <static><duration>100.0</duration><file>morning.jpg</file></static>
+  <transition type="overlay"><duration>20.0</duration><from>morning.jpg</from><to>/usr/share/night.png</to></transition>
+  <static>
+    <duration>200</duration>
+    <file>
+      <size width="1024" height="768">small &amp; old.png</size>
+      <size width="3840" height="2160">large &amp; new.png</size>
+    </file>
+  </static>
+</background>"#;
+        fs::write(dir.path().join("day.xml"), xml).unwrap();
+        fs::write(dir.path().join("morning.jpg"), b"jpeg").unwrap();
+        fs::write(dir.path().join("large & new.png"), b"png").unwrap();
+
+        let uri = format!("'file://{}/day.xml'", dir.path().display());
+        let wallpaper = gnome_wallpaper(&uri, "'zoom'", "'#023c88'").unwrap();
+        let DesktopWallpaper::Slideshow { name, slides } = &wallpaper else {
+            panic!("{wallpaper:?} is not a slideshow");
+        };
+        assert_eq!(name, "day");
+        assert_eq!(slides.len(), 2);
+        assert_eq!(slides[0].path, dir.path().join("morning.jpg"));
+        assert_eq!(slides[0].duration, 120.0);
+        assert_eq!(slides[1].path, dir.path().join("large & new.png"));
+
+        let import = convert(&wallpaper).unwrap();
+        assert_eq!(
+            import.playlist,
+            Some(Playlist {
+                tag: "day".to_string(),
+                interval: 160,
+            })
+        );
+        assert_eq!(import.wallpapers.len(), 2);
+        let first = &import.wallpapers[0];
+        assert_eq!(first.manifest.name, "day-morning");
+        assert_eq!(first.manifest.tags, vec!["day".to_string()]);
+        assert!(first.files.contains_key("morning.jpg"));
+
+        assert_eq!(
+            gnome_wallpaper("'file:///a.png'", "'none'", "'#023c88'").unwrap(),
+            DesktopWallpaper::Color("#023c88".to_string())
+        );
+        assert_eq!(
+            gnome_wallpaper("'file:///my%20wallpaper.png'", "'scaled'", "''").unwrap(),
+            DesktopWallpaper::Image {
+                path: PathBuf::from("/my wallpaper.png"),
+                scale_mode: ScaleMode::Fit,
+            }
+        );
+    }
+
+    #[test]
+    fn test_plasma_wallpapers() {
+        let dir = tempdir().unwrap();
+        let images = dir.path().join("Next").join("contents").join("images");
+        fs::create_dir_all(&images).unwrap();
+        fs::write(images.join("1920x1080.png"), b"png").unwrap();
+        fs::write(images.join("5120x2880.png"), b"png").unwrap();
+        let slides = dir.path().join("slides");
+        fs::create_dir_all(&slides).unwrap();
+        fs::write(slides.join("a.jpg"), b"jpeg").unwrap();
+        fs::write(slides.join("notes.txt"), b"text").unwrap();
+
+        let config = format!(
+            "[Containments][1]\n\
+             activityId=abc\n\
+             wallpaperplugin=org.kde.image\n\
+             \n\
+             [Containments][1][Wallpaper][org.kde.image][General]\n\
+             FillMode=1\n\
+             Image=file://{next}\n\
+             \n\
+             [Containments][2]\n\
+             plugin=org.kde.panel\n\
+             \n\
+             [Containments][3]\n\
+             wallpaperplugin=org.kde.slideshow\n\
+             \n\
+             [Containments][3][Wallpaper][org.kde.slideshow][General]\n\
+             SlideInterval=300\n\
+             SlidePaths={slides}/\n\
+             \n\
+             [Containments][4]\n\
+             wallpaperplugin=org.kde.color\n\
+             \n\
+             [Containments][4][Wallpaper][org.kde.color][General]\n\
+             Color=255,128,0\n",
+            next = dir.path().join("Next").display(),
+            slides = slides.display(),
+        );
+        let wallpapers = plasma_wallpapers(&config);
+        assert_eq!(
+            wallpapers,
+            vec![
+                DesktopWallpaper::Image {
+                    path: images.join("5120x2880.png"),
+                    scale_mode: ScaleMode::Fit,
+                },
+                DesktopWallpaper::Slideshow {
+                    name: "plasma-slideshow".to_string(),
+                    slides: vec![Slide {
+                        path: slides.join("a.jpg"),
+                        duration: 300.0,
+                    }],
+                },
+                DesktopWallpaper::Color("#ff8000".to_string()),
+            ]
+        );
+    }
+}
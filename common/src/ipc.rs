@@ -1,25 +1,108 @@
 use std::{
-    env, fs,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, Write},
     marker::PhantomData,
     ops::{Deref, DerefMut},
     os::{
-        fd::AsFd,
-        unix::net::{UnixListener, UnixStream},
+        fd::{AsFd, AsRawFd},
+        unix::{
+            fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
     },
     path::Path,
+    sync::Mutex,
+    time::Duration,
 };
 
 use bincode::{config, decode_from_std_read, encode_into_std_write};
+use thiserror::Error;
 
 use crate::types::{IntoRequest, Response};
 
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum IpcError {
+    #[error("Socket error: {0}")]
     Io(std::io::Error),
+    #[error("Failed to encode message: {0}")]
     Encoding(bincode::error::EncodeError),
+    #[error("Failed to decode message: {0}")]
     Decoding(bincode::error::DecodeError),
+    #[error("Unexpected response")]
     InvalidResponse,
+    #[error("Connection closed")]
     ConnectionClosed,
+    #[error("The daemon did not answer in time")]
+    Timeout,
+    #[error("{}", already_running_message(*.0))]
+    AlreadyRunning(Option<u32>),
+}
+
+/// How long a request waits for the daemon by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Timeout of the connections opened from now on, see [`set_timeout`]
+static TIMEOUT: Mutex<Option<Duration>> = Mutex::new(Some(DEFAULT_TIMEOUT));
+
+/// Set how long connections opened with [`IpcSocket::connect`] wait for the
+/// daemon to take or answer a request, None to wait forever
+pub fn set_timeout(timeout: Option<Duration>) {
+    *TIMEOUT.lock().unwrap() = timeout;
+}
+
+/// Namespace of the daemon talked to or run, see [`set_namespace`]
+static NAMESPACE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Talk to, or run as, the daemon of `namespace` rather than the session's
+/// own, e.g. in a nested compositor or for tests
+///
+/// Namespaced daemons have their own socket, lock and state files, and
+/// their own layer-shell namespace.
+pub fn set_namespace(namespace: Option<String>) {
+    *NAMESPACE.lock().unwrap() = namespace;
+}
+
+/// Namespace set with [`set_namespace`], if any
+pub fn namespace() -> Option<String> {
+    NAMESPACE.lock().unwrap().clone()
+}
+
+/// Check a namespace given on the command line, it ends up in file names
+pub fn parse_namespace(namespace: &str) -> Result<String, String> {
+    if namespace.is_empty() {
+        return Err("the namespace is empty".to_string());
+    }
+    if !namespace
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err("only letters, digits, '-', '_' and '.' are allowed".to_string());
+    }
+    Ok(namespace.to_string())
+}
+
+/// Name of the socket file of the daemon on `display` in `namespace`
+fn socket_name(display: &str, namespace: Option<&str>) -> String {
+    match namespace {
+        Some(namespace) => format!("wlrs-{display}-{namespace}.sock"),
+        None => format!("wlrs-{display}.sock"),
+    }
+}
+
+fn already_running_message(pid: Option<u32>) -> String {
+    match pid {
+        Some(pid) => format!("Another daemon is already running (pid {pid})"),
+        None => "Another daemon is already running".to_string(),
+    }
+}
+
+/// Process on the other end of a connection, as the kernel saw it connect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peer {
+    pub pid: u32,
+    pub uid: u32,
+    pub gid: u32,
 }
 
 pub struct IpcSocket<T> {
@@ -27,20 +110,48 @@ pub struct IpcSocket<T> {
     marker: PhantomData<T>,
 }
 
-pub struct Listener(UnixListener);
+pub struct Listener {
+    listener: UnixListener,
+    /// Lock file held while the daemon runs, None for sockets passed by
+    /// systemd, which already ensures a single instance
+    lock: Option<File>,
+}
 pub struct Stream(UnixStream);
 
+/// Most bytes a message may decode to. The largest real one is the detailed
+/// wallpaper list, around a KiB per wallpaper, this leaves room for many
+/// thousands. A length prefix claiming more fails to decode rather than
+/// having `receive` allocate it.
+const MESSAGE_LIMIT: usize = 16 * 1024 * 1024;
+
 // Configuration for bincode serialization
-fn bincode_config() -> config::Configuration {
-    config::standard()
+fn bincode_config()
+-> config::Configuration<config::LittleEndian, config::Varint, config::Limit<MESSAGE_LIMIT>> {
+    config::standard().with_limit::<MESSAGE_LIMIT>()
 }
 
 impl AsFd for Listener {
+    fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+        self.listener.as_fd()
+    }
+}
+
+impl AsFd for Stream {
     fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
         self.0.as_fd()
     }
 }
 
+impl Drop for Listener {
+    fn drop(&mut self) {
+        // Only the daemon holding the lock owns the socket file, the lock is
+        // released after the file is gone
+        if self.lock.is_some() {
+            let _ = fs::remove_file(IpcSocket::<Listener>::socket_file());
+        }
+    }
+}
+
 impl<T> IpcSocket<T> {
     fn new(data: T) -> Self {
         Self {
@@ -54,7 +165,15 @@ impl<T> IpcSocket<T> {
         std::fs::metadata("/proc/self").map(|m| m.uid()).unwrap()
     }
 
+    /// Path of the daemon's socket, `$WLRS_SOCKET` if set, otherwise one per
+    /// Wayland display and namespace in the runtime dir
     pub fn socket_file() -> String {
+        if let Some(socket) = env::var("WLRS_SOCKET")
+            .ok()
+            .filter(|socket| !socket.is_empty())
+        {
+            return socket;
+        }
         let runtime =
             env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| format!("/run/user/{}", Self::getuid()));
 
@@ -73,7 +192,17 @@ impl<T> IpcSocket<T> {
             "wayland-0.sock".to_string()
         };
 
-        format!("{runtime}/wlrs-{display}.sock")
+        format!(
+            "{runtime}/{}",
+            socket_name(&display, namespace().as_deref())
+        )
+    }
+
+    /// File locked by the running daemon, holding its pid
+    pub fn lock_file() -> String {
+        let socket_file = Self::socket_file();
+        let base = socket_file.strip_suffix(".sock").unwrap_or(&socket_file);
+        format!("{base}.lock")
     }
 }
 
@@ -91,25 +220,56 @@ impl<T> DerefMut for IpcSocket<T> {
 }
 
 impl IpcSocket<Listener> {
+    /// Bind the daemon's socket, making sure no other daemon is running
+    ///
+    /// The daemon holds a lock on `lock_file()` while it runs. A socket file
+    /// left behind by a daemon that crashed is removed, but a socket that still
+    /// accepts connections belongs to a live daemon and is never taken over.
     pub fn listen() -> Result<Self, IpcError> {
         let socket_file = Self::socket_file();
 
-        // Make sure the parent directory exists
+        // Make sure the parent directory exists, private like a runtime dir
         if let Some(parent) = Path::new(&socket_file).parent() {
-            fs::create_dir_all(parent).map_err(IpcError::Io)?;
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(parent)
+                .map_err(IpcError::Io)?;
         }
 
-        // Remove the socket file if it already exists
+        let mut lock = lock(&Self::lock_file())?;
+
         if Path::new(&socket_file).exists() {
+            // A daemon without the lock, e.g. an older version, may still be listening
+            if UnixStream::connect(&socket_file).is_ok() {
+                return Err(IpcError::AlreadyRunning(None));
+            }
             fs::remove_file(&socket_file).map_err(IpcError::Io)?;
         }
 
         let listener = UnixListener::bind(&socket_file).map_err(IpcError::Io)?;
-        Ok(Self::new(Listener(listener)))
+        // Only the user may connect, even if the runtime dir is shared. A
+        // connection made before this is still turned away by the daemon,
+        // which checks who its peers are.
+        fs::set_permissions(&socket_file, fs::Permissions::from_mode(0o600))
+            .map_err(IpcError::Io)?;
+        write_pid(&mut lock).map_err(IpcError::Io)?;
+        Ok(Self::new(Listener {
+            listener,
+            lock: Some(lock),
+        }))
+    }
+
+    /// Use a listener that is already bound, e.g. one passed by socket activation
+    pub fn from_listener(listener: UnixListener) -> Self {
+        Self::new(Listener {
+            listener,
+            lock: None,
+        })
     }
 
     pub fn accept(&self) -> Result<IpcSocket<Stream>, IpcError> {
-        let (stream, _) = self.0.accept().map_err(IpcError::Io)?;
+        let (stream, _) = self.listener.accept().map_err(IpcError::Io)?;
         Ok(IpcSocket::new(Stream(stream)))
     }
 }
@@ -118,15 +278,62 @@ impl IpcSocket<Stream> {
     pub fn connect() -> Result<Self, IpcError> {
         let socket_file = Self::socket_file();
         let stream = UnixStream::connect(&socket_file).map_err(IpcError::Io)?;
-        Ok(Self::new(Stream(stream)))
+        let socket = Self::new(Stream(stream));
+        socket.set_timeout(*TIMEOUT.lock().unwrap())?;
+        Ok(socket)
+    }
+
+    /// Credentials of the process on the other end, from `SO_PEERCRED`
+    pub fn peer(&self) -> Result<Peer, IpcError> {
+        let mut credentials = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: `credentials` and `len` outlive the call and `len` is its size
+        let result = unsafe {
+            libc::getsockopt(
+                self.0.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                (&mut credentials as *mut libc::ucred).cast(),
+                &mut len,
+            )
+        };
+        if result != 0 {
+            return Err(IpcError::Io(io::Error::last_os_error()));
+        }
+        Ok(Peer {
+            pid: credentials.pid as u32,
+            uid: credentials.uid,
+            gid: credentials.gid,
+        })
+    }
+
+    /// Fail reads and writes that block for longer than `timeout` with
+    /// [`IpcError::Timeout`], None to block forever
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> Result<(), IpcError> {
+        self.0.set_read_timeout(timeout).map_err(IpcError::Io)?;
+        self.0.set_write_timeout(timeout).map_err(IpcError::Io)
     }
 
     pub fn send<T: bincode::Encode>(&mut self, message: &T) -> Result<usize, IpcError> {
-        encode_into_std_write(message, &mut self.0, bincode_config()).map_err(IpcError::Encoding)
+        encode_into_std_write(message, &mut self.0, bincode_config()).map_err(|e| match e {
+            bincode::error::EncodeError::Io { inner, .. } if is_timeout(&inner) => {
+                IpcError::Timeout
+            }
+            e => IpcError::Encoding(e),
+        })
     }
 
     pub fn receive<T: bincode::Decode<()>>(&mut self) -> Result<T, IpcError> {
-        decode_from_std_read(&mut self.0, bincode_config()).map_err(IpcError::Decoding)
+        decode_from_std_read(&mut self.0, bincode_config()).map_err(|e| match e {
+            bincode::error::DecodeError::Io { inner, .. } if is_timeout(&inner) => {
+                IpcError::Timeout
+            }
+            e => IpcError::Decoding(e),
+        })
     }
 
     pub fn request<R: IntoRequest>(&mut self, request: R) -> Result<R::Response, IpcError>
@@ -148,3 +355,110 @@ impl IpcSocket<Stream> {
         UnixStream::connect(&socket_file).is_ok()
     }
 }
+
+/// Whether a socket operation failed because its timeout ran out
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Take the exclusive lock on the lock file, failing if another daemon holds it
+///
+/// The lock is released by the kernel when the daemon exits, even if it crashes.
+fn lock(path: &str) -> Result<File, IpcError> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(path)
+        .map_err(IpcError::Io)?;
+
+    // SAFETY: the descriptor is valid for as long as `file` lives
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::WouldBlock {
+            return Err(IpcError::Io(error));
+        }
+        let mut pid = String::new();
+        let _ = file.read_to_string(&mut pid);
+        return Err(IpcError::AlreadyRunning(pid.trim().parse().ok()));
+    }
+    Ok(file)
+}
+
+/// Replace the content of the lock file with the pid of this process
+fn write_pid(file: &mut File) -> io::Result<()> {
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}", std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Checkhealth, Health};
+
+    #[test]
+    fn test_request_timeout() {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let mut socket = IpcSocket::new(Stream(ours));
+        socket.set_timeout(Some(Duration::from_millis(50))).unwrap();
+
+        // The other end never answers
+        assert!(matches!(
+            socket.request(Checkhealth),
+            Err(IpcError::Timeout)
+        ));
+
+        let mut daemon = IpcSocket::new(Stream(theirs));
+        let _: crate::types::Request = daemon.receive().unwrap();
+        daemon.send(&Response::Health(Health(true))).unwrap();
+        assert!(matches!(
+            socket.receive::<Response>(),
+            Ok(Response::Health(Health(true)))
+        ));
+    }
+
+    #[test]
+    fn test_oversized_message() {
+        let (ours, mut theirs) = UnixStream::pair().unwrap();
+        let mut socket = IpcSocket::new(Stream(ours));
+
+        // A string claiming to be a TiB long, as a u64 varint
+        theirs.write_all(&[253]).unwrap();
+        theirs.write_all(&(1u64 << 40).to_le_bytes()).unwrap();
+        assert!(matches!(
+            socket.receive::<String>(),
+            Err(IpcError::Decoding(
+                bincode::error::DecodeError::LimitExceeded
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_namespace() {
+        assert_eq!(socket_name("wayland-1", None), "wlrs-wayland-1.sock");
+        assert_eq!(
+            socket_name("wayland-1", Some("nested")),
+            "wlrs-wayland-1-nested.sock"
+        );
+        assert_eq!(parse_namespace("test_2.a-b"), Ok("test_2.a-b".to_string()));
+        assert!(parse_namespace("").is_err());
+        assert!(parse_namespace("../x").is_err());
+        assert!(parse_namespace("a b").is_err());
+    }
+
+    #[test]
+    fn test_peer() {
+        let (ours, _theirs) = UnixStream::pair().unwrap();
+        let socket = IpcSocket::new(Stream(ours));
+        let peer = socket.peer().unwrap();
+        assert_eq!(peer.pid, std::process::id());
+        assert_eq!(peer.uid, unsafe { libc::getuid() });
+        assert_eq!(peer.gid, unsafe { libc::getgid() });
+    }
+}
@@ -1,4 +1,10 @@
+pub mod baked;
+pub mod color;
+pub mod colorscheme;
+pub mod desktop;
 pub mod ipc;
 pub mod types;
 pub mod manifest;
+pub mod repository;
 pub mod wallpaper;
+pub mod wallpaper_engine;
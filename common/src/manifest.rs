@@ -1,6 +1,33 @@
+use crate::{color::Color, colorscheme::ColorScheme};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, io, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
 use thiserror::Error;
+use toml_edit::{DocumentMut, Item, TableLike, Value};
+
+/// Split a setting key like `clouds.speed` into the layer and the parameter
+pub fn split_setting_key(key: &str) -> Result<(&str, &str), ManifestError> {
+    key.split_once('.')
+        .filter(|(layer, param)| !layer.is_empty() && !param.is_empty())
+        .ok_or_else(|| {
+            ManifestError::ValidationError(format!(
+                "Setting {key} is no declared parameter and not written as layer.parameter"
+            ))
+        })
+}
+
+/// Read the value of a setting as TOML, so `0.5` is a number and `true` a
+/// boolean, taking anything that is not valid TOML as a string
+pub fn parse_setting_value(text: &str) -> toml::Value {
+    let text = text.trim();
+    toml::from_str::<toml::Table>(&format!("value = {text}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(text.to_string()))
+}
 
 /// Errors that can occur when working with wallpaper manifests
 #[derive(Error, Debug)]
@@ -13,18 +40,51 @@ pub enum ManifestError {
 
     #[error("Invalid manifest: {0}")]
     ValidationError(String),
+
+    #[error(
+        "The wallpaper requires a newer wlrs, its manifest has schema version {0} and this one reads up to {SCHEMA_VERSION}"
+    )]
+    UnsupportedVersion(i64),
 }
 
+/// Version of the manifest format, the one written and the newest read
+///
+/// Manifests without a `schema_version` are version 1 and migrated when
+/// parsed. Version 2 spells out the `"default"` framerate and tickrate,
+/// which meant 30 FPS for one and the compositor's rate for the other, and
+/// the `sunny`, `rainy` and `snowy` weather aliases.
+pub const SCHEMA_VERSION: i64 = 2;
+
 /// Content configuration for a layer
 #[derive(Debug, Serialize, Clone, PartialEq, Default)]
 #[serde(untagged)]
 pub enum LayerContent {
-    /// A solid color (CSS-style color string)
+    /// A solid color (CSS color: hex, `rgb()`, `rgba()`, `hsl()`, `hsla()` or a name),
+    /// or a color of the user's scheme like `{color4}`
     Color(String),
 
     /// An image file (path relative to wallpaper directory)
     Image(String),
 
+    /// An SVG file (path relative to wallpaper directory), rasterised at the
+    /// output's resolution
+    Svg(String),
+
+    /// Text drawn with a system font, written as a table
+    Text(TextContent),
+
+    /// A linear, radial or conic gradient, written as a table
+    Gradient(GradientContent),
+
+    /// Live picture of another output, written as a table
+    Capture(CaptureContent),
+
+    /// A web page rendered by a headless browser, written as a table
+    Web(WebContent),
+
+    /// Album art of the track a media player plays, written as a table
+    AlbumArt(AlbumArtContent),
+
     /// No content specified (defaults to transparent)
     #[default]
     None,
@@ -35,18 +95,372 @@ impl<'de> Deserialize<'de> for LayerContent {
     where
         D: serde::Deserializer<'de>,
     {
-        let value = String::deserialize(deserializer)?;
-        if value.starts_with('#') || value.contains("rgba") {
+        /// Shapes `content` may take in a manifest
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawContent {
+            Path(String),
+            Text(TextContent),
+            Gradient(GradientContent),
+            Capture(CaptureContent),
+            Web(WebContent),
+            AlbumArt(AlbumArtContent),
+        }
+
+        let value = match RawContent::deserialize(deserializer)? {
+            RawContent::Path(value) => value,
+            RawContent::Text(text) => return Ok(LayerContent::Text(text)),
+            RawContent::Gradient(gradient) => return Ok(LayerContent::Gradient(gradient)),
+            RawContent::Capture(capture) => return Ok(LayerContent::Capture(capture)),
+            RawContent::Web(web) => return Ok(LayerContent::Web(web)),
+            RawContent::AlbumArt(art) => return Ok(LayerContent::AlbumArt(art)),
+        };
+        if Color::looks_like_color(&value) || ColorScheme::is_placeholder(&value) {
             Ok(LayerContent::Color(value))
+        } else if is_svg_path(&value) {
+            Ok(LayerContent::Svg(value))
         } else {
             Ok(LayerContent::Image(value))
         }
     }
 }
 
+/// Whether a content path names an SVG document (`.svg` or gzipped `.svgz`)
+fn is_svg_path(value: &str) -> bool {
+    let value = value.to_ascii_lowercase();
+    value.ends_with(".svg") || value.ends_with(".svgz")
+}
+
+/// Configuration of a text layer
+///
+/// `{time}`, `{date}` and `{hostname}` in the text are replaced when the layer
+/// updates, `{time:FORMAT}` and `{date:FORMAT}` take a strftime format.
+/// Daemons built with MPRIS support also fill in `{title}`, `{artist}` and
+/// `{album}` of the playing track.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TextContent {
+    /// Text to draw, may span several lines
+    pub text: String,
+
+    /// Font family name (defaults to the system sans-serif font)
+    #[serde(default)]
+    pub font: Option<String>,
+
+    /// Font size in logical pixels
+    #[serde(default = "default_font_size")]
+    pub size: f32,
+
+    /// Text color (CSS color)
+    #[serde(default = "default_text_color")]
+    pub color: String,
+
+    /// Alignment of lines, also decides which edge of the text sits at `position`
+    #[serde(default)]
+    pub align: TextAlign,
+
+    /// Where the text is placed, from `[0.0, 0.0]` at the top left of the
+    /// screen to `[1.0, 1.0]` at the bottom right
+    #[serde(default = "default_text_position")]
+    pub position: [f32; 2],
+}
+
+/// Horizontal alignment for text layers
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TextAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+/// Most color stops a gradient layer can have
+pub const MAX_GRADIENT_STOPS: usize = 8;
+
+/// Configuration of a gradient layer
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GradientContent {
+    /// Shape of the gradient
+    pub gradient: GradientKind,
+
+    /// Colors along the gradient, between 2 and [`MAX_GRADIENT_STOPS`]
+    pub stops: Vec<ColorStop>,
+
+    /// Direction in degrees for linear and conic gradients, 0 points up and
+    /// 90 to the right like CSS
+    #[serde(default = "default_gradient_angle")]
+    pub angle: f32,
+
+    /// Center of radial and conic gradients, from `[0.0, 0.0]` at the top
+    /// left of the screen to `[1.0, 1.0]` at the bottom right
+    #[serde(default = "default_gradient_center")]
+    pub center: [f32; 2],
+
+    /// Rotation of the angle in degrees per second, 0 keeps the gradient still
+    #[serde(default)]
+    pub speed: f32,
+}
+
+/// Shape of a gradient layer
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientKind {
+    /// Colors change along a straight line
+    Linear,
+    /// Colors change with the distance from the center
+    Radial,
+    /// Colors change with the angle around the center
+    Conic,
+}
+
+/// A color in a gradient
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ColorStop {
+    /// A color spaced evenly between its positioned neighbours
+    Color(String),
+    /// A color at a position from 0.0 to 1.0 along the gradient
+    Positioned { color: String, position: f32 },
+}
+
+impl ColorStop {
+    pub fn color(&self) -> &str {
+        match self {
+            ColorStop::Color(color) | ColorStop::Positioned { color, .. } => color,
+        }
+    }
+
+    pub fn position(&self) -> Option<f32> {
+        match self {
+            ColorStop::Color(_) => None,
+            ColorStop::Positioned { position, .. } => Some(*position),
+        }
+    }
+}
+
+impl GradientContent {
+    /// Check the number of stops and that their positions are in range
+    pub fn validate(&self, layer: &str) -> Result<(), ManifestError> {
+        if !(2..=MAX_GRADIENT_STOPS).contains(&self.stops.len()) {
+            return Err(ManifestError::ValidationError(format!(
+                "Gradient layer {layer} needs between 2 and {MAX_GRADIENT_STOPS} color stops, found {}",
+                self.stops.len()
+            )));
+        }
+
+        if let Some(position) = self
+            .stops
+            .iter()
+            .filter_map(ColorStop::position)
+            .find(|position| !(0.0..=1.0).contains(position))
+        {
+            return Err(ManifestError::ValidationError(format!(
+                "Gradient layer {layer} has a color stop at {position}, positions must be between 0 and 1"
+            )));
+        }
+
+        for stop in &self.stops {
+            validate_color(layer, stop.color())?;
+        }
+
+        Ok(())
+    }
+
+    /// Stops with every position filled in
+    ///
+    /// Like CSS, the first and last stops default to 0 and 1, stops without a
+    /// position are spread evenly between their neighbours, and a position
+    /// smaller than an earlier one is raised to it.
+    pub fn resolved_stops(&self) -> Vec<(&str, f32)> {
+        let count = self.stops.len();
+        let mut positions: Vec<Option<f32>> = self.stops.iter().map(ColorStop::position).collect();
+        if let Some(first) = positions.first_mut() {
+            first.get_or_insert(0.0);
+        }
+        if let Some(last) = positions.last_mut() {
+            last.get_or_insert(1.0);
+        }
+
+        // Keep positions in order
+        let mut highest = 0.0f32;
+        for position in positions.iter_mut().flatten() {
+            highest = highest.max(*position);
+            *position = highest;
+        }
+
+        // Spread runs of missing positions between the known ones around them
+        let mut start = 0;
+        while start < count {
+            if positions[start].is_some() {
+                start += 1;
+                continue;
+            }
+            let end = (start..count)
+                .find(|&index| positions[index].is_some())
+                .unwrap_or(count - 1);
+            let before = positions[start - 1].unwrap_or(0.0);
+            let after = positions[end].unwrap_or(1.0);
+            let steps = (end - start + 1) as f32;
+            for (step, position) in positions[start..end].iter_mut().enumerate() {
+                *position = Some(before + (after - before) * (step + 1) as f32 / steps);
+            }
+            start = end;
+        }
+
+        self.stops
+            .iter()
+            .zip(positions)
+            .map(|(stop, position)| (stop.color(), position.unwrap_or(0.0)))
+            .collect()
+    }
+}
+
+/// Configuration of a capture layer, showing what another output shows
+///
+/// Outputs are captured whole, so a single application is shown by giving it
+/// an output of its own, e.g. a headless one created through the compositor,
+/// and starting it with `command`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CaptureContent {
+    /// Name of the output to show, like `HEADLESS-1`
+    pub capture: String,
+
+    /// Shell command started with the wallpaper and stopped along with it
+    #[serde(default)]
+    pub command: Option<String>,
+
+    /// Whether the cursor is drawn when it is over the captured output
+    #[serde(default)]
+    pub cursor: bool,
+}
+
+impl CaptureContent {
+    pub fn validate(&self, layer: &str) -> Result<(), ManifestError> {
+        if self.capture.trim().is_empty() {
+            return Err(ManifestError::ValidationError(format!(
+                "Capture layer {layer} needs the name of an output"
+            )));
+        }
+        if self
+            .command
+            .as_ref()
+            .is_some_and(|command| command.trim().is_empty())
+        {
+            return Err(ManifestError::ValidationError(format!(
+                "Capture layer {layer} has an empty command"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Configuration of a web layer, a page rendered offscreen by a headless
+/// browser at the output's size
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WebContent {
+    /// Page to show, an `http(s)://` or `file://` URL, or an HTML file
+    /// relative to the wallpaper directory
+    pub web: String,
+
+    /// Whether pointer motion, clicks and scrolling over the wallpaper are
+    /// forwarded to the page
+    #[serde(default)]
+    pub input: bool,
+
+    /// Whether the page's background is left transparent so the layers
+    /// below show through, instead of white
+    #[serde(default)]
+    pub transparent: bool,
+}
+
+impl WebContent {
+    pub fn validate(&self, layer: &str) -> Result<(), ManifestError> {
+        if self.web.trim().is_empty() {
+            return Err(ManifestError::ValidationError(format!(
+                "Web layer {layer} needs a URL or an HTML file"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether `web` is a URL rather than a path in the wallpaper directory
+    pub fn is_url(&self) -> bool {
+        self.web.split_once(':').is_some_and(|(scheme, _)| {
+            scheme.len() > 1
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+')
+        })
+    }
+}
+
+/// Configuration of an album art layer, the cover of the track an MPRIS
+/// media player plays or was paused on
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AlbumArtContent {
+    /// How the art is fitted to the output
+    pub album_art: ArtFit,
+
+    /// Image shown while no player has art, relative to the wallpaper
+    /// directory (defaults to transparent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+}
+
+/// How album art is fitted to an output of another aspect ratio
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtFit {
+    /// Scaled to cover the output, cropped at the center
+    #[default]
+    Cover,
+    /// Scaled to fit the output, transparent around it
+    Contain,
+}
+
+/// Something a wallpaper may do beyond computing its layers, granted by
+/// the user when the wallpaper is installed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Permission {
+    /// Fetch data from the web with `wlrs.fetch`
+    Network,
+    /// Read files with `io.open` and `io.lines`
+    FileRead,
+    /// Start the `command` of capture layers
+    Process,
+    /// Show web pages in web layers
+    Web,
+}
+
+impl Permission {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Permission::Network => "network",
+            Permission::FileRead => "file-read",
+            Permission::Process => "process",
+            Permission::Web => "web",
+        }
+    }
+
+    /// What the permission allows, for the user deciding on it
+    pub fn description(self) -> &'static str {
+        match self {
+            Permission::Network => "fetch data from the web",
+            Permission::FileRead => "read any of your files",
+            Permission::Process => "run commands as you",
+            Permission::Web => "show web pages, which run scripts of their own",
+        }
+    }
+}
+
 /// The root structure for a wallpaper manifest
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WallpaperManifest {
+    /// Version of the manifest format, see [`SCHEMA_VERSION`]
+    #[serde(default = "default_schema_version")]
+    pub schema_version: i64,
+
     // Core metadata - directly at the root level
     /// Name of the wallpaper
     pub name: String,
@@ -63,6 +477,15 @@ pub struct WallpaperManifest {
     #[serde(default)]
     pub description: String,
 
+    /// Image shown in wallpaper listings, relative to the wallpaper directory
+    /// (optional, a thumbnail is rendered from the layers otherwise)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+
+    /// Free-form tags used to search and filter wallpapers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
     // Performance and display settings
     /// The frames per second for visual updates
     /// Special values:
@@ -82,15 +505,186 @@ pub struct WallpaperManifest {
     #[serde(default = "default_tps", deserialize_with = "deserialize_tickrate")]
     pub tickrate: i32,
 
+    /// Seconds the wallpaper's animations run behind the daemon's shared
+    /// clock, which keeps every output in step, to offset it from other
+    /// wallpapers (optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase: Option<f32>,
+
     /// Scale mode for images
     #[serde(default)]
     pub scale_mode: ScaleMode,
 
+    /// Opacity where no opaque layer covers the screen (0.0 to 1.0), lower
+    /// values let the desktop show through on compositors that blur or blend
+    /// the background
+    #[serde(default = "default_background_alpha")]
+    pub background_alpha: f32,
+
+    /// Seconds without input before the daemon's idle action applies to
+    /// this wallpaper, overriding its config, never if 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<u64>,
+
+    /// Hashes of the wallpaper's files by their path relative to the
+    /// wallpaper directory, like `"images/sky.png" = "sha256:…"`, checked
+    /// when the wallpaper is installed or loaded (optional)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub assets: BTreeMap<String, String>,
+
+    /// Parameters the user can change with `wlrs config-wallpaper`, declared
+    /// so editors can offer a control for each, by their name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, ParamSpec>,
+
+    /// What the wallpaper needs to be allowed to do, like `needs = ["network"]`,
+    /// it runs without it until the user grants it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub needs: Vec<Permission>,
+
+    /// Presets that change or hide some layers, picked with
+    /// `wlrs set-wallpaper --variant`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<Variant>,
+
     // All visual layers including background and effects
     #[serde(default)]
     pub layers: Vec<Layer>,
 }
 
+/// Kind of value a declared parameter takes
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    Float,
+    Int,
+    Bool,
+    /// A CSS color, see [`Color::parse`]
+    Color,
+    String,
+    /// One of the strings in `options`
+    Choice,
+}
+
+impl ParamType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ParamType::Float => "float",
+            ParamType::Int => "int",
+            ParamType::Bool => "bool",
+            ParamType::Color => "color",
+            ParamType::String => "string",
+            ParamType::Choice => "choice",
+        }
+    }
+}
+
+/// A parameter of layers the user is meant to change, like
+/// `[params.radius] type = "float" min = 0 max = 20 default = 3.5`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ParamSpec {
+    #[serde(rename = "type")]
+    pub kind: ParamType,
+    /// Name shown by editors, the parameter's name if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Increment of sliders and spin boxes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<f64>,
+    /// Value of layers that do not set the parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<toml::Value>,
+    /// Values of a `choice`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    /// Layers the parameter is passed to, every layer that sets it if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<String>,
+}
+
+impl ParamSpec {
+    /// Check that `value` is of the declared type and within the range
+    pub fn check(&self, value: &toml::Value) -> Result<(), String> {
+        let number = match (self.kind, value) {
+            (ParamType::Float, toml::Value::Float(number)) => Some(*number),
+            (ParamType::Float | ParamType::Int, toml::Value::Integer(number)) => {
+                Some(*number as f64)
+            }
+            (ParamType::Bool, toml::Value::Boolean(_))
+            | (ParamType::String, toml::Value::String(_)) => None,
+            (ParamType::Color, toml::Value::String(color)) => {
+                Color::parse(color).map_err(|e| e.to_string())?;
+                None
+            }
+            (ParamType::Choice, toml::Value::String(choice)) => {
+                if !self.options.contains(choice) {
+                    return Err(format!(
+                        "{choice} is not one of {}",
+                        self.options.join(", ")
+                    ));
+                }
+                None
+            }
+            (kind, value) => return Err(format!("{value} is not a {}", kind.as_str())),
+        };
+        if let Some(number) = number {
+            if self.min.is_some_and(|min| number < min) || self.max.is_some_and(|max| number > max)
+            {
+                return Err(format!(
+                    "{number} is not between {} and {}",
+                    self.min.map_or("-inf".to_string(), |min| min.to_string()),
+                    self.max.map_or("inf".to_string(), |max| max.to_string())
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the parameter is passed to `layer`
+    fn targets(&self, name: &str, layer: &Layer) -> bool {
+        if self.layers.is_empty() {
+            layer.params.contains_key(name)
+        } else {
+            self.layers.contains(&layer.name)
+        }
+    }
+}
+
+/// A preset of a wallpaper, like a calmer or a darker take on it
+///
+/// Layers with a `when.variant` are only shown in the variants it names.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Variant {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// Layers not shown in this variant
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hide: Vec<String>,
+    /// Changes to layers, by their name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub layers: BTreeMap<String, LayerOverride>,
+}
+
+/// What a variant changes about a layer
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LayerOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f32>,
+    /// Parameters replacing the ones of the layer, the others are kept
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub params: HashMap<String, toml::Value>,
+}
+
 /// A layer within a wallpaper (background or effect)
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Layer {
@@ -113,9 +707,21 @@ pub struct Layer {
     #[serde(default = "default_opacity")]
     pub opacity: f32,
 
+    /// Texture filtering used when the layer's image is scaled
+    #[serde(default)]
+    pub sampling: Sampling,
+
+    /// What a shader effect processes, its own image or the layers below it
+    #[serde(default, skip_serializing_if = "EffectInput::is_image")]
+    pub input: EffectInput,
+
     /// Additional parameters for the layer effect
     #[serde(default)]
     pub params: HashMap<String, toml::Value>,
+
+    /// Conditions the layer is only shown under
+    #[serde(default, skip_serializing_if = "When::is_always")]
+    pub when: When,
 }
 
 impl Layer {
@@ -127,7 +733,10 @@ impl Layer {
             effect_type: None,
             z_index: -1000, // Very bottom layer
             opacity: 1.0,
+            sampling: Sampling::default(),
+            input: EffectInput::default(),
             params: HashMap::new(),
+            when: When::default(),
         }
     }
 
@@ -139,7 +748,10 @@ impl Layer {
             effect_type: None,
             z_index: -999, // Just above background color
             opacity: 1.0,
+            sampling: Sampling::default(),
+            input: EffectInput::default(),
             params: HashMap::new(),
+            when: When::default(),
         }
     }
 
@@ -156,7 +768,10 @@ impl Layer {
             effect_type: Some(effect_type),
             z_index,
             opacity: 1.0,
+            sampling: Sampling::default(),
+            input: EffectInput::default(),
             params: HashMap::new(),
+            when: When::default(),
         }
     }
 
@@ -166,6 +781,223 @@ impl Layer {
     }
 }
 
+/// Conditions a layer is shown under, all of the given ones have to hold
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct When {
+    /// Weather the layer is shown in, one kind or a list of them
+    #[serde(
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub weather: Vec<Weather>,
+
+    /// Outputs the layer is shown on, one name or a list of them
+    #[serde(
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub output: Vec<String>,
+
+    /// Aspect ratios of the outputs the layer is shown on, like `"21:9"`,
+    /// `"portrait"` or `"landscape"`, one or a list of them
+    #[serde(
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub aspect: Vec<Aspect>,
+
+    /// Variants of the wallpaper the layer is only shown in, one name or a
+    /// list of them
+    #[serde(
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub variant: Vec<String>,
+
+    /// Output size in pixels the layer needs at least or at most
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_height: Option<u32>,
+}
+
+impl When {
+    /// Whether the layer is shown regardless of circumstances
+    pub fn is_always(&self) -> bool {
+        self.weather.is_empty() && self.variant.is_empty() && !self.depends_on_screen()
+    }
+
+    /// Whether the layer is shown in `variant`, the default look if `None`
+    pub fn in_variant(&self, variant: Option<&str>) -> bool {
+        self.variant.is_empty()
+            || variant.is_some_and(|variant| self.variant.iter().any(|own| own == variant))
+    }
+
+    /// Whether the layer is only shown on some outputs
+    pub fn depends_on_screen(&self) -> bool {
+        !self.output.is_empty()
+            || !self.aspect.is_empty()
+            || self.min_width.is_some()
+            || self.max_width.is_some()
+            || self.min_height.is_some()
+            || self.max_height.is_some()
+    }
+
+    /// Whether the conditions hold in `environment`
+    ///
+    /// Without a screen, e.g. while preloading, the conditions on outputs
+    /// are taken to hold, so every layer that may be shown gets loaded.
+    pub fn holds(&self, environment: &Environment) -> bool {
+        let weather = self.weather.is_empty() || self.weather.contains(&environment.weather);
+        let screen = environment.screen.as_ref().is_none_or(|screen| {
+            let within = |value: u32, min: Option<u32>, max: Option<u32>| {
+                min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+            };
+            (self.output.is_empty() || self.output.contains(&screen.output))
+                && (self.aspect.is_empty() || self.aspect.iter().any(|aspect| aspect.fits(screen)))
+                && within(screen.width, self.min_width, self.max_width)
+                && within(screen.height, self.min_height, self.max_height)
+        });
+        weather && screen
+    }
+}
+
+/// How far the aspect ratio of an output may be off the one of a `when`
+/// condition, monitors sold as 21:9 range from 2.33 to 2.39
+const ASPECT_TOLERANCE: f32 = 0.03;
+
+/// Shape of the outputs a layer is shown on
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub enum Aspect {
+    /// Width to height, like 16:9
+    Ratio(u32, u32),
+    /// Taller than wide
+    Portrait,
+    /// Wider than tall, or square
+    Landscape,
+}
+
+impl Aspect {
+    /// Whether `screen` has this shape
+    pub fn fits(self, screen: &Screen) -> bool {
+        match self {
+            Aspect::Ratio(width, height) => {
+                let wanted = width as f32 / height as f32;
+                let actual = screen.width as f32 / screen.height.max(1) as f32;
+                (actual / wanted - 1.0).abs() <= ASPECT_TOLERANCE
+            }
+            Aspect::Portrait => screen.height > screen.width,
+            Aspect::Landscape => screen.width >= screen.height,
+        }
+    }
+}
+
+impl TryFrom<String> for Aspect {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "portrait" => return Ok(Aspect::Portrait),
+            "landscape" => return Ok(Aspect::Landscape),
+            _ => {}
+        }
+        value
+            .split_once(':')
+            .and_then(|(width, height)| Some((width.trim().parse().ok()?, height.trim().parse().ok()?)))
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .map(|(width, height)| Aspect::Ratio(width, height))
+            .ok_or_else(|| {
+                format!("invalid aspect {value:?}, expected a ratio like \"16:9\", \"portrait\" or \"landscape\"")
+            })
+    }
+}
+
+impl From<Aspect> for String {
+    fn from(aspect: Aspect) -> Self {
+        match aspect {
+            Aspect::Ratio(width, height) => format!("{width}:{height}"),
+            Aspect::Portrait => "portrait".to_string(),
+            Aspect::Landscape => "landscape".to_string(),
+        }
+    }
+}
+
+/// The circumstances layer conditions are evaluated in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Environment {
+    /// Weather outside, clear while it is not known
+    pub weather: Weather,
+    /// Colors that placeholders like `{color4}` stand for
+    pub colors: ColorScheme,
+    /// Output the layers are drawn on, none when preloading
+    pub screen: Option<Screen>,
+}
+
+/// An output as `when` conditions see it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Screen {
+    /// Name of the output, like `DP-1`
+    pub output: String,
+    /// Size in pixels, as the output is oriented
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Kinds of weather layers can be shown in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Weather {
+    #[default]
+    #[serde(alias = "sunny")]
+    Clear,
+    Cloudy,
+    Fog,
+    #[serde(alias = "rainy")]
+    Rain,
+    #[serde(alias = "snowy")]
+    Snow,
+    /// Thunderstorms
+    Storm,
+}
+
+impl Weather {
+    pub fn name(self) -> &'static str {
+        match self {
+            Weather::Clear => "clear",
+            Weather::Cloudy => "cloudy",
+            Weather::Fog => "fog",
+            Weather::Rain => "rain",
+            Weather::Snow => "snow",
+            Weather::Storm => "storm",
+        }
+    }
+}
+
+/// Deserialize a single value or a list of them
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
 /// Type of effect
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "snake_case")]
@@ -187,9 +1019,37 @@ pub enum ShaderType {
     Wave,
     Glitch,
     Gaussian,
+    /// Rings spreading from a point, distorting the image
+    Ripple,
+    /// Falling snowflakes, needs no content
+    Snow,
+    /// Slanted rain streaks, needs no content
+    Rain,
+    /// Flames rising from the bottom, needs no content
+    Fire,
+    /// Flowing rainbow bands, needs no content
+    Plasma,
+    /// Stars flying towards the viewer, needs no content
+    Starfield,
+    /// Color grading with a 3D lookup table, see `ColorGrade`
+    Lut,
     Custom(String),
 }
 
+impl ShaderType {
+    /// Whether the effect generates its picture instead of processing an image
+    pub fn is_procedural(&self) -> bool {
+        matches!(
+            self,
+            ShaderType::Snow
+                | ShaderType::Rain
+                | ShaderType::Fire
+                | ShaderType::Plasma
+                | ShaderType::Starfield
+        )
+    }
+}
+
 /// Scale mode for background images
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "snake_case")]
@@ -207,27 +1067,792 @@ pub enum ScaleMode {
     Tile,
 }
 
-/// Function to deserialize framerate from either a number or a string
-fn deserialize_framerate<'de, D>(deserializer: D) -> Result<i32, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    use serde::de::{Error, Unexpected, Visitor};
-    use std::fmt;
+/// Texture filtering for image layers
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Sampling {
+    /// Nearest-neighbour without mipmaps, keeps pixel art sharp
+    Nearest,
+    /// Bilinear filtering without mipmaps
+    Linear,
+    /// Trilinear filtering across generated mipmaps
+    #[default]
+    Trilinear,
+    /// Trilinear filtering with 16x anisotropic filtering
+    Anisotropic,
+}
 
-    struct RateVisitor;
+impl Sampling {
+    /// Whether a full mip chain should be generated for this mode
+    pub fn uses_mipmaps(&self) -> bool {
+        matches!(self, Sampling::Trilinear | Sampling::Anisotropic)
+    }
+}
 
-    impl<'de> Visitor<'de> for RateVisitor {
-        type Value = i32;
+/// Texture a shader effect reads
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectInput {
+    /// The layer's own image
+    #[default]
+    Image,
+    /// Everything drawn below the layer, e.g. to blur or distort the background
+    Below,
+}
 
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a number or string representing a framerate")
-        }
+impl EffectInput {
+    pub fn is_image(&self) -> bool {
+        *self == EffectInput::Image
+    }
+}
 
-        fn visit_i8<E>(self, value: i8) -> Result<i32, E>
-        where
-            E: Error,
-        {
+/// Built-in lookup tables of the `lut` effect
+pub const LUT_PRESETS: &[&str] = &["identity", "warm", "cool", "sepia", "mono"];
+
+/// Lookup table of a color grading effect
+#[derive(Debug, Clone, PartialEq)]
+pub enum LutSource {
+    /// One of `LUT_PRESETS`
+    Preset(String),
+    /// A `.cube` file, relative to the wallpaper directory until the
+    /// wallpaper is loaded
+    File(PathBuf),
+}
+
+impl LutSource {
+    fn parse(layer: &str, value: &str) -> Result<Self, ManifestError> {
+        if LUT_PRESETS.contains(&value) {
+            Ok(LutSource::Preset(value.to_string()))
+        } else if value.to_ascii_lowercase().ends_with(".cube") {
+            Ok(LutSource::File(PathBuf::from(value)))
+        } else {
+            Err(ManifestError::ValidationError(format!(
+                "Layer {layer}: '{value}' is neither a .cube file nor one of the presets {}",
+                LUT_PRESETS.join(", ")
+            )))
+        }
+    }
+}
+
+/// Settings of a `lut` effect, read from its `params`
+///
+/// `lut` grades the picture by day. With `night_lut` set the effect blends
+/// over to it around `sunset` and back around `sunrise`, both in local hours.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorGrade {
+    pub lut: LutSource,
+    pub night_lut: Option<LutSource>,
+    pub sunrise: f32,
+    pub sunset: f32,
+    /// Hours a blend between the tables takes, centered on sunrise and sunset
+    pub transition: f32,
+}
+
+impl Default for ColorGrade {
+    fn default() -> Self {
+        Self {
+            lut: LutSource::Preset("identity".to_string()),
+            night_lut: None,
+            sunrise: 7.0,
+            sunset: 19.0,
+            transition: 1.0,
+        }
+    }
+}
+
+impl ColorGrade {
+    /// Read the settings from the `params` of layer `layer`
+    pub fn from_params(
+        layer: &str,
+        params: &HashMap<String, toml::Value>,
+    ) -> Result<Self, ManifestError> {
+        let invalid =
+            |message: String| ManifestError::ValidationError(format!("Layer {layer}: {message}"));
+        let lut = |key: &str| -> Result<Option<LutSource>, ManifestError> {
+            match params.get(key) {
+                None => Ok(None),
+                Some(toml::Value::String(value)) => LutSource::parse(layer, value).map(Some),
+                Some(_) => Err(invalid(format!(
+                    "{key} must be a preset name or a .cube file"
+                ))),
+            }
+        };
+        let hours = |key: &str, default: f32| -> Result<f32, ManifestError> {
+            let value = match params.get(key) {
+                None => return Ok(default),
+                Some(toml::Value::Float(value)) => *value as f32,
+                Some(toml::Value::Integer(value)) => *value as f32,
+                Some(_) => return Err(invalid(format!("{key} must be a number of hours"))),
+            };
+            if (0.0..=24.0).contains(&value) {
+                Ok(value)
+            } else {
+                Err(invalid(format!("{key} must be between 0 and 24 hours")))
+            }
+        };
+
+        let defaults = ColorGrade::default();
+        let grade = ColorGrade {
+            lut: lut("lut")?.unwrap_or(defaults.lut),
+            night_lut: lut("night_lut")?,
+            sunrise: hours("sunrise", defaults.sunrise)?,
+            sunset: hours("sunset", defaults.sunset)?,
+            transition: hours("transition", defaults.transition)?,
+        };
+        if grade.sunrise >= grade.sunset {
+            return Err(invalid("sunrise must be before sunset".to_string()));
+        }
+        Ok(grade)
+    }
+
+    /// The tables read from files
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        [Some(&self.lut), self.night_lut.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|source| match source {
+                LutSource::File(path) => Some(path.as_path()),
+                LutSource::Preset(_) => None,
+            })
+    }
+
+    /// How much of the night table shows at `hour` local time, 0 by day and 1 at night
+    pub fn night_amount(&self, hour: f32) -> f32 {
+        if self.night_lut.is_none() {
+            return 0.0;
+        }
+        // Rises from 0 to 1 over the transition centered on `edge`
+        let ramp = |edge: f32| {
+            if self.transition <= 0.0 {
+                if hour >= edge { 1.0 } else { 0.0 }
+            } else {
+                ((hour - edge) / self.transition + 0.5).clamp(0.0, 1.0)
+            }
+        };
+        1.0 - ramp(self.sunrise) * (1.0 - ramp(self.sunset))
+    }
+}
+
+/// Kind of noise in a `NoiseTexture`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseKind {
+    /// Smooth gradient noise
+    Perlin,
+    /// Gradient noise on a simplex grid, without Perlin's axis-aligned streaks
+    Simplex,
+    /// Distance to the nearest of scattered points, for cells and caustics
+    Worley,
+}
+
+impl NoiseKind {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "perlin" => Some(Self::Perlin),
+            "simplex" => Some(Self::Simplex),
+            "worley" => Some(Self::Worley),
+            _ => None,
+        }
+    }
+}
+
+/// Tileable noise texture generated for a shader effect, read from its `params`
+///
+/// `noise` picks the kind, `noise_size` the width and height in pixels and
+/// `noise_seed` varies the pattern. The channels hold the noise at 4, 8 and
+/// 16 features per tile, alpha their sum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseTexture {
+    pub kind: NoiseKind,
+    pub size: u32,
+    pub seed: u32,
+}
+
+impl NoiseTexture {
+    pub const DEFAULT_SIZE: u32 = 256;
+    /// Largest texture, 64 MiB
+    pub const MAX_SIZE: u32 = 4096;
+
+    /// Read the settings from the `params` of layer `layer`, None if it
+    /// declares no noise
+    pub fn from_params(
+        layer: &str,
+        params: &HashMap<String, toml::Value>,
+    ) -> Result<Option<Self>, ManifestError> {
+        let invalid =
+            |message: String| ManifestError::ValidationError(format!("Layer {layer}: {message}"));
+        let kind = match params.get("noise") {
+            None => return Ok(None),
+            Some(toml::Value::String(name)) => NoiseKind::parse(name).ok_or_else(|| {
+                invalid(format!(
+                    "unknown noise '{name}', expected perlin, simplex or worley"
+                ))
+            })?,
+            Some(_) => return Err(invalid("noise must be the name of a noise".to_string())),
+        };
+        let integer = |key: &str, default: u32, range: std::ops::RangeInclusive<u32>| {
+            let value = match params.get(key) {
+                None => return Ok(default),
+                Some(toml::Value::Integer(value)) => u32::try_from(*value).ok(),
+                Some(_) => None,
+            };
+            value.filter(|value| range.contains(value)).ok_or_else(|| {
+                invalid(format!(
+                    "{key} must be a whole number between {} and {}",
+                    range.start(),
+                    range.end()
+                ))
+            })
+        };
+
+        Ok(Some(NoiseTexture {
+            kind,
+            size: integer("noise_size", Self::DEFAULT_SIZE, 16..=Self::MAX_SIZE)?,
+            seed: integer("noise_seed", 0, 0..=u32::MAX)?,
+        }))
+    }
+}
+
+/// Shape of the wave a `ParamAnimation` follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lfo {
+    Sine,
+    /// Straight up and down, with sharp turns
+    Triangle,
+    /// The top and the bottom, half a period each
+    Square,
+    /// Rising from the bottom to the top, then dropping back
+    Saw,
+}
+
+impl Lfo {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sine" => Some(Self::Sine),
+            "triangle" => Some(Self::Triangle),
+            "square" => Some(Self::Square),
+            "saw" => Some(Self::Saw),
+            _ => None,
+        }
+    }
+
+    /// The wave at `phase`, a fraction of its period, between -1 and 1
+    pub fn at(self, phase: f32) -> f32 {
+        match self {
+            Lfo::Sine => (phase * std::f32::consts::TAU).sin(),
+            Lfo::Triangle => 1.0 - 4.0 * ((phase + 0.25).fract() - 0.5).abs(),
+            Lfo::Square if phase.fract() < 0.5 => 1.0,
+            Lfo::Square => -1.0,
+            Lfo::Saw => 2.0 * phase.fract() - 1.0,
+        }
+    }
+}
+
+/// A shader effect parameter swinging around a value, for breathing and
+/// pulsing effects without a script
+///
+/// Written as a table in place of the number, like
+/// `radius = { base = 3, lfo = "sine", period = "10s", amplitude = 2 }`:
+/// the parameter goes `amplitude` above and below `base` once a `period`,
+/// following the `lfo` wave, sine unless set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamAnimation {
+    pub base: f32,
+    pub lfo: Lfo,
+    /// Seconds
+    pub period: f32,
+    pub amplitude: f32,
+}
+
+impl ParamAnimation {
+    /// Read the animation parameter `key` of layer `layer` is set to, None
+    /// if `value` is not a table
+    pub fn from_value(
+        layer: &str,
+        key: &str,
+        value: &toml::Value,
+    ) -> Result<Option<Self>, ManifestError> {
+        let Some(table) = value.as_table() else {
+            return Ok(None);
+        };
+        let invalid = |message: String| {
+            ManifestError::ValidationError(format!("Layer {layer}: {key} {message}"))
+        };
+        if let Some(unknown) = table
+            .keys()
+            .find(|name| !["base", "lfo", "period", "amplitude"].contains(&name.as_str()))
+        {
+            return Err(invalid(format!(
+                "has an unknown field {unknown}, expected base, lfo, period and amplitude"
+            )));
+        }
+        let number = |name: &str| {
+            match table.get(name) {
+                Some(toml::Value::Float(value)) => Some(*value as f32),
+                Some(toml::Value::Integer(value)) => Some(*value as f32),
+                _ => None,
+            }
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| invalid(format!("needs a number as its {name}")))
+        };
+        let lfo = match table.get("lfo") {
+            None => Lfo::Sine,
+            Some(toml::Value::String(name)) => Lfo::parse(name).ok_or_else(|| {
+                invalid(format!(
+                    "has an unknown lfo '{name}', expected sine, triangle, square or saw"
+                ))
+            })?,
+            Some(_) => return Err(invalid("needs the name of a wave as its lfo".to_string())),
+        };
+        let period = match table.get("period") {
+            Some(toml::Value::String(text)) => parse_duration(text),
+            Some(_) => number("period").ok(),
+            None => None,
+        }
+        .filter(|period| *period > 0.0)
+        .ok_or_else(|| invalid("needs a period above 0, in seconds or like \"10s\"".to_string()))?;
+
+        Ok(Some(ParamAnimation {
+            base: number("base")?,
+            lfo,
+            period,
+            amplitude: number("amplitude")?,
+        }))
+    }
+
+    /// The value of the parameter `seconds` after the start
+    pub fn value_at(&self, seconds: f64) -> f32 {
+        // Taken in double precision, the phase stays exact for long sessions
+        let phase = (seconds / self.period as f64).rem_euclid(1.0) as f32;
+        self.base + self.amplitude * self.lfo.at(phase)
+    }
+}
+
+/// Seconds in a duration like "500ms", "10s", "2m" or "1h"
+fn parse_duration(text: &str) -> Option<f32> {
+    let text = text.trim();
+    let (number, scale) = [("ms", 0.001), ("s", 1.0), ("m", 60.0), ("h", 3600.0)]
+        .into_iter()
+        .find_map(|(unit, scale)| Some((text.strip_suffix(unit)?, scale)))?;
+    let seconds = number.trim().parse::<f32>().ok()? * scale;
+    seconds.is_finite().then_some(seconds)
+}
+
+/// Most colors or sizes an emitter's particles go through over their life
+pub const MAX_EMITTER_STOPS: usize = 8;
+
+/// Where an `Emitter` spawns its particles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitterShape {
+    /// All at `from`
+    Point,
+    /// Anywhere on the line from `from` to `to`
+    Line,
+    /// Anywhere in the rectangle between the corners `from` and `to`
+    Area,
+}
+
+impl EmitterShape {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "point" => Some(Self::Point),
+            "line" => Some(Self::Line),
+            "area" => Some(Self::Area),
+            _ => None,
+        }
+    }
+}
+
+/// Particles spawned and moved by the daemon, read from the `params` of a
+/// particle layer and used when it has no script
+///
+/// `shape` is where particles are spawned, at `position` for a point and
+/// between `from` and `to` for a line or an area, in fractions of the
+/// screen from its top left corner. `rate` particles are spawned a second,
+/// each living for `lifetime` seconds and moving at `velocity_x` and
+/// `velocity_y` screens a second, y pointing down. These take a number or
+/// a range like `[1, 2]` to pick from for each particle. `gravity` pulls
+/// them down in screens per second squared. `colors` and `sizes`, in
+/// fractions of the screen, are spread evenly over the life of a
+/// particle and blended between.
+///
+/// The particles drift with the `wind`, in screens a second, which swells
+/// and dies down by `gust` of itself. `attractors` pull them in, or push
+/// them away with a negative strength, and `edges` says what happens to
+/// those reaching the edges of the screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emitter {
+    pub shape: EmitterShape,
+    pub from: [f32; 2],
+    pub to: [f32; 2],
+    pub rate: f32,
+    pub lifetime: [f32; 2],
+    pub velocity_x: [f32; 2],
+    pub velocity_y: [f32; 2],
+    pub gravity: f32,
+    pub colors: Vec<String>,
+    pub sizes: Vec<f32>,
+    pub wind: [f32; 2],
+    /// From 0 for a steady wind to 1 for one that stops now and then
+    pub gust: f32,
+    pub attractors: Vec<Attractor>,
+    pub edges: Edges,
+    /// Speed a particle keeps bouncing off an edge, from 0 to 1
+    pub bounciness: f32,
+}
+
+/// A point of an `Emitter` pulling its particles in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attractor {
+    /// In fractions of the screen from its top left corner
+    pub position: [f32; 2],
+    /// Pull in screens per second squared at the point, fading to nothing
+    /// at `radius` away from it, negative to push particles away
+    pub strength: f32,
+    pub radius: f32,
+}
+
+/// What happens to particles of an `Emitter` reaching the screen's edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edges {
+    /// They fly on, off the screen
+    Pass,
+    /// They bounce back, losing speed by the emitter's `bounciness`
+    Bounce,
+    /// They come back in from the opposite edge
+    Wrap,
+    /// They die
+    Kill,
+}
+
+impl Edges {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "pass" => Some(Self::Pass),
+            "bounce" => Some(Self::Bounce),
+            "wrap" => Some(Self::Wrap),
+            "kill" => Some(Self::Kill),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            shape: EmitterShape::Point,
+            from: [0.5, 0.5],
+            to: [0.5, 0.5],
+            rate: 20.0,
+            lifetime: [2.0, 2.0],
+            velocity_x: [-0.1, 0.1],
+            velocity_y: [-0.1, 0.1],
+            gravity: 0.0,
+            colors: vec!["#ffffff".to_string(), "#ffffff00".to_string()],
+            sizes: vec![0.01],
+            wind: [0.0, 0.0],
+            gust: 0.0,
+            attractors: Vec::new(),
+            edges: Edges::Pass,
+            bounciness: 0.5,
+        }
+    }
+}
+
+impl Emitter {
+    /// Read the emitter from the `params` of layer `layer`
+    pub fn from_params(
+        layer: &str,
+        params: &HashMap<String, toml::Value>,
+    ) -> Result<Self, ManifestError> {
+        fn number(value: &toml::Value) -> Option<f32> {
+            match value {
+                toml::Value::Float(value) => Some(*value as f32),
+                toml::Value::Integer(value) => Some(*value as f32),
+                _ => None,
+            }
+            .filter(|value| value.is_finite())
+        }
+        fn pair(value: &toml::Value) -> Option<[f32; 2]> {
+            match value {
+                toml::Value::Array(xy) if xy.len() == 2 => {
+                    number(&xy[0]).zip(number(&xy[1])).map(|(x, y)| [x, y])
+                }
+                _ => None,
+            }
+        }
+        let invalid =
+            |message: String| ManifestError::ValidationError(format!("Layer {layer}: {message}"));
+        let scalar = |key: &str, default: f32| match params.get(key) {
+            None => Ok(default),
+            Some(value) => number(value).ok_or_else(|| invalid(format!("{key} must be a number"))),
+        };
+        let range = |key: &str, default: [f32; 2]| {
+            let range = match params.get(key) {
+                None => return Ok(default),
+                Some(toml::Value::Array(ends)) if ends.len() == 2 => number(&ends[0])
+                    .zip(number(&ends[1]))
+                    .map(|(min, max)| [min, max]),
+                Some(value) => number(value).map(|value| [value, value]),
+            };
+            range.filter(|[min, max]| min <= max).ok_or_else(|| {
+                invalid(format!(
+                    "{key} must be a number or a range like [1, 2], smallest first"
+                ))
+            })
+        };
+        let point = |key: &str, default: [f32; 2]| match params.get(key) {
+            None => Ok(default),
+            Some(value) => {
+                pair(value).ok_or_else(|| invalid(format!("{key} must be a pair like [0.5, 0.5]")))
+            }
+        };
+
+        let defaults = Emitter::default();
+        let shape = match params.get("shape") {
+            None => defaults.shape,
+            Some(toml::Value::String(name)) => EmitterShape::parse(name).ok_or_else(|| {
+                invalid(format!(
+                    "unknown shape '{name}', expected point, line or area"
+                ))
+            })?,
+            Some(_) => return Err(invalid("shape must be point, line or area".to_string())),
+        };
+        let (from, to) = match shape {
+            EmitterShape::Point => {
+                let position = point("position", defaults.from)?;
+                (position, position)
+            }
+            EmitterShape::Line | EmitterShape::Area => {
+                (point("from", [0.0, 0.0])?, point("to", [1.0, 0.0])?)
+            }
+        };
+        let colors = match params.get("colors") {
+            None => defaults.colors,
+            Some(toml::Value::String(color)) => vec![color.clone()],
+            Some(toml::Value::Array(colors)) => colors
+                .iter()
+                .map(|color| color.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("colors must be a list of colors".to_string()))?,
+            Some(_) => return Err(invalid("colors must be a list of colors".to_string())),
+        };
+        for color in &colors {
+            validate_color(layer, color)?;
+        }
+        let sizes = match params.get("sizes") {
+            None => Some(defaults.sizes),
+            Some(toml::Value::Array(sizes)) => sizes.iter().map(number).collect::<Option<_>>(),
+            Some(size) => number(size).map(|size| vec![size]),
+        }
+        .filter(|sizes: &Vec<f32>| sizes.iter().all(|size| *size >= 0.0))
+        .ok_or_else(|| invalid("sizes must be a list of sizes from 0".to_string()))?;
+        if [colors.len(), sizes.len()]
+            .iter()
+            .any(|stops| !(1..=MAX_EMITTER_STOPS).contains(stops))
+        {
+            return Err(invalid(format!(
+                "colors and sizes take between 1 and {MAX_EMITTER_STOPS} values"
+            )));
+        }
+
+        let attractors = match params.get("attractors") {
+            None => Some(Vec::new()),
+            Some(toml::Value::Array(attractors)) => attractors
+                .iter()
+                .map(|attractor| {
+                    let attractor = attractor.as_table()?;
+                    Some(Attractor {
+                        position: pair(attractor.get("position")?)?,
+                        strength: number(attractor.get("strength")?)?,
+                        radius: attractor.get("radius").map_or(Some(0.5), number)?,
+                    })
+                })
+                .collect(),
+            Some(_) => None,
+        }
+        .filter(|attractors: &Vec<Attractor>| {
+            attractors.iter().all(|attractor| attractor.radius > 0.0)
+        })
+        .ok_or_else(|| {
+            invalid(
+                "attractors must be a list like [{ position = [0.5, 0.5], strength = 1, \
+                 radius = 0.5 }]"
+                    .to_string(),
+            )
+        })?;
+        let edges = match params.get("edges") {
+            None => defaults.edges,
+            Some(toml::Value::String(name)) => Edges::parse(name).ok_or_else(|| {
+                invalid(format!(
+                    "unknown edges '{name}', expected pass, bounce, wrap or kill"
+                ))
+            })?,
+            Some(_) => {
+                return Err(invalid(
+                    "edges must be pass, bounce, wrap or kill".to_string(),
+                ));
+            }
+        };
+
+        let emitter = Emitter {
+            shape,
+            from,
+            to,
+            rate: scalar("rate", defaults.rate)?,
+            lifetime: range("lifetime", defaults.lifetime)?,
+            velocity_x: range("velocity_x", defaults.velocity_x)?,
+            velocity_y: range("velocity_y", defaults.velocity_y)?,
+            gravity: scalar("gravity", defaults.gravity)?,
+            colors,
+            sizes,
+            wind: point("wind", defaults.wind)?,
+            gust: scalar("gust", defaults.gust)?,
+            attractors,
+            edges,
+            bounciness: scalar("bounciness", defaults.bounciness)?,
+        };
+        if emitter.rate < 0.0 {
+            return Err(invalid("rate must not be negative".to_string()));
+        }
+        if emitter.lifetime[0] <= 0.0 {
+            return Err(invalid("lifetime must be more than 0 seconds".to_string()));
+        }
+        if !(0.0..=1.0).contains(&emitter.gust) || !(0.0..=1.0).contains(&emitter.bounciness) {
+            return Err(invalid(
+                "gust and bounciness must be between 0 and 1".to_string(),
+            ));
+        }
+        Ok(emitter)
+    }
+
+    /// The two of `stops` values spread over a particle's life that are
+    /// around `age`, from 0 at its birth to 1, and how far it is from the
+    /// first to the second
+    pub fn stops_at(stops: usize, age: f32) -> (usize, usize, f32) {
+        if stops < 2 {
+            return (0, 0, 0.0);
+        }
+        let position = age.clamp(0.0, 1.0) * (stops - 1) as f32;
+        let first = (position as usize).min(stops - 2);
+        (first, first + 1, position - first as f32)
+    }
+
+    /// Size of a particle at `age`, see `stops_at`
+    pub fn size_at(&self, age: f32) -> f32 {
+        let (first, second, blend) = Self::stops_at(self.sizes.len(), age);
+        self.sizes[first] + (self.sizes[second] - self.sizes[first]) * blend
+    }
+}
+
+/// The textures of a particle layer and how they are cut into frames, read
+/// from its `params`
+///
+/// The layer's `content` image comes first, then the images of `textures`,
+/// scaled to the size of the first. Each particle draws one of them, picked
+/// at random by the emitter or set by the script. Every texture is a sprite
+/// sheet of `frames = [columns, rows]` frames, played left to right and top
+/// to bottom at `fps` frames a second from the birth of each particle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParticleSprites {
+    /// Paths of the images after the content, relative to the wallpaper
+    /// until the layer is resolved
+    pub textures: Vec<PathBuf>,
+    pub columns: u32,
+    pub rows: u32,
+    pub fps: f32,
+}
+
+impl Default for ParticleSprites {
+    fn default() -> Self {
+        Self {
+            textures: Vec::new(),
+            columns: 1,
+            rows: 1,
+            fps: 0.0,
+        }
+    }
+}
+
+impl ParticleSprites {
+    /// Read the textures and frames from the `params` of layer `layer`
+    pub fn from_params(
+        layer: &str,
+        params: &HashMap<String, toml::Value>,
+    ) -> Result<Self, ManifestError> {
+        let invalid =
+            |message: &str| ManifestError::ValidationError(format!("Layer {layer}: {message}"));
+        let textures = match params.get("textures") {
+            None => Vec::new(),
+            Some(toml::Value::Array(textures)) => textures
+                .iter()
+                .map(|texture| texture.as_str().map(PathBuf::from))
+                .collect::<Option<_>>()
+                .ok_or_else(|| invalid("textures must be a list of images"))?,
+            Some(_) => return Err(invalid("textures must be a list of images")),
+        };
+        let (columns, rows) = match params.get("frames") {
+            None => (1, 1),
+            Some(toml::Value::Array(grid)) if grid.len() == 2 => {
+                let count = |value: &toml::Value| {
+                    value
+                        .as_integer()
+                        .and_then(|value| u32::try_from(value).ok())
+                        .filter(|value| (1..=64).contains(value))
+                };
+                count(&grid[0])
+                    .zip(count(&grid[1]))
+                    .ok_or_else(|| invalid("frames must be a grid like [4, 2], up to 64 a side"))?
+            }
+            Some(_) => {
+                return Err(invalid(
+                    "frames must be a grid like [4, 2], up to 64 a side",
+                ));
+            }
+        };
+        let fps = match params.get("fps") {
+            None => 0.0,
+            Some(toml::Value::Integer(fps)) => *fps as f32,
+            Some(toml::Value::Float(fps)) => *fps as f32,
+            Some(_) => return Err(invalid("fps must be a number")),
+        };
+        if !(fps >= 0.0 && fps.is_finite()) {
+            return Err(invalid("fps must not be negative"));
+        }
+        Ok(Self {
+            textures,
+            columns,
+            rows,
+            fps,
+        })
+    }
+
+    /// Frames in each texture
+    pub fn frames(&self) -> u32 {
+        self.columns * self.rows
+    }
+}
+
+/// Function to deserialize framerate from either a number or a string
+fn deserialize_framerate<'de, D>(deserializer: D) -> Result<i32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Error, Unexpected, Visitor};
+    use std::fmt;
+
+    struct RateVisitor;
+
+    impl<'de> Visitor<'de> for RateVisitor {
+        type Value = i32;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a number or string representing a framerate")
+        }
+
+        fn visit_i8<E>(self, value: i8) -> Result<i32, E>
+        where
+            E: Error,
+        {
             Ok(value as i32)
         }
 
@@ -402,6 +2027,10 @@ where
 }
 
 /// Default functions for serde defaults
+fn default_schema_version() -> i64 {
+    SCHEMA_VERSION
+}
+
 fn default_version() -> String {
     "1.0.0".to_string()
 }
@@ -419,6 +2048,10 @@ fn default_tps() -> i32 {
     -1 // Use compositor-driven update rate by default
 }
 
+fn default_background_alpha() -> f32 {
+    1.0
+}
+
 fn default_opacity() -> f32 {
     1.0
 }
@@ -427,70 +2060,603 @@ fn default_z_index() -> i32 {
     0 // Default z-index, backgrounds should use negative values
 }
 
-impl WallpaperManifest {
-    /// Load a manifest from a TOML file
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ManifestError> {
-        let content = fs::read_to_string(path)?;
-        let manifest: WallpaperManifest = toml::from_str(&content)?;
+fn default_font_size() -> f32 {
+    48.0
+}
 
-        // Basic validation
-        if manifest.name.is_empty() {
-            return Err(ManifestError::ValidationError(
-                "Wallpaper name cannot be empty".to_string(),
-            ));
-        }
+fn default_text_color() -> String {
+    "#ffffff".to_string()
+}
+
+fn default_text_position() -> [f32; 2] {
+    [0.5, 0.5]
+}
+
+fn default_gradient_angle() -> f32 {
+    180.0 // Top to bottom, like CSS
+}
+
+fn default_gradient_center() -> [f32; 2] {
+    [0.5, 0.5]
+}
+
+/// Check that a layer's color parses, so typos fail loudly instead of rendering black
+///
+/// Placeholders of the color scheme are always valid, they are filled in later.
+fn validate_color(layer: &str, color: &str) -> Result<(), ManifestError> {
+    Color::parse(&ColorScheme::default().resolve(color))
+        .map(|_| ())
+        .map_err(|e| ManifestError::ValidationError(format!("Layer {layer}: {e}")))
+}
+
+/// Prefix of the hashes in the `[assets]` table, the only algorithm so far
+pub const ASSET_HASH_PREFIX: &str = "sha256:";
 
-        Ok(manifest)
+/// Whether `path` is relative and stays inside the directory it is joined
+/// to, made only of plain names without `..`, `.` or a root
+pub fn is_inside(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Check that an `[assets]` entry names a file inside the wallpaper
+/// directory and holds a well-formed hash
+fn validate_asset_hash(path: &str, hash: &str) -> Result<(), ManifestError> {
+    if !is_inside(path) {
+        return Err(ManifestError::ValidationError(format!(
+            "Asset {path:?} must be a path inside the wallpaper directory"
+        )));
     }
 
-    /// Save the manifest to a TOML file
-    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ManifestError> {
-        let content =
-            toml::to_string(self).map_err(|e| ManifestError::ValidationError(e.to_string()))?;
-        fs::write(path, content)?;
-        Ok(())
+    let valid = hash
+        .strip_prefix(ASSET_HASH_PREFIX)
+        .is_some_and(|digest| digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()));
+    if !valid {
+        return Err(ManifestError::ValidationError(format!(
+            "Asset {path} has the hash {hash:?}, expected {ASSET_HASH_PREFIX} and 64 hex digits"
+        )));
     }
+    Ok(())
+}
 
-    /// Get all layers sorted by z-index (bottom to top)
-    pub fn get_sorted_layers(&self) -> Vec<&Layer> {
-        let mut layers: Vec<&Layer> = self.layers.iter().collect();
-        layers.sort_by_key(|layer| layer.z_index);
-        layers
+/// Parse the text of a manifest keeping its comments and formatting, for
+/// migrations
+fn parse_document(content: &str) -> Result<DocumentMut, ManifestError> {
+    content
+        .parse()
+        .map_err(|e: toml_edit::TomlError| ManifestError::ParseError(serde::de::Error::custom(e)))
+}
+
+/// Schema version of a parsed manifest, 1 if it does not declare one
+pub fn schema_version(document: &DocumentMut) -> Result<i64, ManifestError> {
+    let Some(item) = document.get("schema_version") else {
+        return Ok(1);
+    };
+    match item.as_integer() {
+        Some(version) if version > SCHEMA_VERSION => {
+            Err(ManifestError::UnsupportedVersion(version))
+        }
+        Some(version) if version >= 1 => Ok(version),
+        _ => Err(ManifestError::ValidationError(format!(
+            "schema_version must be a number from 1 to {SCHEMA_VERSION}, found {}",
+            item.to_string().trim()
+        ))),
     }
+}
 
-    /// Get a layer by name
-    pub fn get_layer_by_name(&self, name: &str) -> Option<&Layer> {
-        self.layers.iter().find(|layer| layer.name == name)
+/// Upgrade a parsed manifest to [`SCHEMA_VERSION`], returns the version it had
+///
+/// Only the values that changed are rewritten, comments and formatting are
+/// kept. A `schema_version` is added as the first key, so it is the first
+/// thing readers of the file see.
+pub fn migrate(document: &mut DocumentMut) -> Result<i64, ManifestError> {
+    let from = schema_version(document)?;
+    if from < 2 {
+        migrate_v1(document);
+    }
+    match document
+        .get_mut("schema_version")
+        .and_then(Item::as_value_mut)
+    {
+        Some(version) => replace(version, SCHEMA_VERSION),
+        None => {
+            document.insert("schema_version", toml_edit::value(SCHEMA_VERSION));
+            document.sort_values_by(|a, _, b, _| {
+                (a.get() != "schema_version").cmp(&(b.get() != "schema_version"))
+            });
+        }
     }
+    Ok(from)
+}
 
-    /// Get background layers (any with negative z-index or named "background")
-    pub fn get_background_layers(&self) -> Vec<&Layer> {
-        let mut backgrounds: Vec<&Layer> = self
-            .layers
-            .iter()
-            .filter(|layer| layer.z_index < 0 || layer.name.contains("background"))
-            .collect();
-        backgrounds.sort_by_key(|layer| layer.z_index);
-        backgrounds
+/// Migrate the text of a manifest, returns the version it had and the
+/// migrated text, or `None` if it already has the current version
+///
+/// The result is checked to load.
+pub fn migrate_text(content: &str) -> Result<Option<(i64, String)>, ManifestError> {
+    let mut document = parse_document(content)?;
+    if schema_version(&document)? == SCHEMA_VERSION {
+        return Ok(None);
     }
+    let from = migrate(&mut document)?;
+    let migrated = document.to_string();
+    WallpaperManifest::parse(&migrated)?;
+    Ok(Some((from, migrated)))
+}
 
-    /// Get the effective tickrate
-    /// Returns a positive value for actual tickrate, 0 for static content,
-    /// or negative value for compositor-driven timing
-    pub fn get_tickrate(&self) -> i32 {
-        self.tickrate
+/// Replace a value, keeping the comments and whitespace around it
+fn replace(value: &mut Value, with: impl Into<Value>) {
+    let decor = value.decor().clone();
+    *value = with.into();
+    *value.decor_mut() = decor;
+}
+
+/// Spell out what version 1 left to keywords and aliases
+fn migrate_v1(document: &mut DocumentMut) {
+    let keyword = |value: &Value| value.as_str() == Some("default");
+    if let Some(framerate) = document
+        .get_mut("framerate")
+        .and_then(Item::as_value_mut)
+        .filter(|value| keyword(value))
+    {
+        replace(framerate, 30);
+    }
+    if let Some(tickrate) = document
+        .get_mut("tickrate")
+        .and_then(Item::as_value_mut)
+        .filter(|value| keyword(value))
+    {
+        replace(tickrate, "compositor");
     }
 
-    /// Check if this wallpaper is animated
-    pub fn is_animated(&self) -> bool {
-        // Consider animated if either framerate or tickrate is non-zero (i.e., not static)
-        (self.framerate != 0 || self.tickrate != 0)
-            && self.layers.iter().any(|layer| {
-                matches!(
-                    layer.effect_type,
-                    Some(EffectType::Particles) | Some(EffectType::Shader(_))
-                )
-            })
+    // Layers are usually `[[layers]]` tables but may be an inline array
+    let layers: Vec<&mut dyn TableLike> = match document.get_mut("layers") {
+        Some(Item::ArrayOfTables(layers)) => layers
+            .iter_mut()
+            .map(|layer| layer as &mut dyn TableLike)
+            .collect(),
+        Some(Item::Value(Value::Array(layers))) => layers
+            .iter_mut()
+            .filter_map(Value::as_inline_table_mut)
+            .map(|layer| layer as &mut dyn TableLike)
+            .collect(),
+        _ => Vec::new(),
+    };
+    for weather in layers.into_iter().filter_map(|layer| {
+        layer
+            .get_mut("when")
+            .and_then(Item::as_table_like_mut)
+            .and_then(|when| when.get_mut("weather"))
+            .and_then(Item::as_value_mut)
+    }) {
+        let kinds = match weather {
+            Value::Array(kinds) => kinds.iter_mut().collect(),
+            kind => vec![kind],
+        };
+        for kind in kinds {
+            let name = match kind.as_str() {
+                Some("sunny") => "clear",
+                Some("rainy") => "rain",
+                Some("snowy") => "snow",
+                _ => continue,
+            };
+            replace(kind, name);
+        }
+    }
+}
+
+impl WallpaperManifest {
+    /// Load a manifest from a TOML file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ManifestError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Load a manifest from a TOML file, along with warnings about the
+    /// fields it has that wlrs does not know, see
+    /// [`parse_with_warnings`](Self::parse_with_warnings)
+    pub fn from_file_with_warnings<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<(Self, Vec<String>), ManifestError> {
+        Self::parse_with_warnings(&fs::read_to_string(path)?)
+    }
+
+    /// Load a manifest from TOML text, e.g. one that was downloaded,
+    /// migrating it if it has an older schema version
+    pub fn parse(content: &str) -> Result<Self, ManifestError> {
+        Self::parse_with_warnings(content).map(|(manifest, _)| manifest)
+    }
+
+    /// Load a manifest like [`parse`](Self::parse), along with a warning
+    /// for every field wlrs does not know, like `layers.0.opactiy`
+    ///
+    /// Such fields are ignored, so newer manifests still load, but they are
+    /// mostly typos that silently leave a setting at its default.
+    pub fn parse_with_warnings(content: &str) -> Result<(Self, Vec<String>), ManifestError> {
+        let mut unknown = Vec::new();
+        let mut track = |path: serde_ignored::Path| unknown.push(path.to_string());
+
+        let mut document = parse_document(content)?;
+        // Deserialized from text, for errors that point at a line
+        let mut manifest: WallpaperManifest = if schema_version(&document)? == SCHEMA_VERSION {
+            serde_ignored::deserialize(toml::Deserializer::new(content), &mut track)?
+        } else {
+            migrate(&mut document)?;
+            let migrated = document.to_string();
+            serde_ignored::deserialize(toml::Deserializer::new(&migrated), &mut track)?
+        };
+        manifest.validate()?;
+        manifest.fill_param_defaults();
+
+        let warnings = unknown
+            .into_iter()
+            .map(|field| format!("Unknown field {field} is ignored"))
+            .collect();
+        Ok((manifest, warnings))
+    }
+
+    /// Check that the manifest asks for `permission`, which `layer` uses
+    fn validate_needs(&self, layer: &str, permission: Permission) -> Result<(), ManifestError> {
+        if self.needs.contains(&permission) {
+            return Ok(());
+        }
+        Err(ManifestError::ValidationError(format!(
+            "Layer {layer} needs the {} permission, add it to `needs`",
+            permission.as_str()
+        )))
+    }
+
+    /// Check values that parse as TOML but cannot be rendered
+    pub fn validate(&self) -> Result<(), ManifestError> {
+        if self.name.is_empty() {
+            return Err(ManifestError::ValidationError(
+                "Wallpaper name cannot be empty".to_string(),
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.background_alpha) {
+            return Err(ManifestError::ValidationError(format!(
+                "background_alpha must be between 0 and 1, found {}",
+                self.background_alpha
+            )));
+        }
+
+        if let Some(phase) = self.phase.filter(|phase| !phase.is_finite()) {
+            return Err(ManifestError::ValidationError(format!(
+                "phase must be a number of seconds, found {phase}"
+            )));
+        }
+
+        for (path, hash) in &self.assets {
+            validate_asset_hash(path, hash)?;
+        }
+
+        for layer in &self.layers {
+            match &layer.content {
+                LayerContent::Color(color) => validate_color(&layer.name, color)?,
+                LayerContent::Text(text) => validate_color(&layer.name, &text.color)?,
+                LayerContent::Gradient(gradient) => gradient.validate(&layer.name)?,
+                LayerContent::Capture(capture) => {
+                    capture.validate(&layer.name)?;
+                    if capture.command.is_some() {
+                        self.validate_needs(&layer.name, Permission::Process)?;
+                    }
+                }
+                LayerContent::Web(web) => {
+                    web.validate(&layer.name)?;
+                    self.validate_needs(&layer.name, Permission::Web)?;
+                }
+                _ => {}
+            }
+            match &layer.effect_type {
+                Some(EffectType::Shader(ShaderType::Lut)) => {
+                    ColorGrade::from_params(&layer.name, &layer.params)?;
+                }
+                Some(EffectType::Shader(_)) => {
+                    NoiseTexture::from_params(&layer.name, &layer.params)?;
+                    for (key, value) in &layer.params {
+                        ParamAnimation::from_value(&layer.name, key, value)?;
+                    }
+                }
+                Some(EffectType::Particles) => {
+                    Emitter::from_params(&layer.name, &layer.params)?;
+                    ParticleSprites::from_params(&layer.name, &layer.params)?;
+                }
+                _ => {}
+            }
+            if layer.input == EffectInput::Below
+                && !matches!(layer.effect_type, Some(EffectType::Shader(_)))
+            {
+                return Err(ManifestError::ValidationError(format!(
+                    "Layer {}: only shader effects can read the layers below",
+                    layer.name
+                )));
+            }
+            if let Some(variant) = layer
+                .when
+                .variant
+                .iter()
+                .find(|variant| self.variant(variant).is_none())
+            {
+                return Err(ManifestError::ValidationError(format!(
+                    "Layer {} is shown in the variant {variant}, which is not in [[variants]]",
+                    layer.name
+                )));
+            }
+        }
+
+        self.validate_variants()?;
+        self.validate_params()
+    }
+
+    /// Check that declared parameters make sense and that layers set them
+    /// to values they accept
+    fn validate_params(&self) -> Result<(), ManifestError> {
+        for (name, spec) in &self.params {
+            let invalid = |reason: String| {
+                ManifestError::ValidationError(format!("Parameter {name} {reason}"))
+            };
+            if name.is_empty() || name.contains('.') {
+                return Err(invalid("needs a name without dots".to_string()));
+            }
+            if let (Some(min), Some(max)) = (spec.min, spec.max) {
+                if min > max {
+                    return Err(invalid(format!(
+                        "has a min of {min} above its max of {max}"
+                    )));
+                }
+            }
+            if spec.kind == ParamType::Choice && spec.options.is_empty() {
+                return Err(invalid("is a choice without options".to_string()));
+            }
+            if let Some(layer) = spec
+                .layers
+                .iter()
+                .find(|layer| self.get_layer_by_name(layer).is_none())
+            {
+                return Err(invalid(format!(
+                    "is passed to layer {layer}, which does not exist"
+                )));
+            }
+            if let Some(default) = &spec.default {
+                spec.check(default)
+                    .map_err(|e| invalid(format!("has an invalid default: {e}")))?;
+            }
+            for layer in self.layers.iter().filter(|layer| spec.targets(name, layer)) {
+                if let Some(value) = layer.params.get(name) {
+                    spec.check(value)
+                        .map_err(|e| invalid(format!("of layer {}: {e}", layer.name)))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Give layers that do not set a declared parameter its default
+    fn fill_param_defaults(&mut self) {
+        for (name, spec) in &self.params {
+            let Some(default) = &spec.default else {
+                continue;
+            };
+            for layer in &mut self.layers {
+                if spec.layers.contains(&layer.name) {
+                    layer
+                        .params
+                        .entry(name.clone())
+                        .or_insert_with(|| default.clone());
+                }
+            }
+        }
+    }
+
+    /// Check that variants have distinct names and only name layers that exist
+    fn validate_variants(&self) -> Result<(), ManifestError> {
+        for (index, variant) in self.variants.iter().enumerate() {
+            if variant.name.trim().is_empty() {
+                return Err(ManifestError::ValidationError(
+                    "Variant names cannot be empty".to_string(),
+                ));
+            }
+            if self.variants[..index]
+                .iter()
+                .any(|other| other.name == variant.name)
+            {
+                return Err(ManifestError::ValidationError(format!(
+                    "There are several variants named {}",
+                    variant.name
+                )));
+            }
+            if let Some(layer) = variant
+                .hide
+                .iter()
+                .chain(variant.layers.keys())
+                .find(|layer| self.get_layer_by_name(layer).is_none())
+            {
+                return Err(ManifestError::ValidationError(format!(
+                    "Variant {} changes the layer {layer}, which does not exist",
+                    variant.name
+                )));
+            }
+            if let Some(opacity) = variant
+                .layers
+                .values()
+                .filter_map(|layer| layer.opacity)
+                .find(|opacity| !(0.0..=1.0).contains(opacity))
+            {
+                return Err(ManifestError::ValidationError(format!(
+                    "Variant {} sets an opacity of {opacity}, it must be between 0 and 1",
+                    variant.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The variant named `name`
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+
+    /// Apply the changes of the variant `name` to the layers, removing the
+    /// ones it hides
+    ///
+    /// Layers that only belong to some variants are kept, see
+    /// [`When::in_variant`].
+    pub fn apply_variant(&mut self, name: &str) -> Result<(), ManifestError> {
+        let variant = self.variant(name).cloned().ok_or_else(|| {
+            let names: Vec<&str> = self.variants.iter().map(|v| v.name.as_str()).collect();
+            ManifestError::ValidationError(if names.is_empty() {
+                format!("{} has no variants", self.name)
+            } else {
+                format!(
+                    "{} has no variant {name}, it has {}",
+                    self.name,
+                    names.join(", ")
+                )
+            })
+        })?;
+
+        for layer in &mut self.layers {
+            let Some(changes) = variant.layers.get(&layer.name) else {
+                continue;
+            };
+            if let Some(opacity) = changes.opacity {
+                layer.opacity = opacity;
+            }
+            layer.params.extend(changes.params.clone());
+        }
+        // Changed parameters may not make sense for the effect
+        self.validate()?;
+        self.layers
+            .retain(|layer| !variant.hide.contains(&layer.name));
+        Ok(())
+    }
+
+    /// Override layer parameters with settings of the user, keyed by
+    /// `layer.param`, with `layer.opacity` setting the opacity of the layer,
+    /// or by the name of a declared parameter to set it on all its layers
+    ///
+    /// Values of declared parameters are checked against their [`ParamSpec`].
+    pub fn apply_settings(
+        &mut self,
+        settings: &BTreeMap<String, toml::Value>,
+    ) -> Result<(), ManifestError> {
+        for (key, value) in settings {
+            if let Some(spec) = self.params.get(key) {
+                spec.check(value)
+                    .map_err(|e| ManifestError::ValidationError(format!("Setting {key}: {e}")))?;
+                for layer in &mut self.layers {
+                    if spec.targets(key, layer) {
+                        layer.params.insert(key.clone(), value.clone());
+                    }
+                }
+                continue;
+            }
+
+            let (layer_name, param) = split_setting_key(key)?;
+            let layer = self
+                .layers
+                .iter_mut()
+                .find(|layer| layer.name == layer_name)
+                .ok_or_else(|| {
+                    ManifestError::ValidationError(format!(
+                        "{} has no layer {layer_name} for setting {key}",
+                        self.name
+                    ))
+                })?;
+            if param == "opacity" {
+                layer.opacity = value
+                    .as_float()
+                    .or_else(|| value.as_integer().map(|opacity| opacity as f64))
+                    .map(|opacity| opacity as f32)
+                    .filter(|opacity| (0.0..=1.0).contains(opacity))
+                    .ok_or_else(|| {
+                        ManifestError::ValidationError(format!(
+                            "Setting {key} must be a number between 0 and 1"
+                        ))
+                    })?;
+            } else {
+                layer.params.insert(param.to_string(), value.clone());
+            }
+        }
+        // Changed parameters may not make sense for the effect
+        self.validate()
+    }
+
+    /// Save the manifest to a TOML file
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ManifestError> {
+        let content =
+            toml::to_string(self).map_err(|e| ManifestError::ValidationError(e.to_string()))?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Get all layers sorted by z-index (bottom to top)
+    pub fn get_sorted_layers(&self) -> Vec<&Layer> {
+        let mut layers: Vec<&Layer> = self.layers.iter().collect();
+        layers.sort_by_key(|layer| layer.z_index);
+        layers
+    }
+
+    /// Get a layer by name
+    pub fn get_layer_by_name(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    /// Get background layers (any with negative z-index or named "background")
+    pub fn get_background_layers(&self) -> Vec<&Layer> {
+        let mut backgrounds: Vec<&Layer> = self
+            .layers
+            .iter()
+            .filter(|layer| layer.z_index < 0 || layer.name.contains("background"))
+            .collect();
+        backgrounds.sort_by_key(|layer| layer.z_index);
+        backgrounds
+    }
+
+    /// Get the effective tickrate
+    /// Returns a positive value for actual tickrate, 0 for static content,
+    /// or negative value for compositor-driven timing
+    pub fn get_tickrate(&self) -> i32 {
+        self.tickrate
+    }
+
+    /// Whether the name, author, description or a tag contains `query`,
+    /// ignoring case
+    pub fn matches_query(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        [&self.name, &self.author, &self.description]
+            .into_iter()
+            .chain(&self.tags)
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+
+    /// Whether the wallpaper carries every one of `tags`, ignoring case
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter()
+            .all(|tag| self.tags.iter().any(|own| own.eq_ignore_ascii_case(tag)))
+    }
+
+    /// Check if this wallpaper is animated
+    pub fn is_animated(&self) -> bool {
+        // Consider animated if either framerate or tickrate is non-zero (i.e., not static)
+        (self.framerate != 0 || self.tickrate != 0)
+            && self.layers.iter().any(|layer| {
+                let has_effect = matches!(
+                    layer.effect_type,
+                    Some(EffectType::Particles) | Some(EffectType::Shader(_))
+                );
+                let rotating_gradient = matches!(
+                    &layer.content,
+                    LayerContent::Gradient(gradient) if gradient.speed != 0.0
+                );
+                let live = matches!(
+                    layer.content,
+                    LayerContent::Capture(_) | LayerContent::Web(_) | LayerContent::AlbumArt(_)
+                );
+                has_effect || rotating_gradient || live
+            })
     }
 }
 
@@ -502,13 +2668,23 @@ mod tests {
     fn test_tickrate_defaults() {
         // Test default framerate and tickrate
         let manifest = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: 30,
             tickrate: -1,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![],
         };
 
@@ -518,13 +2694,23 @@ mod tests {
 
         // Test with explicit positive tickrate
         let manifest_with_tickrate = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: 30,
             tickrate: 60,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![],
         };
 
@@ -532,13 +2718,23 @@ mod tests {
 
         // Test compositor-driven framerate with static animations
         let compositor_static = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: -1,
             tickrate: 0,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![],
         };
 
@@ -547,13 +2743,23 @@ mod tests {
 
         // Test compositor-driven for both
         let compositor_both = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: -1,
             tickrate: -1,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![],
         };
 
@@ -570,18 +2776,31 @@ mod tests {
             effect_type: Some(EffectType::Shader(ShaderType::Wave)),
             z_index: 0,
             opacity: 1.0,
+            sampling: Sampling::default(),
+            input: EffectInput::default(),
             params: HashMap::new(),
+            when: When::default(),
         };
 
         // Non-animated wallpaper (framerate=0, tickrate=None, has effect)
         let non_animated = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: 0,
             tickrate: 0,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![effect_layer.clone()],
         };
 
@@ -590,13 +2809,23 @@ mod tests {
 
         // Animated by framerate (framerate>0, tickrate=None, has effect)
         let animated_by_framerate = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: 30,
             tickrate: 0,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![effect_layer.clone()],
         };
 
@@ -605,13 +2834,23 @@ mod tests {
 
         // Animated by tickrate (framerate=0, tickrate>0, has effect)
         let animated_by_tickrate = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: 0,
             tickrate: 60,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![effect_layer.clone()],
         };
 
@@ -620,13 +2859,23 @@ mod tests {
 
         // Animated by compositor-driven framerate (framerate=-1, has effect)
         let animated_by_compositor = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: -1,
             tickrate: -1,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![effect_layer.clone()],
         };
 
@@ -635,20 +2884,33 @@ mod tests {
 
         // Non-animated because no effects (framerate>0, tickrate>0, no effects)
         let no_effects = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: 30,
             tickrate: 60,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![Layer {
                 name: "no_effect".to_string(),
                 content: LayerContent::Color("#000000".to_string()),
                 effect_type: None,
                 z_index: 0,
                 opacity: 1.0,
+                sampling: Sampling::default(),
+                input: EffectInput::default(),
                 params: HashMap::new(),
+                when: When::default(),
             }],
         };
 
@@ -657,20 +2919,33 @@ mod tests {
 
         // Non-animated despite compositor-driven framerate (-1) because no effects
         let compositor_no_effects = WallpaperManifest {
+            schema_version: SCHEMA_VERSION,
             name: "Test Wallpaper".to_string(),
             author: "Test Author".to_string(),
             version: "1.0.0".to_string(),
             description: "Test Description".to_string(),
+            preview: None,
+            tags: vec![],
             framerate: -1,
             tickrate: -1,
             scale_mode: ScaleMode::Fill,
+            background_alpha: 1.0,
+            idle_timeout: None,
+            phase: None,
+            assets: BTreeMap::new(),
+            params: BTreeMap::new(),
+            needs: Vec::new(),
+            variants: Vec::new(),
             layers: vec![Layer {
                 name: "no_effect".to_string(),
                 content: LayerContent::Color("#000000".to_string()),
                 effect_type: None,
                 z_index: 0,
                 opacity: 1.0,
+                sampling: Sampling::default(),
+                input: EffectInput::default(),
                 params: HashMap::new(),
+                when: When::default(),
             }],
         };
 
@@ -717,4 +2992,1255 @@ mod tests {
         assert_eq!(manifest.framerate, 60); // 60 -> 60
         assert_eq!(manifest.tickrate, -1); // "compositor" -> -1
     }
+
+    #[test]
+    fn test_layer_sampling() {
+        let toml_str = r#"
+            name = "Sampling Test"
+
+            [[layers]]
+            name = "pixel-art"
+            content = "assets/sprite.png"
+            sampling = "nearest"
+
+            [[layers]]
+            name = "photo"
+            content = "assets/photo.jpg"
+        "#;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        assert_eq!(manifest.layers[0].sampling, Sampling::Nearest);
+        assert!(!manifest.layers[0].sampling.uses_mipmaps());
+        assert_eq!(manifest.layers[1].sampling, Sampling::Trilinear);
+        assert!(manifest.layers[1].sampling.uses_mipmaps());
+    }
+
+    #[test]
+    fn test_effect_input() {
+        let toml_str = r#"
+            name = "Frosted Glass"
+
+            [[layers]]
+            name = "photo"
+            content = "assets/photo.jpg"
+
+            [[layers]]
+            name = "frost"
+            effect_type = { shader = "gaussian" }
+            input = "below"
+            z_index = 1
+        "#;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        assert_eq!(manifest.layers[0].input, EffectInput::Image);
+        assert_eq!(manifest.layers[1].input, EffectInput::Below);
+        assert!(manifest.validate().is_ok());
+
+        let plain: WallpaperManifest = toml::from_str(
+            "name = \"Invalid\"\n[[layers]]\nname = \"plain\"\ncontent = \"red\"\ninput = \"below\"",
+        )
+        .unwrap();
+        assert!(matches!(
+            plain.validate(),
+            Err(ManifestError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_procedural_shaders() {
+        let toml_str = r#"
+            name = "Winter Night"
+
+            [[layers]]
+            name = "stars"
+            effect_type = { shader = "starfield" }
+
+            [[layers]]
+            name = "snow"
+            effect_type = { shader = "snow" }
+            params = { density = 0.6, wind = -0.3 }
+
+            [[layers]]
+            name = "ripples"
+            content = "assets/lake.png"
+            effect_type = { shader = "ripple" }
+        "#;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        let shaders: Vec<_> = manifest
+            .layers
+            .iter()
+            .map(|layer| match &layer.effect_type {
+                Some(EffectType::Shader(shader)) => shader.clone(),
+                other => panic!("unexpected effect {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            shaders,
+            [ShaderType::Starfield, ShaderType::Snow, ShaderType::Ripple]
+        );
+        assert!(shaders[0].is_procedural());
+        assert!(!shaders[2].is_procedural());
+        assert!(manifest.validate().is_ok());
+    }
+
+    #[test]
+    fn test_color_grade() {
+        let params = |pairs: &[(&str, toml::Value)]| -> HashMap<String, toml::Value> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect()
+        };
+
+        let grade = ColorGrade::from_params(
+            "grade",
+            &params(&[
+                ("lut", "warm".into()),
+                ("night_lut", "luts/Night.CUBE".into()),
+                ("sunset", 20.into()),
+                ("transition", 2.0.into()),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(grade.lut, LutSource::Preset("warm".to_string()));
+        assert_eq!(
+            grade.files().collect::<Vec<_>>(),
+            [Path::new("luts/Night.CUBE")]
+        );
+        assert_eq!(grade.night_amount(12.0), 0.0);
+        assert_eq!(grade.night_amount(2.0), 1.0);
+        assert_eq!(grade.night_amount(20.0), 0.5);
+        assert_eq!(grade.night_amount(7.5), 0.25);
+
+        // Without a night table the day table shows all day
+        let day_only =
+            ColorGrade::from_params("grade", &params(&[("lut", "sepia".into())])).unwrap();
+        assert_eq!(day_only.night_amount(23.0), 0.0);
+
+        for invalid in [
+            params(&[("lut", "vivid".into())]),
+            params(&[("lut", 3.into())]),
+            params(&[("sunrise", 25.into())]),
+            params(&[("sunrise", 20.into()), ("sunset", 8.into())]),
+        ] {
+            assert!(matches!(
+                ColorGrade::from_params("grade", &invalid),
+                Err(ManifestError::ValidationError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_noise_texture() {
+        let params = |pairs: &[(&str, toml::Value)]| -> HashMap<String, toml::Value> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect()
+        };
+
+        let noise = NoiseTexture::from_params(
+            "fire",
+            &params(&[("noise", "Simplex".into()), ("noise_size", 512.into())]),
+        )
+        .unwrap();
+        assert_eq!(
+            noise,
+            Some(NoiseTexture {
+                kind: NoiseKind::Simplex,
+                size: 512,
+                seed: 0,
+            })
+        );
+        assert_eq!(
+            NoiseTexture::from_params("fire", &params(&[("speed", 2.into())])).unwrap(),
+            None
+        );
+
+        for invalid in [
+            params(&[("noise", "fractal".into())]),
+            params(&[("noise", 1.into())]),
+            params(&[("noise", "perlin".into()), ("noise_size", 8192.into())]),
+            params(&[("noise", "worley".into()), ("noise_seed", (-1).into())]),
+        ] {
+            assert!(matches!(
+                NoiseTexture::from_params("fire", &invalid),
+                Err(ManifestError::ValidationError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_emitter() {
+        let params = |pairs: &[(&str, toml::Value)]| -> HashMap<String, toml::Value> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect()
+        };
+
+        let snow = Emitter::from_params(
+            "snow",
+            &params(&[
+                ("shape", "line".into()),
+                ("from", toml::Value::Array(vec![0.into(), (-0.05).into()])),
+                ("to", toml::Value::Array(vec![1.into(), (-0.05).into()])),
+                ("rate", 40.into()),
+                ("lifetime", toml::Value::Array(vec![8.into(), 12.into()])),
+                ("velocity_y", 0.1.into()),
+                ("colors", "white".into()),
+                ("sizes", toml::Value::Array(vec![0.01.into(), 0.02.into()])),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(snow.shape, EmitterShape::Line);
+        assert_eq!(snow.to, [1.0, -0.05]);
+        assert_eq!(snow.lifetime, [8.0, 12.0]);
+        assert_eq!(snow.velocity_y, [0.1, 0.1]);
+        assert_eq!(snow.velocity_x, Emitter::default().velocity_x);
+        assert_eq!(snow.colors, ["white"]);
+        assert_eq!(snow.size_at(0.5), 0.015);
+        assert_eq!(
+            Emitter::from_params("fountain", &params(&[])).unwrap(),
+            Emitter::default()
+        );
+
+        let mut vortex = toml::Table::new();
+        vortex.insert(
+            "position".to_string(),
+            toml::Value::Array(vec![0.5.into(), 0.5.into()]),
+        );
+        vortex.insert("strength".to_string(), (-2).into());
+        let leaves = Emitter::from_params(
+            "leaves",
+            &params(&[
+                ("wind", toml::Value::Array(vec![0.2.into(), 0.into()])),
+                ("gust", 0.5.into()),
+                ("attractors", toml::Value::Array(vec![vortex.into()])),
+                ("edges", "bounce".into()),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(leaves.wind, [0.2, 0.0]);
+        assert_eq!(leaves.edges, Edges::Bounce);
+        assert_eq!(
+            leaves.attractors,
+            [Attractor {
+                position: [0.5, 0.5],
+                strength: -2.0,
+                radius: 0.5
+            }]
+        );
+
+        assert_eq!(Emitter::stops_at(1, 0.7), (0, 0, 0.0));
+        assert_eq!(Emitter::stops_at(3, 0.75), (1, 2, 0.5));
+        assert_eq!(Emitter::stops_at(3, 1.0), (1, 2, 1.0));
+
+        for invalid in [
+            params(&[("shape", "circle".into())]),
+            params(&[("rate", (-1).into())]),
+            params(&[("lifetime", toml::Value::Array(vec![3.into(), 1.into()]))]),
+            params(&[("lifetime", 0.into())]),
+            params(&[("position", 0.5.into())]),
+            params(&[(
+                "colors",
+                toml::Value::Array(vec!["#fff".into(), "blurple".into()]),
+            )]),
+            params(&[("sizes", toml::Value::Array(Vec::new()))]),
+            params(&[("wind", 0.2.into())]),
+            params(&[("gust", 2.into())]),
+            params(&[("edges", "stick".into())]),
+            params(&[("attractors", toml::Value::Array(vec![0.5.into()]))]),
+        ] {
+            assert!(matches!(
+                Emitter::from_params("fountain", &invalid),
+                Err(ManifestError::ValidationError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_particle_sprites() {
+        let params = |pairs: &[(&str, toml::Value)]| -> HashMap<String, toml::Value> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect()
+        };
+
+        let flames = ParticleSprites::from_params(
+            "flames",
+            &params(&[
+                ("textures", toml::Value::Array(vec!["smoke.png".into()])),
+                ("frames", toml::Value::Array(vec![4.into(), 2.into()])),
+                ("fps", 12.into()),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(flames.textures, [PathBuf::from("smoke.png")]);
+        assert_eq!((flames.columns, flames.rows, flames.frames()), (4, 2, 8));
+        assert_eq!(flames.fps, 12.0);
+        assert_eq!(
+            ParticleSprites::from_params("flames", &params(&[])).unwrap(),
+            ParticleSprites::default()
+        );
+
+        for invalid in [
+            params(&[("textures", "smoke.png".into())]),
+            params(&[("frames", toml::Value::Array(vec![4.into()]))]),
+            params(&[("frames", toml::Value::Array(vec![0.into(), 2.into()]))]),
+            params(&[("fps", (-1.0).into())]),
+        ] {
+            assert!(matches!(
+                ParticleSprites::from_params("flames", &invalid),
+                Err(ManifestError::ValidationError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_lfo() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        let quarters = |lfo: Lfo| [0.0, 0.25, 0.5, 0.75, 1.0].map(|phase| lfo.at(phase));
+
+        for (lfo, expected) in [
+            (Lfo::Sine, [0.0, 1.0, 0.0, -1.0, 0.0]),
+            (Lfo::Triangle, [0.0, 1.0, 0.0, -1.0, 0.0]),
+            (Lfo::Square, [1.0, 1.0, -1.0, -1.0, 1.0]),
+            (Lfo::Saw, [-1.0, -0.5, 0.0, 0.5, -1.0]),
+        ] {
+            let values = quarters(lfo);
+            assert!(
+                values.iter().zip(expected).all(|(&a, b)| close(a, b)),
+                "{lfo:?}: {values:?}"
+            );
+        }
+        // Between the quarters the triangle is straight and the sine is not
+        assert!(close(Lfo::Triangle.at(0.125), 0.5));
+        assert!(Lfo::Sine.at(0.125) > 0.7);
+        // Phases past one period repeat it
+        assert!(close(Lfo::Saw.at(2.25), Lfo::Saw.at(0.25)));
+    }
+
+    #[test]
+    fn test_param_animation() {
+        let animation = |text: &str| {
+            let value: toml::Value = toml::from_str::<toml::Table>(&format!("radius = {text}"))
+                .unwrap()
+                .remove("radius")
+                .unwrap();
+            ParamAnimation::from_value("blur", "radius", &value)
+        };
+
+        let breathing = animation(r#"{ base = 3, period = "10s", amplitude = 2 }"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(breathing.lfo, Lfo::Sine);
+        assert_eq!(breathing.period, 10.0);
+        assert!((breathing.value_at(0.0) - 3.0).abs() < 1e-5);
+        assert!((breathing.value_at(2.5) - 5.0).abs() < 1e-5);
+        assert!((breathing.value_at(17.5) - 1.0).abs() < 1e-5);
+
+        let pulse = animation(r#"{ base = 1, lfo = "square", period = 0.5, amplitude = 1 }"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!((pulse.value_at(0.1), pulse.value_at(0.3)), (2.0, 0.0));
+        let slow = animation(r#"{ base = 0, lfo = "Triangle", period = "2m", amplitude = 1 }"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(slow.period, 120.0);
+        assert!((slow.value_at(30.0) - 1.0).abs() < 1e-5);
+        assert_eq!(animation("3.5").unwrap(), None);
+
+        for invalid in [
+            r#"{ period = "10s", amplitude = 2 }"#,
+            r#"{ base = 3, period = "soon", amplitude = 2 }"#,
+            r#"{ base = 3, period = 0, amplitude = 2 }"#,
+            r#"{ base = 3, lfo = "noise", period = "1s", amplitude = 2 }"#,
+            r#"{ base = 3, period = "1s", amplitude = 2, phase = 1 }"#,
+        ] {
+            assert!(matches!(
+                animation(invalid),
+                Err(ManifestError::ValidationError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_svg_content() {
+        let toml_str = r#"
+            name = "SVG Test"
+
+            [[layers]]
+            name = "vector"
+            content = "assets/Logo.SVG"
+
+            [[layers]]
+            name = "compressed"
+            content = "assets/waves.svgz"
+
+            [[layers]]
+            name = "raster"
+            content = "assets/svg-preview.png"
+        "#;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            manifest.layers[0].content,
+            LayerContent::Svg("assets/Logo.SVG".to_string())
+        );
+        assert_eq!(
+            manifest.layers[1].content,
+            LayerContent::Svg("assets/waves.svgz".to_string())
+        );
+        assert_eq!(
+            manifest.layers[2].content,
+            LayerContent::Image("assets/svg-preview.png".to_string())
+        );
+    }
+
+    #[test]
+    fn test_text_content() {
+        let toml_str = r##"
+            name = "Clock"
+
+            [[layers]]
+            name = "clock"
+            content = { text = "{time}", font = "Inter", size = 96.0, align = "left", position = [0.1, 0.9] }
+
+            [[layers]]
+            name = "hostname"
+            content = { text = "{hostname}" }
+
+            [[layers]]
+            name = "background"
+            content = "#102030"
+        "##;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        let LayerContent::Text(clock) = &manifest.layers[0].content else {
+            panic!("expected text content");
+        };
+        assert_eq!(clock.text, "{time}");
+        assert_eq!(clock.font.as_deref(), Some("Inter"));
+        assert_eq!(clock.size, 96.0);
+        assert_eq!(clock.align, TextAlign::Left);
+        assert_eq!(clock.position, [0.1, 0.9]);
+
+        let LayerContent::Text(hostname) = &manifest.layers[1].content else {
+            panic!("expected text content");
+        };
+        assert_eq!(hostname.font, None);
+        assert_eq!(hostname.size, 48.0);
+        assert_eq!(hostname.color, "#ffffff");
+        assert_eq!(hostname.align, TextAlign::Center);
+        assert_eq!(hostname.position, [0.5, 0.5]);
+
+        assert_eq!(
+            manifest.layers[2].content,
+            LayerContent::Color("#102030".to_string())
+        );
+    }
+
+    #[test]
+    fn test_gradient_content() {
+        let toml_str = r##"
+            name = "Gradient Test"
+
+            [[layers]]
+            name = "sky"
+            content = { gradient = "linear", stops = ["#000033", { color = "#336699", position = 0.7 }, "#ffcc99"] }
+
+            [[layers]]
+            name = "glow"
+            content = { gradient = "radial", stops = ["#ffffff", "#000000"], center = [0.5, 0.3], speed = 5.0 }
+        "##;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        let LayerContent::Gradient(sky) = &manifest.layers[0].content else {
+            panic!("expected gradient content");
+        };
+        assert_eq!(sky.gradient, GradientKind::Linear);
+        assert_eq!(sky.angle, 180.0);
+        assert_eq!(sky.center, [0.5, 0.5]);
+        assert_eq!(
+            sky.resolved_stops(),
+            vec![("#000033", 0.0), ("#336699", 0.7), ("#ffcc99", 1.0)]
+        );
+        assert!(sky.validate("sky").is_ok());
+
+        let LayerContent::Gradient(glow) = &manifest.layers[1].content else {
+            panic!("expected gradient content");
+        };
+        assert_eq!(glow.gradient, GradientKind::Radial);
+        assert_eq!(glow.center, [0.5, 0.3]);
+        assert_eq!(glow.speed, 5.0);
+    }
+
+    #[test]
+    fn test_capture_content() {
+        let toml_str = r##"
+            name = "Capture Test"
+            framerate = 30
+            needs = ["process"]
+
+            [[layers]]
+            name = "visualizer"
+            content = { capture = "HEADLESS-1", command = "cava", cursor = true }
+
+            [[layers]]
+            name = "screen"
+            content = { capture = "DP-2" }
+        "##;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        let LayerContent::Capture(visualizer) = &manifest.layers[0].content else {
+            panic!("expected capture content");
+        };
+        assert_eq!(visualizer.capture, "HEADLESS-1");
+        assert_eq!(visualizer.command.as_deref(), Some("cava"));
+        assert!(visualizer.cursor);
+
+        let LayerContent::Capture(screen) = &manifest.layers[1].content else {
+            panic!("expected capture content");
+        };
+        assert_eq!(screen.command, None);
+        assert!(!screen.cursor);
+        assert!(manifest.validate().is_ok());
+        assert!(manifest.is_animated());
+
+        // Commands need the process permission, plain captures do not
+        let unapproved: WallpaperManifest =
+            toml::from_str(&toml_str.replace("needs = [\"process\"]", "")).unwrap();
+        assert!(unapproved.validate().is_err());
+        let mut plain = unapproved.clone();
+        plain.layers.remove(0);
+        assert!(plain.validate().is_ok());
+
+        let empty = CaptureContent {
+            capture: " ".to_string(),
+            command: None,
+            cursor: false,
+        };
+        assert!(empty.validate("empty").is_err());
+    }
+
+    #[test]
+    fn test_web_content() {
+        let toml_str = r##"
+            name = "Web Test"
+            framerate = 30
+            needs = ["web"]
+
+            [[layers]]
+            name = "clock"
+            content = { web = "clock/index.html", transparent = true }
+
+            [[layers]]
+            name = "dashboard"
+            content = { web = "https://example.com/board", input = true }
+        "##;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        let LayerContent::Web(clock) = &manifest.layers[0].content else {
+            panic!("expected web content");
+        };
+        assert!(!clock.is_url());
+        assert!(clock.transparent);
+        assert!(!clock.input);
+
+        let LayerContent::Web(dashboard) = &manifest.layers[1].content else {
+            panic!("expected web content");
+        };
+        assert!(dashboard.is_url());
+        assert!(dashboard.input);
+        assert!(manifest.validate().is_ok());
+        assert!(manifest.is_animated());
+
+        let web = |web: &str| WebContent {
+            web: web.to_string(),
+            input: false,
+            transparent: false,
+        };
+        assert!(web("file:///srv/page.html").is_url());
+        assert!(web("about:blank").is_url());
+        assert!(!web("C:/page.html").is_url());
+        assert!(!web("pages/a:b.html").is_url());
+        assert!(web("").validate("empty").is_err());
+    }
+
+    #[test]
+    fn test_scheme_color_placeholders() {
+        let toml_str = r##"
+            name = "Themed"
+
+            [[layers]]
+            name = "background"
+            content = "{color0}"
+
+            [[layers]]
+            name = "glow"
+            content = { gradient = "radial", stops = ["{color4}", "transparent"] }
+
+            [[layers]]
+            name = "clock"
+            content = { text = "{time}", color = "{foreground}" }
+
+            [[layers]]
+            name = "logo"
+            content = "{logo}.png"
+        "##;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            manifest.layers[0].content,
+            LayerContent::Color("{color0}".to_string())
+        );
+        assert!(matches!(manifest.layers[3].content, LayerContent::Image(_)));
+        assert!(manifest.validate().is_ok());
+        assert!(validate_color("unknown", "{color16}").is_err());
+    }
+
+    #[test]
+    fn test_album_art_content() {
+        let toml_str = r##"
+            name = "Now Playing"
+            framerate = 30
+
+            [[layers]]
+            name = "cover"
+            content = { album_art = "cover", fallback = "vinyl.png" }
+
+            [[layers]]
+            name = "sleeve"
+            content = { album_art = "contain" }
+        "##;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        let LayerContent::AlbumArt(cover) = &manifest.layers[0].content else {
+            panic!("expected album art content");
+        };
+        assert_eq!(cover.album_art, ArtFit::Cover);
+        assert_eq!(cover.fallback.as_deref(), Some("vinyl.png"));
+
+        let LayerContent::AlbumArt(sleeve) = &manifest.layers[1].content else {
+            panic!("expected album art content");
+        };
+        assert_eq!(sleeve.album_art, ArtFit::Contain);
+        assert!(sleeve.fallback.is_none());
+        assert!(manifest.is_animated());
+
+        assert!(
+            toml::from_str::<WallpaperManifest>(
+                r#"
+            name = "Bad"
+            [[layers]]
+            name = "cover"
+            content = { album_art = "stretch" }
+            "#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_weather_conditions() {
+        let toml_str = r##"
+            name = "Weather Test"
+
+            [[layers]]
+            name = "sky"
+            content = "#87ceeb"
+
+            [[layers]]
+            name = "rain"
+            effect_type = { shader = "rain" }
+            when.weather = "rainy"
+
+            [[layers]]
+            name = "clouds"
+            content = "clouds.png"
+            when = { weather = ["cloudy", "rain", "storm"] }
+        "##;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        assert!(manifest.layers[0].when.is_always());
+        assert_eq!(manifest.layers[1].when.weather, vec![Weather::Rain]);
+        assert_eq!(manifest.layers[2].when.weather.len(), 3);
+
+        let shown = |weather| {
+            let environment = Environment {
+                weather,
+                ..Environment::default()
+            };
+            manifest
+                .layers
+                .iter()
+                .filter(|layer| layer.when.holds(&environment))
+                .map(|layer| layer.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shown(Weather::Clear), ["sky"]);
+        assert_eq!(shown(Weather::Rain), ["sky", "rain", "clouds"]);
+        assert_eq!(shown(Weather::Storm), ["sky", "clouds"]);
+
+        let unknown = r#"
+            name = "x"
+            [[layers]]
+            name = "a"
+            when.weather = "hail"
+        "#;
+        assert!(toml::from_str::<WallpaperManifest>(unknown).is_err());
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let v1 = r#"
+            name = "Old"
+            framerate = "default" # as fast as the old default
+            tickrate = "default"
+
+            [[layers]]
+            name = "snow"
+            effect_type = { shader = "snow" }
+            when = { weather = ["snowy", "fog"] }
+        "#;
+        let mut document = parse_document(v1).unwrap();
+        assert_eq!(migrate(&mut document).unwrap(), 1);
+        assert_eq!(
+            document["schema_version"].as_integer(),
+            Some(SCHEMA_VERSION)
+        );
+        assert_eq!(document["framerate"].as_integer(), Some(30));
+        assert_eq!(document["tickrate"].as_str(), Some("compositor"));
+        assert_eq!(
+            document["layers"][0]["when"]["weather"][0].as_str(),
+            Some("snow")
+        );
+
+        let (from, text) = migrate_text(v1).unwrap().unwrap();
+        assert_eq!(from, 1);
+        assert!(text.starts_with("schema_version = 2\n"));
+        assert!(text.contains("framerate = 30 # as fast as the old default\n"));
+        assert!(text.contains("when = { weather = [\"snow\", \"fog\"] }"));
+        assert!(migrate_text(&text).unwrap().is_none());
+
+        let inline = r#"
+            schema_version = 1
+            name = "Inline"
+            layers = [{ name = "rain", when.weather = "rainy" }]
+        "#;
+        let (_, text) = migrate_text(inline).unwrap().unwrap();
+        assert!(text.contains("schema_version = 2\n"));
+        assert!(text.contains("when.weather = \"rain\""));
+
+        let manifest = WallpaperManifest::parse(v1).unwrap();
+        assert_eq!(manifest.schema_version, SCHEMA_VERSION);
+        assert_eq!(manifest.framerate, 30);
+        assert_eq!(manifest.tickrate, -1);
+        assert_eq!(
+            manifest.layers[0].when.weather,
+            vec![Weather::Snow, Weather::Fog]
+        );
+
+        let newer = format!("schema_version = {}\nname = \"New\"", SCHEMA_VERSION + 1);
+        assert!(matches!(
+            WallpaperManifest::parse(&newer),
+            Err(ManifestError::UnsupportedVersion(version)) if version == SCHEMA_VERSION + 1
+        ));
+        assert!(WallpaperManifest::parse("schema_version = 0\nname = \"Zero\"").is_err());
+        assert!(WallpaperManifest::parse("schema_version = \"2\"\nname = \"Text\"").is_err());
+    }
+
+    #[test]
+    fn test_variants() {
+        let toml_str = r#"
+            name = "Variants"
+
+            [[variants]]
+            name = "night"
+            hide = ["sun"]
+            layers.stars = { opacity = 0.5, params = { speed = 0.1 } }
+
+            [[variants]]
+            name = "minimal"
+            hide = ["stars"]
+
+            [[layers]]
+            name = "sun"
+            content = "sun.png"
+
+            [[layers]]
+            name = "stars"
+            effect_type = { shader = "starfield" }
+            params = { speed = 1.0, density = 0.5 }
+
+            [[layers]]
+            name = "moon"
+            content = "moon.png"
+            when.variant = "night"
+        "#;
+        let manifest = WallpaperManifest::parse(toml_str).unwrap();
+        let shown = |manifest: &WallpaperManifest, variant| {
+            manifest
+                .layers
+                .iter()
+                .filter(|layer| layer.when.in_variant(variant))
+                .map(|layer| layer.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shown(&manifest, None), ["sun", "stars"]);
+
+        let mut night = manifest.clone();
+        night.apply_variant("night").unwrap();
+        assert_eq!(shown(&night, Some("night")), ["stars", "moon"]);
+        let stars = night.get_layer_by_name("stars").unwrap();
+        assert_eq!(stars.opacity, 0.5);
+        assert_eq!(stars.params["speed"].as_float(), Some(0.1));
+        assert_eq!(stars.params["density"].as_float(), Some(0.5));
+
+        let mut minimal = manifest.clone();
+        minimal.apply_variant("minimal").unwrap();
+        assert_eq!(shown(&minimal, Some("minimal")), ["sun"]);
+        assert!(manifest.clone().apply_variant("day").is_err());
+
+        let missing_layer = toml_str.replace("hide = [\"stars\"]", "hide = [\"planets\"]");
+        assert!(WallpaperManifest::parse(&missing_layer).is_err());
+        let missing_variant =
+            toml_str.replace("when.variant = \"night\"", "when.variant = \"dusk\"");
+        assert!(WallpaperManifest::parse(&missing_variant).is_err());
+        let duplicate = toml_str.replace("name = \"minimal\"", "name = \"night\"");
+        assert!(WallpaperManifest::parse(&duplicate).is_err());
+    }
+
+    #[test]
+    fn test_settings() {
+        let toml_str = r##"
+            name = "Settings"
+
+            [[layers]]
+            name = "snow"
+            content = "particles"
+            params = { density = 0.5, color = "#ffffff" }
+        "##;
+        let manifest = WallpaperManifest::parse(toml_str).unwrap();
+
+        assert_eq!(parse_setting_value("0.8").as_float(), Some(0.8));
+        assert_eq!(parse_setting_value("true").as_bool(), Some(true));
+        assert_eq!(parse_setting_value("#ff0000").as_str(), Some("#ff0000"));
+        assert_eq!(parse_setting_value("\"a b\"").as_str(), Some("a b"));
+
+        let settings = BTreeMap::from([
+            ("snow.density".to_string(), parse_setting_value("0.8")),
+            ("snow.opacity".to_string(), parse_setting_value("0.25")),
+        ]);
+        let mut changed = manifest.clone();
+        changed.apply_settings(&settings).unwrap();
+        let snow = changed.get_layer_by_name("snow").unwrap();
+        assert_eq!(snow.params["density"].as_float(), Some(0.8));
+        assert_eq!(snow.params["color"].as_str(), Some("#ffffff"));
+        assert_eq!(snow.opacity, 0.25);
+
+        for (key, value) in [
+            ("rain.density", "0.8"),
+            ("density", "0.8"),
+            ("snow.opacity", "2"),
+            ("snow.opacity", "full"),
+        ] {
+            let settings = BTreeMap::from([(key.to_string(), parse_setting_value(value))]);
+            assert!(manifest.clone().apply_settings(&settings).is_err());
+        }
+    }
+
+    #[test]
+    fn test_param_schema() {
+        let toml_str = r##"
+            name = "Schema"
+
+            [params.radius]
+            type = "float"
+            label = "Blur radius"
+            min = 0
+            max = 20
+            step = 0.5
+            default = 3.5
+            layers = ["blur"]
+
+            [params.tint]
+            type = "color"
+
+            [params.style]
+            type = "choice"
+            options = ["soft", "sharp"]
+            default = "soft"
+            layers = ["blur"]
+
+            [[layers]]
+            name = "blur"
+            content = "#000000"
+
+            [[layers]]
+            name = "glow"
+            content = "#000000"
+            params = { tint = "#ff8800" }
+        "##;
+        let manifest = WallpaperManifest::parse(toml_str).unwrap();
+        let blur = manifest.get_layer_by_name("blur").unwrap();
+        assert_eq!(blur.params["radius"].as_float(), Some(3.5));
+        assert_eq!(blur.params["style"].as_str(), Some("soft"));
+        assert_eq!(manifest.params["radius"].step, Some(0.5));
+
+        let set = |key: &str, value: &str| {
+            let settings = BTreeMap::from([(key.to_string(), parse_setting_value(value))]);
+            let mut changed = manifest.clone();
+            changed.apply_settings(&settings).map(|_| changed)
+        };
+        let changed = set("radius", "10").unwrap();
+        assert_eq!(
+            changed.get_layer_by_name("blur").unwrap().params["radius"].as_integer(),
+            Some(10)
+        );
+        let changed = set("tint", "red").unwrap();
+        assert_eq!(
+            changed.get_layer_by_name("glow").unwrap().params["tint"].as_str(),
+            Some("red")
+        );
+        assert!(set("radius", "25").is_err());
+        assert!(set("radius", "wide").is_err());
+        assert!(set("blur.radius", "-1").is_err());
+        assert!(set("style", "round").is_err());
+        assert!(set("tint", "#zzz").is_err());
+
+        for invalid in [
+            toml_str.replace("max = 20", "max = -1"),
+            toml_str.replace("default = 3.5", "default = 30"),
+            toml_str.replace("layers = [\"blur\"]", "layers = [\"haze\"]"),
+            toml_str.replace("\"#ff8800\"", "\"nothing\""),
+            toml_str.replace("options = [\"soft\", \"sharp\"]", ""),
+            toml_str.replace("type = \"float\"", "type = \"vector\""),
+        ] {
+            assert!(WallpaperManifest::parse(&invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_screen_conditions() {
+        let toml_str = r#"
+            name = "Screens"
+
+            [[layers]]
+            name = "wide"
+            content = "wide.png"
+            when = { aspect = "21:9", min_width = 2560 }
+
+            [[layers]]
+            name = "tall"
+            content = "tall.png"
+            when.aspect = "portrait"
+
+            [[layers]]
+            name = "main"
+            content = "main.png"
+            when.output = ["DP-1", "eDP-1"]
+        "#;
+        let manifest = WallpaperManifest::parse(toml_str).unwrap();
+        assert!(
+            manifest
+                .layers
+                .iter()
+                .all(|layer| layer.when.depends_on_screen())
+        );
+
+        let shown = |output: &str, width, height| {
+            let environment = Environment {
+                screen: Some(Screen {
+                    output: output.to_string(),
+                    width,
+                    height,
+                }),
+                ..Environment::default()
+            };
+            manifest
+                .layers
+                .iter()
+                .filter(|layer| layer.when.holds(&environment))
+                .map(|layer| layer.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shown("DP-1", 3440, 1440), ["wide", "main"]);
+        assert_eq!(shown("HDMI-A-1", 2560, 1080), ["wide"]);
+        assert_eq!(shown("HDMI-A-1", 1920, 1080), Vec::<&str>::new());
+        assert_eq!(shown("DP-2", 1080, 1920), ["tall"]);
+
+        // Preloading loads everything
+        assert!(
+            manifest
+                .layers
+                .iter()
+                .all(|layer| layer.when.holds(&Environment::default()))
+        );
+
+        for aspect in ["16", "0:9", "wide"] {
+            let invalid =
+                format!("name = \"x\"\n[[layers]]\nname = \"a\"\nwhen.aspect = \"{aspect}\"");
+            assert!(WallpaperManifest::parse(&invalid).is_err(), "{aspect}");
+        }
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let toml_str = r#"
+            name = "Typos"
+            framerat = 60
+
+            [[layers]]
+            name = "sky"
+            content = "sky.png"
+            opactiy = 0.5
+        "#;
+        let (manifest, warnings) = WallpaperManifest::parse_with_warnings(toml_str).unwrap();
+        assert_eq!(manifest.layers[0].opacity, 1.0);
+        assert_eq!(
+            warnings,
+            [
+                "Unknown field framerat is ignored",
+                "Unknown field layers.0.opactiy is ignored"
+            ]
+        );
+
+        let (_, warnings) =
+            WallpaperManifest::parse_with_warnings("name = \"Clean\"\nframerate = 60").unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_gradient_stop_positions() {
+        let gradient = |stops: Vec<ColorStop>| GradientContent {
+            gradient: GradientKind::Linear,
+            stops,
+            angle: 180.0,
+            center: [0.5, 0.5],
+            speed: 0.0,
+        };
+        let color = |color: &str| ColorStop::Color(color.to_string());
+        let at = |color: &str, position: f32| ColorStop::Positioned {
+            color: color.to_string(),
+            position,
+        };
+
+        // Unpositioned stops are spread evenly
+        let even = gradient(vec![
+            color("a"),
+            color("b"),
+            color("c"),
+            color("d"),
+            color("e"),
+        ]);
+        assert_eq!(
+            even.resolved_stops(),
+            vec![("a", 0.0), ("b", 0.25), ("c", 0.5), ("d", 0.75), ("e", 1.0)]
+        );
+
+        // Runs are spread between their positioned neighbours
+        let mixed = gradient(vec![
+            color("a"),
+            at("b", 0.2),
+            color("c"),
+            color("d"),
+            at("e", 0.8),
+        ]);
+        let positions: Vec<f32> = mixed.resolved_stops().iter().map(|(_, p)| *p).collect();
+        assert_eq!(positions[..2], [0.0, 0.2]);
+        assert!((positions[2] - 0.4).abs() < 1e-6);
+        assert!((positions[3] - 0.6).abs() < 1e-6);
+        assert_eq!(positions[4], 0.8);
+
+        // Positions never go backwards
+        let backwards = gradient(vec![at("a", 0.6), at("b", 0.3), color("c")]);
+        assert_eq!(
+            backwards.resolved_stops(),
+            vec![("a", 0.6), ("b", 0.6), ("c", 1.0)]
+        );
+
+        assert!(gradient(vec![color("a")]).validate("single").is_err());
+        assert!(
+            gradient(vec![color("a"); MAX_GRADIENT_STOPS + 1])
+                .validate("many")
+                .is_err()
+        );
+        assert!(
+            gradient(vec![color("a"), at("b", 1.5)])
+                .validate("range")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_color_validation() {
+        let toml_str = r##"
+            name = "Colors"
+
+            [[layers]]
+            name = "hex"
+            content = "#1a2b3c80"
+
+            [[layers]]
+            name = "function"
+            content = "hsl(200, 50%, 40%)"
+
+            [[layers]]
+            name = "named"
+            content = "midnightblue"
+
+            [[layers]]
+            name = "image"
+            content = "assets/navy.png"
+        "##;
+
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        assert!(manifest.validate().is_ok());
+        assert_eq!(
+            manifest.layers[2].content,
+            LayerContent::Color("midnightblue".to_string())
+        );
+        assert_eq!(
+            manifest.layers[3].content,
+            LayerContent::Image("assets/navy.png".to_string())
+        );
+
+        for invalid in [
+            r##"content = "#12345""##,
+            r##"content = "rgba(255, 0)""##,
+            r##"content = { text = "hi", color = "#zzzzzz" }"##,
+            r##"content = { gradient = "linear", stops = ["#000000", "hsl(x, 1, 2)"] }"##,
+        ] {
+            let toml_str = format!("name = \"Invalid\"\n[[layers]]\nname = \"bad\"\n{invalid}");
+            let manifest: WallpaperManifest = toml::from_str(&toml_str).unwrap();
+            assert!(
+                matches!(manifest.validate(), Err(ManifestError::ValidationError(_))),
+                "{invalid} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_background_alpha() {
+        let opaque: WallpaperManifest = toml::from_str(r#"name = "Opaque""#).unwrap();
+        assert_eq!(opaque.background_alpha, 1.0);
+
+        let toml_str = r##"
+            name = "Frosted"
+            background_alpha = 0.4
+
+            [[layers]]
+            name = "tint"
+            content = "rgba(20, 30, 60, 0.5)"
+        "##;
+        let frosted: WallpaperManifest = toml::from_str(toml_str).unwrap();
+        assert_eq!(frosted.background_alpha, 0.4);
+        assert!(frosted.validate().is_ok());
+
+        let invalid: WallpaperManifest =
+            toml::from_str("name = \"Invalid\"\nbackground_alpha = 1.5").unwrap();
+        assert!(matches!(
+            invalid.validate(),
+            Err(ManifestError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_phase() {
+        let shared: WallpaperManifest = toml::from_str(r#"name = "Shared""#).unwrap();
+        assert_eq!(shared.phase, None);
+
+        let staggered: WallpaperManifest =
+            toml::from_str("name = \"Staggered\"\nphase = 0.5").unwrap();
+        assert_eq!(staggered.phase, Some(0.5));
+        assert!(staggered.validate().is_ok());
+
+        let invalid: WallpaperManifest = toml::from_str("name = \"Invalid\"\nphase = nan").unwrap();
+        assert!(matches!(
+            invalid.validate(),
+            Err(ManifestError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_needs() {
+        let manifest: WallpaperManifest =
+            toml::from_str("name = \"Ticker\"\nneeds = [\"network\", \"file-read\"]").unwrap();
+        assert_eq!(
+            manifest.needs,
+            vec![Permission::Network, Permission::FileRead]
+        );
+
+        let unknown = toml::from_str::<WallpaperManifest>("name = \"Shell\"\nneeds = [\"exec\"]");
+        assert!(unknown.is_err());
+
+        let page = r#"
+            name = "Clock"
+
+            [[layers]]
+            name = "clock"
+            content = { web = "clock.html" }
+        "#;
+        let manifest: WallpaperManifest = toml::from_str(page).unwrap();
+        assert!(matches!(
+            manifest.validate(),
+            Err(ManifestError::ValidationError(_))
+        ));
+        let manifest: WallpaperManifest =
+            toml::from_str(&format!("needs = [\"web\"]\n{page}")).unwrap();
+        assert!(manifest.validate().is_ok());
+    }
+
+    #[test]
+    fn test_search() {
+        let toml_str = r##"
+            name = "Misty Forest"
+            author = "Jane"
+            description = "Pines in the morning fog"
+            tags = ["Nature", "calm"]
+        "##;
+        let manifest: WallpaperManifest = toml::from_str(toml_str).unwrap();
+
+        assert!(manifest.matches_query("forest"));
+        assert!(manifest.matches_query("JANE"));
+        assert!(manifest.matches_query("fog"));
+        assert!(manifest.matches_query("nat"));
+        assert!(!manifest.matches_query("city"));
+
+        assert!(manifest.has_tags(&[]));
+        assert!(manifest.has_tags(&["nature".to_string()]));
+        assert!(manifest.has_tags(&["calm".to_string(), "NATURE".to_string()]));
+        assert!(!manifest.has_tags(&["nature".to_string(), "night".to_string()]));
+    }
+
+    #[test]
+    fn test_asset_hash_validation() {
+        let digest = "0123456789abcdef".repeat(4);
+        let toml_str =
+            format!("name = \"Hashed\"\n[assets]\n\"images/sky.png\" = \"sha256:{digest}\"");
+        let manifest: WallpaperManifest = toml::from_str(&toml_str).unwrap();
+        assert!(manifest.validate().is_ok());
+        assert_eq!(
+            manifest.assets.get("images/sky.png"),
+            Some(&format!("sha256:{digest}"))
+        );
+
+        for invalid in [
+            format!("\"../sky.png\" = \"sha256:{digest}\""),
+            format!("\"/etc/passwd\" = \"sha256:{digest}\""),
+            "\"sky.png\" = \"sha256:1234\"".to_string(),
+            format!("\"sky.png\" = \"md5:{digest}\""),
+        ] {
+            let toml_str = format!("name = \"Invalid\"\n[assets]\n{invalid}");
+            let manifest: WallpaperManifest = toml::from_str(&toml_str).unwrap();
+            assert!(
+                matches!(manifest.validate(), Err(ManifestError::ValidationError(_))),
+                "{invalid} should be rejected"
+            );
+        }
+    }
 }
@@ -0,0 +1,246 @@
+//! Wallpaper repositories, static JSON indexes of wallpapers to download
+//!
+//! A repository is a directory served over HTTPS with an `index.json`:
+//!
+//! ```json
+//! {
+//!   "wallpapers": [
+//!     {
+//!       "name": "aurora",
+//!       "version": "1.2.0",
+//!       "author": "someone",
+//!       "description": "Northern lights over a lake",
+//!       "tags": ["nature", "animated"],
+//!       "manifest": "aurora/manifest.toml",
+//!       "hash": "sha256:…"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! URLs are relative to the index unless absolute. The files of a wallpaper
+//! are the ones in the `[assets]` table of its manifest, downloaded relative
+//! to the manifest and checked against their hashes, so the hash of the
+//! manifest in the index vouches for every file.
+
+use thiserror::Error;
+
+use crate::manifest::ASSET_HASH_PREFIX;
+use serde_json::Value;
+
+/// File name of the index in a repository
+pub const INDEX_FILE: &str = "index.json";
+
+#[derive(Debug, Error)]
+pub enum RepositoryError {
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid repository index: {0}")]
+    Invalid(String),
+}
+
+/// A wallpaper offered by a repository
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepositoryEntry {
+    /// Name of the wallpaper, also the directory it is installed to
+    pub name: String,
+    pub version: String,
+    pub author: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    /// Absolute URL of the manifest
+    pub manifest: String,
+    /// Hash of the manifest, in the form of the `[assets]` table
+    pub hash: String,
+}
+
+impl RepositoryEntry {
+    /// Whether the name, author, description or a tag contains `query`,
+    /// ignoring case
+    pub fn matches_query(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        [&self.name, &self.author, &self.description]
+            .into_iter()
+            .chain(&self.tags)
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+
+    /// Whether the wallpaper carries every one of `tags`, ignoring case
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter()
+            .all(|tag| self.tags.iter().any(|own| own.eq_ignore_ascii_case(tag)))
+    }
+
+    /// URL of a file of the `[assets]` table, relative to the manifest
+    pub fn asset_url(&self, asset: &str) -> String {
+        resolve_url(&self.manifest, &encode_path(asset))
+    }
+}
+
+/// URL of the index of the repository at `source`, which names either the
+/// index itself or the directory it is in
+pub fn index_url(source: &str) -> String {
+    if source.ends_with(".json") {
+        source.to_string()
+    } else {
+        format!("{}/{INDEX_FILE}", source.trim_end_matches('/'))
+    }
+}
+
+/// Parse the index found at `index_url`, resolving the manifest URLs
+pub fn parse_index(text: &str, index_url: &str) -> Result<Vec<RepositoryEntry>, RepositoryError> {
+    let index: Value = serde_json::from_str(text)?;
+    let wallpapers = index
+        .get("wallpapers")
+        .and_then(Value::as_array)
+        .ok_or_else(|| RepositoryError::Invalid("no \"wallpapers\" list".to_string()))?;
+
+    wallpapers
+        .iter()
+        .enumerate()
+        .map(|(position, wallpaper)| {
+            let field = |key: &str| wallpaper.get(key).and_then(Value::as_str);
+            let required = |key: &str| {
+                field(key).map(str::to_string).ok_or_else(|| {
+                    RepositoryError::Invalid(format!("wallpaper {} has no \"{key}\"", position + 1))
+                })
+            };
+
+            let name = required("name")?;
+            if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+                return Err(RepositoryError::Invalid(format!(
+                    "{name:?} cannot be the name of a wallpaper directory"
+                )));
+            }
+            let hash = required("hash")?;
+            if !hash.starts_with(ASSET_HASH_PREFIX) {
+                return Err(RepositoryError::Invalid(format!(
+                    "the hash of {name} does not start with {ASSET_HASH_PREFIX}"
+                )));
+            }
+            let tags = wallpaper
+                .get("tags")
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect();
+
+            Ok(RepositoryEntry {
+                manifest: resolve_url(index_url, &required("manifest")?),
+                version: field("version").unwrap_or("1.0.0").to_string(),
+                author: field("author").unwrap_or_default().to_string(),
+                description: field("description").unwrap_or_default().to_string(),
+                tags,
+                hash,
+                name,
+            })
+        })
+        .collect()
+}
+
+/// Resolve `reference` against the URL of the file it appears in
+///
+/// Absolute URLs are kept, `/path` replaces the path of `base` and anything
+/// else replaces its last segment.
+pub fn resolve_url(base: &str, reference: &str) -> String {
+    if reference.contains("://") {
+        return reference.to_string();
+    }
+    let base = base.split(['?', '#']).next().unwrap_or(base);
+    let path_start = base
+        .find("://")
+        .and_then(|scheme| base[scheme + 3..].find('/').map(|i| scheme + 3 + i))
+        .unwrap_or(base.len());
+    if let Some(absolute) = reference.strip_prefix('/') {
+        return format!("{}/{absolute}", &base[..path_start]);
+    }
+    match base[path_start..].rfind('/') {
+        Some(slash) => format!("{}{reference}", &base[..path_start + slash + 1]),
+        None => format!("{base}/{reference}"),
+    }
+}
+
+/// Percent-encode a relative path for use in a URL, keeping its slashes
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_index() {
+        let hash = format!("{ASSET_HASH_PREFIX}{}", "0".repeat(64));
+        let text = format!(
+            r#"{{"wallpapers": [
+                {{"name": "aurora", "version": "1.2.0", "tags": ["Nature"],
+                  "manifest": "aurora/manifest.toml", "hash": "{hash}"}},
+                {{"name": "mirror", "manifest": "https://cdn.example.org/m.toml", "hash": "{hash}"}}
+            ]}}"#
+        );
+        let entries = parse_index(&text, "https://example.org/wlrs/index.json").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].manifest,
+            "https://example.org/wlrs/aurora/manifest.toml"
+        );
+        assert_eq!(entries[0].version, "1.2.0");
+        assert!(entries[0].has_tags(&["nature".to_string()]));
+        assert!(entries[0].matches_query("AUR"));
+        assert_eq!(entries[1].manifest, "https://cdn.example.org/m.toml");
+        assert_eq!(entries[1].version, "1.0.0");
+
+        let escaping = text.replace("\"aurora\",", "\"../aurora\",");
+        assert!(parse_index(&escaping, "https://example.org/index.json").is_err());
+        let unhashed = text.replace(&hash, "md5:abc");
+        assert!(parse_index(&unhashed, "https://example.org/index.json").is_err());
+        assert!(parse_index("{}", "https://example.org/index.json").is_err());
+    }
+
+    #[test]
+    fn test_urls() {
+        assert_eq!(
+            index_url("https://example.org/wlrs/"),
+            "https://example.org/wlrs/index.json"
+        );
+        assert_eq!(
+            index_url("https://example.org/list.json"),
+            "https://example.org/list.json"
+        );
+        assert_eq!(
+            resolve_url("https://example.org/a/index.json", "/b/m.toml"),
+            "https://example.org/b/m.toml"
+        );
+        assert_eq!(
+            resolve_url("https://example.org", "m.toml"),
+            "https://example.org/m.toml"
+        );
+
+        let entry = RepositoryEntry {
+            name: "aurora".to_string(),
+            version: String::new(),
+            author: String::new(),
+            description: String::new(),
+            tags: Vec::new(),
+            manifest: "https://example.org/aurora/manifest.toml?v=2".to_string(),
+            hash: String::new(),
+        };
+        assert_eq!(
+            entry.asset_url("assets/night sky.png"),
+            "https://example.org/aurora/assets/night%20sky.png"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 
 /// Trait for converting a type into a Request enum variant
@@ -43,6 +44,66 @@ pub mod type_pairs {
 
     pub type GetInstallDirectoryRequest = GetInstallDirectory;
     pub type GetInstallDirectoryResponse = InstallDirectory;
+
+    pub type GetCacheStatsRequest = GetCacheStats;
+    pub type GetCacheStatsResponse = CacheStats;
+
+    pub type ClearCacheRequest = ClearCache;
+    pub type ClearCacheResponse = CacheCleared;
+
+    pub type SetFavoriteRequest = SetFavorite;
+    pub type SetFavoriteResponse = FavoriteSet;
+
+    pub type GetWallpaperStatsRequest = GetWallpaperStats;
+    pub type GetWallpaperStatsResponse = WallpaperStats;
+
+    pub type SetRandomWallpaperRequest = SetRandomWallpaper;
+    pub type SetRandomWallpaperResponse = RandomWallpaperSet;
+
+    pub type SetLogLevelRequest = SetLogLevel;
+    pub type SetLogLevelResponse = LogLevelSet;
+
+    pub type GetStatsRequest = GetStats;
+    pub type GetStatsResponse = RenderStats;
+
+    pub type MirrorOutputRequest = MirrorOutput;
+    pub type MirrorOutputResponse = OutputMirrored;
+
+    pub type SetRenderScaleRequest = SetRenderScale;
+    pub type SetRenderScaleResponse = RenderScaleSet;
+
+    pub type SetDisplayAdjustmentRequest = SetDisplayAdjustment;
+    pub type SetDisplayAdjustmentResponse = DisplayAdjusted;
+
+    pub type SetOverviewModeRequest = SetOverviewMode;
+    pub type SetOverviewModeResponse = OverviewModeSet;
+
+    pub type SetColorSchemeRequest = SetColorScheme;
+    pub type SetColorSchemeResponse = ColorSchemeSet;
+
+    pub type ExtractColorsRequest = ExtractColors;
+    pub type ExtractColorsResponse = ColorsExtracted;
+
+    pub type CaptureFrameRequest = CaptureFrame;
+    pub type CaptureFrameResponse = FrameCaptured;
+
+    pub type SetMotionRequest = SetMotion;
+    pub type SetMotionResponse = MotionSet;
+
+    pub type SetWallpaperSettingRequest = SetWallpaperSetting;
+    pub type SetWallpaperSettingResponse = WallpaperSettingSet;
+
+    pub type GetWallpaperSettingsRequest = GetWallpaperSettings;
+    pub type GetWallpaperSettingsResponse = WallpaperSettings;
+
+    pub type DescribeWallpaperRequest = DescribeWallpaper;
+    pub type DescribeWallpaperResponse = WallpaperDescription;
+
+    pub type PlanWallpaperRequest = PlanWallpaper;
+    pub type PlanWallpaperResponse = WallpaperPlan;
+
+    pub type SetPermissionsRequest = SetPermissions;
+    pub type SetPermissionsResponse = PermissionsSet;
 }
 
 /// Macro to implement request-response conversion traits
@@ -94,17 +155,20 @@ pub struct Checkhealth;
 #[derive(Encode, Decode, Debug)]
 pub struct Health(pub bool);
 
-/// Request to load a wallpaper into cache by name
+/// Request to load a wallpaper into cache by name or path
 ///
-/// This request will load the wallpaper with the given name into memory cache
-/// but will not set it as the current wallpaper.
+/// This request will decode the wallpaper's images and build its render
+/// pipelines in the background, but will not set it as the current
+/// wallpaper. Setting it afterwards then takes no time.
 #[derive(Encode, Decode, Debug)]
 pub struct LoadWallpaper {
-    /// Name of the wallpaper to load
+    /// Name of the wallpaper to load, or the path of its directory
     pub path: String,
+    /// Refuse the wallpaper if its manifest has warnings, like unknown fields
+    pub strict: bool,
 }
 
-/// Response indicating if a wallpaper was successfully loaded into cache
+/// Response indicating if a wallpaper was found and queued to be loaded into cache
 #[derive(Encode, Decode, Debug)]
 pub struct WallpaperLoaded {
     /// Name of the loaded wallpaper
@@ -113,11 +177,26 @@ pub struct WallpaperLoaded {
     pub success: bool,
     /// Error message if loading failed
     pub error: Option<String>,
+    /// Problems with the manifest that did not keep it from loading
+    pub warnings: Vec<String>,
 }
 
 /// Request to list all available wallpapers
-#[derive(Encode, Decode, Debug)]
-pub struct ListWallpapers;
+///
+/// The filters are combined, only wallpapers matching all of them are listed.
+#[derive(Encode, Decode, Debug, Default)]
+pub struct ListWallpapers {
+    /// Include manifest metadata and a thumbnail path for each wallpaper
+    pub details: bool,
+    /// Only list wallpapers whose name, author, description or tags contain this text
+    pub filter: Option<String>,
+    /// Only list wallpapers that have every one of these tags
+    pub tags: Vec<String>,
+    /// Only list animated (`true`) or static (`false`) wallpapers
+    pub animated: Option<bool>,
+    /// Only list wallpapers marked as favorite
+    pub favorites: bool,
+}
 
 /// Response containing a list of all available wallpapers
 #[derive(Encode, Decode, Debug)]
@@ -133,6 +212,35 @@ pub struct WallpaperInfo {
     pub name: String,
     /// Path to the wallpaper directory
     pub path: String,
+    /// Path of the wallpaper of the same name that is used instead, this
+    /// one can only be set by its path or `dir/name`
+    pub shadowed_by: Option<String>,
+    /// Manifest metadata, only filled in when the request asked for details
+    pub details: Option<WallpaperDetails>,
+}
+
+/// Manifest metadata of a wallpaper, for rich listings
+#[derive(Encode, Decode, Debug)]
+pub struct WallpaperDetails {
+    /// Author named in the manifest
+    pub author: String,
+    /// Version of the wallpaper
+    pub version: String,
+    /// Description of the wallpaper
+    pub description: String,
+    /// Tags from the manifest
+    pub tags: Vec<String>,
+    /// Names of the variants the wallpaper can be set in
+    pub variants: Vec<String>,
+    /// Whether the wallpaper animates
+    pub animated: bool,
+    /// Path to the preview image named in the manifest, if any
+    pub preview: Option<String>,
+    /// Path to a small PNG rendered by the daemon, None until it has been
+    /// rendered in the background
+    pub thumbnail: Option<String>,
+    /// How the wallpaper has been used
+    pub usage: WallpaperUsage,
 }
 
 /// Request to set a wallpaper as the current active wallpaper
@@ -145,6 +253,8 @@ pub struct SetCurrentWallpaper {
     pub name: String,
     /// Optional monitor to set the wallpaper for, if not specified will set for all monitors
     pub monitor: Option<String>,
+    /// Variant of the wallpaper to show, its default look if not specified
+    pub variant: Option<String>,
 }
 
 /// Response indicating if a wallpaper was successfully set as current
@@ -169,6 +279,8 @@ pub struct StopServer;
 pub struct ServerStopping {
     /// Whether the shutdown was initiated successfully
     pub success: bool,
+    /// Why the daemon refused to stop
+    pub error: Option<String>,
 }
 
 /// Request to query active wallpapers on all monitors
@@ -190,10 +302,49 @@ pub struct ActiveWallpaperInfo {
     pub name: String,
     /// Output/monitor name the wallpaper is displayed on
     pub output_name: String,
+    /// Description of the output given by the compositor
+    pub description: Option<String>,
+    /// Manufacturer of the monitor
+    pub make: String,
+    /// Model of the monitor
+    pub model: String,
+    /// Position of the output in the compositor's logical space
+    pub x: i32,
+    /// Position of the output in the compositor's logical space
+    pub y: i32,
     /// Width of the wallpaper
     pub width: u32,
     /// Height of the wallpaper
     pub height: u32,
+    /// Output whose wallpaper this output mirrors, if any
+    pub mirror_of: Option<String>,
+    /// Resolution the wallpaper is rendered at relative to the output's
+    pub render_scale: f32,
+    /// Color adjustment applied to the wallpaper
+    pub adjustment: DisplayAdjustment,
+    /// Whether the wallpaper is blurred and dimmed for the compositor's overview
+    pub overview: bool,
+    /// Time available to draw a frame in microseconds
+    pub frame_budget_us: u64,
+    /// Cost of each layer of the wallpaper, in drawing order
+    pub layers: Vec<LayerTiming>,
+}
+
+/// Smoothed time a wallpaper layer takes to draw
+#[derive(Encode, Decode, Debug)]
+pub struct LayerTiming {
+    /// Name of the layer in the manifest
+    pub name: String,
+    /// Time spent updating the layer and recording its draw calls, in microseconds
+    pub cpu_us: u64,
+    /// Time the GPU spent drawing the layer in microseconds, None if the GPU
+    /// cannot measure it
+    pub gpu_us: Option<u64>,
+    /// Whether the layer takes more than half of the frame budget
+    pub over_budget: bool,
+    /// What keeps the layer from drawing as the wallpaper meant, like a
+    /// script that was stopped
+    pub warning: Option<String>,
 }
 
 /// Response containing a list of all active wallpapers
@@ -212,12 +363,562 @@ pub struct ActiveWallpaperList {
 pub struct InstallDirectory {
     /// Path to the installation directory
     pub path: String,
+    /// Directories wallpapers are looked up in, earlier ones win when two
+    /// have a wallpaper of the same name
+    pub search_paths: Vec<String>,
+    /// Repositories wallpapers can be fetched from, from the daemon's config
+    pub repositories: Vec<String>,
     /// Whether the query was successful
     pub success: bool,
     /// Error message if query failed
     pub error: Option<String>,
 }
 
+/// Request to get statistics about the daemon's decoded asset cache
+#[derive(Encode, Decode, Debug)]
+pub struct GetCacheStats;
+
+/// Response containing asset cache statistics
+#[derive(Encode, Decode, Debug)]
+pub struct CacheStats {
+    /// Number of decoded assets held in the cache
+    pub entries: u64,
+    /// Approximate GPU memory used by cached assets in bytes
+    pub bytes: u64,
+    /// Memory budget before least recently used assets are evicted, in bytes
+    pub budget: u64,
+    /// Lookups served from the cache
+    pub hits: u64,
+    /// Lookups that had to decode the asset
+    pub misses: u64,
+    /// Assets dropped to stay within the budget
+    pub evictions: u64,
+    /// Approximate GPU memory held by all wallpaper textures in bytes
+    pub gpu_bytes: u64,
+    /// GPU memory budget for wallpaper textures in bytes, if one is set
+    pub gpu_budget: Option<u64>,
+}
+
+/// Request to drop every asset from the daemon's cache
+///
+/// Wallpapers currently on screen keep their textures, only the cache's
+/// references are released.
+#[derive(Encode, Decode, Debug)]
+pub struct ClearCache;
+
+/// Response describing what was released from the asset cache
+#[derive(Encode, Decode, Debug)]
+pub struct CacheCleared {
+    /// Number of assets that were dropped
+    pub entries: u64,
+    /// Approximate memory released from the cache in bytes
+    pub bytes: u64,
+}
+
+/// Request to mark a wallpaper as favorite, or to unmark it
+#[derive(Encode, Decode, Debug)]
+pub struct SetFavorite {
+    /// Name of the wallpaper
+    pub name: String,
+    /// Whether the wallpaper should be a favorite
+    pub favorite: bool,
+}
+
+/// Response indicating if the favorite flag was changed
+#[derive(Encode, Decode, Debug)]
+pub struct FavoriteSet {
+    /// Name of the wallpaper
+    pub name: String,
+    /// Whether the flag was stored successfully
+    pub success: bool,
+    /// Error message if the wallpaper does not exist
+    pub error: Option<String>,
+}
+
+/// Request to get the usage the daemon recorded for wallpapers
+#[derive(Encode, Decode, Debug)]
+pub struct GetWallpaperStats {
+    /// Only return this wallpaper, all wallpapers with recorded usage otherwise
+    pub name: Option<String>,
+}
+
+/// Response containing recorded wallpaper usage
+#[derive(Encode, Decode, Debug)]
+pub struct WallpaperStats {
+    /// Usage per wallpaper name
+    pub wallpapers: Vec<(String, WallpaperUsage)>,
+}
+
+/// Usage the daemon records for a wallpaper across restarts
+///
+/// Also the format of the daemon's state file, hence the serde derives.
+#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct WallpaperUsage {
+    /// Whether the wallpaper is marked as favorite
+    pub favorite: bool,
+    /// Number of times the wallpaper was set
+    pub use_count: u64,
+    /// When the wallpaper was last set, in seconds since the Unix epoch
+    pub last_used: Option<u64>,
+}
+
+/// Request to set a randomly picked wallpaper
+///
+/// Favorites are more likely to be picked and recently set wallpapers are
+/// avoided while there are others to choose from.
+#[derive(Encode, Decode, Debug, Default)]
+pub struct SetRandomWallpaper {
+    /// Only pick wallpapers that have all of these tags
+    pub tags: Vec<String>,
+    /// Never pick these wallpapers
+    pub exclude: Vec<String>,
+    /// Never pick the wallpaper that was set last
+    pub exclude_current: bool,
+    /// Optional monitor name to set the wallpaper for (all monitors if None)
+    pub monitor: Option<String>,
+}
+
+/// Response indicating which wallpaper was picked and if it was set
+#[derive(Encode, Decode, Debug)]
+pub struct RandomWallpaperSet {
+    /// Name of the picked wallpaper, None if nothing matched
+    pub name: Option<String>,
+    /// Whether the wallpaper was set successfully
+    pub success: bool,
+    /// Error message if nothing matched or the wallpaper failed to load
+    pub error: Option<String>,
+}
+
+/// Request to change which log messages the daemon records
+#[derive(Encode, Decode, Debug)]
+pub struct SetLogLevel {
+    /// A level like `debug`, or per-module directives in the `RUST_LOG` syntax
+    pub filter: String,
+}
+
+/// Response indicating if the log filter was changed
+#[derive(Encode, Decode, Debug)]
+pub struct LogLevelSet {
+    /// Whether the filter is in effect
+    pub success: bool,
+    /// Error message if the filter could not be parsed
+    pub error: Option<String>,
+}
+
+/// Request to get render statistics of every output
+#[derive(Encode, Decode, Debug)]
+pub struct GetStats;
+
+/// Response containing render statistics of the daemon
+#[derive(Encode, Decode, Debug)]
+pub struct RenderStats {
+    /// Statistics per output
+    pub outputs: Vec<OutputStats>,
+    /// Approximate GPU memory used by cached textures in bytes
+    pub texture_bytes: u64,
+    /// Memory budget of the texture cache in bytes
+    pub texture_budget: u64,
+}
+
+/// Render statistics of a single output since its wallpaper was set
+#[derive(Encode, Decode, Debug)]
+pub struct OutputStats {
+    /// Output/monitor name
+    pub output_name: String,
+    /// Name of the wallpaper shown on the output, if any
+    pub wallpaper: Option<String>,
+    /// Frames presented during the last second
+    pub fps: u32,
+    /// Refresh rate of the output's current mode in mHz, 0 if unknown
+    pub refresh_rate: u32,
+    /// Average time to draw a recent frame in microseconds
+    pub frame_time_avg_us: u64,
+    /// Time 99% of the recent frames were drawn within, in microseconds
+    pub frame_time_p99_us: u64,
+    /// Frames presented
+    pub frames: u64,
+    /// Frames that were missed or could not be drawn
+    pub dropped_frames: u64,
+    /// Recent GPU validation errors, with the layer dropped for each if it
+    /// could be told
+    pub gpu_errors: Vec<String>,
+    /// Number of active models per kind, like `Texture` or `Effect`
+    pub models: Vec<(String, u32)>,
+}
+
+/// Request to show the wallpaper of one output on others, frame for frame
+///
+/// The targets keep mirroring the source until they are given a wallpaper
+/// of their own.
+#[derive(Encode, Decode, Debug)]
+pub struct MirrorOutput {
+    /// Output whose wallpaper is shown
+    pub source: String,
+    /// Outputs that show it
+    pub targets: Vec<String>,
+}
+
+/// Response indicating if the outputs mirror the source
+#[derive(Encode, Decode, Debug)]
+pub struct OutputMirrored {
+    /// Whether the targets now mirror the source
+    pub success: bool,
+    /// Error message if an output was not found
+    pub error: Option<String>,
+}
+
+/// Request to render outputs at a fraction or multiple of their resolution
+///
+/// The wallpaper is drawn into a texture of the scaled size and stretched
+/// onto the output, e.g. 0.5 makes heavy effects cheaper on a 4K screen.
+#[derive(Encode, Decode, Debug)]
+pub struct SetRenderScale {
+    /// Output/monitor name, every output if None
+    pub output: Option<String>,
+    /// Factor applied to the width and height, 1.0 renders at the output resolution
+    pub scale: f32,
+}
+
+/// Response indicating if the render scale was changed
+#[derive(Encode, Decode, Debug)]
+pub struct RenderScaleSet {
+    /// Whether the outputs render at the new scale
+    pub success: bool,
+    /// Error message if the output was not found or the scale is out of range
+    pub error: Option<String>,
+}
+
+/// Color adjustment applied to everything drawn on an output
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq)]
+pub struct DisplayAdjustment {
+    /// Multiplier of the color, 1.0 leaves it unchanged
+    pub brightness: f32,
+    /// Spread of the color around middle gray, 1.0 leaves it unchanged
+    pub contrast: f32,
+    /// Gamma of the color, above 1.0 brightens the midtones
+    pub gamma: f32,
+    /// White point in Kelvin, lower is warmer
+    pub temperature: u32,
+}
+
+impl DisplayAdjustment {
+    /// White point that leaves colors unchanged
+    pub const NEUTRAL_TEMPERATURE: u32 = 6500;
+
+    /// Whether the adjustment leaves colors unchanged
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for DisplayAdjustment {
+    fn default() -> Self {
+        Self {
+            brightness: 1.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            temperature: Self::NEUTRAL_TEMPERATURE,
+        }
+    }
+}
+
+/// Request to change the color adjustment of outputs, e.g. to dim and warm
+/// the wallpaper at night
+///
+/// Settings left as None keep their current value.
+#[derive(Encode, Decode, Debug, Default)]
+pub struct SetDisplayAdjustment {
+    /// Output/monitor name, every output if None
+    pub output: Option<String>,
+    pub brightness: Option<f32>,
+    pub contrast: Option<f32>,
+    pub gamma: Option<f32>,
+    /// White point in Kelvin
+    pub temperature: Option<u32>,
+    /// Start from the neutral adjustment instead of the current one
+    pub reset: bool,
+}
+
+/// Response indicating if the color adjustment was changed
+#[derive(Encode, Decode, Debug)]
+pub struct DisplayAdjusted {
+    /// Whether the outputs show the new adjustment
+    pub success: bool,
+    /// Error message if the output was not found or a setting is out of range
+    pub error: Option<String>,
+}
+
+/// Request to blur and dim the wallpaper while the compositor shows an
+/// overview or app launcher, e.g. from a keybinding
+#[derive(Encode, Decode, Debug, Default)]
+pub struct SetOverviewMode {
+    /// Output/monitor name, every output if None
+    pub output: Option<String>,
+    /// Whether the overview shows, toggles it if None
+    pub enabled: Option<bool>,
+}
+
+/// Response indicating if the overview mode was changed
+#[derive(Encode, Decode, Debug)]
+pub struct OverviewModeSet {
+    /// Whether the overview is fading in or out
+    pub success: bool,
+    /// Error message if the output was not found
+    pub error: Option<String>,
+}
+
+/// Request to recolor wallpapers with a color scheme, e.g. from a theme
+/// switcher, until the scheme file changes
+#[derive(Encode, Decode, Debug, Default)]
+pub struct SetColorScheme {
+    /// Background, foreground, then color0 to color15 as CSS colors, see
+    /// `ColorScheme::to_list`, or empty to read the scheme file again
+    pub colors: Vec<String>,
+}
+
+/// Response indicating if the color scheme was changed
+#[derive(Encode, Decode, Debug)]
+pub struct ColorSchemeSet {
+    /// Whether wallpapers use the new scheme
+    pub success: bool,
+    /// Error message if a color is invalid
+    pub error: Option<String>,
+}
+
+/// Request to compute a color scheme from the wallpaper of an output
+#[derive(Encode, Decode, Debug, Default)]
+pub struct ExtractColors {
+    /// Output/monitor name, the first output with a wallpaper if None
+    pub output: Option<String>,
+}
+
+/// Response containing the color scheme of a wallpaper
+#[derive(Encode, Decode, Debug)]
+pub struct ColorsExtracted {
+    /// Name of the wallpaper the colors were taken from
+    pub wallpaper: Option<String>,
+    /// Background, foreground, then color0 to color15 as hex colors
+    pub colors: Vec<String>,
+    /// Whether the colors were extracted
+    pub success: bool,
+    /// Error message if the output has no wallpaper or it could not be rendered
+    pub error: Option<String>,
+}
+
+/// Request to save the frame an output shows as a PNG image
+#[derive(Encode, Decode, Debug, Default)]
+pub struct CaptureFrame {
+    /// Output/monitor name, the first output with a wallpaper if None
+    pub output: Option<String>,
+    /// Absolute path of the image to write
+    pub path: String,
+}
+
+/// Response to a frame capture
+#[derive(Encode, Decode, Debug)]
+pub struct FrameCaptured {
+    /// Output the frame was captured from
+    pub output: Option<String>,
+    /// Whether the image was written
+    pub success: bool,
+    /// Error message if the output has nothing drawn or the image could not be written
+    pub error: Option<String>,
+}
+
+/// Request to turn the animations of every wallpaper on or off, e.g. for
+/// accessibility or while recording the screen
+#[derive(Encode, Decode, Debug, Default)]
+pub struct SetMotion {
+    /// Whether wallpapers animate, toggles it if None
+    pub enabled: Option<bool>,
+}
+
+/// Response with the new motion setting
+#[derive(Encode, Decode, Debug)]
+pub struct MotionSet {
+    /// Whether wallpapers animate now
+    pub enabled: bool,
+}
+
+/// Request to change settings of a wallpaper, overriding parameters of its
+/// layers every time it is built
+///
+/// The changes are applied together, or none of them if the wallpaper
+/// rejects one.
+#[derive(Encode, Decode, Debug)]
+pub struct SetWallpaperSetting {
+    /// Name of the wallpaper
+    pub name: String,
+    /// Layer and parameter to override, as `layer.parameter`, and the new
+    /// value as TOML, or None to go back to the manifest's value
+    pub changes: Vec<(String, Option<String>)>,
+}
+
+/// Response indicating if the settings were stored
+#[derive(Encode, Decode, Debug)]
+pub struct WallpaperSettingSet {
+    /// Name of the wallpaper
+    pub name: String,
+    /// Whether the settings were stored and applied
+    pub success: bool,
+    /// Error message if the wallpaper does not exist or rejects a value
+    pub error: Option<String>,
+}
+
+/// Request to get the settings stored for a wallpaper
+#[derive(Encode, Decode, Debug)]
+pub struct GetWallpaperSettings {
+    /// Name of the wallpaper
+    pub name: String,
+}
+
+/// Response with the settings of a wallpaper
+#[derive(Encode, Decode, Debug)]
+pub struct WallpaperSettings {
+    /// Name of the wallpaper
+    pub name: String,
+    /// Settings as `layer.parameter` and their values as TOML
+    pub settings: Vec<(String, String)>,
+}
+
+/// Request for the parameters a wallpaper declares, so front-ends can offer
+/// a control for each
+#[derive(Encode, Decode, Debug)]
+pub struct DescribeWallpaper {
+    /// Name of the wallpaper
+    pub name: String,
+}
+
+/// A parameter declared in a manifest's `[params]`
+#[derive(Encode, Decode, Debug, Clone)]
+pub struct ParamDescription {
+    /// Name to set it by, with `SetWallpaperSetting`
+    pub name: String,
+    /// One of `float`, `int`, `bool`, `color`, `string` and `choice`
+    pub kind: String,
+    /// Name to show, the parameter's name if the manifest has none
+    pub label: String,
+    pub description: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,
+    /// Default as TOML
+    pub default: Option<String>,
+    /// Values of a `choice`
+    pub options: Vec<String>,
+    /// Value the user set as TOML, None if it is the manifest's
+    pub value: Option<String>,
+}
+
+/// Response describing the parameters of a wallpaper
+#[derive(Encode, Decode, Debug)]
+pub struct WallpaperDescription {
+    /// Name of the wallpaper
+    pub name: String,
+    pub params: Vec<ParamDescription>,
+    /// Names of the variants of the wallpaper
+    pub variants: Vec<String>,
+    /// Whether the wallpaper was found
+    pub success: bool,
+    /// Error message if the wallpaper does not exist
+    pub error: Option<String>,
+}
+
+/// Request to work out how a wallpaper would be drawn without showing it,
+/// takes the same arguments as `SetCurrentWallpaper`
+#[derive(Encode, Decode, Debug)]
+pub struct PlanWallpaper {
+    /// Name of the wallpaper
+    pub name: String,
+    /// Monitor it would be set on, every monitor if not specified
+    pub monitor: Option<String>,
+    /// Variant of the wallpaper, its default look if not specified
+    pub variant: Option<String>,
+}
+
+/// How a layer of a wallpaper would be drawn
+#[derive(Encode, Decode, Debug)]
+pub struct LayerPlan {
+    pub name: String,
+    /// What draws the layer, like `image`, `animation` or `effect`
+    pub kind: String,
+    /// Shader of an effect layer, like `wave` or the path of a custom one
+    pub shader: Option<String>,
+    /// Parameters of the layer after the user's settings, as TOML
+    pub params: Vec<(String, String)>,
+    /// File the layer is drawn from, if any
+    pub source: Option<String>,
+    /// Size of the layer's texture in pixels, after downscaling
+    pub size: Option<(u32, u32)>,
+    /// Estimated GPU memory of the layer's textures in bytes
+    pub texture_bytes: u64,
+    /// Why the layer would fail to load, if it would
+    pub error: Option<String>,
+}
+
+/// How a wallpaper would be drawn on one output
+#[derive(Encode, Decode, Debug)]
+pub struct OutputPlan {
+    /// Output/monitor name
+    pub output: String,
+    /// Size of the output in pixels
+    pub width: u32,
+    pub height: u32,
+    /// Layers drawn, bottom to top
+    pub layers: Vec<LayerPlan>,
+    /// Layers left out on this output by their `when` conditions
+    pub hidden: Vec<String>,
+    /// Estimated GPU memory of all layers in bytes
+    pub texture_bytes: u64,
+}
+
+/// Response describing how a wallpaper would be drawn
+#[derive(Encode, Decode, Debug)]
+pub struct WallpaperPlan {
+    /// Name of the wallpaper
+    pub name: String,
+    /// Framerate and tickrate as in the manifest, -1 for the compositor's
+    pub framerate: i32,
+    pub tickrate: i32,
+    /// Problems with the manifest that do not keep it from loading
+    pub warnings: Vec<String>,
+    pub outputs: Vec<OutputPlan>,
+    /// Whether the wallpaper could be loaded
+    pub success: bool,
+    /// Error message if it could not
+    pub error: Option<String>,
+}
+
+/// Request to grant a wallpaper's scripts the permissions its manifest
+/// says they need, to revoke them, or to look them up
+#[derive(Encode, Decode, Debug)]
+pub struct SetPermissions {
+    /// Name of the wallpaper
+    pub name: String,
+    /// Grant all of them for true, revoke them for false, only look them
+    /// up if not specified
+    pub grant: Option<bool>,
+}
+
+/// Response with the permissions of a wallpaper's scripts
+#[derive(Encode, Decode, Debug)]
+pub struct PermissionsSet {
+    /// Name of the wallpaper
+    pub name: String,
+    /// Permissions its manifest asks for, like `network`
+    pub needs: Vec<String>,
+    /// Permissions its scripts run with
+    pub granted: Vec<String>,
+    /// Whether its scripts changed since the permissions were granted,
+    /// which takes them back
+    pub stale: bool,
+    /// Whether the wallpaper was found and the change stored
+    pub success: bool,
+    /// Error message if not
+    pub error: Option<String>,
+}
+
 /// All possible request types that can be sent to the server
 ///
 /// Each variant corresponds to a specific request type and has a matching
@@ -232,6 +933,26 @@ pub enum Request {
     StopServer(StopServer),                       // -> ServerStopping
     QueryActiveWallpapers(QueryActiveWallpapers), // -> ActiveWallpaperList
     GetInstallDirectory(GetInstallDirectory),     // -> InstallDirectory
+    GetCacheStats(GetCacheStats),                 // -> CacheStats
+    ClearCache(ClearCache),                       // -> CacheCleared
+    SetFavorite(SetFavorite),                     // -> FavoriteSet
+    GetWallpaperStats(GetWallpaperStats),         // -> WallpaperStats
+    SetRandomWallpaper(SetRandomWallpaper),       // -> RandomWallpaperSet
+    SetLogLevel(SetLogLevel),                     // -> LogLevelSet
+    GetStats(GetStats),                           // -> RenderStats
+    MirrorOutput(MirrorOutput),                   // -> OutputMirrored
+    SetRenderScale(SetRenderScale),               // -> RenderScaleSet
+    SetDisplayAdjustment(SetDisplayAdjustment),   // -> DisplayAdjusted
+    SetOverviewMode(SetOverviewMode),             // -> OverviewModeSet
+    SetColorScheme(SetColorScheme),               // -> ColorSchemeSet
+    ExtractColors(ExtractColors),                 // -> ColorsExtracted
+    CaptureFrame(CaptureFrame),                   // -> FrameCaptured
+    SetMotion(SetMotion),                         // -> MotionSet
+    SetWallpaperSetting(SetWallpaperSetting),     // -> WallpaperSettingSet
+    GetWallpaperSettings(GetWallpaperSettings),   // -> WallpaperSettings
+    DescribeWallpaper(DescribeWallpaper),         // -> WallpaperDescription
+    PlanWallpaper(PlanWallpaper),                 // -> WallpaperPlan
+    SetPermissions(SetPermissions),               // -> PermissionsSet
 }
 
 /// All possible response types that can be received from the server
@@ -241,13 +962,33 @@ pub enum Request {
 #[derive(Encode, Decode, Debug)]
 pub enum Response {
     // Variant                                // Request Type
-    Health(Health),                           // <- Checkhealth
-    WallpaperLoaded(WallpaperLoaded),         // <- LoadWallpaper
-    WallpaperList(WallpaperList),             // <- ListWallpapers
-    WallpaperSet(WallpaperSet),               // <- SetCurrentWallpaper
-    ServerStopping(ServerStopping),           // <- StopServer
-    ActiveWallpaperList(ActiveWallpaperList), // <- QueryActiveWallpapers
-    InstallDirectory(InstallDirectory),       // <- GetInstallDirectory
+    Health(Health),                             // <- Checkhealth
+    WallpaperLoaded(WallpaperLoaded),           // <- LoadWallpaper
+    WallpaperList(WallpaperList),               // <- ListWallpapers
+    WallpaperSet(WallpaperSet),                 // <- SetCurrentWallpaper
+    ServerStopping(ServerStopping),             // <- StopServer
+    ActiveWallpaperList(ActiveWallpaperList),   // <- QueryActiveWallpapers
+    InstallDirectory(InstallDirectory),         // <- GetInstallDirectory
+    CacheStats(CacheStats),                     // <- GetCacheStats
+    CacheCleared(CacheCleared),                 // <- ClearCache
+    FavoriteSet(FavoriteSet),                   // <- SetFavorite
+    WallpaperStats(WallpaperStats),             // <- GetWallpaperStats
+    RandomWallpaperSet(RandomWallpaperSet),     // <- SetRandomWallpaper
+    LogLevelSet(LogLevelSet),                   // <- SetLogLevel
+    RenderStats(RenderStats),                   // <- GetStats
+    OutputMirrored(OutputMirrored),             // <- MirrorOutput
+    RenderScaleSet(RenderScaleSet),             // <- SetRenderScale
+    DisplayAdjusted(DisplayAdjusted),           // <- SetDisplayAdjustment
+    OverviewModeSet(OverviewModeSet),           // <- SetOverviewMode
+    ColorSchemeSet(ColorSchemeSet),             // <- SetColorScheme
+    ColorsExtracted(ColorsExtracted),           // <- ExtractColors
+    FrameCaptured(FrameCaptured),               // <- CaptureFrame
+    MotionSet(MotionSet),                       // <- SetMotion
+    WallpaperSettingSet(WallpaperSettingSet),   // <- SetWallpaperSetting
+    WallpaperSettings(WallpaperSettings),       // <- GetWallpaperSettings
+    WallpaperDescription(WallpaperDescription), // <- DescribeWallpaper
+    WallpaperPlan(WallpaperPlan),               // <- PlanWallpaper
+    PermissionsSet(PermissionsSet),             // <- SetPermissions
 }
 
 // Use the macro to implement all request-response pairs
@@ -278,3 +1019,78 @@ impl_request_response_pair!(
     GetInstallDirectory,
     InstallDirectory
 );
+impl_request_response_pair!(GetCacheStats, CacheStats, GetCacheStats, CacheStats);
+impl_request_response_pair!(ClearCache, CacheCleared, ClearCache, CacheCleared);
+impl_request_response_pair!(SetFavorite, FavoriteSet, SetFavorite, FavoriteSet);
+impl_request_response_pair!(
+    GetWallpaperStats,
+    WallpaperStats,
+    GetWallpaperStats,
+    WallpaperStats
+);
+impl_request_response_pair!(
+    SetRandomWallpaper,
+    RandomWallpaperSet,
+    SetRandomWallpaper,
+    RandomWallpaperSet
+);
+impl_request_response_pair!(SetLogLevel, LogLevelSet, SetLogLevel, LogLevelSet);
+impl_request_response_pair!(GetStats, RenderStats, GetStats, RenderStats);
+impl_request_response_pair!(MirrorOutput, OutputMirrored, MirrorOutput, OutputMirrored);
+impl_request_response_pair!(
+    SetRenderScale,
+    RenderScaleSet,
+    SetRenderScale,
+    RenderScaleSet
+);
+impl_request_response_pair!(
+    SetDisplayAdjustment,
+    DisplayAdjusted,
+    SetDisplayAdjustment,
+    DisplayAdjusted
+);
+impl_request_response_pair!(
+    SetOverviewMode,
+    OverviewModeSet,
+    SetOverviewMode,
+    OverviewModeSet
+);
+impl_request_response_pair!(
+    SetColorScheme,
+    ColorSchemeSet,
+    SetColorScheme,
+    ColorSchemeSet
+);
+impl_request_response_pair!(
+    ExtractColors,
+    ColorsExtracted,
+    ExtractColors,
+    ColorsExtracted
+);
+impl_request_response_pair!(CaptureFrame, FrameCaptured, CaptureFrame, FrameCaptured);
+impl_request_response_pair!(SetMotion, MotionSet, SetMotion, MotionSet);
+impl_request_response_pair!(
+    SetWallpaperSetting,
+    WallpaperSettingSet,
+    SetWallpaperSetting,
+    WallpaperSettingSet
+);
+impl_request_response_pair!(
+    GetWallpaperSettings,
+    WallpaperSettings,
+    GetWallpaperSettings,
+    WallpaperSettings
+);
+impl_request_response_pair!(
+    DescribeWallpaper,
+    WallpaperDescription,
+    DescribeWallpaper,
+    WallpaperDescription
+);
+impl_request_response_pair!(PlanWallpaper, WallpaperPlan, PlanWallpaper, WallpaperPlan);
+impl_request_response_pair!(
+    SetPermissions,
+    PermissionsSet,
+    SetPermissions,
+    PermissionsSet
+);
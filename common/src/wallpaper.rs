@@ -1,10 +1,16 @@
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+use crate::colorscheme::ColorScheme;
 use crate::manifest::{
-    EffectType, Layer, LayerContent, ManifestError, ScaleMode, ShaderType, WallpaperManifest,
+    ASSET_HASH_PREFIX, ArtFit, CaptureContent, ColorGrade, ColorStop, EffectInput, EffectType,
+    Emitter, Environment, GradientContent, Layer, LayerContent, LutSource, ManifestError,
+    NoiseTexture, ParticleSprites, Permission, Sampling, ScaleMode, ShaderType, TextContent,
+    WallpaperManifest, WebContent,
 };
 
 /// Errors that can occur when working with wallpapers
@@ -21,6 +27,9 @@ pub enum WallpaperError {
 
     #[error("Missing asset: {0}")]
     MissingAsset(String),
+
+    #[error("Corrupted asset: {0}")]
+    CorruptedAsset(String),
 }
 
 /// Represents a loaded wallpaper
@@ -31,6 +40,17 @@ pub struct Wallpaper {
 
     /// The absolute path to the wallpaper directory
     pub path: PathBuf,
+
+    /// Problems with the manifest that do not keep it from loading, like
+    /// fields wlrs does not know
+    pub warnings: Vec<String>,
+
+    /// Variant of the manifest applied, `None` for the default look
+    pub variant: Option<String>,
+
+    /// What its scripts may do, the permissions of `manifest.needs` the
+    /// user granted
+    pub permissions: Vec<Permission>,
 }
 
 impl Wallpaper {
@@ -56,12 +76,89 @@ impl Wallpaper {
         }
 
         // Parse the manifest
-        let manifest = WallpaperManifest::from_file(&manifest_path)?;
+        let (manifest, warnings) = WallpaperManifest::from_file_with_warnings(&manifest_path)?;
 
-        // Validate that the assets exist
+        // Validate that the assets exist and match their hashes
         Self::validate_assets(&path, &manifest)?;
+        Self::verify_integrity(&path, &manifest)?;
+
+        Ok(Self {
+            manifest,
+            path,
+            warnings,
+            variant: None,
+            permissions: Vec::new(),
+        })
+    }
+
+    /// The wallpaper as its variant `name` shows it
+    pub fn with_variant(mut self, name: &str) -> Result<Self, WallpaperError> {
+        self.manifest.apply_variant(name)?;
+        self.variant = Some(name.to_string());
+        Ok(self)
+    }
+
+    /// The wallpaper with the settings of the user applied, see
+    /// [`WallpaperManifest::apply_settings`]
+    pub fn with_settings(
+        mut self,
+        settings: &BTreeMap<String, toml::Value>,
+    ) -> Result<Self, WallpaperError> {
+        self.manifest.apply_settings(settings)?;
+        Ok(self)
+    }
+
+    /// The wallpaper with the permissions the user `granted` it, the ones
+    /// it does not need are left out
+    pub fn with_permissions(mut self, granted: &[Permission]) -> Self {
+        self.permissions = self
+            .manifest
+            .needs
+            .iter()
+            .copied()
+            .filter(|permission| granted.contains(permission))
+            .collect();
+        self
+    }
+
+    /// Paths of the scripts of its layers, relative to its directory
+    pub fn scripts(&self) -> Vec<&str> {
+        let mut scripts: Vec<&str> = self
+            .manifest
+            .layers
+            .iter()
+            .filter_map(|layer| layer.params.get("script").and_then(|v| v.as_str()))
+            .collect();
+        scripts.sort_unstable();
+        scripts.dedup();
+        scripts
+    }
 
-        Ok(Self { manifest, path })
+    /// Hash of all its scripts, the pages of its web layers and the commands
+    /// of its capture layers, permissions granted to the wallpaper are only
+    /// valid for what they were granted to
+    pub fn hash_scripts(&self) -> io::Result<String> {
+        let mut hasher = Sha256::new();
+        for script in self.scripts() {
+            hasher.update(script.as_bytes());
+            hasher.update(hash_file(&self.path.join(script))?.as_bytes());
+        }
+        for layer in &self.manifest.layers {
+            if let LayerContent::Web(content) = &layer.content {
+                hasher.update(content.web.as_bytes());
+                if !content.is_url() {
+                    hasher.update(hash_file(&self.path.join(&content.web))?.as_bytes());
+                }
+            }
+            if let LayerContent::Capture(CaptureContent {
+                command: Some(command),
+                ..
+            }) = &layer.content
+            {
+                hasher.update(command.as_bytes());
+            }
+        }
+        Ok(format_hash(hasher))
     }
 
     /// Get the absolute path to an asset
@@ -74,10 +171,21 @@ impl Wallpaper {
         wallpaper_path: &Path,
         manifest: &WallpaperManifest,
     ) -> Result<(), WallpaperError> {
+        if let Some(preview) = manifest
+            .preview
+            .as_ref()
+            .filter(|preview| !wallpaper_path.join(preview).exists())
+        {
+            return Err(WallpaperError::MissingAsset(format!(
+                "Preview image not found: {preview}"
+            )));
+        }
+
         // Check assets for all layers
         for layer in &manifest.layers {
             // Check content images
-            if let LayerContent::Image(image_path) = &layer.content {
+            if let LayerContent::Image(image_path) | LayerContent::Svg(image_path) = &layer.content
+            {
                 let full_path = wallpaper_path.join(image_path);
                 if !full_path.exists() {
                     return Err(WallpaperError::MissingAsset(format!(
@@ -87,6 +195,34 @@ impl Wallpaper {
                 }
             }
 
+            // Check the lookup tables of color grading effects
+            if layer.effect_type == Some(EffectType::Shader(ShaderType::Lut)) {
+                let grade = ColorGrade::from_params(&layer.name, &layer.params)?;
+                if let Some(lut) = grade.files().find(|lut| !wallpaper_path.join(lut).exists()) {
+                    return Err(WallpaperError::MissingAsset(format!(
+                        "Lookup table not found: {} for layer {}",
+                        lut.display(),
+                        layer.name
+                    )));
+                }
+            }
+
+            // Check the extra textures of particle layers
+            if layer.effect_type == Some(EffectType::Particles) {
+                let sprites = ParticleSprites::from_params(&layer.name, &layer.params)?;
+                if let Some(texture) = sprites
+                    .textures
+                    .iter()
+                    .find(|texture| !wallpaper_path.join(texture).exists())
+                {
+                    return Err(WallpaperError::MissingAsset(format!(
+                        "Image not found: {} for layer {}",
+                        texture.display(),
+                        layer.name
+                    )));
+                }
+            }
+
             // Check if layer has script parameters
             if let Some(script_path) = layer.params.get("script").and_then(|v| v.as_str()) {
                 let full_path = wallpaper_path.join(script_path);
@@ -102,6 +238,28 @@ impl Wallpaper {
         Ok(())
     }
 
+    /// Check the files listed in the manifest's `[assets]` table against
+    /// their hashes, so a damaged download fails with the file to blame
+    pub fn verify_integrity(
+        wallpaper_path: &Path,
+        manifest: &WallpaperManifest,
+    ) -> Result<(), WallpaperError> {
+        for (asset, expected) in &manifest.assets {
+            let full_path = wallpaper_path.join(asset);
+            if !full_path.is_file() {
+                return Err(WallpaperError::MissingAsset(format!(
+                    "{asset} listed in [assets] not found"
+                )));
+            }
+            if hash_file(&full_path)? != *expected {
+                return Err(WallpaperError::CorruptedAsset(format!(
+                    "{asset} does not match its hash, the file is damaged or incomplete"
+                )));
+            }
+        }
+        Ok(())
+    }
+
     /// Get the name of the wallpaper
     pub fn name(&self) -> &str {
         &self.manifest.name
@@ -122,6 +280,14 @@ impl Wallpaper {
         &self.manifest.description
     }
 
+    /// Get the absolute path to the preview image, if the manifest names one
+    pub fn preview_path(&self) -> Option<PathBuf> {
+        self.manifest
+            .preview
+            .as_ref()
+            .map(|preview| self.asset_path(preview))
+    }
+
     /// Get the framerate (FPS)
     /// Special values:
     ///   -1: Use compositor-driven refresh rate
@@ -138,6 +304,16 @@ impl Wallpaper {
         self.manifest.get_tickrate()
     }
 
+    /// Get how many seconds the animations run behind the shared clock
+    pub fn phase(&self) -> f32 {
+        self.manifest.phase.unwrap_or(0.0)
+    }
+
+    /// Get the opacity of the area not covered by opaque layers
+    pub fn background_alpha(&self) -> f32 {
+        self.manifest.background_alpha
+    }
+
     /// Get the scale mode
     pub fn scale_mode(&self) -> &ScaleMode {
         &self.manifest.scale_mode
@@ -168,18 +344,81 @@ impl Wallpaper {
     }
 
     /// Get all layers in this wallpaper in rendering order
-    pub fn get_layers(&self) -> Vec<RenderLayer> {
+    pub fn get_layers(&self) -> Result<Vec<RenderLayer>, ManifestError> {
         let mut render_layers = Vec::new();
 
         // Convert manifest layers to render layers
         for layer in &self.manifest.layers {
-            render_layers.push(RenderLayer::from_manifest_layer(layer, &self.path));
+            render_layers.push(RenderLayer::from_manifest_layer(layer, &self.path)?);
         }
 
         // Sort by z-index
         render_layers.sort_by_key(|layer| layer.z_index);
 
-        dbg!(render_layers)
+        Ok(dbg!(render_layers))
+    }
+
+    /// Get the render layers whose conditions hold in `environment`, sorted
+    /// by z-index
+    pub fn get_layers_in(
+        &self,
+        environment: &Environment,
+    ) -> Result<Vec<RenderLayer>, ManifestError> {
+        let mut render_layers: Vec<_> = self
+            .manifest
+            .layers
+            .iter()
+            .filter(|layer| {
+                layer.when.holds(environment) && layer.when.in_variant(self.variant.as_deref())
+            })
+            .map(|layer| {
+                let mut render_layer = RenderLayer::from_manifest_layer(layer, &self.path)?;
+                render_layer.resolve_colors(&environment.colors);
+                Ok(render_layer)
+            })
+            .collect::<Result<_, ManifestError>>()?;
+        render_layers.sort_by_key(|layer| layer.z_index);
+        Ok(render_layers)
+    }
+
+    /// Whether some layers are only shown in some weather
+    pub fn depends_on_weather(&self) -> bool {
+        self.manifest
+            .layers
+            .iter()
+            .any(|layer| !layer.when.weather.is_empty())
+    }
+
+    /// Whether some layers are only shown on some outputs, by their name,
+    /// size or shape
+    pub fn depends_on_screen(&self) -> bool {
+        self.manifest
+            .layers
+            .iter()
+            .any(|layer| layer.when.depends_on_screen())
+    }
+
+    /// Whether some layers use colors of the color scheme
+    pub fn depends_on_colors(&self) -> bool {
+        self.manifest.layers.iter().any(|layer| {
+            let content = match &layer.content {
+                LayerContent::Color(color) => ColorScheme::is_placeholder(color),
+                LayerContent::Text(text) => ColorScheme::is_placeholder(&text.color),
+                LayerContent::Gradient(gradient) => gradient
+                    .stops
+                    .iter()
+                    .any(|stop| ColorScheme::is_placeholder(stop.color())),
+                _ => false,
+            };
+            let emitter = layer.effect_type == Some(EffectType::Particles)
+                && Emitter::from_params(&layer.name, &layer.params).is_ok_and(|emitter| {
+                    emitter
+                        .colors
+                        .iter()
+                        .any(|color| ColorScheme::is_placeholder(color))
+                });
+            content || emitter
+        })
     }
 }
 
@@ -192,6 +431,8 @@ pub struct RenderLayer {
     pub z_index: i32,
     /// Transparency (0.0 to 1.0)
     pub opacity: f32,
+    /// Texture filtering for the layer's image
+    pub sampling: Sampling,
     /// Layer type
     pub layer_type: LayerType,
 }
@@ -201,7 +442,7 @@ pub struct RenderLayer {
 pub enum LayerType {
     /// Solid color layer
     Color {
-        /// Color value (CSS-style hex or rgb/rgba)
+        /// Color value (any CSS color, see [`crate::color::Color::parse`])
         color: String,
     },
     /// Static image layer
@@ -209,6 +450,38 @@ pub enum LayerType {
         /// Path to the image
         image_path: PathBuf,
     },
+    /// Vector image layer, rendered at the output's resolution
+    Svg {
+        /// Path to the SVG document
+        svg_path: PathBuf,
+    },
+    /// Text layer, re-rendered when its template changes
+    Text {
+        /// What to draw and how
+        content: TextContent,
+    },
+    /// Gradient layer
+    Gradient {
+        /// Shape, colors and animation of the gradient
+        content: GradientContent,
+    },
+    /// Live capture of another output
+    Capture {
+        /// Output to capture and the command to start with it
+        content: CaptureContent,
+    },
+    /// Web page rendered by a headless browser
+    Web {
+        /// URL of the page, files in the wallpaper directory as `file://` URLs
+        url: String,
+        content: WebContent,
+    },
+    /// Album art of the track a media player plays
+    AlbumArt {
+        /// Image shown while no player has art
+        fallback_path: Option<PathBuf>,
+        fit: ArtFit,
+    },
     /// Particle effect layer
     Particle {
         /// Path to the particle image
@@ -217,6 +490,10 @@ pub enum LayerType {
         script_path: Option<PathBuf>,
         /// Parameters for the effect
         params: HashMap<String, toml::Value>,
+        /// Moves the particles when there is no script
+        emitter: Emitter,
+        /// More textures and their frames, with the paths resolved
+        sprites: ParticleSprites,
     },
     /// Shader effect layer
     Shader {
@@ -224,14 +501,52 @@ pub enum LayerType {
         shader_type: ShaderType,
         /// Optional image for the shader
         image_path: Option<PathBuf>,
+        /// Whether the shader reads the image or the layers below
+        input: EffectInput,
         /// Uniforms for the shader
         uniforms: HashMap<String, toml::Value>,
+        /// Noise texture generated for the shader
+        noise: Option<NoiseTexture>,
+    },
+    /// Color grading effect layer, with the lookup table paths resolved
+    ColorGrade {
+        /// Image graded when the effect does not read the layers below
+        image_path: Option<PathBuf>,
+        /// Whether the effect reads the image or the layers below
+        input: EffectInput,
+        grade: ColorGrade,
     },
 }
 
 impl RenderLayer {
+    /// Replace placeholders like `{color4}` in color fields with the colors
+    /// of `colors`
+    fn resolve_colors(&mut self, colors: &ColorScheme) {
+        match &mut self.layer_type {
+            LayerType::Color { color } => *color = colors.resolve(color).into_owned(),
+            LayerType::Text { content } => {
+                content.color = colors.resolve(&content.color).into_owned();
+            }
+            LayerType::Gradient { content } => {
+                for stop in &mut content.stops {
+                    let (ColorStop::Color(color) | ColorStop::Positioned { color, .. }) = stop;
+                    *color = colors.resolve(color).into_owned();
+                }
+            }
+            LayerType::Particle { emitter, .. } => {
+                for color in &mut emitter.colors {
+                    *color = colors.resolve(color).into_owned();
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Create a render layer from a manifest layer
-    pub fn from_manifest_layer(layer: &Layer, base_path: &Path) -> Self {
+    ///
+    /// Fails like the validation of the manifest would, for layers changed
+    /// after it was loaded.
+    pub fn from_manifest_layer(layer: &Layer, base_path: &Path) -> Result<Self, ManifestError> {
         let layer_type = match &layer.content {
             LayerContent::Color(color) => LayerType::Color {
                 color: color.clone(),
@@ -239,6 +554,30 @@ impl RenderLayer {
             LayerContent::Image(image) => LayerType::Image {
                 image_path: base_path.join(image),
             },
+            LayerContent::Svg(svg) => LayerType::Svg {
+                svg_path: base_path.join(svg),
+            },
+            LayerContent::Text(content) => LayerType::Text {
+                content: content.clone(),
+            },
+            LayerContent::Gradient(content) => LayerType::Gradient {
+                content: content.clone(),
+            },
+            LayerContent::Capture(content) => LayerType::Capture {
+                content: content.clone(),
+            },
+            LayerContent::Web(content) => LayerType::Web {
+                url: if content.is_url() {
+                    content.web.clone()
+                } else {
+                    format!("file://{}", base_path.join(&content.web).display())
+                },
+                content: content.clone(),
+            },
+            LayerContent::AlbumArt(content) => LayerType::AlbumArt {
+                fallback_path: content.fallback.as_ref().map(|path| base_path.join(path)),
+                fit: content.album_art,
+            },
             LayerContent::None => {
                 // Empty layer, fallback to a transparent layer
                 LayerType::Color {
@@ -257,6 +596,10 @@ impl RenderLayer {
                         .get("script")
                         .and_then(|v| v.as_str())
                         .map(|s| base_path.join(s));
+                    let mut sprites = ParticleSprites::from_params(&layer.name, &layer.params)?;
+                    for texture in &mut sprites.textures {
+                        *texture = base_path.join(&*texture);
+                    }
 
                     LayerType::Particle {
                         image_path: if let LayerContent::Image(img) = &layer.content {
@@ -267,6 +610,28 @@ impl RenderLayer {
                         },
                         script_path,
                         params: layer.params.clone(),
+                        emitter: Emitter::from_params(&layer.name, &layer.params)?,
+                        sprites,
+                    }
+                }
+                EffectType::Shader(ShaderType::Lut) => {
+                    let mut grade = ColorGrade::from_params(&layer.name, &layer.params)?;
+                    for source in [Some(&mut grade.lut), grade.night_lut.as_mut()]
+                        .into_iter()
+                        .flatten()
+                    {
+                        if let LutSource::File(path) = source {
+                            *path = base_path.join(&*path);
+                        }
+                    }
+                    LayerType::ColorGrade {
+                        image_path: if let LayerContent::Image(img) = &layer.content {
+                            Some(base_path.join(img))
+                        } else {
+                            None
+                        },
+                        input: layer.input,
+                        grade,
                     }
                 }
                 EffectType::Shader(shader_type) => LayerType::Shader {
@@ -276,7 +641,9 @@ impl RenderLayer {
                     } else {
                         None
                     },
+                    input: layer.input,
                     uniforms: layer.params.clone(),
+                    noise: NoiseTexture::from_params(&layer.name, &layer.params)?,
                 },
                 EffectType::None => layer_type, // No effect, use original layer type
             }
@@ -284,12 +651,13 @@ impl RenderLayer {
             layer_type // No effect, use original layer type
         };
 
-        Self {
+        Ok(Self {
             name: layer.name.clone(),
             z_index: layer.z_index,
             opacity: layer.opacity,
+            sampling: layer.sampling,
             layer_type,
-        }
+        })
     }
 }
 
@@ -348,6 +716,53 @@ impl WallpaperDirectory {
     }
 }
 
+/// Hash of a file in the form used by the `[assets]` table of a manifest
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format_hash(hasher))
+}
+
+/// Hash of data in memory, e.g. a download before it is written
+pub fn hash_bytes(data: &[u8]) -> String {
+    format_hash(Sha256::new_with_prefix(data))
+}
+
+fn format_hash(hasher: Sha256) -> String {
+    let digest: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("{ASSET_HASH_PREFIX}{digest}")
+}
+
+/// Hash every file of a wallpaper directory but its manifest, giving the
+/// entries of an `[assets]` table
+pub fn hash_assets(wallpaper_path: &Path) -> io::Result<BTreeMap<String, String>> {
+    fn visit(root: &Path, dir: &Path, hashes: &mut BTreeMap<String, String>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                visit(root, &path, hashes)?;
+            } else if path != root.join("manifest.toml") {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                let key = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                hashes.insert(key, hash_file(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    let mut hashes = BTreeMap::new();
+    visit(wallpaper_path, wallpaper_path, &mut hashes)?;
+    Ok(hashes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -388,4 +803,69 @@ mod tests {
         assert!(wallpapers.contains(&"wallpaper1".to_string()));
         assert!(wallpapers.contains(&"wallpaper2".to_string()));
     }
+
+    #[test]
+    fn test_asset_integrity() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("images")).unwrap();
+        fs::write(
+            dir.path().join("images").join("sky.png"),
+            b"not really a png",
+        )
+        .unwrap();
+
+        let hashes = hash_assets(dir.path()).unwrap();
+        assert_eq!(hashes.keys().collect::<Vec<_>>(), ["images/sky.png"]);
+        assert_eq!(hashes["images/sky.png"], hash_bytes(b"not really a png"));
+
+        let mut manifest: WallpaperManifest = toml::from_str("name = \"Hashed\"").unwrap();
+        manifest.assets = hashes;
+        manifest.validate().unwrap();
+        manifest.to_file(dir.path().join("manifest.toml")).unwrap();
+        assert!(Wallpaper::load(dir.path()).is_ok());
+
+        fs::write(dir.path().join("images").join("sky.png"), b"truncated").unwrap();
+        assert!(matches!(
+            Wallpaper::load(dir.path()),
+            Err(WallpaperError::CorruptedAsset(_))
+        ));
+
+        fs::remove_file(dir.path().join("images").join("sky.png")).unwrap();
+        assert!(matches!(
+            Wallpaper::load(dir.path()),
+            Err(WallpaperError::MissingAsset(_))
+        ));
+    }
+
+    #[test]
+    fn test_script_permissions() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("snow.png"), b"flake").unwrap();
+        fs::write(dir.path().join("snow.lua"), b"function update() end").unwrap();
+        fs::write(
+            dir.path().join("manifest.toml"),
+            r#"
+            name = "Snow"
+            needs = ["network", "file-read"]
+
+            [[layers]]
+            name = "flakes"
+            content = "snow.png"
+            effect_type = "particles"
+            params = { script = "snow.lua" }
+            "#,
+        )
+        .unwrap();
+
+        let wallpaper = Wallpaper::load(dir.path()).unwrap();
+        assert_eq!(wallpaper.scripts(), ["snow.lua"]);
+        assert!(wallpaper.permissions.is_empty());
+        let hash = wallpaper.hash_scripts().unwrap();
+
+        let granted = wallpaper.clone().with_permissions(&[Permission::FileRead]);
+        assert_eq!(granted.permissions, [Permission::FileRead]);
+
+        fs::write(dir.path().join("snow.lua"), b"os.execute('rm -rf ~')").unwrap();
+        assert_ne!(wallpaper.hash_scripts().unwrap(), hash);
+    }
 }
@@ -0,0 +1,728 @@
+//! Conversion of Wallpaper Engine projects into wlrs wallpapers
+//!
+//! Only the simple parts translate: web wallpapers, and scenes made of
+//! images with a few common effects and particle systems. Everything else is
+//! reported so the user knows what the converted wallpaper leaves out.
+//! Scenes have to be extracted from `scene.pkg` first, and their `.tex`
+//! textures converted to regular images, e.g. with RePKG.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::manifest::{
+    self, EffectType, Layer, LayerContent, ShaderType, WallpaperManifest, WebContent,
+};
+use serde_json::Value;
+
+/// Image formats a texture of a scene may have been converted to
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "gif"];
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("Failed to read the project: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("{file}: {source}")]
+    Json {
+        file: String,
+        source: serde_json::Error,
+    },
+
+    #[error("{0}")]
+    Unsupported(String),
+}
+
+/// A Wallpaper Engine project translated to a manifest
+#[derive(Debug)]
+pub struct Conversion {
+    pub manifest: WallpaperManifest,
+    /// Files of the project to copy, with their path in the new wallpaper
+    pub files: BTreeMap<String, PathBuf>,
+    /// What the conversion left out or changed, one line each
+    pub unsupported: Vec<String>,
+}
+
+/// Convert the project in `dir`, the directory with its `project.json`
+pub fn convert(dir: &Path) -> Result<Conversion, ImportError> {
+    let project = read_json(dir, "project.json")?;
+    let field = |key: &str| project.get(key).and_then(Value::as_str).unwrap_or_default();
+
+    let mut conversion = Conversion {
+        manifest: WallpaperManifest::parse("name = \"imported\"")
+            .map_err(|e| ImportError::Unsupported(e.to_string()))?,
+        files: BTreeMap::new(),
+        unsupported: Vec::new(),
+    };
+    let manifest = &mut conversion.manifest;
+    manifest.name = title(dir, field("title"));
+    manifest.description = field("description").to_string();
+    manifest.tags = project
+        .get("tags")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect();
+    let preview = field("preview");
+    if !preview.is_empty() && project_path(dir, preview)?.is_file() {
+        manifest.preview = Some(preview.to_string());
+        conversion
+            .files
+            .insert(preview.to_string(), dir.join(preview));
+    }
+    if project.pointer("/general/properties").is_some() {
+        conversion
+            .unsupported
+            .push("User properties are not supported, their defaults are used".to_string());
+    }
+
+    let file = field("file");
+    match field("type").to_ascii_lowercase().as_str() {
+        "scene" => convert_scene(dir, file, &mut conversion)?,
+        "web" => convert_web(dir, file, &mut conversion)?,
+        "video" => {
+            let Some(preview) = conversion.manifest.preview.clone() else {
+                return Err(ImportError::Unsupported(
+                    "Video wallpapers are not supported and the project has no preview to show instead"
+                        .to_string(),
+                ));
+            };
+            conversion.manifest.framerate = 0;
+            conversion.manifest.tickrate = 0;
+            conversion
+                .manifest
+                .layers
+                .push(Layer::new_background_image(&preview));
+            conversion.unsupported.push(format!(
+                "Video wallpapers are not supported, the preview {preview} is shown instead"
+            ));
+        }
+        kind => {
+            return Err(ImportError::Unsupported(format!(
+                "Wallpapers of type {kind:?} are not supported"
+            )));
+        }
+    }
+    Ok(conversion)
+}
+
+/// A web wallpaper takes the whole project along, the page may use any of it
+fn convert_web(dir: &Path, file: &str, conversion: &mut Conversion) -> Result<(), ImportError> {
+    if file.is_empty() || !project_path(dir, file)?.is_file() {
+        return Err(ImportError::Unsupported(format!(
+            "The page {file:?} of the web wallpaper is missing"
+        )));
+    }
+    for (relative, path) in project_files(dir)? {
+        if relative != "project.json" {
+            conversion.files.insert(relative, path);
+        }
+    }
+    let mut layer = Layer::new_background_image(file);
+    layer.content = LayerContent::Web(WebContent {
+        web: file.to_string(),
+        input: true,
+        transparent: false,
+    });
+    conversion.manifest.layers.push(layer);
+    conversion
+        .unsupported
+        .push("The page cannot read the user properties Wallpaper Engine passes".to_string());
+    Ok(())
+}
+
+fn convert_scene(dir: &Path, file: &str, conversion: &mut Conversion) -> Result<(), ImportError> {
+    let file = if file.is_empty() { "scene.json" } else { file };
+    if !project_path(dir, file)?.is_file() {
+        return Err(ImportError::Unsupported(
+            if dir.join("scene.pkg").is_file() {
+                "The scene is packed in scene.pkg, extract it first, e.g. with RePKG".to_string()
+            } else {
+                format!("The scene {file} is missing")
+            },
+        ));
+    }
+    let scene = read_json(dir, file)?;
+
+    if let Some(color) = scene
+        .pointer("/general/clearcolor")
+        .and_then(Value::as_str)
+        .and_then(vector_color)
+    {
+        conversion
+            .manifest
+            .layers
+            .push(Layer::new_background_color(&color));
+    }
+
+    let mut animated = false;
+    let mut names: HashMap<String, usize> = HashMap::new();
+    for (index, object) in scene
+        .get("objects")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+    {
+        let name = object
+            .get("name")
+            .and_then(Value::as_str)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("object-{}", index + 1));
+        // Layer names have to be unique
+        let count = names.entry(name.clone()).or_default();
+        *count += 1;
+        let name = if *count > 1 {
+            format!("{name}-{count}")
+        } else {
+            name
+        };
+        if !is_visible(object) {
+            continue;
+        }
+        let z_index = index as i32;
+
+        if let Some(model) = object.get("image").and_then(Value::as_str) {
+            let Some(image) = model_texture(dir, model, conversion)? else {
+                continue;
+            };
+            let mut layer = Layer::new_background_image(&image);
+            layer.name = name.clone();
+            layer.z_index = z_index;
+            if let Some(alpha) = object.get("alpha").and_then(value_f64) {
+                layer.opacity = alpha.clamp(0.0, 1.0) as f32;
+            }
+            if object.get("origin").is_some() || object.get("scale").is_some() {
+                conversion.unsupported.push(format!(
+                    "{name}: the image is scaled to the whole screen, its position and size are ignored"
+                ));
+            }
+            animated |= apply_effects(object, &mut layer, &mut conversion.unsupported);
+            conversion.manifest.layers.push(layer);
+        } else if let Some(particle) = object.get("particle").and_then(Value::as_str) {
+            let Some(shader) = particle_shader(particle) else {
+                conversion.unsupported.push(format!(
+                    "{name}: the particle system {particle} has no counterpart and was left out"
+                ));
+                continue;
+            };
+            let mut layer = Layer::new_effect(
+                &name,
+                EffectType::Shader(shader),
+                LayerContent::None,
+                z_index,
+            );
+            if let Some(speed) = object
+                .pointer("/instanceoverride/speed")
+                .and_then(value_f64)
+            {
+                layer
+                    .params
+                    .insert("speed".to_string(), toml::Value::Float(speed));
+            }
+            if let Some(count) = object
+                .pointer("/instanceoverride/count")
+                .and_then(value_f64)
+            {
+                // Wallpaper Engine multiplies the particle count, the shaders
+                // take a density around 0.4
+                layer.params.insert(
+                    "density".to_string(),
+                    toml::Value::Float((0.4 * count).clamp(0.05, 1.0)),
+                );
+            }
+            conversion.unsupported.push(format!(
+                "{name}: the particle system {particle} is approximated by the {} shader",
+                shader_name(&layer)
+            ));
+            conversion.manifest.layers.push(layer);
+            animated = true;
+        } else if object.get("sound").is_some() {
+            conversion
+                .unsupported
+                .push(format!("{name}: sounds are not supported"));
+        } else if object.get("text").is_some() {
+            conversion
+                .unsupported
+                .push(format!("{name}: text objects are not supported"));
+        } else {
+            conversion
+                .unsupported
+                .push(format!("{name}: objects of this kind are not supported"));
+        }
+    }
+
+    if conversion.manifest.layers.is_empty() {
+        return Err(ImportError::Unsupported(
+            "Nothing in the scene could be converted".to_string(),
+        ));
+    }
+    if !animated {
+        conversion.manifest.framerate = 0;
+        conversion.manifest.tickrate = 0;
+    }
+    Ok(())
+}
+
+/// Apply the first effect of an image object that has a counterpart,
+/// returning whether the layer animates
+fn apply_effects(object: &Value, layer: &mut Layer, unsupported: &mut Vec<String>) -> bool {
+    let effects = object
+        .get("effects")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let mut applied = false;
+    for effect in effects.iter().filter(|effect| is_visible(effect)) {
+        let file = effect
+            .get("file")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        match effect_shader(file) {
+            Some(shader) if !applied => {
+                // Constants of the first pass, like `speed` and `strength`
+                let constants = effect
+                    .pointer("/passes/0/constantshadervalues")
+                    .cloned()
+                    .unwrap_or_default();
+                let constant = |key: &str| constants.get(key).and_then(value_f64);
+                let params = &mut layer.params;
+                match shader {
+                    ShaderType::Wave | ShaderType::Ripple => {
+                        if let Some(strength) = constant("strength") {
+                            params.insert("amplitude".to_string(), toml::Value::Float(strength));
+                        }
+                    }
+                    ShaderType::Gaussian => {
+                        if let Some(radius) = constant("scale").or(constant("strength")) {
+                            params.insert("radius".to_string(), toml::Value::Float(radius));
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(speed) = constant("speed") {
+                    params.insert("speed".to_string(), toml::Value::Float(speed));
+                }
+                layer.effect_type = Some(EffectType::Shader(shader));
+                applied = true;
+            }
+            Some(_) => unsupported.push(format!(
+                "{}: only one effect per image is supported, {file} was left out",
+                layer.name
+            )),
+            None => unsupported.push(format!(
+                "{}: the effect {file} is not supported",
+                layer.name
+            )),
+        }
+    }
+    applied && layer.effect_type != Some(EffectType::Shader(ShaderType::Gaussian))
+}
+
+/// The shader closest to a Wallpaper Engine effect, by its file
+fn effect_shader(file: &str) -> Option<ShaderType> {
+    let file = file.to_ascii_lowercase();
+    if file.contains("waterripple") {
+        Some(ShaderType::Ripple)
+    } else if file.contains("waterwaves") || file.contains("waterflow") || file.contains("shake") {
+        Some(ShaderType::Wave)
+    } else if file.contains("blur") {
+        Some(ShaderType::Gaussian)
+    } else if file.contains("glitch") {
+        Some(ShaderType::Glitch)
+    } else {
+        None
+    }
+}
+
+/// The procedural shader closest to a particle system, by its file name
+fn particle_shader(file: &str) -> Option<ShaderType> {
+    let file = file.to_ascii_lowercase();
+    if file.contains("snow") {
+        Some(ShaderType::Snow)
+    } else if file.contains("rain") {
+        Some(ShaderType::Rain)
+    } else if file.contains("fire") || file.contains("ember") || file.contains("flame") {
+        Some(ShaderType::Fire)
+    } else if file.contains("star") {
+        Some(ShaderType::Starfield)
+    } else {
+        None
+    }
+}
+
+fn shader_name(layer: &Layer) -> String {
+    match &layer.effect_type {
+        Some(EffectType::Shader(shader)) => format!("{shader:?}").to_lowercase(),
+        _ => String::new(),
+    }
+}
+
+/// Follow an image object's model to its material and the first texture of
+/// that, returning the path of the image to use
+///
+/// The image is added to the files to copy. Textures only present as `.tex`
+/// are reported and give None.
+fn model_texture(
+    dir: &Path,
+    model: &str,
+    conversion: &mut Conversion,
+) -> Result<Option<String>, ImportError> {
+    let texture = if IMAGE_EXTENSIONS
+        .iter()
+        .any(|extension| model.to_ascii_lowercase().ends_with(extension))
+    {
+        // Some scenes name an image directly
+        model
+            .trim_end_matches(|c| c != '.')
+            .trim_end_matches('.')
+            .to_string()
+    } else {
+        project_path(dir, model)?;
+        let Ok(model_json) = read_json(dir, model) else {
+            conversion.unsupported.push(format!(
+                "The model {model} is missing, its object was left out"
+            ));
+            return Ok(None);
+        };
+        let material = model_json
+            .get("material")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        project_path(dir, material)?;
+        let Ok(material_json) = read_json(dir, material) else {
+            conversion.unsupported.push(format!(
+                "The material of {model} is missing, its object was left out"
+            ));
+            return Ok(None);
+        };
+        match material_json
+            .pointer("/passes/0/textures/0")
+            .and_then(Value::as_str)
+        {
+            Some(texture) => format!("materials/{texture}"),
+            None => {
+                conversion
+                    .unsupported
+                    .push(format!("{model} has no texture, its object was left out"));
+                return Ok(None);
+            }
+        }
+    };
+
+    project_path(dir, &texture)?;
+    let found = IMAGE_EXTENSIONS
+        .iter()
+        .map(|extension| format!("{texture}.{extension}"))
+        .find(|image| dir.join(image).is_file());
+    match found {
+        Some(image) => {
+            conversion.files.insert(image.clone(), dir.join(&image));
+            Ok(Some(image))
+        }
+        None => {
+            conversion.unsupported.push(format!(
+                "{texture}.tex has to be converted to PNG first, its object was left out"
+            ));
+            Ok(None)
+        }
+    }
+}
+
+/// Whether an object or effect is shown, `visible` may be a bool or a
+/// user property with a default `value`
+fn is_visible(value: &Value) -> bool {
+    match value.get("visible") {
+        Some(Value::Bool(visible)) => *visible,
+        Some(property) => property
+            .get("value")
+            .and_then(Value::as_bool)
+            .unwrap_or(true),
+        None => true,
+    }
+}
+
+/// A number, or a user property with a numeric default `value`
+fn value_f64(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.get("value").and_then(Value::as_f64))
+}
+
+/// A color written as three numbers from 0 to 1, like `"0.1 0.2 0.3"`, as hex
+fn vector_color(value: &str) -> Option<String> {
+    let channels: Vec<f32> = value
+        .split_whitespace()
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+    let byte = |channel: f32| (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Some(format!("#{:02x}{:02x}{:02x}", byte(r), byte(g), byte(b)))
+}
+
+/// Name of the converted wallpaper, the title or else the directory name
+fn title(dir: &Path, title: &str) -> String {
+    let title = title.trim();
+    if !title.is_empty() {
+        return title.to_string();
+    }
+    dir.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Imported wallpaper".to_string())
+}
+
+/// Path of a file the project names, refusing ones that lead out of it
+fn project_path(dir: &Path, file: &str) -> Result<PathBuf, ImportError> {
+    if !manifest::is_inside(file) {
+        return Err(ImportError::Unsupported(format!(
+            "The project names {file:?}, which is not a path inside it"
+        )));
+    }
+    Ok(dir.join(file))
+}
+
+fn read_json(dir: &Path, file: &str) -> Result<Value, ImportError> {
+    let text = fs::read_to_string(project_path(dir, file)?)?;
+    // Some editors write a byte order mark
+    serde_json::from_str(text.trim_start_matches('\u{feff}')).map_err(|source| ImportError::Json {
+        file: file.to_string(),
+        source,
+    })
+}
+
+/// Every file of a project with its path relative to the project, using `/`
+fn project_files(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    fn visit(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                visit(root, &path, files)?;
+            } else {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                let key = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.push((key, path));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    visit(dir, dir, &mut files)?;
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(dir: &Path, file: &str, content: &str) {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_convert_scene() {
+        let dir = tempdir().unwrap();
+        write(
+            dir.path(),
+            "project.json",
+            r#"{"title": "Lake", "type": "scene", "file": "scene.json", "tags": ["Nature"]}"#,
+        );
+        write(
+            dir.path(),
+            "scene.json",
+            r#"{
+                "general": {"clearcolor": "0 0 1"},
+                "objects": [
+                    {"name": "lake", "image": "models/lake.json", "effects": [
+                        {"file": "effects/waterripple/effect.json",
+                         "passes": [{"constantshadervalues": {"speed": 2, "strength": 0.05}}]},
+                        {"file": "effects/pulse/effect.json"}
+                    ]},
+                    {"name": "hidden", "image": "models/lake.json", "visible": false},
+                    {"name": "sky", "image": "models/sky.json"},
+                    {"name": "snow", "particle": "particles/snowfall.json",
+                     "instanceoverride": {"speed": 0.5}},
+                    {"name": "birds", "sound": ["sounds/birds.mp3"]}
+                ]
+            }"#,
+        );
+        write(
+            dir.path(),
+            "models/lake.json",
+            r#"{"material": "materials/lake.json"}"#,
+        );
+        write(
+            dir.path(),
+            "materials/lake.json",
+            r#"{"passes": [{"textures": ["lake"]}]}"#,
+        );
+        write(dir.path(), "materials/lake.png", "png");
+        write(
+            dir.path(),
+            "models/sky.json",
+            r#"{"material": "materials/sky.json"}"#,
+        );
+        write(
+            dir.path(),
+            "materials/sky.json",
+            r#"{"passes": [{"textures": ["sky"]}]}"#,
+        );
+
+        let conversion = convert(dir.path()).unwrap();
+        let manifest = &conversion.manifest;
+        assert_eq!(manifest.name, "Lake");
+        assert_eq!(manifest.tags, ["Nature"]);
+        assert_eq!(
+            manifest
+                .layers
+                .iter()
+                .map(|layer| layer.name.as_str())
+                .collect::<Vec<_>>(),
+            ["background", "lake", "snow"]
+        );
+        assert_eq!(
+            manifest.layers[0].content,
+            LayerContent::Color("#0000ff".to_string())
+        );
+        let lake = &manifest.layers[1];
+        assert_eq!(
+            lake.content,
+            LayerContent::Image("materials/lake.png".to_string())
+        );
+        assert_eq!(
+            lake.effect_type,
+            Some(EffectType::Shader(ShaderType::Ripple))
+        );
+        assert_eq!(lake.params["speed"].as_float(), Some(2.0));
+        assert_eq!(
+            manifest.layers[2].effect_type,
+            Some(EffectType::Shader(ShaderType::Snow))
+        );
+        assert!(manifest.is_animated());
+        manifest.validate().unwrap();
+
+        assert_eq!(
+            conversion.files.keys().collect::<Vec<_>>(),
+            ["materials/lake.png"]
+        );
+        let report = conversion.unsupported.join("\n");
+        assert!(report.contains("pulse"));
+        assert!(report.contains("materials/sky.tex"));
+        assert!(report.contains("birds"));
+    }
+
+    #[test]
+    fn test_convert_other_kinds() {
+        let dir = tempdir().unwrap();
+        write(
+            dir.path(),
+            "project.json",
+            r#"{"title": "Page", "type": "web", "file": "index.html"}"#,
+        );
+        write(dir.path(), "index.html", "<html></html>");
+        write(dir.path(), "js/main.js", "");
+        let conversion = convert(dir.path()).unwrap();
+        assert!(matches!(
+            &conversion.manifest.layers[0].content,
+            LayerContent::Web(web) if web.web == "index.html"
+        ));
+        assert_eq!(
+            conversion.files.keys().collect::<Vec<_>>(),
+            ["index.html", "js/main.js"]
+        );
+
+        write(
+            dir.path(),
+            "project.json",
+            r#"{"title": "Clip", "type": "video", "file": "clip.mp4"}"#,
+        );
+        assert!(matches!(
+            convert(dir.path()),
+            Err(ImportError::Unsupported(_))
+        ));
+        write(
+            dir.path(),
+            "project.json",
+            r#"{"title": "Clip", "type": "Video", "file": "clip.mp4", "preview": "preview.gif"}"#,
+        );
+        write(dir.path(), "preview.gif", "gif");
+        let conversion = convert(dir.path()).unwrap();
+        assert_eq!(
+            conversion.manifest.layers[0].content,
+            LayerContent::Image("preview.gif".to_string())
+        );
+        assert!(!conversion.manifest.is_animated());
+
+        write(
+            dir.path(),
+            "project.json",
+            r#"{"title": "Packed", "type": "scene"}"#,
+        );
+        write(dir.path(), "scene.pkg", "");
+        let Err(ImportError::Unsupported(message)) = convert(dir.path()) else {
+            panic!("packed scenes cannot be converted");
+        };
+        assert!(message.contains("scene.pkg"));
+    }
+
+    #[test]
+    fn test_convert_outside_paths() {
+        let root = tempdir().unwrap();
+        let dir = root.path().join("project");
+        write(root.path(), "secret.png", "secret");
+        write(&dir, "index.html", "<html></html>");
+
+        for project in [
+            r#"{"type": "web", "file": "index.html", "preview": "../secret.png"}"#,
+            r#"{"type": "web", "file": "../secret.png"}"#,
+            r#"{"type": "scene", "file": "/etc/hostname"}"#,
+        ] {
+            write(&dir, "project.json", project);
+            let Err(ImportError::Unsupported(message)) = convert(&dir) else {
+                panic!("{project} names a file outside the project");
+            };
+            assert!(message.contains("not a path inside it"), "{message}");
+        }
+
+        // Models and materials are followed no further out either
+        write(
+            &dir,
+            "project.json",
+            r#"{"type": "scene", "file": "scene.json"}"#,
+        );
+        write(
+            &dir,
+            "scene.json",
+            r#"{"objects": [{"name": "lake", "image": "models/lake.json"}]}"#,
+        );
+        write(
+            &dir,
+            "models/lake.json",
+            r#"{"material": "materials/lake.json"}"#,
+        );
+        write(
+            &dir,
+            "materials/lake.json",
+            r#"{"passes": [{"textures": ["../../secret"]}]}"#,
+        );
+        assert!(matches!(convert(&dir), Err(ImportError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,106 @@
+use std::{fs, path::PathBuf, sync::OnceLock};
+
+use common::ipc::Peer;
+
+use crate::config::{IpcConfig, StopPolicy};
+
+static CONFIG: OnceLock<IpcConfig> = OnceLock::new();
+
+/// Set who may send which requests, before the first one is served
+pub fn init(config: IpcConfig) {
+    if CONFIG.set(config).is_err() {
+        tracing::warn!("A request was served before the IPC limits were configured");
+    }
+}
+
+fn config() -> &'static IpcConfig {
+    CONFIG.get_or_init(IpcConfig::default)
+}
+
+/// Whether `peer` may talk to the daemon at all
+///
+/// The socket is only accessible to the user, this catches connections
+/// made through a runtime dir shared with others before it was locked down.
+pub fn may_connect(peer: &Peer) -> bool {
+    // SAFETY: getuid takes no arguments and cannot fail
+    peer.uid == unsafe { libc::getuid() }
+}
+
+/// Check that `peer` may stop the daemon, the reason it may not otherwise
+pub fn check_stop(peer: &Peer) -> Result<(), String> {
+    let config = config();
+    let exe = executable(peer.pid);
+    // A daemon started with `--replace` takes over from this one
+    if exe.is_some() && exe == executable(std::process::id()) {
+        return Ok(());
+    }
+    let name = exe
+        .as_ref()
+        .and_then(|exe| exe.file_name())
+        .map(|name| name.to_string_lossy().into_owned());
+    if name
+        .as_ref()
+        .is_some_and(|name| config.allow.contains(name))
+    {
+        return Ok(());
+    }
+    let allowed = match config.stop {
+        StopPolicy::Anyone => true,
+        StopPolicy::Interactive => has_terminal(peer.pid),
+        StopPolicy::Nobody => false,
+    };
+    if allowed {
+        return Ok(());
+    }
+    let name = name.unwrap_or_else(|| format!("pid {}", peer.pid));
+    tracing::warn!("Refusing to stop on request of {name}");
+    Err(match config.stop {
+        StopPolicy::Interactive => format!("{name} is not running in a terminal"),
+        _ => format!("{name} is not allowed to stop the daemon"),
+    })
+}
+
+/// Path of the program the process runs, as it was when it started, so an
+/// upgraded daemon is still recognized
+fn executable(pid: u32) -> Option<PathBuf> {
+    let exe = fs::read_link(format!("/proc/{pid}/exe")).ok()?;
+    match exe.to_str().and_then(|exe| exe.strip_suffix(" (deleted)")) {
+        Some(replaced) => Some(PathBuf::from(replaced)),
+        None => Some(exe),
+    }
+}
+
+/// Whether the process has a controlling terminal, from `/proc/<pid>/stat`
+fn has_terminal(pid: u32) -> bool {
+    fs::read_to_string(format!("/proc/{pid}/stat")).is_ok_and(|stat| stat_has_terminal(&stat))
+}
+
+fn stat_has_terminal(stat: &str) -> bool {
+    // The command name in parentheses may contain spaces, the fields after
+    // it are state, ppid, pgrp, session and tty_nr
+    stat.rsplit_once(')')
+        .and_then(|(_, fields)| fields.split_whitespace().nth(4))
+        .and_then(|tty| tty.parse::<i32>().ok())
+        .is_some_and(|tty| tty != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_terminal() {
+        assert!(stat_has_terminal(
+            "4242 (bash) S 4200 4242 4242 34816 4242 4194560 1035 0 0 0"
+        ));
+        assert!(stat_has_terminal(
+            "4243 (my (odd) prog) R 4242 4243 4242 34816 4243 4194304 90 0 0 0"
+        ));
+        assert!(!stat_has_terminal(
+            "812 (wlrs) S 1 812 812 0 -1 4194560 5120 0 0 0"
+        ));
+        assert!(!stat_has_terminal("812 (wlrs) S 1"));
+        assert!(!stat_has_terminal(""));
+        assert!(!has_terminal(u32::MAX));
+    }
+}
@@ -1,46 +1,90 @@
-use std::path::Path;
+use std::collections::VecDeque;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
-use image::{AnimationDecoder, DynamicImage, ImageFormat};
+use image::{AnimationDecoder, DynamicImage, Frame, ImageDecoder, ImageFormat, RgbaImage};
 use wgpu::{
     AddressMode, Device, Extent3d, FilterMode, Queue, Sampler, SamplerDescriptor, Texture,
     TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
 };
 
-use super::image::ImageTexture;
+use super::{
+    color,
+    image::{texture_size_bytes, ImageTexture},
+};
+use crate::renderer::memory::Allocation;
+
+/// Number of GPU textures kept per animation: the frame on screen plus frames
+/// uploaded ahead of time so decoding hiccups don't stall playback
+pub const RING_SIZE: usize = 3;
+
+/// Represents an animated texture streamed from disk
+///
+/// Frames are decoded lazily on a worker thread and uploaded into a small ring
+/// of GPU textures, so memory use does not grow with the length of the animation.
+/// Clones share the decoder, the ring and the playback position, every output
+/// showing the animation shows the same frame and it is decoded only once.
+#[derive(Debug, Clone)]
+pub struct AnimatedTexture {
+    stream: Arc<Mutex<Stream>>,
+    /// Ring slot this handle showed last
+    shown: usize,
+    /// The base sampler configuration
+    sampler: Sampler,
+}
 
-/// Represents an animated texture with multiple frames
+/// The decoder, ring and playback state shared by the clones of a texture
 #[derive(Debug)]
-pub struct AnimatedTexture {
-    /// The individual frames of the animation
-    frames: Vec<FrameTexture>,
-    /// Current frame index
-    current_frame: usize,
-    /// Total number of frames
-    frame_count: usize,
-    /// Whether the animation should loop
-    looping: bool,
-    /// Last time the frame was updated
-    last_update: Instant,
+struct Stream {
+    /// GPU textures the frames are uploaded into
+    ring: Vec<FrameTexture>,
+    /// Ring slot currently on screen
+    current: usize,
+    /// Ring slots holding uploaded frames waiting to be shown, with their durations
+    ready: VecDeque<(usize, Duration)>,
+    /// How long the frame on screen should be displayed
+    current_duration: Duration,
+    /// Decoded frames coming from the decoder thread, `None` for static images
+    frames: Option<Receiver<RgbaFrame>>,
+    /// Shared with the decoder thread to stop or restart at the end of the file
+    looping: Arc<AtomicBool>,
+    /// Whether the decoder has stopped producing frames
+    finished: bool,
     /// Animation timing accumulator
     time_accumulator: Duration,
-    /// The base sampler configuration
-    sampler: Sampler,
+    /// When playback was last advanced, by any clone
+    advanced: Instant,
+    /// Queue used to upload frames as they arrive
+    queue: Queue,
 }
 
-/// Represents a single frame in an animated texture
+/// A single GPU texture in the ring
 #[derive(Debug)]
 struct FrameTexture {
-    /// The texture for this frame
+    /// The texture frames are written into
     texture: Texture,
     /// The texture view for rendering
     view: TextureView,
-    /// Duration to display this frame
+    /// Counts the texture against the GPU memory budget
+    _allocation: Arc<Allocation>,
+}
+
+/// A decoded frame ready to be uploaded
+struct RgbaFrame {
+    buffer: RgbaImage,
     duration: Duration,
 }
 
 impl AnimatedTexture {
     /// Load an animated texture from a path
+    ///
+    /// Only the first frame is decoded before returning, the rest stream in
+    /// while the animation plays.
     pub fn from_path(
         device: &Device,
         queue: &Queue,
@@ -49,173 +93,67 @@ impl AnimatedTexture {
         looping: bool,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let path = path.as_ref();
-        println!("Loading animation from path: {}", path.display());
         let format = ImageFormat::from_path(path)?;
-        println!("Detected image format: {format:?}");
-        let file = std::fs::File::open(path)?;
-        let reader = std::io::BufReader::new(file);
-
-        // Create decoder based on format
-        let frames = match format {
-            ImageFormat::WebP => {
-                let decoder = image::codecs::webp::WebPDecoder::new(reader)?;
-                let is_animated = decoder.has_animation();
-                println!(
-                    "WebP file at {} is_animated: {}",
-                    path.display(),
-                    is_animated
-                );
-
-                if !is_animated {
-                    // If it's not animated, create a single frame
-                    println!("Loading as static image instead of animation");
-                    let img =
-                        image::load(std::io::BufReader::new(std::fs::File::open(path)?), format)?;
-                    return Ok(Self::from_single_image(device, queue, &img, label, looping));
-                }
+        tracing::debug!("Loading animation {} as {format:?}", path.display());
 
-                println!("Attempting to collect animation frames...");
-                // Extract frames from animated WebP
-                let frames_result = decoder.into_frames().collect::<Result<Vec<_>, _>>();
-
-                match &frames_result {
-                    Ok(frames) => {
-                        println!("Successfully collected {} animation frames", frames.len())
-                    }
-                    Err(e) => println!("Error collecting animation frames: {e}"),
-                }
-
-                frames_result?
-            }
-            ImageFormat::Gif => {
-                // Process GIF animation
-                let decoder = image::codecs::gif::GifDecoder::new(reader)?;
-                decoder.into_frames().collect::<Result<Vec<_>, _>>()?
-            }
-            _ => {
-                // For other formats, just load as a single image
-                let img = image::load(std::io::BufReader::new(std::fs::File::open(path)?), format)?;
-                return Ok(Self::from_single_image(device, queue, &img, label, looping));
-            }
-        };
-
-        let frame_count = frames.len();
-        println!("Loaded {} frames from {}", frame_count, path.display());
-
-        if frame_count == 0 {
-            println!("WARNING: No frames loaded from WebP file! Using fallback single image");
-            let img = image::load(std::io::BufReader::new(std::fs::File::open(path)?), format)?;
+        if !probe_animation(path, format)? {
+            tracing::debug!(
+                "{} is not animated, loading as static image",
+                path.display()
+            );
+            let img = color::load_image(path)?;
             return Ok(Self::from_single_image(device, queue, &img, label, looping));
         }
 
-        Self::from_frames(device, queue, frames, label, looping)
-    }
-
-    /// Create an animated texture from animation frames
-    fn from_frames(
-        device: &Device,
-        queue: &Queue,
-        frames: Vec<image::Frame>,
-        label: &str,
-        looping: bool,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        let frame_count = frames.len();
-
-        // Create shared sampler for all frames
-        let sampler = device.create_sampler(&SamplerDescriptor {
-            address_mode_u: AddressMode::ClampToEdge,
-            address_mode_v: AddressMode::ClampToEdge,
-            address_mode_w: AddressMode::ClampToEdge,
-            mag_filter: FilterMode::Nearest,
-            min_filter: FilterMode::Nearest,
-            mipmap_filter: FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        // Process each frame
-        let mut frame_textures = Vec::with_capacity(frame_count);
-        for (i, frame) in frames.into_iter().enumerate() {
-            let frame_label = format!("{label}_{i}");
-            let frame_buffer = frame.buffer();
-            let (width, height) = frame_buffer.dimensions();
-
-            // Determine frame duration (use a reasonable default if values are extreme)
-            let frame_delay = frame.delay().numer_denom_ms();
-            println!(
-                "Raw frame delay values: {}/{}",
-                frame_delay.0, frame_delay.1
-            );
-
-            // Check for potential issues with frame delay values
-            let duration = if frame_delay.0 == 0 || frame_delay.1 == 0 {
-                println!("WARNING: Invalid frame delay! Using default 100ms");
-                Duration::from_millis(100)
-            } else if (frame_delay.0 as u64 * 1000) / frame_delay.1 as u64 > 10000 {
-                // Cap extremely long durations to 500ms
-                println!("WARNING: Very long frame duration detected! Capping to 500ms");
-                Duration::from_millis(500)
-            } else {
-                Duration::from_millis((frame_delay.0 as u64 * 1000) / frame_delay.1 as u64)
-            };
-
-            // Debug: Print frame duration
-            println!(
-                "Frame {} duration: {:?} ({}/{}ms)",
-                i, duration, frame_delay.0, frame_delay.1
-            );
-
-            // Create texture for this frame
-            let size = Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            };
+        let looping = Arc::new(AtomicBool::new(looping));
+        let (sender, frames) = mpsc::sync_channel(RING_SIZE);
+        {
+            let path = path.to_path_buf();
+            let looping = looping.clone();
+            thread::Builder::new()
+                .name(format!("wlrs-decode-{label}"))
+                .spawn(move || decode_frames(path, format, sender, looping))?;
+        }
 
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some(&frame_label),
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: TextureFormat::Rgba8Unorm,
-                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
-
-            // Write frame data to texture
-            queue.write_texture(
-                wgpu::TexelCopyTextureInfo {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                frame_buffer,
-                wgpu::TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * width),
-                    rows_per_image: Some(height),
-                },
-                size,
+        // The first frame determines the size of the ring
+        let Ok(first) = frames.recv() else {
+            tracing::warn!(
+                "No frames decoded from {}, using fallback single image",
+                path.display()
             );
+            let img = color::load_image(path)?;
+            return Ok(Self::from_single_image(
+                device,
+                queue,
+                &img,
+                label,
+                looping.load(Ordering::Relaxed),
+            ));
+        };
 
-            let view = texture.create_view(&TextureViewDescriptor::default());
-
-            frame_textures.push(FrameTexture {
-                texture,
-                view,
-                duration,
-            });
-        }
-
-        Ok(Self {
-            frames: frame_textures,
-            current_frame: 0,
-            frame_count,
+        let (width, height) = first.buffer.dimensions();
+        let ring = (0..RING_SIZE)
+            .map(|i| FrameTexture::new(device, width, height, &format!("{label}_{i}")))
+            .collect();
+
+        let stream = Stream {
+            ring,
+            current: 0,
+            ready: VecDeque::with_capacity(RING_SIZE),
+            current_duration: first.duration,
+            frames: Some(frames),
             looping,
-            last_update: Instant::now(),
+            finished: false,
             time_accumulator: Duration::ZERO,
-            sampler,
+            advanced: Instant::now(),
+            queue: queue.clone(),
+        };
+        stream.upload(0, &first.buffer);
+
+        Ok(Self {
+            stream: Arc::new(Mutex::new(stream)),
+            shown: 0,
+            sampler: device.create_sampler(&frame_sampler()),
         })
     }
 
@@ -234,23 +172,32 @@ impl AnimatedTexture {
         let frame = FrameTexture {
             texture: image_texture.texture,
             view: image_texture.view,
-            duration: Duration::MAX, // Static image doesn't change
+            _allocation: image_texture.allocation,
         };
 
-        Self {
-            frames: vec![frame],
-            current_frame: 0,
-            frame_count: 1,
-            looping,
-            last_update: Instant::now(),
+        let stream = Stream {
+            ring: vec![frame],
+            current: 0,
+            ready: VecDeque::new(),
+            current_duration: Duration::MAX, // Static image doesn't change
+            frames: None,
+            looping: Arc::new(AtomicBool::new(looping)),
+            finished: true,
             time_accumulator: Duration::ZERO,
+            advanced: Instant::now(),
+            queue: queue.clone(),
+        };
+
+        Self {
+            stream: Arc::new(Mutex::new(stream)),
+            shown: 0,
             sampler: image_texture.sampler,
         }
     }
 
-    /// Get the current frame's texture view
-    pub fn view(&self) -> &TextureView {
-        &self.frames[self.current_frame].view
+    /// Get the texture view of the frame this handle shows
+    pub fn view(&self) -> TextureView {
+        self.stream.lock().unwrap().ring[self.shown].view.clone()
     }
 
     /// Get the sampler
@@ -259,85 +206,344 @@ impl AnimatedTexture {
     }
 
     /// Update the animation state based on elapsed time
-    /// Returns true if the frame changed
+    /// Returns true if the frame this handle shows changed
+    ///
+    /// Every clone calls this once per frame of its output, so playback only
+    /// advances by the time since any clone last advanced it.
     pub fn update(&mut self, dt: Duration) -> bool {
-        // Early return if we only have one frame
-        if self.frame_count <= 1 {
-            println!(
-                "No animation: only {} frame, {} total frames in buffer",
-                self.frame_count,
-                self.frames.len()
-            );
-            return false;
-        }
+        let mut stream = self.stream.lock().unwrap();
+        stream.advance(dt);
+        let changed = stream.current != self.shown;
+        self.shown = stream.current;
+        changed
+    }
 
-        self.time_accumulator += dt;
-        let old_frame = self.current_frame;
+    /// Check if this is an animated texture (has more than one frame)
+    pub fn is_animated(&self) -> bool {
+        self.stream.lock().unwrap().frames.is_some()
+    }
 
-        let frame_duration = self.frames[self.current_frame].duration;
-        println!(
-            "Animation update: frame {}/{}, time_acc: {:?}, frame_duration: {:?}",
-            self.current_frame, self.frame_count, self.time_accumulator, frame_duration
-        );
+    /// Check if the animation has finished playing (only relevant when not looping)
+    pub fn is_finished(&self) -> bool {
+        let stream = self.stream.lock().unwrap();
+        stream.finished && stream.ready.is_empty()
+    }
 
-        // DEBUGGING: Force frame advancement every second regardless of frame duration
-        let force_advance = self.time_accumulator >= Duration::from_millis(1000);
+    /// Set whether the animation should loop, for every clone
+    ///
+    /// Has no effect once the decoder has already reached the end of a non-looping animation.
+    pub fn set_looping(&self, looping: bool) {
+        self.stream
+            .lock()
+            .unwrap()
+            .looping
+            .store(looping, Ordering::Relaxed);
+    }
+
+    /// GPU memory held by the ring
+    pub fn size_bytes(&self) -> u64 {
+        self.stream
+            .lock()
+            .unwrap()
+            .ring
+            .iter()
+            .map(|frame| texture_size_bytes(&frame.texture))
+            .sum()
+    }
 
-        if self.time_accumulator >= frame_duration || force_advance {
-            // Consume the used time and advance frame
-            if force_advance {
-                println!("  FORCED FRAME ADVANCEMENT (debug mode)");
+    /// Whether nothing but this handle keeps the animation alive
+    pub fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.stream) == 1
+    }
+}
+
+impl Stream {
+    /// Advance playback by `dt`, or less if another clone advanced it since
+    fn advance(&mut self, dt: Duration) {
+        self.fill_ring();
+
+        let now = Instant::now();
+        let dt = dt.min(now.duration_since(self.advanced));
+        self.advanced = now;
+
+        if self.frames.is_none() {
+            return;
+        }
+
+        self.time_accumulator += dt;
+        if self.time_accumulator < self.current_duration {
+            return;
+        }
+
+        let Some((slot, duration)) = self.ready.pop_front() else {
+            // Decoder is behind, keep showing the current frame
+            if self.finished {
                 self.time_accumulator = Duration::ZERO;
-            } else {
-                self.time_accumulator -= frame_duration;
             }
+            return;
+        };
 
-            self.current_frame = (self.current_frame + 1) % self.frame_count;
-            println!("  Advancing to frame {}", self.current_frame);
+        self.time_accumulator -= self.current_duration;
+        // Don't try to catch up after a long stall, just resume from here
+        if self.time_accumulator >= duration {
+            self.time_accumulator = Duration::ZERO;
+        }
+        self.current = slot;
+        self.current_duration = duration;
+    }
 
-            // If we reached the end and not looping, stay on the last frame
-            if !self.looping && self.current_frame == 0 {
-                self.current_frame = self.frame_count - 1;
-                self.time_accumulator = Duration::ZERO;
-                println!(
-                    "  Not looping, staying on last frame {}",
-                    self.current_frame
+    /// Upload decoded frames into free ring slots without blocking
+    fn fill_ring(&mut self) {
+        let Some(frames) = &self.frames else {
+            return;
+        };
+
+        while self.ready.len() < self.ring.len() - 1 {
+            let frame = match frames.try_recv() {
+                Ok(frame) => frame,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                }
+            };
+
+            let expected = (self.ring[0].texture.width(), self.ring[0].texture.height());
+            if frame.buffer.dimensions() != expected {
+                tracing::warn!(
+                    "Skipping animation frame of size {:?}, expected {expected:?}",
+                    frame.buffer.dimensions()
                 );
+                continue;
             }
-        } else {
-            println!("  Not enough time accumulated to advance frame");
+
+            let slot = self
+                .ready
+                .back()
+                .map_or(self.current, |(slot, _)| *slot)
+                .wrapping_add(1)
+                % self.ring.len();
+            self.upload(slot, &frame.buffer);
+            self.ready.push_back((slot, frame.duration));
         }
+    }
 
-        // Return true if frame changed
-        let changed = old_frame != self.current_frame;
-        println!("  Frame changed: {changed}");
-        changed
+    /// Write a decoded frame into a ring slot
+    fn upload(&self, slot: usize, buffer: &RgbaImage) {
+        let (width, height) = buffer.dimensions();
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.ring[slot].texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            buffer,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
     }
+}
+
+impl FrameTexture {
+    fn new(device: &Device, width: u32, height: u32, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let _allocation = Arc::new(Allocation::new(texture_size_bytes(&texture)));
 
-    /// Reset the animation to the first frame
-    pub fn reset(&mut self) {
-        self.current_frame = 0;
-        self.time_accumulator = Duration::ZERO;
-        self.last_update = Instant::now();
+        Self {
+            texture,
+            view,
+            _allocation,
+        }
     }
+}
 
-    /// Get the number of frames
-    pub fn frame_count(&self) -> usize {
-        self.frame_count
+fn frame_sampler() -> SamplerDescriptor<'static> {
+    SamplerDescriptor {
+        address_mode_u: AddressMode::ClampToEdge,
+        address_mode_v: AddressMode::ClampToEdge,
+        address_mode_w: AddressMode::ClampToEdge,
+        mag_filter: FilterMode::Nearest,
+        min_filter: FilterMode::Nearest,
+        mipmap_filter: FilterMode::Nearest,
+        ..Default::default()
     }
+}
 
-    /// Check if this is an animated texture (has more than one frame)
-    pub fn is_animated(&self) -> bool {
-        self.frame_count > 1
+/// Decoder thread body, sends frames until the receiver is dropped
+///
+/// The bounded channel keeps the decoder at most a few frames ahead of playback.
+/// When looping, the file is reopened and decoded again from the start.
+fn decode_frames(
+    path: PathBuf,
+    format: ImageFormat,
+    sender: SyncSender<RgbaFrame>,
+    looping: Arc<AtomicBool>,
+) {
+    loop {
+        let frames = match open_frames(&path, format) {
+            Ok(frames) => frames,
+            Err(e) => {
+                tracing::error!("Failed to decode animation {}: {e}", path.display());
+                return;
+            }
+        };
+
+        let mut count = 0;
+        for frame in frames {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) => {
+                    tracing::error!("Failed to decode frame of {}: {e}", path.display());
+                    return;
+                }
+            };
+            count += 1;
+
+            let duration = frame_duration(&frame);
+            let frame = RgbaFrame {
+                buffer: frame.into_buffer(),
+                duration,
+            };
+            if sender.send(frame).is_err() {
+                return; // Texture dropped
+            }
+        }
+
+        // A single frame doesn't need to be decoded over and over
+        if count <= 1 || !looping.load(Ordering::Relaxed) {
+            return;
+        }
     }
+}
 
-    /// Check if the animation has finished playing (only relevant when not looping)
-    pub fn is_finished(&self) -> bool {
-        !self.looping && self.current_frame == self.frame_count - 1
+/// Check whether a file holds an animation that [`AnimatedTexture`] can play
+///
+/// Used to route static images through the regular texture path. Unreadable
+/// files report `false` so the static loader produces the error.
+pub fn is_animated(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    ImageFormat::from_path(path)
+        .map_err(Box::<dyn std::error::Error>::from)
+        .and_then(|format| probe_animation(path, format))
+        .unwrap_or(false)
+}
+
+/// Inspect the container header to see if a file of a given format is animated
+fn probe_animation(path: &Path, format: ImageFormat) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(match format {
+        ImageFormat::WebP => {
+            let reader = BufReader::new(std::fs::File::open(path)?);
+            image::codecs::webp::WebPDecoder::new(reader)?.has_animation()
+        }
+        ImageFormat::Png => {
+            let reader = BufReader::new(std::fs::File::open(path)?);
+            image::codecs::png::PngDecoder::new(reader)?.is_apng()?
+        }
+        ImageFormat::Gif => true,
+        ImageFormat::Avif => {
+            if is_avif_sequence(path)? {
+                tracing::warn!(
+                    "Animated AVIF is not supported yet, showing the first frame of {}",
+                    path.display()
+                );
+            }
+            false
+        }
+        _ => false,
+    })
+}
+
+/// Check the ISO-BMFF `ftyp` box for the `avis` brand used by AVIF image sequences
+fn is_avif_sequence(path: &Path) -> std::io::Result<bool> {
+    let mut header = [0u8; 64];
+    let len = std::fs::File::open(path)?.read(&mut header)?;
+    let header = &header[..len];
+
+    if header.len() < 16 || &header[4..8] != b"ftyp" {
+        return Ok(false);
     }
+    let box_size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let brands_end = box_size.min(header.len());
+
+    // Major brand, then compatible brands after the minor version
+    let major = &header[8..12];
+    let compatible = header[16..brands_end].chunks_exact(4);
+    Ok(major == b"avis" || compatible.into_iter().any(|brand| brand == b"avis"))
+}
 
-    /// Set whether the animation should loop
-    pub fn set_looping(&mut self, looping: bool) {
-        self.looping = looping;
+/// Open a lazily decoding frame iterator, converting frames into sRGB
+///
+/// The decoders composite each frame onto the full canvas, applying the
+/// container's disposal and blend operations, so every frame can be uploaded as is.
+fn open_frames(
+    path: &Path,
+    format: ImageFormat,
+) -> Result<Box<dyn Iterator<Item = image::ImageResult<Frame>>>, Box<dyn std::error::Error>> {
+    let reader = BufReader::new(std::fs::File::open(path)?);
+
+    Ok(match format {
+        ImageFormat::WebP => {
+            let mut decoder = image::codecs::webp::WebPDecoder::new(reader)?;
+            let icc = decoder.icc_profile()?;
+            let frames = decoder.into_frames();
+            match icc {
+                Some(icc) => Box::new(frames.map(move |frame| Ok(frame_to_srgb(frame?, &icc)))),
+                None => Box::new(frames),
+            }
+        }
+        ImageFormat::Png => {
+            let mut decoder = image::codecs::png::PngDecoder::new(reader)?;
+            let icc = decoder.icc_profile()?;
+            let frames = decoder.apng()?.into_frames();
+            match icc {
+                Some(icc) => Box::new(frames.map(move |frame| Ok(frame_to_srgb(frame?, &icc)))),
+                None => Box::new(frames),
+            }
+        }
+        ImageFormat::Gif => Box::new(image::codecs::gif::GifDecoder::new(reader)?.into_frames()),
+        _ => return Err(format!("{format:?} is not an animated format").into()),
+    })
+}
+
+/// Determine how long a frame is shown, using a reasonable default if values are extreme
+fn frame_duration(frame: &Frame) -> Duration {
+    let (numer, denom) = frame.delay().numer_denom_ms();
+    if numer == 0 || denom == 0 {
+        Duration::from_millis(100)
+    } else if numer as u64 / denom as u64 > 10000 {
+        // Cap extremely long durations
+        Duration::from_millis(500)
+    } else {
+        Duration::from_micros(numer as u64 * 1000 / denom as u64)
     }
 }
+
+/// Convert a decoded animation frame into sRGB using the container's ICC profile
+fn frame_to_srgb(frame: Frame, icc: &[u8]) -> Frame {
+    let (left, top, delay) = (frame.left(), frame.top(), frame.delay());
+    let image = color::convert_to_srgb(DynamicImage::ImageRgba8(frame.into_buffer()), icc);
+    Frame::from_parts(image.to_rgba8(), left, top, delay)
+}
@@ -0,0 +1,103 @@
+use std::path::Path;
+
+use common::{
+    baked::{baked_path, BakedTexture, BlockFormat},
+    manifest::{Sampling, ScaleMode},
+};
+use wgpu::{Device, Features, Queue};
+
+use super::image::ImageTexture;
+use crate::renderer::{memory, prescale};
+
+/// Upload the texture `wlrs bake` made of an image, None to decode the
+/// image instead
+///
+/// Baked textures carry a full mip chain, so they are skipped for layers
+/// sampled without mipmaps and on GPUs without BC or ASTC compression. Levels
+/// larger than what the outputs show are left out instead of shrinking the
+/// image.
+pub fn load(
+    device: &Device,
+    queue: &Queue,
+    path: &Path,
+    label: &str,
+    sampling: Sampling,
+    scale_mode: &ScaleMode,
+    target: Option<(u32, u32)>,
+) -> Option<ImageTexture> {
+    if !sampling.uses_mipmaps() {
+        return None;
+    }
+    let format = block_format(device)?;
+    let baked_path = baked_path(path, format);
+    if !baked_path.exists() {
+        return None;
+    }
+    let baked = match BakedTexture::read(&baked_path, path) {
+        Ok(baked) => baked,
+        Err(e) => {
+            tracing::warn!("Ignoring {}: {e}", baked_path.display());
+            return None;
+        }
+    };
+
+    let first_level = first_level(&baked, scale_mode, target);
+    let bytes = baked.levels[first_level..]
+        .iter()
+        .map(|level| level.len() as u64)
+        .sum();
+    if !memory::fits(bytes) {
+        tracing::info!(
+            "{} does not fit in the GPU memory budget, decoding the image",
+            baked_path.display()
+        );
+        return None;
+    }
+
+    tracing::debug!(
+        "Using {} from mip level {first_level}",
+        baked_path.display()
+    );
+    Some(ImageTexture::from_baked(
+        device,
+        queue,
+        &baked,
+        first_level,
+        label,
+        sampling,
+    ))
+}
+
+/// Block format the device samples, BC7 where it has both
+fn block_format(device: &Device) -> Option<BlockFormat> {
+    let features = device.features();
+    if features.contains(Features::TEXTURE_COMPRESSION_BC) {
+        Some(BlockFormat::Bc7)
+    } else if features.contains(Features::TEXTURE_COMPRESSION_ASTC) {
+        Some(BlockFormat::Astc)
+    } else {
+        None
+    }
+}
+
+/// Smallest mip level still as large as what `target` shows of the image
+///
+/// The level becomes the texture's largest, so its size must stay a multiple
+/// of the block size.
+fn first_level(baked: &BakedTexture, scale_mode: &ScaleMode, target: Option<(u32, u32)>) -> usize {
+    let Some((width, height)) =
+        prescale::target_size((baked.width, baked.height), scale_mode, target)
+    else {
+        return 0;
+    };
+    let mut level = 0;
+    while level + 1 < baked.levels.len() {
+        let (next_width, next_height) = baked.level_size(level + 1);
+        if next_width < width || next_height < height || next_width % 4 != 0 || next_height % 4 != 0
+        {
+            break;
+        }
+        level += 1;
+    }
+    level
+}
@@ -0,0 +1,133 @@
+use std::path::Path;
+
+use image::{DynamicImage, ImageDecoder, ImageReader, ImageResult};
+use qcms::{DataType, Intent, Profile, Transform};
+
+use super::decoder::{self, LoadError};
+
+/// Decode an image from disk, converting it into sRGB if it carries an
+/// embedded ICC profile for a different color space.
+///
+/// Textures are uploaded as `Rgba8UnormSrgb`, so every image handed to the
+/// GPU must already be sRGB-encoded for sampling to yield correct linear values.
+/// Formats the `image` crate can't read are handled by the loaders in [`decoder`].
+pub fn load_image(path: impl AsRef<Path>) -> Result<DynamicImage, LoadError> {
+    let path = path.as_ref();
+    if let Some(loader) = decoder::loader_for(path) {
+        tracing::debug!("Decoding {} with {}", path.display(), loader.name());
+        return loader.load(path);
+    }
+
+    decode_image(path).map_err(|e| LoadError::from_image(path, e))
+}
+
+fn decode_image(path: &Path) -> ImageResult<DynamicImage> {
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    let icc = decoder.icc_profile()?;
+    let image = DynamicImage::from_decoder(decoder)?;
+
+    Ok(match icc {
+        Some(icc) => convert_to_srgb(image, &icc),
+        None => image,
+    })
+}
+
+/// Convert an image described by the given ICC profile into sRGB
+///
+/// Images with an unparsable or already-sRGB profile are returned unchanged.
+pub fn convert_to_srgb(image: DynamicImage, icc: &[u8]) -> DynamicImage {
+    let Some(input) = Profile::new_from_slice(icc, false) else {
+        tracing::warn!("Ignoring unparsable embedded ICC profile");
+        return image;
+    };
+    if input.is_sRGB() {
+        return image;
+    }
+
+    let output = Profile::new_sRGB();
+    let Some(transform) = Transform::new(&input, &output, DataType::RGBA8, Intent::default())
+    else {
+        tracing::warn!("Unsupported embedded ICC profile, treating image as sRGB");
+        return image;
+    };
+
+    let mut rgba = image.to_rgba8();
+    transform.apply(&mut rgba);
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Convert an sRGB-encoded channel value (0.0 to 1.0) to linear light
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert a linear-light channel value (0.0 to 1.0) to sRGB encoding
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Approximate the sRGB-encoded color of a black body at `kelvin`, scaled so
+/// 6500K is white and the brightest channel stays at 1.0
+///
+/// Follows Tanner Helland's fit of the black body curve, good from 1000K to 40000K.
+pub fn temperature_to_rgb(kelvin: u32) -> [f32; 3] {
+    let unscaled = |kelvin: u32| {
+        let t = kelvin.clamp(1000, 40000) as f32 / 100.0;
+        let red = if t <= 66.0 {
+            255.0
+        } else {
+            329.698_73 * (t - 60.0).powf(-0.133_204_76)
+        };
+        let green = if t <= 66.0 {
+            99.470_8 * t.ln() - 161.119_57
+        } else {
+            288.122_16 * (t - 60.0).powf(-0.075_514_85)
+        };
+        let blue = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.517_73 * (t - 10.0).ln() - 305.044_8
+        };
+        [red, green, blue].map(|channel| channel.clamp(0.0, 255.0) / 255.0)
+    };
+
+    let white = unscaled(6500);
+    let color = unscaled(kelvin);
+    let rgb = [0, 1, 2].map(|i| color[i] / white[i].max(f32::EPSILON));
+    let max = rgb.iter().cloned().fold(f32::EPSILON, f32::max);
+    rgb.map(|channel| channel / max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temperature_to_rgb() {
+        assert_eq!(temperature_to_rgb(6500), [1.0, 1.0, 1.0]);
+
+        let [red, green, blue] = temperature_to_rgb(2700);
+        assert_eq!(red, 1.0);
+        assert!(blue < green && green < 1.0);
+
+        let [red, green, blue] = temperature_to_rgb(10000);
+        assert_eq!(blue, 1.0);
+        assert!(red < green && green < 1.0);
+
+        // Outside of the fit the ends are used
+        assert_eq!(temperature_to_rgb(0), temperature_to_rgb(1000));
+        assert_eq!(temperature_to_rgb(100_000), temperature_to_rgb(40000));
+    }
+}
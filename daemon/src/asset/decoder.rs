@@ -0,0 +1,248 @@
+use std::fmt;
+use std::path::Path;
+
+use image::{DynamicImage, ImageError, ImageFormat};
+
+/// Decoder for an image format the `image` crate can't read
+///
+/// Loaders are compiled in through cargo features and looked up by file extension.
+/// They must return sRGB-encoded pixels, converting from any embedded ICC profile.
+pub trait ImageLoader: Sync {
+    /// Human readable name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Lowercase file extensions handled by this loader
+    fn extensions(&self) -> &'static [&'static str];
+
+    fn load(&self, path: &Path) -> Result<DynamicImage, LoadError>;
+}
+
+/// Loaders enabled in this build
+static LOADERS: &[&dyn ImageLoader] = &[
+    #[cfg(feature = "heif")]
+    &heif::HeifLoader,
+    #[cfg(feature = "jxl")]
+    &jxl::JxlLoader,
+];
+
+/// Formats that are only available with an optional feature, and that feature's name
+const OPTIONAL_FORMATS: &[(&str, &str)] = &[
+    ("avif", "avif"),
+    ("heic", "heif"),
+    ("heif", "heif"),
+    ("jxl", "jxl"),
+];
+
+/// Errors that can occur while decoding an image file
+#[derive(Debug)]
+pub enum LoadError {
+    /// No decoder in this build handles the file's format
+    Unsupported {
+        path: String,
+        extension: String,
+        /// Cargo feature that would add support, if one exists
+        feature: Option<&'static str>,
+    },
+    /// The `image` crate failed to read or decode the file
+    Image { path: String, source: ImageError },
+    /// A registered loader failed to decode the file
+    Decode { path: String, message: String },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Unsupported {
+                path,
+                extension,
+                feature,
+            } => {
+                write!(f, "Unsupported image format '{extension}' for {path}")?;
+                if let Some(feature) = feature {
+                    write!(f, " (rebuild wlrs-daemon with the '{feature}' feature)")?;
+                }
+                write!(
+                    f,
+                    "; supported formats: {}",
+                    supported_extensions().join(", ")
+                )
+            }
+            LoadError::Image { path, source } => write!(f, "Failed to load {path}: {source}"),
+            LoadError::Decode { path, message } => write!(f, "Failed to decode {path}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Image { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl LoadError {
+    /// Wrap an `image` crate error, turning unknown formats into [`LoadError::Unsupported`]
+    pub fn from_image(path: &Path, error: ImageError) -> Self {
+        match error {
+            ImageError::Unsupported(_) => Self::unsupported(path),
+            source => LoadError::Image {
+                path: path.display().to_string(),
+                source,
+            },
+        }
+    }
+
+    fn unsupported(path: &Path) -> Self {
+        let extension = extension(path).unwrap_or_default();
+        let feature = OPTIONAL_FORMATS
+            .iter()
+            .find(|(ext, _)| *ext == extension)
+            .map(|(_, feature)| *feature);
+
+        LoadError::Unsupported {
+            path: path.display().to_string(),
+            extension,
+            feature,
+        }
+    }
+}
+
+/// Find a registered loader for a file based on its extension
+pub fn loader_for(path: &Path) -> Option<&'static dyn ImageLoader> {
+    let extension = extension(path)?;
+    LOADERS
+        .iter()
+        .copied()
+        .find(|loader| loader.extensions().contains(&extension.as_str()))
+}
+
+/// All file extensions this build can decode
+pub fn supported_extensions() -> Vec<&'static str> {
+    let mut extensions: Vec<&'static str> = ImageFormat::all()
+        .filter(|format| format.reading_enabled())
+        .flat_map(|format| format.extensions_str().iter().copied())
+        .chain(
+            LOADERS
+                .iter()
+                .flat_map(|loader| loader.extensions().iter().copied()),
+        )
+        .collect();
+    extensions.sort_unstable();
+    extensions.dedup();
+    extensions
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+#[cfg(feature = "heif")]
+mod heif {
+    use std::path::Path;
+
+    use image::{DynamicImage, RgbaImage};
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    use super::{ImageLoader, LoadError};
+    use crate::asset::color;
+
+    /// HEIF/HEIC decoding through the system libheif
+    pub struct HeifLoader;
+
+    impl ImageLoader for HeifLoader {
+        fn name(&self) -> &'static str {
+            "libheif"
+        }
+
+        fn extensions(&self) -> &'static [&'static str] {
+            &["heic", "heif"]
+        }
+
+        fn load(&self, path: &Path) -> Result<DynamicImage, LoadError> {
+            let error = |e: libheif_rs::HeifError| LoadError::Decode {
+                path: path.display().to_string(),
+                message: e.to_string(),
+            };
+
+            let context = HeifContext::read_from_file(&path.to_string_lossy()).map_err(error)?;
+            let handle = context.primary_image_handle().map_err(error)?;
+            let decoded = LibHeif::new()
+                .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)
+                .map_err(error)?;
+
+            let planes = decoded.planes();
+            let Some(plane) = planes.interleaved else {
+                return Err(LoadError::Decode {
+                    path: path.display().to_string(),
+                    message: "decoder returned no interleaved RGBA plane".to_string(),
+                });
+            };
+
+            // Rows may be padded, copy them into a tightly packed buffer
+            let row_len = plane.width as usize * 4;
+            let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+            for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+                pixels.extend_from_slice(&row[..row_len]);
+            }
+            let image = RgbaImage::from_raw(plane.width, plane.height, pixels)
+                .map(DynamicImage::ImageRgba8)
+                .ok_or_else(|| LoadError::Decode {
+                    path: path.display().to_string(),
+                    message: "decoded plane is smaller than its dimensions".to_string(),
+                })?;
+
+            Ok(match handle.color_profile_raw() {
+                Some(profile) => color::convert_to_srgb(image, &profile.data),
+                None => image,
+            })
+        }
+    }
+}
+
+#[cfg(feature = "jxl")]
+mod jxl {
+    use std::{fs::File, io::BufReader, path::Path};
+
+    use image::{DynamicImage, ImageDecoder};
+    use jxl_oxide::integration::JxlDecoder;
+
+    use super::{ImageLoader, LoadError};
+    use crate::asset::color;
+
+    /// JPEG XL decoding through jxl-oxide
+    pub struct JxlLoader;
+
+    impl ImageLoader for JxlLoader {
+        fn name(&self) -> &'static str {
+            "jxl-oxide"
+        }
+
+        fn extensions(&self) -> &'static [&'static str] {
+            &["jxl"]
+        }
+
+        fn load(&self, path: &Path) -> Result<DynamicImage, LoadError> {
+            let file = File::open(path).map_err(|e| LoadError::from_image(path, e.into()))?;
+            let mut decoder =
+                JxlDecoder::new(BufReader::new(file)).map_err(|e| LoadError::Decode {
+                    path: path.display().to_string(),
+                    message: e.to_string(),
+                })?;
+
+            let icc = decoder
+                .icc_profile()
+                .map_err(|e| LoadError::from_image(path, e))?;
+            let image =
+                DynamicImage::from_decoder(decoder).map_err(|e| LoadError::from_image(path, e))?;
+
+            Ok(match icc {
+                Some(icc) => color::convert_to_srgb(image, &icc),
+                None => image,
+            })
+        }
+    }
+}
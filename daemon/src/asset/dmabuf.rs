@@ -0,0 +1,333 @@
+use std::{
+    ffi::CStr,
+    os::fd::{FromRawFd, OwnedFd},
+};
+
+use ash::vk;
+use wgpu::{
+    hal::{self, api::Vulkan},
+    Adapter, Device, DeviceDescriptor, Extent3d, Queue, Texture, TextureDimension, TextureFormat,
+    TextureUsages, TextureView,
+};
+
+/// Device extensions needed to hand images to other processes as dmabufs
+const EXTENSIONS: [&CStr; 2] = [
+    ash::khr::external_memory_fd::NAME,
+    ash::ext::external_memory_dma_buf::NAME,
+];
+
+/// DRM format modifier of images laid out row by row, see `drm_fourcc.h`
+pub const LINEAR_MODIFIER: u64 = 0;
+
+/// DRM formats images can be allocated in, with 8 bits per channel
+pub const ARGB8888: u32 = fourcc(b"AR24");
+pub const XRGB8888: u32 = fourcc(b"XR24");
+pub const ABGR8888: u32 = fourcc(b"AB24");
+pub const XBGR8888: u32 = fourcc(b"XB24");
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+/// Whether a DRM format leaves its alpha channel undefined
+pub fn is_opaque(format: u32) -> bool {
+    matches!(format, XRGB8888 | XBGR8888)
+}
+
+/// Vulkan and wgpu format sampling a DRM format, colors being sRGB encoded
+fn formats(format: u32) -> Option<(vk::Format, TextureFormat)> {
+    match format {
+        ARGB8888 | XRGB8888 => Some((vk::Format::B8G8R8A8_SRGB, TextureFormat::Bgra8UnormSrgb)),
+        ABGR8888 | XBGR8888 => Some((vk::Format::R8G8B8A8_SRGB, TextureFormat::Rgba8UnormSrgb)),
+        _ => None,
+    }
+}
+
+/// Open a device like `Adapter::request_device`, with the extensions for
+/// dmabuf images enabled
+///
+/// Fails on adapters other than Vulkan ones and on drivers without the
+/// extensions, callers fall back to a plain device.
+pub fn open_device(
+    adapter: &Adapter,
+    descriptor: &DeviceDescriptor,
+) -> Result<(Device, Queue), String> {
+    let features = descriptor.required_features;
+    // SAFETY: the device is created from the adapter with the extensions and
+    // features wgpu-hal asks for, plus ours
+    let opened = unsafe {
+        adapter.as_hal::<Vulkan, _, _>(|adapter| {
+            let adapter = adapter.ok_or("not a Vulkan adapter")?;
+            if let Some(missing) = EXTENSIONS.iter().find(|extension| {
+                !adapter
+                    .physical_device_capabilities()
+                    .supports_extension(extension)
+            }) {
+                return Err(format!("{} is not supported", missing.to_string_lossy()));
+            }
+
+            let mut extensions = adapter.required_device_extensions(features);
+            extensions.extend(EXTENSIONS);
+            let mut device_features = adapter.physical_device_features(&extensions, features);
+
+            // wgpu-hal always uses the first queue family
+            let family_index = 0;
+            let priorities = [1.0];
+            let queues = [vk::DeviceQueueCreateInfo::default()
+                .queue_family_index(family_index)
+                .queue_priorities(&priorities)];
+            let names: Vec<_> = extensions.iter().map(|name| name.as_ptr()).collect();
+            let info = device_features.add_to_device_create(
+                vk::DeviceCreateInfo::default()
+                    .queue_create_infos(&queues)
+                    .enabled_extension_names(&names),
+            );
+            let raw = adapter
+                .shared_instance()
+                .raw_instance()
+                .create_device(adapter.raw_physical_device(), &info, None)
+                .map_err(|e| format!("vkCreateDevice failed: {e}"))?;
+            adapter
+                .device_from_raw(
+                    raw,
+                    None,
+                    &extensions,
+                    features,
+                    &descriptor.memory_hints,
+                    family_index,
+                    0,
+                )
+                .map_err(|e| e.to_string())
+        })
+    }?;
+
+    // SAFETY: the device was opened on this adapter with the descriptor's features
+    unsafe { adapter.create_device_from_hal::<Vulkan>(opened, descriptor, None) }
+        .map_err(|e| e.to_string())
+}
+
+/// Whether `device` was opened by `open_device` and can allocate `DmabufImage`s
+pub fn is_supported(device: &Device) -> bool {
+    // SAFETY: the device is only inspected
+    unsafe {
+        device.as_hal::<Vulkan, _, _>(|device| {
+            device.is_some_and(|device| {
+                EXTENSIONS
+                    .iter()
+                    .all(|extension| device.enabled_device_extensions().contains(extension))
+            })
+        })
+    }
+}
+
+/// An image in GPU memory that other processes, like the compositor, can
+/// write to through a dmabuf and wgpu samples directly
+#[derive(Debug)]
+pub struct DmabufImage {
+    pub texture: Texture,
+    pub view: TextureView,
+    /// Dmabuf of the image's memory
+    pub fd: OwnedFd,
+    /// DRM format of the image
+    pub format: u32,
+    /// Bytes from the start of one row to the next
+    pub stride: u32,
+    /// Bytes before the first row
+    pub offset: u32,
+}
+
+impl DmabufImage {
+    /// Allocate a linear `width` by `height` image in a DRM `format` that
+    /// can be exported
+    pub fn allocate(
+        device: &Device,
+        width: u32,
+        height: u32,
+        format: u32,
+        label: &str,
+    ) -> Result<Self, String> {
+        let (vk_format, texture_format) =
+            formats(format).ok_or_else(|| format!("unsupported format {format:#010x}"))?;
+
+        // SAFETY: every Vulkan object is created on the device and either
+        // destroyed on failure or handed over to wgpu along with a callback
+        // destroying it
+        let (image, fd, layout) = unsafe {
+            device.as_hal::<Vulkan, _, _>(|device| {
+                let device = device.ok_or("not a Vulkan device")?;
+                allocate_image(device, vk_format, width, height)
+            })
+        }?;
+
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let hal_descriptor = hal::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: texture_format,
+            usage: hal::TextureUses::RESOURCE | hal::TextureUses::COPY_DST,
+            memory_flags: hal::MemoryFlags::empty(),
+            view_formats: Vec::new(),
+        };
+        // SAFETY: the image was created to match the descriptor, the callback
+        // frees it once wgpu is done with it
+        let texture = unsafe {
+            let hal_texture = hal::vulkan::Device::texture_from_raw(
+                image.image,
+                &hal_descriptor,
+                Some(Box::new(move || image.destroy())),
+            );
+            device.create_texture_from_hal::<Vulkan>(
+                hal_texture,
+                &wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: texture_format,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                    view_formats: &[],
+                },
+            )
+        };
+
+        Ok(Self {
+            view: texture.create_view(&Default::default()),
+            texture,
+            fd,
+            format,
+            stride: layout.row_pitch as u32,
+            offset: layout.offset as u32,
+        })
+    }
+}
+
+/// A Vulkan image and the memory bound to it
+struct RawImage {
+    device: ash::Device,
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+}
+
+impl RawImage {
+    /// # Safety
+    ///
+    /// The image must not be in use anymore
+    unsafe fn destroy(self) {
+        unsafe {
+            self.device.destroy_image(self.image, None);
+            self.device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// Create a linear image in exportable memory, returning it with its dmabuf
+/// and the layout of its pixels
+///
+/// Linear images keep the same memory layout in every image layout, so
+/// writes through the dmabuf show up without transitions.
+unsafe fn allocate_image(
+    device: &hal::vulkan::Device,
+    format: vk::Format,
+    width: u32,
+    height: u32,
+) -> Result<(RawImage, OwnedFd, vk::SubresourceLayout), String> {
+    let raw = device.raw_device();
+    let instance = device.shared_instance().raw_instance();
+    let handle_type = vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT;
+
+    let mut external = vk::ExternalMemoryImageCreateInfo::default().handle_types(handle_type);
+    let info = vk::ImageCreateInfo::default()
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(format)
+        .extent(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        })
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .tiling(vk::ImageTiling::LINEAR)
+        .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .push_next(&mut external);
+    let image = unsafe { raw.create_image(&info, None) }
+        .map_err(|e| format!("vkCreateImage failed: {e}"))?;
+
+    // Prefer memory on the GPU, the compositor copies into it on the GPU too
+    let requirements = unsafe { raw.get_image_memory_requirements(image) };
+    let properties =
+        unsafe { instance.get_physical_device_memory_properties(device.raw_physical_device()) };
+    let allowed = |index: &u32| requirements.memory_type_bits & (1 << index) != 0;
+    let Some(memory_type) = (0..properties.memory_type_count)
+        .filter(allowed)
+        .find(|&index| {
+            properties.memory_types[index as usize]
+                .property_flags
+                .contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+        })
+        .or_else(|| (0..properties.memory_type_count).find(allowed))
+    else {
+        unsafe { raw.destroy_image(image, None) };
+        return Err("no memory type can hold the image".to_string());
+    };
+
+    let mut export = vk::ExportMemoryAllocateInfo::default().handle_types(handle_type);
+    let mut dedicated = vk::MemoryDedicatedAllocateInfo::default().image(image);
+    let allocation = vk::MemoryAllocateInfo::default()
+        .allocation_size(requirements.size)
+        .memory_type_index(memory_type)
+        .push_next(&mut export)
+        .push_next(&mut dedicated);
+    let memory = match unsafe { raw.allocate_memory(&allocation, None) } {
+        Ok(memory) => memory,
+        Err(e) => {
+            unsafe { raw.destroy_image(image, None) };
+            return Err(format!("vkAllocateMemory failed: {e}"));
+        }
+    };
+    let image = RawImage {
+        device: raw.clone(),
+        image,
+        memory,
+    };
+
+    let exported = unsafe { raw.bind_image_memory(image.image, memory, 0) }
+        .map_err(|e| format!("vkBindImageMemory failed: {e}"))
+        .and_then(|()| {
+            let info = vk::MemoryGetFdInfoKHR::default()
+                .memory(memory)
+                .handle_type(handle_type);
+            unsafe { ash::khr::external_memory_fd::Device::new(instance, raw).get_memory_fd(&info) }
+                .map_err(|e| format!("vkGetMemoryFdKHR failed: {e}"))
+        });
+    let fd = match exported {
+        // SAFETY: Vulkan hands over ownership of the new descriptor
+        Ok(fd) => unsafe { OwnedFd::from_raw_fd(fd) },
+        Err(e) => {
+            unsafe { image.destroy() };
+            return Err(e);
+        }
+    };
+
+    let layout = unsafe {
+        raw.get_image_subresource_layout(
+            image.image,
+            vk::ImageSubresource {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                array_layer: 0,
+            },
+        )
+    };
+    Ok((image, fd, layout))
+}
@@ -0,0 +1,59 @@
+use std::sync::{Arc, OnceLock};
+
+use ab_glyph::{FontArc, FontVec};
+use resvg::usvg::fontdb::{Database, Family, Query};
+
+/// System fonts, loaded once and shared by text layers and SVG documents
+pub fn system_fonts() -> Arc<Database> {
+    static FONTS: OnceLock<Arc<Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut database = Database::new();
+            database.load_system_fonts();
+            tracing::debug!("Loaded {} system font faces", database.len());
+            Arc::new(database)
+        })
+        .clone()
+}
+
+/// Find a system font by family name, falling back to the default sans-serif
+/// font and then to any installed font
+pub fn load_font(family: Option<&str>) -> Result<FontArc, String> {
+    let database = system_fonts();
+
+    let families = match family {
+        Some(name) => vec![Family::Name(name), Family::SansSerif],
+        None => vec![Family::SansSerif],
+    };
+    let id = database
+        .query(&Query {
+            families: &families,
+            ..Default::default()
+        })
+        .or_else(|| database.faces().next().map(|face| face.id))
+        .ok_or_else(|| "no fonts are installed".to_string())?;
+
+    if let (Some(requested), Some(face)) = (family, database.face(id)) {
+        if !face
+            .families
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(requested))
+        {
+            tracing::warn!(
+                "Font '{requested}' not found, using '{}'",
+                face.families
+                    .first()
+                    .map(|(name, _)| name.as_str())
+                    .unwrap_or("unknown")
+            );
+        }
+    }
+
+    database
+        .with_face_data(id, |data, index| {
+            FontVec::try_from_vec_and_index(data.to_vec(), index)
+                .map(FontArc::new)
+                .map_err(|e| e.to_string())
+        })
+        .unwrap_or_else(|| Err("font file could not be read".to_string()))
+}
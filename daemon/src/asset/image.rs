@@ -1,19 +1,43 @@
+use std::sync::Arc;
+
+use common::{
+    baked::{BakedTexture, BlockFormat},
+    manifest::Sampling,
+};
 use image::{DynamicImage, GenericImageView};
 use wgpu::{
-    AddressMode, Device, Extent3d, FilterMode, Queue, Sampler, SamplerDescriptor,
-    TexelCopyBufferLayout, TexelCopyTextureInfo, Texture, TextureDescriptor, TextureDimension,
-    TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+    AddressMode, AstcBlock, AstcChannel, Device, Extent3d, FilterMode, Queue, Sampler,
+    SamplerDescriptor, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
 };
 
-#[derive(Debug)]
+use crate::renderer::memory::Allocation;
+
+const TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+#[derive(Debug, Clone)]
 pub struct ImageTexture {
     pub texture: Texture,
     pub view: TextureView,
     pub sampler: Sampler,
+    /// Shared by clones, the memory is released with the last one
+    pub allocation: Arc<Allocation>,
 }
 
 impl ImageTexture {
     pub fn from_image(device: &Device, queue: &Queue, image: &DynamicImage, label: &str) -> Self {
+        Self::with_sampling(device, queue, image, label, Sampling::default())
+    }
+
+    /// Upload an image using the given filtering mode, generating a full mip
+    /// chain when the mode samples across mip levels
+    pub fn with_sampling(
+        device: &Device,
+        queue: &Queue,
+        image: &DynamicImage,
+        label: &str,
+        sampling: Sampling,
+    ) -> Self {
         let (width, height) = image.dimensions();
         let rgba = image.to_rgba8();
 
@@ -23,14 +47,24 @@ impl ImageTexture {
             depth_or_array_layers: 1,
         };
 
+        let mip_level_count = if sampling.uses_mipmaps() {
+            size.max_mips(TextureDimension::D2)
+        } else {
+            1
+        };
+        let mut usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
+        if mip_level_count > 1 {
+            usage |= TextureUsages::RENDER_ATTACHMENT;
+        }
+
         let texture = device.create_texture(&TextureDescriptor {
             label: Some(label),
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8Unorm,
-            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            format: TEXTURE_FORMAT,
+            usage,
             view_formats: &[],
         });
 
@@ -49,21 +83,249 @@ impl ImageTexture {
             },
             size,
         );
+
+        if mip_level_count > 1 {
+            generate_mipmaps(device, queue, &texture, mip_level_count);
+        }
+
         let view = texture.create_view(&TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&SamplerDescriptor {
-            address_mode_u: AddressMode::ClampToEdge,
-            address_mode_v: AddressMode::ClampToEdge,
-            address_mode_w: AddressMode::ClampToEdge,
-            mag_filter: FilterMode::Linear,
-            min_filter: FilterMode::Nearest,
-            mipmap_filter: FilterMode::Nearest,
-            ..Default::default()
+        let sampler = device.create_sampler(&sampler_descriptor(sampling));
+        let allocation = Arc::new(Allocation::new(texture_size_bytes(&texture)));
+
+        Self {
+            texture,
+            view,
+            sampler,
+            allocation,
+        }
+    }
+
+    /// Upload a texture baked by `wlrs bake`, its mip levels from
+    /// `first_level` on
+    ///
+    /// The device needs `Features::TEXTURE_COMPRESSION_BC` for BC7 textures
+    /// and `Features::TEXTURE_COMPRESSION_ASTC` for ASTC ones.
+    pub fn from_baked(
+        device: &Device,
+        queue: &Queue,
+        baked: &BakedTexture,
+        first_level: usize,
+        label: &str,
+        sampling: Sampling,
+    ) -> Self {
+        let (width, height) = baked.level_size(first_level);
+        let levels = &baked.levels[first_level..];
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: match baked.format {
+                BlockFormat::Bc7 => TextureFormat::Bc7RgbaUnormSrgb,
+                BlockFormat::Astc => TextureFormat::Astc {
+                    block: AstcBlock::B4x4,
+                    channel: AstcChannel::UnormSrgb,
+                },
+            },
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
         });
 
+        for (mip, blocks) in levels.iter().enumerate() {
+            // Copies cover whole blocks, also on levels smaller than one
+            let (width, height) = baked.level_size(first_level + mip);
+            let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+            queue.write_texture(
+                TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: mip as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                blocks,
+                TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(blocks_x * common::baked::BLOCK_SIZE as u32),
+                    rows_per_image: Some(blocks_y),
+                },
+                Extent3d {
+                    width: blocks_x * 4,
+                    height: blocks_y * 4,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&sampler_descriptor(sampling));
+        let allocation = Arc::new(Allocation::new(texture_size_bytes(&texture)));
+
         Self {
             texture,
             view,
             sampler,
+            allocation,
+        }
+    }
+
+    /// Approximate GPU memory used by the texture including its mip chain
+    pub fn size_bytes(&self) -> u64 {
+        texture_size_bytes(&self.texture)
+    }
+
+    /// Whether nothing but this handle keeps the texture alive
+    pub fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.allocation) == 1
+    }
+}
+
+/// Approximate GPU memory of a texture, summing every mip level
+pub fn texture_size_bytes(texture: &Texture) -> u64 {
+    let format = texture.format();
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4) as u64;
+    (0..texture.mip_level_count())
+        .map(|mip| {
+            let width = (texture.width() >> mip).max(1).div_ceil(block_width) as u64;
+            let height = (texture.height() >> mip).max(1).div_ceil(block_height) as u64;
+            width * height * block_size
+        })
+        .sum()
+}
+
+/// Approximate GPU memory an RGBA8 texture of the given size would take,
+/// with a full mip chain if `mipmaps` is set
+pub fn rgba_size_bytes(width: u32, height: u32, mipmaps: bool) -> u64 {
+    let mip_level_count = if mipmaps {
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
         }
+        .max_mips(TextureDimension::D2)
+    } else {
+        1
+    };
+    mip_chain_bytes(width, height, mip_level_count)
+}
+
+fn mip_chain_bytes(width: u32, height: u32, mip_level_count: u32) -> u64 {
+    (0..mip_level_count)
+        .map(|mip| {
+            let width = (width >> mip).max(1) as u64;
+            let height = (height >> mip).max(1) as u64;
+            width * height * 4
+        })
+        .sum()
+}
+
+/// Sampler settings for a filtering mode
+pub fn sampler_descriptor(sampling: Sampling) -> SamplerDescriptor<'static> {
+    let (filter, mipmap_filter, anisotropy_clamp) = match sampling {
+        Sampling::Nearest => (FilterMode::Nearest, FilterMode::Nearest, 1),
+        Sampling::Linear => (FilterMode::Linear, FilterMode::Nearest, 1),
+        Sampling::Trilinear => (FilterMode::Linear, FilterMode::Linear, 1),
+        Sampling::Anisotropic => (FilterMode::Linear, FilterMode::Linear, 16),
+    };
+
+    SamplerDescriptor {
+        address_mode_u: AddressMode::ClampToEdge,
+        address_mode_v: AddressMode::ClampToEdge,
+        address_mode_w: AddressMode::ClampToEdge,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter,
+        anisotropy_clamp,
+        ..Default::default()
     }
 }
+
+/// Fill mip levels 1.. of a texture by repeatedly downsampling the previous
+/// level with a bilinear blit
+fn generate_mipmaps(device: &Device, queue: &Queue, texture: &Texture, mip_level_count: u32) {
+    let shader = device.create_shader_module(crate::shaders::MIPMAP_SHADER);
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Mipmap Pipeline"),
+        layout: None,
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(TEXTURE_FORMAT.into())],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: crate::renderer::pipeline_cache::current().as_deref(),
+    });
+    let bind_group_layout = pipeline.get_bind_group_layout(0);
+    let sampler = device.create_sampler(&SamplerDescriptor {
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let views: Vec<TextureView> = (0..mip_level_count)
+        .map(|mip| {
+            texture.create_view(&TextureViewDescriptor {
+                label: Some("mip"),
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Mipmap Encoder"),
+    });
+
+    for target in 1..mip_level_count as usize {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&views[target - 1]),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: None,
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Mipmap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &views[target],
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    queue.submit(Some(encoder.finish()));
+}
@@ -0,0 +1,295 @@
+use std::{fmt, fs, path::Path};
+
+use common::manifest::LutSource;
+use wgpu::{
+    Device, Extent3d, Queue, TexelCopyBufferLayout, TexelCopyTextureInfo, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureView,
+};
+
+/// Entries along each axis of the built-in tables
+const PRESET_SIZE: u32 = 17;
+
+/// Largest table a `.cube` file may declare, 64 MiB of texels
+const MAX_SIZE: u32 = 256;
+
+/// A 3D color lookup table mapping sRGB-encoded colors to graded ones
+#[derive(Debug, Clone)]
+pub struct Lut {
+    /// Entries along each axis
+    pub size: u32,
+    /// RGBA entries with red changing fastest, then green, then blue
+    pub data: Vec<[u8; 4]>,
+}
+
+/// Errors that can occur while loading a lookup table
+#[derive(Debug)]
+pub enum LutError {
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The file is not a valid 3D `.cube` table
+    Parse {
+        path: String,
+        line: usize,
+        message: String,
+    },
+    UnknownPreset(String),
+}
+
+impl fmt::Display for LutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LutError::Io { path, source } => write!(f, "Failed to read {path}: {source}"),
+            LutError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "Invalid lookup table {path} at line {line}: {message}"),
+            LutError::UnknownPreset(name) => write!(f, "Unknown lookup table preset '{name}'"),
+        }
+    }
+}
+
+impl std::error::Error for LutError {}
+
+impl Lut {
+    /// Load a table from a `.cube` file or generate a built-in one
+    pub fn load(source: &LutSource) -> Result<Self, LutError> {
+        match source {
+            LutSource::Preset(name) => Self::preset(name),
+            LutSource::File(path) => Self::from_file(path),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, LutError> {
+        let display = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|source| LutError::Io {
+            path: display.clone(),
+            source,
+        })?;
+        Self::parse_cube(&text).map_err(|(line, message)| LutError::Parse {
+            path: display,
+            line,
+            message,
+        })
+    }
+
+    /// Parse the text of an Adobe/Resolve `.cube` file, failing with the
+    /// line number and reason
+    pub fn parse_cube(text: &str) -> Result<Self, (usize, String)> {
+        let mut size = None;
+        let mut domain_min = [0.0f32; 3];
+        let mut domain_max = [1.0f32; 3];
+        let mut entries: Vec<[f32; 3]> = Vec::new();
+
+        let triple = |line: usize, words: &[&str]| -> Result<[f32; 3], (usize, String)> {
+            let values: Vec<f32> = words
+                .iter()
+                .map(|word| word.parse::<f32>())
+                .collect::<Result<_, _>>()
+                .map_err(|e| (line, e.to_string()))?;
+            values
+                .try_into()
+                .map_err(|_| (line, "expected three numbers".to_string()))
+        };
+
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[0] {
+                "TITLE" => {}
+                "LUT_3D_SIZE" => {
+                    let value = words
+                        .get(1)
+                        .and_then(|word| word.parse::<u32>().ok())
+                        .filter(|value| (2..=MAX_SIZE).contains(value))
+                        .ok_or((number, format!("size must be between 2 and {MAX_SIZE}")))?;
+                    size = Some(value);
+                }
+                "LUT_1D_SIZE" => {
+                    return Err((number, "1D tables are not supported".to_string()));
+                }
+                "DOMAIN_MIN" => domain_min = triple(number, &words[1..])?,
+                "DOMAIN_MAX" => domain_max = triple(number, &words[1..])?,
+                keyword if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    tracing::debug!("Ignoring {keyword} in lookup table");
+                }
+                _ => entries.push(triple(number, &words)?),
+            }
+        }
+
+        let line_count = text.lines().count();
+        let size = size.ok_or((line_count, "missing LUT_3D_SIZE".to_string()))?;
+        let expected = (size * size * size) as usize;
+        if entries.len() != expected {
+            return Err((
+                line_count,
+                format!("expected {expected} entries, found {}", entries.len()),
+            ));
+        }
+
+        let data = entries
+            .into_iter()
+            .map(|entry| {
+                let mut texel = [u8::MAX; 4];
+                for channel in 0..3 {
+                    let range = (domain_max[channel] - domain_min[channel]).max(f32::EPSILON);
+                    let value = (entry[channel] - domain_min[channel]) / range;
+                    texel[channel] = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                }
+                texel
+            })
+            .collect();
+        Ok(Self { size, data })
+    }
+
+    /// Generate one of `common::manifest::LUT_PRESETS`
+    pub fn preset(name: &str) -> Result<Self, LutError> {
+        let grade: fn([f32; 3]) -> [f32; 3] = match name {
+            "identity" => |color| color,
+            "warm" => |[r, g, b]| [r * 1.06 + 0.02, g * 1.01, b * 0.86],
+            "cool" => |[r, g, b]| [r * 0.88, g * 0.99 + 0.01, b * 1.06 + 0.03],
+            "sepia" => |[r, g, b]| {
+                [
+                    0.393 * r + 0.769 * g + 0.189 * b,
+                    0.349 * r + 0.686 * g + 0.168 * b,
+                    0.272 * r + 0.534 * g + 0.131 * b,
+                ]
+            },
+            "mono" => |[r, g, b]| {
+                let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+                [luma; 3]
+            },
+            _ => return Err(LutError::UnknownPreset(name.to_string())),
+        };
+
+        let step = 1.0 / (PRESET_SIZE - 1) as f32;
+        let mut data = Vec::with_capacity((PRESET_SIZE * PRESET_SIZE * PRESET_SIZE) as usize);
+        for b in 0..PRESET_SIZE {
+            for g in 0..PRESET_SIZE {
+                for r in 0..PRESET_SIZE {
+                    let color = grade([r as f32 * step, g as f32 * step, b as f32 * step]);
+                    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                    data.push([channel(color[0]), channel(color[1]), channel(color[2]), 255]);
+                }
+            }
+        }
+        Ok(Self {
+            size: PRESET_SIZE,
+            data,
+        })
+    }
+
+    /// Upload the table as a 3D texture
+    ///
+    /// Entries are stored as they are, the shader looks colors up in their
+    /// sRGB encoding like the tables expect.
+    pub fn upload(&self, device: &Device, queue: &Queue, label: &str) -> TextureView {
+        let size = Extent3d {
+            width: self.size,
+            height: self.size,
+            depth_or_array_layers: self.size,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&self.data),
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * self.size),
+                rows_per_image: Some(self.size),
+            },
+            size,
+        );
+        texture.create_view(&Default::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2×2×2 table with `body` as its entries
+    fn cube(body: &str) -> String {
+        format!("TITLE \"test\"\n# comment\nLUT_3D_SIZE 2\n\n{body}")
+    }
+
+    const IDENTITY: &str = "0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+
+    #[test]
+    fn test_parse_cube() {
+        let lut = Lut::parse_cube(&cube(IDENTITY)).unwrap();
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.data.len(), 8);
+        assert_eq!(lut.data[0], [0, 0, 0, 255]);
+        assert_eq!(lut.data[1], [255, 0, 0, 255]);
+        assert_eq!(lut.data[7], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_parse_cube_domain() {
+        let text = cube(IDENTITY).replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2");
+        let lut = Lut::parse_cube(&text).unwrap();
+        assert_eq!(lut.data[7], [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn test_parse_cube_errors() {
+        let missing_size = Lut::parse_cube(IDENTITY).unwrap_err();
+        assert!(missing_size.1.contains("LUT_3D_SIZE"));
+
+        let (line, message) = Lut::parse_cube(&cube("0 0 0\n1 0 0\n")).unwrap_err();
+        assert_eq!(line, 6);
+        assert!(message.contains("expected 8 entries, found 2"));
+
+        let (line, _) = Lut::parse_cube(&cube("0 0 zero\n")).unwrap_err();
+        assert_eq!(line, 5);
+
+        let (line, _) = Lut::parse_cube(&cube("0 0\n")).unwrap_err();
+        assert_eq!(line, 5);
+
+        let (_, message) = Lut::parse_cube("LUT_3D_SIZE 1\n").unwrap_err();
+        assert!(message.contains("between 2"));
+
+        let (_, message) = Lut::parse_cube("LUT_1D_SIZE 16\n").unwrap_err();
+        assert!(message.contains("1D"));
+    }
+
+    #[test]
+    fn test_presets() {
+        for name in ["identity", "warm", "cool", "sepia", "mono"] {
+            let lut = Lut::preset(name).unwrap();
+            assert_eq!(
+                lut.data.len(),
+                (PRESET_SIZE * PRESET_SIZE * PRESET_SIZE) as usize
+            );
+        }
+        let identity = Lut::preset("identity").unwrap();
+        assert_eq!(identity.data[0], [0, 0, 0, 255]);
+        assert_eq!(*identity.data.last().unwrap(), [255; 4]);
+        assert!(matches!(
+            Lut::preset("vivid"),
+            Err(LutError::UnknownPreset(_))
+        ));
+    }
+}
@@ -1,6 +1,16 @@
 pub mod animated;
+pub mod baked;
+pub mod color;
 pub mod damage;
+pub mod decoder;
+pub mod dmabuf;
+pub mod font;
 pub mod image;
+pub mod lut;
+pub mod noise;
+pub mod svg;
+pub mod text;
+pub mod thumbnail;
 
 pub trait Asset {
     fn damage(&self) -> damage::Damage;
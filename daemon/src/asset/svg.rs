@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use image::{DynamicImage, RgbaImage};
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{Options, Tree},
+};
+
+use super::{decoder::LoadError, font::system_fonts};
+
+/// A parsed SVG document that can be rasterised at any size
+///
+/// Parsing happens once, rasterising is repeated whenever the output
+/// resolution changes so the result is always pixel-exact.
+#[derive(Debug)]
+pub struct SvgDocument {
+    tree: Tree,
+}
+
+impl SvgDocument {
+    /// Parse an SVG or gzip-compressed SVGZ file
+    ///
+    /// Relative references inside the document, such as embedded images,
+    /// resolve against the file's directory.
+    pub fn open(path: &Path) -> Result<Self, LoadError> {
+        let error = |message: String| LoadError::Decode {
+            path: path.display().to_string(),
+            message,
+        };
+
+        let data = std::fs::read(path).map_err(|e| error(e.to_string()))?;
+        let options = Options {
+            resources_dir: path.parent().map(Path::to_path_buf),
+            fontdb: system_fonts(),
+            ..Default::default()
+        };
+        let tree = Tree::from_data(&data, &options).map_err(|e| error(e.to_string()))?;
+
+        Ok(Self { tree })
+    }
+
+    /// Size of the document's viewport in CSS pixels
+    pub fn size(&self) -> (u32, u32) {
+        let size = self.tree.size().to_int_size();
+        (size.width(), size.height())
+    }
+
+    /// Rasterise the document to exactly `width` x `height` pixels
+    ///
+    /// The drawing is scaled uniformly to cover the target and centered,
+    /// cropping whichever axis overflows, like image layers.
+    pub fn render(&self, width: u32, height: u32) -> DynamicImage {
+        let width = width.max(1);
+        let height = height.max(1);
+        let mut pixmap = Pixmap::new(width, height).expect("SVG target size is non-zero");
+
+        let size = self.tree.size();
+        let scale = (width as f32 / size.width()).max(height as f32 / size.height());
+        let offset_x = (width as f32 - size.width() * scale) / 2.0;
+        let offset_y = (height as f32 - size.height() * scale) / 2.0;
+        let transform = Transform::from_row(scale, 0.0, 0.0, scale, offset_x, offset_y);
+        resvg::render(&self.tree, transform, &mut pixmap.as_mut());
+
+        // tiny-skia produces premultiplied alpha, textures are straight alpha
+        let pixels = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect();
+        let image =
+            RgbaImage::from_raw(width, height, pixels).expect("pixmap holds width * height pixels");
+
+        DynamicImage::ImageRgba8(image)
+    }
+}
@@ -0,0 +1,374 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use ab_glyph::{point, Font, FontArc, GlyphId, PxScale, ScaleFont};
+use chrono::{format::StrftimeItems, DateTime, Local};
+use common::manifest::TextAlign;
+use wgpu::{
+    AddressMode, Device, Extent3d, FilterMode, Origin3d, Queue, Sampler, SamplerDescriptor,
+    TexelCopyBufferLayout, TexelCopyTextureInfo, Texture, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+};
+
+/// Format used for `{time}` without an explicit format
+const TIME_FORMAT: &str = "%H:%M";
+/// Format used for `{date}` without an explicit format
+const DATE_FORMAT: &str = "%A, %B %-d";
+
+/// Placeholders filled in from the playing track, with the `mpris` feature
+const TRACK_PLACEHOLDERS: [&str; 3] = ["{title}", "{artist}", "{album}"];
+
+/// Width and height of the glyph atlas texture
+const ATLAS_SIZE: u32 = 1024;
+/// Empty pixels kept between glyphs so filtering never bleeds into neighbours
+const ATLAS_PADDING: u32 = 1;
+
+/// Text with `{placeholder}`s that are filled in each time it is rendered
+///
+/// Unknown placeholders and invalid formats are kept verbatim so mistakes
+/// show up on screen instead of silently disappearing.
+#[derive(Debug, Clone)]
+pub struct TextTemplate {
+    source: String,
+    dynamic: bool,
+}
+
+impl TextTemplate {
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let dynamic = source.contains("{time")
+            || source.contains("{date")
+            || (cfg!(feature = "mpris")
+                && TRACK_PLACEHOLDERS
+                    .iter()
+                    .any(|placeholder| source.contains(placeholder)));
+        Self { source, dynamic }
+    }
+
+    /// Whether the text changes over time, static text only needs one layout
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic
+    }
+
+    /// Fill in the placeholders using the current local time
+    pub fn render(&self) -> String {
+        let now = Local::now();
+        let mut text = String::with_capacity(self.source.len());
+        let mut rest = self.source.as_str();
+
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let placeholder = &rest[start + 1..start + len];
+            match expand(placeholder, &now) {
+                Some(value) => text.push_str(&value),
+                None => text.push_str(&rest[start..=start + len]),
+            }
+            rest = &rest[start + len + 1..];
+        }
+
+        text.push_str(rest);
+        text
+    }
+}
+
+fn expand(placeholder: &str, now: &DateTime<Local>) -> Option<String> {
+    let (name, format) = match placeholder.split_once(':') {
+        Some((name, format)) => (name, Some(format)),
+        None => (placeholder, None),
+    };
+
+    match name {
+        "time" => format_time(now, format.unwrap_or(TIME_FORMAT)),
+        "date" => format_time(now, format.unwrap_or(DATE_FORMAT)),
+        "hostname" => Some(hostname().to_string()),
+        // Empty while nothing plays
+        #[cfg(feature = "mpris")]
+        "title" | "artist" | "album" => Some(
+            crate::mpris::current()
+                .map(|track| match name {
+                    "title" => track.title,
+                    "artist" => track.artist,
+                    _ => track.album,
+                })
+                .unwrap_or_default(),
+        ),
+        _ => None,
+    }
+}
+
+fn format_time(now: &DateTime<Local>, format: &str) -> Option<String> {
+    // chrono panics while displaying an invalid format, validate it first
+    let items = StrftimeItems::new(format).parse().ok()?;
+    Some(now.format_with_items(items.iter()).to_string())
+}
+
+fn hostname() -> &'static str {
+    static HOSTNAME: OnceLock<String> = OnceLock::new();
+    HOSTNAME.get_or_init(|| {
+        let mut buffer = [0u8; 256];
+        let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+        if result != 0 {
+            tracing::warn!(
+                "Failed to read hostname: {}",
+                std::io::Error::last_os_error()
+            );
+            return String::new();
+        }
+        let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+        String::from_utf8_lossy(&buffer[..len]).into_owned()
+    })
+}
+
+/// A glyph placed on screen, matches the instance input of the text shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlyphInstance {
+    /// Top left corner and size in pixels
+    pub rect: [f32; 4],
+    /// Top left and bottom right corners in the atlas
+    pub uv: [f32; 4],
+}
+
+/// Where a rasterised glyph lives in the atlas
+#[derive(Debug, Clone, Copy)]
+struct AtlasGlyph {
+    /// Offset of the bitmap from the pen position on the baseline
+    offset: [f32; 2],
+    size: [f32; 2],
+    uv: [f32; 4],
+}
+
+/// Coverage bitmaps of rasterised glyphs packed into one texture
+///
+/// Glyphs are packed in rows as they are first used. When the atlas is full
+/// it is cleared and the text laid out again, which only happens if a layer
+/// cycles through many different glyphs or sizes.
+#[derive(Debug)]
+pub struct GlyphAtlas {
+    texture: Texture,
+    pub view: TextureView,
+    pub sampler: Sampler,
+    /// Rasterised glyphs by id and pixel size, `None` for glyphs without an outline
+    glyphs: HashMap<(GlyphId, u32), Option<AtlasGlyph>>,
+    /// Next free position in the current row
+    cursor: (u32, u32),
+    row_height: u32,
+}
+
+impl GlyphAtlas {
+    pub fn new(device: &Device, label: &str) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(&format!("glyph_atlas_{label}")),
+            size: Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_height: 0,
+        }
+    }
+
+    /// Lay out `text` and return one instance per visible glyph
+    ///
+    /// `anchor` is the pixel position of the text block: its vertical center,
+    /// and its left edge, center or right edge depending on `align`.
+    pub fn layout(
+        &mut self,
+        queue: &Queue,
+        font: &FontArc,
+        text: &str,
+        size: f32,
+        align: TextAlign,
+        anchor: [f32; 2],
+    ) -> Vec<GlyphInstance> {
+        match self.try_layout(queue, font, text, size, align, anchor) {
+            Some(instances) => instances,
+            None => {
+                tracing::debug!("Glyph atlas full, clearing it");
+                self.clear();
+                self.try_layout(queue, font, text, size, align, anchor)
+                    .unwrap_or_else(|| {
+                        tracing::warn!("Text does not fit into the glyph atlas: {text:?}");
+                        Vec::new()
+                    })
+            }
+        }
+    }
+
+    fn try_layout(
+        &mut self,
+        queue: &Queue,
+        font: &FontArc,
+        text: &str,
+        size: f32,
+        align: TextAlign,
+        anchor: [f32; 2],
+    ) -> Option<Vec<GlyphInstance>> {
+        let scale = PxScale::from(size);
+        let scaled = font.as_scaled(scale);
+        let line_height = scaled.height() + scaled.line_gap();
+
+        let lines: Vec<_> = text.lines().collect();
+        let top = anchor[1] - line_height * lines.len() as f32 / 2.0;
+        let mut instances = Vec::with_capacity(text.len());
+
+        for (index, line) in lines.iter().enumerate() {
+            // Pen positions along the line, including kerning
+            let mut pen = 0.0;
+            let mut previous = None;
+            let mut glyphs = Vec::with_capacity(line.len());
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                if let Some(previous) = previous {
+                    pen += scaled.kern(previous, id);
+                }
+                glyphs.push((id, pen));
+                pen += scaled.h_advance(id);
+                previous = Some(id);
+            }
+
+            let left = match align {
+                TextAlign::Left => anchor[0],
+                TextAlign::Center => anchor[0] - pen / 2.0,
+                TextAlign::Right => anchor[0] - pen,
+            };
+            let baseline = (top + line_height * index as f32 + scaled.ascent()).round();
+
+            for (id, x) in glyphs {
+                let Some(glyph) = self.glyph(queue, font, id, scale)? else {
+                    continue;
+                };
+                instances.push(GlyphInstance {
+                    rect: [
+                        (left + x).round() + glyph.offset[0],
+                        baseline + glyph.offset[1],
+                        glyph.size[0],
+                        glyph.size[1],
+                    ],
+                    uv: glyph.uv,
+                });
+            }
+        }
+
+        Some(instances)
+    }
+
+    /// Look up a glyph, rasterising it into the atlas on first use
+    ///
+    /// Returns `None` when the atlas is full and `Some(None)` for glyphs
+    /// that have nothing to draw, like spaces.
+    fn glyph(
+        &mut self,
+        queue: &Queue,
+        font: &FontArc,
+        id: GlyphId,
+        scale: PxScale,
+    ) -> Option<Option<AtlasGlyph>> {
+        let key = (id, scale.y.to_bits());
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return Some(*glyph);
+        }
+
+        let Some(outline) = font.outline_glyph(id.with_scale_and_position(scale, point(0.0, 0.0)))
+        else {
+            self.glyphs.insert(key, None);
+            return Some(None);
+        };
+        let bounds = outline.px_bounds();
+        let width = bounds.width() as u32;
+        let height = bounds.height() as u32;
+        if width == 0 || height == 0 {
+            self.glyphs.insert(key, None);
+            return Some(None);
+        }
+
+        let (x, y) = self.allocate(width, height)?;
+        let mut coverage = vec![0u8; (width * height) as usize];
+        outline.draw(|gx, gy, c| {
+            if gx < width && gy < height {
+                coverage[(gy * width + gx) as usize] = (c.clamp(0.0, 1.0) * 255.0) as u8;
+            }
+        });
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &coverage,
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let atlas = ATLAS_SIZE as f32;
+        let glyph = AtlasGlyph {
+            offset: [bounds.min.x, bounds.min.y],
+            size: [width as f32, height as f32],
+            uv: [
+                x as f32 / atlas,
+                y as f32 / atlas,
+                (x + width) as f32 / atlas,
+                (y + height) as f32 / atlas,
+            ],
+        };
+        self.glyphs.insert(key, Some(glyph));
+        Some(Some(glyph))
+    }
+
+    /// Reserve space for a glyph bitmap, returns `None` when the atlas is full
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if self.cursor.0 + width + ATLAS_PADDING > ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height + ATLAS_PADDING);
+            self.row_height = 0;
+        }
+        if self.cursor.0 + width > ATLAS_SIZE || self.cursor.1 + height > ATLAS_SIZE {
+            return None;
+        }
+
+        let position = self.cursor;
+        self.cursor.0 += width + ATLAS_PADDING;
+        self.row_height = self.row_height.max(height);
+        Some(position)
+    }
+
+    /// Forget every glyph, quads are pixel aligned so leftover pixels next
+    /// to newly added glyphs are never sampled
+    fn clear(&mut self) {
+        self.glyphs.clear();
+        self.cursor = (0, 0);
+        self.row_height = 0;
+    }
+}
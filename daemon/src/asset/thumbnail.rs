@@ -0,0 +1,256 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use common::{
+    color::Color,
+    colorscheme::ColorScheme,
+    manifest::{AlbumArtContent, GradientContent, GradientKind, LayerContent, ASSET_HASH_PREFIX},
+    wallpaper::{hash_bytes, Wallpaper},
+};
+use image::{imageops::FilterType, DynamicImage, Rgba, RgbaImage};
+
+use super::{color::load_image, svg::SvgDocument};
+use crate::colors;
+
+/// Size of generated thumbnails, 16:9 like most monitors
+const THUMBNAIL_WIDTH: u32 = 320;
+const THUMBNAIL_HEIGHT: u32 = 180;
+
+/// Return the path of a small PNG showing the wallpaper, rendering it first
+/// if there is no up to date copy in the cache
+///
+/// The manifest's `preview` image is used when there is one. Otherwise the
+/// color, gradient, image and SVG layers are composited on the CPU; text,
+/// particles and shader effects are left out. Rendering takes a while for
+/// large images, so the daemon does it on a loader thread, see
+/// `Loader::thumbnail`.
+pub fn thumbnail(wallpaper: &Wallpaper) -> Result<PathBuf, String> {
+    let path = thumbnail_path(wallpaper);
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let image = render(wallpaper)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+    }
+    // Renamed into place, so readers never see half a file when two
+    // loader threads render the same thumbnail
+    let partial = path.with_extension(format!("{}.partial", std::process::id()));
+    image
+        .save_with_format(&partial, image::ImageFormat::Png)
+        .and_then(|()| fs::rename(&partial, &path).map_err(Into::into))
+        .map_err(|e| {
+            let _ = fs::remove_file(&partial);
+            format!("Failed to save {}: {e}", path.display())
+        })?;
+    tracing::debug!(
+        "Rendered thumbnail for {} to {}",
+        wallpaper.name(),
+        path.display()
+    );
+
+    Ok(path)
+}
+
+fn thumbnail_dir() -> PathBuf {
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.cache_dir().join("wlrs").join("thumbnails"))
+        .unwrap_or_else(|| PathBuf::from("/tmp/wlrs/thumbnails"))
+}
+
+/// Path of the thumbnail of `wallpaper` if it was rendered already
+pub fn cached(wallpaper: &Wallpaper) -> Option<PathBuf> {
+    Some(thumbnail_path(wallpaper)).filter(|path| path.exists())
+}
+
+fn thumbnail_path(wallpaper: &Wallpaper) -> PathBuf {
+    thumbnail_dir().join(format!("{}.png", cache_key(wallpaper)))
+}
+
+/// Thumbnails are keyed by the wallpaper's location, the modification times
+/// of its manifest and of the files it is drawn from, and the color scheme,
+/// so edits produce a fresh thumbnail
+///
+/// SHA-256 rather than `DefaultHasher`, whose output may change between
+/// builds and would orphan the cached thumbnails.
+fn cache_key(wallpaper: &Wallpaper) -> String {
+    let mut sources = vec![wallpaper.path.join("manifest.toml")];
+    sources.extend(wallpaper.preview_path());
+    for layer in &wallpaper.manifest.layers {
+        match &layer.content {
+            LayerContent::Image(path)
+            | LayerContent::Svg(path)
+            | LayerContent::AlbumArt(AlbumArtContent {
+                fallback: Some(path),
+                ..
+            }) => sources.push(wallpaper.asset_path(path)),
+            _ => {}
+        }
+    }
+
+    let mut key = wallpaper.path.to_string_lossy().into_owned();
+    for source in &sources {
+        key.push_str(&format!("\0{}\0{}", source.display(), modified(source)));
+    }
+    key.push_str(&format!("\0{}", colors::current().to_list().join(",")));
+    let hash = hash_bytes(key.as_bytes());
+    hash[ASSET_HASH_PREFIX.len()..][..32].to_string()
+}
+
+/// Modification time in nanoseconds, empty for a missing file
+fn modified(path: &Path) -> String {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_nanos().to_string())
+        .unwrap_or_default()
+}
+
+fn render(wallpaper: &Wallpaper) -> Result<RgbaImage, String> {
+    if let Some(preview) = wallpaper.preview_path() {
+        let image = load_image(&preview).map_err(|e| e.to_string())?;
+        return Ok(cover(&image));
+    }
+
+    let mut thumbnail = RgbaImage::from_pixel(
+        THUMBNAIL_WIDTH,
+        THUMBNAIL_HEIGHT,
+        Rgba([0, 0, 0, to_u8(wallpaper.background_alpha())]),
+    );
+    let colors = colors::current();
+    for layer in wallpaper.manifest.get_sorted_layers() {
+        let image = match &layer.content {
+            LayerContent::Color(color) => {
+                let color = Color::parse(&colors.resolve(color)).map_err(|e| e.to_string())?;
+                RgbaImage::from_pixel(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, to_rgba(color))
+            }
+            LayerContent::Gradient(gradient) => render_gradient(gradient, &colors)?,
+            LayerContent::Image(path) => {
+                let image = load_image(wallpaper.asset_path(path)).map_err(|e| e.to_string())?;
+                cover(&image)
+            }
+            LayerContent::Svg(path) => {
+                let document =
+                    SvgDocument::open(&wallpaper.asset_path(path)).map_err(|e| e.to_string())?;
+                document
+                    .render(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+                    .to_rgba8()
+            }
+            LayerContent::AlbumArt(AlbumArtContent {
+                fallback: Some(path),
+                ..
+            }) => {
+                let image = load_image(wallpaper.asset_path(path)).map_err(|e| e.to_string())?;
+                cover(&image)
+            }
+            LayerContent::Text(_)
+            | LayerContent::AlbumArt(_)
+            | LayerContent::Capture(_)
+            | LayerContent::Web(_)
+            | LayerContent::None => continue,
+        };
+        blend(&mut thumbnail, &image, layer.opacity);
+    }
+
+    Ok(thumbnail)
+}
+
+/// Scale an image to cover the thumbnail and crop it centered, like image layers
+fn cover(image: &DynamicImage) -> RgbaImage {
+    image
+        .resize_to_fill(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, FilterType::Triangle)
+        .to_rgba8()
+}
+
+/// Draw `layer` over `target` with straight alpha
+///
+/// Blending happens on the sRGB values, which is close enough at this size.
+fn blend(target: &mut RgbaImage, layer: &RgbaImage, opacity: f32) {
+    for (dst, src) in target.pixels_mut().zip(layer.pixels()) {
+        let src_alpha = src[3] as f32 / 255.0 * opacity.clamp(0.0, 1.0);
+        let dst_alpha = dst[3] as f32 / 255.0;
+        let alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+        if alpha <= 0.0 {
+            continue;
+        }
+        for (dst, src) in dst.0.iter_mut().zip(src.0).take(3) {
+            let value =
+                (src as f32 * src_alpha + *dst as f32 * dst_alpha * (1.0 - src_alpha)) / alpha;
+            *dst = value.round() as u8;
+        }
+        dst[3] = to_u8(alpha);
+    }
+}
+
+/// Evaluate a gradient the same way the gradient shader does
+fn render_gradient(gradient: &GradientContent, colors: &ColorScheme) -> Result<RgbaImage, String> {
+    let stops = gradient
+        .resolved_stops()
+        .into_iter()
+        .map(|(color, position)| {
+            Color::parse(&colors.resolve(color)).map(|color| (color.to_array(), position))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let (width, height) = (THUMBNAIL_WIDTH as f32, THUMBNAIL_HEIGHT as f32);
+    let center = [gradient.center[0] * width, gradient.center[1] * height];
+    let angle = gradient.angle.to_radians();
+    // Pixel coordinates grow downwards, so "up" is negative y
+    let direction = [angle.sin(), -angle.cos()];
+
+    Ok(RgbaImage::from_fn(
+        THUMBNAIL_WIDTH,
+        THUMBNAIL_HEIGHT,
+        |x, y| {
+            let pixel = [x as f32 + 0.5, y as f32 + 0.5];
+            let t = match gradient.gradient {
+                GradientKind::Linear => {
+                    let span = (width * direction[0]).abs() + (height * direction[1]).abs();
+                    ((pixel[0] - width / 2.0) * direction[0]
+                        + (pixel[1] - height / 2.0) * direction[1])
+                        / span
+                        + 0.5
+                }
+                GradientKind::Radial => {
+                    let corner = [
+                        center[0].max(width - center[0]),
+                        center[1].max(height - center[1]),
+                    ];
+                    (pixel[0] - center[0]).hypot(pixel[1] - center[1]) / corner[0].hypot(corner[1])
+                }
+                GradientKind::Conic => {
+                    let offset = [pixel[0] - center[0], pixel[1] - center[1]];
+                    let turn = (offset[0].atan2(-offset[1]) - angle) / std::f32::consts::TAU;
+                    turn - turn.floor()
+                }
+            };
+
+            let mut color = stops[0].0;
+            for pair in stops.windows(2) {
+                let ((from, start), (to, end)) = (pair[0], pair[1]);
+                if t >= start {
+                    let factor = ((t - start) / (end - start).max(1e-5)).clamp(0.0, 1.0);
+                    for ((channel, from), to) in color.iter_mut().zip(from).zip(to) {
+                        *channel = from + (to - from) * factor;
+                    }
+                }
+            }
+            Rgba(color.map(to_u8))
+        },
+    ))
+}
+
+fn to_rgba(color: Color) -> Rgba<u8> {
+    Rgba(color.to_array().map(to_u8))
+}
+
+fn to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
@@ -0,0 +1,93 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use common::colorscheme::{ColorScheme, SchemeMode};
+
+use crate::config::{expand_home, ColorsConfig};
+
+/// Time between two looks at the scheme file
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// None until a scheme was loaded, wallpapers then get the default one
+static CURRENT: Mutex<Option<ColorScheme>> = Mutex::new(None);
+static CHANGED: AtomicBool = AtomicBool::new(false);
+/// Read the file again even if it did not change, after a reset
+static RELOAD: AtomicBool = AtomicBool::new(false);
+
+/// Start following the scheme file, pywal's cache unless configured
+///
+/// The scheme is read again whenever the file changes, so running pywal or
+/// matugen recolors the wallpapers.
+pub fn start(config: ColorsConfig) {
+    let Some(path) = config.file.map(expand_home).or_else(|| {
+        directories::BaseDirs::new().map(|dirs| dirs.cache_dir().join("wal").join("colors.json"))
+    }) else {
+        return;
+    };
+    let mode = config.mode.into();
+
+    let spawned = thread::Builder::new()
+        .name("colors".to_string())
+        .spawn(move || {
+            let mut last: Option<SystemTime> = None;
+            loop {
+                let reload = RELOAD.swap(false, Ordering::Acquire);
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                if reload || modified != last {
+                    last = modified;
+                    match modified.map(|_| load(&path, mode)).transpose() {
+                        Ok(scheme) => replace(scheme),
+                        Err(e) => tracing::warn!(
+                            "Failed to read the color scheme {}: {e}",
+                            path.display()
+                        ),
+                    }
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::error!("Failed to start following the color scheme: {e}");
+    }
+}
+
+/// The scheme placeholders like `{color4}` stand for
+pub fn current() -> ColorScheme {
+    CURRENT.lock().unwrap().unwrap_or_default()
+}
+
+/// Use `scheme` until the file changes, or go back to the file if None
+pub fn set(scheme: Option<ColorScheme>) {
+    match scheme {
+        Some(scheme) => replace(Some(scheme)),
+        None => RELOAD.store(true, Ordering::Release),
+    }
+}
+
+/// Whether the scheme changed since the last call
+pub fn take_change() -> bool {
+    CHANGED.swap(false, Ordering::Acquire)
+}
+
+fn replace(scheme: Option<ColorScheme>) {
+    let mut current = CURRENT.lock().unwrap();
+    if *current != scheme {
+        tracing::info!("Color scheme changed");
+        *current = scheme;
+        CHANGED.store(true, Ordering::Release);
+        crate::wake::wake();
+    }
+}
+
+fn load(path: &Path, mode: SchemeMode) -> Result<ColorScheme, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    ColorScheme::from_json(&text, mode).map_err(|e| e.to_string())
+}
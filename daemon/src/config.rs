@@ -0,0 +1,521 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use clap::{Parser, ValueEnum};
+use common::colorscheme::SchemeMode;
+use serde::Deserialize;
+
+/// Command line options of the daemon
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Wayland wallpaper daemon", long_about = None)]
+pub struct Args {
+    /// Run as a systemd service: report readiness and accept socket activation
+    #[arg(long)]
+    pub systemd: bool,
+
+    /// GPU adapter to render with, by index or part of its name (see --list-adapters)
+    #[arg(long, value_name = "NAME|INDEX")]
+    pub adapter: Option<String>,
+
+    /// Prefer the integrated or the discrete GPU when no adapter is named
+    #[arg(long, value_enum)]
+    pub power_preference: Option<PowerPreference>,
+
+    /// List the GPU adapters that can be selected and exit
+    #[arg(long)]
+    pub list_adapters: bool,
+
+    /// Log to the systemd journal instead of stderr (needs the `journald` feature)
+    #[arg(long)]
+    pub journald: bool,
+
+    /// Stop the running daemon and take over its wallpapers
+    #[arg(long)]
+    pub replace: bool,
+
+    /// Render in a child process that is restarted with the same
+    /// wallpapers when it crashes, e.g. in the GPU driver
+    #[arg(long)]
+    pub watchdog: bool,
+
+    /// Run next to the session's daemon with its own socket, state and
+    /// layer namespace, e.g. in a nested compositor
+    #[arg(long, value_name = "NAME", value_parser = common::ipc::parse_namespace)]
+    pub namespace: Option<String>,
+
+    /// Show the wallpapers the state file has for each output, as the
+    /// watchdog does after a crash
+    #[arg(long, hide = true)]
+    pub restore: bool,
+}
+
+/// Daemon settings, read from `$XDG_CONFIG_HOME/wlrs/config.toml`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub gpu: GpuConfig,
+    /// Settings for outputs by name, for each setting the first matching
+    /// rule that has it wins
+    pub outputs: Vec<OutputRule>,
+    pub fetch: FetchConfig,
+    /// Where to look up the weather, for layers shown only in some weather
+    pub weather: Option<WeatherConfig>,
+    pub colors: ColorsConfig,
+    /// What happens to the wallpapers while the user is away
+    pub idle: IdleConfig,
+    /// Draw every wallpaper as a single still frame, follows the desktop's
+    /// animation setting if unset
+    pub reduce_motion: Option<bool>,
+    /// Directories wallpapers are looked up in besides the user's and the
+    /// system-wide ones, see `library::search_paths`
+    pub wallpaper_dirs: Vec<PathBuf>,
+    /// Repositories `wlrs browse` and `wlrs fetch` look in, HTTPS URLs of
+    /// an `index.json` or of the directory it is in
+    pub repositories: Vec<String>,
+    /// Commands run on events, see `hooks`
+    pub hooks: HooksConfig,
+    /// How image layers are prepared for the outputs
+    pub images: ImagesConfig,
+    /// What outputs show when their wallpaper failed to load
+    pub error: ErrorConfig,
+    /// Who may send which requests to the daemon
+    pub ipc: IpcConfig,
+    /// How much wallpaper scripts may take
+    pub scripts: ScriptsConfig,
+}
+
+/// Settings for outputs whose name matches a pattern
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputRule {
+    /// Output name pattern, `*` matches any run of characters and `?` a
+    /// single one, e.g. `eDP-*` or `*` for every output
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Name of the wallpaper shown if the output was not assigned one
+    pub wallpaper: Option<String>,
+    /// Resolution to render at relative to the output's, e.g. 0.5 for heavy
+    /// effects on a 4K screen
+    pub render_scale: Option<f32>,
+}
+
+impl OutputRule {
+    pub fn matches(&self, output: &str) -> bool {
+        glob_match(&self.pattern, output)
+    }
+}
+
+/// Which GPU the daemon renders with
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GpuConfig {
+    /// Adapter index or part of its name, chosen automatically if unset
+    pub adapter: Option<String>,
+    pub power_preference: PowerPreference,
+    /// Render on the CPU when no hardware adapter is usable
+    pub allow_software: bool,
+    /// GPU memory wallpaper textures may use in MiB, unlimited if unset
+    pub vram_budget: Option<u64>,
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            adapter: None,
+            power_preference: PowerPreference::default(),
+            allow_software: true,
+            vram_budget: None,
+        }
+    }
+}
+
+/// How image layers are prepared for the outputs they are shown on
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImagesConfig {
+    /// Shrink images larger than the largest output when loading them
+    pub downscale: bool,
+    /// Filter images are shrunk with
+    pub quality: DownscaleQuality,
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self {
+            downscale: true,
+            quality: DownscaleQuality::default(),
+        }
+    }
+}
+
+/// Wallpaper shown on an output whose wallpaper failed to load, instead of
+/// leaving it black
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorConfig {
+    /// Background color (CSS color)
+    pub color: String,
+    /// Image drawn over the color, filling the output
+    pub image: Option<PathBuf>,
+    /// Write what went wrong on the wallpaper
+    pub text: bool,
+}
+
+impl Default for ErrorConfig {
+    fn default() -> Self {
+        Self {
+            color: "#2a1416".to_string(),
+            image: None,
+            text: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DownscaleQuality {
+    /// Bilinear, quick but slightly soft
+    Fast,
+    /// Bicubic
+    #[default]
+    Balanced,
+    /// Lanczos, sharpest and slowest
+    Best,
+}
+
+/// What wallpaper scripts may fetch from the web, and how often
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchConfig {
+    /// Hosts scripts may fetch from, subdomains included, any if empty
+    pub hosts: Vec<String>,
+    /// Allow addresses of this machine and the local network
+    pub local: bool,
+    /// Seconds between two fetches of the same URL, at least
+    pub min_interval: u64,
+    /// Fetches a minute across all scripts, at most
+    pub max_per_minute: u32,
+    /// Seconds a fetch may take
+    pub timeout: u64,
+    /// Size of the largest document accepted, in bytes
+    pub max_size: u64,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            hosts: Vec::new(),
+            local: false,
+            min_interval: 60,
+            max_per_minute: 10,
+            timeout: 15,
+            max_size: 1024 * 1024,
+        }
+    }
+}
+
+/// Time, instructions and memory wallpaper scripts may take for each call,
+/// like an update, a script that goes over is stopped for good
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptsConfig {
+    /// Milliseconds a call may take
+    pub time_budget: u64,
+    /// Lua instructions a call may run
+    pub max_instructions: u64,
+    /// Memory a script may hold in MiB
+    pub max_memory: u64,
+}
+
+impl Default for ScriptsConfig {
+    fn default() -> Self {
+        Self {
+            time_budget: 8,
+            max_instructions: 5_000_000,
+            max_memory: 64,
+        }
+    }
+}
+
+/// Location and service the weather is looked up for
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeatherConfig {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub provider: WeatherProvider,
+    /// Key of providers that need one
+    pub api_key: Option<String>,
+    /// Minutes between lookups
+    #[serde(default = "default_weather_interval")]
+    pub interval: u64,
+}
+
+fn default_weather_interval() -> u64 {
+    15
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WeatherProvider {
+    /// open-meteo.com, free without a key
+    #[default]
+    OpenMeteo,
+    /// openweathermap.org, needs an `api_key`
+    #[serde(rename = "openweathermap")]
+    OpenWeatherMap,
+}
+
+/// Where the color scheme of the user's theme is read from
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorsConfig {
+    /// pywal's `colors.json` or the output of `matugen --json hex`, `~/` is
+    /// the home directory, pywal's cache if unset
+    pub file: Option<PathBuf>,
+    /// Variant of matugen schemes, pywal has only one
+    pub mode: ColorMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMode {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl From<ColorMode> for SchemeMode {
+    fn from(mode: ColorMode) -> Self {
+        match mode {
+            ColorMode::Dark => SchemeMode::Dark,
+            ColorMode::Light => SchemeMode::Light,
+        }
+    }
+}
+
+/// Shell commands the daemon runs on events, without waiting for them
+///
+/// They learn about the event from `WLRS_*` environment variables, e.g.
+/// `on_wallpaper_change = 'notify-send "$WLRS_OUTPUT" "$WLRS_WALLPAPER"'`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Run when an output shows a new wallpaper, with `WLRS_OUTPUT`,
+    /// `WLRS_WALLPAPER` and `WLRS_WALLPAPER_PATH`
+    pub on_wallpaper_change: Option<String>,
+    /// Run when an output appears, with `WLRS_OUTPUT` and
+    /// `WLRS_OUTPUT_DESCRIPTION`
+    pub on_output_added: Option<String>,
+    /// Run when a wallpaper fails to load, with `WLRS_OUTPUT`,
+    /// `WLRS_WALLPAPER` and `WLRS_ERROR`
+    pub on_error: Option<String>,
+}
+
+/// Limits on the processes talking to the daemon
+///
+/// Only processes of the user running the daemon can connect in any case.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IpcConfig {
+    /// Who may stop the daemon
+    pub stop: StopPolicy,
+    /// Programs allowed to stop the daemon whatever `stop` says, by
+    /// executable name, e.g. `wlrs` or `systemctl`
+    pub allow: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StopPolicy {
+    /// Any process of the user
+    #[default]
+    Anyone,
+    /// Processes started from a terminal, not scripts or services running
+    /// in the background
+    Interactive,
+    /// Only the programs in `allow`
+    Nobody,
+}
+
+/// What happens to the wallpapers after some time without input
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdleConfig {
+    /// Seconds without input before `action` applies, never if 0
+    ///
+    /// Wallpapers can set their own with `idle_timeout`.
+    pub timeout: u64,
+    pub action: IdleAction,
+    /// Seconds between two wallpapers of the slideshow
+    pub slideshow_interval: u64,
+    /// Only show wallpapers with all of these tags in the slideshow
+    pub slideshow_tags: Vec<String>,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            timeout: 0,
+            action: IdleAction::default(),
+            slideshow_interval: 600,
+            slideshow_tags: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdleAction {
+    /// Stop animating, the wallpaper stays as it is
+    #[default]
+    Pause,
+    /// Replace the wallpaper with its current frame, freeing its GPU
+    /// memory, it is loaded again on activity
+    Static,
+    /// Show random wallpapers, the last one stays on activity
+    Slideshow,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PowerPreference {
+    /// Let wgpu decide
+    #[default]
+    None,
+    /// Prefer an integrated GPU
+    LowPower,
+    /// Prefer a discrete GPU
+    HighPerformance,
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(preference: PowerPreference) -> Self {
+        match preference {
+            PowerPreference::None => wgpu::PowerPreference::None,
+            PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+            PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config file and apply command line overrides
+    ///
+    /// A missing file gives the defaults, an invalid one is reported and ignored.
+    pub fn load(args: &Args) -> Self {
+        let path = config_path();
+        let mut config = match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+                tracing::error!("Ignoring invalid config file {}: {e}", path.display());
+                Config::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(e) => {
+                tracing::error!("Failed to read config file {}: {e}", path.display());
+                Config::default()
+            }
+        };
+
+        if let Some(adapter) = &args.adapter {
+            config.gpu.adapter = Some(adapter.clone());
+        }
+        if let Some(preference) = args.power_preference {
+            config.gpu.power_preference = preference;
+        }
+        config
+    }
+
+    /// Whether wallpapers start out still, see `reduce_motion`
+    pub fn reduce_motion(&self) -> bool {
+        self.reduce_motion.unwrap_or_else(desktop_reduces_motion)
+    }
+}
+
+/// Whether animations are turned off in the desktop's accessibility
+/// settings, GNOME's and those of desktops sharing its settings schema
+fn desktop_reduces_motion() -> bool {
+    Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", "enable-animations"])
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| output.status.success() && output.stdout.trim_ascii() == b"false")
+}
+
+/// Replace a leading `~` with the home directory
+pub fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), directories::BaseDirs::new()) {
+        (Ok(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => path,
+    }
+}
+
+/// Match `text` against a shell-style pattern with `*` and `?` wildcards
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Pattern position after the last `*` and the text position it resumes at
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, t));
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            // Let the last `*` swallow one more character and try again
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    star = Some((star_p, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn config_path() -> PathBuf {
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.config_dir().join("wlrs").join("config.toml"))
+        .unwrap_or_else(|| PathBuf::from("/tmp/wlrs/config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("DP-1", "DP-1"));
+        assert!(!glob_match("DP-1", "DP-10"));
+        assert!(glob_match("DP-*", "DP-10"));
+        assert!(glob_match("DP-*", "DP-"));
+        assert!(glob_match("HDMI-A-?", "HDMI-A-1"));
+        assert!(!glob_match("HDMI-A-?", "HDMI-A-"));
+        assert!(!glob_match("HDMI-A-?", "HDMI-A-12"));
+        // The first `*` gives characters back until the rest matches
+        assert!(glob_match("*-*-1", "HDMI-A-1"));
+        assert!(glob_match("*1", "eDP-11"));
+        assert!(!glob_match("*2", "eDP-11"));
+        assert!(glob_match("**", ""));
+        assert!(!glob_match("", "eDP-1"));
+        // Wildcards stand for characters, not bytes
+        assert!(glob_match("Écran-?", "Écran-é"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    process::{Command, Stdio},
+    sync::OnceLock,
+    thread,
+};
+
+use crate::config::HooksConfig;
+
+static HOOKS: OnceLock<HooksConfig> = OnceLock::new();
+
+/// Set the commands run on events, before any event happens
+pub fn init(config: HooksConfig) {
+    if HOOKS.set(config).is_err() {
+        tracing::warn!("Hooks were run before they were configured");
+    }
+}
+
+/// Something a hook can react to
+pub enum Event<'a> {
+    /// An output shows a new wallpaper
+    WallpaperChanged {
+        output: &'a str,
+        wallpaper: &'a str,
+        path: &'a str,
+    },
+    /// An output appeared, at startup or when it was plugged in
+    OutputAdded {
+        output: &'a str,
+        description: &'a str,
+    },
+    /// A wallpaper failed to load
+    Error {
+        output: &'a str,
+        wallpaper: &'a str,
+        message: &'a str,
+    },
+}
+
+impl Event<'_> {
+    /// The command configured for the event, if any
+    fn command(&self, hooks: &'static HooksConfig) -> Option<&'static str> {
+        match self {
+            Event::WallpaperChanged { .. } => hooks.on_wallpaper_change.as_deref(),
+            Event::OutputAdded { .. } => hooks.on_output_added.as_deref(),
+            Event::Error { .. } => hooks.on_error.as_deref(),
+        }
+    }
+
+    /// What the command gets to know about the event, as environment
+    /// variables
+    fn env(&self) -> Vec<(&'static str, &str)> {
+        match *self {
+            Event::WallpaperChanged {
+                output,
+                wallpaper,
+                path,
+            } => vec![
+                ("WLRS_EVENT", "wallpaper_change"),
+                ("WLRS_OUTPUT", output),
+                ("WLRS_WALLPAPER", wallpaper),
+                ("WLRS_WALLPAPER_PATH", path),
+            ],
+            Event::OutputAdded {
+                output,
+                description,
+            } => vec![
+                ("WLRS_EVENT", "output_added"),
+                ("WLRS_OUTPUT", output),
+                ("WLRS_OUTPUT_DESCRIPTION", description),
+            ],
+            Event::Error {
+                output,
+                wallpaper,
+                message,
+            } => vec![
+                ("WLRS_EVENT", "error"),
+                ("WLRS_OUTPUT", output),
+                ("WLRS_WALLPAPER", wallpaper),
+                ("WLRS_ERROR", message),
+            ],
+        }
+    }
+}
+
+/// Run the hook of `event` with `sh -c`, without waiting for it
+///
+/// A thread waits for the command instead, so it does not linger as a
+/// zombie and its failure is logged.
+pub fn run(event: Event) {
+    let Some(command) = HOOKS.get().and_then(|hooks| event.command(hooks)) else {
+        return;
+    };
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(event.env())
+        .stdin(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            tracing::warn!("Failed to run hook `{command}`: {e}");
+            return;
+        }
+    };
+    thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => {
+            tracing::warn!("Hook `{command}` failed with {status}")
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Failed to wait for hook `{command}`: {e}"),
+    });
+}
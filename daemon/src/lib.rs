@@ -1,10 +1,24 @@
-#![feature(thread_sleep_until)]
-
 use std::sync::{LazyLock, Mutex};
 
+pub mod access;
 pub mod asset;
+pub mod colors;
+pub mod config;
+pub mod hooks;
+pub mod library;
+pub mod logging;
+pub mod metrics;
+#[cfg(feature = "mpris")]
+pub mod mpris;
 pub mod renderer;
+pub mod script;
 pub mod shaders;
+pub mod state;
+pub mod systemd;
+pub mod timer;
 pub mod utils;
+pub mod wake;
+pub mod watchdog;
+pub mod weather;
 
 pub static EXIT: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(false));
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+
+use common::wallpaper::Wallpaper;
+
+use crate::config::expand_home;
+
+/// Directories wallpapers are looked up in, see `search_paths`
+static SEARCH_PATHS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+/// Repositories wallpapers can be fetched from, see `repositories`
+static REPOSITORIES: OnceLock<Vec<String>> = OnceLock::new();
+/// Search paths listed and wallpapers parsed so far, see `wallpapers`
+static INDEX: Mutex<Option<Index>> = Mutex::new(None);
+
+#[derive(Clone, Default)]
+struct Index {
+    listings: HashMap<PathBuf, Listing>,
+    /// Wallpapers by their directory
+    wallpapers: HashMap<PathBuf, Indexed>,
+}
+
+/// A search path as it was when it was last listed
+#[derive(Clone)]
+struct Listing {
+    modified: Option<SystemTime>,
+    entries: Vec<PathBuf>,
+}
+
+/// A wallpaper directory as it was when its manifest was last parsed
+#[derive(Clone)]
+struct Indexed {
+    /// Modification times of the directory and of its manifest
+    modified: (Option<SystemTime>, Option<SystemTime>),
+    /// None if the wallpaper failed to load, so it is not parsed again
+    /// until it changes
+    wallpaper: Option<Arc<Wallpaper>>,
+}
+
+/// Add the directories and repositories named in the config, before any
+/// wallpaper is looked up
+pub fn init(extra: Vec<PathBuf>, repositories: Vec<String>) {
+    if SEARCH_PATHS.set(collect(extra)).is_err() {
+        tracing::warn!("Wallpapers were looked up before the search paths were set");
+    }
+    let _ = REPOSITORIES.set(repositories);
+}
+
+/// Repositories the frontend fetches wallpapers from, as configured
+pub fn repositories() -> &'static [String] {
+    REPOSITORIES.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Directories wallpapers are looked up in, in order of precedence
+///
+/// The user's directory comes first, then the ones from the config in their
+/// order, then the system-wide ones from `$XDG_DATA_DIRS`. When two of them
+/// have a wallpaper of the same name, the earlier one is used.
+pub fn search_paths() -> &'static [PathBuf] {
+    SEARCH_PATHS.get_or_init(|| collect(Vec::new()))
+}
+
+/// Every valid wallpaper in the search paths, in their order
+///
+/// Search paths are only listed again when they changed, and manifests only
+/// parsed again when they or their directory changed, otherwise looking the
+/// wallpapers up costs a few `stat`s. The index is only locked to copy and
+/// store it, other threads do not wait for the scan.
+pub fn wallpapers() -> Vec<Arc<Wallpaper>> {
+    let previous = INDEX.lock().unwrap().clone().unwrap_or_default();
+    let mut current = Index::default();
+    let mut wallpapers = Vec::new();
+
+    for path in search_paths() {
+        let changed = modified(path);
+        let listing = match previous.listings.get(path) {
+            Some(listing) if changed.is_some() && listing.modified == changed => listing.clone(),
+            _ => Listing {
+                modified: changed,
+                entries: entries(path),
+            },
+        };
+        for dir in &listing.entries {
+            let changed = (modified(dir), modified(&dir.join("manifest.toml")));
+            if changed.1.is_none() {
+                continue;
+            }
+            let indexed = match previous.wallpapers.get(dir) {
+                Some(indexed) if indexed.modified == changed => indexed.clone(),
+                _ => Indexed {
+                    modified: changed,
+                    wallpaper: load(dir),
+                },
+            };
+            wallpapers.extend(indexed.wallpaper.clone());
+            current.wallpapers.insert(dir.clone(), indexed);
+        }
+        current.listings.insert(path.clone(), listing);
+    }
+
+    *INDEX.lock().unwrap() = Some(current);
+    wallpapers
+}
+
+fn load(dir: &Path) -> Option<Arc<Wallpaper>> {
+    Wallpaper::load(dir)
+        .inspect(|wallpaper| {
+            for warning in &wallpaper.warnings {
+                tracing::warn!("Wallpaper {}: {warning}", dir.display());
+            }
+        })
+        .inspect_err(|e| tracing::warn!("Skipping wallpaper {}: {e}", dir.display()))
+        .ok()
+        .map(Arc::new)
+}
+
+/// Entries of a search path that may be wallpapers, none if it cannot be
+/// read
+///
+/// Hidden directories are skipped, `wlrs fetch` assembles downloads in them.
+/// Whether an entry has a manifest is checked on every scan, as adding one
+/// does not change the search path.
+fn entries(path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|dir| {
+            dir.file_name()
+                .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+        })
+        .collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Where wallpapers are installed, the user's directory
+pub fn install_dir() -> PathBuf {
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.data_dir().join("wlrs").join("wallpapers"))
+        .unwrap_or_else(|| PathBuf::from("/tmp/wlrs/wallpapers"))
+}
+
+fn collect(extra: Vec<PathBuf>) -> Vec<PathBuf> {
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    let system = data_dirs
+        .split(':')
+        .filter(|dir| Path::new(dir).is_absolute())
+        .map(|dir| Path::new(dir).join("wlrs").join("wallpapers"));
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for path in [install_dir()]
+        .into_iter()
+        .chain(extra.into_iter().map(expand_home))
+        .chain(system)
+    {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
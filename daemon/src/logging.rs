@@ -0,0 +1,57 @@
+use std::sync::OnceLock;
+
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter, Registry};
+
+/// Filter used when `RUST_LOG` is not set
+const DEFAULT_FILTER: &str = "info";
+
+/// Handle for swapping the filter while the daemon runs
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Install the global subscriber, writing to stderr or to the journal
+///
+/// Records from crates that use `log`, like wgpu, are forwarded as well.
+pub fn init(journald: bool) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let (filter, handle) = reload::Layer::new(filter);
+    let journald = journald_layer(journald);
+    let stderr = journald.is_none().then(fmt::layer);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(journald)
+        .with(stderr)
+        .init();
+    let _ = FILTER.set(handle);
+}
+
+/// Replace the filter, using the `RUST_LOG` syntax like `debug` or
+/// `info,daemon::renderer=trace`
+pub fn set_filter(directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(directives)
+        .map_err(|e| format!("Invalid log filter '{directives}': {e}"))?;
+    FILTER
+        .get()
+        .ok_or("Logging is not initialized")?
+        .reload(filter)
+        .map_err(|e| format!("Failed to change the log filter: {e}"))
+}
+
+#[cfg(feature = "journald")]
+fn journald_layer(enabled: bool) -> Option<tracing_journald::Layer> {
+    if !enabled {
+        return None;
+    }
+    tracing_journald::layer()
+        .inspect_err(|e| eprintln!("Failed to connect to the journal, logging to stderr: {e}"))
+        .ok()
+}
+
+#[cfg(not(feature = "journald"))]
+fn journald_layer(enabled: bool) -> Option<tracing_subscriber::layer::Identity> {
+    if enabled {
+        eprintln!("Built without journald support, logging to stderr");
+    }
+    None
+}
@@ -1,30 +1,114 @@
 use epoll::Events;
+use std::io;
 use std::os::fd::{AsFd, AsRawFd};
-use std::path::Path;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
 
+use clap::Parser;
 use common::{
-    ipc::{IpcSocket, Listener},
+    ipc::{self, IpcError, IpcSocket, Listener, Stream},
     types::{
-        ActiveWallpaperInfo, ActiveWallpaperList, Health, InstallDirectory, Request, Response,
-        ServerStopping, WallpaperList, WallpaperLoaded,
+        ActiveWallpaperInfo, ActiveWallpaperList, CacheCleared, CacheStats, Health,
+        InstallDirectory, LayerTiming, LogLevelSet, MotionSet, RandomWallpaperSet, Request,
+        Response, ServerStopping, SetRandomWallpaper, StopServer,
     },
     wallpaper::Wallpaper,
 };
-use daemon::renderer::client::Client;
+use daemon::access;
+use daemon::colors;
+use daemon::config::{Args, Config};
+use daemon::hooks;
+use daemon::library;
+use daemon::logging;
+use daemon::renderer::client::{list_adapters, Client};
+use daemon::renderer::fallback;
+use daemon::renderer::memory;
+use daemon::renderer::prescale;
+use daemon::renderer::profiler::DEFAULT_FRAME_BUDGET;
+use daemon::script;
+use daemon::state::State;
+use daemon::systemd;
+use daemon::timer::{self, Timer};
 use daemon::utils::*;
+use daemon::wake;
+use daemon::watchdog;
+use daemon::weather;
+use wayland_client::{backend::WaylandError, EventQueue, QueueHandle};
+
+/// Longest wait between attempts to reconnect to the compositor
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Wait between attempts to recreate a lost GPU device
+const DEVICE_RECOVERY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long `--replace` waits for the running daemon to exit
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an IPC client may take to send its request or read the answer,
+/// the wallpapers are not drawn meanwhile
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
 
 fn main() {
-    env_logger::init();
+    let args = Args::parse();
+    // Before anything derives a path from it
+    ipc::set_namespace(args.namespace.clone());
+    if args.list_adapters {
+        list_adapters();
+        return;
+    }
+    logging::init(args.journald);
+    if args.watchdog {
+        if args.systemd {
+            tracing::warn!("Ignoring --watchdog, systemd restarts the daemon after a crash");
+        } else {
+            std::process::exit(watchdog::supervise());
+        }
+    }
+    let config = Config::load(&args);
+    let reduce_motion = config.reduce_motion();
+    // Before the threads that wake the main loop start
+    let wake_fd = wake::init().expect("Failed to create the wake event");
+    script::data::init(config.fetch);
+    script::limits::init(config.scripts);
+    library::init(config.wallpaper_dirs, config.repositories);
+    if let Some(weather) = config.weather {
+        weather::start(weather);
+    }
+    colors::start(config.colors);
+    hooks::init(config.hooks);
+    memory::init(config.gpu.vram_budget);
+    prescale::init(config.images);
+    fallback::init(config.error);
+    access::init(config.ipc);
+
+    // Started as a systemd service: report readiness and accept a socket
+    // passed by socket activation
+    let systemd_mode = args.systemd;
+    let stream = match systemd_mode.then(systemd::take_listener).flatten() {
+        Some(listener) => {
+            tracing::info!("Using the IPC socket passed by systemd");
+            IpcSocket::from_listener(listener)
+        }
+        None => listen(args.replace),
+    };
 
     // Ensure wallpaper directory exists
     ensure_wallpaper_directory();
+    let mut state = State::load();
 
     // Create initial wallpaper state with manager
-    let (mut client, mut event_queue) = Client::new(Some("wlrs"));
-    let stream = IpcSocket::<Listener>::listen()
-        .expect("A ipc socket need to be created for client-server functionality");
+    let namespace = match &args.namespace {
+        Some(namespace) => format!("wlrs-{namespace}"),
+        None => "wlrs".to_string(),
+    };
+    let (mut client, mut event_queue) =
+        Client::new(Some(namespace), config.gpu, config.outputs, config.idle);
+    client.set_motion(Some(!reduce_motion));
+    if args.replace || args.restore {
+        restore_outputs(&mut client, &state);
+    }
 
-    let wayland_event_fd = event_queue.as_fd().as_raw_fd();
+    let mut wayland_event_fd = event_queue.as_fd().as_raw_fd();
     let client_event_fd = stream.as_fd().as_raw_fd();
 
     let ep = epoll::create(false).expect("Epoll create failed");
@@ -44,137 +128,177 @@ fn main() {
         client_event,
     )
     .expect("Epoll ctl failed");
+    epoll::ctl(
+        ep,
+        epoll::ControlOptions::EPOLL_CTL_ADD,
+        wake_fd,
+        epoll::Event::new(Events::EPOLLIN, wake_fd as u64),
+    )
+    .expect("Epoll ctl failed");
+    let mut timer = Timer::new().expect("Failed to create the frame timer");
+    let timer_fd = timer.fd();
+    epoll::ctl(
+        ep,
+        epoll::ControlOptions::EPOLL_CTL_ADD,
+        timer_fd,
+        epoll::Event::new(Events::EPOLLIN, timer_fd as u64),
+    )
+    .expect("Epoll ctl failed");
 
     // Pre-allocate events array for epoll
-    let mut events = [epoll::Event::new(Events::empty(), 0); 2];
+    let mut events = [epoll::Event::new(Events::empty(), 0); 4];
     let mut wayland_event_ready = false;
     let mut client_event_ready = false;
 
-    // Frame counter for animation timing (roughly ~60 frames per second)
-    let qh = event_queue.handle();
-    let mut last_render_time = std::time::Instant::now();
-    let target_frame_time = std::time::Duration::from_millis(32); // ~60 FPS
+    let mut qh = event_queue.handle();
+    let mut ready_notified = !systemd_mode;
+    let mut last_device_recovery: Option<Instant> = None;
 
     loop {
-        // Handle rendering frames
-        let current_time = std::time::Instant::now();
-        if current_time.duration_since(last_render_time) >= target_frame_time {
-            // Render a new frame
-            client.request_update(&qh);
-            last_render_time = current_time;
+        // A GPU reset invalidates everything built on the device
+        if client.is_device_lost()
+            && last_device_recovery.is_none_or(|last| last.elapsed() >= DEVICE_RECOVERY_INTERVAL)
+        {
+            last_device_recovery = Some(Instant::now());
+            match client.recover_lost_device() {
+                Ok(()) => tracing::info!("Recreated the GPU device"),
+                Err(e) => tracing::error!("Failed to recreate the GPU device: {e}"),
+            }
+        }
+
+        if weather::take_change() {
+            client.weather_changed();
+        }
+        if colors::take_change() {
+            client.colors_changed();
+        }
+
+        // Swap in any wallpapers the loader finished since the last iteration
+        client.apply_finished_loads();
+        client.update_idle_notifications(&qh);
+        for output in client.slideshow_due() {
+            let request = SetRandomWallpaper {
+                tags: client.idle.config.slideshow_tags.clone(),
+                exclude: Vec::new(),
+                exclude_current: true,
+                monitor: Some(output.clone()),
+            };
+            if let Response::RandomWallpaperSet(RandomWallpaperSet {
+                success: false,
+                error,
+                ..
+            }) = handle_set_random_wallpaper(&request, &mut client, &mut state)
+            {
+                tracing::warn!(
+                    "Slideshow on {output} failed: {}",
+                    error.unwrap_or_else(|| "Unknown error".to_string())
+                );
+            }
         }
 
-        event_queue.flush().unwrap();
+        // Draw what is due, frame callbacks draw animated layers as soon as
+        // the compositor wants their next frame
+        client.draw_due(&qh);
+
+        // Errors on the Wayland connection mean the compositor went away
+        let mut wayland_error = None;
+        if let Err(e) = event_queue.flush() {
+            if is_connection_lost(&e) {
+                wayland_error = Some(e.to_string());
+            }
+        }
         let wayland_event_read_guard = event_queue.prepare_read();
         if wayland_event_read_guard.is_none() {
-            event_queue
-                .dispatch_pending(&mut client)
-                .expect("Failed to dispatch wayland events");
+            if let Err(e) = event_queue.dispatch_pending(&mut client) {
+                wayland_error.get_or_insert(e.to_string());
+            }
         }
 
-        // Wait for events with epoll with a timeout to ensure animations continue
-        let tickrate = 5; // Short timeout to ensure animations remain smooth
-        let num_events = epoll::wait(ep, tickrate, &mut events).unwrap();
+        // Sleep until an event arrives or the timer fires for the next
+        // frame, slide or device recovery, indefinitely when nothing animates
+        let recovery = client.is_device_lost().then(|| {
+            last_device_recovery.map_or_else(Instant::now, |last| last + DEVICE_RECOVERY_INTERVAL)
+        });
+        if let Err(e) = timer.set(timer::next_wake(client.next_deadline(), recovery)) {
+            tracing::error!("Failed to set the frame timer, exiting: {e}");
+            break;
+        }
+        let timeout = timer::wait_timeout(wayland_event_read_guard.is_none());
+        let num_events = match epoll::wait(ep, timeout, &mut events) {
+            Ok(num_events) => num_events,
+            // A signal arrived, nothing to handle this time around
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => 0,
+            Err(e) => {
+                tracing::error!("Waiting for events failed, exiting: {e}");
+                break;
+            }
+        };
 
         // Only process the number of events that were returned
         (0..num_events).for_each(|i| {
             let event = &events[i];
             if event.data == wayland_event_fd as u64 {
-                log::debug!("Wayland event ready");
+                tracing::debug!("Wayland event ready");
                 wayland_event_ready = true;
             } else if event.data == client_event_fd as u64 {
-                log::debug!("Client event ready");
+                tracing::debug!("Client event ready");
                 client_event_ready = true;
+            } else if event.data == wake_fd as u64 {
+                wake::clear();
+            } else if event.data == timer_fd as u64 {
+                timer.clear();
             }
         });
 
         if let Some(wayland_event_read_guard) = wayland_event_read_guard {
-            log::debug!("Wayland event read guard");
-            wayland_event_read_guard.read().unwrap();
+            tracing::debug!("Wayland event read guard");
+            if let Err(e) = wayland_event_read_guard.read() {
+                if is_connection_lost(&e) {
+                    wayland_error.get_or_insert(e.to_string());
+                }
+            }
             if wayland_event_ready {
-                event_queue
-                    .dispatch_pending(&mut client)
-                    .expect("Failed to dispatch wayland events");
+                if let Err(e) = event_queue.dispatch_pending(&mut client) {
+                    wayland_error.get_or_insert(e.to_string());
+                }
             }
         }
 
-        if client_event_ready {
-            // stream.handle_request(handler).unwrap();
-            let mut client_socket = stream.accept().unwrap();
-            let request: Request = client_socket.receive().unwrap();
-            let response = match request {
-                Request::Checkhealth(_) => Response::Health(Health(true)),
-                Request::LoadWallpaper(req) => {
-                    // Try to load the wallpaper from the specified path
-                    match Wallpaper::load(&req.path) {
-                        Ok(wallpaper) => Response::WallpaperLoaded(WallpaperLoaded {
-                            name: wallpaper.name().to_string(),
-                            success: true,
-                            error: None,
-                        }),
-                        Err(e) => Response::WallpaperLoaded(WallpaperLoaded {
-                            name: Path::new(&req.path)
-                                .file_name()
-                                .and_then(|n| n.to_str())
-                                .unwrap_or("unknown")
-                                .to_string(),
-                            success: false,
-                            error: Some(format!("Failed to load wallpaper: {e}")),
-                        }),
-                    }
-                }
-                Request::StopServer(_) => {
-                    *daemon::EXIT.lock().unwrap() = true;
-                    Response::ServerStopping(ServerStopping {
-                        success: *daemon::EXIT.lock().unwrap(),
-                    })
-                }
-                Request::ListWallpapers(_) => {
-                    // Scan for available wallpapers in the standard directories
-                    let wallpapers = find_available_wallpapers();
-                    Response::WallpaperList(WallpaperList { wallpapers })
-                }
-                Request::SetCurrentWallpaper(req) => handle_set_wallpaper(&req, &mut client),
-                Request::QueryActiveWallpapers(_) => {
-                    // Get information about active wallpapers from client.wallpapers
-                    let mut active_wallpapers = Vec::new();
-
-                    // Iterate through wallpapers in client
-                    for layer in client.wallpapers.iter() {
-                        active_wallpapers.push(ActiveWallpaperInfo {
-                            name: layer.name.clone(),
-                            output_name: layer.name.clone(), // Using the same name since it's derived from output name
-                            width: layer.width,
-                            height: layer.height,
-                        });
-                    }
+        if let Some(e) = wayland_error {
+            tracing::error!("Lost the connection to the compositor: {e}");
+            // Stop watching the dead socket, it would report a hangup forever
+            let _ = epoll::ctl(
+                ep,
+                epoll::ControlOptions::EPOLL_CTL_DEL,
+                wayland_event_fd,
+                epoll::Event::new(Events::empty(), 0),
+            );
+            event_queue = reconnect(&mut client);
 
-                    Response::ActiveWallpaperList(ActiveWallpaperList {
-                        wallpapers: active_wallpapers,
-                        success: true,
-                        error: None,
-                    })
-                }
-                Request::GetInstallDirectory(_) => {
-                    // Return the standardized XDG data directory for wallpaper installations
-                    let install_dir = directories::BaseDirs::new()
-                        .map(|dirs| {
-                            dirs.data_dir()
-                                .join("wlrs")
-                                .join("wallpapers")
-                                .to_string_lossy()
-                                .to_string()
-                        })
-                        .unwrap_or_else(|| String::from("/tmp/wlrs/wallpapers"));
-
-                    Response::InstallDirectory(InstallDirectory {
-                        path: install_dir,
-                        success: true,
-                        error: None,
-                    })
-                }
-            };
-            client_socket.send(&response).unwrap();
+            wayland_event_fd = event_queue.as_fd().as_raw_fd();
+            epoll::ctl(
+                ep,
+                epoll::ControlOptions::EPOLL_CTL_ADD,
+                wayland_event_fd,
+                epoll::Event::new(Events::EPOLLIN, wayland_event_fd as u64),
+            )
+            .expect("Epoll ctl failed");
+            qh = event_queue.handle();
+        }
+
+        // The daemon is ready once the first output shows a wallpaper surface
+        if !ready_notified && client.wallpapers.iter().any(|layer| layer.configured) {
+            if let Err(e) = systemd::notify("READY=1") {
+                tracing::warn!("Failed to notify systemd: {e}");
+            }
+            ready_notified = true;
+        }
+
+        if client_event_ready {
+            // A misbehaving client only loses its own connection
+            if let Err(e) = serve_client(&stream, &mut client, &mut state, &qh) {
+                tracing::warn!("Failed to serve IPC client: {e}");
+            }
         }
 
         wayland_event_ready = false;
@@ -183,4 +307,282 @@ fn main() {
             break;
         }
     }
+
+    if systemd_mode {
+        let _ = systemd::notify("STOPPING=1");
+    }
+}
+
+/// Accept one IPC connection, answer its request and close it
+fn serve_client(
+    stream: &IpcSocket<Listener>,
+    client: &mut Client,
+    state: &mut State,
+    qh: &QueueHandle<Client>,
+) -> Result<(), IpcError> {
+    let mut client_socket = stream.accept()?;
+    client_socket.set_timeout(Some(CLIENT_TIMEOUT))?;
+    let peer = client_socket.peer()?;
+    if !access::may_connect(&peer) {
+        tracing::warn!(
+            "Turning away a connection from uid {} (pid {})",
+            peer.uid,
+            peer.pid
+        );
+        return Ok(());
+    }
+    // Decoding runs client input too, keep the daemon alive if it panics
+    let request: Request = match panic::catch_unwind(AssertUnwindSafe(|| client_socket.receive())) {
+        Ok(request) => request?,
+        Err(_) => {
+            tracing::error!("Decoding an IPC request panicked, dropping the connection");
+            return Ok(());
+        }
+    };
+    tracing::debug!("Received request from pid {}: {request:?}", peer.pid);
+
+    if let Request::StopServer(_) = request {
+        if let Err(error) = access::check_stop(&peer) {
+            client_socket.send(&Response::ServerStopping(ServerStopping {
+                success: false,
+                error: Some(error),
+            }))?;
+            return Ok(());
+        }
+    }
+
+    // Most requests are answered right away, slow ones from a loader thread
+    let reply: Reply = Box::new(move |response| {
+        if let Err(e) = client_socket.send(&response) {
+            tracing::warn!("Failed to answer IPC client: {e}");
+        }
+    });
+    // Keep the daemon alive if a handler panics, the client just gets no answer
+    if panic::catch_unwind(AssertUnwindSafe(|| {
+        handle_request(request, client, state, qh, reply)
+    }))
+    .is_err()
+    {
+        tracing::error!("Handling an IPC request panicked, dropping the connection");
+    }
+    Ok(())
+}
+
+fn handle_request(
+    request: Request,
+    client: &mut Client,
+    state: &mut State,
+    qh: &QueueHandle<Client>,
+    reply: Reply,
+) {
+    let response = match request {
+        Request::Checkhealth(_) => Response::Health(Health(true)),
+        Request::LoadWallpaper(req) => handle_load_wallpaper(&req, client),
+        Request::StopServer(_) => {
+            *daemon::EXIT.lock().unwrap() = true;
+            Response::ServerStopping(ServerStopping {
+                success: *daemon::EXIT.lock().unwrap(),
+                error: None,
+            })
+        }
+        Request::ListWallpapers(req) => handle_list_wallpapers(&req, client, state),
+        Request::SetCurrentWallpaper(req) => handle_set_wallpaper(&req, client, state),
+        Request::QueryActiveWallpapers(_) => {
+            // Get information about active wallpapers from client.wallpapers
+            let mut active_wallpapers = Vec::new();
+
+            // Iterate through wallpapers in client, outputs without one are not listed
+            for layer in client.wallpapers.iter() {
+                let Some(wallpaper) = client.assignment(&layer.name) else {
+                    continue;
+                };
+                let Some(info) = client.output.info(&layer.output) else {
+                    continue;
+                };
+                let pipelines = layer.wallpaper.lock().unwrap();
+                let layers = pipelines
+                    .labels()
+                    .zip(&pipelines.data)
+                    .zip(layer.profiler.costs())
+                    .map(|((label, model), cost)| LayerTiming {
+                        name: label.to_string(),
+                        cpu_us: cost.cpu.as_micros() as u64,
+                        gpu_us: cost.gpu.map(|gpu| gpu.as_micros() as u64),
+                        over_budget: cost.over_budget,
+                        warning: model.warning(),
+                    })
+                    .collect();
+                drop(pipelines);
+                let (x, y) = info.logical_position.unwrap_or(info.location);
+                active_wallpapers.push(ActiveWallpaperInfo {
+                    name: wallpaper.name().to_string(),
+                    output_name: layer.name.clone(),
+                    description: info.description,
+                    make: info.make,
+                    model: info.model,
+                    x,
+                    y,
+                    width: layer.width,
+                    height: layer.height,
+                    mirror_of: layer.mirror_of.clone(),
+                    render_scale: layer.render_scale(),
+                    adjustment: layer.adjustment(),
+                    overview: layer.overview.enabled(),
+                    frame_budget_us: layer
+                        .get_recommended_update_interval()
+                        .unwrap_or(DEFAULT_FRAME_BUDGET)
+                        .as_micros() as u64,
+                    layers,
+                });
+            }
+
+            Response::ActiveWallpaperList(ActiveWallpaperList {
+                wallpapers: active_wallpapers,
+                success: true,
+                error: None,
+            })
+        }
+        Request::GetInstallDirectory(_) => Response::InstallDirectory(InstallDirectory {
+            path: library::install_dir().to_string_lossy().to_string(),
+            search_paths: library::search_paths()
+                .iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+            repositories: library::repositories().to_vec(),
+            success: true,
+            error: None,
+        }),
+        Request::GetCacheStats(_) => {
+            let stats = client.asset_cache.lock().unwrap().stats();
+            Response::CacheStats(CacheStats {
+                entries: stats.entries,
+                bytes: stats.bytes,
+                budget: stats.budget,
+                hits: stats.hits,
+                misses: stats.misses,
+                evictions: stats.evictions,
+                gpu_bytes: memory::used(),
+                gpu_budget: memory::budget(),
+            })
+        }
+        Request::ClearCache(_) => {
+            let released = client.asset_cache.lock().unwrap().clear();
+            Response::CacheCleared(CacheCleared {
+                entries: released.entries,
+                bytes: released.bytes,
+            })
+        }
+        Request::SetFavorite(req) => handle_set_favorite(&req, state),
+        Request::GetWallpaperStats(req) => handle_get_wallpaper_stats(&req, state),
+        Request::SetRandomWallpaper(req) => handle_set_random_wallpaper(&req, client, state),
+        Request::SetLogLevel(req) => {
+            let result = logging::set_filter(&req.filter);
+            if result.is_ok() {
+                tracing::info!("Log filter set to {}", req.filter);
+            }
+            Response::LogLevelSet(LogLevelSet {
+                success: result.is_ok(),
+                error: result.err(),
+            })
+        }
+        Request::GetStats(_) => handle_get_stats(client),
+        Request::MirrorOutput(req) => handle_mirror_output(&req, client),
+        Request::SetRenderScale(req) => handle_set_render_scale(&req, client),
+        Request::SetDisplayAdjustment(req) => handle_set_display_adjustment(&req, client),
+        Request::SetOverviewMode(req) => handle_set_overview_mode(&req, client),
+        Request::SetColorScheme(req) => handle_set_color_scheme(&req),
+        Request::ExtractColors(req) => return handle_extract_colors(&req, client, reply),
+        Request::CaptureFrame(req) => handle_capture_frame(&req, client, qh),
+        Request::SetMotion(req) => Response::MotionSet(MotionSet {
+            enabled: client.set_motion(req.enabled),
+        }),
+        Request::SetWallpaperSetting(req) => handle_set_wallpaper_setting(&req, client, state),
+        Request::GetWallpaperSettings(req) => handle_get_wallpaper_settings(&req, state),
+        Request::DescribeWallpaper(req) => handle_describe_wallpaper(&req, state),
+        Request::PlanWallpaper(req) => handle_plan_wallpaper(&req, client, state),
+        Request::SetPermissions(req) => handle_set_permissions(&req, client, state),
+    };
+    reply(response);
+}
+
+/// Bind the IPC socket, asking a running daemon to stop first if `replace` is set
+///
+/// The old daemon releases its lock only after its surfaces are gone, so once
+/// the socket is bound no layers of the old daemon are left on screen.
+fn listen(replace: bool) -> IpcSocket<Listener> {
+    let deadline = Instant::now() + REPLACE_TIMEOUT;
+    let mut stop_requested = false;
+    loop {
+        match IpcSocket::<Listener>::listen() {
+            Ok(stream) => return stream,
+            Err(e @ IpcError::AlreadyRunning(_)) if !replace => {
+                tracing::error!("{e}, stop it with 'wlrs stop' or take over with --replace");
+                std::process::exit(watchdog::ALREADY_RUNNING);
+            }
+            Err(e @ IpcError::AlreadyRunning(_)) => {
+                if !stop_requested {
+                    tracing::info!("{e}, asking it to stop");
+                    let stopped = IpcSocket::<Stream>::connect()
+                        .and_then(|mut running| running.request(StopServer));
+                    match stopped {
+                        Ok(ServerStopping {
+                            error: Some(error), ..
+                        }) => tracing::warn!("The running daemon refused to stop: {error}"),
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!("Failed to ask the running daemon to stop: {e}"),
+                    }
+                    stop_requested = true;
+                } else if Instant::now() >= deadline {
+                    tracing::error!("{e} and did not stop within {REPLACE_TIMEOUT:?}");
+                    std::process::exit(watchdog::ALREADY_RUNNING);
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => {
+                panic!("A ipc socket need to be created for client-server functionality: {e}")
+            }
+        }
+    }
+}
+
+/// Show the wallpapers the previous daemon had on each output
+fn restore_outputs(client: &mut Client, state: &State) {
+    for (output, path, variant) in state.outputs() {
+        let wallpaper = Wallpaper::load(path)
+            .map_err(|e| e.to_string())
+            .and_then(|wallpaper| customize(wallpaper, variant, state));
+        match wallpaper {
+            Ok(wallpaper) => {
+                tracing::info!("Restoring wallpaper {} on {output}", wallpaper.name());
+                client.assign(output, wallpaper);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to restore the wallpaper of {output}: {e}");
+                client.show_error(output, &e);
+            }
+        }
+    }
+}
+
+/// Whether a Wayland error means the connection to the compositor is gone
+///
+/// A full socket buffer is only a reason to try again later.
+fn is_connection_lost(error: &WaylandError) -> bool {
+    !matches!(error, WaylandError::Io(e) if e.kind() == io::ErrorKind::WouldBlock)
+}
+
+/// Connect to the compositor again, retrying with exponential backoff
+fn reconnect(client: &mut Client) -> EventQueue<Client> {
+    let mut delay = Duration::from_millis(500);
+    loop {
+        match client.reconnect() {
+            Ok(event_queue) => {
+                tracing::info!("Reconnected to the compositor");
+                return event_queue;
+            }
+            Err(e) => tracing::warn!("{e}, retrying in {delay:?}"),
+        }
+        std::thread::sleep(delay);
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
 }
@@ -0,0 +1,163 @@
+use std::{
+    fs,
+    sync::{Mutex, Once},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time between two samples, shaders and scripts read the latest one
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+static CURRENT: Mutex<Metrics> = Mutex::new(Metrics::UNKNOWN);
+static SAMPLER: Once = Once::new();
+
+/// Load of the system, as effect shaders see it at binding 6
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Metrics {
+    /// Busy time of all cores, 0 to 1
+    pub cpu: f32,
+    /// Fraction of the memory in use
+    pub memory: f32,
+    /// Bytes received per second over all interfaces but loopback
+    pub net_down: f32,
+    /// Bytes sent per second over all interfaces but loopback
+    pub net_up: f32,
+    /// Charge of the batteries, 0 to 1, or -1 without a battery
+    pub battery: f32,
+    _padding: [f32; 3],
+}
+
+impl Metrics {
+    const UNKNOWN: Self = Self {
+        cpu: 0.0,
+        memory: 0.0,
+        net_down: 0.0,
+        net_up: 0.0,
+        battery: -1.0,
+        _padding: [0.0; 3],
+    };
+}
+
+/// The latest sample, sampling starts with the first call
+pub fn current() -> Metrics {
+    SAMPLER.call_once(|| {
+        let spawned = thread::Builder::new()
+            .name("metrics".to_string())
+            .spawn(run);
+        if let Err(e) = spawned {
+            tracing::error!("Failed to start sampling system metrics: {e}");
+        }
+    });
+    *CURRENT.lock().unwrap()
+}
+
+fn run() {
+    let mut previous: Option<(Counters, Instant)> = None;
+    loop {
+        let counters = Counters::read();
+        let now = Instant::now();
+
+        let mut metrics = Metrics {
+            memory: memory_usage().unwrap_or(0.0),
+            battery: battery_charge().unwrap_or(-1.0),
+            ..Metrics::UNKNOWN
+        };
+        if let Some((before, then)) = &previous {
+            let seconds = now.duration_since(*then).as_secs_f32();
+            let busy = counters.cpu_busy.saturating_sub(before.cpu_busy) as f32;
+            let total = counters.cpu_total.saturating_sub(before.cpu_total) as f32;
+            if total > 0.0 {
+                metrics.cpu = busy / total;
+            }
+            metrics.net_down = counters.received.saturating_sub(before.received) as f32 / seconds;
+            metrics.net_up = counters.sent.saturating_sub(before.sent) as f32 / seconds;
+        }
+        *CURRENT.lock().unwrap() = metrics;
+
+        previous = Some((counters, now));
+        thread::sleep(SAMPLE_INTERVAL);
+    }
+}
+
+/// Counters of the kernel that rates are computed from
+#[derive(Debug, Default)]
+struct Counters {
+    /// Clock ticks all cores spent working
+    cpu_busy: u64,
+    /// Clock ticks of all cores
+    cpu_total: u64,
+    /// Bytes received over all interfaces but loopback
+    received: u64,
+    /// Bytes sent over all interfaces but loopback
+    sent: u64,
+}
+
+impl Counters {
+    fn read() -> Self {
+        let mut counters = Self::default();
+
+        // The first line adds up all cores: user nice system idle iowait irq
+        // softirq steal, see proc_stat(5)
+        if let Some(line) = fs::read_to_string("/proc/stat")
+            .ok()
+            .and_then(|stat| stat.lines().next().map(str::to_string))
+        {
+            let ticks: Vec<u64> = line
+                .split_whitespace()
+                .skip(1)
+                .take(8)
+                .filter_map(|value| value.parse().ok())
+                .collect();
+            counters.cpu_total = ticks.iter().sum();
+            let idle = ticks.get(3).unwrap_or(&0) + ticks.get(4).unwrap_or(&0);
+            counters.cpu_busy = counters.cpu_total.saturating_sub(idle);
+        }
+
+        // Two header lines, then `name: received ... sent ...` with eight
+        // received counters before the sent bytes
+        if let Ok(dev) = fs::read_to_string("/proc/net/dev") {
+            for (name, values) in dev.lines().skip(2).filter_map(|line| line.split_once(':')) {
+                if name.trim() == "lo" {
+                    continue;
+                }
+                let values: Vec<u64> = values
+                    .split_whitespace()
+                    .filter_map(|value| value.parse().ok())
+                    .collect();
+                counters.received += values.first().unwrap_or(&0);
+                counters.sent += values.get(8).unwrap_or(&0);
+            }
+        }
+
+        counters
+    }
+}
+
+fn memory_usage() -> Option<f32> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| value.split_whitespace().next()?.parse::<f32>().ok())
+    };
+    let total = field("MemTotal")?;
+    let available = field("MemAvailable")?;
+    (total > 0.0).then(|| 1.0 - available / total)
+}
+
+/// Average charge of the batteries, none without any
+fn battery_charge() -> Option<f32> {
+    let charges: Vec<f32> = fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read_to_string(path.join("type")).is_ok_and(|kind| kind.trim() == "Battery")
+        })
+        .filter_map(|path| fs::read_to_string(path.join("capacity")).ok())
+        .filter_map(|capacity| capacity.trim().parse::<f32>().ok())
+        .collect();
+    (!charges.is_empty()).then(|| charges.iter().sum::<f32>() / charges.len() as f32 / 100.0)
+}
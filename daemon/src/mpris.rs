@@ -0,0 +1,274 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    sync::{Arc, Mutex, Once},
+    thread,
+    time::Duration,
+};
+
+use image::RgbaImage;
+use zbus::{
+    blocking::{fdo::DBusProxy, fdo::PropertiesProxy, Connection, MessageIterator},
+    message::Type,
+    names::InterfaceName,
+    zvariant::OwnedValue,
+    MatchRule,
+};
+
+/// Bus names of MPRIS players start with this
+const PLAYER_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const PLAYER_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// Largest album art downloaded from the web
+const MAX_ART_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Wait before connecting to the session bus again after losing it
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+static STATE: Mutex<State> = Mutex::new(State {
+    track: None,
+    art: None,
+    art_serial: 0,
+});
+static LISTENER: Once = Once::new();
+
+/// The track a media player plays, or paused on
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Track {
+    pub title: String,
+    /// Artists joined with commas
+    pub artist: String,
+    pub album: String,
+    /// Where the player says the album art is, a `file://` or web URL
+    pub art_url: Option<String>,
+    /// Length of the track
+    pub length: Option<Duration>,
+    /// False while paused
+    pub playing: bool,
+}
+
+struct State {
+    track: Option<Track>,
+    /// Album art of `track`, decoded
+    art: Option<Arc<RgbaImage>>,
+    /// Increases whenever `art` changes
+    art_serial: u64,
+}
+
+/// The track of the player that plays, or was paused last, listening to
+/// the players from the first call on
+pub fn current() -> Option<Track> {
+    listen();
+    STATE.lock().unwrap().track.clone()
+}
+
+/// The album art of the current track, if it changed since `serial`
+///
+/// Returns the new serial along with the art, none if the track has none.
+pub fn art_since(serial: u64) -> Option<(u64, Option<Arc<RgbaImage>>)> {
+    listen();
+    let state = STATE.lock().unwrap();
+    (state.art_serial != serial).then(|| (state.art_serial, state.art.clone()))
+}
+
+fn listen() {
+    LISTENER.call_once(|| {
+        let spawned = thread::Builder::new()
+            .name("mpris".to_string())
+            .spawn(|| loop {
+                if let Err(e) = watch() {
+                    tracing::warn!("Lost track of media players: {e}");
+                }
+                thread::sleep(RECONNECT_INTERVAL);
+            });
+        if let Err(e) = spawned {
+            tracing::error!("Failed to start listening to media players: {e}");
+        }
+    });
+}
+
+/// Look at the players again whenever one of them changes or players come
+/// and go
+fn watch() -> zbus::Result<()> {
+    let connection = Connection::session()?;
+    let messages = MessageIterator::from(&connection);
+    let dbus = DBusProxy::new(&connection)?;
+    dbus.add_match_rule(
+        MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface("org.freedesktop.DBus.Properties")?
+            .member("PropertiesChanged")?
+            .path(PLAYER_PATH)?
+            .build(),
+    )?;
+    dbus.add_match_rule(
+        MatchRule::builder()
+            .msg_type(Type::Signal)
+            .sender("org.freedesktop.DBus")?
+            .member("NameOwnerChanged")?
+            .arg0ns("org.mpris.MediaPlayer2")?
+            .build(),
+    )?;
+
+    let mut current = None;
+    update(&connection, &dbus, &mut current);
+    for message in messages {
+        if message?.message_type() == Type::Signal {
+            update(&connection, &dbus, &mut current);
+        }
+    }
+    Ok(())
+}
+
+/// Find the track to show, preferring players that are playing, and fetch
+/// its album art if it changed
+fn update(connection: &Connection, dbus: &DBusProxy, current: &mut Option<Track>) {
+    let names = match dbus.list_names() {
+        Ok(names) => names,
+        Err(e) => {
+            tracing::warn!("Failed to list media players: {e}");
+            return;
+        }
+    };
+    let tracks: Vec<Track> = names
+        .iter()
+        .filter(|name| name.starts_with(PLAYER_PREFIX))
+        .filter_map(|name| {
+            track(connection, name)
+                .inspect_err(|e| tracing::debug!("Failed to query media player {name}: {e}"))
+                .ok()
+        })
+        .collect();
+    // Keep showing a paused track until another one plays
+    let track = tracks
+        .iter()
+        .find(|track| track.playing)
+        .or_else(|| {
+            tracks.iter().find(|track| {
+                current
+                    .as_ref()
+                    .is_some_and(|current| current.title == track.title)
+            })
+        })
+        .or(tracks.first())
+        .cloned();
+    if track == *current {
+        return;
+    }
+
+    let art_changed = track.as_ref().and_then(|track| track.art_url.as_ref())
+        != current.as_ref().and_then(|track| track.art_url.as_ref());
+    if let Some(track) = &track {
+        tracing::debug!("Now playing {} by {}", track.title, track.artist);
+    }
+    *current = track.clone();
+
+    // Downloads happen outside the lock, the renderer keeps drawing the old art
+    let art = if art_changed {
+        Some(
+            track
+                .as_ref()
+                .and_then(|track| track.art_url.as_deref())
+                .and_then(|url| {
+                    load_art(url)
+                        .inspect_err(|e| tracing::warn!("Failed to load album art {url}: {e}"))
+                        .ok()
+                })
+                .map(Arc::new),
+        )
+    } else {
+        None
+    };
+
+    let mut state = STATE.lock().unwrap();
+    state.track = track;
+    if let Some(art) = art {
+        state.art = art;
+        state.art_serial += 1;
+    }
+}
+
+fn track(connection: &Connection, name: &str) -> zbus::Result<Track> {
+    let properties = PropertiesProxy::builder(connection)
+        .destination(name)?
+        .path(PLAYER_PATH)?
+        .build()?;
+    let mut all = properties.get_all(InterfaceName::from_static_str_unchecked(PLAYER_INTERFACE))?;
+
+    let playing = all
+        .remove("PlaybackStatus")
+        .and_then(|status| String::try_from(status).ok())
+        .is_some_and(|status| status == "Playing");
+    let mut metadata: HashMap<String, OwnedValue> = all
+        .remove("Metadata")
+        .and_then(|metadata| metadata.try_into().ok())
+        .unwrap_or_default();
+    let mut text = |key: &str| {
+        metadata
+            .remove(key)
+            .and_then(|value| String::try_from(value).ok())
+    };
+
+    Ok(Track {
+        title: text("xesam:title").unwrap_or_default(),
+        album: text("xesam:album").unwrap_or_default(),
+        art_url: text("mpris:artUrl").filter(|url| !url.is_empty()),
+        artist: metadata
+            .remove("xesam:artist")
+            .and_then(|artists| Vec::<String>::try_from(artists).ok())
+            .unwrap_or_default()
+            .join(", "),
+        length: metadata
+            .remove("mpris:length")
+            .and_then(|length| i64::try_from(length).ok())
+            .and_then(|micros| u64::try_from(micros).ok())
+            .map(Duration::from_micros),
+        playing,
+    })
+}
+
+fn load_art(url: &str) -> Result<RgbaImage, String> {
+    let bytes = match url.strip_prefix("file://") {
+        Some(path) => std::fs::read(percent_decode(path)).map_err(|e| e.to_string())?,
+        None if url.starts_with("https://") || url.starts_with("http://") => {
+            let mut bytes = Vec::new();
+            ureq::get(url)
+                .timeout(Duration::from_secs(15))
+                .call()
+                .map_err(|e| e.to_string())?
+                .into_reader()
+                .take(MAX_ART_SIZE)
+                .read_to_end(&mut bytes)
+                .map_err(|e| e.to_string())?;
+            bytes
+        }
+        None => return Err("unsupported URL".to_string()),
+    };
+    image::load_from_memory(&bytes)
+        .map(|image| image.to_rgba8())
+        .map_err(|e| e.to_string())
+}
+
+/// Decode the `%XX` escapes of a file URL's path
+fn percent_decode(path: &str) -> String {
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+
+use common::manifest::Sampling;
+
+use crate::asset::{animated::AnimatedTexture, image::ImageTexture};
+
+/// Default amount of decoded texture memory kept alive by the cache
+pub const DEFAULT_BUDGET: u64 = 512 * 1024 * 1024;
+
+/// Identifies a decoded asset, a changed modification time invalidates the entry
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssetKey {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    kind: AssetKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum AssetKind {
+    /// With the size of the largest output it was shrunk for
+    Image(Sampling, Option<(u32, u32)>),
+    Animation,
+}
+
+impl AssetKey {
+    fn new(path: &Path, kind: AssetKind) -> Self {
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        Self {
+            path: path.to_path_buf(),
+            modified,
+            kind,
+        }
+    }
+}
+
+/// A decoded asset, GPU handles are reference counted so clones share the textures
+///
+/// Animations share their decoder and ring of textures, not the whole file.
+#[derive(Debug, Clone)]
+pub enum CachedAsset {
+    Image(ImageTexture),
+    Animation(AnimatedTexture),
+}
+
+impl CachedAsset {
+    fn size_bytes(&self) -> u64 {
+        match self {
+            CachedAsset::Image(texture) => texture.size_bytes(),
+            CachedAsset::Animation(texture) => texture.size_bytes(),
+        }
+    }
+
+    /// Whether no layer shows the asset, so dropping it frees its memory
+    fn is_unused(&self) -> bool {
+        match self {
+            CachedAsset::Image(texture) => texture.is_unique(),
+            CachedAsset::Animation(texture) => texture.is_unique(),
+        }
+    }
+}
+
+/// An asset, or why it failed to load
+type Slot = Arc<OnceLock<Result<CachedAsset, String>>>;
+
+struct Entry {
+    /// Filled by whichever loader thread gets to the asset first
+    slot: Slot,
+    last_used: u64,
+}
+
+impl Entry {
+    /// The asset once it loaded successfully
+    fn asset(&self) -> Option<&CachedAsset> {
+        self.slot.get()?.as_ref().ok()
+    }
+}
+
+/// Counters reported by `wlrs cache stats`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub entries: u64,
+    pub bytes: u64,
+    pub budget: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// Decoded textures shared across outputs and wallpaper reloads
+///
+/// Entries are evicted least-recently-used first once the decoded size exceeds
+/// the budget. Evicting only drops the cache's handle, layers still showing the
+/// asset keep it alive.
+pub struct AssetCache {
+    entries: HashMap<AssetKey, Entry>,
+    budget: u64,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl AssetCache {
+    pub fn new(budget: u64) -> Self {
+        Self {
+            entries: HashMap::new(),
+            budget,
+            clock: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Get a static image texture shrunk for outputs of up to `target`,
+    /// uploading it with `load` on a miss
+    pub fn image(
+        cache: &Mutex<Self>,
+        path: &Path,
+        sampling: Sampling,
+        target: Option<(u32, u32)>,
+        load: impl FnOnce() -> Result<ImageTexture, String>,
+    ) -> Result<ImageTexture, String> {
+        let key = AssetKey::new(path, AssetKind::Image(sampling, target));
+        match Self::get_or_load(cache, key, || load().map(CachedAsset::Image))? {
+            CachedAsset::Image(texture) => Ok(texture),
+            CachedAsset::Animation(_) => unreachable!("image key resolved to an animation"),
+        }
+    }
+
+    /// Get an animated texture, starting to stream it with `load` on a miss
+    ///
+    /// The returned texture shares the decoder and playback with other users.
+    pub fn animation(
+        cache: &Mutex<Self>,
+        path: &Path,
+        load: impl FnOnce() -> Result<AnimatedTexture, String>,
+    ) -> Result<AnimatedTexture, String> {
+        let key = AssetKey::new(path, AssetKind::Animation);
+        match Self::get_or_load(cache, key, || load().map(CachedAsset::Animation))? {
+            CachedAsset::Animation(texture) => Ok(texture),
+            CachedAsset::Image(_) => unreachable!("animation key resolved to an image"),
+        }
+    }
+
+    /// Look up an asset, decoding it outside the cache lock on a miss
+    ///
+    /// Concurrent requests for the same key wait for the first decode instead
+    /// of repeating it, while unrelated assets keep loading in parallel. A
+    /// failed decode is reported to everyone waiting for it and forgotten,
+    /// the next request tries again.
+    fn get_or_load(
+        cache: &Mutex<Self>,
+        key: AssetKey,
+        load: impl FnOnce() -> Result<CachedAsset, String>,
+    ) -> Result<CachedAsset, String> {
+        let slot = cache.lock().unwrap().touch(&key);
+
+        let mut loaded = false;
+        let asset = slot
+            .get_or_init(|| {
+                loaded = true;
+                load()
+            })
+            .clone();
+
+        let mut cache = cache.lock().unwrap();
+        if loaded {
+            cache.misses += 1;
+            if asset.is_ok() {
+                cache.evict(&key);
+            } else if cache
+                .entries
+                .get(&key)
+                .is_some_and(|entry| Arc::ptr_eq(&entry.slot, &slot))
+            {
+                cache.entries.remove(&key);
+            }
+        } else {
+            cache.hits += 1;
+        }
+        asset
+    }
+
+    /// Get the slot for a key, creating it if needed, and mark it as recently used
+    fn touch(&mut self, key: &AssetKey) -> Slot {
+        self.clock += 1;
+        let entry = self.entries.entry(key.clone()).or_insert_with(|| Entry {
+            slot: Arc::new(OnceLock::new()),
+            last_used: 0,
+        });
+        entry.last_used = self.clock;
+        entry.slot.clone()
+    }
+
+    /// Drop least recently used assets until the cache fits its budget,
+    /// never evicting `keep` or assets that are still being decoded
+    fn evict(&mut self, keep: &AssetKey) {
+        while self.bytes() > self.budget {
+            let victim = self
+                .entries
+                .iter()
+                .filter(|(key, entry)| *key != keep && entry.asset().is_some())
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+
+            let Some(victim) = victim else {
+                break;
+            };
+            tracing::debug!("Evicting cached asset {}", victim.path.display());
+            self.entries.remove(&victim);
+            self.evictions += 1;
+        }
+    }
+
+    /// Drop least recently used assets no layer shows until `bytes` are
+    /// freed, returning how much was
+    pub fn release_unused(&mut self, bytes: u64) -> u64 {
+        let mut unused: Vec<_> = self
+            .entries
+            .iter()
+            .filter_map(|(key, entry)| {
+                let asset = entry.asset()?;
+                asset
+                    .is_unused()
+                    .then(|| (entry.last_used, asset.size_bytes(), key.clone()))
+            })
+            .collect();
+        unused.sort_by_key(|(last_used, _, _)| *last_used);
+
+        let mut released = 0;
+        for (_, size, key) in unused {
+            if released >= bytes {
+                break;
+            }
+            tracing::debug!("Dropping cached asset {} for memory", key.path.display());
+            self.entries.remove(&key);
+            self.evictions += 1;
+            released += size;
+        }
+        released
+    }
+
+    /// Total decoded size of all loaded entries
+    fn bytes(&self) -> u64 {
+        self.entries
+            .values()
+            .filter_map(Entry::asset)
+            .map(CachedAsset::size_bytes)
+            .sum()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self
+                .entries
+                .values()
+                .filter(|entry| entry.asset().is_some())
+                .count() as u64,
+            bytes: self.bytes(),
+            budget: self.budget,
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+        }
+    }
+
+    /// Drop every cached asset, returning what was held before clearing
+    pub fn clear(&mut self) -> CacheStats {
+        let stats = self.stats();
+        self.entries.clear();
+        stats
+    }
+}
+
+impl Default for AssetCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET)
+    }
+}
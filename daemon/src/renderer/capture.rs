@@ -0,0 +1,571 @@
+use std::{
+    os::{fd::AsFd, unix::process::CommandExt},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex, Weak},
+};
+
+use common::manifest::CaptureContent;
+use image::RgbaImage;
+use smithay_client_toolkit::{
+    reexports::protocols::wp::linux_dmabuf::zv1::client::zwp_linux_buffer_params_v1,
+    shm::{raw::RawPool, Shm},
+};
+use wayland_client::{
+    protocol::{wl_buffer::WlBuffer, wl_output::WlOutput, wl_shm},
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols_wlr::screencopy::v1::client::{
+    zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+    zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+};
+
+use super::client::Client;
+use crate::asset::dmabuf::{self, DmabufImage, LINEAR_MODIFIER};
+
+/// Shared memory formats frames are accepted in, all 8 bits per channel
+const SUPPORTED_FORMATS: [wl_shm::Format; 4] = [
+    wl_shm::Format::Argb8888,
+    wl_shm::Format::Xrgb8888,
+    wl_shm::Format::Abgr8888,
+    wl_shm::Format::Xbgr8888,
+];
+
+/// Frames of one captured output, shared by the capture layers showing it
+///
+/// The client requests a frame whenever the previous one arrived, see
+/// `Captures::request_frames`, and models pick up the latest one when they
+/// update. Dropping the last layer showing it stops the capture and the
+/// command started with it.
+///
+/// When the compositor offers dmabufs and the device can export images, see
+/// `dmabuf::open_device`, frames are copied by the compositor straight into
+/// GPU images the models sample. Otherwise they go through shared memory and
+/// are uploaded by every model showing them.
+#[derive(Debug)]
+pub struct CaptureFeed {
+    content: CaptureContent,
+    state: Mutex<FeedState>,
+    /// Process started for the capture, stopped when the feed is dropped
+    command: Option<Child>,
+}
+
+/// A captured frame
+#[derive(Debug, Clone)]
+pub enum Frame {
+    /// Pixels copied through shared memory
+    Pixels(Arc<RgbaImage>),
+    /// GPU image the compositor copied the frame into
+    Image(Arc<DmabufImage>),
+}
+
+#[derive(Debug, Default)]
+struct FeedState {
+    /// Latest frame, shared with the models showing it
+    frame: Option<Frame>,
+    /// Incremented with every frame
+    serial: u64,
+    /// Whether a frame was requested and has not arrived yet
+    in_flight: bool,
+    /// Buffer the compositor offered for the frame in flight
+    layout: Option<BufferLayout>,
+    /// Dmabuf the compositor offered for the frame in flight
+    dmabuf: Option<DmabufLayout>,
+    /// Whether the frame in flight is upside down
+    y_invert: bool,
+    /// Shared memory the compositor copies frames into, reused across frames
+    pool: Option<RawPool>,
+    /// Buffer in `pool` and the layout it was created with
+    buffer: Option<(WlBuffer, BufferLayout)>,
+    /// GPU images frames are copied into, alternated so the compositor does
+    /// not write to the one shown
+    images: Vec<(Arc<DmabufImage>, WlBuffer)>,
+    /// Index in `images` of the next frame
+    next_image: usize,
+    /// Index in `images` the frame in flight is copied into, None when it is
+    /// copied into shared memory
+    target: Option<usize>,
+    /// Whether dmabufs failed for this feed, which then sticks to shared memory
+    no_dmabuf: bool,
+    /// Whether a failure was logged, so a missing output is reported once
+    failed: bool,
+}
+
+/// Shared memory buffer layout of a frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BufferLayout {
+    format: wl_shm::Format,
+    width: u32,
+    height: u32,
+    stride: u32,
+}
+
+/// Dmabuf layout of a frame, the compositor picks the stride of shared
+/// memory buffers only
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DmabufLayout {
+    /// DRM format
+    format: u32,
+    width: u32,
+    height: u32,
+}
+
+impl CaptureFeed {
+    /// Name of the captured output
+    pub fn output(&self) -> &str {
+        &self.content.capture
+    }
+
+    /// Latest frame if it is newer than `serial`, along with its serial
+    pub fn frame_since(&self, serial: u64) -> Option<(u64, Frame)> {
+        let state = self.state.lock().unwrap();
+        if state.serial == serial {
+            return None;
+        }
+        state.frame.clone().map(|frame| (state.serial, frame))
+    }
+
+    /// Note a failure, logging it if it is the first since the last frame
+    fn fail(&self, state: &mut FeedState, reason: &str) {
+        state.in_flight = false;
+        if !state.failed {
+            tracing::warn!("Failed to capture output {}: {reason}", self.output());
+            state.failed = true;
+        }
+    }
+}
+
+impl Drop for CaptureFeed {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.command {
+            tracing::info!("Stopping capture command of {}", self.content.capture);
+            // The command runs in its own process group, stop the whole group
+            // in case the shell forked
+            unsafe {
+                libc::kill(-(child.id() as i32), libc::SIGTERM);
+            }
+            let _ = child.wait();
+        }
+        let state = self.state.get_mut().unwrap();
+        if let Some((buffer, _)) = state.buffer.take() {
+            buffer.destroy();
+        }
+        state.drop_images();
+    }
+}
+
+impl FeedState {
+    /// Destroy the buffers of the GPU images, models keep the images they show
+    fn drop_images(&mut self) {
+        for (_, buffer) in self.images.drain(..) {
+            buffer.destroy();
+        }
+        self.next_image = 0;
+    }
+}
+
+/// Outputs captured by the loaded wallpapers, shared with the loader
+#[derive(Debug, Clone, Default)]
+pub struct Captures(Arc<Mutex<Vec<Weak<CaptureFeed>>>>);
+
+impl Captures {
+    /// Feed of the output a capture layer shows, starting its command
+    ///
+    /// Layers capturing the same output with the same command share a feed,
+    /// so a wallpaper shown on several outputs starts its command once.
+    pub fn subscribe(&self, content: &CaptureContent) -> Arc<CaptureFeed> {
+        let mut feeds = self.0.lock().unwrap();
+        feeds.retain(|feed| feed.strong_count() > 0);
+        if let Some(feed) = feeds
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|feed| feed.content == *content)
+        {
+            return feed;
+        }
+
+        let command = content.command.as_ref().and_then(|command| {
+            tracing::info!(
+                "Starting capture command for {}: {command}",
+                content.capture
+            );
+            Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .stdin(Stdio::null())
+                .process_group(0)
+                .spawn()
+                .inspect_err(|e| tracing::error!("Failed to start '{command}': {e}"))
+                .ok()
+        });
+        let feed = Arc::new(CaptureFeed {
+            content: content.clone(),
+            state: Mutex::new(FeedState::default()),
+            command,
+        });
+        feeds.push(Arc::downgrade(&feed));
+        feed
+    }
+
+    /// Whether any layer shows a captured output
+    pub fn is_active(&self) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|feed| feed.strong_count() > 0)
+    }
+
+    /// Request the next frame of every feed that is not waiting for one,
+    /// `find_output` looks captured outputs up by name
+    pub fn request_frames<'a>(
+        &self,
+        manager: &ZwlrScreencopyManagerV1,
+        qh: &QueueHandle<Client>,
+        find_output: impl Fn(&str) -> Option<&'a WlOutput>,
+    ) {
+        let feeds: Vec<Arc<CaptureFeed>> = self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        for feed in feeds {
+            let mut state = feed.state.lock().unwrap();
+            if state.in_flight {
+                continue;
+            }
+            // The output may come up later, e.g. a headless one created by the command
+            let Some(output) = find_output(feed.output()) else {
+                feed.fail(&mut state, "no such output");
+                continue;
+            };
+            state.in_flight = true;
+            state.layout = None;
+            state.dmabuf = None;
+            state.target = None;
+            state.y_invert = false;
+            manager.capture_output(feed.content.cursor as i32, output, qh, feed.clone());
+        }
+    }
+
+    /// Forget frames in flight and buffers of a lost compositor connection
+    /// or device
+    pub fn reset(&self) {
+        for feed in self.0.lock().unwrap().iter().filter_map(Weak::upgrade) {
+            let mut state = feed.state.lock().unwrap();
+            state.in_flight = false;
+            state.pool = None;
+            state.buffer = None;
+            state.drop_images();
+            state.no_dmabuf = false;
+        }
+    }
+}
+
+/// Hand the compositor a buffer to copy the frame into, once it told which
+/// buffers it can fill, preferring a GPU image
+fn copy(
+    feed: &CaptureFeed,
+    frame: &ZwlrScreencopyFrameV1,
+    client: &Client,
+    qh: &QueueHandle<Client>,
+) {
+    let mut state = feed.state.lock().unwrap();
+    if let Some(layout) = state
+        .dmabuf
+        .filter(|layout| !state.no_dmabuf && can_import(client, layout.format))
+    {
+        match image_buffer(feed, &mut state, layout, client, qh) {
+            Ok(buffer) => {
+                frame.copy(&buffer);
+                return;
+            }
+            Err(e) => {
+                tracing::warn!("Capturing {} through shared memory: {e}", feed.output());
+                state.no_dmabuf = true;
+                state.drop_images();
+            }
+        }
+    }
+
+    copy_shm(feed, &mut state, frame, &client.shm, qh);
+}
+
+/// Whether images in a DRM format can be shared with the compositor
+fn can_import(client: &Client, format: u32) -> bool {
+    if !dmabuf::is_supported(&client.device) {
+        return false;
+    }
+    match client.dmabuf.version() {
+        None => false,
+        // Later versions list formats in the feedback instead
+        Some(version) if version < 4 => client
+            .dmabuf
+            .modifiers()
+            .iter()
+            .any(|pair| pair.format == format && pair.modifier == LINEAR_MODIFIER),
+        Some(_) => client.dmabuf_formats.contains(&format),
+    }
+}
+
+/// Buffer of the GPU image the next frame is copied into, allocating the
+/// image if needed
+fn image_buffer(
+    feed: &CaptureFeed,
+    state: &mut FeedState,
+    layout: DmabufLayout,
+    client: &Client,
+    qh: &QueueHandle<Client>,
+) -> Result<WlBuffer, String> {
+    let matches = |(image, _): &(Arc<DmabufImage>, WlBuffer)| {
+        let size = image.texture.size();
+        (image.format, size.width, size.height) == (layout.format, layout.width, layout.height)
+    };
+    if !state.images.iter().all(matches) {
+        state.drop_images();
+    }
+
+    let index = state.next_image.min(state.images.len());
+    if index == state.images.len() {
+        let image = DmabufImage::allocate(
+            &client.device,
+            layout.width,
+            layout.height,
+            layout.format,
+            &format!("capture_{}", feed.output()),
+        )?;
+        let params = client.dmabuf.create_params(qh).map_err(|e| e.to_string())?;
+        params.add(
+            image.fd.as_fd(),
+            0,
+            image.offset,
+            image.stride,
+            LINEAR_MODIFIER,
+        );
+        let (buffer, params) = params.create_immed(
+            layout.width as i32,
+            layout.height as i32,
+            layout.format,
+            zwp_linux_buffer_params_v1::Flags::empty(),
+            qh,
+        );
+        params.destroy();
+        state.images.push((Arc::new(image), buffer));
+    }
+
+    state.target = Some(index);
+    Ok(state.images[index].1.clone())
+}
+
+/// Hand the compositor a shared memory buffer to copy the frame into
+fn copy_shm(
+    feed: &CaptureFeed,
+    state: &mut FeedState,
+    frame: &ZwlrScreencopyFrameV1,
+    shm: &Shm,
+    qh: &QueueHandle<Client>,
+) {
+    let Some(layout) = state.layout else {
+        feed.fail(state, "no supported shared memory format offered");
+        frame.destroy();
+        return;
+    };
+
+    if state.buffer.as_ref().is_none_or(|(_, old)| *old != layout) {
+        if let Some((buffer, _)) = state.buffer.take() {
+            buffer.destroy();
+        }
+        let size = (layout.stride * layout.height) as usize;
+        let pool = match state.pool.take() {
+            Some(mut pool) => pool.resize(size).map(|()| pool).map_err(|e| e.to_string()),
+            None => RawPool::new(size, shm).map_err(|e| e.to_string()),
+        };
+        let mut pool = match pool {
+            Ok(pool) => pool,
+            Err(e) => {
+                feed.fail(state, &format!("cannot allocate a buffer: {e}"));
+                frame.destroy();
+                return;
+            }
+        };
+        let buffer = pool.create_buffer(
+            0,
+            layout.width as i32,
+            layout.height as i32,
+            layout.stride as i32,
+            layout.format,
+            (),
+            qh,
+        );
+        state.pool = Some(pool);
+        state.buffer = Some((buffer, layout));
+    }
+
+    if let Some((buffer, _)) = &state.buffer {
+        frame.copy(buffer);
+    }
+}
+
+/// Convert the frame in the pool to RGBA, the right way up
+fn read_frame(state: &mut FeedState) -> Option<RgbaImage> {
+    let (_, layout) = state.buffer.as_ref()?;
+    let layout = *layout;
+    let y_invert = state.y_invert;
+    let memory = state.pool.as_mut()?.mmap();
+
+    let mut image = RgbaImage::new(layout.width, layout.height);
+    for (y, row) in image.rows_mut().enumerate() {
+        let y = if y_invert {
+            layout.height as usize - 1 - y
+        } else {
+            y
+        };
+        let start = y * layout.stride as usize;
+        let source = memory.get(start..start + layout.width as usize * 4)?;
+        // Formats are named by their channels from most to least significant
+        // bit of a little endian word, so ARGB is stored as B, G, R, A
+        for (pixel, texel) in row.zip(source.chunks_exact(4)) {
+            pixel.0 = match layout.format {
+                wl_shm::Format::Argb8888 => [texel[2], texel[1], texel[0], texel[3]],
+                wl_shm::Format::Xrgb8888 => [texel[2], texel[1], texel[0], u8::MAX],
+                wl_shm::Format::Abgr8888 => [texel[0], texel[1], texel[2], texel[3]],
+                _ => [texel[0], texel[1], texel[2], u8::MAX],
+            };
+        }
+    }
+    Some(image)
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for Client {
+    fn event(
+        _state: &mut Self,
+        _manager: &ZwlrScreencopyManagerV1,
+        _event: <ZwlrScreencopyManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, Arc<CaptureFeed>> for Client {
+    fn event(
+        state: &mut Self,
+        frame: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        feed: &Arc<CaptureFeed>,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_screencopy_frame_v1::Event::Buffer {
+                format: WEnum::Value(format),
+                width,
+                height,
+                stride,
+            } => {
+                if SUPPORTED_FORMATS.contains(&format) {
+                    feed.state
+                        .lock()
+                        .unwrap()
+                        .layout
+                        .get_or_insert(BufferLayout {
+                            format,
+                            width,
+                            height,
+                            stride,
+                        });
+                }
+                // Before version 3 this is the only buffer offered
+                if frame.version() < 3 {
+                    copy(feed, frame, state, qh);
+                }
+            }
+            zwlr_screencopy_frame_v1::Event::LinuxDmabuf {
+                format,
+                width,
+                height,
+            } => {
+                feed.state
+                    .lock()
+                    .unwrap()
+                    .dmabuf
+                    .get_or_insert(DmabufLayout {
+                        format,
+                        width,
+                        height,
+                    });
+            }
+            zwlr_screencopy_frame_v1::Event::BufferDone => copy(feed, frame, state, qh),
+            zwlr_screencopy_frame_v1::Event::Flags {
+                flags: WEnum::Value(flags),
+            } => {
+                feed.state.lock().unwrap().y_invert =
+                    flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert);
+            }
+            zwlr_screencopy_frame_v1::Event::Ready { .. } => {
+                let mut feed_state = feed.state.lock().unwrap();
+                let image = match feed_state.target {
+                    // Images are sampled as they are, the shader cannot flip them
+                    Some(_) if feed_state.y_invert => {
+                        tracing::debug!(
+                            "Capture of {} is upside down, using shared memory",
+                            feed.output()
+                        );
+                        feed_state.no_dmabuf = true;
+                        feed_state.drop_images();
+                        None
+                    }
+                    // The images may have been dropped since, with the device
+                    Some(index) => {
+                        feed_state.next_image = (index + 1) % 2;
+                        feed_state
+                            .images
+                            .get(index)
+                            .map(|(image, _)| Frame::Image(image.clone()))
+                    }
+                    None => {
+                        read_frame(&mut feed_state).map(|pixels| Frame::Pixels(Arc::new(pixels)))
+                    }
+                };
+                if let Some(image) = image {
+                    feed_state.frame = Some(image);
+                    feed_state.serial += 1;
+                    feed_state.failed = false;
+                }
+                feed_state.in_flight = false;
+                frame.destroy();
+            }
+            zwlr_screencopy_frame_v1::Event::Failed => {
+                let mut feed_state = feed.state.lock().unwrap();
+                // The compositor may be unable to write to the image, try
+                // shared memory for the next frame
+                if feed_state.target.is_some() {
+                    tracing::debug!(
+                        "Capture of {} into a dmabuf failed, using shared memory",
+                        feed.output()
+                    );
+                    feed_state.no_dmabuf = true;
+                    feed_state.drop_images();
+                }
+                feed.fail(&mut feed_state, "the compositor refused");
+                frame.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlBuffer, ()> for Client {
+    fn event(
+        _state: &mut Self,
+        _buffer: &WlBuffer,
+        _event: <WlBuffer as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // Buffers are only reused once their frame is ready, release needs no handling
+    }
+}
@@ -1,29 +1,82 @@
 use std::{
+    collections::HashMap,
     ops::{Deref, DerefMut},
-    sync::{Arc, Mutex},
-    time::Duration,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
+use common::{
+    manifest::Screen,
+    types::{DisplayAdjustment, OutputPlan},
+    wallpaper::Wallpaper,
+};
 use smithay_client_toolkit::{
     compositor::{CompositorHandler, CompositorState},
-    delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_seat,
+    delegate_compositor, delegate_dmabuf, delegate_layer, delegate_output, delegate_pointer,
+    delegate_registry, delegate_seat, delegate_shm,
+    dmabuf::{DmabufFeedback, DmabufHandler, DmabufState},
     output::{OutputHandler, OutputState},
+    reexports::protocols::wp::linux_dmabuf::zv1::client::{
+        zwp_linux_buffer_params_v1, zwp_linux_dmabuf_feedback_v1,
+    },
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
-    seat::{Capability, SeatHandler, SeatState},
-    shell::wlr_layer::{Layer, LayerShell, LayerShellHandler, LayerSurface, LayerSurfaceConfigure},
+    seat::{
+        pointer::{
+            CursorIcon, PointerEvent, PointerEventKind, PointerHandler, ThemeSpec, ThemedPointer,
+        },
+        Capability, SeatHandler, SeatState,
+    },
+    shell::{
+        wlr_layer::{Layer, LayerShell, LayerShellHandler, LayerSurface, LayerSurfaceConfigure},
+        WaylandSurface,
+    },
+    shm::{Shm, ShmHandler},
 };
 use wayland_client::{
     globals::registry_queue_init,
     protocol::{
+        wl_buffer,
         wl_output::{self, WlOutput},
-        wl_seat, wl_surface,
+        wl_pointer, wl_seat, wl_surface,
     },
     Connection, EventQueue, QueueHandle,
 };
-use wgpu::{Adapter, BindGroupLayout, Device, Instance, Queue, RenderPipeline};
+use wayland_protocols::ext::idle_notify::v1::client::ext_idle_notifier_v1::ExtIdleNotifierV1;
+use wayland_protocols_wlr::{
+    output_power_management::v1::client::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
+    screencopy::v1::client::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+};
+use wgpu::{
+    Adapter, Backends, BindGroupLayout, Device, DeviceDescriptor, DeviceType, Features, Instance,
+    Queue, RenderPipeline,
+};
 
-use super::{manager::Manager, wallpaper_layer::WallpaperLayer};
+use crate::asset::dmabuf;
+use crate::config::{GpuConfig, IdleConfig, OutputRule};
+use crate::hooks::{self, Event};
+use crate::utils::find_wallpaper;
+
+use super::{
+    cache::AssetCache,
+    capture::Captures,
+    fallback,
+    idle::Idle,
+    loader::Loader,
+    manager::Manager,
+    pipeline::Pipelines,
+    pipeline::PointerInput,
+    pipeline_cache, plan, prescale,
+    profiler::Profiler,
+    stats::FrameStats,
+    wallpaper_layer::{output_pixels, WallpaperLayer, RENDER_SCALE_RANGE},
+};
+
+/// How often screen captures are asked for a new frame while a layer shows one
+const CAPTURE_INTERVAL: Duration = Duration::from_millis(32);
 
 pub struct Client {
     pub namespace: Option<String>,
@@ -32,17 +85,124 @@ pub struct Client {
     pub layer: LayerShell,
     pub registry: RegistryState,
     pub seat: SeatState,
+    /// Pointer of the seat, its input is forwarded to the models under it
+    pub pointer: Option<ThemedPointer>,
     pub output: OutputState,
+    pub shm: Shm,
+    /// Captures outputs for capture layers, None if the compositor cannot
+    pub screencopy: Option<ZwlrScreencopyManagerV1>,
+    /// Shares GPU images with the compositor, so captures skip the CPU
+    pub dmabuf: DmabufState,
+    /// Formats the compositor takes linear dmabufs in, from the default
+    /// feedback of version 4 and later
+    pub dmabuf_formats: Vec<u32>,
 
     pub instance: Instance,
     pub adapter: Adapter,
     pub device: Device,
     pub queue: Queue,
+    /// How the adapter was chosen, reused when the device has to be recreated
+    pub gpu: GpuConfig,
+    /// Set by wgpu when the device is lost, e.g. after a GPU reset
+    device_lost: Arc<AtomicBool>,
 
     pub bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
     pub pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    pub asset_cache: Arc<Mutex<AssetCache>>,
+    /// Outputs shown by capture layers
+    pub captures: Captures,
+    /// When the captures were last asked for frames, see `CAPTURE_INTERVAL`
+    captures_requested: Option<Instant>,
+    pub loader: Loader,
 
     pub wallpapers: Wallpapers,
+    /// Wallpaper chosen for each output by name, kept while the output is
+    /// gone so it can be restored when the output or compositor comes back
+    pub assignments: HashMap<String, Wallpaper>,
+    /// Source output mirrored by each output, kept like `assignments` while
+    /// either output is gone
+    pub mirrors: HashMap<String, String>,
+    /// Render scale set for each output over IPC, overriding the config
+    pub render_scales: HashMap<String, f32>,
+    /// Display adjustment set for each output over IPC
+    pub adjustments: HashMap<String, DisplayAdjustment>,
+    /// Wallpapers for new outputs without an assignment
+    pub output_rules: Vec<OutputRule>,
+    /// What happens to the wallpapers while the user is idle
+    pub idle: Idle,
+    /// Whether wallpapers animate, otherwise they show a still frame
+    pub motion: bool,
+    /// Tells when outputs are turned off, None if the compositor cannot
+    pub power_manager: Option<ZwlrOutputPowerManagerV1>,
+}
+
+/// Wayland globals the daemon binds, recreated when reconnecting
+struct Globals {
+    compositor: CompositorState,
+    layer: LayerShell,
+    registry: RegistryState,
+    seat: SeatState,
+    output: OutputState,
+    shm: Shm,
+    screencopy: Option<ZwlrScreencopyManagerV1>,
+    dmabuf: DmabufState,
+    idle_notifier: Option<ExtIdleNotifierV1>,
+    power_manager: Option<ZwlrOutputPowerManagerV1>,
+}
+
+impl Globals {
+    /// Connect to the compositor named by the environment and bind the globals
+    fn connect() -> Result<(Self, EventQueue<Client>), String> {
+        let connection = Connection::connect_to_env()
+            .map_err(|e| format!("Failed to connect to the compositor: {e}"))?;
+        let (globals, event_queue) = registry_queue_init(&connection)
+            .map_err(|e| format!("Failed to list the compositor's globals: {e}"))?;
+        let qh = event_queue.handle();
+
+        let compositor = CompositorState::bind(&globals, &qh)
+            .map_err(|e| format!("No compositor available: {e}"))?;
+        let layer = LayerShell::bind(&globals, &qh)
+            .map_err(|e| format!("No layer shell available: {e}"))?;
+        let shm =
+            Shm::bind(&globals, &qh).map_err(|e| format!("No shared memory available: {e}"))?;
+        // Only capture layers need it, they stay transparent without it
+        let screencopy = globals
+            .bind(&qh, 1..=3, ())
+            .inspect_err(|e| tracing::warn!("Capture layers are not supported: {e}"))
+            .ok();
+        // Wallpapers keep animating while the user is away without it
+        let idle_notifier = globals
+            .bind(&qh, 1..=1, ())
+            .inspect_err(|e| tracing::info!("Idle notifications are not supported: {e}"))
+            .ok();
+        // Layers of outputs that are off are noticed by their frame callbacks
+        // stopping without it
+        let power_manager = globals
+            .bind(&qh, 1..=1, ())
+            .inspect_err(|e| tracing::info!("Output power states are not supported: {e}"))
+            .ok();
+        // Captures fall back to shared memory without it
+        let dmabuf = DmabufState::new(&globals, &qh);
+        if dmabuf.version().is_some_and(|version| version >= 4) {
+            let _ = dmabuf.get_default_feedback(&qh);
+        }
+
+        Ok((
+            Self {
+                compositor,
+                layer,
+                registry: RegistryState::new(&globals),
+                seat: SeatState::new(&globals, &qh),
+                output: OutputState::new(&globals, &qh),
+                shm,
+                screencopy,
+                dmabuf,
+                idle_notifier,
+                power_manager,
+            },
+            event_queue,
+        ))
+    }
 }
 
 #[derive(Default)]
@@ -74,46 +234,506 @@ impl Client {
         )
     }
 
-    pub fn new(namespace: Option<impl Into<String>>) -> (Self, EventQueue<Self>) {
-        let connection = Connection::connect_to_env().unwrap();
-        let (globals, event_queue) = registry_queue_init(&connection).unwrap();
-        let qh = event_queue.handle();
-
-        let compositor = CompositorState::bind(&globals, &qh).expect("No compositor available");
-        let layer = LayerShell::bind(&globals, &qh).expect("No layer shell available");
-        let registry = RegistryState::new(&globals);
-        let seat = SeatState::new(&globals, &qh);
-        let output = OutputState::new(&globals, &qh);
+    pub fn new(
+        namespace: Option<impl Into<String>>,
+        gpu: GpuConfig,
+        output_rules: Vec<OutputRule>,
+        idle: IdleConfig,
+    ) -> (Self, EventQueue<Self>) {
+        let (globals, event_queue) = Globals::connect().unwrap_or_else(|e| panic!("{e}"));
 
         let instance = Instance::default();
-        let adapter =
-            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
-                .expect("Failed to find suitable adapter");
-
-        let (device, queue) = pollster::block_on(adapter.request_device(&Default::default(), None))
-            .expect("Failed to request device");
+        let (adapter, device, queue) =
+            create_device(&instance, &gpu).unwrap_or_else(|e| panic!("{e}"));
+        let device_lost = watch_device_lost(&device);
+        pipeline_cache::open(&adapter, &device);
         let wallpapers = Wallpapers::default();
+        let bindgroup_layout_manager = Arc::new(Mutex::new(Manager::new()));
+        let pipeline_manager = Arc::new(Mutex::new(Manager::new()));
+        let asset_cache = Arc::new(Mutex::new(AssetCache::default()));
+        let captures = Captures::default();
+        let loader = Loader::new(
+            device.clone(),
+            queue.clone(),
+            bindgroup_layout_manager.clone(),
+            pipeline_manager.clone(),
+            asset_cache.clone(),
+            captures.clone(),
+        );
 
         (
             Self {
                 namespace: namespace.map(Into::into),
-                compositor,
-                layer,
-                registry,
-                seat,
-                output,
+                compositor: globals.compositor,
+                layer: globals.layer,
+                registry: globals.registry,
+                seat: globals.seat,
+                pointer: None,
+                output: globals.output,
+                shm: globals.shm,
+                screencopy: globals.screencopy,
+                dmabuf: globals.dmabuf,
+                dmabuf_formats: Vec::new(),
                 instance,
                 adapter,
                 device,
                 queue,
-                bindgroup_layout_manager: Arc::new(Mutex::new(Manager::new())),
-                pipeline_manager: Arc::new(Mutex::new(Manager::new())),
+                gpu,
+                device_lost,
+                bindgroup_layout_manager,
+                pipeline_manager,
+                asset_cache,
+                captures,
+                captures_requested: None,
+                loader,
                 wallpapers,
+                assignments: HashMap::new(),
+                mirrors: HashMap::new(),
+                render_scales: HashMap::new(),
+                adjustments: HashMap::new(),
+                output_rules,
+                idle: Idle::new(idle, globals.idle_notifier),
+                power_manager: globals.power_manager,
+                motion: true,
             },
             event_queue,
         )
     }
 
+    /// Replace a lost compositor connection with a new one
+    ///
+    /// All layers are dropped. Outputs announced on the new connection get
+    /// fresh layers, and the wallpapers assigned to them are loaded again.
+    pub fn reconnect(&mut self) -> Result<EventQueue<Self>, String> {
+        // wgpu surfaces point into the old display, drop them before it goes away
+        self.wallpapers.clear();
+        self.captures.reset();
+
+        let (globals, event_queue) = Globals::connect()?;
+        self.compositor = globals.compositor;
+        self.layer = globals.layer;
+        self.registry = globals.registry;
+        self.seat = globals.seat;
+        self.pointer = None;
+        self.output = globals.output;
+        self.shm = globals.shm;
+        self.screencopy = globals.screencopy;
+        self.dmabuf = globals.dmabuf;
+        self.dmabuf_formats.clear();
+        self.idle.reset(globals.idle_notifier);
+        self.power_manager = globals.power_manager;
+        Ok(event_queue)
+    }
+
+    /// Whether the GPU device was lost and has to be recreated
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Replace a lost GPU device with a new one and rebuild everything on it
+    ///
+    /// Pipelines, bind group layouts and cached textures belong to the old
+    /// device, so they are dropped and every assigned wallpaper is loaded again.
+    pub fn recover_lost_device(&mut self) -> Result<(), String> {
+        let instance = Instance::default();
+        let (adapter, device, queue) = create_device(&instance, &self.gpu)?;
+
+        for layer in self.wallpapers.iter_mut() {
+            layer.wallpaper = Arc::new(Mutex::new(Pipelines::new()));
+            layer.profiler = Profiler::default();
+            layer.pending_load = None;
+            layer.recreate_surface(&instance);
+        }
+        self.bindgroup_layout_manager.lock().unwrap().clear();
+        self.pipeline_manager.lock().unwrap().clear();
+        self.asset_cache.lock().unwrap().clear();
+        // Capture images belong to the old device too
+        self.captures.reset();
+
+        self.device_lost = watch_device_lost(&device);
+        pipeline_cache::open(&adapter, &device);
+        self.loader = Loader::new(
+            device.clone(),
+            queue.clone(),
+            self.bindgroup_layout_manager.clone(),
+            self.pipeline_manager.clone(),
+            self.asset_cache.clone(),
+            self.captures.clone(),
+        );
+        self.instance = instance;
+        self.adapter = adapter;
+        self.device = device;
+        self.queue = queue;
+
+        for layer in self.wallpapers.iter_mut().filter(|layer| layer.configured) {
+            layer.configure(&self.adapter, &self.device);
+        }
+        let assignments: Vec<_> = self
+            .assignments
+            .iter()
+            .map(|(output, wallpaper)| (output.clone(), wallpaper.clone()))
+            .collect();
+        for (output, wallpaper) in assignments {
+            self.assign(&output, wallpaper);
+        }
+        Ok(())
+    }
+
+    /// Start loading a wallpaper for an output and remember the choice
+    ///
+    /// The output's current wallpaper stays on screen until loading finishes.
+    /// This ends mirroring another output.
+    pub fn assign(&mut self, output: &str, wallpaper: Wallpaper) {
+        self.mirrors.remove(output);
+        let screen = self.screen(output);
+        let target = self
+            .largest_output()
+            .unwrap_or((screen.width, screen.height));
+        if let Some(layer) = self
+            .wallpapers
+            .iter_mut()
+            .find(|layer| layer.name == output)
+        {
+            layer.pending_load = Some(self.loader.submit(wallpaper.clone(), screen, target));
+            layer.load_target = Some(target);
+        }
+        self.assignments.insert(output.to_string(), wallpaper);
+    }
+
+    /// Show the error wallpaper on `output` after its wallpaper failed with
+    /// `error`, see `fallback`
+    ///
+    /// An output still showing a wallpaper that loaded fine keeps it, as does
+    /// one with another wallpaper on its way, only outputs that would stay
+    /// black get the error wallpaper.
+    pub fn show_error(&mut self, output: &str, error: &str) {
+        let blank = self
+            .wallpapers
+            .iter()
+            .find(|layer| layer.name == output)
+            .is_none_or(|layer| {
+                layer.pending_load.is_none() && layer.wallpaper.lock().unwrap().is_empty()
+            });
+        if !blank {
+            return;
+        }
+        match fallback::wallpaper(error) {
+            Ok(wallpaper) => {
+                tracing::info!("Showing the error wallpaper on {output}");
+                self.assign(output, wallpaper);
+            }
+            Err(e) => tracing::error!("{e}"),
+        }
+    }
+
+    /// How `wallpaper` would be drawn on `output` if it was assigned now
+    pub fn plan(&self, wallpaper: &Wallpaper, output: &str) -> Result<OutputPlan, String> {
+        let screen = self.screen(output);
+        let target = self
+            .largest_output()
+            .unwrap_or((screen.width, screen.height));
+        plan::output(wallpaper, screen, target)
+    }
+
+    /// The output named `output` as the `when` conditions of layers see it
+    fn screen(&self, output: &str) -> Screen {
+        let (width, height) = self
+            .wallpapers
+            .iter()
+            .find(|layer| layer.name == output)
+            .and_then(|layer| self.output.info(&layer.output))
+            .map(|info| output_pixels(&info))
+            .unwrap_or_default();
+        Screen {
+            output: output.to_string(),
+            width,
+            height,
+        }
+    }
+
+    /// Width and height in pixels covering every output, None before the
+    /// first one is known
+    fn largest_output(&self) -> Option<(u32, u32)> {
+        self.wallpapers
+            .iter()
+            .filter_map(|layer| self.output.info(&layer.output))
+            .map(|info| output_pixels(&info))
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .reduce(|(width, height), (other_width, other_height)| {
+                (width.max(other_width), height.max(other_height))
+            })
+    }
+
+    /// Load the wallpapers again whose images were shrunk for a smaller
+    /// output than the largest one now, e.g. after switching to a higher
+    /// resolution
+    fn reload_undersized(&mut self) {
+        if !prescale::enabled() {
+            return;
+        }
+        let Some((width, height)) = self.largest_output() else {
+            return;
+        };
+        let outputs: Vec<_> = self
+            .wallpapers
+            .iter()
+            .filter(|layer| layer.mirror_of.is_none())
+            .filter(|layer| {
+                layer
+                    .load_target
+                    .is_some_and(|(target_width, target_height)| {
+                        target_width < width || target_height < height
+                    })
+            })
+            .filter_map(|layer| {
+                let wallpaper = self.assignments.get(&layer.name)?;
+                Some((layer.name.clone(), wallpaper.clone()))
+            })
+            .collect();
+        for (output, wallpaper) in outputs {
+            tracing::debug!(
+                "Reloading {} on {output} for the larger output",
+                wallpaper.name()
+            );
+            self.assign(&output, wallpaper);
+        }
+    }
+
+    /// Decode the assets of a wallpaper in the background without showing it
+    pub fn preload(&mut self, wallpaper: Wallpaper) {
+        let target = self.largest_output();
+        self.loader.preload(wallpaper, target);
+    }
+
+    /// Show the wallpaper of `source` on `targets`, drawing the same frame
+    ///
+    /// Targets share the source's pipelines until they are assigned a
+    /// wallpaper of their own. Naming an output that mirrors another as the
+    /// source mirrors that one's source instead.
+    pub fn mirror(&mut self, source: &str, targets: &[String]) -> Result<(), String> {
+        let source = self
+            .mirrors
+            .get(source)
+            .cloned()
+            .unwrap_or_else(|| source.to_string());
+        for output in targets.iter().chain(Some(&source)) {
+            if !self.wallpapers.iter().any(|layer| layer.name == *output) {
+                return Err(format!("Output {output} not found"));
+            }
+        }
+        if targets.contains(&source) {
+            return Err(format!("Output {source} cannot mirror itself"));
+        }
+
+        for target in targets {
+            // Outputs mirroring a target follow it to the new source
+            for mirrored in self.mirrors.values_mut() {
+                if *mirrored == *target {
+                    *mirrored = source.clone();
+                }
+            }
+            self.mirrors.insert(target.clone(), source.clone());
+            self.assignments.remove(target);
+            tracing::info!("Mirroring {source} on {target}");
+        }
+        self.sync_mirrors();
+        Ok(())
+    }
+
+    /// Render an output, or every output if `output` is None, at `scale`
+    /// times its resolution, scales outside `RENDER_SCALE_RANGE` are refused
+    pub fn set_render_scale(&mut self, output: Option<&str>, scale: f32) -> Result<(), String> {
+        let (min, max) = RENDER_SCALE_RANGE;
+        if !(min..=max).contains(&scale) {
+            return Err(format!(
+                "Invalid render scale {scale}, it must be between {min} and {max}"
+            ));
+        }
+        let outputs: Vec<String> = match output {
+            Some(output) if !self.wallpapers.iter().any(|layer| layer.name == output) => {
+                return Err(format!("Output {output} not found"));
+            }
+            Some(output) => vec![output.to_string()],
+            None => self
+                .wallpapers
+                .iter()
+                .map(|layer| layer.name.clone())
+                .collect(),
+        };
+
+        for output in outputs {
+            self.render_scales.insert(output.clone(), scale);
+            self.apply_render_scale(&output, scale);
+        }
+        Ok(())
+    }
+
+    /// Render scale of an output set over IPC or by the first matching rule
+    fn render_scale_for(&self, output: &str) -> Option<f32> {
+        self.render_scales.get(output).copied().or_else(|| {
+            self.output_rules
+                .iter()
+                .filter(|rule| rule.matches(output))
+                .find_map(|rule| rule.render_scale)
+        })
+    }
+
+    fn apply_render_scale(&mut self, output: &str, scale: f32) {
+        let Some(layer) = self
+            .wallpapers
+            .iter_mut()
+            .find(|layer| layer.name == output)
+        else {
+            return;
+        };
+        if !layer.set_render_scale(scale) {
+            return;
+        }
+
+        tracing::info!("Rendering {output} at {}x", layer.render_scale());
+        if layer.configured {
+            layer.configure(&self.adapter, &self.device);
+            layer.resize_models(&self.device, &self.queue);
+        }
+    }
+
+    /// Change the display adjustment of an output, or every output if
+    /// `output` is None, nothing changes if the result is out of range
+    pub fn adjust_display(
+        &mut self,
+        output: Option<&str>,
+        change: impl Fn(&mut DisplayAdjustment),
+    ) -> Result<(), String> {
+        let outputs: Vec<String> = match output {
+            Some(output) if !self.wallpapers.iter().any(|layer| layer.name == output) => {
+                return Err(format!("Output {output} not found"));
+            }
+            Some(output) => vec![output.to_string()],
+            None => self
+                .wallpapers
+                .iter()
+                .map(|layer| layer.name.clone())
+                .collect(),
+        };
+
+        let mut adjusted = Vec::with_capacity(outputs.len());
+        for output in outputs {
+            let mut adjustment = self.adjustments.get(&output).copied().unwrap_or_default();
+            change(&mut adjustment);
+            validate_adjustment(&adjustment)?;
+            adjusted.push((output, adjustment));
+        }
+
+        for (output, adjustment) in adjusted {
+            if let Some(layer) = self
+                .wallpapers
+                .iter_mut()
+                .find(|layer| layer.name == output)
+            {
+                if layer.set_adjustment(adjustment) {
+                    tracing::info!("Adjusting {output} to {adjustment:?}");
+                }
+            }
+            if adjustment.is_identity() {
+                self.adjustments.remove(&output);
+            } else {
+                self.adjustments.insert(output, adjustment);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fade the overview blur in or out on an output, or every output if
+    /// `output` is None, toggling it if `enabled` is None
+    ///
+    /// Mirrors show the overview of their source.
+    pub fn set_overview(
+        &mut self,
+        output: Option<&str>,
+        enabled: Option<bool>,
+    ) -> Result<(), String> {
+        if let Some(output) = output {
+            if !self.wallpapers.iter().any(|layer| layer.name == output) {
+                return Err(format!("Output {output} not found"));
+            }
+        }
+
+        for layer in self
+            .wallpapers
+            .iter_mut()
+            .filter(|layer| output.is_none_or(|output| layer.name == output))
+        {
+            let enabled = enabled.unwrap_or(!layer.overview.enabled());
+            if layer.overview.set_enabled(enabled) {
+                tracing::info!(
+                    "Overview mode {} on {}",
+                    if enabled { "on" } else { "off" },
+                    layer.name
+                );
+                layer.damaged = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Turn the animations of every output on or off, toggles them if None,
+    /// returns whether wallpapers animate now
+    pub fn set_motion(&mut self, enabled: Option<bool>) -> bool {
+        let enabled = enabled.unwrap_or(!self.motion);
+        if enabled != self.motion {
+            tracing::info!("Motion {}", if enabled { "on" } else { "off" });
+        }
+        self.motion = enabled;
+        for layer in self.wallpapers.iter_mut() {
+            layer.set_still(!enabled);
+        }
+        enabled
+    }
+
+    /// Wallpaper shown on an output, following mirrors to their source
+    pub fn assignment(&self, output: &str) -> Option<&Wallpaper> {
+        let output = self.mirrors.get(output).map_or(output, String::as_str);
+        self.assignments.get(output)
+    }
+
+    /// Point mirrors at the current pipelines of their source, after either
+    /// output appeared or the source got a new wallpaper
+    pub(crate) fn sync_mirrors(&mut self) {
+        for (target, source) in &self.mirrors {
+            let Some((wallpaper, alpha)) = self
+                .wallpapers
+                .iter()
+                .find(|layer| layer.name == *source)
+                .map(|layer| (layer.wallpaper.clone(), layer.background_alpha()))
+            else {
+                continue;
+            };
+            let Some(layer) = self
+                .wallpapers
+                .iter_mut()
+                .find(|layer| layer.name == *target)
+            else {
+                continue;
+            };
+            if Arc::ptr_eq(&layer.wallpaper, &wallpaper) {
+                continue;
+            }
+
+            layer.wallpaper = wallpaper;
+            layer.mirror_of = Some(source.clone());
+            layer.pending_load = None;
+            layer.stats = FrameStats::default();
+            layer.profiler = Profiler::default();
+            if layer.set_background_alpha(alpha) && layer.configured {
+                layer.configure(&self.adapter, &self.device);
+            }
+            // Redrawn whenever the source presents a frame
+            layer.set_framerate(0);
+            layer.set_tickrate(0);
+            layer.damaged = true;
+        }
+    }
+
     pub fn get_recommended_update_interval(&self) -> Option<Duration> {
         self.wallpapers
             .iter()
@@ -121,11 +741,316 @@ impl Client {
             .max()
     }
 
-    pub fn request_update(&mut self, qh: &QueueHandle<Self>) {
-        self.wallpapers.iter_mut().for_each(|v| {
-            v.request_compositor_update(qh);
+    /// Rebuild the wallpapers that show other layers in the new weather
+    pub fn weather_changed(&mut self) {
+        let outputs: Vec<_> = self
+            .assignments
+            .iter()
+            .filter(|(_, wallpaper)| wallpaper.depends_on_weather())
+            .map(|(output, wallpaper)| (output.clone(), wallpaper.clone()))
+            .collect();
+        for (output, wallpaper) in outputs {
+            tracing::debug!(
+                "Rebuilding {} on {output} for the weather",
+                wallpaper.name()
+            );
+            self.assign(&output, wallpaper);
+        }
+    }
+
+    /// Rebuild the wallpapers that use colors of the new color scheme,
+    /// shaders read it every frame
+    pub fn colors_changed(&mut self) {
+        let outputs: Vec<_> = self
+            .assignments
+            .iter()
+            .filter(|(_, wallpaper)| wallpaper.depends_on_colors())
+            .map(|(output, wallpaper)| (output.clone(), wallpaper.clone()))
+            .collect();
+        for (output, wallpaper) in outputs {
+            tracing::debug!(
+                "Rebuilding {} on {output} for the color scheme",
+                wallpaper.name()
+            );
+            self.assign(&output, wallpaper);
+        }
+    }
+
+    /// Swap in wallpapers that finished loading in the background
+    ///
+    /// Results superseded by a newer request for the same output are dropped,
+    /// so the layer keeps showing its current wallpaper until the latest load lands.
+    pub fn apply_finished_loads(&mut self) {
+        let mut failed = Vec::new();
+        for result in self.loader.finished() {
+            let Some(output) = &result.output else {
+                match result.pipelines {
+                    Ok(_) => tracing::info!("Wallpaper {} preloaded", result.name),
+                    Err(e) => tracing::error!("Failed to preload wallpaper {}: {e}", result.name),
+                }
+                continue;
+            };
+            let Some(layer) = self
+                .wallpapers
+                .iter_mut()
+                .find(|layer| layer.name == *output)
+            else {
+                continue;
+            };
+            if layer.pending_load != Some(result.id) {
+                tracing::debug!(
+                    "Discarding stale load of {} for {}",
+                    result.name,
+                    layer.name
+                );
+                continue;
+            }
+            layer.pending_load = None;
+
+            match result.pipelines {
+                Ok(pipelines) => {
+                    layer.profiler = Profiler::new(&self.device, &self.queue, pipelines.len());
+                    layer.wallpaper = Arc::new(Mutex::new(pipelines));
+                    layer.mirror_of = None;
+                    layer.stats = FrameStats::default();
+                    if layer.set_background_alpha(result.background_alpha) && layer.configured {
+                        // Switching between opaque and transparent changes the alpha mode
+                        layer.configure(&self.adapter, &self.device);
+                    }
+                    layer.resize_models(&self.device, &self.queue);
+                    layer.set_framerate(result.framerate);
+                    layer.set_tickrate(result.tickrate);
+                    layer.set_phase(result.phase);
+                    layer.damaged = true;
+                    tracing::info!("Wallpaper {} ready on {}", result.name, layer.name);
+                    if result.name == fallback::NAME {
+                        continue;
+                    }
+                    let path = self
+                        .assignments
+                        .get(output)
+                        .map(|wallpaper| wallpaper.path.to_string_lossy())
+                        .unwrap_or_default();
+                    hooks::run(Event::WallpaperChanged {
+                        output,
+                        wallpaper: &result.name,
+                        path: &path,
+                    });
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to load wallpaper {} for {}: {e}",
+                        result.name,
+                        layer.name
+                    );
+                    hooks::run(Event::Error {
+                        output,
+                        wallpaper: &result.name,
+                        message: &e,
+                    });
+                    if result.name != fallback::NAME {
+                        failed.push((output.clone(), format!("{}: {e}", result.name)));
+                    }
+                }
+            }
+        }
+        for (output, error) in failed {
+            self.show_error(&output, &error);
+        }
+        self.sync_mirrors();
+    }
+
+    /// Draw the layers that are due, sources before their mirrors, and ask
+    /// the screen captures for their next frames
+    ///
+    /// Layers that committed a frame wait for the compositor's frame callback
+    /// before drawing again, so nothing is drawn faster than it is shown.
+    pub fn draw_due(&mut self, qh: &QueueHandle<Self>) {
+        let mut presented = Vec::new();
+        for layer in self.wallpapers.iter_mut() {
+            if layer.mirror_of.is_none()
+                && layer.is_due()
+                && layer.draw(qh, &self.device, &self.queue)
+            {
+                presented.push(layer.name.clone());
+            }
+        }
+        for layer in self.wallpapers.iter_mut() {
+            let Some(source) = &layer.mirror_of else {
+                continue;
+            };
+            if presented.contains(source) {
+                layer.damaged = true;
+            }
+            if layer.is_due() {
+                layer.draw(qh, &self.device, &self.queue);
+            }
+        }
+
+        let Some(screencopy) = &self.screencopy else {
+            return;
+        };
+        let due = self
+            .captures_requested
+            .is_none_or(|requested| requested.elapsed() >= CAPTURE_INTERVAL);
+        if due && self.captures.is_active() {
+            self.captures_requested = Some(Instant::now());
+            self.captures.request_frames(screencopy, qh, |name| {
+                self.wallpapers
+                    .iter()
+                    .find(|layer| layer.name == name)
+                    .map(|layer| &layer.output)
+            });
+        }
+    }
+
+    /// When the main loop has to wake up without an event, for the next
+    /// frame of an animation, screen captures or the slideshow, None to sleep
+    /// until an event arrives
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let captures = (self.screencopy.is_some() && self.captures.is_active()).then(|| {
+            self.captures_requested
+                .map_or_else(Instant::now, |requested| requested + CAPTURE_INTERVAL)
         });
+        self.wallpapers
+            .iter()
+            .filter_map(WallpaperLayer::next_frame)
+            .chain(captures)
+            .chain(self.idle.next_slide())
+            .min()
+    }
+}
+
+/// Check that a display adjustment only holds settings the shader can apply
+fn validate_adjustment(adjustment: &DisplayAdjustment) -> Result<(), String> {
+    if !adjustment.brightness.is_finite() || adjustment.brightness < 0.0 {
+        return Err(format!("Invalid brightness {}", adjustment.brightness));
+    }
+    if !adjustment.contrast.is_finite() || adjustment.contrast < 0.0 {
+        return Err(format!("Invalid contrast {}", adjustment.contrast));
+    }
+    if !adjustment.gamma.is_finite() || adjustment.gamma <= 0.0 {
+        return Err(format!("Invalid gamma {}", adjustment.gamma));
+    }
+    if !(1000..=40000).contains(&adjustment.temperature) {
+        return Err(format!(
+            "Color temperature {}K is outside 1000K to 40000K",
+            adjustment.temperature
+        ));
     }
+    Ok(())
+}
+
+/// Pick an adapter as configured and open a device on it
+///
+/// If no hardware adapter works, a software adapter such as llvmpipe is used
+/// unless the config forbids it.
+fn create_device(instance: &Instance, gpu: &GpuConfig) -> Result<(Adapter, Device, Queue), String> {
+    let error = match select_adapter(instance, gpu) {
+        Ok(adapter) => match open_device(&adapter) {
+            Ok((device, queue)) => return Ok((adapter, device, queue)),
+            Err(e) => e,
+        },
+        Err(e) => e,
+    };
+    if !gpu.allow_software {
+        return Err(error);
+    }
+
+    let adapter = instance
+        .enumerate_adapters(Backends::all())
+        .into_iter()
+        .find(is_software)
+        .ok_or_else(|| format!("{error}, and no software adapter is available"))?;
+    tracing::warn!(
+        "{error}, falling back to software rendering on {}. Expect high CPU usage",
+        adapter.get_info().name
+    );
+    let (device, queue) = open_device(&adapter)?;
+    Ok((adapter, device, queue))
+}
+
+/// Find the adapter named in the config, or the best hardware adapter
+fn select_adapter(instance: &Instance, gpu: &GpuConfig) -> Result<Adapter, String> {
+    if let Some(wanted) = &gpu.adapter {
+        let adapters = instance.enumerate_adapters(Backends::all());
+        let found = match wanted.parse::<usize>() {
+            Ok(index) => adapters.into_iter().nth(index),
+            Err(_) => {
+                let wanted = wanted.to_lowercase();
+                adapters
+                    .into_iter()
+                    .find(|adapter| adapter.get_info().name.to_lowercase().contains(&wanted))
+            }
+        };
+        return found.ok_or_else(|| format!("No GPU adapter matches '{wanted}'"));
+    }
+
+    let options = wgpu::RequestAdapterOptions {
+        power_preference: gpu.power_preference.into(),
+        ..Default::default()
+    };
+    pollster::block_on(instance.request_adapter(&options))
+        .filter(|adapter| !is_software(adapter))
+        .ok_or_else(|| "No hardware GPU adapter found".to_string())
+}
+
+/// Open a device, with timestamp queries if the adapter has them so layer
+/// costs can be measured on the GPU, BC and ASTC compressed textures for
+/// images baked by `wlrs bake` and a pipeline cache, see `pipeline_cache`
+fn open_device(adapter: &Adapter) -> Result<(Device, Queue), String> {
+    let descriptor = DeviceDescriptor {
+        required_features: adapter.features()
+            & (Features::TIMESTAMP_QUERY
+                | Features::TIMESTAMP_QUERY_INSIDE_PASSES
+                | Features::TEXTURE_COMPRESSION_BC
+                | Features::TEXTURE_COMPRESSION_ASTC
+                | Features::PIPELINE_CACHE),
+        ..Default::default()
+    };
+    // Capture layers sample the compositor's copies in place on such a device
+    match dmabuf::open_device(adapter, &descriptor) {
+        Ok(opened) => return Ok(opened),
+        Err(e) => tracing::debug!("Opening a device without dmabuf support: {e}"),
+    }
+    pollster::block_on(adapter.request_device(&descriptor, None)).map_err(|e| {
+        format!(
+            "Failed to open a device on {}: {e}",
+            adapter.get_info().name
+        )
+    })
+}
+
+fn is_software(adapter: &Adapter) -> bool {
+    adapter.get_info().device_type == DeviceType::Cpu
+}
+
+/// Print the adapters `--adapter` can select, with their index
+pub fn list_adapters() {
+    let instance = Instance::default();
+    for (index, adapter) in instance
+        .enumerate_adapters(Backends::all())
+        .iter()
+        .enumerate()
+    {
+        let info = adapter.get_info();
+        println!(
+            "{index}: {} ({:?}, {:?})",
+            info.name, info.device_type, info.backend
+        );
+    }
+}
+
+/// Return a flag that is raised once the device is lost
+fn watch_device_lost(device: &Device) -> Arc<AtomicBool> {
+    let lost = Arc::new(AtomicBool::new(false));
+    let flag = lost.clone();
+    device.set_device_lost_callback(move |reason, message| {
+        tracing::error!("GPU device lost ({reason:?}): {message}");
+        flag.store(true, Ordering::Relaxed);
+        crate::wake::wake();
+    });
+    lost
 }
 
 impl CompositorHandler for Client {
@@ -133,9 +1058,25 @@ impl CompositorHandler for Client {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_factor: i32,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
     ) {
+        let Some(layer) = self
+            .wallpapers
+            .iter_mut()
+            .find(|layer| layer.layer.wl_surface() == surface)
+        else {
+            return;
+        };
+        if !layer.set_scale(new_factor) {
+            return;
+        }
+
+        tracing::info!("Output {} scale changed to {new_factor}", layer.name);
+        if layer.configured {
+            layer.configure(&self.adapter, &self.device);
+            layer.resize_models(&self.device, &self.queue);
+        }
     }
 
     fn transform_changed(
@@ -151,13 +1092,18 @@ impl CompositorHandler for Client {
         &mut self,
         _conn: &Connection,
         qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
+        surface: &wl_surface::WlSurface,
         _time: u32,
     ) {
-        // Draw all wallpapers that need updating
-        self.wallpapers
+        // The layer may draw again, animated ones right away
+        if let Some(layer) = self
+            .wallpapers
             .iter_mut()
-            .for_each(|v| WallpaperLayer::draw(v, qh, &self.device, &self.queue));
+            .find(|layer| layer.layer.wl_surface() == surface)
+        {
+            layer.frame_done();
+        }
+        self.draw_due(qh);
     }
 
     fn surface_enter(
@@ -199,22 +1145,27 @@ impl LayerShellHandler for Client {
         configure: LayerSurfaceConfigure,
         _serial: u32,
     ) {
-        println!("Received configure request");
+        tracing::trace!("Received configure request");
         // iter_mut().find(|wallpaper| wallpaper.layer == *layer)
         if let Some(v) = self
             .wallpapers
             .iter_mut()
             .find(|wallpaper| wallpaper.layer == *layer)
         {
-            println!(
+            tracing::debug!(
                 "Received configure layer {} with new size: {:?}",
-                v.name, configure.new_size
+                v.name,
+                configure.new_size
             );
-            v.set_size(configure.new_size.0, configure.new_size.1);
+            let resized = v.set_size(configure.new_size.0, configure.new_size.1);
             if !v.configured {
-                println!("Configuring layer: {}", v.name);
+                tracing::debug!("Configuring layer: {}", v.name);
                 v.configure(&self.adapter, &self.device);
+                v.resize_models(&self.device, &self.queue);
                 v.draw(qh, &self.device, &self.queue);
+            } else if resized {
+                v.configure(&self.adapter, &self.device);
+                v.resize_models(&self.device, &self.queue);
             }
         };
     }
@@ -230,10 +1181,25 @@ impl SeatHandler for Client {
     fn new_capability(
         &mut self,
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _seat: wl_seat::WlSeat,
-        _capability: Capability,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
     ) {
+        if capability != Capability::Pointer || self.pointer.is_some() {
+            return;
+        }
+        // Themed so the cursor keeps its look over the wallpaper
+        let surface = self.compositor.create_surface(qh);
+        match self.seat.get_pointer_with_theme(
+            qh,
+            &seat,
+            self.shm.wl_shm(),
+            surface,
+            ThemeSpec::default(),
+        ) {
+            Ok(pointer) => self.pointer = Some(pointer),
+            Err(e) => tracing::warn!("Failed to get the pointer: {e}"),
+        }
     }
 
     fn remove_capability(
@@ -241,13 +1207,117 @@ impl SeatHandler for Client {
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
         _seat: wl_seat::WlSeat,
-        _capability: Capability,
+        capability: Capability,
     ) {
+        if capability == Capability::Pointer {
+            if let Some(pointer) = self.pointer.take() {
+                pointer.pointer().release();
+            }
+        }
     }
 
     fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
 }
 
+impl PointerHandler for Client {
+    fn pointer_frame(
+        &mut self,
+        conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _pointer: &wl_pointer::WlPointer,
+        events: &[PointerEvent],
+    ) {
+        for event in events {
+            let (x, y) = event.position;
+            let input = match event.kind {
+                PointerEventKind::Enter { .. } => {
+                    if let Some(pointer) = &self.pointer {
+                        let _ = pointer.set_cursor(conn, CursorIcon::Default);
+                    }
+                    PointerInput::Motion { x, y }
+                }
+                PointerEventKind::Leave { .. } => continue,
+                PointerEventKind::Motion { .. } => PointerInput::Motion { x, y },
+                PointerEventKind::Press { button, .. } => PointerInput::Press { x, y, button },
+                PointerEventKind::Release { button, .. } => PointerInput::Release { x, y, button },
+                PointerEventKind::Axis {
+                    horizontal,
+                    vertical,
+                    ..
+                } => PointerInput::Scroll {
+                    x,
+                    y,
+                    dx: horizontal.absolute,
+                    dy: vertical.absolute,
+                },
+            };
+            let Some(layer) = self
+                .wallpapers
+                .iter()
+                .find(|layer| layer.layer.wl_surface() == &event.surface)
+            else {
+                continue;
+            };
+            for model in layer.wallpaper.lock().unwrap().iter_mut() {
+                model.pointer(&input);
+            }
+        }
+    }
+}
+
+impl ShmHandler for Client {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
+impl DmabufHandler for Client {
+    fn dmabuf_state(&mut self) -> &mut DmabufState {
+        &mut self.dmabuf
+    }
+
+    fn dmabuf_feedback(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _proxy: &zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1,
+        feedback: DmabufFeedback,
+    ) {
+        self.dmabuf_formats = feedback
+            .format_table()
+            .iter()
+            .filter(|pair| pair.modifier == dmabuf::LINEAR_MODIFIER)
+            .map(|pair| pair.format)
+            .collect();
+    }
+
+    // Buffers are created with `create_immed`, a failure fails the capture
+    fn created(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _params: &zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1,
+        _buffer: wl_buffer::WlBuffer,
+    ) {
+    }
+
+    fn failed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _params: &zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1,
+    ) {
+    }
+
+    fn released(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _buffer: &wl_buffer::WlBuffer,
+    ) {
+    }
+}
+
 impl OutputHandler for Client {
     fn output_state(&mut self) -> &mut OutputState {
         &mut self.output
@@ -259,17 +1329,107 @@ impl OutputHandler for Client {
         qh: &QueueHandle<Self>,
         output: wl_output::WlOutput,
     ) {
-        println!("Accepted new output: {output:?}");
+        tracing::info!("Accepted new output: {output:?}");
         let wallpaper = WallpaperLayer::new(self, conn, qh, &output);
+        let name = wallpaper.name.clone();
         self.wallpapers.push(wallpaper);
+        let description = self
+            .output
+            .info(&output)
+            .and_then(|info| info.description)
+            .unwrap_or_default();
+        hooks::run(Event::OutputAdded {
+            output: &name,
+            description: &description,
+        });
+        if let Some(scale) = self.render_scale_for(&name) {
+            self.apply_render_scale(&name, scale);
+        }
+        if let Some(layer) = self.wallpapers.last_mut() {
+            if let Some(adjustment) = self.adjustments.get(&name).copied() {
+                layer.set_adjustment(adjustment);
+            }
+            layer.set_still(!self.motion);
+        }
+
+        // Bring back what the output showed before it went away, or pick a
+        // wallpaper by the configured rules
+        if self.mirrors.contains_key(&name) {
+            tracing::info!("Restoring mirror of {} on {name}", self.mirrors[&name]);
+            self.sync_mirrors();
+        } else if let Some(wallpaper) = self.assignments.get(&name).cloned() {
+            tracing::info!("Restoring wallpaper {} on {name}", wallpaper.name());
+            self.assign(&name, wallpaper);
+        } else if let Some(wanted) = self
+            .output_rules
+            .iter()
+            .filter(|rule| rule.matches(&name))
+            .find_map(|rule| rule.wallpaper.clone())
+        {
+            match find_wallpaper(&wanted) {
+                Ok(wallpaper) => {
+                    tracing::info!("Showing wallpaper {wanted} on {name} as configured");
+                    self.assign(&name, wallpaper);
+                }
+                Err(e) => {
+                    tracing::warn!("Wallpaper {wanted} configured for {name}: {e}");
+                    self.show_error(&name, &format!("{wanted}: {e}"));
+                }
+            }
+        }
+        // A larger output than the others needs larger images
+        self.reload_undersized();
     }
 
     fn update_output(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
+        output: wl_output::WlOutput,
     ) {
+        let info = self.output.info(&output);
+        let Some(layer) = self
+            .wallpapers
+            .iter_mut()
+            .find(|layer| layer.output == output)
+        else {
+            return;
+        };
+        let Some(info) = info else {
+            return;
+        };
+        layer.set_refresh_rate(&info);
+
+        // The compositor resizes the layer with a configure of its own, which
+        // may come later or not at all if only the pixels behind it changed
+        let resized = info.logical_size.is_some_and(|(width, height)| {
+            layer.set_size(width.max(0) as u32, height.max(0) as u32)
+        });
+        let rescaled = layer.set_scale(info.scale_factor);
+        if (resized || rescaled) && layer.configured {
+            tracing::info!(
+                "Output {} changed to {}x{} at scale {}",
+                layer.name,
+                layer.width,
+                layer.height,
+                layer.scale
+            );
+            layer.configure(&self.adapter, &self.device);
+            layer.resize_models(&self.device, &self.queue);
+        }
+
+        // A new mode or rotation may change which layers the wallpaper shows
+        let name = layer.name.clone();
+        if let Some(wallpaper) = self
+            .assignments
+            .get(&name)
+            .filter(|wallpaper| wallpaper.depends_on_screen())
+            .cloned()
+        {
+            tracing::debug!("Rebuilding {} on {name} for its new mode", wallpaper.name());
+            self.assign(&name, wallpaper);
+        }
+        self.reload_undersized();
     }
 
     fn output_destroyed(
@@ -286,3 +1446,6 @@ delegate_layer!(Client);
 delegate_registry!(Client);
 delegate_seat!(Client);
 delegate_output!(Client);
+delegate_shm!(Client);
+delegate_pointer!(Client);
+delegate_dmabuf!(Client);
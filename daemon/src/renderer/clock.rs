@@ -0,0 +1,40 @@
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+/// Start of the animation clock shared by every layer
+///
+/// Layers advance their wallpaper's animations to this clock's time rather
+/// than by the time since their own previous tick, so the same wallpaper on
+/// several outputs shows the same moment however late each one was loaded,
+/// paused or drawn. Layers ticking at the same rate tick together on a grid
+/// counted from the start, see `next_tick`. The clock starts when it is
+/// first read, before the first frame.
+static EPOCH: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Time on the clock at `at`, `phase` seconds behind it for a wallpaper
+/// offset from the others, see `WallpaperManifest::phase`
+pub fn time(at: Instant, phase: f32) -> Duration {
+    let elapsed = at.saturating_duration_since(*EPOCH).as_secs_f64();
+    Duration::try_from_secs_f64((elapsed - phase as f64).max(0.0)).unwrap_or_default()
+}
+
+/// Latest tick at or before `at` on the grid of a layer ticking every
+/// `interval`, `at` itself for layers that tick on every frame
+pub fn tick_before(at: Instant, interval: Duration) -> Instant {
+    let interval = interval.as_nanos();
+    if interval == 0 {
+        return at;
+    }
+    let elapsed = at.saturating_duration_since(*EPOCH).as_nanos();
+    *EPOCH + Duration::from_nanos((elapsed - elapsed % interval) as u64)
+}
+
+/// First tick after `at` on the grid of a layer ticking every `interval`
+///
+/// A layer that fell behind skips the ticks it missed instead of drawing
+/// them in a burst.
+pub fn next_tick(at: Instant, interval: Duration) -> Instant {
+    tick_before(at, interval) + interval
+}
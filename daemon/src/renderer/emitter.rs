@@ -0,0 +1,314 @@
+use common::manifest::{Edges, Emitter, EmitterShape};
+use rand::Rng;
+
+use crate::{
+    asset::color::srgb_to_linear, renderer::models::color::parse_color, script::particles::Particle,
+};
+
+/// The particles of an `Emitter` from the manifest, moved on the CPU each
+/// tick for particle layers without a script
+///
+/// The emitter works in fractions of the screen with y pointing down, the
+/// particles are written in clip space like a script's.
+#[derive(Debug)]
+pub struct Simulator {
+    emitter: Emitter,
+    /// The emitter's colors, linear
+    colors: Vec<[f32; 4]>,
+    /// Textures of the layer, each particle draws one at random
+    textures: u32,
+    /// Frames of the sprite sheets a second
+    fps: f32,
+    /// Seconds since the start, for the gusts of wind
+    time: f32,
+    /// Particles owed to the rate, one is spawned for each whole one
+    pending: f32,
+    /// Seconds each particle has lived and lives for, by slot
+    ages: Vec<[f32; 2]>,
+}
+
+impl Simulator {
+    /// Simulate `emitter` for a layer with `textures` sprite sheets played
+    /// at `fps`
+    pub fn new(emitter: Emitter, textures: u32, fps: f32) -> Result<Self, String> {
+        let colors = emitter
+            .colors
+            .iter()
+            .map(|color| {
+                let [r, g, b, a] = parse_color(color)?;
+                Ok([srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a])
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            emitter,
+            colors,
+            textures: textures.max(1),
+            fps,
+            time: 0.0,
+            pending: 0.0,
+            ages: Vec::new(),
+        })
+    }
+
+    /// Spawn, move and age `particles` for `dt` seconds
+    pub fn step(&mut self, particles: &mut [Particle], dt: f32) {
+        let Self {
+            emitter,
+            colors,
+            textures,
+            fps,
+            time,
+            pending,
+            ages,
+        } = self;
+        *time += dt;
+        // Two waves out of step, so the gusts do not come like clockwork
+        let calm = 0.5 + 0.25 * ((*time * 0.9).sin() + (*time * 2.3).sin());
+        let wind = emitter.wind.map(|wind| wind * (1.0 - emitter.gust * calm));
+        ages.resize(particles.len(), [0.0, 0.0]);
+        *pending += emitter.rate * dt;
+        let mut rng = rand::thread_rng();
+
+        for (particle, [age, lifetime]) in particles.iter_mut().zip(ages.iter_mut()) {
+            if particle.alive == 1 {
+                *age += dt;
+                if *age >= *lifetime {
+                    particle.alive = 0;
+                }
+            }
+            if particle.alive == 1 {
+                let [ax, ay] = acceleration(emitter, particle.position);
+                particle.velocity[0] += 2.0 * ax * dt;
+                particle.velocity[1] -= 2.0 * ay * dt;
+                particle.position[0] += (particle.velocity[0] + 2.0 * wind[0]) * dt;
+                particle.position[1] += (particle.velocity[1] - 2.0 * wind[1]) * dt;
+                if !collide(particle, emitter) {
+                    continue;
+                }
+            } else if *pending >= 1.0 {
+                *pending -= 1.0;
+                *age = 0.0;
+                *lifetime = pick(&mut rng, emitter.lifetime);
+                let [x, y] = spawn_position(&mut rng, emitter);
+                *particle = Particle::new(x * 2.0 - 1.0, 1.0 - y * 2.0, 0.0);
+                particle.velocity = [
+                    2.0 * pick(&mut rng, emitter.velocity_x),
+                    -2.0 * pick(&mut rng, emitter.velocity_y),
+                ];
+                particle.texture = rng.gen_range(0..*textures);
+            } else {
+                continue;
+            }
+
+            particle.frame = *age * *fps;
+            let life = *age / *lifetime;
+            particle.size = emitter.size_at(life);
+            particle.color = color_at(colors, life);
+        }
+        // Particles owed while all slots were taken are dropped, rather
+        // than spawned in a burst once some are free
+        *pending = pending.fract();
+    }
+}
+
+/// A random value between the ends of `range`
+fn pick(rng: &mut impl Rng, [min, max]: [f32; 2]) -> f32 {
+    if min < max {
+        rng.gen_range(min..max)
+    } else {
+        min
+    }
+}
+
+fn spawn_position(rng: &mut impl Rng, emitter: &Emitter) -> [f32; 2] {
+    let [from, to] = [emitter.from, emitter.to];
+    let (along, across) = match emitter.shape {
+        EmitterShape::Point => return from,
+        EmitterShape::Line => {
+            let along = rng.gen::<f32>();
+            (along, along)
+        }
+        EmitterShape::Area => (rng.gen::<f32>(), rng.gen::<f32>()),
+    };
+    [
+        from[0] + (to[0] - from[0]) * along,
+        from[1] + (to[1] - from[1]) * across,
+    ]
+}
+
+/// Pull of gravity and the attractors on a particle at `position` in clip
+/// space, in screens per second squared, y pointing down
+fn acceleration(emitter: &Emitter, [x, y]: [f32; 2]) -> [f32; 2] {
+    let position = [(x + 1.0) / 2.0, (1.0 - y) / 2.0];
+    let mut acceleration = [0.0, emitter.gravity];
+    for attractor in &emitter.attractors {
+        let offset = [
+            attractor.position[0] - position[0],
+            attractor.position[1] - position[1],
+        ];
+        let distance = offset[0].hypot(offset[1]);
+        // Right on the point the pull has no direction
+        if distance < attractor.radius && distance > f32::EPSILON {
+            let pull = attractor.strength * (1.0 - distance / attractor.radius) / distance;
+            acceleration[0] += offset[0] * pull;
+            acceleration[1] += offset[1] * pull;
+        }
+    }
+    acceleration
+}
+
+/// Apply the emitter's `edges` to a particle past them, returns whether it
+/// is still alive
+fn collide(particle: &mut Particle, emitter: &Emitter) -> bool {
+    for axis in 0..2 {
+        let position = particle.position[axis];
+        if position.abs() <= 1.0 {
+            continue;
+        }
+        let side = position.signum();
+        match emitter.edges {
+            Edges::Pass => {}
+            Edges::Bounce => {
+                particle.position[axis] = side * 2.0 - position;
+                particle.velocity[axis] =
+                    -side * particle.velocity[axis].abs() * emitter.bounciness;
+            }
+            Edges::Wrap => particle.position[axis] -= side * 2.0,
+            Edges::Kill => {
+                particle.alive = 0;
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Color of a particle at `life`, see `Emitter::stops_at`
+fn color_at(colors: &[[f32; 4]], life: f32) -> [f32; 4] {
+    let (first, second, blend) = Emitter::stops_at(colors.len(), life);
+    let [first, second] = [colors[first], colors[second]];
+    std::array::from_fn(|i| first[i] + (second[i] - first[i]) * blend)
+}
+
+#[cfg(test)]
+mod tests {
+    use common::manifest::Attractor;
+
+    use super::*;
+
+    #[test]
+    fn test_acceleration() {
+        let mut emitter = Emitter {
+            gravity: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(acceleration(&emitter, [0.0, 0.0]), [0.0, 0.5]);
+
+        // Halfway to its radius the pull is half of its strength
+        emitter.attractors.push(Attractor {
+            position: [0.75, 0.5],
+            strength: 1.0,
+            radius: 0.5,
+        });
+        assert_eq!(acceleration(&emitter, [0.0, 0.0]), [0.5, 0.5]);
+        assert_eq!(acceleration(&emitter, [0.5, 0.0]), [0.0, 0.5]);
+        assert_eq!(acceleration(&emitter, [-0.5, 0.0]), [0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_collide() {
+        let particle = |x: f32| {
+            let mut particle = Particle::new(x, 0.0, 0.1);
+            particle.velocity = [0.5, 0.0];
+            particle
+        };
+        let collide = |edges: Edges, mut particle: Particle| {
+            let emitter = Emitter {
+                edges,
+                ..Default::default()
+            };
+            let alive = collide(&mut particle, &emitter);
+            (alive, particle)
+        };
+
+        let (alive, bounced) = collide(Edges::Bounce, particle(1.25));
+        assert!(alive);
+        assert_eq!(
+            (bounced.position, bounced.velocity),
+            ([0.75, 0.0], [-0.25, 0.0])
+        );
+
+        let (alive, wrapped) = collide(Edges::Wrap, particle(1.25));
+        assert!(alive);
+        assert_eq!(wrapped.position, [-0.75, 0.0]);
+
+        let (alive, passed) = collide(Edges::Pass, particle(1.25));
+        assert!(alive);
+        assert_eq!(passed.position, [1.25, 0.0]);
+
+        let (alive, killed) = collide(Edges::Kill, particle(1.25));
+        assert!(!alive);
+        assert_eq!(killed.alive, 0);
+
+        let (alive, inside) = collide(Edges::Kill, particle(0.5));
+        assert!(alive);
+        assert_eq!(inside.position, [0.5, 0.0]);
+    }
+
+    #[test]
+    fn test_step() {
+        let emitter = Emitter {
+            rate: 8.0,
+            lifetime: [1.0, 1.0],
+            velocity_x: [0.0, 0.0],
+            velocity_y: [0.0, 0.0],
+            colors: vec!["#000000".to_string(), "#ffffff00".to_string()],
+            sizes: vec![0.1, 0.3],
+            ..Default::default()
+        };
+        let mut simulator = Simulator::new(emitter, 1, 10.0).unwrap();
+        let mut particles = vec![Particle::dead(); 4];
+        let alive = |particles: &[Particle]| particles.iter().filter(|p| p.alive == 1).count();
+        let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+
+        // A quarter of a second owes two particles, born at the point
+        simulator.step(&mut particles, 0.25);
+        assert_eq!(alive(&particles), 2);
+        assert_eq!(particles[0].position, [0.0, 0.0]);
+        assert_eq!(particles[0].velocity, [0.0, 0.0]);
+        assert!(close(&[particles[0].size], &[0.1]));
+        assert!(close(&particles[0].color, &[0.0, 0.0, 0.0, 1.0]));
+
+        // Halfway through their life, while the other two are born
+        simulator.step(&mut particles, 0.5);
+        assert_eq!(alive(&particles), 4);
+        assert_eq!(particles[0].frame, 5.0);
+        assert!(close(&[particles[0].size], &[0.2]));
+        assert!(close(&particles[0].color, &[0.5, 0.5, 0.5, 0.5]));
+
+        // The two particles owed once every slot was taken are dropped
+        // rather than spawned in a burst
+        particles[0].alive = 0;
+        simulator.step(&mut particles, 0.0625);
+        assert_eq!(alive(&particles), 3);
+
+        // The oldest particle dies at the end of its life, and the next one
+        // owed takes its slot
+        simulator.step(&mut particles, 0.5);
+        assert_eq!(alive(&particles), 4);
+        assert_eq!(particles[1].frame, 0.0);
+    }
+
+    #[test]
+    fn test_color_at() {
+        let colors = [
+            [0.0, 0.0, 0.0, 1.0],
+            [1.0, 0.5, 0.0, 1.0],
+            [1.0, 1.0, 1.0, 0.0],
+        ];
+        assert_eq!(color_at(&colors, 0.0), colors[0]);
+        assert_eq!(color_at(&colors, 0.25), [0.5, 0.25, 0.0, 1.0]);
+        assert_eq!(color_at(&colors, 1.0), colors[2]);
+        assert_eq!(color_at(&colors[..1], 0.5), colors[0]);
+    }
+}
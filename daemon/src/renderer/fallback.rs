@@ -0,0 +1,81 @@
+use std::{path::PathBuf, sync::OnceLock};
+
+use common::{manifest::WallpaperManifest, wallpaper::Wallpaper};
+use toml::{Table, Value};
+
+use crate::config::{expand_home, ErrorConfig};
+
+/// Name of the error wallpaper, parenthesized so it does not pass for one
+/// of the library
+pub const NAME: &str = "(error)";
+
+/// Characters of the error written on the wallpaper, the rest is in the log
+const MAX_TEXT: usize = 160;
+
+static CONFIG: OnceLock<ErrorConfig> = OnceLock::new();
+
+/// Set what the error wallpaper looks like, before any wallpaper is loaded
+pub fn init(config: ErrorConfig) {
+    if CONFIG.set(config).is_err() {
+        tracing::warn!("A wallpaper failed before the error wallpaper was configured");
+    }
+}
+
+fn config() -> &'static ErrorConfig {
+    CONFIG.get_or_init(ErrorConfig::default)
+}
+
+/// Wallpaper shown on an output whose wallpaper failed to load with `error`
+///
+/// Built like any other from a manifest, so it loads through the same
+/// workers and fails the same way if the configured color or image is bad.
+pub fn wallpaper(error: &str) -> Result<Wallpaper, String> {
+    let config = config();
+    let mut layers = vec![layer("background", config.color.clone().into())];
+    if let Some(image) = &config.image {
+        let image = expand_home(image.clone());
+        layers.push(layer("image", image.to_string_lossy().into_owned().into()));
+    }
+    if config.text {
+        let mut text = Table::new();
+        text.insert("text".to_string(), summary(error).into());
+        text.insert("size".to_string(), 20.0.into());
+        text.insert("color".to_string(), "#ffffff".into());
+        layers.push(layer("message", text.into()));
+    }
+
+    let mut manifest = Table::new();
+    manifest.insert("name".to_string(), NAME.into());
+    manifest.insert("framerate".to_string(), 0.into());
+    manifest.insert("tickrate".to_string(), 0.into());
+    manifest.insert("layers".to_string(), layers.into());
+    let manifest: WallpaperManifest = Value::Table(manifest)
+        .try_into()
+        .map_err(|e| format!("Invalid error wallpaper: {e}"))?;
+
+    Ok(Wallpaper {
+        manifest,
+        path: PathBuf::new(),
+        warnings: Vec::new(),
+        variant: None,
+        permissions: Vec::new(),
+    })
+}
+
+fn layer(name: &str, content: Value) -> Value {
+    let mut layer = Table::new();
+    layer.insert("name".to_string(), name.into());
+    layer.insert("content".to_string(), content);
+    layer.into()
+}
+
+/// First line of `error`, shortened to fit on the screen
+fn summary(error: &str) -> String {
+    let line = error.lines().next().unwrap_or_default();
+    if line.chars().count() <= MAX_TEXT {
+        return line.to_string();
+    }
+    let mut summary: String = line.chars().take(MAX_TEXT - 1).collect();
+    summary.push('…');
+    summary
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use common::{manifest::Sampling, types::DisplayAdjustment};
+use image::DynamicImage;
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols::ext::idle_notify::v1::client::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::ExtIdleNotifierV1,
+};
+
+use super::{
+    client::Client,
+    models::{texture::TextureModelBuilder, ModelBuilder},
+    pipeline::Pipelines,
+    profiler::Profiler,
+};
+use crate::{
+    asset::image::ImageTexture,
+    config::{IdleAction, IdleConfig},
+};
+
+/// Label of the model showing the frame of an idle output, see `IdleAction::Static`
+const IDLE_FRAME: &str = "idle-frame";
+
+/// Idle notifications and the outputs the idle action was applied to
+#[derive(Default)]
+pub struct Idle {
+    pub config: IdleConfig,
+    /// None if the compositor cannot tell when the user is idle
+    pub notifier: Option<ExtIdleNotifierV1>,
+    /// One notification for each timeout in use, in seconds
+    notifications: HashMap<u64, ExtIdleNotificationV1>,
+    /// Outputs that are idle, by name
+    outputs: HashMap<String, IdleOutput>,
+}
+
+struct IdleOutput {
+    /// Timeout of the notification that made the output idle
+    timeout: u64,
+    /// When the slideshow shows the next wallpaper
+    next_slide: Option<Instant>,
+}
+
+impl Idle {
+    pub fn new(config: IdleConfig, notifier: Option<ExtIdleNotifierV1>) -> Self {
+        Self {
+            config,
+            notifier,
+            ..Default::default()
+        }
+    }
+
+    /// When the slideshow shows the next wallpaper on any idle output
+    pub fn next_slide(&self) -> Option<Instant> {
+        self.outputs
+            .values()
+            .filter_map(|output| output.next_slide)
+            .min()
+    }
+
+    /// Forget the notifications and idle outputs of a lost connection, the
+    /// layers of the new one start out active
+    pub fn reset(&mut self, notifier: Option<ExtIdleNotifierV1>) {
+        self.notifier = notifier;
+        self.notifications.clear();
+        self.outputs.clear();
+    }
+}
+
+impl Client {
+    /// Seconds without input before the idle action applies to an output,
+    /// the wallpaper's own timeout wins over the config
+    fn idle_timeout(&self, output: &str) -> Option<u64> {
+        let timeout = self
+            .assignments
+            .get(output)
+            .and_then(|wallpaper| wallpaper.manifest.idle_timeout)
+            .unwrap_or(self.idle.config.timeout);
+        (timeout > 0).then_some(timeout)
+    }
+
+    /// Ask for a notification for every timeout the outputs use and drop
+    /// the ones no output needs anymore
+    pub fn update_idle_notifications(&mut self, qh: &QueueHandle<Self>) {
+        let (Some(notifier), Some(seat)) = (&self.idle.notifier, self.seat.seats().next()) else {
+            return;
+        };
+        // Idle outputs keep theirs until they resume, even if their wallpaper changed
+        let wanted: HashSet<u64> = self
+            .wallpapers
+            .iter()
+            .filter(|layer| layer.mirror_of.is_none())
+            .filter_map(|layer| self.idle_timeout(&layer.name))
+            .chain(self.idle.outputs.values().map(|output| output.timeout))
+            .collect();
+
+        self.idle.notifications.retain(|timeout, notification| {
+            let keep = wanted.contains(timeout);
+            if !keep {
+                notification.destroy();
+            }
+            keep
+        });
+        for timeout in wanted {
+            self.idle.notifications.entry(timeout).or_insert_with(|| {
+                let millis = u32::try_from(timeout * 1000).unwrap_or(u32::MAX);
+                notifier.get_idle_notification(millis, &seat, qh, timeout)
+            });
+        }
+    }
+
+    /// Show the next wallpaper on idle outputs whose slideshow is due,
+    /// returns the outputs to pick a new wallpaper for
+    pub fn slideshow_due(&mut self) -> Vec<String> {
+        let now = Instant::now();
+        let interval = Duration::from_secs(self.idle.config.slideshow_interval.max(1));
+        let mut due = Vec::new();
+        for (output, idle) in self.idle.outputs.iter_mut() {
+            if idle.next_slide.is_some_and(|next| next <= now) {
+                idle.next_slide = Some(now + interval);
+                due.push(output.clone());
+            }
+        }
+        due
+    }
+
+    /// Apply or undo the idle action on the outputs using `timeout`
+    fn set_idle(&mut self, timeout: u64, idle: bool, qh: &QueueHandle<Self>) {
+        let outputs: Vec<String> = if idle {
+            self.wallpapers
+                .iter()
+                .filter(|layer| layer.mirror_of.is_none() && layer.configured)
+                .map(|layer| layer.name.clone())
+                .filter(|output| !self.idle.outputs.contains_key(output))
+                .filter(|output| self.idle_timeout(output) == Some(timeout))
+                .collect()
+        } else {
+            self.idle
+                .outputs
+                .iter()
+                .filter(|(_, output)| output.timeout == timeout)
+                .map(|(name, _)| name.clone())
+                .collect()
+        };
+        if !outputs.is_empty() {
+            tracing::info!(
+                "{} after {timeout}s, {:?} {}",
+                if idle { "Idle" } else { "Active again" },
+                self.idle.config.action,
+                outputs.join(", ")
+            );
+        }
+
+        for output in outputs {
+            if idle {
+                let next_slide =
+                    (self.idle.config.action == IdleAction::Slideshow).then(Instant::now);
+                self.idle.outputs.insert(
+                    output.clone(),
+                    IdleOutput {
+                        timeout,
+                        next_slide,
+                    },
+                );
+                self.go_idle(&output, qh);
+            } else {
+                self.idle.outputs.remove(&output);
+                self.resume(&output);
+            }
+        }
+    }
+
+    fn go_idle(&mut self, output: &str, qh: &QueueHandle<Self>) {
+        let Some(layer) = self
+            .wallpapers
+            .iter_mut()
+            .find(|layer| layer.name == output)
+        else {
+            return;
+        };
+        match self.idle.config.action {
+            IdleAction::Pause => layer.set_paused(true),
+            IdleAction::Static => {
+                let frame = match layer.capture(qh, &self.device, &self.queue) {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        tracing::warn!("Failed to keep the frame of {output}, pausing it: {e}");
+                        layer.set_paused(true);
+                        return;
+                    }
+                };
+                let mut pipelines = Pipelines::new();
+                let label = pipelines.gpu_label(IDLE_FRAME);
+                let texture = ImageTexture::with_sampling(
+                    &self.device,
+                    &self.queue,
+                    &DynamicImage::ImageRgba8(frame),
+                    &label,
+                    Sampling::Linear,
+                );
+                let model = TextureModelBuilder::from_texture(texture, &label)
+                    .build(
+                        &self.device,
+                        &self.queue,
+                        self.bindgroup_layout_manager.clone(),
+                        self.pipeline_manager.clone(),
+                    )
+                    .expect("the idle frame is already a texture");
+                pipelines.push(IDLE_FRAME, model);
+
+                layer.profiler = Profiler::new(&self.device, &self.queue, pipelines.len());
+                layer.wallpaper = Arc::new(Mutex::new(pipelines));
+                // The frame was adjusted when it was drawn
+                layer.set_adjustment(DisplayAdjustment::default());
+                layer.set_paused(true);
+                layer.damaged = true;
+                self.sync_mirrors();
+            }
+            // The main loop picks the wallpapers, see `slideshow_due`
+            IdleAction::Slideshow => {}
+        }
+    }
+
+    fn resume(&mut self, output: &str) {
+        let adjustment = self.adjustments.get(output).copied().unwrap_or_default();
+        let Some(layer) = self
+            .wallpapers
+            .iter_mut()
+            .find(|layer| layer.name == output)
+        else {
+            return;
+        };
+        layer.set_paused(false);
+        if self.idle.config.action == IdleAction::Static {
+            layer.set_adjustment(adjustment);
+            if let Some(wallpaper) = self.assignments.get(output).cloned() {
+                self.assign(output, wallpaper);
+            }
+        }
+    }
+}
+
+impl Dispatch<ExtIdleNotifierV1, ()> for Client {
+    fn event(
+        _state: &mut Self,
+        _notifier: &ExtIdleNotifierV1,
+        _event: <ExtIdleNotifierV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, u64> for Client {
+    fn event(
+        state: &mut Self,
+        _notification: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        timeout: &u64,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => state.set_idle(*timeout, true, qh),
+            ext_idle_notification_v1::Event::Resumed => state.set_idle(*timeout, false, qh),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use common::{manifest::Screen, wallpaper::Wallpaper};
+use wgpu::{BindGroupLayout, Device, Queue, RenderPipeline};
+
+use super::{
+    cache::AssetCache, capture::Captures, manager::Manager, pipeline::Pipelines, pipeline_cache,
+};
+use crate::asset::thumbnail;
+
+/// Upper bound on loader threads, decoding is memory hungry for large animations
+const MAX_WORKERS: usize = 4;
+
+/// Work for the loader threads
+enum Job {
+    Load(LoadJob),
+    /// Render the thumbnail of a wallpaper, see [`Loader::thumbnail`]
+    Thumbnail {
+        wallpaper: Wallpaper,
+        done: Box<dyn FnOnce(Result<PathBuf, String>) + Send>,
+    },
+}
+
+/// A request to build the pipelines of a wallpaper for one output
+struct LoadJob {
+    id: u64,
+    /// None to only fill the caches, see [`Loader::preload`]
+    screen: Option<Screen>,
+    /// Size of the largest output in pixels, images are shrunk to it
+    target: Option<(u32, u32)>,
+    wallpaper: Wallpaper,
+    /// Set once a newer job for the same output supersedes this one
+    cancelled: Arc<AtomicBool>,
+}
+
+/// A finished load, ready to be swapped into its output
+pub struct LoadResult {
+    /// Identifier returned by [`Loader::submit`]
+    pub id: u64,
+    /// Name of the output the wallpaper was built for, None for a preload
+    pub output: Option<String>,
+    /// Name of the wallpaper that was loaded
+    pub name: String,
+    pub framerate: i32,
+    pub tickrate: i32,
+    /// Seconds the animations run behind the shared clock, see `clock`
+    pub phase: f32,
+    pub background_alpha: f32,
+    /// The built pipelines, or a description of why loading failed
+    pub pipelines: Result<Pipelines, String>,
+}
+
+/// Thread pool that decodes assets and uploads textures away from the render thread
+///
+/// wgpu devices and queues are safe to use from multiple threads, so workers
+/// build complete `Pipelines` and hand them back to be swapped in between frames.
+pub struct Loader {
+    jobs: Sender<Job>,
+    results: Receiver<LoadResult>,
+    next_id: u64,
+    /// Flag of the latest job for each output, by output name
+    pending: HashMap<String, Arc<AtomicBool>>,
+}
+
+impl Loader {
+    pub fn new(
+        device: Device,
+        queue: Queue,
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+        asset_cache: Arc<Mutex<AssetCache>>,
+        captures: Captures,
+    ) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_WORKERS);
+
+        for index in 0..workers {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            let device = device.clone();
+            let queue = queue.clone();
+            let bindgroup_layout_manager = bindgroup_layout_manager.clone();
+            let pipeline_manager = pipeline_manager.clone();
+            let asset_cache = asset_cache.clone();
+            let captures = captures.clone();
+
+            thread::Builder::new()
+                .name(format!("wlrs-loader-{index}"))
+                .spawn(move || loop {
+                    // Hold the lock only while waiting for a job
+                    let job = job_receiver.lock().unwrap().recv();
+                    let job = match job {
+                        Ok(Job::Load(job)) => job,
+                        Ok(Job::Thumbnail { wallpaper, done }) => {
+                            done(thumbnail::thumbnail(&wallpaper));
+                            continue;
+                        }
+                        Err(_) => break, // Loader dropped
+                    };
+
+                    let name = job.wallpaper.name().to_string();
+                    let framerate = job.wallpaper.framerate();
+                    let tickrate = job.wallpaper.tickrate();
+                    let phase = job.wallpaper.phase();
+                    let background_alpha = job.wallpaper.background_alpha();
+                    let output = job.screen.as_ref().map(|screen| screen.output.clone());
+                    let span_output = output.as_deref().unwrap_or("preload");
+                    let _span =
+                        tracing::info_span!("load", wallpaper = %name, output = %span_output)
+                            .entered();
+                    if job.cancelled.load(Ordering::Relaxed) {
+                        tracing::debug!("Skipping superseded load");
+                        continue;
+                    }
+                    tracing::info!("Loading wallpaper");
+
+                    // Builders report bad assets as errors, this only keeps the
+                    // worker alive should wgpu itself panic
+                    let pipelines = panic::catch_unwind(AssertUnwindSafe(|| {
+                        Pipelines::from(
+                            job.wallpaper,
+                            job.screen,
+                            &device,
+                            &queue,
+                            bindgroup_layout_manager.clone(),
+                            pipeline_manager.clone(),
+                            asset_cache.clone(),
+                            &captures,
+                            job.target,
+                            &job.cancelled,
+                        )
+                    }))
+                    .map_err(|e| {
+                        e.downcast_ref::<String>()
+                            .cloned()
+                            .or_else(|| e.downcast_ref::<&str>().map(|s| s.to_string()))
+                            .unwrap_or_else(|| "unknown error".to_string())
+                    })
+                    .and_then(|pipelines| pipelines);
+
+                    // Keep the pipelines the load compiled for the next start
+                    if pipelines.is_ok() {
+                        pipeline_cache::save();
+                    }
+
+                    // Nobody waits for it anymore, see `Client::apply_finished_loads`
+                    if job.cancelled.load(Ordering::Relaxed) {
+                        tracing::debug!("Dropping superseded load");
+                        continue;
+                    }
+                    let result = LoadResult {
+                        id: job.id,
+                        output,
+                        name,
+                        framerate,
+                        tickrate,
+                        phase,
+                        background_alpha,
+                        pipelines,
+                    };
+                    if result_sender.send(result).is_err() {
+                        break;
+                    }
+                    crate::wake::wake();
+                })
+                .expect("Failed to spawn loader thread");
+        }
+
+        Self {
+            jobs,
+            results,
+            next_id: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Queue a wallpaper to be built for an output, returning the job id
+    ///
+    /// Images are shrunk to `target`, the largest output, so other outputs
+    /// showing the same wallpaper share its textures. A job still queued or
+    /// building for the same output is cancelled, its wallpaper would be
+    /// replaced as soon as it is shown.
+    pub fn submit(&mut self, wallpaper: Wallpaper, screen: Screen, target: (u32, u32)) -> u64 {
+        self.queue(wallpaper, Some(screen), Some(target))
+    }
+
+    /// Queue a wallpaper to be built for no output, returning the job id
+    ///
+    /// The pipelines are dropped once built, what stays are the decoded
+    /// textures in the asset cache and the compiled render pipelines, so
+    /// assigning the wallpaper later skips the slow part. Animations stream
+    /// from disk and are not preloaded.
+    pub fn preload(&mut self, wallpaper: Wallpaper, target: Option<(u32, u32)>) -> u64 {
+        self.queue(wallpaper, None, target)
+    }
+
+    fn queue(
+        &mut self,
+        wallpaper: Wallpaper,
+        screen: Option<Screen>,
+        target: Option<(u32, u32)>,
+    ) -> u64 {
+        self.next_id += 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Some(screen) = &screen {
+            if let Some(previous) = self
+                .pending
+                .insert(screen.output.clone(), cancelled.clone())
+            {
+                previous.store(true, Ordering::Relaxed);
+            }
+        }
+        let job = LoadJob {
+            id: self.next_id,
+            screen,
+            target,
+            wallpaper,
+            cancelled,
+        };
+        self.jobs
+            .send(Job::Load(job))
+            .expect("Loader threads exited unexpectedly");
+        self.next_id
+    }
+
+    /// Render the thumbnail of `wallpaper` unless it is cached, then call
+    /// `done` with its path on the loader thread
+    ///
+    /// Large images take long enough to decode to hold up the outputs, so
+    /// requests needing a thumbnail are answered from here.
+    pub fn thumbnail(
+        &self,
+        wallpaper: Wallpaper,
+        done: impl FnOnce(Result<PathBuf, String>) + Send + 'static,
+    ) {
+        self.jobs
+            .send(Job::Thumbnail {
+                wallpaper,
+                done: Box::new(done),
+            })
+            .expect("Loader threads exited unexpectedly");
+    }
+
+    /// Collect all loads that finished since the last call, without blocking
+    pub fn finished(&self) -> Vec<LoadResult> {
+        self.results.try_iter().collect()
+    }
+}
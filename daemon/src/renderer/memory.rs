@@ -0,0 +1,134 @@
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use common::manifest::Sampling;
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+
+use super::cache::AssetCache;
+use crate::asset::image::rgba_size_bytes;
+
+/// Shortest side a texture is downscaled to before its load is refused
+const MIN_SIDE: u32 = 256;
+
+/// Budget for wallpaper textures in bytes, unlimited if unset
+static BUDGET: OnceLock<Option<u64>> = OnceLock::new();
+
+/// Bytes held by the textures alive right now
+static USED: AtomicU64 = AtomicU64::new(0);
+
+/// Set the GPU memory budget in MiB, before anything is loaded
+pub fn init(budget: Option<u64>) {
+    if BUDGET.set(budget.map(|mib| mib * 1024 * 1024)).is_err() {
+        tracing::warn!("Textures were loaded before the GPU memory budget was set");
+    }
+}
+
+/// The configured budget in bytes
+pub fn budget() -> Option<u64> {
+    BUDGET.get().copied().flatten()
+}
+
+/// Approximate GPU memory held by wallpaper textures in bytes
+pub fn used() -> u64 {
+    USED.load(Ordering::Relaxed)
+}
+
+fn available() -> Option<u64> {
+    budget().map(|budget| budget.saturating_sub(used()))
+}
+
+/// Whether `bytes` more fit in the budget
+pub fn fits(bytes: u64) -> bool {
+    available().is_none_or(|available| bytes <= available)
+}
+
+/// GPU memory held by a texture, released when dropped
+#[derive(Debug)]
+pub struct Allocation {
+    bytes: u64,
+}
+
+impl Allocation {
+    pub fn new(bytes: u64) -> Self {
+        USED.fetch_add(bytes, Ordering::Relaxed);
+        Self { bytes }
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        USED.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// Make a decoded image fit in what is left of the budget before uploading it
+///
+/// Cached assets no layer shows are dropped first, then the image is
+/// downscaled. Fails if it would have to shrink below `MIN_SIDE`.
+pub fn fit_image(
+    image: DynamicImage,
+    sampling: Sampling,
+    cache: &Mutex<AssetCache>,
+    path: &Path,
+) -> Result<DynamicImage, String> {
+    let Some(budget) = budget() else {
+        return Ok(image);
+    };
+    let (width, height) = image.dimensions();
+    let mipmaps = sampling.uses_mipmaps();
+    let needed = rgba_size_bytes(width, height, mipmaps);
+
+    let mut left = available().unwrap_or(budget);
+    if needed > left {
+        let released = cache.lock().unwrap().release_unused(needed - left);
+        if released > 0 {
+            tracing::info!(
+                "Dropped {} MiB of cached textures to load {}",
+                released / (1024 * 1024),
+                path.display()
+            );
+        }
+        left = available().unwrap_or(budget);
+    }
+    if needed <= left {
+        return Ok(image);
+    }
+
+    let Some((fit_width, fit_height)) = fit_size(width, height, mipmaps, left) else {
+        return Err(format!(
+            "Not enough GPU memory for {}: it needs {} MiB, {} MiB of the {} MiB budget are left",
+            path.display(),
+            needed.div_ceil(1024 * 1024),
+            left / (1024 * 1024),
+            budget / (1024 * 1024)
+        ));
+    };
+    tracing::warn!(
+        "Downscaling {} from {width}x{height} to {fit_width}x{fit_height} to stay within the GPU memory budget",
+        path.display()
+    );
+    Ok(image.resize_exact(fit_width, fit_height, FilterType::Triangle))
+}
+
+/// Largest size with the image's aspect ratio whose texture fits in
+/// `available` bytes, if its shorter side stays at least `MIN_SIDE`
+fn fit_size(width: u32, height: u32, mipmaps: bool, available: u64) -> Option<(u32, u32)> {
+    let needed = rgba_size_bytes(width, height, mipmaps);
+    let mut scale = (available as f64 / needed as f64).sqrt();
+    loop {
+        let fit_width = (width as f64 * scale) as u32;
+        let fit_height = (height as f64 * scale) as u32;
+        if fit_width.min(fit_height) < MIN_SIDE.min(width.min(height)) {
+            return None;
+        }
+        if rgba_size_bytes(fit_width, fit_height, mipmaps) <= available {
+            return Some((fit_width, fit_height));
+        }
+        scale *= 0.95;
+    }
+}
@@ -1,7 +1,25 @@
+pub mod cache;
+pub mod capture;
 pub mod client;
+pub mod clock;
 pub mod config;
+pub mod emitter;
+pub mod fallback;
+pub mod idle;
+pub mod loader;
+pub mod overview;
 pub mod pipeline;
-// pub mod stages;
+pub mod pipeline_cache;
+pub mod plan;
+pub mod power;
+pub mod prescale;
+pub mod profiler;
+pub mod render_target;
+pub mod screenshot;
+pub mod stats;
+#[cfg(feature = "web")]
+pub mod web;
 pub mod wallpaper_layer;
 pub mod manager;
+pub mod memory;
 pub mod models;
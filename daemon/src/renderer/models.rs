@@ -4,19 +4,31 @@ use wgpu::{BindGroupLayout, Device, Queue, RenderPipeline};
 
 use super::{manager::Manager, pipeline::Render};
 
+#[cfg(feature = "mpris")]
+pub mod album_art;
 pub mod animated_texture;
+pub mod capture;
 pub mod color;
 pub mod effect;
-// pub mod particle;
+pub mod gradient;
+pub mod lut;
+pub mod particle;
+pub mod svg;
+pub mod text;
 pub mod texture;
+pub mod tile;
+#[cfg(feature = "web")]
+pub mod web;
 
 pub trait ModelBuilder {
     type Target: Render;
+    /// Build the model, failing with a message when its assets cannot be
+    /// loaded
     fn build(
         &self,
         device: &Device,
         queue: &Queue,
         bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
         pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
-    ) -> Self::Target;
+    ) -> Result<Self::Target, String>;
 }
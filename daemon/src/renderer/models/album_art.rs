@@ -0,0 +1,223 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::manifest::{ArtFit, Sampling};
+use image::{imageops, DynamicImage, RgbaImage};
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPipeline};
+
+use crate::{
+    asset::{color::load_image, image::ImageTexture},
+    mpris,
+    renderer::{
+        manager::Manager,
+        models::{texture::TextureModelBuilder, ModelBuilder},
+        pipeline::Render,
+    },
+};
+
+/// Album art changes with every track, mipmaps would be regenerated each time
+const ART_SAMPLING: Sampling = Sampling::Linear;
+
+/// A model that shows the album art of the playing track, see `mpris`
+#[derive(Debug)]
+pub struct AlbumArtModel {
+    label: String,
+    queue: Queue,
+    fit: ArtFit,
+    /// Shown while the track has no art
+    fallback: Option<Arc<RgbaImage>>,
+    /// Art of the current track
+    art: Option<Arc<RgbaImage>>,
+    /// Serial of `art`, see `mpris::art_since`
+    serial: u64,
+    /// Width over height of the output, 0 before the first resize
+    aspect: f32,
+    texture: ImageTexture,
+    /// The render pipeline, shared with texture models
+    render_pipeline: Arc<RenderPipeline>,
+    /// The bind group for the current texture
+    bind_group: Arc<BindGroup>,
+    /// Layout for the bind group
+    bind_group_layout: Arc<BindGroupLayout>,
+}
+
+impl AlbumArtModel {
+    /// Upload the art, or the fallback, fitted to the output
+    fn show(&mut self, device: &Device) {
+        let image = match self.art.as_ref().or(self.fallback.as_ref()) {
+            Some(image) => fit(image, self.fit, self.aspect),
+            None => RgbaImage::new(1, 1),
+        };
+        self.texture = ImageTexture::with_sampling(
+            device,
+            &self.queue,
+            &DynamicImage::ImageRgba8(image),
+            &self.label,
+            ART_SAMPLING,
+        );
+        self.bind_group = Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.texture.sampler),
+                },
+            ],
+            label: Some(&format!("album_art_bind_group_{}", self.label)),
+        }));
+    }
+}
+
+impl Render for AlbumArtModel {
+    fn pipeline(&self) -> Arc<RenderPipeline> {
+        self.render_pipeline.clone()
+    }
+
+    fn bindgroup(&self) -> Arc<BindGroup> {
+        self.bind_group.clone()
+    }
+
+    /// The track is looked at once a second
+    fn update_interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(1))
+    }
+
+    /// Show the new art when the track changed
+    fn pre_render(&mut self, device: &Device, _dt: Duration) {
+        if let Some((serial, art)) = mpris::art_since(self.serial) {
+            self.serial = serial;
+            self.art = art;
+            self.show(device);
+        }
+    }
+
+    /// Fit the art to the output's aspect ratio again
+    fn resize(&mut self, device: &Device, _queue: &Queue, width: u32, height: u32, _scale: f32) {
+        let aspect = width as f32 / height.max(1) as f32;
+        if aspect != self.aspect {
+            self.aspect = aspect;
+            self.show(device);
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Crop or pad an image to an aspect ratio, the texture is stretched over
+/// the whole output
+fn fit(image: &RgbaImage, fit: ArtFit, aspect: f32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    if aspect <= 0.0 || width == 0 || height == 0 {
+        return image.clone();
+    }
+    let wider = width as f32 / height as f32 > aspect;
+    let fitted_width = ((height as f32 * aspect).round() as u32).max(1);
+    let fitted_height = ((width as f32 / aspect).round() as u32).max(1);
+
+    match (fit, wider) {
+        (ArtFit::Cover, true) => {
+            imageops::crop_imm(image, (width - fitted_width) / 2, 0, fitted_width, height)
+                .to_image()
+        }
+        (ArtFit::Cover, false) => {
+            imageops::crop_imm(image, 0, (height - fitted_height) / 2, width, fitted_height)
+                .to_image()
+        }
+        (ArtFit::Contain, true) => {
+            let mut canvas = RgbaImage::new(width, fitted_height);
+            imageops::overlay(&mut canvas, image, 0, ((fitted_height - height) / 2).into());
+            canvas
+        }
+        (ArtFit::Contain, false) => {
+            let mut canvas = RgbaImage::new(fitted_width, height);
+            imageops::overlay(&mut canvas, image, ((fitted_width - width) / 2).into(), 0);
+            canvas
+        }
+    }
+}
+
+/// Builder for album art models
+pub struct AlbumArtModelBuilder {
+    fallback_path: Option<PathBuf>,
+    fit: ArtFit,
+    label: String,
+}
+
+impl AlbumArtModelBuilder {
+    pub fn new(fallback_path: Option<PathBuf>, fit: ArtFit, label: impl Into<String>) -> Self {
+        Self {
+            fallback_path,
+            fit,
+            label: label.into(),
+        }
+    }
+}
+
+impl ModelBuilder for AlbumArtModelBuilder {
+    type Target = AlbumArtModel;
+
+    fn build(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    ) -> Result<Self::Target, String> {
+        // The layer stays transparent without its fallback
+        let fallback = self.fallback_path.as_ref().and_then(|path| {
+            load_image(path)
+                .inspect_err(|e| tracing::error!("Album art layer {}: {e}", self.label))
+                .ok()
+                .map(|image| Arc::new(image.to_rgba8()))
+        });
+
+        let texture = ImageTexture::with_sampling(
+            device,
+            queue,
+            &DynamicImage::new_rgba8(1, 1),
+            &self.label,
+            ART_SAMPLING,
+        );
+
+        // Reuse the texture model's pipeline and bind group layout
+        let model = TextureModelBuilder::from_texture(texture.clone(), &self.label).build(
+            device,
+            queue,
+            bindgroup_layout_manager.clone(),
+            pipeline_manager,
+        )?;
+        let bind_group_layout = bindgroup_layout_manager
+            .lock()
+            .unwrap()
+            .get("texture_bind_group_layout")
+            .expect("texture model registers its bind group layout");
+
+        // Transparent until the first resize fits the fallback to the output
+        Ok(AlbumArtModel {
+            label: self.label.clone(),
+            queue: queue.clone(),
+            fit: self.fit,
+            fallback,
+            art: None,
+            serial: 0,
+            aspect: 0.0,
+            texture,
+            render_pipeline: model.pipeline(),
+            bind_group: model.bindgroup(),
+            bind_group_layout,
+        })
+    }
+}
@@ -55,15 +55,20 @@ impl Render for AnimatedTextureModel {
         self
     }
 
+    /// Every tick until a non-looping animation played to its end
+    fn update_interval(&self) -> Option<Duration> {
+        (self.texture.is_animated() && !self.texture.is_finished()).then_some(Duration::ZERO)
+    }
+
     fn pre_render(&mut self, device: &Device, dt: Duration) {
         // Print the dt value for debugging
-        println!("Animation pre_render dt: {dt:?}");
+        tracing::trace!("Animation pre_render dt: {dt:?}");
 
         // Update the animated texture and track if the frame changed
         let frame_changed = self.texture.update(dt);
 
         // Debug print frame change status
-        println!("Frame changed: {frame_changed}");
+        tracing::trace!("Frame changed: {frame_changed}");
 
         // Flag for bind group update if frame changed
         if !frame_changed {
@@ -76,7 +81,7 @@ impl Render for AnimatedTextureModel {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(self.texture.view()),
+                    resource: wgpu::BindingResource::TextureView(&self.texture.view()),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
@@ -88,9 +93,17 @@ impl Render for AnimatedTextureModel {
     }
 }
 
+/// Where the builder gets its animation from
+enum AnimationSource {
+    /// File that still needs to be decoded
+    Path(Box<Path>),
+    /// A stream already decoding, e.g. from the asset cache
+    Texture(AnimatedTexture),
+}
+
 /// Builder for animated texture models
 pub struct AnimatedTextureModelBuilder {
-    path: Box<Path>,
+    source: AnimationSource,
     label: String,
     looping: bool,
 }
@@ -98,7 +111,16 @@ pub struct AnimatedTextureModelBuilder {
 impl AnimatedTextureModelBuilder {
     pub fn new(path: impl AsRef<Path>, label: impl Into<String>) -> Self {
         Self {
-            path: path.as_ref().into(),
+            source: AnimationSource::Path(path.as_ref().into()),
+            label: label.into(),
+            looping: true,
+        }
+    }
+
+    /// Build from a stream shared with other users, playing in step with them
+    pub fn from_texture(texture: AnimatedTexture, label: impl Into<String>) -> Self {
+        Self {
+            source: AnimationSource::Texture(texture),
             label: label.into(),
             looping: true,
         }
@@ -120,11 +142,18 @@ impl ModelBuilder for AnimatedTextureModelBuilder {
         queue: &Queue,
         bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
         pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
-    ) -> Self::Target {
+    ) -> Result<Self::Target, String> {
         // Load the animated texture
-        let texture =
-            AnimatedTexture::from_path(device, queue, &self.path, &self.label, self.looping)
-                .expect("Failed to load animated texture");
+        let texture = match &self.source {
+            AnimationSource::Path(path) => {
+                AnimatedTexture::from_path(device, queue, path, &self.label, self.looping)
+                    .map_err(|e| format!("Failed to load {}: {e}", path.display()))?
+            }
+            AnimationSource::Texture(texture) => {
+                texture.set_looping(self.looping);
+                texture.clone()
+            }
+        };
 
         // Get or create the bind group layout
         let bind_group_layout = bindgroup_layout_manager.lock().unwrap().get_or_init(
@@ -209,7 +238,7 @@ impl ModelBuilder for AnimatedTextureModelBuilder {
                                 alpha_to_coverage_enabled: false,
                             },
                             multiview: None,
-                            cache: None,
+                            cache: crate::renderer::pipeline_cache::current().as_deref(),
                         }),
                     )
                 });
@@ -220,7 +249,7 @@ impl ModelBuilder for AnimatedTextureModelBuilder {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(texture.view()),
+                    resource: wgpu::BindingResource::TextureView(&texture.view()),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
@@ -230,11 +259,11 @@ impl ModelBuilder for AnimatedTextureModelBuilder {
             label: Some(&format!("animated_texture_bind_group_{}", self.label)),
         });
 
-        AnimatedTextureModel::new(
+        Ok(AnimatedTextureModel::new(
             texture,
             pipeline.clone(),
             Arc::new(bind_group),
             bind_group_layout,
-        )
+        ))
     }
 }
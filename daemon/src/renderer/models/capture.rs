@@ -0,0 +1,244 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::manifest::{CaptureContent, Sampling};
+use image::{DynamicImage, RgbaImage};
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPipeline};
+
+use crate::{
+    asset::{
+        dmabuf::{self, DmabufImage},
+        image::ImageTexture,
+    },
+    renderer::{
+        capture::{CaptureFeed, Captures, Frame},
+        manager::Manager,
+        models::{
+            texture::{self, TextureModelBuilder},
+            ModelBuilder,
+        },
+        pipeline::Render,
+    },
+};
+
+/// Frames match the captured output's resolution, mipmaps would be
+/// regenerated for every frame
+const CAPTURE_SAMPLING: Sampling = Sampling::Linear;
+
+/// A model that shows what another output shows, see `CaptureFeed`
+#[derive(Debug)]
+pub struct CaptureModel {
+    feed: Arc<CaptureFeed>,
+    label: String,
+    queue: Queue,
+    /// Serial of the frame in `texture`, 0 before the first one arrived
+    serial: u64,
+    /// The latest frame copied through shared memory
+    texture: ImageTexture,
+    /// The GPU image shown instead of `texture`, if frames come as dmabufs
+    image: Option<Arc<DmabufImage>>,
+    /// The render pipeline, shared with texture models
+    render_pipeline: Arc<RenderPipeline>,
+    /// The texture model's pipeline
+    blended_pipeline: Arc<RenderPipeline>,
+    /// Pipeline ignoring alpha, for images in formats without it
+    opaque_pipeline: Arc<RenderPipeline>,
+    /// The bind group for the current texture
+    bind_group: Arc<BindGroup>,
+    /// Layout for the bind group
+    bind_group_layout: Arc<BindGroupLayout>,
+}
+
+impl CaptureModel {
+    /// Upload a frame, replacing the texture if the output changed size
+    fn upload(&mut self, device: &Device, frame: &RgbaImage) {
+        let size = self.texture.texture.size();
+        if (size.width, size.height) == frame.dimensions() {
+            self.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &self.texture.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                frame,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * frame.width()),
+                    rows_per_image: Some(frame.height()),
+                },
+                size,
+            );
+            if self.image.take().is_some() {
+                self.bind(device);
+            }
+            return;
+        }
+
+        tracing::debug!(
+            "Capture of {} is now {}x{}",
+            self.feed.output(),
+            frame.width(),
+            frame.height()
+        );
+        self.texture = ImageTexture::with_sampling(
+            device,
+            &self.queue,
+            &DynamicImage::ImageRgba8(frame.clone()),
+            &self.label,
+            CAPTURE_SAMPLING,
+        );
+        self.image = None;
+        self.bind(device);
+    }
+
+    /// Show a GPU image, the compositor already copied the frame into it
+    fn show_image(&mut self, device: &Device, image: Arc<DmabufImage>) {
+        if self
+            .image
+            .as_ref()
+            .is_some_and(|shown| Arc::ptr_eq(shown, &image))
+        {
+            return;
+        }
+        self.image = Some(image);
+        self.bind(device);
+    }
+
+    /// Bind the shown image, or the texture if there is none
+    fn bind(&mut self, device: &Device) {
+        let (view, pipeline) = match &self.image {
+            Some(image) if dmabuf::is_opaque(image.format) => (&image.view, &self.opaque_pipeline),
+            Some(image) => (&image.view, &self.blended_pipeline),
+            None => (&self.texture.view, &self.blended_pipeline),
+        };
+        self.render_pipeline = pipeline.clone();
+        self.bind_group = Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.texture.sampler),
+                },
+            ],
+            label: Some(&format!("capture_bind_group_{}", self.label)),
+        }));
+    }
+}
+
+impl Render for CaptureModel {
+    fn pipeline(&self) -> Arc<RenderPipeline> {
+        self.render_pipeline.clone()
+    }
+
+    fn bindgroup(&self) -> Arc<BindGroup> {
+        self.bind_group.clone()
+    }
+
+    fn update_interval(&self) -> Option<Duration> {
+        Some(Duration::ZERO)
+    }
+
+    /// Show the latest frame if one arrived since the last update
+    fn pre_render(&mut self, device: &Device, _dt: Duration) {
+        if let Some((serial, frame)) = self.feed.frame_since(self.serial) {
+            match frame {
+                Frame::Pixels(pixels) => self.upload(device, &pixels),
+                Frame::Image(image) => self.show_image(device, image),
+            }
+            self.serial = serial;
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Builder for capture models
+pub struct CaptureModelBuilder {
+    content: CaptureContent,
+    captures: Captures,
+    label: String,
+}
+
+impl CaptureModelBuilder {
+    pub fn new(content: CaptureContent, captures: Captures, label: impl Into<String>) -> Self {
+        Self {
+            content,
+            captures,
+            label: label.into(),
+        }
+    }
+}
+
+impl ModelBuilder for CaptureModelBuilder {
+    type Target = CaptureModel;
+
+    fn build(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    ) -> Result<Self::Target, String> {
+        let feed = self.captures.subscribe(&self.content);
+
+        // Transparent until the first frame arrives
+        let texture = ImageTexture::with_sampling(
+            device,
+            queue,
+            &DynamicImage::new_rgba8(1, 1),
+            &self.label,
+            CAPTURE_SAMPLING,
+        );
+
+        // Reuse the texture model's pipeline and bind group layout
+        let model = TextureModelBuilder::from_texture(texture.clone(), &self.label).build(
+            device,
+            queue,
+            bindgroup_layout_manager.clone(),
+            pipeline_manager.clone(),
+        )?;
+        let bind_group_layout = bindgroup_layout_manager
+            .lock()
+            .unwrap()
+            .get("texture_bind_group_layout")
+            .expect("texture model registers its bind group layout");
+        let opaque_pipeline =
+            pipeline_manager
+                .lock()
+                .unwrap()
+                .get_or_init("texture_opaque_render_pipeline", || {
+                    Arc::new(texture::create_pipeline(
+                        device,
+                        &bind_group_layout,
+                        "fs_opaque",
+                    ))
+                });
+
+        Ok(CaptureModel {
+            feed,
+            label: self.label.clone(),
+            queue: queue.clone(),
+            serial: 0,
+            texture,
+            image: None,
+            render_pipeline: model.pipeline(),
+            blended_pipeline: model.pipeline(),
+            opaque_pipeline,
+            bind_group: model.bindgroup(),
+            bind_group_layout,
+        })
+    }
+}
@@ -1,25 +1,23 @@
 use std::sync::{Arc, Mutex};
 
+use common::color::Color;
 use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, Device, Queue, RenderPipeline};
 
-use crate::renderer::{manager::Manager, models::ModelBuilder, pipeline::Render};
+use crate::{
+    asset::color::srgb_to_linear,
+    renderer::{manager::Manager, models::ModelBuilder, pipeline::Render},
+};
 
 /// Represents a solid color to render
 #[derive(Debug)]
 pub struct ColorModel {
-    color_buffer: wgpu::Buffer,
     render_pipeline: Arc<RenderPipeline>,
     bind_group: Arc<BindGroup>,
 }
 
 impl ColorModel {
-    pub fn new(
-        color_buffer: wgpu::Buffer,
-        render_pipeline: Arc<RenderPipeline>,
-        bind_group: Arc<BindGroup>,
-    ) -> Self {
+    pub fn new(render_pipeline: Arc<RenderPipeline>, bind_group: Arc<BindGroup>) -> Self {
         Self {
-            color_buffer,
             render_pipeline,
             bind_group,
         }
@@ -59,10 +57,16 @@ impl ColorModelBuilder {
         }
     }
 
-    /// Parse a hex color string (#RRGGBB) and create a builder
-    pub fn from_hex_color(hex_color: &str, label: impl Into<String>) -> Self {
-        let rgba = parse_hex_color(hex_color);
-        Self::new(rgba, label)
+    /// Parse a CSS color string and create a builder
+    ///
+    /// Manifest colors are sRGB-encoded; the render target is an sRGB surface
+    /// that expects linear shader output, so the channels are linearized here.
+    pub fn from_css_color(color: &str, label: impl Into<String>) -> Result<Self, String> {
+        let [r, g, b, a] = parse_color(color)?;
+        Ok(Self::new(
+            [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a],
+            label,
+        ))
     }
 }
 
@@ -72,10 +76,10 @@ impl ModelBuilder for ColorModelBuilder {
     fn build(
         &self,
         device: &Device,
-        queue: &Queue,
+        _queue: &Queue,
         bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
         pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
-    ) -> Self::Target {
+    ) -> Result<Self::Target, String> {
         // Create a buffer for the color uniform
         let color_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("Color Buffer: {}", self.label)),
@@ -156,7 +160,7 @@ impl ModelBuilder for ColorModelBuilder {
                                 alpha_to_coverage_enabled: false,
                             },
                             multiview: None,
-                            cache: None,
+                            cache: crate::renderer::pipeline_cache::current().as_deref(),
                         }),
                     )
                 });
@@ -171,7 +175,8 @@ impl ModelBuilder for ColorModelBuilder {
             label: Some(&format!("color_bind_group_{}", self.label)),
         });
 
-        ColorModel::new(color_buffer, pipeline.clone(), Arc::new(bind_group))
+        // The bind group keeps the buffer alive
+        Ok(ColorModel::new(pipeline.clone(), Arc::new(bind_group)))
     }
 }
 
@@ -182,29 +187,12 @@ struct ColorUniform {
     color: [f32; 4],
 }
 
-/// Parse a hex color string to RGBA [f32; 4] values
-/// Supports #RRGGBB format
-fn parse_hex_color(hex: &str) -> [f32; 4] {
-    // Default to opaque black
-    let mut rgba = [0.0, 0.0, 0.0, 1.0];
-
-    // Check if the string starts with '#' and has the right length
-    if hex.starts_with('#') && hex.len() == 7 {
-        let hex = &hex[1..]; // Remove the # prefix
-
-        // Try to parse the hex values
-        if let (Some(r), Some(g), Some(b)) = (
-            u8::from_str_radix(&hex[0..2], 16).ok(),
-            u8::from_str_radix(&hex[2..4], 16).ok(),
-            u8::from_str_radix(&hex[4..6], 16).ok(),
-        ) {
-            // Convert to normalized float values (0.0 - 1.0)
-            rgba[0] = r as f32 / 255.0;
-            rgba[1] = g as f32 / 255.0;
-            rgba[2] = b as f32 / 255.0;
-        }
-    }
-
-    rgba
+/// Parse a CSS color string to sRGB-encoded RGBA [f32; 4] values
+///
+/// Manifests are validated when loaded, an invalid color here still fails
+/// the build of its layer instead of the loader.
+pub(crate) fn parse_color(color: &str) -> Result<[f32; 4], String> {
+    Color::parse(color)
+        .map(|color| color.to_array())
+        .map_err(|e| e.to_string())
 }
-
@@ -4,14 +4,69 @@ use std::{
     time::Duration,
 };
 
+use common::manifest::{EffectInput, NoiseTexture, ParamAnimation, Sampling};
 use image::{DynamicImage, GenericImage, GenericImageView};
-use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPipeline};
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, CommandEncoder, Device, Queue, RenderPass,
+    RenderPipeline, TextureView,
+};
 
 use crate::{
-    asset::image::ImageTexture,
+    asset::{
+        color::{linear_to_srgb, srgb_to_linear},
+        image::ImageTexture,
+        noise::Noise,
+    },
+    colors, metrics,
     renderer::{manager::Manager, models::ModelBuilder, pipeline::Render},
 };
 
+/// Format every pass renders in, the one all models draw in
+const PASS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+
+/// One step of an effect, see `EffectModel::encode_passes`
+///
+/// Every pass but the last renders into a texture of the output's size that
+/// the next pass reads at binding 0. The last pass draws onto the layers
+/// below like a single pass effect does.
+#[derive(Debug, Clone, Copy)]
+pub struct EffectPass {
+    /// Fragment shader entry point
+    pub entry_point: &'static str,
+    /// Whether the pass reads what it rendered on the previous frame at
+    /// binding 3, e.g. for trails. Ignored for the last pass.
+    pub feedback: bool,
+}
+
+impl EffectPass {
+    pub const fn new(entry_point: &'static str) -> Self {
+        Self {
+            entry_point,
+            feedback: false,
+        }
+    }
+}
+
+/// Passes of effects drawn in one go
+pub const SINGLE_PASS: &[EffectPass] = &[EffectPass::new("fs_main")];
+
+/// Textures a pass before the last renders into
+#[derive(Debug)]
+struct PassTarget {
+    /// Written on even and odd frames, a pass with feedback reads the other one
+    views: Vec<TextureView>,
+}
+
+impl PassTarget {
+    fn output(&self, parity: usize) -> &TextureView {
+        &self.views[parity % self.views.len()]
+    }
+
+    fn history(&self, parity: usize) -> Option<&TextureView> {
+        (self.views.len() > 1).then(|| &self.views[(parity + 1) % 2])
+    }
+}
+
 /// Base effect model that can render image-based effects
 #[derive(Debug)]
 pub struct EffectModel {
@@ -27,6 +82,30 @@ pub struct EffectModel {
     animated: bool,
     /// Parameters buffer (for updating time)
     params_buffer: Option<wgpu::Buffer>,
+    /// Whether the effect samples its texture or the layers below it
+    input: EffectInput,
+    /// Layout of `bind_group`, kept to rebind the effect to a new backdrop
+    bind_group_layout: Option<Arc<BindGroupLayout>>,
+    /// Steps the effect renders in, the last one draws the layer
+    passes: &'static [EffectPass],
+    /// Pipelines of `passes`, in the same order
+    pass_pipelines: Vec<Arc<RenderPipeline>>,
+    /// What the first pass reads, the texture or the backdrop
+    source: Option<TextureView>,
+    /// Targets of the passes before the last, created at the output size
+    targets: Vec<PassTarget>,
+    /// Bind groups of every pass for even and odd frames
+    pass_bind_groups: Vec<[Arc<BindGroup>; 2]>,
+    /// Alternates every frame to swap the feedback textures
+    parity: usize,
+    /// Noise texture at bindings 4 and 5, kept to rebind the effect
+    noise: Option<Noise>,
+    /// System metrics at binding 6, see `metrics::Metrics`
+    metrics_buffer: Option<wgpu::Buffer>,
+    /// Colors of the color scheme at binding 7, see `ColorScheme::to_uniform`
+    palette_buffer: Option<wgpu::Buffer>,
+    /// Parameters the manifest animates, written with the time
+    animations: Vec<AnimatedParam>,
 }
 
 impl EffectModel {
@@ -42,6 +121,18 @@ impl EffectModel {
             current_time: 0.0,
             animated: false,
             params_buffer: None,
+            input: EffectInput::Image,
+            bind_group_layout: None,
+            passes: SINGLE_PASS,
+            pass_pipelines: Vec::new(),
+            source: None,
+            targets: Vec::new(),
+            pass_bind_groups: Vec::new(),
+            parity: 0,
+            noise: None,
+            metrics_buffer: None,
+            palette_buffer: None,
+            animations: Vec::new(),
         }
     }
 
@@ -59,11 +150,153 @@ impl EffectModel {
             current_time: 0.0,
             animated: true,
             params_buffer: Some(params_buffer),
+            input: EffectInput::Image,
+            bind_group_layout: None,
+            passes: SINGLE_PASS,
+            pass_pipelines: Vec::new(),
+            source: None,
+            targets: Vec::new(),
+            pass_bind_groups: Vec::new(),
+            parity: 0,
+            noise: None,
+            metrics_buffer: None,
+            palette_buffer: None,
+            animations: Vec::new(),
         }
     }
 
-    /// Update effect time for animations
-    pub fn update_time(&mut self, dt: Duration, queue: &Queue) {
+    /// Read `input` instead of the texture, rebinding with `layout` when it is the backdrop
+    pub fn with_input(mut self, input: EffectInput, layout: Arc<BindGroupLayout>) -> Self {
+        self.input = input;
+        self.bind_group_layout = Some(layout);
+        self
+    }
+
+    /// Keep the noise texture bound at bindings 4 and 5 when rebinding
+    pub fn with_noise(mut self, noise: Noise) -> Self {
+        self.noise = Some(noise);
+        self
+    }
+
+    /// Keep the metrics buffer bound at binding 6 when rebinding
+    pub fn with_metrics(mut self, metrics_buffer: wgpu::Buffer) -> Self {
+        self.metrics_buffer = Some(metrics_buffer);
+        self
+    }
+
+    /// Keep the palette buffer bound at binding 7 when rebinding
+    pub fn with_palette(mut self, palette_buffer: wgpu::Buffer) -> Self {
+        self.palette_buffer = Some(palette_buffer);
+        self
+    }
+
+    /// Move `animations` along with the time, see `EffectModel::set_time`
+    pub fn with_animations(mut self, animations: Vec<AnimatedParam>) -> Self {
+        self.animations = animations;
+        self
+    }
+
+    /// Render in `passes` with their `pipelines`, see `EffectPass`
+    pub fn with_passes(
+        mut self,
+        passes: &'static [EffectPass],
+        pipelines: Vec<Arc<RenderPipeline>>,
+    ) -> Self {
+        self.source = Some(self.texture.view.clone());
+        self.passes = passes;
+        self.pass_pipelines = pipelines;
+        self
+    }
+
+    fn multi_pass(&self) -> bool {
+        self.passes.len() > 1
+    }
+
+    /// Rebuild the bind groups of all passes after their inputs changed
+    fn bind_passes(&mut self, device: &Device) {
+        let (
+            Some(layout),
+            Some(params_buffer),
+            Some(source),
+            Some(noise),
+            Some(metrics_buffer),
+            Some(palette_buffer),
+        ) = (
+            &self.bind_group_layout,
+            &self.params_buffer,
+            &self.source,
+            &self.noise,
+            &self.metrics_buffer,
+            &self.palette_buffer,
+        )
+        else {
+            return;
+        };
+        if self.targets.len() + 1 != self.passes.len() {
+            return;
+        }
+
+        self.pass_bind_groups = (0..self.passes.len())
+            .map(|index| {
+                [0, 1].map(|parity| {
+                    let input = match index {
+                        0 => source,
+                        _ => self.targets[index - 1].output(parity),
+                    };
+                    // Passes without feedback get their input again, the
+                    // texture they render into cannot be bound
+                    let history = self
+                        .targets
+                        .get(index)
+                        .and_then(|target| target.history(parity))
+                        .unwrap_or(input);
+                    Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(input),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(&self.texture.sampler),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 2,
+                                resource: params_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 3,
+                                resource: wgpu::BindingResource::TextureView(history),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 4,
+                                resource: wgpu::BindingResource::TextureView(&noise.view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 5,
+                                resource: wgpu::BindingResource::Sampler(&noise.sampler),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 6,
+                                resource: metrics_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 7,
+                                resource: palette_buffer.as_entire_binding(),
+                            },
+                        ],
+                        label: Some("effect_pass_bind_group"),
+                    }))
+                })
+            })
+            .collect();
+        self.bind_group = self.pass_bind_groups[self.passes.len() - 1][self.parity].clone();
+    }
+
+    /// Set the effect's time from the shared animation clock, see `clock`,
+    /// so the effect shows the same moment on every output
+    pub fn set_time(&mut self, time: Duration, queue: &Queue) {
         if !self.animated || self.params_buffer.is_none() {
             // No debug output to reduce noise
             return;
@@ -72,20 +305,39 @@ impl EffectModel {
         // Update time with a larger multiplier to make animations move faster for the demo
         // This makes the animations more noticeable for testing
         let time_scale = 5.0; // 5x faster animations to make effects more obvious
-        self.current_time += dt.as_secs_f32() * time_scale;
 
-        // Avoid precision issues by keeping time in reasonable range
-        if self.current_time > 1000.0 {
-            self.current_time -= 1000.0;
-        }
+        // Avoid precision issues by keeping time in reasonable range, computed
+        // in double precision so outputs agree on where it wraps
+        self.current_time = (time.as_secs_f64() * time_scale).rem_euclid(1000.0) as f32;
 
         // Print debug time update more frequently for debugging
         if self.current_time < 0.2 || (self.current_time % 2.0 < 0.1) {
-            println!(
-                "Updating effect shader time: {:.2} (dt: {:?}, scaled: {:?})",
+            tracing::trace!(
+                "Updating effect shader time: {:.2} (clock: {:?})",
                 self.current_time,
-                dt,
-                dt.as_secs_f32() * time_scale
+                time
+            );
+        }
+
+        if let Some(metrics_buffer) = &self.metrics_buffer {
+            queue.write_buffer(metrics_buffer, 0, bytemuck::bytes_of(&metrics::current()));
+        }
+        if let Some(palette_buffer) = &self.palette_buffer {
+            queue.write_buffer(
+                palette_buffer,
+                0,
+                bytemuck::bytes_of(&colors::current().to_uniform()),
+            );
+        }
+
+        // Animations go by the clock's seconds, unscaled, so their periods
+        // are the ones in the manifest
+        let seconds = time.as_secs_f64();
+        for param in &self.animations {
+            queue.write_buffer(
+                self.params_buffer.as_ref().unwrap(),
+                param.offset,
+                bytemuck::cast_slice(&[param.animation.value_at(seconds) * param.scale]),
             );
         }
 
@@ -101,11 +353,19 @@ impl EffectModel {
 
         // Force more frequent updates to prevent animation stalling
         // This is a debug measure to ensure time updates are happening
-        println!("Time updated for shader: {:.2}", self.current_time);
+        tracing::trace!("Time updated for shader: {:.2}", self.current_time);
     }
 }
 
 impl EffectModel {
+    /// Change how strongly the effect shows, the layer opacity it was built with
+    pub fn set_strength(&self, queue: &Queue, strength: f32) {
+        if let Some(params_buffer) = &self.params_buffer {
+            // Third parameter, see `EffectModelBuilder::build`
+            queue.write_buffer(params_buffer, 8, bytemuck::cast_slice(&[strength]));
+        }
+    }
+
     /// Check if this effect is animated
     pub fn is_animated(&self) -> bool {
         self.animated
@@ -121,11 +381,135 @@ impl Render for EffectModel {
         self.bind_group.clone()
     }
 
+    fn update_interval(&self) -> Option<Duration> {
+        self.animated.then_some(Duration::ZERO)
+    }
+
     fn pre_render(&mut self, _device: &Device, _dt: Duration) {
         // Time updates are handled by the layer's draw method with direct queue access
         // No special handling needed here
     }
 
+    fn resize(&mut self, device: &Device, _queue: &Queue, width: u32, height: u32, _scale: f32) {
+        if !self.multi_pass() {
+            return;
+        }
+        let passes = &self.passes[..self.passes.len() - 1];
+        self.targets = passes
+            .iter()
+            .map(|pass| PassTarget {
+                views: (0..if pass.feedback { 2 } else { 1 })
+                    .map(|_| create_pass_texture(device, width, height))
+                    .collect(),
+            })
+            .collect();
+        self.bind_passes(device);
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        // Multi-pass effects have nothing to draw until they know the output size
+        if self.multi_pass() && self.pass_bind_groups.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, Some(&*self.bind_group), &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+
+    fn has_passes(&self) -> bool {
+        self.multi_pass()
+    }
+
+    /// Render the passes before the last, swapping the feedback textures first
+    fn encode_passes(&mut self, encoder: &mut CommandEncoder) {
+        if self.pass_bind_groups.is_empty() {
+            return;
+        }
+        self.parity = (self.parity + 1) % 2;
+        for (index, target) in self.targets.iter().enumerate() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Effect Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target.output(self.parity),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.pass_pipelines[index]);
+            render_pass.set_bind_group(0, Some(&*self.pass_bind_groups[index][self.parity]), &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+        self.bind_group = self.pass_bind_groups[self.targets.len()][self.parity].clone();
+    }
+
+    fn reads_backdrop(&self) -> bool {
+        self.input == EffectInput::Below
+    }
+
+    fn bind_backdrop(&mut self, device: &Device, backdrop: &TextureView) {
+        if self.multi_pass() {
+            self.source = Some(backdrop.clone());
+            self.bind_passes(device);
+            return;
+        }
+        let (
+            Some(layout),
+            Some(params_buffer),
+            Some(noise),
+            Some(metrics_buffer),
+            Some(palette_buffer),
+        ) = (
+            &self.bind_group_layout,
+            &self.params_buffer,
+            &self.noise,
+            &self.metrics_buffer,
+            &self.palette_buffer,
+        )
+        else {
+            return;
+        };
+        self.bind_group = Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(backdrop),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&noise.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&noise.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: metrics_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: palette_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("effect_backdrop_bind_group"),
+        }));
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -144,6 +528,12 @@ pub struct EffectModelBuilder {
     /// The label for this effect
     label: String,
     /// Whether to pre-multiply the RGB values by the alpha value
+    ///
+    /// Off by default, it used to be on. The effect pipeline blends with
+    /// `ALPHA_BLENDING`, which multiplies the color by alpha itself, so
+    /// premultiplied texels were multiplied twice and soft mask edges came
+    /// out darker than the image. Only shaders that expect premultiplied
+    /// input should turn it on.
     premultiply_alpha: bool,
     /// Effect parameters from manifest
     params: HashMap<String, toml::Value>,
@@ -151,6 +541,14 @@ pub struct EffectModelBuilder {
     opacity: f32,
     /// The shader to use
     shader: wgpu::ShaderModuleDescriptor<'static>,
+    /// Texture filtering for the effect image
+    sampling: Sampling,
+    /// Whether the effect reads the image or the layers below it
+    input: EffectInput,
+    /// Steps the effect renders in
+    passes: &'static [EffectPass],
+    /// Noise texture generated for the shader
+    noise: Option<NoiseTexture>,
 }
 
 impl EffectModelBuilder {
@@ -164,13 +562,41 @@ impl EffectModelBuilder {
             image,
             mask: None,
             label: label.into(),
-            premultiply_alpha: true,
+            premultiply_alpha: false,
             params: HashMap::new(),
             opacity: 1.0, // Default opacity is 1.0 (fully opaque)
             shader,
+            sampling: Sampling::default(),
+            input: EffectInput::default(),
+            passes: SINGLE_PASS,
+            noise: None,
         }
     }
 
+    /// Set the noise texture generated for the shader, see `NoiseTexture`
+    pub fn with_noise(mut self, noise: Option<NoiseTexture>) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    /// Set the passes the effect renders in, see `EffectPass`
+    pub fn with_passes(mut self, passes: &'static [EffectPass]) -> Self {
+        self.passes = passes;
+        self
+    }
+
+    /// Set what the effect reads
+    pub fn with_input(mut self, input: EffectInput) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Set the texture filtering mode
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
     /// Set the layer opacity
     pub fn with_opacity(mut self, opacity: f32) -> Self {
         self.opacity = opacity;
@@ -193,37 +619,29 @@ impl EffectModelBuilder {
                 } else if let Some(int_val) = value.as_integer() {
                     int_val as f32
                 } else {
-                    println!(
-                        "Warning: Parameter '{param_name}' has invalid type, using default: {default_value}"
+                    tracing::warn!(
+                        "Parameter '{param_name}' has invalid type, using default: {default_value}"
                     );
                     default_value
                 }
             }
             None => {
-                println!("Parameter '{param_name}' not found, using default: {default_value}");
+                tracing::debug!(
+                    "Parameter '{param_name}' not found, using default: {default_value}"
+                );
                 default_value
             }
         }
     }
 
-    /// Parse an integer parameter from the params map with a default value
-    fn parse_i32_param(&self, param_name: &str, default_value: i32) -> i32 {
-        match self.params.get(param_name) {
-            Some(value) => {
-                if let Some(int_val) = value.as_integer() {
-                    int_val as i32
-                } else if let Some(float_val) = value.as_float() {
-                    float_val as i32
-                } else {
-                    println!(
-                        "Warning: Parameter '{param_name}' has invalid type, using default: {default_value}"
-                    );
-                    default_value
-                }
-            }
-            None => {
-                println!("Parameter '{param_name}' not found, using default: {default_value}");
-                default_value
+    /// Parse a parameter set to a `ParamAnimation`, None if it is not one
+    fn parse_animation(&self, param_name: &str) -> Option<ParamAnimation> {
+        let value = self.params.get(param_name)?;
+        match ParamAnimation::from_value(&self.label, param_name, value) {
+            Ok(animation) => animation,
+            Err(e) => {
+                tracing::warn!("{e}, the parameter is not animated");
+                None
             }
         }
     }
@@ -246,7 +664,6 @@ impl EffectModelBuilder {
         let (width, height) = processed.dimensions();
 
         // If a separate mask is provided, apply it
-        let mask_present = self.mask.is_some();
         let mask_resized = self
             .mask
             .as_ref()
@@ -277,12 +694,14 @@ impl EffectModelBuilder {
                 // Apply final alpha
                 pixel[3] = final_alpha;
 
-                // Pre-multiply RGB by alpha if requested
+                // Pre-multiply RGB by alpha if requested, in linear space since
+                // the texture is decoded from sRGB when sampled
                 if self.premultiply_alpha {
                     let alpha_factor = final_alpha as f32 / 255.0;
-                    pixel[0] = (pixel[0] as f32 * alpha_factor) as u8;
-                    pixel[1] = (pixel[1] as f32 * alpha_factor) as u8;
-                    pixel[2] = (pixel[2] as f32 * alpha_factor) as u8;
+                    for channel in &mut pixel.0[..3] {
+                        let linear = srgb_to_linear(*channel as f32 / 255.0) * alpha_factor;
+                        *channel = (linear_to_srgb(linear) * 255.0).round() as u8;
+                    }
                 }
 
                 processed.put_pixel(x, y, pixel);
@@ -302,57 +721,46 @@ impl ModelBuilder for EffectModelBuilder {
         queue: &Queue,
         bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
         pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
-    ) -> Self::Target {
+    ) -> Result<Self::Target, String> {
         // Process the image using any mask provided
         let processed_image = self.process_image();
 
         // Create texture from the processed image
-        let texture = ImageTexture::from_image(device, queue, &processed_image, &self.label);
+        let texture = ImageTexture::with_sampling(
+            device,
+            queue,
+            &processed_image,
+            &self.label,
+            self.sampling,
+        );
+
+        // Effects without noise get a placeholder, the layout always has it
+        let noise = match &self.noise {
+            Some(noise) => Noise::generate(device, queue, noise, &self.label),
+            None => Noise::placeholder(device, queue),
+        };
+
+        // Multi-pass effects also bind the feedback texture
+        let multi_pass = self.passes.len() > 1;
+        let layout_label = if multi_pass {
+            "effect_pass_bind_group_layout"
+        } else {
+            "effect_bind_group_layout"
+        };
 
         // Get or create the bind group layout
-        let bind_group_layout = bindgroup_layout_manager.lock().unwrap().get_or_init(
-            "effect_bind_group_layout",
-            || {
-                Arc::new(
-                    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                        entries: &[
-                            // Texture binding
-                            wgpu::BindGroupLayoutEntry {
-                                binding: 0,
-                                visibility: wgpu::ShaderStages::FRAGMENT,
-                                ty: wgpu::BindingType::Texture {
-                                    multisampled: false,
-                                    view_dimension: wgpu::TextureViewDimension::D2,
-                                    sample_type: wgpu::TextureSampleType::Float {
-                                        filterable: true,
-                                    },
-                                },
-                                count: None,
-                            },
-                            // Sampler binding
-                            wgpu::BindGroupLayoutEntry {
-                                binding: 1,
-                                visibility: wgpu::ShaderStages::FRAGMENT,
-                                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                                count: None,
-                            },
-                            // Time uniform binding (needed for effect shaders)
-                            wgpu::BindGroupLayoutEntry {
-                                binding: 2,
-                                visibility: wgpu::ShaderStages::FRAGMENT,
-                                ty: wgpu::BindingType::Buffer {
-                                    ty: wgpu::BufferBindingType::Uniform,
-                                    has_dynamic_offset: false,
-                                    min_binding_size: None,
-                                },
-                                count: None,
-                            },
-                        ],
-                        label: Some("effect_bind_group_layout"),
-                    }),
-                )
-            },
-        );
+        let bind_group_layout =
+            bindgroup_layout_manager
+                .lock()
+                .unwrap()
+                .get_or_init(layout_label, || {
+                    Arc::new(
+                        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                            entries: &bind_group_layout_entries(multi_pass),
+                            label: Some(layout_label),
+                        }),
+                    )
+                });
 
         // Create pipeline layout
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -361,73 +769,38 @@ impl ModelBuilder for EffectModelBuilder {
             push_constant_ranges: &[],
         });
 
-        // Get or create pipeline
-        let pipeline =
-            pipeline_manager
-                .lock()
-                .unwrap()
-                .get_or_init("effect_render_pipeline", || {
+        // Get or create the pipelines of the passes, shared by effects using
+        // the same shader
+        let last = self.passes.len() - 1;
+        let pass_pipelines: Vec<_> = self
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| {
+                let key = format!(
+                    "effect_pipeline_{}_{}",
+                    self.shader.label.unwrap_or(""),
+                    pass.entry_point
+                );
+                pipeline_manager.lock().unwrap().get_or_init(&key, || {
                     // Use the specialized effect shader
                     let shader = device.create_shader_module(self.shader.clone());
-
-                    Arc::new(
-                        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                            label: Some("Effect Render Pipeline"),
-                            layout: Some(&pipeline_layout),
-                            vertex: wgpu::VertexState {
-                                module: &shader,
-                                entry_point: Some("vs_main"),
-                                buffers: &[],
-                                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                            },
-                            fragment: Some(wgpu::FragmentState {
-                                module: &shader,
-                                entry_point: Some("fs_main"),
-                                targets: &[Some(wgpu::ColorTargetState {
-                                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
-                                    blend: Some(wgpu::BlendState {
-                                        color: wgpu::BlendComponent {
-                                            src_factor: wgpu::BlendFactor::SrcAlpha,
-                                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                                            operation: wgpu::BlendOperation::Add,
-                                        },
-                                        alpha: wgpu::BlendComponent {
-                                            src_factor: wgpu::BlendFactor::One,
-                                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                                            operation: wgpu::BlendOperation::Add,
-                                        },
-                                    }),
-                                    write_mask: wgpu::ColorWrites::ALL,
-                                })],
-                                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                            }),
-                            primitive: wgpu::PrimitiveState {
-                                topology: wgpu::PrimitiveTopology::TriangleList,
-                                strip_index_format: None,
-                                front_face: wgpu::FrontFace::Ccw,
-                                cull_mode: None,
-                                polygon_mode: wgpu::PolygonMode::Fill,
-                                unclipped_depth: false,
-                                conservative: false,
-                            },
-                            depth_stencil: None,
-                            multisample: wgpu::MultisampleState {
-                                count: 1,
-                                mask: !0,
-                                alpha_to_coverage_enabled: false,
-                            },
-                            multiview: None,
-                            cache: None,
-                        }),
-                    )
-                });
+                    Arc::new(create_pipeline(
+                        device,
+                        &pipeline_layout,
+                        &shader,
+                        pass.entry_point,
+                        index == last,
+                    ))
+                })
+            })
+            .collect();
+        let pipeline = pass_pipelines[last].clone();
 
         // Create uniform buffer for shader parameters
-        // For Gaussian blur, we pass radius and time
-        let is_gaussian = matches!(self.shader.label, Some("gaussian.effect.wgsl"));
-
-        // Buffer will contain radius, time, and padding
-        let buffer_size = std::mem::size_of::<f32>() * 4; // 16 bytes for alignment
+        // Buffer holds two vec4s: two parameters, opacity and time, then up
+        // to four more parameters
+        let buffer_size = std::mem::size_of::<f32>() * 8;
         let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Effect Parameters Buffer"),
             size: buffer_size as u64,
@@ -435,89 +808,93 @@ impl ModelBuilder for EffectModelBuilder {
             mapped_at_creation: false,
         });
 
-        // Get shader type
-        let shader_label = self.shader.label.as_ref().map(|&s| s).unwrap_or("");
-        println!("Shader type: {shader_label}");
-
-        // Prepare parameters based on shader type
-        let initial_data = if shader_label == "gaussian.effect.wgsl" {
-            // Gaussian blur parameters
-            println!("Setting up Gaussian blur parameters for {}", self.label);
-
-            // Parse radius from manifest or use default
-            let radius = self.parse_f32_param("radius", 3.5f32);
-
-            // Use layer opacity to scale the effect intensity
-            let effect_strength = self.opacity;
-            let actual_radius = radius * effect_strength;
-
-            println!("Using blur radius: {radius} scaled by opacity: {effect_strength} = {actual_radius}");
-
-            // Parameters: radius, time, opacity (for intensity scaling), padding
-            [actual_radius, 0.0f32, effect_strength, 0.0f32]
-        } else if shader_label == "glitch.effect.wgsl" {
-            // Glitch effect parameters
-            println!("Setting up Glitch effect parameters for {}", self.label);
-
-            // Parse parameters from manifest or use defaults
-            let intensity = self.parse_f32_param("intensity", 0.5f32); // Strength of the glitch
-            let frequency = self.parse_f32_param("frequency", 0.3f32); // How often glitches occur
-
-            // Use layer opacity to scale the effect intensity
-            let effect_strength = self.opacity;
-            let actual_intensity = intensity * effect_strength;
-
-            println!("Using glitch intensity: {intensity} scaled by opacity: {effect_strength} = {actual_intensity}, frequency: {frequency}");
-
-            // Parameters: intensity, frequency, opacity (for intensity scaling), time
-            [actual_intensity, frequency, effect_strength, 0.0f32]
-        } else if shader_label == "wave.effect.wgsl" {
-            // Wave effect parameters
-            println!("Setting up Wave effect parameters for {}", self.label);
-
-            // Parse parameters from manifest or use defaults
-            let amplitude = self.parse_f32_param("amplitude", 0.2f32); // Wave height/strength
-            let frequency = self.parse_f32_param("frequency", 0.5f32); // Wave density
-
-            // Parse additional wave parameters (these will be ignored by the shader but kept for future expansion)
-            let _speed = self.parse_f32_param("speed", 1.0f32); // Animation speed multiplier
-            let _complexity = self.parse_f32_param("complexity", 1.0f32); // Wave complexity multiplier
-            let _direction = self.parse_f32_param("direction", 0.0f32); // Wave direction (0-360 degrees)
-
-            // Use layer opacity to scale the effect intensity
-            let effect_strength = self.opacity;
-            let actual_amplitude = amplitude * effect_strength;
-
-            println!("Using wave amplitude: {amplitude} scaled by opacity: {effect_strength} = {actual_amplitude}, frequency: {frequency}");
-
-            // Parameters: amplitude, frequency, opacity (for intensity scaling), time
-            [actual_amplitude, frequency, effect_strength, 0.0f32]
-        } else {
-            // Default parameters for other shaders
-            // Include opacity as the third parameter
-            [0.0f32, 0.0f32, self.opacity, 0.0f32]
-        };
+        // Get shader type, `include_wgsl!` labels shaders with their path
+        let shader_label = self.shader.label.unwrap_or("").trim_start_matches("./");
+        tracing::debug!("Setting up {shader_label} parameters for {}", self.label);
+
+        // Animated parameters start at their base, `EffectModel::set_time`
+        // moves them along
+        let mut animations = Vec::new();
+        let initial_data: Vec<f32> = param_slots(shader_label)
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                let (name, default, scale) = match *slot {
+                    Slot::Param(name, default) => (name, default, 1.0),
+                    Slot::Scaled(name, default) => (name, default, self.opacity),
+                    Slot::Opacity => return self.opacity,
+                    Slot::Time | Slot::Unused => return 0.0,
+                };
+                match self.parse_animation(name) {
+                    Some(animation) => {
+                        animations.push(AnimatedParam {
+                            offset: (index * std::mem::size_of::<f32>()) as u64,
+                            animation,
+                            scale,
+                        });
+                        animation.base * scale
+                    }
+                    None => self.parse_f32_param(name, default) * scale,
+                }
+            })
+            .collect();
 
         // Initialize the buffer with the appropriate parameters
         queue.write_buffer(&params_buffer, 0, bytemuck::cast_slice(&initial_data));
 
-        // Create bind group for this specific texture
+        // Refreshed with the time, see `EffectModel::update_time`
+        let metrics_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Effect Metrics Buffer"),
+            contents: bytemuck::bytes_of(&metrics::current()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let palette_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Effect Palette Buffer"),
+            contents: bytemuck::bytes_of(&colors::current().to_uniform()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Create bind group for this specific texture, multi-pass effects
+        // replace it once their passes have targets
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: params_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&noise.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::Sampler(&noise.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: metrics_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: palette_buffer.as_entire_binding(),
+            },
+        ];
+        if multi_pass {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+        }
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: params_buffer.as_entire_binding(),
-                },
-            ],
+            entries: &entries,
             label: Some(&format!("effect_bind_group_{}", self.label)),
         });
 
@@ -526,22 +903,265 @@ impl ModelBuilder for EffectModelBuilder {
         let is_animated = true;
 
         // Print animation status
-        println!("Effect {} is animated: {}", self.label, is_animated);
+        tracing::debug!("Effect {} is animated: {}", self.label, is_animated);
 
         if is_animated {
-            println!("Effect {} requires time updates for animation", self.label);
-            EffectModel::new_animated(
+            tracing::debug!("Effect {} requires time updates for animation", self.label);
+            Ok(EffectModel::new_animated(
                 texture,
                 pipeline.clone(),
                 Arc::new(bind_group),
                 params_buffer,
             )
+            .with_input(self.input, bind_group_layout.clone())
+            .with_noise(noise)
+            .with_metrics(metrics_buffer)
+            .with_palette(palette_buffer)
+            .with_passes(self.passes, pass_pipelines)
+            .with_animations(animations))
         } else {
-            EffectModel::new(texture, pipeline.clone(), Arc::new(bind_group))
+            Ok(EffectModel::new(
+                texture,
+                pipeline.clone(),
+                Arc::new(bind_group),
+            ))
         }
     }
 }
 
+/// A parameter of an effect following a `ParamAnimation`
+#[derive(Debug, Clone, Copy)]
+pub struct AnimatedParam {
+    /// Where the parameter is in the parameters buffer, in bytes
+    offset: u64,
+    animation: ParamAnimation,
+    /// What the value is multiplied by, the layer opacity for the
+    /// parameters scaled by it
+    scale: f32,
+}
+
+/// What a slot of an effect's parameters buffer holds
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    /// A parameter from the manifest, with its default
+    Param(&'static str, f32),
+    /// A parameter scaled by the layer opacity, so the effect fades with it
+    Scaled(&'static str, f32),
+    /// The layer opacity, see `EffectModel::set_strength`
+    Opacity,
+    /// The animation time, see `EffectModel::set_time`
+    Time,
+    Unused,
+}
+
+/// Layout of the parameters buffer of the shader labelled `shader_label`:
+/// two parameters, opacity and time, then up to four more parameters
+fn param_slots(shader_label: &str) -> &'static [Slot] {
+    use Slot::*;
+    match shader_label {
+        "gaussian.effect.wgsl" => &[Scaled("radius", 3.5), Unused, Opacity, Time],
+        // Intensity is the strength of the glitches, frequency how often
+        // they occur
+        "glitch.effect.wgsl" => &[
+            Scaled("intensity", 0.5),
+            Param("frequency", 0.3),
+            Opacity,
+            Time,
+        ],
+        "wave.effect.wgsl" => &[
+            Scaled("amplitude", 0.2),
+            Param("frequency", 0.5),
+            Opacity,
+            Time,
+        ],
+        "ripple.effect.wgsl" => &[
+            Param("amplitude", 0.01),
+            Param("frequency", 12.0),
+            Opacity,
+            Time,
+            Param("speed", 1.0),
+            Param("center_x", 0.5),
+            Param("center_y", 0.5),
+            Param("damping", 2.0),
+        ],
+        "snow.effect.wgsl" => &[
+            Param("density", 0.4),
+            Param("speed", 1.0),
+            Opacity,
+            Time,
+            Param("size", 1.0),
+            Param("wind", 0.2),
+        ],
+        "rain.effect.wgsl" => &[
+            Param("density", 0.3),
+            Param("speed", 1.0),
+            Opacity,
+            Time,
+            Param("angle", 0.15),
+            Param("length", 1.0),
+        ],
+        "fire.effect.wgsl" => &[
+            Param("height", 0.4),
+            Param("speed", 1.0),
+            Opacity,
+            Time,
+            Param("scale", 1.0),
+        ],
+        "plasma.effect.wgsl" => &[Param("scale", 1.0), Param("speed", 1.0), Opacity, Time],
+        // The strength follows the overview fading in and out
+        "overview.effect.wgsl" => &[Param("radius", 6.0), Param("dim", 0.35), Opacity, Time],
+        "starfield.effect.wgsl" => &[
+            Param("density", 0.5),
+            Param("speed", 1.0),
+            Opacity,
+            Time,
+            Param("twinkle", 0.5),
+        ],
+        _ => &[Unused, Unused, Opacity, Time],
+    }
+}
+
+/// Bindings of the effect shaders: texture, sampler and parameters, the
+/// noise texture and its sampler at 4 and 5, system metrics at 6, the color
+/// scheme at 7, plus the previous frame of the pass at binding 3 for
+/// multi-pass effects
+fn bind_group_layout_entries(feedback: bool) -> Vec<wgpu::BindGroupLayoutEntry> {
+    let texture = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+    };
+    let mut entries = vec![
+        texture(0),
+        // Sampler binding
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+        // Time uniform binding (needed for effect shaders)
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        },
+    ];
+    if feedback {
+        entries.push(texture(3));
+    }
+    entries.push(texture(4));
+    entries.push(wgpu::BindGroupLayoutEntry {
+        binding: 5,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    });
+    let uniform = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    entries.push(uniform(6));
+    entries.push(uniform(7));
+    entries
+}
+
+/// Create the pipeline of an effect pass, the last one blends onto the
+/// layers below and the others replace their target
+fn create_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    last: bool,
+) -> RenderPipeline {
+    let blend = last.then_some(wgpu::BlendState {
+        color: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Effect Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(entry_point),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: PASS_FORMAT,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: crate::renderer::pipeline_cache::current().as_deref(),
+    })
+}
+
+/// Create a `width` by `height` texture for a pass before the last
+fn create_pass_texture(device: &Device, width: u32, height: u32) -> TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("Effect Pass Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PASS_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&Default::default())
+}
+
 /// Animated effect model that adds time-based animation parameters
 #[derive(Debug)]
 pub struct AnimatedEffectModel {
@@ -555,15 +1175,13 @@ pub struct AnimatedEffectModel {
     current_time: f32,
     /// Custom bind group that includes animation parameters
     animated_bind_group: Arc<BindGroup>,
-    // Lua script ctx
-    ctx: mlua::Lua,
 }
 
 impl AnimatedEffectModel {
     pub fn new(
         effect: EffectModel,
         speed: f32,
-        device: &Device,
+        _device: &Device,
         time_buffer: wgpu::Buffer,
         animated_bind_group: Arc<BindGroup>,
     ) -> Self {
@@ -573,7 +1191,6 @@ impl AnimatedEffectModel {
             time_buffer,
             current_time: 0.0,
             animated_bind_group,
-            ctx: mlua::Lua::new(),
         }
     }
 
@@ -607,10 +1224,10 @@ impl Render for AnimatedEffectModel {
         self.animated_bind_group.clone()
     }
 
-    fn pre_render(&mut self, _device: &Device, dt: Duration) {
+    fn pre_render(&mut self, _device: &Device, _dt: Duration) {
         // In the animated effect model, we update the time directly here
         if self.current_time < 0.1 {
-            println!("AnimatedEffectModel pre_render called, will update time");
+            tracing::trace!("AnimatedEffectModel pre_render called, will update time");
         }
         // We can't update the time here because we don't have access to queue
     }
@@ -625,6 +1242,7 @@ impl Render for AnimatedEffectModel {
 }
 
 /// Builder for animated effect models
+#[allow(dead_code)]
 pub struct AnimatedEffectModelBuilder {
     /// The base effect builder
     effect_builder: EffectModelBuilder,
@@ -665,8 +1283,8 @@ impl ModelBuilder for AnimatedEffectModelBuilder {
         queue: &Queue,
         bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
         pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
-    ) -> Self::Target {
-        println!(
+    ) -> Result<Self::Target, String> {
+        tracing::debug!(
             "Building animated effect model for {}",
             self.effect_builder.label
         );
@@ -676,7 +1294,7 @@ impl ModelBuilder for AnimatedEffectModelBuilder {
             queue,
             bindgroup_layout_manager.clone(),
             pipeline_manager.clone(),
-        );
+        )?;
 
         // Create the time uniform buffer
         let time_buffer = device.create_buffer(&wgpu::BufferDescriptor {
@@ -754,12 +1372,12 @@ impl ModelBuilder for AnimatedEffectModelBuilder {
             label: Some("animated_effect_bind_group"),
         });
 
-        AnimatedEffectModel::new(
+        Ok(AnimatedEffectModel::new(
             base_effect,
             self.speed,
             device,
             time_buffer,
             Arc::new(animated_bind_group),
-        )
+        ))
     }
 }
@@ -0,0 +1,240 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::manifest::{GradientContent, GradientKind, MAX_GRADIENT_STOPS};
+use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPipeline};
+
+use crate::renderer::{
+    manager::Manager,
+    models::{color::parse_color, ModelBuilder},
+    pipeline::Render,
+};
+
+/// A model that fills the screen with a gradient, optionally rotating it
+#[derive(Debug)]
+pub struct GradientModel {
+    uniform: GradientUniform,
+    uniform_buffer: Buffer,
+    /// Rotation speed in radians per second
+    speed: f32,
+    queue: Queue,
+    render_pipeline: Arc<RenderPipeline>,
+    bind_group: Arc<BindGroup>,
+}
+
+impl Render for GradientModel {
+    fn pipeline(&self) -> Arc<RenderPipeline> {
+        self.render_pipeline.clone()
+    }
+
+    fn bindgroup(&self) -> Arc<BindGroup> {
+        self.bind_group.clone()
+    }
+
+    fn update_interval(&self) -> Option<Duration> {
+        (self.speed != 0.0).then_some(Duration::ZERO)
+    }
+
+    fn pre_render(&mut self, _device: &Device, dt: Duration) {
+        if self.speed == 0.0 {
+            return;
+        }
+
+        self.uniform.angle =
+            (self.uniform.angle + self.speed * dt.as_secs_f32()).rem_euclid(std::f32::consts::TAU);
+        self.queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    fn resize(&mut self, _device: &Device, queue: &Queue, width: u32, height: u32, _scale: f32) {
+        self.uniform.screen_size = [width as f32, height as f32];
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Builds a model for rendering a gradient
+pub struct GradientModelBuilder {
+    content: GradientContent,
+    label: String,
+}
+
+impl GradientModelBuilder {
+    pub fn new(content: GradientContent, label: impl Into<String>) -> Self {
+        Self {
+            content,
+            label: label.into(),
+        }
+    }
+}
+
+impl ModelBuilder for GradientModelBuilder {
+    type Target = GradientModel;
+
+    fn build(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    ) -> Result<Self::Target, String> {
+        let uniform = GradientUniform::new(&self.content)?;
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("Gradient Buffer: {}", self.label)),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Get or create the bind group layout
+        let bind_group_layout = bindgroup_layout_manager.lock().unwrap().get_or_init(
+            "gradient_bind_group_layout",
+            || {
+                Arc::new(
+                    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        entries: &[wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        }],
+                        label: Some("gradient_bind_group_layout"),
+                    }),
+                )
+            },
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gradient Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Create pipeline if it doesn't exist yet
+        let pipeline =
+            pipeline_manager
+                .lock()
+                .unwrap()
+                .get_or_init("gradient_render_pipeline", || {
+                    let shader = device.create_shader_module(crate::shaders::GRADIENT_SHADER);
+
+                    Arc::new(
+                        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                            label: Some("Gradient Render Pipeline"),
+                            layout: Some(&pipeline_layout),
+                            vertex: wgpu::VertexState {
+                                module: &shader,
+                                entry_point: Some("vs_main"),
+                                buffers: &[],
+                                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                            },
+                            fragment: Some(wgpu::FragmentState {
+                                module: &shader,
+                                entry_point: Some("fs_main"),
+                                targets: &[Some(wgpu::ColorTargetState {
+                                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                                    write_mask: wgpu::ColorWrites::ALL,
+                                })],
+                                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                            }),
+                            primitive: wgpu::PrimitiveState {
+                                topology: wgpu::PrimitiveTopology::TriangleList,
+                                strip_index_format: None,
+                                front_face: wgpu::FrontFace::Ccw,
+                                cull_mode: None,
+                                polygon_mode: wgpu::PolygonMode::Fill,
+                                unclipped_depth: false,
+                                conservative: false,
+                            },
+                            depth_stencil: None,
+                            multisample: wgpu::MultisampleState {
+                                count: 1,
+                                mask: !0,
+                                alpha_to_coverage_enabled: false,
+                            },
+                            multiview: None,
+                            cache: crate::renderer::pipeline_cache::current().as_deref(),
+                        }),
+                    )
+                });
+
+        // Create bind group
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some(&format!("gradient_bind_group_{}", self.label)),
+        });
+
+        Ok(GradientModel {
+            uniform,
+            uniform_buffer,
+            speed: self.content.speed.to_radians(),
+            queue: queue.clone(),
+            render_pipeline: pipeline,
+            bind_group: Arc::new(bind_group),
+        })
+    }
+}
+
+// Uniform structure matching the gradient shader's expected format
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientUniform {
+    /// sRGB-encoded colors, the shader interpolates before linearizing
+    colors: [[f32; 4]; MAX_GRADIENT_STOPS],
+    positions: [f32; MAX_GRADIENT_STOPS],
+    center: [f32; 2],
+    screen_size: [f32; 2],
+    angle: f32,
+    kind: u32,
+    stop_count: u32,
+    _padding: u32,
+}
+
+impl GradientUniform {
+    fn new(content: &GradientContent) -> Result<Self, String> {
+        let mut uniform = Self {
+            colors: [[0.0; 4]; MAX_GRADIENT_STOPS],
+            positions: [0.0; MAX_GRADIENT_STOPS],
+            center: content.center,
+            screen_size: [1.0, 1.0],
+            angle: content.angle.to_radians(),
+            kind: match content.gradient {
+                GradientKind::Linear => 0,
+                GradientKind::Radial => 1,
+                GradientKind::Conic => 2,
+            },
+            stop_count: 0,
+            _padding: 0,
+        };
+
+        let stops = content.resolved_stops();
+        for (index, (color, position)) in stops.iter().take(MAX_GRADIENT_STOPS).enumerate() {
+            uniform.colors[index] = parse_color(color)?;
+            uniform.positions[index] = *position;
+        }
+        uniform.stop_count = stops.len().min(MAX_GRADIENT_STOPS) as u32;
+        Ok(uniform)
+    }
+}
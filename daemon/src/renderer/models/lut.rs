@@ -0,0 +1,344 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{Local, Timelike};
+use common::manifest::{ColorGrade, EffectInput, Sampling};
+use image::DynamicImage;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPipeline, Sampler, TextureView,
+};
+
+use crate::{
+    asset::{image::ImageTexture, lut::Lut},
+    renderer::{manager::Manager, models::ModelBuilder, pipeline::Render},
+};
+
+/// Lookup tables of a color grading model, the night one being the day one
+/// again when the effect has none
+#[derive(Debug)]
+struct LutTables {
+    day: TextureView,
+    night: TextureView,
+    sampler: Sampler,
+}
+
+/// A model that color grades its image or the layers below it with 3D lookup
+/// tables, blending from the day to the night table as configured
+#[derive(Debug)]
+pub struct LutModel {
+    grade: ColorGrade,
+    input: EffectInput,
+    texture: ImageTexture,
+    tables: LutTables,
+    queue: Queue,
+    params_buffer: Buffer,
+    opacity: f32,
+    /// Night amount last written to `params_buffer`
+    night: f32,
+    bind_group_layout: Arc<BindGroupLayout>,
+    render_pipeline: Arc<RenderPipeline>,
+    bind_group: Arc<BindGroup>,
+}
+
+impl LutModel {
+    fn write_params(&self) {
+        self.queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[self.night, self.opacity, 0.0, 0.0]),
+        );
+    }
+}
+
+impl Render for LutModel {
+    fn pipeline(&self) -> Arc<RenderPipeline> {
+        self.render_pipeline.clone()
+    }
+
+    fn bindgroup(&self) -> Arc<BindGroup> {
+        self.bind_group.clone()
+    }
+
+    /// The blend between the day and night grades moves slowly, once a
+    /// minute is plenty
+    fn update_interval(&self) -> Option<Duration> {
+        self.grade
+            .night_lut
+            .is_some()
+            .then_some(Duration::from_secs(60))
+    }
+
+    /// Follow the time of day, only writing the parameters when the blend moved
+    fn pre_render(&mut self, _device: &Device, _dt: Duration) {
+        if self.grade.night_lut.is_none() {
+            return;
+        }
+
+        let now = Local::now();
+        let hour = now.hour() as f32 + now.minute() as f32 / 60.0 + now.second() as f32 / 3600.0;
+        let night = self.grade.night_amount(hour);
+        if (night - self.night).abs() > 0.001 {
+            self.night = night;
+            self.write_params();
+        }
+    }
+
+    fn reads_backdrop(&self) -> bool {
+        self.input == EffectInput::Below
+    }
+
+    fn bind_backdrop(&mut self, device: &Device, backdrop: &TextureView) {
+        self.bind_group = Arc::new(create_bind_group(
+            device,
+            &self.bind_group_layout,
+            backdrop,
+            &self.texture.sampler,
+            &self.params_buffer,
+            &self.tables,
+        ));
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Builder for color grading models
+pub struct LutModelBuilder {
+    /// Image graded when the effect does not read the layers below
+    image: DynamicImage,
+    grade: ColorGrade,
+    label: String,
+    input: EffectInput,
+    opacity: f32,
+    sampling: Sampling,
+}
+
+impl LutModelBuilder {
+    pub fn new(image: DynamicImage, grade: ColorGrade, label: impl Into<String>) -> Self {
+        Self {
+            image,
+            grade,
+            label: label.into(),
+            input: EffectInput::default(),
+            opacity: 1.0,
+            sampling: Sampling::default(),
+        }
+    }
+
+    /// Set what the effect grades
+    pub fn with_input(mut self, input: EffectInput) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Set the layer opacity, how much of the graded picture shows
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Set the texture filtering mode of the image
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+}
+
+impl ModelBuilder for LutModelBuilder {
+    type Target = LutModel;
+
+    fn build(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    ) -> Result<Self::Target, String> {
+        let texture =
+            ImageTexture::with_sampling(device, queue, &self.image, &self.label, self.sampling);
+
+        // Decoding failures fail the wallpaper like broken images do
+        let load = |source| Lut::load(source).map_err(|e| e.to_string());
+        let day = load(&self.grade.lut)?.upload(device, queue, &self.label);
+        let night = match &self.grade.night_lut {
+            Some(source) => load(source)?.upload(device, queue, &self.label),
+            None => day.clone(),
+        };
+        let tables = LutTables {
+            day,
+            night,
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("LUT Sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }),
+        };
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("LUT Parameters Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout =
+            bindgroup_layout_manager
+                .lock()
+                .unwrap()
+                .get_or_init("lut_bind_group_layout", || {
+                    let texture = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+                        binding,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    };
+                    let sampler = |binding| wgpu::BindGroupLayoutEntry {
+                        binding,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    };
+                    Arc::new(
+                        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                            label: Some("lut_bind_group_layout"),
+                            entries: &[
+                                texture(0, wgpu::TextureViewDimension::D2),
+                                sampler(1),
+                                wgpu::BindGroupLayoutEntry {
+                                    binding: 2,
+                                    visibility: wgpu::ShaderStages::FRAGMENT,
+                                    ty: wgpu::BindingType::Buffer {
+                                        ty: wgpu::BufferBindingType::Uniform,
+                                        has_dynamic_offset: false,
+                                        min_binding_size: None,
+                                    },
+                                    count: None,
+                                },
+                                texture(3, wgpu::TextureViewDimension::D3),
+                                texture(4, wgpu::TextureViewDimension::D3),
+                                sampler(5),
+                            ],
+                        }),
+                    )
+                });
+
+        let render_pipeline =
+            pipeline_manager
+                .lock()
+                .unwrap()
+                .get_or_init("lut_render_pipeline", || {
+                    let shader = device.create_shader_module(crate::shaders::LUT_EFFECT_SHADER);
+                    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some("LUT Pipeline Layout"),
+                        bind_group_layouts: &[&bind_group_layout],
+                        push_constant_ranges: &[],
+                    });
+                    Arc::new(
+                        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                            label: Some("LUT Render Pipeline"),
+                            layout: Some(&layout),
+                            vertex: wgpu::VertexState {
+                                module: &shader,
+                                entry_point: Some("vs_main"),
+                                buffers: &[],
+                                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                            },
+                            fragment: Some(wgpu::FragmentState {
+                                module: &shader,
+                                entry_point: Some("fs_main"),
+                                targets: &[Some(wgpu::ColorTargetState {
+                                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                                    write_mask: wgpu::ColorWrites::ALL,
+                                })],
+                                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                            }),
+                            primitive: wgpu::PrimitiveState::default(),
+                            depth_stencil: None,
+                            multisample: wgpu::MultisampleState::default(),
+                            multiview: None,
+                            cache: crate::renderer::pipeline_cache::current().as_deref(),
+                        }),
+                    )
+                });
+
+        let bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            &texture.view,
+            &texture.sampler,
+            &params_buffer,
+            &tables,
+        );
+
+        let mut model = LutModel {
+            grade: self.grade.clone(),
+            input: self.input,
+            texture,
+            tables,
+            queue: queue.clone(),
+            params_buffer,
+            opacity: self.opacity,
+            night: 0.0,
+            bind_group_layout,
+            render_pipeline,
+            bind_group: Arc::new(bind_group),
+        };
+        model.write_params();
+        // Start at the right blend instead of fading in on the first frame
+        model.pre_render(device, Duration::ZERO);
+        Ok(model)
+    }
+}
+
+fn create_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    input: &TextureView,
+    input_sampler: &Sampler,
+    params_buffer: &Buffer,
+    tables: &LutTables,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("lut_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(input),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(input_sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: params_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&tables.day),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&tables.night),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::Sampler(&tables.sampler),
+            },
+        ],
+    })
+}
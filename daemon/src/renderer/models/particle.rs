@@ -1,51 +1,36 @@
 use std::{
-    sync::{Arc, Mutex},
-    path::Path,
     fs,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
-use image::DynamicImage;
-use mlua::{Lua, Function, Table, Value, FromLua};
-use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPipeline, Buffer};
+use common::manifest::{Emitter, ParticleSprites, Permission};
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, GenericImageView, RgbaImage,
+};
+use wgpu::{
+    util::{DeviceExt, DrawIndirectArgs},
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, ComputePipeline, Device, Queue, RenderPass,
+    RenderPipeline,
+};
 
 use crate::{
     asset::image::ImageTexture,
-    renderer::{manager::Manager, models::ModelBuilder, pipeline::Render},
+    renderer::{emitter::Simulator, manager::Manager, models::ModelBuilder, pipeline::Render},
+    script::{particles::Particle, thread::ParticleScript},
 };
 
-/// A single particle in the particle system
-#[derive(Debug, Clone, Copy)]
-#[repr(C)]
-pub struct Particle {
-    pub position: [f32; 2], // x, y normalized coords (-1.0 to 1.0)
-    pub velocity: [f32; 2], // movement direction and speed
-    pub color: [f32; 4],    // rgba color
-    pub size: f32,          // particle size
-    pub rotation: f32,      // rotation in radians
-    pub life: f32,          // remaining lifetime (0.0 to 1.0)
-    pub alive: u32,         // 1 if alive, 0 if dead (for GPU filtering)
-}
+/// Particles a workgroup of the compaction shader goes over
+const COMPACT_WORKGROUP_SIZE: u32 = 64;
 
-// Make Particle compatible with GPU buffers
-unsafe impl bytemuck::Pod for Particle {}
-unsafe impl bytemuck::Zeroable for Particle {}
+/// Where the instance count is in `DrawIndirectArgs`
+const INSTANCE_COUNT_OFFSET: u64 = 4;
 
-impl Particle {
-    pub fn new(x: f32, y: f32, size: f32) -> Self {
-        Self {
-            position: [x, y],
-            velocity: [0.0, 0.0],
-            color: [1.0, 1.0, 1.0, 1.0],
-            size,
-            rotation: 0.0,
-            life: 1.0,
-            alive: 1,
-        }
-    }
-}
-
-/// Represents a collection of particles controlled by a Lua script
+/// Particles drawn as textured quads, moved by a Lua script or by the
+/// layer's emitter
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct ParticleModel {
     /// The texture for rendering particles
     texture: ImageTexture,
@@ -53,349 +38,109 @@ pub struct ParticleModel {
     render_pipeline: Arc<RenderPipeline>,
     /// The bind group for the particle system
     bind_group: Arc<BindGroup>,
-    /// Buffer containing particle data
+    /// Buffer containing particle data, alive or not
     particle_buffer: Buffer,
-    /// Maximum number of particles
-    max_particles: u32,
-    /// Current number of active particles
-    active_particles: u32,
-    /// Lua context for particle simulation
-    lua: Lua,
-    /// Current simulation time
-    time: f32,
-    /// Update script
-    update_script: Option<String>,
-    /// Current particle data (CPU side)
-    particles: Vec<Particle>,
+    /// The alive particles, compacted from `particle_buffer` on the GPU
+    /// and drawn from there
+    alive_buffer: Buffer,
+    /// Arguments of the indirect draw, the compaction counts the instances
+    draw_buffer: Buffer,
+    compact_pipeline: ComputePipeline,
+    compact_bind_group: BindGroup,
+    /// Workgroups the compaction takes to go over all particles
+    workgroups: u32,
+    queue: Queue,
+    /// What moves the particles
+    motion: Motion,
+    /// Why the script was stopped or failed to load, it does not run again
+    warning: Option<String>,
+}
+
+/// What moves the particles of a `ParticleModel`
+#[derive(Debug)]
+enum Motion {
+    /// A script on the scripting thread, with its own particles
+    Script(ParticleScript),
+    /// The emitter of the manifest, on the CPU side particles
+    Emitter(Simulator, Vec<Particle>),
 }
 
 impl ParticleModel {
-    pub fn new(
-        texture: ImageTexture,
-        render_pipeline: Arc<RenderPipeline>,
-        bind_group: Arc<BindGroup>,
-        particle_buffer: Buffer,
-        max_particles: u32,
-        update_script: Option<String>,
-    ) -> Self {
-        // Create Lua environment and load standard libraries
-        let lua = Lua::new();
-        
-        // Load the standard libraries
-        lua.load_from_std_lib(mlua::StdLib::ALL).expect("Failed to load Lua standard libraries");
-        
-        // Pre-allocate particle array
-        let mut particles = Vec::with_capacity(max_particles as usize);
-        for _ in 0..max_particles {
-            particles.push(Particle::new(0.0, 0.0, 0.1)); // Default inactive particles
-        }
+    /// Update the particles by `delta_time` seconds and upload them
+    fn update(&mut self, delta_time: f32) {
+        let script = match &mut self.motion {
+            Motion::Script(script) => script,
+            Motion::Emitter(simulator, particles) => {
+                if self.warning.is_none() {
+                    simulator.step(particles, delta_time);
+                }
+                self.queue
+                    .write_buffer(&self.particle_buffer, 0, bytemuck::cast_slice(particles));
+                return;
+            }
+        };
 
-        // Initialize all particles as inactive
-        for particle in &mut particles {
-            particle.alive = 0;
+        // The script runs on its own thread, what it made of the particles
+        // by now is drawn
+        if self.warning.is_none() {
+            script.tick(delta_time);
         }
-
-        Self {
-            texture,
-            render_pipeline,
-            bind_group,
-            particle_buffer,
-            max_particles,
-            active_particles: 0,
-            lua,
-            time: 0.0,
-            update_script,
-            particles,
+        if let Some(warning) = script.warning() {
+            self.warning = Some(warning);
         }
-    }
-
-    /// Update the particle simulation
-    pub fn update(&mut self, delta_time: f32, queue: &Queue) {
-        self.time += delta_time;
-
-        if let Some(script_path) = &self.update_script {
-            // Execute the Lua script to update particles
-            self.update_particles_with_lua(delta_time);
-        } else {
-            // Use a simple built-in update if no script is provided
-            self.update_particles_builtin(delta_time);
+        // Uploaded straight from the script's array, unless it is busy
+        // with it, then the GPU keeps the last ones
+        if let Some(particles) = script.particles() {
+            self.queue
+                .write_buffer(&self.particle_buffer, 0, bytemuck::cast_slice(&particles.0));
         }
-
-        // Upload updated particle data to the GPU
-        queue.write_buffer(
-            &self.particle_buffer,
-            0,
-            bytemuck::cast_slice(&self.particles),
-        );
     }
+}
 
-    /// Simple built-in particle update function for when no Lua script is provided
-    fn update_particles_builtin(&mut self, delta_time: f32) {
-        // Count how many alive particles we have
-        let mut alive_count = 0;
-
-        // Update each particle
-        for i in 0..self.max_particles as usize {
-            let particle = &mut self.particles[i];
-            
-            if particle.alive == 1 {
-                // Update position based on velocity
-                particle.position[0] += particle.velocity[0] * delta_time;
-                particle.position[1] += particle.velocity[1] * delta_time;
-                
-                // Update rotation
-                particle.rotation += 0.1 * delta_time;
-                
-                // Decrease lifetime
-                particle.life -= delta_time;
-                
-                // If lifetime is over, mark as dead
-                if particle.life <= 0.0 {
-                    particle.alive = 0;
-                } else {
-                    // Fade out as lifetime decreases
-                    particle.color[3] = particle.life;
-                    alive_count += 1;
-                }
-            }
-        }
-
-        // Spawn new particles if needed (simple fountain effect)
-        if alive_count < (self.max_particles as usize / 2) {
-            // Randomly spawn some new particles
-            let spawn_count = 5.min(self.max_particles as usize - alive_count);
-            
-            for _ in 0..spawn_count {
-                // Find an inactive particle slot
-                if let Some(idx) = self.particles.iter().position(|p| p.alive == 0) {
-                    let p = &mut self.particles[idx];
-                    
-                    // Reset the particle
-                    p.position = [0.0, -0.5]; // Start at bottom center
-                    p.velocity = [
-                        (rand::random::<f32>() - 0.5) * 0.5, // Random x velocity
-                        rand::random::<f32>() * 0.5,         // Upward y velocity
-                    ];
-                    p.color = [
-                        rand::random::<f32>(), 
-                        rand::random::<f32>(), 
-                        rand::random::<f32>(), 
-                        1.0,
-                    ];
-                    p.size = 0.02 + rand::random::<f32>() * 0.03;
-                    p.rotation = rand::random::<f32>() * std::f32::consts::PI * 2.0;
-                    p.life = 0.5 + rand::random::<f32>() * 1.0;
-                    p.alive = 1;
-                }
-            }
-        }
+impl Render for ParticleModel {
+    fn pipeline(&self) -> Arc<RenderPipeline> {
+        self.render_pipeline.clone()
+    }
 
-        // Update active count
-        self.active_particles = alive_count as u32;
+    fn bindgroup(&self) -> Arc<BindGroup> {
+        self.bind_group.clone()
     }
 
-    /// Update particles using the Lua script
-    fn update_particles_with_lua(&mut self, delta_time: f32) {
-        if let Some(script_path) = &self.update_script {
-            // Try to load the script if not already loaded
-            let script_content = match fs::read_to_string(script_path) {
-                Ok(content) => content,
-                Err(err) => {
-                    eprintln!("Error reading Lua script {}: {}", script_path, err);
-                    return;
-                }
-            };
+    fn pre_render(&mut self, _device: &Device, dt: Duration) {
+        self.update(dt.as_secs_f32());
+    }
 
-            // Set up the Lua environment
-            let globals = self.lua.globals();
-            
-            // Pass in delta_time and time to Lua
-            globals.set("delta_time", delta_time).unwrap();
-            globals.set("time", self.time).unwrap();
-            globals.set("max_particles", self.max_particles).unwrap();
-            globals.set("active_particles", self.active_particles).unwrap();
-
-            // Helper function to emit a particle
-            let emit_fn = self.lua.create_function(|lua, args: Table| {
-                // Extract arguments
-                let x = args.get::<_, Option<f32>>("x").unwrap_or(0.0);
-                let y = args.get::<_, Option<f32>>("y").unwrap_or(0.0);
-                let vx = args.get::<_, Option<f32>>("vx").unwrap_or(0.0);
-                let vy = args.get::<_, Option<f32>>("vy").unwrap_or(0.0);
-                let size = args.get::<_, Option<f32>>("size").unwrap_or(0.05);
-                let life = args.get::<_, Option<f32>>("life").unwrap_or(1.0);
-                let r = args.get::<_, Option<f32>>("r").unwrap_or(1.0);
-                let g = args.get::<_, Option<f32>>("g").unwrap_or(1.0);
-                let b = args.get::<_, Option<f32>>("b").unwrap_or(1.0);
-                let a = args.get::<_, Option<f32>>("a").unwrap_or(1.0);
-                let rotation = args.get::<_, Option<f32>>("rotation").unwrap_or(0.0);
-
-                // This is now a Lua userdata - we'll need to convert it back to our particles Vec
-                let particles_ref = lua.globals().get::<_, mlua::AnyUserData>("_particles_ref").expect("Particles reference not found");
-                
-                // Get a mutable reference to the particles vector
-                let result: mlua::Result<()> = (|particles_ref: &mlua::AnyUserData| {
-                    let mut particles = particles_ref.borrow_mut::<Vec<Particle>>()?;
-                    
-                    // Find an inactive particle
-                    if let Some(idx) = particles.iter().position(|p| p.alive == 0) {
-                        let p = &mut particles[idx];
-                        
-                        // Reset the particle with the specified values
-                        p.position = [x, y];
-                        p.velocity = [vx, vy];
-                        p.color = [r, g, b, a];
-                        p.size = size;
-                        p.rotation = rotation;
-                        p.life = life;
-                        p.alive = 1;
-                    }
-                    
-                    Ok(())
-                })(particles_ref);
-
-                if let Err(e) = result {
-                    eprintln!("Error in emit_particle: {}", e);
-                }
-                
-                Ok(())
-            }).expect("Failed to create emit function");
-            
-            globals.set("emit_particle", emit_fn).unwrap();
-            
-            // Helper function to update a particle
-            let update_particle_fn = self.lua.create_function(|lua, (index, args): (usize, Table)| {
-                // This is a Lua userdata - we'll need to convert it back to our particles Vec
-                let particles_ref = lua.globals().get::<_, mlua::AnyUserData>("_particles_ref").expect("Particles reference not found");
-                
-                // Get a mutable reference to the particles vector
-                let result: mlua::Result<()> = (|particles_ref: &mlua::AnyUserData| {
-                    let mut particles = particles_ref.borrow_mut::<Vec<Particle>>()?;
-                    
-                    if index < particles.len() {
-                        let p = &mut particles[index];
-                        
-                        // Only update the fields that are specified
-                        if let Ok(x) = args.get::<_, f32>("x") { p.position[0] = x; }
-                        if let Ok(y) = args.get::<_, f32>("y") { p.position[1] = y; }
-                        if let Ok(vx) = args.get::<_, f32>("vx") { p.velocity[0] = vx; }
-                        if let Ok(vy) = args.get::<_, f32>("vy") { p.velocity[1] = vy; }
-                        if let Ok(size) = args.get::<_, f32>("size") { p.size = size; }
-                        if let Ok(life) = args.get::<_, f32>("life") { p.life = life; }
-                        if let Ok(r) = args.get::<_, f32>("r") { p.color[0] = r; }
-                        if let Ok(g) = args.get::<_, f32>("g") { p.color[1] = g; }
-                        if let Ok(b) = args.get::<_, f32>("b") { p.color[2] = b; }
-                        if let Ok(a) = args.get::<_, f32>("a") { p.color[3] = a; }
-                        if let Ok(rotation) = args.get::<_, f32>("rotation") { p.rotation = rotation; }
-                        if let Ok(alive) = args.get::<_, bool>("alive") { p.alive = if alive { 1 } else { 0 }; }
-                    }
-                    
-                    Ok(())
-                })(particles_ref);
-
-                if let Err(e) = result {
-                    eprintln!("Error in update_particle: {}", e);
-                }
-                
-                Ok(())
-            }).expect("Failed to create update_particle function");
-            
-            globals.set("update_particle", update_particle_fn).unwrap();
-
-            // Helper function to get particle data
-            let get_particle_fn = self.lua.create_function(|lua, index: usize| {
-                // This is a Lua userdata - convert it back to our particles Vec
-                let particles_ref = lua.globals().get::<_, mlua::AnyUserData>("_particles_ref").expect("Particles reference not found");
-                
-                // Create a result table
-                let result_table = lua.create_table()?;
-                
-                // Get a reference to the particles vector
-                let result: mlua::Result<Table> = (|particles_ref: &mlua::AnyUserData| {
-                    let particles = particles_ref.borrow::<Vec<Particle>>()?;
-                    
-                    if index < particles.len() {
-                        let p = &particles[index];
-                        
-                        // Create a new table with the particle data
-                        let result_table = lua.create_table()?;
-                        result_table.set("x", p.position[0])?;
-                        result_table.set("y", p.position[1])?;
-                        result_table.set("vx", p.velocity[0])?;
-                        result_table.set("vy", p.velocity[1])?;
-                        result_table.set("size", p.size)?;
-                        result_table.set("life", p.life)?;
-                        result_table.set("r", p.color[0])?;
-                        result_table.set("g", p.color[1])?;
-                        result_table.set("b", p.color[2])?;
-                        result_table.set("a", p.color[3])?;
-                        result_table.set("rotation", p.rotation)?;
-                        result_table.set("alive", p.alive == 1)?;
-                        
-                        Ok(result_table)
-                    } else {
-                        // Return an empty table if the index is out of bounds
-                        Ok(lua.create_table()?)
-                    }
-                })(particles_ref);
-
-                match result {
-                    Ok(table) => Ok(table),
-                    Err(e) => {
-                        eprintln!("Error in get_particle: {}", e);
-                        Ok(lua.create_table()?)
-                    }
-                }
-            }).expect("Failed to create get_particle function");
-            
-            globals.set("get_particle", get_particle_fn).unwrap();
-
-            // Register our particle array with Lua
-            let particles_userdata = self.lua.create_userdata(self.particles.clone())
-                .expect("Failed to create particles userdata");
-            
-            globals.set("_particles_ref", particles_userdata).unwrap();
-
-            // Define a random function for Lua
-            let random_fn = self.lua.create_function(|_, (min, max): (f32, f32)| {
-                Ok(min + (max - min) * rand::random::<f32>())
-            }).expect("Failed to create random function");
-            
-            globals.set("random", random_fn).unwrap();
-
-            // Execute the Lua script
-            if let Err(err) = self.lua.load(&script_content).exec() {
-                eprintln!("Error running Lua script: {}", err);
-                return;
-            }
+    fn update_interval(&self) -> Option<Duration> {
+        // A stopped script leaves its particles where they are
+        self.warning.is_none().then_some(Duration::ZERO)
+    }
 
-            // Call the update function if it exists
-            if let Ok(update_fn) = globals.get::<_, Function>("update") {
-                if let Err(err) = update_fn.call::<_, ()>(()) {
-                    eprintln!("Error calling Lua update function: {}", err);
-                }
-            }
+    fn has_passes(&self) -> bool {
+        true
+    }
 
-            // Retrieve the updated particles from Lua
-            if let Ok(particles_ref) = globals.get::<_, mlua::AnyUserData>("_particles_ref") {
-                if let Ok(updated_particles) = particles_ref.take::<Vec<Particle>>() {
-                    self.particles = updated_particles;
-                    
-                    // Count active particles
-                    self.active_particles = self.particles.iter().filter(|p| p.alive == 1).count() as u32;
-                }
-            }
-        }
+    fn encode_passes(&mut self, encoder: &mut CommandEncoder) {
+        // Counted again from zero, the CPU never reads the count back
+        encoder.clear_buffer(&self.draw_buffer, INSTANCE_COUNT_OFFSET, Some(4));
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Particle Compaction"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.compact_pipeline);
+        pass.set_bind_group(0, Some(&self.compact_bind_group), &[]);
+        pass.dispatch_workgroups(self.workgroups, 1, 1);
     }
-}
 
-impl Render for ParticleModel {
-    fn pipeline(&self) -> Arc<RenderPipeline> {
-        self.render_pipeline.clone()
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        // An instance of the quad for each alive particle, pulled from the
+        // compacted buffer by its index
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, Some(&*self.bind_group), &[]);
+        render_pass.draw_indirect(&self.draw_buffer, 0);
     }
 
-    fn bindgroup(&self) -> Arc<BindGroup> {
-        self.bind_group.clone()
+    fn warning(&self) -> Option<String> {
+        self.warning.clone()
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -414,6 +159,13 @@ pub struct ParticleModelBuilder {
     max_particles: u32,
     /// Lua script to control particle behavior (relative to wallpaper path)
     script_path: Option<String>,
+    /// Moves the particles when there is no script
+    emitter: Emitter,
+    /// Textures after `particle_image` and their frames
+    textures: Vec<DynamicImage>,
+    sprites: ParticleSprites,
+    /// What the script may do, see `Wallpaper::permissions`
+    permissions: Vec<Permission>,
     /// Label for this particle system
     label: String,
 }
@@ -423,15 +175,30 @@ impl ParticleModelBuilder {
         particle_image: DynamicImage,
         max_particles: u32,
         script_path: Option<String>,
+        emitter: Emitter,
+        permissions: Vec<Permission>,
         label: impl Into<String>,
     ) -> Self {
         Self {
             particle_image,
-            max_particles,
+            // At least one, buffers cannot be empty
+            max_particles: max_particles.max(1),
             script_path,
+            emitter,
+            textures: Vec::new(),
+            sprites: ParticleSprites::default(),
+            permissions,
             label: label.into(),
         }
     }
+
+    /// Draw the particles with `textures` too, cut into frames as `sprites`
+    /// says
+    pub fn with_sprites(mut self, textures: Vec<DynamicImage>, sprites: ParticleSprites) -> Self {
+        self.textures = textures;
+        self.sprites = sprites;
+        self
+    }
 }
 
 impl ModelBuilder for ParticleModelBuilder {
@@ -443,18 +210,49 @@ impl ModelBuilder for ParticleModelBuilder {
         queue: &Queue,
         bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
         pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
-    ) -> Self::Target {
-        // Create texture from the particle image
-        let texture = ImageTexture::from_image(device, queue, &self.particle_image, &self.label);
+    ) -> Result<Self::Target, String> {
+        // All textures side by side in one, the shader picks the frame
+        let texture_count = 1 + self.textures.len() as u32;
+        let texture = ImageTexture::from_image(device, queue, &self.atlas(), &self.label);
+        let sheet = SheetUniform {
+            textures: texture_count,
+            columns: self.sprites.columns,
+            rows: self.sprites.rows,
+            _padding: 0,
+        };
+        let sheet_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("Particle Sheet Buffer {}", self.label)),
+            contents: bytemuck::cast_slice(&[sheet]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
 
         // Create the particle data buffer (initialize with zeros)
         let particle_buffer_size = std::mem::size_of::<Particle>() * self.max_particles as usize;
         let particle_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some(&format!("Particle Buffer {}", self.label)),
             size: particle_buffer_size as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
+        let alive_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("Alive Particle Buffer {}", self.label)),
+            size: particle_buffer_size as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let draw_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("Particle Draw Buffer {}", self.label)),
+            contents: DrawIndirectArgs {
+                vertex_count: 6,
+                instance_count: 0,
+                first_vertex: 0,
+                first_instance: 0,
+            }
+            .as_bytes(),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST,
+        });
 
         // Get or create the bind group layout for particles
         let bind_group_layout = bindgroup_layout_manager.lock().unwrap().get_or_init(
@@ -483,7 +281,7 @@ impl ModelBuilder for ParticleModelBuilder {
                                 ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                                 count: None,
                             },
-                            // Particle buffer (vertex buffer)
+                            // Particle buffer, one instance each
                             wgpu::BindGroupLayoutEntry {
                                 binding: 2,
                                 visibility: wgpu::ShaderStages::VERTEX,
@@ -494,6 +292,17 @@ impl ModelBuilder for ParticleModelBuilder {
                                 },
                                 count: None,
                             },
+                            // Textures and frames of the sprite sheets
+                            wgpu::BindGroupLayoutEntry {
+                                binding: 3,
+                                visibility: wgpu::ShaderStages::VERTEX,
+                                ty: wgpu::BindingType::Buffer {
+                                    ty: wgpu::BufferBindingType::Uniform,
+                                    has_dynamic_offset: false,
+                                    min_binding_size: None,
+                                },
+                                count: None,
+                            },
                         ],
                         label: Some("particle_bind_group_layout"),
                     }),
@@ -509,56 +318,54 @@ impl ModelBuilder for ParticleModelBuilder {
         });
 
         // Create or get the pipeline
-        let pipeline = pipeline_manager.lock().unwrap().get_or_init(
-            "particle_render_pipeline",
-            || {
-                // Create the shader for particles
-                let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                    label: Some("Particle Shader"),
-                    source: wgpu::ShaderSource::Wgsl(include_str!("../../shaders/particle.wgsl").into()),
-                });
-
-                Arc::new(
-                    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                        label: Some("Particle Render Pipeline"),
-                        layout: Some(&pipeline_layout),
-                        vertex: wgpu::VertexState {
-                            module: &shader,
-                            entry_point: Some("vs_main"),
-                            buffers: &[],
-                            compilation_options: wgpu::PipelineCompilationOptions::default(),
-                        },
-                        fragment: Some(wgpu::FragmentState {
-                            module: &shader,
-                            entry_point: Some("fs_main"),
-                            targets: &[Some(wgpu::ColorTargetState {
-                                format: wgpu::TextureFormat::Bgra8UnormSrgb,
-                                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                                write_mask: wgpu::ColorWrites::ALL,
-                            })],
-                            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        let pipeline =
+            pipeline_manager
+                .lock()
+                .unwrap()
+                .get_or_init("particle_render_pipeline", || {
+                    // Create the shader for particles
+                    let shader = device.create_shader_module(crate::shaders::PARTICLE_SHADER);
+
+                    Arc::new(
+                        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                            label: Some("Particle Render Pipeline"),
+                            layout: Some(&pipeline_layout),
+                            vertex: wgpu::VertexState {
+                                module: &shader,
+                                entry_point: Some("vs_main"),
+                                buffers: &[],
+                                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                            },
+                            fragment: Some(wgpu::FragmentState {
+                                module: &shader,
+                                entry_point: Some("fs_main"),
+                                targets: &[Some(wgpu::ColorTargetState {
+                                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                                    write_mask: wgpu::ColorWrites::ALL,
+                                })],
+                                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                            }),
+                            primitive: wgpu::PrimitiveState {
+                                topology: wgpu::PrimitiveTopology::TriangleList,
+                                strip_index_format: None,
+                                front_face: wgpu::FrontFace::Ccw,
+                                cull_mode: None,
+                                polygon_mode: wgpu::PolygonMode::Fill,
+                                unclipped_depth: false,
+                                conservative: false,
+                            },
+                            depth_stencil: None,
+                            multisample: wgpu::MultisampleState {
+                                count: 1,
+                                mask: !0,
+                                alpha_to_coverage_enabled: false,
+                            },
+                            multiview: None,
+                            cache: crate::renderer::pipeline_cache::current().as_deref(),
                         }),
-                        primitive: wgpu::PrimitiveState {
-                            topology: wgpu::PrimitiveTopology::TriangleList,
-                            strip_index_format: None,
-                            front_face: wgpu::FrontFace::Ccw,
-                            cull_mode: None,
-                            polygon_mode: wgpu::PolygonMode::Fill,
-                            unclipped_depth: false,
-                            conservative: false,
-                        },
-                        depth_stencil: None,
-                        multisample: wgpu::MultisampleState {
-                            count: 1,
-                            mask: !0,
-                            alpha_to_coverage_enabled: false,
-                        },
-                        multiview: None,
-                        cache: None,
-                    }),
-                )
-            },
-        );
+                    )
+                });
 
         // Create bind group for this particle system
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -574,19 +381,162 @@ impl ModelBuilder for ParticleModelBuilder {
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: particle_buffer.as_entire_binding(),
+                    resource: alive_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: sheet_buffer.as_entire_binding(),
                 },
             ],
             label: Some(&format!("particle_bind_group_{}", self.label)),
         });
 
-        ParticleModel::new(
+        let (compact_pipeline, compact_bind_group) = create_compaction(
+            device,
+            &bindgroup_layout_manager,
+            [&particle_buffer, &alive_buffer, &draw_buffer],
+            &self.label,
+        );
+
+        // Read and compiled once, the script keeps its state from then on.
+        // It runs with only what the user allowed the wallpaper, see
+        // `script::sandbox`
+        let mut warning = None;
+        let motion = match &self.script_path {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(source) => Some(Motion::Script(ParticleScript::load(
+                    path.clone(),
+                    source,
+                    self.permissions.clone(),
+                    self.max_particles,
+                ))),
+                Err(e) => {
+                    tracing::error!("Failed to read the particle script {path}: {e}");
+                    warning = Some(format!("Script failed to load: {e}"));
+                    None
+                }
+            },
+            None => None,
+        };
+        let motion = match motion {
+            Some(motion) => motion,
+            None => Motion::Emitter(
+                Simulator::new(self.emitter.clone(), texture_count, self.sprites.fps)?,
+                vec![Particle::dead(); self.max_particles as usize],
+            ),
+        };
+
+        Ok(ParticleModel {
             texture,
-            pipeline.clone(),
-            Arc::new(bind_group),
+            render_pipeline: pipeline,
+            bind_group: Arc::new(bind_group),
             particle_buffer,
-            self.max_particles,
-            self.script_path.clone(),
-        )
+            alive_buffer,
+            draw_buffer,
+            compact_pipeline,
+            compact_bind_group,
+            workgroups: self.max_particles.div_ceil(COMPACT_WORKGROUP_SIZE),
+            queue: queue.clone(),
+            motion,
+            warning,
+        })
     }
-}
\ No newline at end of file
+}
+
+impl ParticleModelBuilder {
+    /// The particle image and the other textures in a row, scaled to the
+    /// size of the first
+    fn atlas(&self) -> DynamicImage {
+        if self.textures.is_empty() {
+            return self.particle_image.clone();
+        }
+        let (width, height) = self.particle_image.dimensions();
+        let mut atlas = RgbaImage::new(width * (1 + self.textures.len() as u32), height);
+        for (index, texture) in std::iter::once(&self.particle_image)
+            .chain(&self.textures)
+            .enumerate()
+        {
+            let texture = texture.resize_exact(width, height, FilterType::Triangle);
+            imageops::replace(
+                &mut atlas,
+                &texture.to_rgba8(),
+                (index as u32 * width) as i64,
+                0,
+            );
+        }
+        DynamicImage::ImageRgba8(atlas)
+    }
+}
+
+/// The compute pipeline compacting the particles of `buffers`, all of them,
+/// the alive ones and the draw's arguments, and its bind group
+fn create_compaction(
+    device: &Device,
+    bindgroup_layout_manager: &Mutex<Manager<BindGroupLayout>>,
+    buffers: [&Buffer; 3],
+    label: &str,
+) -> (ComputePipeline, BindGroup) {
+    let bind_group_layout = bindgroup_layout_manager.lock().unwrap().get_or_init(
+        "particle_compact_bind_group_layout",
+        || {
+            let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            };
+            Arc::new(
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[storage(0, true), storage(1, false), storage(2, false)],
+                    label: Some("particle_compact_bind_group_layout"),
+                }),
+            )
+        },
+    );
+
+    // Compute pipelines are not shared through the pipeline manager, one
+    // per layer is cheap next to its buffers
+    let shader = device.create_shader_module(crate::shaders::PARTICLE_COMPACT_SHADER);
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Particle Compaction Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Particle Compaction Pipeline"),
+        layout: Some(&pipeline_layout),
+        module: &shader,
+        entry_point: Some("cs_main"),
+        compilation_options: wgpu::PipelineCompilationOptions::default(),
+        cache: crate::renderer::pipeline_cache::current().as_deref(),
+    });
+
+    let entries: Vec<_> = buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| wgpu::BindGroupEntry {
+            binding: binding as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &bind_group_layout,
+        entries: &entries,
+        label: Some(&format!("particle_compact_bind_group_{label}")),
+    });
+    (pipeline, bind_group)
+}
+
+// Uniform matching `Sheet` in the particle shader
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SheetUniform {
+    textures: u32,
+    columns: u32,
+    rows: u32,
+    _padding: u32,
+}
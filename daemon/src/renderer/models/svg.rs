@@ -0,0 +1,139 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use common::manifest::Sampling;
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPipeline};
+
+use crate::{
+    asset::{image::ImageTexture, svg::SvgDocument},
+    renderer::{
+        manager::Manager,
+        models::{texture::TextureModelBuilder, ModelBuilder},
+        pipeline::Render,
+    },
+};
+
+/// Textures are rasterised at the output's exact resolution, so mipmaps
+/// would never be sampled
+const SVG_SAMPLING: Sampling = Sampling::Linear;
+
+/// A model that renders an SVG document at the output's resolution
+#[derive(Debug)]
+pub struct SvgModel {
+    /// Parsed document, kept to rasterise again on resize
+    document: SvgDocument,
+    label: String,
+    /// Size in pixels the current texture was rasterised at
+    size: (u32, u32),
+    /// The current rasterisation
+    texture: ImageTexture,
+    /// The render pipeline, shared with texture models
+    render_pipeline: Arc<RenderPipeline>,
+    /// The bind group for the current texture
+    bind_group: Arc<BindGroup>,
+    /// Layout for the bind group
+    bind_group_layout: Arc<BindGroupLayout>,
+}
+
+impl Render for SvgModel {
+    fn pipeline(&self) -> Arc<RenderPipeline> {
+        self.render_pipeline.clone()
+    }
+
+    fn bindgroup(&self) -> Arc<BindGroup> {
+        self.bind_group.clone()
+    }
+
+    fn resize(&mut self, device: &Device, queue: &Queue, width: u32, height: u32, _scale: f32) {
+        if self.size == (width, height) || width == 0 || height == 0 {
+            return;
+        }
+
+        tracing::debug!("Rasterising SVG layer {} at {width}x{height}", self.label);
+        let image = self.document.render(width, height);
+        self.texture =
+            ImageTexture::with_sampling(device, queue, &image, &self.label, SVG_SAMPLING);
+        self.size = (width, height);
+
+        self.bind_group = Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.texture.sampler),
+                },
+            ],
+            label: Some(&format!("svg_bind_group_{}", self.label)),
+        }));
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Builder for SVG models
+pub struct SvgModelBuilder {
+    path: Box<Path>,
+    label: String,
+}
+
+impl SvgModelBuilder {
+    pub fn new(path: impl AsRef<Path>, label: impl Into<String>) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            label: label.into(),
+        }
+    }
+}
+
+impl ModelBuilder for SvgModelBuilder {
+    type Target = SvgModel;
+
+    fn build(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    ) -> Result<Self::Target, String> {
+        let document = SvgDocument::open(&self.path).map_err(|e| e.to_string())?;
+
+        // The output size isn't known until the model is attached to a layer,
+        // start from the document's own size until the first resize
+        let size = document.size();
+        let image = document.render(size.0, size.1);
+        let texture = ImageTexture::with_sampling(device, queue, &image, &self.label, SVG_SAMPLING);
+
+        // Reuse the texture model's pipeline and bind group layout
+        let model = TextureModelBuilder::from_texture(texture.clone(), &self.label).build(
+            device,
+            queue,
+            bindgroup_layout_manager.clone(),
+            pipeline_manager,
+        )?;
+        let bind_group_layout = bindgroup_layout_manager
+            .lock()
+            .unwrap()
+            .get("texture_bind_group_layout")
+            .expect("texture model registers its bind group layout");
+
+        Ok(SvgModel {
+            document,
+            label: self.label.clone(),
+            size,
+            texture,
+            render_pipeline: model.pipeline(),
+            bind_group: model.bindgroup(),
+            bind_group_layout,
+        })
+    }
+}
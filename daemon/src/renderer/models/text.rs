@@ -0,0 +1,358 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ab_glyph::FontArc;
+use common::manifest::TextContent;
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPass, RenderPipeline,
+};
+
+use crate::{
+    asset::{
+        color::srgb_to_linear,
+        font::load_font,
+        text::{GlyphAtlas, GlyphInstance, TextTemplate},
+    },
+    renderer::{
+        manager::Manager,
+        models::{color::parse_color, ModelBuilder},
+        pipeline::Render,
+    },
+};
+
+/// Glyphs the instance buffer holds before it has to grow
+const INITIAL_GLYPH_CAPACITY: usize = 64;
+
+/// A model that draws a line or block of text from a glyph atlas
+#[derive(Debug)]
+pub struct TextModel {
+    content: TextContent,
+    /// Color of the text, linear
+    color: [f32; 4],
+    template: TextTemplate,
+    font: FontArc,
+    atlas: GlyphAtlas,
+    /// Text currently laid out, after filling in the template
+    text: String,
+    /// Output size in physical pixels, zero until the first resize
+    screen_size: (u32, u32),
+    scale: f32,
+    label: String,
+    queue: Queue,
+    instance_buffer: Buffer,
+    instance_capacity: usize,
+    instance_count: u32,
+    uniform_buffer: Buffer,
+    render_pipeline: Arc<RenderPipeline>,
+    bind_group: Arc<BindGroup>,
+}
+
+impl TextModel {
+    /// Lay the current text out again and upload the glyph instances
+    fn relayout(&mut self, device: &Device) {
+        if self.screen_size.0 == 0 || self.screen_size.1 == 0 {
+            return;
+        }
+
+        let (width, height) = (self.screen_size.0 as f32, self.screen_size.1 as f32);
+        let anchor = [
+            self.content.position[0] * width,
+            self.content.position[1] * height,
+        ];
+        let instances = self.atlas.layout(
+            &self.queue,
+            &self.font,
+            &self.text,
+            self.content.size * self.scale,
+            self.content.align,
+            anchor,
+        );
+
+        if instances.len() > self.instance_capacity {
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer =
+                create_instance_buffer(device, &self.label, self.instance_capacity);
+        }
+        self.queue
+            .write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        self.instance_count = instances.len() as u32;
+    }
+}
+
+impl Render for TextModel {
+    fn pipeline(&self) -> Arc<RenderPipeline> {
+        self.render_pipeline.clone()
+    }
+
+    fn bindgroup(&self) -> Arc<BindGroup> {
+        self.bind_group.clone()
+    }
+
+    /// Placeholders change at most once a second
+    fn update_interval(&self) -> Option<Duration> {
+        self.template.is_dynamic().then_some(Duration::from_secs(1))
+    }
+
+    /// Fill in the template on every tick, only laying out again when the
+    /// resulting text changed
+    fn pre_render(&mut self, device: &Device, _dt: Duration) {
+        if !self.template.is_dynamic() {
+            return;
+        }
+
+        let text = self.template.render();
+        if text != self.text {
+            self.text = text;
+            self.relayout(device);
+        }
+    }
+
+    fn resize(&mut self, device: &Device, queue: &Queue, width: u32, height: u32, scale: f32) {
+        if self.screen_size == (width, height) && self.scale == scale {
+            return;
+        }
+
+        self.screen_size = (width, height);
+        self.scale = scale;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[TextUniform::new(self.color, width, height)]),
+        );
+        self.relayout(device);
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        if self.instance_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, Some(&*self.bind_group), &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.instance_count);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Builder for text models
+pub struct TextModelBuilder {
+    content: TextContent,
+    label: String,
+}
+
+impl TextModelBuilder {
+    pub fn new(content: TextContent, label: impl Into<String>) -> Self {
+        Self {
+            content,
+            label: label.into(),
+        }
+    }
+}
+
+impl ModelBuilder for TextModelBuilder {
+    type Target = TextModel;
+
+    fn build(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    ) -> Result<Self::Target, String> {
+        let font = load_font(self.content.font.as_deref())
+            .map_err(|e| format!("Failed to load a font for text layer {}: {e}", self.label))?;
+        // Manifest colors are sRGB-encoded, the shader outputs linear values
+        let [r, g, b, a] = parse_color(&self.content.color)?;
+        let color = [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a];
+        let template = TextTemplate::new(&self.content.text);
+        let atlas = GlyphAtlas::new(device, &self.label);
+
+        let instance_buffer = create_instance_buffer(device, &self.label, INITIAL_GLYPH_CAPACITY);
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("Text Uniform Buffer: {}", self.label)),
+            contents: bytemuck::cast_slice(&[TextUniform::new(color, 1, 1)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Get or create the bind group layout
+        let bind_group_layout =
+            bindgroup_layout_manager
+                .lock()
+                .unwrap()
+                .get_or_init("text_bind_group_layout", || {
+                    Arc::new(
+                        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                            entries: &[
+                                wgpu::BindGroupLayoutEntry {
+                                    binding: 0,
+                                    visibility: wgpu::ShaderStages::FRAGMENT,
+                                    ty: wgpu::BindingType::Texture {
+                                        multisampled: false,
+                                        view_dimension: wgpu::TextureViewDimension::D2,
+                                        sample_type: wgpu::TextureSampleType::Float {
+                                            filterable: true,
+                                        },
+                                    },
+                                    count: None,
+                                },
+                                wgpu::BindGroupLayoutEntry {
+                                    binding: 1,
+                                    visibility: wgpu::ShaderStages::FRAGMENT,
+                                    ty: wgpu::BindingType::Sampler(
+                                        wgpu::SamplerBindingType::Filtering,
+                                    ),
+                                    count: None,
+                                },
+                                wgpu::BindGroupLayoutEntry {
+                                    binding: 2,
+                                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                                    ty: wgpu::BindingType::Buffer {
+                                        ty: wgpu::BufferBindingType::Uniform,
+                                        has_dynamic_offset: false,
+                                        min_binding_size: None,
+                                    },
+                                    count: None,
+                                },
+                            ],
+                            label: Some("text_bind_group_layout"),
+                        }),
+                    )
+                });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Create pipeline if it doesn't exist yet
+        let pipeline = pipeline_manager
+            .lock()
+            .unwrap()
+            .get_or_init("text_render_pipeline", || {
+                let shader = device.create_shader_module(crate::shaders::TEXT_SHADER);
+
+                Arc::new(
+                    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some("Text Render Pipeline"),
+                        layout: Some(&pipeline_layout),
+                        vertex: wgpu::VertexState {
+                            module: &shader,
+                            entry_point: Some("vs_main"),
+                            buffers: &[wgpu::VertexBufferLayout {
+                                array_stride: std::mem::size_of::<GlyphInstance>() as u64,
+                                step_mode: wgpu::VertexStepMode::Instance,
+                                attributes: &wgpu::vertex_attr_array![
+                                    0 => Float32x4,
+                                    1 => Float32x4,
+                                ],
+                            }],
+                            compilation_options: wgpu::PipelineCompilationOptions::default(),
+                        },
+                        fragment: Some(wgpu::FragmentState {
+                            module: &shader,
+                            entry_point: Some("fs_main"),
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                            compilation_options: wgpu::PipelineCompilationOptions::default(),
+                        }),
+                        primitive: wgpu::PrimitiveState {
+                            topology: wgpu::PrimitiveTopology::TriangleList,
+                            strip_index_format: None,
+                            front_face: wgpu::FrontFace::Ccw,
+                            cull_mode: None,
+                            polygon_mode: wgpu::PolygonMode::Fill,
+                            unclipped_depth: false,
+                            conservative: false,
+                        },
+                        depth_stencil: None,
+                        multisample: wgpu::MultisampleState {
+                            count: 1,
+                            mask: !0,
+                            alpha_to_coverage_enabled: false,
+                        },
+                        multiview: None,
+                        cache: crate::renderer::pipeline_cache::current().as_deref(),
+                    }),
+                )
+            });
+
+        // The atlas texture never changes, only its contents, so one bind group is enough
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&atlas.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(&format!("text_bind_group_{}", self.label)),
+        });
+
+        Ok(TextModel {
+            content: self.content.clone(),
+            color,
+            text: template.render(),
+            template,
+            font,
+            atlas,
+            screen_size: (0, 0),
+            scale: 1.0,
+            label: self.label.clone(),
+            queue: queue.clone(),
+            instance_buffer,
+            instance_capacity: INITIAL_GLYPH_CAPACITY,
+            instance_count: 0,
+            uniform_buffer,
+            render_pipeline: pipeline,
+            bind_group: Arc::new(bind_group),
+        })
+    }
+}
+
+fn create_instance_buffer(device: &Device, label: &str, capacity: usize) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(&format!("Glyph Instance Buffer: {label}")),
+        size: (capacity * std::mem::size_of::<GlyphInstance>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// Uniform structure matching the text shader's expected format
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextUniform {
+    screen_size: [f32; 2],
+    _padding: [f32; 2],
+    color: [f32; 4],
+}
+
+impl TextUniform {
+    fn new(color: [f32; 4], width: u32, height: u32) -> Self {
+        Self {
+            screen_size: [width as f32, height as f32],
+            _padding: [0.0; 2],
+            color,
+        }
+    }
+}
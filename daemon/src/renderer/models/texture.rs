@@ -1,5 +1,6 @@
 use std::sync::{Arc, Mutex};
 
+use common::manifest::Sampling;
 use image::DynamicImage;
 use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPipeline};
 
@@ -48,18 +49,43 @@ impl Render for TextureModel {
     }
 }
 
+/// Where the builder gets its texture from
+enum TextureSource {
+    /// Decoded image that still needs to be uploaded
+    Image(DynamicImage),
+    /// Texture already on the GPU, e.g. from the asset cache
+    Texture(ImageTexture),
+}
+
 pub struct TextureModelBuilder {
-    image: DynamicImage,
+    source: TextureSource,
     label: String,
+    sampling: Sampling,
 }
 
 impl TextureModelBuilder {
     pub fn new(image: DynamicImage, label: impl Into<String>) -> Self {
         Self {
-            image,
+            source: TextureSource::Image(image),
             label: label.into(),
+            sampling: Sampling::default(),
         }
     }
+
+    /// Build from an already uploaded texture, sampling is taken from the texture
+    pub fn from_texture(texture: ImageTexture, label: impl Into<String>) -> Self {
+        Self {
+            source: TextureSource::Texture(texture),
+            label: label.into(),
+            sampling: Sampling::default(),
+        }
+    }
+
+    /// Set the texture filtering mode
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
 }
 
 impl ModelBuilder for TextureModelBuilder {
@@ -71,9 +97,14 @@ impl ModelBuilder for TextureModelBuilder {
         queue: &Queue,
         bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
         pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
-    ) -> Self::Target {
+    ) -> Result<Self::Target, String> {
         // Create texture from image using the provided queue
-        let texture = ImageTexture::from_image(device, queue, &self.image, &self.label);
+        let texture = match &self.source {
+            TextureSource::Image(image) => {
+                ImageTexture::with_sampling(device, queue, image, &self.label, self.sampling)
+            }
+            TextureSource::Texture(texture) => texture.clone(),
+        };
 
         // Get or create the bind group layout and pipeline
         let bind_group_layout = bindgroup_layout_manager.lock().unwrap().get_or_init(
@@ -107,60 +138,13 @@ impl ModelBuilder for TextureModelBuilder {
             },
         );
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Texture Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
         // Create pipeline if it doesn't exist yet
-        let pipeline =
-            pipeline_manager
-                .lock()
-                .unwrap()
-                .get_or_init("texture_render_pipeline", || {
-                    let shader = device.create_shader_module(crate::shaders::TEXTURE_SHADER);
-
-                    Arc::new(
-                        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                            label: Some("Texture Render Pipeline"),
-                            layout: Some(&pipeline_layout),
-                            vertex: wgpu::VertexState {
-                                module: &shader,
-                                entry_point: Some("vs_main"),
-                                buffers: &[],
-                                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                            },
-                            fragment: Some(wgpu::FragmentState {
-                                module: &shader,
-                                entry_point: Some("fs_main"),
-                                targets: &[Some(wgpu::ColorTargetState {
-                                    format: wgpu::TextureFormat::Bgra8UnormSrgb, // Use your preferred format
-                                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                                    write_mask: wgpu::ColorWrites::ALL,
-                                })],
-                                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                            }),
-                            primitive: wgpu::PrimitiveState {
-                                topology: wgpu::PrimitiveTopology::TriangleList,
-                                strip_index_format: None,
-                                front_face: wgpu::FrontFace::Ccw,
-                                cull_mode: None,
-                                polygon_mode: wgpu::PolygonMode::Fill,
-                                unclipped_depth: false,
-                                conservative: false,
-                            },
-                            depth_stencil: None,
-                            multisample: wgpu::MultisampleState {
-                                count: 1,
-                                mask: !0,
-                                alpha_to_coverage_enabled: false,
-                            },
-                            multiview: None,
-                            cache: None,
-                        }),
-                    )
-                });
+        let pipeline = pipeline_manager
+            .lock()
+            .unwrap()
+            .get_or_init("texture_render_pipeline", || {
+                Arc::new(create_pipeline(device, &bind_group_layout, "fs_main"))
+            });
 
         // Create bind group
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -178,6 +162,63 @@ impl ModelBuilder for TextureModelBuilder {
             label: Some(&format!("texture_bind_group_{}", self.label)),
         });
 
-        TextureModel::new(texture, pipeline.clone(), Arc::new(bind_group))
+        Ok(TextureModel::new(
+            texture,
+            pipeline.clone(),
+            Arc::new(bind_group),
+        ))
     }
 }
+
+/// Create the pipeline drawing a texture over the whole target with the
+/// fragment shader `entry_point` of the texture shader
+pub fn create_pipeline(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    entry_point: &str,
+) -> RenderPipeline {
+    let shader = device.create_shader_module(crate::shaders::TEXTURE_SHADER);
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Texture Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Texture Render Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some(entry_point),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Bgra8UnormSrgb, // Use your preferred format
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: crate::renderer::pipeline_cache::current().as_deref(),
+    })
+}
@@ -0,0 +1,239 @@
+use std::sync::{Arc, Mutex};
+
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPass, RenderPipeline,
+};
+
+use crate::{
+    asset::image::ImageTexture,
+    renderer::{manager::Manager, models::ModelBuilder, pipeline::Render},
+};
+
+/// A texture repeated over the output pixel for pixel from its top left
+/// corner, for `ScaleMode::Tile`
+///
+/// All tiles are instances of one quad, placed by the vertex shader, so the
+/// draw costs six vertices a tile whatever the texture's size.
+#[derive(Debug)]
+pub struct TileModel {
+    texture: ImageTexture,
+    render_pipeline: Arc<RenderPipeline>,
+    bind_group: Arc<BindGroup>,
+    uniform_buffer: Buffer,
+    /// Tiles covering the output, none until its size is known
+    instances: u32,
+}
+
+impl Render for TileModel {
+    fn pipeline(&self) -> Arc<RenderPipeline> {
+        self.render_pipeline.clone()
+    }
+
+    fn bindgroup(&self) -> Arc<BindGroup> {
+        self.bind_group.clone()
+    }
+
+    fn resize(&mut self, _device: &Device, queue: &Queue, width: u32, height: u32, _scale: f32) {
+        let size = self.texture.texture.size();
+        let columns = width.div_ceil(size.width);
+        let rows = height.div_ceil(size.height);
+        let uniform = TileUniform {
+            size: [
+                2.0 * size.width as f32 / width as f32,
+                2.0 * size.height as f32 / height as f32,
+            ],
+            columns,
+            _padding: 0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.instances = columns * rows;
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        if self.instances == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, Some(&*self.bind_group), &[]);
+        render_pass.draw(0..6, 0..self.instances);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Builds a model tiling an uploaded texture
+pub struct TileModelBuilder {
+    texture: ImageTexture,
+    label: String,
+}
+
+impl TileModelBuilder {
+    pub fn from_texture(texture: ImageTexture, label: impl Into<String>) -> Self {
+        Self {
+            texture,
+            label: label.into(),
+        }
+    }
+}
+
+impl ModelBuilder for TileModelBuilder {
+    type Target = TileModel;
+
+    fn build(
+        &self,
+        device: &Device,
+        _queue: &Queue,
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    ) -> Result<Self::Target, String> {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("Tile Buffer: {}", self.label)),
+            contents: bytemuck::cast_slice(&[TileUniform {
+                size: [2.0, 2.0],
+                columns: 1,
+                _padding: 0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout =
+            bindgroup_layout_manager
+                .lock()
+                .unwrap()
+                .get_or_init("tile_bind_group_layout", || {
+                    Arc::new(
+                        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                            entries: &[
+                                wgpu::BindGroupLayoutEntry {
+                                    binding: 0,
+                                    visibility: wgpu::ShaderStages::FRAGMENT,
+                                    ty: wgpu::BindingType::Texture {
+                                        multisampled: false,
+                                        view_dimension: wgpu::TextureViewDimension::D2,
+                                        sample_type: wgpu::TextureSampleType::Float {
+                                            filterable: true,
+                                        },
+                                    },
+                                    count: None,
+                                },
+                                wgpu::BindGroupLayoutEntry {
+                                    binding: 1,
+                                    visibility: wgpu::ShaderStages::FRAGMENT,
+                                    ty: wgpu::BindingType::Sampler(
+                                        wgpu::SamplerBindingType::Filtering,
+                                    ),
+                                    count: None,
+                                },
+                                wgpu::BindGroupLayoutEntry {
+                                    binding: 2,
+                                    visibility: wgpu::ShaderStages::VERTEX,
+                                    ty: wgpu::BindingType::Buffer {
+                                        ty: wgpu::BufferBindingType::Uniform,
+                                        has_dynamic_offset: false,
+                                        min_binding_size: None,
+                                    },
+                                    count: None,
+                                },
+                            ],
+                            label: Some("tile_bind_group_layout"),
+                        }),
+                    )
+                });
+
+        let pipeline = pipeline_manager
+            .lock()
+            .unwrap()
+            .get_or_init("tile_render_pipeline", || {
+                let shader = device.create_shader_module(crate::shaders::TEXTURE_SHADER);
+                let pipeline_layout =
+                    device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some("Tile Pipeline Layout"),
+                        bind_group_layouts: &[&bind_group_layout],
+                        push_constant_ranges: &[],
+                    });
+
+                Arc::new(
+                    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some("Tile Render Pipeline"),
+                        layout: Some(&pipeline_layout),
+                        vertex: wgpu::VertexState {
+                            module: &shader,
+                            entry_point: Some("vs_tile"),
+                            buffers: &[],
+                            compilation_options: wgpu::PipelineCompilationOptions::default(),
+                        },
+                        fragment: Some(wgpu::FragmentState {
+                            module: &shader,
+                            entry_point: Some("fs_main"),
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                            compilation_options: wgpu::PipelineCompilationOptions::default(),
+                        }),
+                        primitive: wgpu::PrimitiveState {
+                            topology: wgpu::PrimitiveTopology::TriangleList,
+                            strip_index_format: None,
+                            front_face: wgpu::FrontFace::Ccw,
+                            cull_mode: None,
+                            polygon_mode: wgpu::PolygonMode::Fill,
+                            unclipped_depth: false,
+                            conservative: false,
+                        },
+                        depth_stencil: None,
+                        multisample: wgpu::MultisampleState {
+                            count: 1,
+                            mask: !0,
+                            alpha_to_coverage_enabled: false,
+                        },
+                        multiview: None,
+                        cache: crate::renderer::pipeline_cache::current().as_deref(),
+                    }),
+                )
+            });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(&format!("tile_bind_group_{}", self.label)),
+        });
+
+        Ok(TileModel {
+            texture: self.texture.clone(),
+            render_pipeline: pipeline,
+            bind_group: Arc::new(bind_group),
+            uniform_buffer,
+            instances: 0,
+        })
+    }
+}
+
+// Uniform matching `Tiles` in the texture shader
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TileUniform {
+    /// Size of a tile in clip space
+    size: [f32; 2],
+    columns: u32,
+    _padding: u32,
+}
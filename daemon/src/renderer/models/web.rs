@@ -0,0 +1,201 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use common::manifest::{Sampling, WebContent};
+use image::{DynamicImage, RgbaImage};
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPipeline};
+
+use crate::{
+    asset::image::ImageTexture,
+    renderer::{
+        manager::Manager,
+        models::{texture::TextureModelBuilder, ModelBuilder},
+        pipeline::{PointerInput, Render},
+        web::WebView,
+    },
+};
+
+/// Frames match the output's resolution, mipmaps would be regenerated for
+/// every frame
+const WEB_SAMPLING: Sampling = Sampling::Linear;
+
+/// A model that shows a web page, see `WebView`
+#[derive(Debug)]
+pub struct WebModel {
+    /// None if the browser could not be started
+    view: Option<WebView>,
+    label: String,
+    queue: Queue,
+    /// Serial of the frame in `texture`, 0 before the first one arrived
+    serial: u64,
+    /// The latest frame
+    texture: ImageTexture,
+    /// The render pipeline, shared with texture models
+    render_pipeline: Arc<RenderPipeline>,
+    /// The bind group for the current texture
+    bind_group: Arc<BindGroup>,
+    /// Layout for the bind group
+    bind_group_layout: Arc<BindGroupLayout>,
+}
+
+impl WebModel {
+    /// Upload a frame, replacing the texture if the page changed size
+    fn upload(&mut self, device: &Device, frame: &RgbaImage) {
+        let size = self.texture.texture.size();
+        if (size.width, size.height) == frame.dimensions() {
+            self.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &self.texture.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                frame,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * frame.width()),
+                    rows_per_image: Some(frame.height()),
+                },
+                size,
+            );
+            return;
+        }
+
+        self.texture = ImageTexture::with_sampling(
+            device,
+            &self.queue,
+            &DynamicImage::ImageRgba8(frame.clone()),
+            &self.label,
+            WEB_SAMPLING,
+        );
+        self.bind_group = Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.texture.sampler),
+                },
+            ],
+            label: Some(&format!("web_bind_group_{}", self.label)),
+        }));
+    }
+}
+
+impl Render for WebModel {
+    fn pipeline(&self) -> Arc<RenderPipeline> {
+        self.render_pipeline.clone()
+    }
+
+    fn bindgroup(&self) -> Arc<BindGroup> {
+        self.bind_group.clone()
+    }
+
+    fn update_interval(&self) -> Option<Duration> {
+        self.view.is_some().then_some(Duration::ZERO)
+    }
+
+    /// Show the latest frame if the page changed since the last update
+    fn pre_render(&mut self, device: &Device, _dt: Duration) {
+        let Some(view) = &self.view else {
+            return;
+        };
+        if let Some((serial, frame)) = view.frame_since(self.serial) {
+            self.upload(device, &frame);
+            self.serial = serial;
+        }
+    }
+
+    /// Lay the page out at the output's size
+    fn resize(&mut self, _device: &Device, _queue: &Queue, width: u32, height: u32, scale: f32) {
+        if let Some(view) = &self.view {
+            view.resize(width, height, scale);
+        }
+    }
+
+    fn pointer(&mut self, input: &PointerInput) {
+        if let Some(view) = &self.view {
+            view.pointer(input);
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Builder for web models
+pub struct WebModelBuilder {
+    url: String,
+    content: WebContent,
+    label: String,
+}
+
+impl WebModelBuilder {
+    pub fn new(url: impl Into<String>, content: WebContent, label: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            content,
+            label: label.into(),
+        }
+    }
+}
+
+impl ModelBuilder for WebModelBuilder {
+    type Target = WebModel;
+
+    fn build(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    ) -> Result<Self::Target, String> {
+        // The layer stays transparent without a browser
+        let view = WebView::open(&self.url, &self.content)
+            .inspect_err(|e| tracing::error!("Web layer {} is not shown: {e}", self.label))
+            .ok();
+
+        // Transparent until the first frame arrives
+        let texture = ImageTexture::with_sampling(
+            device,
+            queue,
+            &DynamicImage::new_rgba8(1, 1),
+            &self.label,
+            WEB_SAMPLING,
+        );
+
+        // Reuse the texture model's pipeline and bind group layout
+        let model = TextureModelBuilder::from_texture(texture.clone(), &self.label).build(
+            device,
+            queue,
+            bindgroup_layout_manager.clone(),
+            pipeline_manager,
+        )?;
+        let bind_group_layout = bindgroup_layout_manager
+            .lock()
+            .unwrap()
+            .get("texture_bind_group_layout")
+            .expect("texture model registers its bind group layout");
+
+        Ok(WebModel {
+            view,
+            label: self.label.clone(),
+            queue: queue.clone(),
+            serial: 0,
+            texture,
+            render_pipeline: model.pipeline(),
+            bind_group: model.bindgroup(),
+            bind_group_layout,
+        })
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use common::manifest::EffectInput;
+use image::DynamicImage;
+use wgpu::{BindGroupLayout, Device, Queue, RenderPipeline};
+
+use super::{
+    manager::Manager,
+    models::{
+        effect::{EffectModel, EffectModelBuilder},
+        ModelBuilder,
+    },
+    pipeline::{Pipelines, Render},
+};
+
+/// Label of the overview model in the pipelines, parenthesized so it does not
+/// pass for a layer of the manifest in diagnostics
+pub const OVERVIEW_LABEL: &str = "(overview)";
+
+/// How long the overview takes to fade in or out
+const FADE: Duration = Duration::from_millis(250);
+
+/// Blur and dim faded in over a layer's wallpaper while the compositor shows
+/// an overview or launcher, see `Client::set_overview`
+///
+/// The effect is added to the wallpaper's pipelines while it shows and taken
+/// out once it faded out, so the wallpaper pays nothing for it otherwise. A
+/// wallpaper swapped in while it shows gets it on its first frame.
+pub struct Overview {
+    enabled: bool,
+    /// How far the effect faded in, 0 to 1
+    amount: f32,
+    /// Time of the previous fade step, None while not fading
+    last_step: Option<Instant>,
+    bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+    pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+}
+
+impl Overview {
+    pub fn new(
+        bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
+        pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
+    ) -> Self {
+        Self {
+            enabled: false,
+            amount: 0.0,
+            last_step: None,
+            bindgroup_layout_manager,
+            pipeline_manager,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether the effect is still fading in or out, the layer is redrawn on
+    /// every frame until it settled
+    pub fn is_fading(&self) -> bool {
+        self.amount != if self.enabled { 1.0 } else { 0.0 }
+    }
+
+    /// Start fading in or out, returns whether it changed
+    pub fn set_enabled(&mut self, enabled: bool) -> bool {
+        if self.enabled == enabled {
+            return false;
+        }
+        self.enabled = enabled;
+        true
+    }
+
+    /// Advance the fade and add, update or remove the effect in `pipelines`,
+    /// which render at `width` by `height` pixels, returns whether the
+    /// wallpaper needs to be redrawn
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        pipelines: &mut Pipelines,
+        (width, height, scale): (u32, u32, f32),
+    ) -> bool {
+        let target = if self.enabled { 1.0 } else { 0.0 };
+        let faded = self.amount != target;
+        if faded {
+            let now = Instant::now();
+            let dt = self.last_step.map_or(Duration::ZERO, |last| now - last);
+            self.last_step = Some(now);
+            let step = dt.as_secs_f32() / FADE.as_secs_f32();
+            self.amount = if target > self.amount {
+                (self.amount + step).min(target)
+            } else {
+                (self.amount - step).max(target)
+            };
+        } else {
+            self.last_step = None;
+        }
+
+        let present = pipelines.model_mut(OVERVIEW_LABEL).is_some();
+        if self.amount == 0.0 {
+            return pipelines.remove_model(OVERVIEW_LABEL) || faded;
+        }
+        if !present {
+            let mut model = self.build(device, queue, pipelines.gpu_label(OVERVIEW_LABEL));
+            model.resize(device, queue, width, height, scale);
+            pipelines.push(OVERVIEW_LABEL, model);
+            pipelines.resize_backdrop(device, width, height);
+        }
+
+        if let Some(effect) = pipelines
+            .model_mut(OVERVIEW_LABEL)
+            .and_then(|model| model.as_any().downcast_ref::<EffectModel>())
+        {
+            // Eased so the blur settles instead of stopping abruptly
+            let amount = self.amount;
+            effect.set_strength(queue, amount * amount * (3.0 - 2.0 * amount));
+        }
+        faded || !present
+    }
+
+    fn build(&self, device: &Device, queue: &Queue, label: String) -> EffectModel {
+        EffectModelBuilder::new(
+            DynamicImage::new_rgba8(1, 1),
+            crate::shaders::OVERVIEW_EFFECT_SHADER,
+            label,
+        )
+        .with_input(EffectInput::Below)
+        .with_passes(crate::shaders::OVERVIEW_EFFECT_PASSES)
+        .with_opacity(0.0)
+        .build(
+            device,
+            queue,
+            self.bindgroup_layout_manager.clone(),
+            self.pipeline_manager.clone(),
+        )
+        .expect("the overview blur has no assets to load")
+    }
+}
@@ -1,23 +1,78 @@
 use std::{
     ops::{Deref, DerefMut},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 
-use common::{manifest::ShaderType, wallpaper::Wallpaper};
-use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPipeline};
+use common::{
+    manifest::{EffectInput, Environment, ScaleMode, Screen, ShaderType},
+    wallpaper::Wallpaper,
+};
+use image::DynamicImage;
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, Device, Queue, RenderPass, RenderPipeline,
+    TextureView,
+};
 
+use crate::asset::color::load_image;
+use crate::asset::{
+    animated::{self, AnimatedTexture},
+    baked,
+    image::ImageTexture,
+};
 use crate::renderer::{
+    cache::AssetCache,
+    capture::Captures,
     manager::Manager,
+    memory,
     models::{
-        animated_texture::AnimatedTextureModelBuilder, color::ColorModelBuilder,
-        texture::TextureModelBuilder, ModelBuilder,
+        animated_texture::AnimatedTextureModelBuilder, capture::CaptureModelBuilder,
+        color::ColorModelBuilder, gradient::GradientModelBuilder, lut::LutModelBuilder,
+        particle::ParticleModelBuilder, svg::SvgModelBuilder, text::TextModelBuilder,
+        texture::TextureModelBuilder, tile::TileModelBuilder, ModelBuilder,
     },
+    prescale,
+    render_target::create_backdrop,
 };
+use crate::{colors, weather};
 
-use super::models::effect::EffectModelBuilder;
+#[cfg(feature = "mpris")]
+use super::models::album_art::AlbumArtModelBuilder;
+use super::models::effect::{EffectModelBuilder, SINGLE_PASS};
+#[cfg(feature = "web")]
+use super::models::web::WebModelBuilder;
 
-pub trait Render: std::fmt::Debug + std::any::Any {
+/// Pointer input over a layer's surface, positions in logical pixels
+#[derive(Debug, Clone, Copy)]
+pub enum PointerInput {
+    Motion {
+        x: f64,
+        y: f64,
+    },
+    /// `button` is a Linux input event code, like `BTN_LEFT`
+    Press {
+        x: f64,
+        y: f64,
+        button: u32,
+    },
+    Release {
+        x: f64,
+        y: f64,
+        button: u32,
+    },
+    /// Scroll distance in logical pixels
+    Scroll {
+        x: f64,
+        y: f64,
+        dx: f64,
+        dy: f64,
+    },
+}
+
+pub trait Render: std::fmt::Debug + std::any::Any + Send {
     fn pipeline(&self) -> Arc<RenderPipeline>;
     fn bindgroup(&self) -> Arc<BindGroup>;
 
@@ -26,6 +81,59 @@ pub trait Render: std::fmt::Debug + std::any::Any {
         // Default implementation does nothing
     }
 
+    /// How often the model changes on its own and `pre_render` should run,
+    /// zero for every tick of the wallpaper, None if it only changes when
+    /// the wallpaper is damaged
+    fn update_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called when the output's size in physical pixels or its scale changes,
+    /// models that render at output resolution regenerate their textures here
+    fn resize(&mut self, _device: &Device, _queue: &Queue, _width: u32, _height: u32, _scale: f32) {
+    }
+
+    /// Called with pointer input over the output, for models that forward it
+    fn pointer(&mut self, _input: &PointerInput) {}
+
+    /// Record the draw calls for this model, by default a full-screen quad
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        render_pass.set_pipeline(&self.pipeline());
+        render_pass.set_bind_group(0, Some(&*self.bindgroup()), &[]);
+        render_pass.draw(0..6, 0..1); // Draw full-screen quad (6 vertices)
+    }
+
+    /// Whether the model samples the layers drawn before it instead of its own texture
+    fn reads_backdrop(&self) -> bool {
+        false
+    }
+
+    /// Called when the backdrop is recreated, models that read it rebuild
+    /// their bind groups here
+    fn bind_backdrop(&mut self, _device: &Device, _backdrop: &TextureView) {}
+
+    /// Whether the model renders offscreen before drawing, see `encode_passes`
+    fn has_passes(&self) -> bool {
+        false
+    }
+
+    /// Record the passes the model renders into its own textures before it
+    /// draws, outside of the layer's render pass
+    fn encode_passes(&mut self, _encoder: &mut CommandEncoder) {}
+
+    /// What keeps the model from drawing as the wallpaper meant, like a
+    /// script stopped for going over its budget, shown by `wlrs query`
+    fn warning(&self) -> Option<String> {
+        None
+    }
+
+    /// Short name of the model type, like `Texture` for `TextureModel`
+    fn kind(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        let name = name.rsplit("::").next().unwrap_or(name);
+        name.strip_suffix("Model").unwrap_or(name)
+    }
+
     /// Downcast to Any for runtime type checking
     fn as_any(&self) -> &dyn std::any::Any;
 
@@ -33,95 +141,365 @@ pub trait Render: std::fmt::Debug + std::any::Any {
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
 }
 
-#[derive(Default)]
+/// Source of `Pipelines::id`
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 pub struct Pipelines {
     pub data: Vec<Box<dyn Render>>,
+    /// Name of the manifest layer each model draws, used in diagnostics
+    labels: Vec<String>,
+    /// Copy of the layers below a model that reads them, see `Render::reads_backdrop`
+    backdrop: Option<TextureView>,
+    /// Tells the GPU resources of these pipelines apart, see `gpu_label`
+    id: u64,
+}
+
+impl Default for Pipelines {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Pipelines {
     pub fn new() -> Self {
-        Self { data: Vec::new() }
+        Self::with_capacity(0)
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             data: Vec::with_capacity(capacity),
+            labels: Vec::with_capacity(capacity),
+            backdrop: None,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
         }
     }
 
+    /// Label of the GPU resources of the layer named `name`
+    ///
+    /// Other outputs and loads show layers of the same name, the label is
+    /// unique to these pipelines so a GPU error naming it is theirs.
+    pub fn gpu_label(&self, name: &str) -> String {
+        format!("{name}#{}", self.id)
+    }
+
+    /// Add a model drawing the manifest layer named `label`
+    pub fn push(&mut self, label: &str, model: impl Render) {
+        self.data.push(Box::new(model));
+        self.labels.push(label.to_string());
+    }
+
+    /// Names of the manifest layers, in drawing order
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.labels.iter().map(String::as_str)
+    }
+
+    /// Shortest interval any model changes at on its own, see
+    /// `Render::update_interval`, None for a still wallpaper
+    pub fn update_interval(&self) -> Option<Duration> {
+        self.data
+            .iter()
+            .filter_map(|model| model.update_interval())
+            .min()
+    }
+
+    /// Texture the layers below a backdrop reader are copied into before it draws
+    pub fn backdrop(&self) -> Option<&TextureView> {
+        self.backdrop.as_ref()
+    }
+
+    /// Create the backdrop at the size the models render at and hand it to
+    /// the models that read it, if there are any
+    pub fn resize_backdrop(&mut self, device: &Device, width: u32, height: u32) {
+        if !self.data.iter().any(|model| model.reads_backdrop()) {
+            return;
+        }
+        let backdrop = create_backdrop(device, width, height);
+        for model in self.data.iter_mut().filter(|model| model.reads_backdrop()) {
+            model.bind_backdrop(device, &backdrop);
+        }
+        self.backdrop = Some(backdrop);
+    }
+
+    /// Model drawing the layer named `label`
+    pub fn model_mut(&mut self, label: &str) -> Option<&mut Box<dyn Render>> {
+        let index = self.labels.iter().position(|other| other == label)?;
+        Some(&mut self.data[index])
+    }
+
+    /// Remove the model drawing `label`, e.g. one added at runtime over the
+    /// wallpaper, returns whether there was one
+    ///
+    /// The backdrop is dropped along with the last model reading it.
+    pub fn remove_model(&mut self, label: &str) -> bool {
+        let Some(index) = self.labels.iter().position(|other| other == label) else {
+            return false;
+        };
+        self.data.remove(index);
+        self.labels.remove(index);
+        if !self.data.iter().any(|model| model.reads_backdrop()) {
+            self.backdrop = None;
+        }
+        true
+    }
+
     pub fn empty(&self) -> bool {
         self.data.is_empty()
     }
 
+    /// Build the models of the layers `wallpaper` shows on `screen`
+    ///
+    /// Stops before the next layer once `cancelled` is set, the caller
+    /// throws away what was built so far.
+    #[allow(clippy::too_many_arguments)]
     pub fn from(
         wallpaper: Wallpaper,
+        screen: Option<Screen>,
         device: &Device,
         queue: &Queue,
         bindgroup_layout_manager: Arc<Mutex<Manager<BindGroupLayout>>>,
         pipeline_manager: Arc<Mutex<Manager<RenderPipeline>>>,
-    ) -> Self {
+        asset_cache: Arc<Mutex<AssetCache>>,
+        captures: &Captures,
+        target: Option<(u32, u32)>,
+        cancelled: &AtomicBool,
+    ) -> Result<Self, String> {
         let mut pipelines = Self::new();
 
-        // Process all render layers in proper order
-        let render_layers = wallpaper.get_layers();
+        // Process the layers shown in the current circumstances in proper order
+        let environment = Environment {
+            weather: weather::current(),
+            colors: colors::current(),
+            screen,
+        };
+        let render_layers = wallpaper
+            .get_layers_in(&environment)
+            .map_err(|e| e.to_string())?;
 
         for render_layer in render_layers {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+            let label = pipelines.gpu_label(&render_layer.name);
             match &render_layer.layer_type {
                 common::wallpaper::LayerType::Color { color } => {
                     // Create color model
-                    let color_model = ColorModelBuilder::from_hex_color(color, &render_layer.name)
-                        .build(
-                            device,
-                            queue,
-                            bindgroup_layout_manager.clone(),
-                            pipeline_manager.clone(),
-                        );
-                    pipelines.data.push(Box::new(color_model));
+                    let color_model = ColorModelBuilder::from_css_color(color, &label)?.build(
+                        device,
+                        queue,
+                        bindgroup_layout_manager.clone(),
+                        pipeline_manager.clone(),
+                    )?;
+                    pipelines.push(&render_layer.name, color_model);
                 }
                 common::wallpaper::LayerType::Image { image_path } => {
-                    // Check if the image is potentially animated based on extension
-                    let path_str = image_path.to_string_lossy().to_lowercase();
-                    if path_str.ends_with(".webp") || path_str.ends_with(".gif") {
-                        // Try to load as an animated texture
-                        let model =
-                            AnimatedTextureModelBuilder::new(image_path, &render_layer.name)
-                                .looping(true)
-                                .build(
+                    // Check if the image is animated (GIF, animated WebP or APNG)
+                    if animated::is_animated(image_path) {
+                        // Stream it, sharing the decoder with other outputs
+                        let texture = AssetCache::animation(&asset_cache, image_path, || {
+                            AnimatedTexture::from_path(device, queue, image_path, &label, true)
+                                .map_err(|e| {
+                                    format!("Failed to load {}: {e}", image_path.display())
+                                })
+                        })?;
+                        let model = AnimatedTextureModelBuilder::from_texture(texture, &label)
+                            .looping(true)
+                            .build(
+                                device,
+                                queue,
+                                bindgroup_layout_manager.clone(),
+                                pipeline_manager.clone(),
+                            )?;
+                        {
+                            pipelines.push(&render_layer.name, model);
+                        }
+                    } else {
+                        // Load regular static image, reusing it if another output has it
+                        let texture = AssetCache::image(
+                            &asset_cache,
+                            image_path,
+                            render_layer.sampling,
+                            target,
+                            || {
+                                if let Some(texture) = baked::load(
                                     device,
                                     queue,
-                                    bindgroup_layout_manager.clone(),
-                                    pipeline_manager.clone(),
+                                    image_path,
+                                    &label,
+                                    render_layer.sampling,
+                                    wallpaper.scale_mode(),
+                                    target,
+                                ) {
+                                    return Ok(texture);
+                                }
+                                let image = load_image(image_path).map_err(|e| e.to_string())?;
+                                let image = prescale::prescale(
+                                    image,
+                                    wallpaper.scale_mode(),
+                                    target,
+                                    image_path,
                                 );
-                        {
-                            pipelines.data.push(Box::new(model));
+                                let image = memory::fit_image(
+                                    image,
+                                    render_layer.sampling,
+                                    &asset_cache,
+                                    image_path,
+                                )?;
+                                Ok(ImageTexture::with_sampling(
+                                    device,
+                                    queue,
+                                    &image,
+                                    &label,
+                                    render_layer.sampling,
+                                ))
+                            },
+                        )?;
+
+                        // Add the image layer, as instances of one quad
+                        // when tiled
+                        if *wallpaper.scale_mode() == ScaleMode::Tile {
+                            let tiles = TileModelBuilder::from_texture(texture, &label).build(
+                                device,
+                                queue,
+                                bindgroup_layout_manager.clone(),
+                                pipeline_manager.clone(),
+                            )?;
+                            pipelines.push(&render_layer.name, tiles);
+                            continue;
                         }
-                    } else {
-                        // Load regular static image
-                        let image = image::ImageReader::open(image_path)
-                            .unwrap()
-                            .decode()
-                            .unwrap();
-
-                        // Add the image layer
-                        let texture = TextureModelBuilder::new(image, &render_layer.name).build(
+                        let texture = TextureModelBuilder::from_texture(texture, &label).build(
                             device,
                             queue,
                             bindgroup_layout_manager.clone(),
                             pipeline_manager.clone(),
+                        )?;
+                        pipelines.push(&render_layer.name, texture);
+                    }
+                }
+                common::wallpaper::LayerType::Svg { svg_path } => {
+                    // Rasterised at the intrinsic size for now, the layer
+                    // resizes it to the output once the pipelines are attached
+                    let model = SvgModelBuilder::new(svg_path, &label).build(
+                        device,
+                        queue,
+                        bindgroup_layout_manager.clone(),
+                        pipeline_manager.clone(),
+                    )?;
+                    pipelines.push(&render_layer.name, model);
+                }
+                common::wallpaper::LayerType::Text { content } => {
+                    // Laid out once the layer knows the output size
+                    let model = TextModelBuilder::new(content.clone(), &label).build(
+                        device,
+                        queue,
+                        bindgroup_layout_manager.clone(),
+                        pipeline_manager.clone(),
+                    )?;
+                    pipelines.push(&render_layer.name, model);
+                }
+                common::wallpaper::LayerType::Gradient { content } => {
+                    let model = GradientModelBuilder::new(content.clone(), &label).build(
+                        device,
+                        queue,
+                        bindgroup_layout_manager.clone(),
+                        pipeline_manager.clone(),
+                    )?;
+                    pipelines.push(&render_layer.name, model);
+                }
+                common::wallpaper::LayerType::Capture { content } => {
+                    // The output may still come up without the command
+                    let mut content = content.clone();
+                    if content.command.is_some()
+                        && !wallpaper
+                            .permissions
+                            .contains(&common::manifest::Permission::Process)
+                    {
+                        tracing::warn!(
+                            "Capture layer {} does not start its command until the process \
+                             permission is granted",
+                            render_layer.name
                         );
-                        pipelines.data.push(Box::new(texture));
+                        content.command = None;
                     }
+
+                    // Transparent until the client receives the first frame
+                    let model = CaptureModelBuilder::new(content, captures.clone(), &label).build(
+                        device,
+                        queue,
+                        bindgroup_layout_manager.clone(),
+                        pipeline_manager.clone(),
+                    )?;
+                    pipelines.push(&render_layer.name, model);
+                }
+                #[cfg(feature = "web")]
+                common::wallpaper::LayerType::Web { .. }
+                    if !wallpaper
+                        .permissions
+                        .contains(&common::manifest::Permission::Web) =>
+                {
+                    tracing::warn!(
+                        "Web layer {} is not shown until the web permission is granted",
+                        render_layer.name
+                    );
+                }
+                #[cfg(feature = "web")]
+                common::wallpaper::LayerType::Web { url, content } => {
+                    // Transparent until the browser sends the first frame
+                    let model = WebModelBuilder::new(url, content.clone(), &label).build(
+                        device,
+                        queue,
+                        bindgroup_layout_manager.clone(),
+                        pipeline_manager.clone(),
+                    )?;
+                    pipelines.push(&render_layer.name, model);
+                }
+                #[cfg(not(feature = "web"))]
+                common::wallpaper::LayerType::Web { .. } => {
+                    tracing::error!(
+                        "Web layer {} needs a build with the `web` feature",
+                        render_layer.name
+                    );
+                }
+                #[cfg(feature = "mpris")]
+                common::wallpaper::LayerType::AlbumArt { fallback_path, fit } => {
+                    // Fitted to the output on the first resize
+                    let model = AlbumArtModelBuilder::new(fallback_path.clone(), *fit, &label)
+                        .build(
+                            device,
+                            queue,
+                            bindgroup_layout_manager.clone(),
+                            pipeline_manager.clone(),
+                        )?;
+                    pipelines.push(&render_layer.name, model);
+                }
+                #[cfg(not(feature = "mpris"))]
+                common::wallpaper::LayerType::AlbumArt { .. } => {
+                    tracing::error!(
+                        "Album art layer {} needs a build with the `mpris` feature",
+                        render_layer.name
+                    );
                 }
                 common::wallpaper::LayerType::Particle {
                     image_path,
                     script_path,
                     params,
+                    emitter,
+                    sprites,
                 } => {
-                    // Load particle image
-                    let image = image::ImageReader::open(image_path)
-                        .unwrap()
-                        .decode()
-                        .unwrap();
+                    // Plain squares without an image, colored by the emitter
+                    let mut textures = std::iter::once(image_path)
+                        .filter(|path| !path.as_os_str().is_empty())
+                        .chain(&sprites.textures)
+                        .map(|path| load_image(path).map_err(|e| e.to_string()))
+                        .collect::<Result<Vec<_>, String>>()?
+                        .into_iter();
+                    let image = textures.next().unwrap_or_else(|| {
+                        DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                            1,
+                            1,
+                            image::Rgba([255; 4]),
+                        ))
+                    });
+                    let textures = textures.collect();
 
                     // Get max particles from params or use default
                     let max_particles = params
@@ -129,36 +507,101 @@ impl Pipelines {
                         .and_then(|v| v.as_integer())
                         .unwrap_or(1000) as u32;
 
-                    // TODO: Implement particle system
-                    let _ = image;
-                    let _ = max_particles;
-                    let _ = script_path;
-
-                    // For now, just add the image as a texture
-                    let texture = TextureModelBuilder::new(image, &render_layer.name).build(
+                    let model = ParticleModelBuilder::new(
+                        image,
+                        max_particles,
+                        script_path
+                            .as_ref()
+                            .map(|path| path.to_string_lossy().into_owned()),
+                        emitter.clone(),
+                        wallpaper.permissions.clone(),
+                        &label,
+                    )
+                    .with_sprites(textures, sprites.clone())
+                    .build(
                         device,
                         queue,
                         bindgroup_layout_manager.clone(),
                         pipeline_manager.clone(),
-                    );
-                    pipelines.data.push(Box::new(texture));
+                    )?;
+                    pipelines.push(&render_layer.name, model);
+                }
+                common::wallpaper::LayerType::ColorGrade {
+                    image_path,
+                    input,
+                    grade,
+                } => {
+                    // Like other effects reading the layers below, the
+                    // backdrop is bound once the output size is known
+                    let image = match (input, image_path) {
+                        (EffectInput::Image, Some(path)) => {
+                            load_image(path).map_err(|e| e.to_string())?
+                        }
+                        _ => DynamicImage::new_rgba8(1, 1),
+                    };
+                    let model = LutModelBuilder::new(image, grade.clone(), &label)
+                        .with_input(*input)
+                        .with_opacity(render_layer.opacity)
+                        .with_sampling(render_layer.sampling)
+                        .build(
+                            device,
+                            queue,
+                            bindgroup_layout_manager.clone(),
+                            pipeline_manager.clone(),
+                        )?;
+                    pipelines.push(&render_layer.name, model);
                 }
                 common::wallpaper::LayerType::Shader {
                     shader_type,
                     image_path,
+                    input,
                     uniforms,
+                    noise,
                 } => {
-                    // Load image if present
-                    let image = image_path
-                        .as_ref()
-                        .map(|path| image::ImageReader::open(path).unwrap().decode().unwrap());
-
-                    // Get shader from shader type
-                    let shader = match shader_type {
-                        ShaderType::Wave => crate::shaders::WAVE_EFFECT_SHADER,
-                        ShaderType::Glitch => crate::shaders::GLITCH_EFFECT_SHADER,
-                        ShaderType::Gaussian => crate::shaders::GAUSSIAN_EFFECT_SHADER,
-                        ShaderType::Custom(_) => panic!("Custom shaders not supported yet"),
+                    // Load image if present, effects reading the layers below
+                    // get the backdrop bound once the output size is known and
+                    // procedural ones draw without an image
+                    let image = match input {
+                        EffectInput::Image => image_path
+                            .as_ref()
+                            .map(|path| load_image(path).map_err(|e| e.to_string()))
+                            .transpose()?
+                            .or_else(|| {
+                                shader_type
+                                    .is_procedural()
+                                    .then(|| DynamicImage::new_rgba8(1, 1))
+                            }),
+                        EffectInput::Below => Some(DynamicImage::new_rgba8(1, 1)),
+                    };
+
+                    // Get shader and its passes from shader type
+                    let (shader, passes) = match shader_type {
+                        ShaderType::Wave => (crate::shaders::WAVE_EFFECT_SHADER, SINGLE_PASS),
+                        ShaderType::Glitch => (crate::shaders::GLITCH_EFFECT_SHADER, SINGLE_PASS),
+                        ShaderType::Gaussian => (
+                            crate::shaders::GAUSSIAN_EFFECT_SHADER,
+                            crate::shaders::GAUSSIAN_EFFECT_PASSES,
+                        ),
+                        ShaderType::Ripple => (crate::shaders::RIPPLE_EFFECT_SHADER, SINGLE_PASS),
+                        ShaderType::Snow => (crate::shaders::SNOW_EFFECT_SHADER, SINGLE_PASS),
+                        ShaderType::Rain => (crate::shaders::RAIN_EFFECT_SHADER, SINGLE_PASS),
+                        ShaderType::Fire => (crate::shaders::FIRE_EFFECT_SHADER, SINGLE_PASS),
+                        ShaderType::Plasma => (crate::shaders::PLASMA_EFFECT_SHADER, SINGLE_PASS),
+                        ShaderType::Starfield => {
+                            (crate::shaders::STARFIELD_EFFECT_SHADER, SINGLE_PASS)
+                        }
+                        ShaderType::Lut => {
+                            return Err(format!(
+                                "Layer {}: lut effects are built as color grade layers",
+                                render_layer.name
+                            ))
+                        }
+                        ShaderType::Custom(_) => {
+                            return Err(format!(
+                                "Layer {}: custom shaders are not supported yet",
+                                render_layer.name
+                            ))
+                        }
                     };
 
                     // Build effect model
@@ -168,29 +611,32 @@ impl Pipelines {
 
                         // Get shader type from the shader
                         let shader_name = shader.label.unwrap_or("unknown");
-                        
+
                         // Create the effect builder and set parameters
-                        let builder =
-                            EffectModelBuilder::new(img, shader, render_layer.name.clone())
-                                .with_params(uniforms.clone())
-                                .with_opacity(opacity);
+                        let builder = EffectModelBuilder::new(img, shader, label.clone())
+                            .with_params(uniforms.clone())
+                            .with_opacity(opacity)
+                            .with_sampling(render_layer.sampling)
+                            .with_input(*input)
+                            .with_noise(*noise)
+                            .with_passes(passes);
+
+                        tracing::debug!("Building effect for shader type: {}", shader_name);
 
-                        println!("Building effect for shader type: {}", shader_name);
-                        
                         // Build the effect model
                         let effect = builder.build(
                             device,
                             queue,
                             bindgroup_layout_manager.clone(),
                             pipeline_manager.clone(),
-                        );
+                        )?;
 
                         // Add the effect to pipelines
-                        pipelines.data.push(Box::new(effect));
+                        pipelines.push(&render_layer.name, effect);
                     } else {
                         // TODO: Handle effects without images
-                        println!(
-                            "Warning: Shader effect {} has no image and will be skipped",
+                        tracing::warn!(
+                            "Shader effect {} has no image and will be skipped",
                             render_layer.name
                         );
                     }
@@ -198,7 +644,7 @@ impl Pipelines {
             }
         }
 
-        pipelines
+        Ok(pipelines)
     }
 }
 
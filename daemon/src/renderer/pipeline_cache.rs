@@ -0,0 +1,94 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use wgpu::{Adapter, Device, Features, PipelineCache};
+
+/// The cache of the current device and the file it is saved to, None if
+/// the device has no pipeline cache
+static CURRENT: Mutex<Option<(Arc<PipelineCache>, PathBuf)>> = Mutex::new(None);
+/// Held while the cache is written, loader threads finish at the same time
+static SAVING: Mutex<()> = Mutex::new(());
+
+/// Open the pipeline cache of `adapter`'s driver for `device`, with the
+/// pipelines saved by earlier runs
+///
+/// Pipelines are then compiled by the driver once and reused on the next
+/// start or wallpaper switch, instead of stuttering through compilation
+/// again. Only Vulkan has pipeline caches, other backends create their
+/// pipelines without one.
+pub fn open(adapter: &Adapter, device: &Device) {
+    let mut current = CURRENT.lock().unwrap();
+    // A cache belongs to the device it was created on
+    *current = None;
+    if !device.features().contains(Features::PIPELINE_CACHE) {
+        return;
+    }
+    let Some(key) = wgpu::util::pipeline_cache_key(&adapter.get_info()) else {
+        return;
+    };
+    let path = cache_dir().join(key);
+    let data = fs::read(&path).ok();
+
+    // SAFETY: the data was saved from a cache of the same adapter and
+    // driver, which the key names, and wgpu checks its header. A stale or
+    // corrupt file gives an empty cache thanks to `fallback`.
+    let cache = unsafe {
+        device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+            label: Some("wlrs pipeline cache"),
+            data: data.as_deref(),
+            fallback: true,
+        })
+    };
+    tracing::debug!(
+        "Opened the pipeline cache {} with {} bytes",
+        path.display(),
+        data.map_or(0, |data| data.len())
+    );
+    *current = Some((Arc::new(cache), path));
+}
+
+/// The cache to create pipelines with, None without one
+pub fn current() -> Option<Arc<PipelineCache>> {
+    CURRENT
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(cache, _)| cache.clone())
+}
+
+/// Save the cache with the pipelines created so far
+pub fn save() {
+    let Some((cache, path)) = CURRENT.lock().unwrap().clone() else {
+        return;
+    };
+    let Some(data) = cache.get_data() else {
+        return;
+    };
+    let _saving = SAVING.lock().unwrap();
+    if let Err(e) = write(&path, &data) {
+        tracing::warn!(
+            "Failed to save the pipeline cache to {}: {e}",
+            path.display()
+        );
+    }
+}
+
+/// Write `data` aside and move it into place, a daemon reading the cache
+/// meanwhile never sees half of it
+fn write(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension(format!("{}.partial", std::process::id()));
+    fs::write(&partial, data)?;
+    fs::rename(&partial, path)
+}
+
+fn cache_dir() -> PathBuf {
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.cache_dir().join("wlrs").join("pipelines"))
+        .unwrap_or_else(|| PathBuf::from("/tmp/wlrs/pipelines"))
+}
@@ -0,0 +1,247 @@
+use std::path::Path;
+
+use common::{
+    manifest::{EffectInput, Environment, Screen, ShaderType},
+    types::{LayerPlan, OutputPlan},
+    wallpaper::{LayerType, RenderLayer, Wallpaper},
+};
+use image::{GenericImageView, ImageReader};
+
+use crate::asset::{animated, color::load_image, image::rgba_size_bytes};
+use crate::{colors, weather};
+
+use super::prescale;
+
+/// Work out how `wallpaper` would be drawn on `screen`, the way
+/// `Pipelines::from` builds it but without touching the GPU, for
+/// `wlrs set-wallpaper --dry-run`
+///
+/// Images are only measured, not decoded, so the sizes and memory are
+/// estimates. Layers that would fail to load say why instead, layers whose
+/// parameters are invalid fail the whole plan like they fail the load.
+pub fn output(
+    wallpaper: &Wallpaper,
+    screen: Screen,
+    target: (u32, u32),
+) -> Result<OutputPlan, String> {
+    let output = screen.output.clone();
+    let (width, height) = (screen.width, screen.height);
+    let environment = Environment {
+        weather: weather::current(),
+        colors: colors::current(),
+        screen: Some(screen),
+    };
+    let render_layers = wallpaper
+        .get_layers_in(&environment)
+        .map_err(|e| e.to_string())?;
+    let hidden = wallpaper
+        .layers()
+        .iter()
+        .filter(|layer| !render_layers.iter().any(|shown| shown.name == layer.name))
+        .map(|layer| layer.name.clone())
+        .collect();
+
+    let layers: Vec<LayerPlan> = render_layers
+        .iter()
+        .map(|render_layer| plan_layer(wallpaper, render_layer, (width, height), target))
+        .collect();
+    let texture_bytes = layers.iter().map(|layer| layer.texture_bytes).sum();
+    Ok(OutputPlan {
+        output,
+        width,
+        height,
+        layers,
+        hidden,
+        texture_bytes,
+    })
+}
+
+fn plan_layer(
+    wallpaper: &Wallpaper,
+    render_layer: &RenderLayer,
+    output_size: (u32, u32),
+    target: (u32, u32),
+) -> LayerPlan {
+    let mut params: Vec<(String, String)> = wallpaper
+        .layer_by_name(&render_layer.name)
+        .map(|layer| {
+            layer
+                .params
+                .iter()
+                .map(|(key, value)| (key.clone(), value.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    params.sort();
+    let mut plan = LayerPlan {
+        name: render_layer.name.clone(),
+        kind: String::new(),
+        shader: None,
+        params,
+        source: None,
+        size: None,
+        texture_bytes: 0,
+        error: None,
+    };
+    let mipmaps = render_layer.sampling.uses_mipmaps();
+
+    match &render_layer.layer_type {
+        LayerType::Color { color } => {
+            plan.kind = "color".to_string();
+            plan.params.push(("color".to_string(), color.clone()));
+            plan.size = Some((1, 1));
+        }
+        LayerType::Image { image_path } if animated::is_animated(image_path) => {
+            // Streamed through a ring of frame textures
+            plan.kind = "animation".to_string();
+            add_image(&mut plan, image_path, None, mipmaps);
+            plan.texture_bytes *= animated::RING_SIZE as u64;
+        }
+        LayerType::Image { image_path } => {
+            plan.kind = "image".to_string();
+            add_image(&mut plan, image_path, Some((wallpaper, target)), mipmaps);
+        }
+        LayerType::Svg { svg_path } => {
+            // Rasterized at the output's size once it is attached
+            plan.kind = "svg".to_string();
+            plan.source = Some(svg_path.display().to_string());
+            add_screen_texture(&mut plan, output_size);
+        }
+        LayerType::Text { content } => {
+            plan.kind = "text".to_string();
+            plan.params.push(("text".to_string(), content.text.clone()));
+            add_screen_texture(&mut plan, output_size);
+        }
+        LayerType::Gradient { .. } => plan.kind = "gradient".to_string(),
+        LayerType::Capture { .. } => {
+            plan.kind = "capture".to_string();
+            add_screen_texture(&mut plan, output_size);
+        }
+        LayerType::Web { url, .. } => {
+            plan.kind = "web".to_string();
+            plan.source = Some(url.clone());
+            add_screen_texture(&mut plan, output_size);
+            if cfg!(not(feature = "web")) {
+                plan.error = Some("The daemon was built without web support".to_string());
+            }
+        }
+        LayerType::AlbumArt { fallback_path, .. } => {
+            plan.kind = "album art".to_string();
+            if let Some(path) = fallback_path {
+                add_image(&mut plan, path, None, mipmaps);
+            }
+        }
+        LayerType::Particle {
+            image_path,
+            sprites,
+            ..
+        } => {
+            plan.kind = "particles".to_string();
+            let mut textures = std::iter::once(image_path)
+                .filter(|path| !path.as_os_str().is_empty())
+                .chain(&sprites.textures);
+            if let Some(first) = textures.next() {
+                // The others are scaled to the size of the first
+                add_image(&mut plan, first, None, mipmaps);
+                plan.texture_bytes *= 1 + textures.count() as u64;
+            }
+        }
+        LayerType::ColorGrade {
+            image_path, input, ..
+        } => {
+            plan.kind = "effect".to_string();
+            plan.shader = Some("lut".to_string());
+            add_effect_input(
+                &mut plan,
+                image_path.as_deref(),
+                *input,
+                output_size,
+                mipmaps,
+            );
+        }
+        LayerType::Shader {
+            shader_type,
+            image_path,
+            input,
+            ..
+        } => {
+            plan.kind = "effect".to_string();
+            plan.shader = Some(match shader_type {
+                ShaderType::Custom(path) => path.clone(),
+                shader => format!("{shader:?}").to_lowercase(),
+            });
+            if matches!(shader_type, ShaderType::Custom(_)) {
+                plan.error = Some("Custom shaders are not supported yet".to_string());
+            }
+            add_effect_input(
+                &mut plan,
+                image_path.as_deref(),
+                *input,
+                output_size,
+                mipmaps,
+            );
+        }
+    }
+    plan
+}
+
+/// Measure the image at `path` for `plan`, shrunk to `target` like the
+/// wallpaper's static images if given
+fn add_image(
+    plan: &mut LayerPlan,
+    path: &Path,
+    target: Option<(&Wallpaper, (u32, u32))>,
+    mipmaps: bool,
+) {
+    plan.source = Some(path.display().to_string());
+    match image_size(path) {
+        Ok(size) => {
+            let (width, height) = target
+                .and_then(|(wallpaper, target)| {
+                    prescale::target_size(size, wallpaper.scale_mode(), Some(target))
+                })
+                .unwrap_or(size);
+            plan.size = Some((width, height));
+            plan.texture_bytes += rgba_size_bytes(width, height, mipmaps);
+        }
+        Err(e) => plan.error = Some(e),
+    }
+}
+
+/// Count a texture the size of the output for `plan`
+fn add_screen_texture(plan: &mut LayerPlan, (width, height): (u32, u32)) {
+    plan.size = Some((width, height));
+    plan.texture_bytes += rgba_size_bytes(width, height, false);
+}
+
+/// Count what an effect reads, its image or a copy of the layers below
+fn add_effect_input(
+    plan: &mut LayerPlan,
+    image_path: Option<&Path>,
+    input: EffectInput,
+    output_size: (u32, u32),
+    mipmaps: bool,
+) {
+    match (input, image_path) {
+        (EffectInput::Image, Some(path)) => add_image(plan, path, None, mipmaps),
+        (EffectInput::Image, None) => {}
+        (EffectInput::Below, _) => add_screen_texture(plan, output_size),
+    }
+}
+
+/// Size of the image at `path` from its header, decoding it only for
+/// formats the image crate does not read, like camera raw files
+fn image_size(path: &Path) -> Result<(u32, u32), String> {
+    if !path.is_file() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    let header = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| e.to_string())
+        .and_then(|reader| reader.into_dimensions().map_err(|e| e.to_string()));
+    header.or_else(|_| {
+        load_image(path)
+            .map(|image| image.dimensions())
+            .map_err(|e| e.to_string())
+    })
+}
@@ -0,0 +1,63 @@
+use wayland_client::{
+    protocol::wl_output::WlOutput, Connection, Dispatch, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols_wlr::output_power_management::v1::client::{
+    zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
+    zwlr_output_power_v1::{self, ZwlrOutputPowerV1},
+};
+
+use super::client::Client;
+
+impl Client {
+    /// Stop drawing on an output the compositor turned off, e.g. after a
+    /// DPMS timeout, and pick up again once it is back on
+    fn set_output_power(&mut self, output: &WlOutput, on: bool) {
+        let Some(layer) = self
+            .wallpapers
+            .iter_mut()
+            .find(|layer| layer.output == *output)
+        else {
+            return;
+        };
+        if layer.set_powered(on) {
+            tracing::info!(
+                "Output {} turned {}",
+                layer.name,
+                if on { "on" } else { "off" }
+            );
+        }
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerManagerV1, ()> for Client {
+    fn event(
+        _state: &mut Self,
+        _manager: &ZwlrOutputPowerManagerV1,
+        _event: <ZwlrOutputPowerManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerV1, WlOutput> for Client {
+    fn event(
+        state: &mut Self,
+        _power: &ZwlrOutputPowerV1,
+        event: zwlr_output_power_v1::Event,
+        output: &WlOutput,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_output_power_v1::Event::Mode {
+                mode: WEnum::Value(mode),
+            } => state.set_output_power(output, mode == zwlr_output_power_v1::Mode::On),
+            // Another client controls the output's power, or it went away,
+            // frame callbacks still stop when it is off
+            zwlr_output_power_v1::Event::Failed => state.set_output_power(output, true),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::{path::Path, sync::OnceLock};
+
+use common::manifest::ScaleMode;
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+
+use crate::config::{DownscaleQuality, ImagesConfig};
+
+static CONFIG: OnceLock<ImagesConfig> = OnceLock::new();
+
+/// Set how images are shrunk, before anything is loaded
+pub fn init(config: ImagesConfig) {
+    if CONFIG.set(config).is_err() {
+        tracing::warn!("Images were loaded before downscaling was configured");
+    }
+}
+
+/// Whether images are shrunk to the outputs at all
+pub fn enabled() -> bool {
+    config().downscale
+}
+
+fn config() -> &'static ImagesConfig {
+    CONFIG.get_or_init(ImagesConfig::default)
+}
+
+impl From<DownscaleQuality> for FilterType {
+    fn from(quality: DownscaleQuality) -> Self {
+        match quality {
+            DownscaleQuality::Fast => FilterType::Triangle,
+            DownscaleQuality::Balanced => FilterType::CatmullRom,
+            DownscaleQuality::Best => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Shrink a decoded image to what `target` shows of it, so its texture
+/// isn't larger than the output it is drawn on
+///
+/// `target` is the size of the largest output in pixels, images are left as
+/// they are without one, when they are smaller already or when downscaling is
+/// turned off.
+pub fn prescale(
+    image: DynamicImage,
+    scale_mode: &ScaleMode,
+    target: Option<(u32, u32)>,
+    path: &Path,
+) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    let Some((scaled_width, scaled_height)) = target_size((width, height), scale_mode, target)
+    else {
+        return image;
+    };
+
+    tracing::debug!(
+        "Downscaling {} from {width}x{height} to {scaled_width}x{scaled_height}",
+        path.display()
+    );
+    image.resize_exact(scaled_width, scaled_height, config().quality.into())
+}
+
+/// Size an image of `size` should be shrunk to for outputs of up to
+/// `target`, None to keep it as it is
+pub fn target_size(
+    size: (u32, u32),
+    scale_mode: &ScaleMode,
+    target: Option<(u32, u32)>,
+) -> Option<(u32, u32)> {
+    if !enabled() {
+        return None;
+    }
+    scaled_size(size, target?, scale_mode)
+}
+
+/// Size an image of `size` is drawn at on a `target` sized output, if that
+/// is smaller than the image
+fn scaled_size(size: (u32, u32), target: (u32, u32), scale_mode: &ScaleMode) -> Option<(u32, u32)> {
+    let (width, height) = size;
+    let (target_width, target_height) = target;
+    if width == 0 || height == 0 || target_width == 0 || target_height == 0 {
+        return None;
+    }
+
+    let scale_x = target_width as f64 / width as f64;
+    let scale_y = target_height as f64 / height as f64;
+    let (scaled_width, scaled_height) = match scale_mode {
+        ScaleMode::Fill => {
+            let scale = scale_x.max(scale_y);
+            (width as f64 * scale, height as f64 * scale)
+        }
+        ScaleMode::Fit => {
+            let scale = scale_x.min(scale_y);
+            (width as f64 * scale, height as f64 * scale)
+        }
+        ScaleMode::Stretch => (
+            width.min(target_width) as f64,
+            height.min(target_height) as f64,
+        ),
+        // Drawn pixel for pixel, shrinking would change what is shown
+        ScaleMode::Center | ScaleMode::Tile => return None,
+    };
+
+    let scaled = (
+        (scaled_width.ceil() as u32).clamp(1, width),
+        (scaled_height.ceil() as u32).clamp(1, height),
+    );
+    (scaled != size).then_some(scaled)
+}
@@ -0,0 +1,237 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wgpu::{
+    Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder, Device, Features,
+    Maintain, MapMode, QuerySet, QuerySetDescriptor, QueryType, Queue,
+};
+
+/// Budget of a frame when the wallpaper has no update interval
+pub const DEFAULT_FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+
+/// Share of the frame budget a single layer may take before it is flagged
+const LAYER_BUDGET_SHARE: f64 = 0.5;
+
+/// A flagged layer is cleared once it drops below this share of its limit,
+/// so a layer hovering around the limit is not reported over and over
+const RECOVERY_SHARE: f64 = 0.8;
+
+/// Frames measured before a layer can be flagged, the first frames include
+/// uploads and shader compilation
+const WARMUP_FRAMES: u32 = 30;
+
+/// Weight of the newest frame in the smoothed costs
+const SMOOTHING: f64 = 0.1;
+
+/// Smoothed cost of drawing one layer of a wallpaper
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LayerCost {
+    /// Time spent updating the layer and recording its draw calls
+    pub cpu: Duration,
+    /// Time the GPU spent drawing the layer, if the adapter supports
+    /// timestamps inside render passes
+    pub gpu: Option<Duration>,
+    /// Whether the layer takes more than its share of the frame budget
+    pub over_budget: bool,
+    samples: u32,
+}
+
+impl LayerCost {
+    /// The larger of the CPU and GPU time, whichever limits the frame rate
+    pub fn cost(&self) -> Duration {
+        self.cpu.max(self.gpu.unwrap_or_default())
+    }
+}
+
+/// Measures how long each layer of a wallpaper takes to draw and flags the
+/// layers that blow the frame budget
+#[derive(Default)]
+pub struct Profiler {
+    costs: Vec<LayerCost>,
+    gpu: Option<GpuTimer>,
+}
+
+impl Profiler {
+    /// Create a profiler for a wallpaper with `layers` models
+    ///
+    /// GPU times are only measured if the device was opened with timestamp
+    /// queries.
+    pub fn new(device: &Device, queue: &Queue, layers: usize) -> Self {
+        let features = Features::TIMESTAMP_QUERY | Features::TIMESTAMP_QUERY_INSIDE_PASSES;
+        let gpu = (layers > 0 && device.features().contains(features))
+            .then(|| GpuTimer::new(device, queue, layers as u32 + 1));
+        Self {
+            costs: vec![LayerCost::default(); layers],
+            gpu,
+        }
+    }
+
+    /// Costs of every layer, in drawing order
+    pub fn costs(&self) -> &[LayerCost] {
+        &self.costs
+    }
+
+    /// Collect GPU times of an earlier frame and return the query set to
+    /// write this frame's timestamps to, if the previous ones were read
+    pub fn begin_frame(&mut self, device: &Device) -> Option<&QuerySet> {
+        let timer = self.gpu.as_mut()?;
+        if timer.in_flight {
+            device.poll(Maintain::Poll);
+            let times = timer.read()?;
+            for (cost, time) in self.costs.iter_mut().zip(times) {
+                cost.gpu = Some(match cost.gpu {
+                    Some(smoothed) => smooth(smoothed, time),
+                    None => time,
+                });
+            }
+        }
+        timer.recording = true;
+        Some(&timer.queries)
+    }
+
+    /// Record the CPU time of a layer for this frame
+    pub fn record_cpu(&mut self, layer: usize, time: Duration) {
+        if let Some(cost) = self.costs.get_mut(layer) {
+            cost.cpu = if cost.samples == 0 {
+                time
+            } else {
+                smooth(cost.cpu, time)
+            };
+            cost.samples = cost.samples.saturating_add(1);
+        }
+    }
+
+    /// Copy this frame's timestamps out of the query set, called after the
+    /// render pass when `begin_frame` returned a query set
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        if let Some(timer) = self.gpu.as_ref().filter(|timer| timer.recording) {
+            timer.resolve(encoder);
+        }
+    }
+
+    /// Start reading back the timestamps once the frame was submitted
+    pub fn end_frame(&mut self) {
+        if let Some(timer) = self.gpu.as_mut().filter(|timer| timer.recording) {
+            timer.map();
+        }
+    }
+
+    /// Flag layers that take more than their share of `budget` and clear the
+    /// ones that recovered, logging both
+    pub fn check_budget<'a>(
+        &mut self,
+        output: &str,
+        budget: Duration,
+        labels: impl Iterator<Item = &'a str>,
+    ) {
+        let limit = budget.mul_f64(LAYER_BUDGET_SHARE);
+        for (cost, label) in self.costs.iter_mut().zip(labels) {
+            if cost.samples < WARMUP_FRAMES {
+                continue;
+            }
+            if !cost.over_budget && cost.cost() > limit {
+                cost.over_budget = true;
+                tracing::warn!(
+                    "Layer {label} on {output} takes {:?} on the CPU and {:?} on the GPU, \
+                     more than half of the {budget:?} frame budget",
+                    cost.cpu,
+                    cost.gpu.unwrap_or_default()
+                );
+            } else if cost.over_budget && cost.cost() < limit.mul_f64(RECOVERY_SHARE) {
+                cost.over_budget = false;
+                tracing::info!("Layer {label} on {output} is back within the frame budget");
+            }
+        }
+    }
+}
+
+fn smooth(smoothed: Duration, sample: Duration) -> Duration {
+    smoothed.mul_f64(1.0 - SMOOTHING) + sample.mul_f64(SMOOTHING)
+}
+
+/// Timestamp queries written between the layers of a render pass
+struct GpuTimer {
+    queries: QuerySet,
+    /// Destination of `resolve_query_set`
+    resolved: Buffer,
+    /// Mappable copy of `resolved`, read a few frames later
+    readback: Buffer,
+    count: u32,
+    /// Nanoseconds per timestamp tick
+    period: f64,
+    /// Result of the last `map_async`, set once the GPU is done with the frame
+    mapped: Arc<Mutex<Option<Result<(), BufferAsyncError>>>>,
+    /// Whether the current frame writes timestamps
+    recording: bool,
+    /// Whether `readback` is being mapped
+    in_flight: bool,
+}
+
+impl GpuTimer {
+    fn new(device: &Device, queue: &Queue, count: u32) -> Self {
+        let size = count as u64 * std::mem::size_of::<u64>() as u64;
+        Self {
+            queries: device.create_query_set(&QuerySetDescriptor {
+                label: Some("Layer Timestamps"),
+                ty: QueryType::Timestamp,
+                count,
+            }),
+            resolved: device.create_buffer(&BufferDescriptor {
+                label: Some("Layer Timestamps Resolve Buffer"),
+                size,
+                usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback: device.create_buffer(&BufferDescriptor {
+                label: Some("Layer Timestamps Readback Buffer"),
+                size,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            count,
+            period: queue.get_timestamp_period() as f64,
+            mapped: Arc::new(Mutex::new(None)),
+            recording: false,
+            in_flight: false,
+        }
+    }
+
+    fn resolve(&self, encoder: &mut CommandEncoder) {
+        encoder.resolve_query_set(&self.queries, 0..self.count, &self.resolved, 0);
+        encoder.copy_buffer_to_buffer(&self.resolved, 0, &self.readback, 0, self.resolved.size());
+    }
+
+    fn map(&mut self) {
+        let mapped = self.mapped.clone();
+        self.readback
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                *mapped.lock().unwrap() = Some(result);
+            });
+        self.recording = false;
+        self.in_flight = true;
+    }
+
+    /// Time between consecutive timestamps, None while the GPU is not done
+    /// or if the timestamps could not be read
+    fn read(&mut self) -> Option<Vec<Duration>> {
+        let result = self.mapped.lock().unwrap().take()?;
+        self.in_flight = false;
+        result.ok()?;
+
+        let timestamps: Vec<u64> =
+            bytemuck::pod_collect_to_vec(&self.readback.slice(..).get_mapped_range());
+        self.readback.unmap();
+
+        let times = timestamps
+            .windows(2)
+            .map(|pair| {
+                let ticks = pair[1].saturating_sub(pair[0]);
+                Duration::from_nanos((ticks as f64 * self.period) as u64)
+            })
+            .collect();
+        Some(times)
+    }
+}
@@ -0,0 +1,230 @@
+use common::types::DisplayAdjustment;
+use wgpu::{
+    BindGroup, Buffer, CommandEncoder, Device, Extent3d, FilterMode, Queue, RenderPipeline,
+    SamplerDescriptor, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    TextureView,
+};
+
+use crate::asset::color::temperature_to_rgb;
+
+/// Format the models draw in, see their pipelines
+pub const FORMAT: TextureFormat = TextureFormat::Bgra8UnormSrgb;
+
+/// Texture a layer draws into instead of its surface, scaled onto the
+/// surface afterwards
+///
+/// Needed when rendering at a different resolution than the output, when
+/// an effect reads the layers below it, since surface textures cannot be sampled,
+/// or when the output has a display adjustment.
+pub struct RenderTarget {
+    view: TextureView,
+    bind_group: BindGroup,
+    /// Display adjustment applied when presenting
+    adjustment_buffer: Buffer,
+    /// Scales the target onto the surface, applying the display adjustment
+    present_pipeline: RenderPipeline,
+    /// Copies the target into a backdrop, possibly of another size
+    capture_pipeline: RenderPipeline,
+}
+
+impl RenderTarget {
+    /// Create a `width` by `height` target that is presented to views of `output_format`
+    pub fn new(device: &Device, width: u32, height: u32, output_format: TextureFormat) -> Self {
+        let view = create_texture(device, "Render Target", width, height);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Render Target Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Target Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        // The mipmap shader is a plain bilinear blit
+        let mipmap_shader = device.create_shader_module(crate::shaders::MIPMAP_SHADER);
+        let display_shader = device.create_shader_module(crate::shaders::DISPLAY_SHADER);
+        let blit_pipeline = |label, shader: &wgpu::ShaderModule, format: TextureFormat| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(format.into())],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: crate::renderer::pipeline_cache::current().as_deref(),
+            })
+        };
+        let present_pipeline = blit_pipeline(
+            "Render Target Present Pipeline",
+            &display_shader,
+            output_format,
+        );
+        let capture_pipeline =
+            blit_pipeline("Render Target Capture Pipeline", &mipmap_shader, FORMAT);
+
+        let adjustment_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Display Adjustment Buffer"),
+            size: std::mem::size_of::<[f32; 8]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Render Target Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Render Target Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: adjustment_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            view,
+            bind_group,
+            adjustment_buffer,
+            present_pipeline,
+            capture_pipeline,
+        }
+    }
+
+    /// View the models draw into
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Set the display adjustment applied by `blit`
+    pub fn write_adjustment(&self, queue: &Queue, adjustment: &DisplayAdjustment) {
+        let [red, green, blue] = temperature_to_rgb(adjustment.temperature);
+        queue.write_buffer(
+            &self.adjustment_buffer,
+            0,
+            bytemuck::cast_slice(&[
+                adjustment.brightness,
+                adjustment.contrast,
+                1.0 / adjustment.gamma,
+                0.0,
+                red,
+                green,
+                blue,
+                0.0,
+            ]),
+        );
+    }
+
+    /// Scale the target onto `output`, replacing its contents
+    pub fn blit(&self, encoder: &mut CommandEncoder, output: &TextureView) {
+        self.draw_into(encoder, &self.present_pipeline, output);
+    }
+
+    /// Copy what was drawn so far into `backdrop`, for effects that read the
+    /// layers below them
+    pub fn capture(&self, encoder: &mut CommandEncoder, backdrop: &TextureView) {
+        self.draw_into(encoder, &self.capture_pipeline, backdrop);
+    }
+
+    fn draw_into(
+        &self,
+        encoder: &mut CommandEncoder,
+        pipeline: &RenderPipeline,
+        output: &TextureView,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Target Blit Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Create the texture effects reading the layers below them sample, see
+/// `RenderTarget::capture`
+pub fn create_backdrop(device: &Device, width: u32, height: u32) -> TextureView {
+    create_texture(device, "Backdrop", width, height)
+}
+
+fn create_texture(device: &Device, label: &str, width: u32, height: u32) -> TextureView {
+    device
+        .create_texture(&TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&Default::default())
+}
@@ -0,0 +1,101 @@
+use std::sync::mpsc;
+
+use image::RgbaImage;
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsages, CommandEncoder, Device, Maintain, MapMode, Texture,
+    TextureFormat, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+/// A frame copied out of a surface texture, read once the GPU drew it
+pub struct Screenshot {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    /// Bytes per row in `buffer`, padded to the copy alignment
+    row_size: u32,
+    /// Whether the channels are stored blue first
+    bgra: bool,
+}
+
+impl Screenshot {
+    /// Record a copy of `texture` into a mappable buffer
+    ///
+    /// The texture needs `COPY_SRC` usage and 8 bit channels.
+    pub fn copy(
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        texture: &Texture,
+    ) -> Result<Self, String> {
+        let bgra = match texture.format() {
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+            format => return Err(format!("Frames in {format:?} cannot be captured")),
+        };
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err("The compositor does not allow reading the surface".to_string());
+        }
+
+        let (width, height) = (texture.width(), texture.height());
+        let row_size = (width * 4).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Screenshot Buffer"),
+            size: row_size as u64 * height as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(row_size),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            row_size,
+            bgra,
+        })
+    }
+
+    /// Wait for the GPU to finish the copy and return the frame
+    ///
+    /// Colors are premultiplied with the background alpha, the image is made
+    /// opaque, which is the frame as it looks over black.
+    pub fn read(self, device: &Device) -> Result<RgbaImage, String> {
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+
+        let mut pixels = Vec::with_capacity((self.width * self.height * 4) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks_exact(self.row_size as usize)
+        {
+            for pixel in row[..self.width as usize * 4].chunks_exact(4) {
+                let [r, g, b] = if self.bgra {
+                    [pixel[2], pixel[1], pixel[0]]
+                } else {
+                    [pixel[0], pixel[1], pixel[2]]
+                };
+                pixels.extend_from_slice(&[r, g, b, u8::MAX]);
+            }
+        }
+        self.buffer.unmap();
+        RgbaImage::from_raw(self.width, self.height, pixels)
+            .ok_or_else(|| "The captured frame is incomplete".to_string())
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Number of recent frames kept for the frame time statistics
+const FRAME_WINDOW: usize = 600;
+
+/// Number of recent GPU errors kept
+const ERROR_WINDOW: usize = 8;
+
+/// Period over which the frame rate is measured
+const FPS_PERIOD: Duration = Duration::from_secs(1);
+
+/// Render counters of a single output, reported by `wlrs stats`
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    /// When recent frames were presented, oldest first
+    presented: VecDeque<Instant>,
+    /// CPU time spent recording and submitting recent frames, oldest first
+    frame_times: VecDeque<Duration>,
+    frames: u64,
+    dropped: u64,
+    /// Recent validation errors of frames, oldest first
+    gpu_errors: VecDeque<String>,
+}
+
+impl FrameStats {
+    /// Count a presented frame that started drawing at `started`
+    ///
+    /// If the frame came more than twice `interval` after the previous one, the
+    /// frames that should have been drawn in between are counted as dropped.
+    pub fn record_frame(&mut self, started: Instant, interval: Option<Duration>) {
+        let now = Instant::now();
+        if let (Some(previous), Some(interval)) = (self.presented.back(), interval) {
+            let gap = now.duration_since(*previous);
+            if !interval.is_zero() && gap > interval * 2 {
+                self.dropped += (gap.as_secs_f64() / interval.as_secs_f64()) as u64 - 1;
+            }
+        }
+
+        push_bounded(&mut self.presented, now);
+        push_bounded(&mut self.frame_times, now.duration_since(started));
+        self.frames += 1;
+    }
+
+    /// Count a frame that could not be drawn, e.g. when no surface texture was available
+    pub fn record_dropped(&mut self) {
+        self.dropped += 1;
+    }
+
+    /// Note a validation error a frame ran into, see `WallpaperLayer::drop_failed_layer`
+    pub fn record_gpu_error(&mut self, error: String) {
+        if self.gpu_errors.len() == ERROR_WINDOW {
+            self.gpu_errors.pop_front();
+        }
+        self.gpu_errors.push_back(error);
+    }
+
+    /// Frames presented during the last second
+    pub fn fps(&self) -> u32 {
+        let now = Instant::now();
+        self.presented
+            .iter()
+            .rev()
+            .take_while(|presented| now.duration_since(**presented) <= FPS_PERIOD)
+            .count() as u32
+    }
+
+    /// Average time to draw one of the recent frames
+    pub fn average_frame_time(&self) -> Duration {
+        if self.frame_times.is_empty() {
+            return Duration::ZERO;
+        }
+        self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32
+    }
+
+    /// Time that `percentile` (from 0 to 1) of the recent frames were drawn within
+    pub fn percentile_frame_time(&self, percentile: f64) -> Duration {
+        let mut sorted: Vec<Duration> = self.frame_times.iter().copied().collect();
+        sorted.sort_unstable();
+        let index = ((sorted.len() as f64 * percentile).ceil() as usize).saturating_sub(1);
+        sorted.get(index).copied().unwrap_or_default()
+    }
+
+    /// Frames presented since the wallpaper was set
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Frames missed since the wallpaper was set
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Recent validation errors, oldest first
+    pub fn gpu_errors(&self) -> impl Iterator<Item = &str> {
+        self.gpu_errors.iter().map(String::as_str)
+    }
+}
+
+fn push_bounded<T>(queue: &mut VecDeque<T>, value: T) {
+    if queue.len() == FRAME_WINDOW {
+        queue.pop_front();
+    }
+    queue.push_back(value);
+}
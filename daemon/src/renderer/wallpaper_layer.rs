@@ -1,23 +1,82 @@
 use std::{
     ptr::NonNull,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
 use crate::renderer::config::OutputConfig;
+use common::types::DisplayAdjustment;
+use image::RgbaImage;
 use raw_window_handle::{
     RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
 };
-use smithay_client_toolkit::shell::{
-    wlr_layer::{Anchor, KeyboardInteractivity, LayerSurface},
-    WaylandSurface,
+use smithay_client_toolkit::{
+    output::OutputInfo,
+    shell::{
+        wlr_layer::{Anchor, KeyboardInteractivity, LayerSurface},
+        WaylandSurface,
+    },
 };
-use wayland_client::{protocol::wl_output::WlOutput, Connection, Proxy, QueueHandle};
+use wayland_client::{
+    backend::Backend,
+    protocol::wl_output::{Transform, WlOutput},
+    Connection, Proxy, QueueHandle,
+};
+use wayland_protocols_wlr::output_power_management::v1::client::zwlr_output_power_v1::ZwlrOutputPowerV1;
 use wgpu::{
-    Adapter, CompositeAlphaMode, Device, PresentMode, Queue, RenderPipeline, Surface,
-    SurfaceConfiguration, SurfaceTargetUnsafe, TextureUsages,
+    Adapter, CommandEncoder, CompositeAlphaMode, Device, Instance, PresentMode, Queue, RenderPass,
+    RenderPipeline, Surface, SurfaceConfiguration, SurfaceError, SurfaceTargetUnsafe,
+    TextureFormat, TextureUsages, TextureView,
+};
+
+use super::{
+    client::Client,
+    clock,
+    overview::Overview,
+    pipeline::Pipelines,
+    profiler::{Profiler, DEFAULT_FRAME_BUDGET},
+    render_target::{self, RenderTarget},
+    screenshot::Screenshot,
+    stats::FrameStats,
 };
 
-use super::{client::Client, pipeline::Pipelines};
+/// Smallest and largest render scale, beyond these the result is either a
+/// blur or a waste of memory
+pub const RENDER_SCALE_RANGE: (f32, f32) = (0.25, 2.0);
+
+/// Wait before drawing again after the swapchain had no texture to draw into
+const RETRY_DELAY: Duration = Duration::from_millis(16);
+
+/// How often a wallpaper is redrawn or its animations advance, from the
+/// manifest's framerate and tickrate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rate {
+    /// Whenever the compositor asks for a frame, for negative rates
+    Compositor,
+    /// Never on its own, for a rate of 0
+    Never,
+    /// At most once per interval
+    Every(Duration),
+}
+
+impl Rate {
+    pub fn new(rate: i32) -> Self {
+        match rate {
+            ..0 => Self::Compositor,
+            0 => Self::Never,
+            rate => Self::Every(Duration::from_secs(1) / rate as u32),
+        }
+    }
+
+    /// Shortest time between two updates, zero when the compositor paces them
+    fn interval(self) -> Option<Duration> {
+        match self {
+            Self::Compositor => Some(Duration::ZERO),
+            Self::Never => None,
+            Self::Every(interval) => Some(interval),
+        }
+    }
+}
 
 #[allow(dead_code)]
 pub struct WallpaperLayer {
@@ -26,24 +85,83 @@ pub struct WallpaperLayer {
     pub output: WlOutput,
     pub damaged: bool,
     pub configured: bool,
-    pub wallpaper: Pipelines, // Render pipelines for this wallpaper
+    /// Render pipelines for this wallpaper, shared with the outputs mirroring it
+    pub wallpaper: Arc<Mutex<Pipelines>>,
+    /// Output whose wallpaper this layer shows, see `Client::mirror`
+    ///
+    /// A mirror draws the source's pipelines as they are, only the source
+    /// advances animations, so both show the same frame.
+    pub mirror_of: Option<String>,
+    /// Id of the most recent load queued for this layer, if still in flight
+    pub pending_load: Option<u64>,
+    /// Output size in pixels the wallpaper's images were shrunk for, see
+    /// `prescale`
+    pub load_target: Option<(u32, u32)>,
 
     pub width: u32,
     pub height: u32,
-
-    pub framerate: Option<u64>,
-    pub tickrate: Option<u64>,
+    /// Integer buffer scale of the output, the surface renders at
+    /// `width * scale` by `height * scale` pixels
+    pub scale: i32,
+    /// Refresh rate of the output's current mode in mHz, 0 if the compositor
+    /// did not tell
+    refresh_rate: u32,
+    /// Resolution the wallpaper is drawn at relative to the surface, e.g. 0.5
+    /// for half the pixels in each direction
+    render_scale: f32,
+    /// Intermediate texture scaled onto the surface when `render_scale` is not 1
+    render_target: Option<RenderTarget>,
+    /// Brightness, contrast, gamma and color temperature applied when
+    /// presenting the wallpaper
+    adjustment: DisplayAdjustment,
+    /// Blur and dim over the wallpaper while the compositor shows an overview
+    pub overview: Overview,
+
+    /// How often the wallpaper is redrawn while it animates
+    framerate: Rate,
+    /// How often its animations advance
+    tickrate: Rate,
+
+    /// Render counters since the current wallpaper was set
+    pub stats: FrameStats,
+    /// Cost of each model of the current wallpaper
+    pub profiler: Profiler,
+
+    /// Alpha the surface is cleared to before drawing, below 1.0 the
+    /// compositor blends the wallpaper with whatever is behind it
+    background_alpha: f32,
+    /// Animations stand still and the wallpaper is only drawn when damaged
+    paused: bool,
+    /// Like `paused`, for users who turned motion off, see `Client::set_motion`
+    still: bool,
+    /// Whether the next frame drawn is copied back, see `capture`
+    capturing: bool,
+    /// The copied frame, or why it could not be copied
+    captured: Option<Result<RgbaImage, String>>,
 
     config: OutputConfig,
     surface: Surface<'static>,
+    /// Last configuration applied to the surface, reused when it is lost
+    surface_config: Option<SurfaceConfiguration>,
+    /// sRGB view format used for rendering into the surface
+    view_format: TextureFormat,
     pipeline: Option<RenderPipeline>,
-    frame_counter: u32,
-    frames_per_update: u32,
-    tick_counter: u32,
-    ticks_per_update: u32,
-
-    // Animation timing
-    last_animation_update: Instant,
+    /// A frame was committed and the compositor did not ask for the next one
+    /// yet, nothing is drawn until it does, see `frame_done`
+    frame_pending: bool,
+    /// Power state of the output, see `Client::power_manager`
+    power: Option<ZwlrOutputPowerV1>,
+    /// Whether the output is on, nothing is drawn while it is off
+    powered: bool,
+    /// Drawing failed and is tried again at this time
+    retry_at: Option<Instant>,
+    /// Time on the shared clock the animations were advanced to, None until
+    /// the first tick of the wallpaper, see `clock`
+    animation_time: Option<Duration>,
+    /// Seconds the animations run behind the shared clock
+    phase: f32,
+    /// When the next animation tick is due, on the shared clock's grid
+    next_tick: Instant,
 }
 
 impl PartialEq<WallpaperLayer> for WallpaperLayer {
@@ -71,255 +189,568 @@ impl WallpaperLayer {
 
         layer.commit();
 
-        let surface = unsafe {
-            state
-                .instance
-                .create_surface_unsafe(SurfaceTargetUnsafe::RawHandle {
-                    raw_display_handle: RawDisplayHandle::Wayland(WaylandDisplayHandle::new(
-                        NonNull::new(connection.backend().display_ptr() as *mut _).unwrap(),
-                    )),
-                    raw_window_handle: RawWindowHandle::Wayland(WaylandWindowHandle::new(
-                        NonNull::new(layer.wl_surface().id().as_ptr() as *mut _).unwrap(),
-                    )),
-                })
-                .unwrap()
-        };
+        let surface = create_surface(&state.instance, &connection.backend(), &layer);
 
         Self {
-            name: info.name.unwrap_or("UNKNOWN".to_string()),
+            name: output_name(&info),
             layer,
             output: output.clone(),
             damaged: true,
             configured: false,
             width: 0,
             height: 0,
-            wallpaper: Pipelines::new(),
+            scale: info.scale_factor.max(1),
+            refresh_rate: refresh_rate(&info),
+            render_scale: 1.0,
+            render_target: None,
+            adjustment: DisplayAdjustment::default(),
+            overview: Overview::new(
+                state.bindgroup_layout_manager.clone(),
+                state.pipeline_manager.clone(),
+            ),
+            wallpaper: Arc::new(Mutex::new(Pipelines::new())),
+            mirror_of: None,
+            pending_load: None,
+            load_target: None,
             config: OutputConfig::default(),
             surface,
+            surface_config: None,
+            view_format: TextureFormat::Bgra8UnormSrgb,
             pipeline: None,
-            framerate: None,
-            tickrate: None,
-            frame_counter: 0,
-            frames_per_update: 1, // Will redraw every frame by default
-            tick_counter: 0,
-            ticks_per_update: 1, // Will update animations every frame by default
-            last_animation_update: Instant::now(),
+            framerate: Rate::Never,
+            tickrate: Rate::Never,
+            stats: FrameStats::default(),
+            profiler: Profiler::default(),
+            background_alpha: 1.0,
+            paused: false,
+            still: false,
+            capturing: false,
+            captured: None,
+            frame_pending: false,
+            power: state
+                .power_manager
+                .as_ref()
+                .map(|manager| manager.get_output_power(output, qh, output.clone())),
+            powered: true,
+            retry_at: None,
+            animation_time: None,
+            phase: 0.0,
+            next_tick: Instant::now(),
         }
     }
 
-    pub fn request_compositor_update(&mut self, qh: &QueueHandle<Client>) {
-        // Request a frame callback from the compositor
-        self.layer
-            .wl_surface()
-            .frame(qh, self.layer.wl_surface().clone());
+    /// Time between the frames the wallpaper asks for, used as its frame
+    /// budget, no shorter than the output takes to show one
+    pub fn get_recommended_update_interval(&self) -> Option<Duration> {
+        let interval = match (self.framerate, self.tickrate) {
+            (Rate::Every(interval), _) | (_, Rate::Every(interval)) => Some(interval),
+            _ => None,
+        };
+        match (interval, self.refresh_period()) {
+            (Some(interval), Some(refresh)) => Some(interval.max(refresh)),
+            (interval, refresh) => interval.or(refresh),
+        }
     }
 
-    pub fn get_recommended_update_interval(&self) -> Option<Duration> {
-        match (self.framerate, self.tickrate) {
-            (None, None) => None,
-            (None, Some(tickrate)) => {
-                // If only tickrate is set, use it for update interval
-                Some(Duration::from_millis(1000 / tickrate))
-            }
-            (Some(framerate), None) => {
-                // If only framerate is set, use it for update interval
-                Some(Duration::from_millis(1000 / framerate))
-            }
-            (Some(framerate), Some(tickrate)) => {
-                // If both are set, use the lower of the two
-                let framerate_duration = Duration::from_millis(1000 / framerate);
-                let tickrate_duration = Duration::from_millis(1000 / tickrate);
-                Some(framerate_duration.min(tickrate_duration))
-            }
+    /// Refresh rate of the output's current mode in mHz, 0 if unknown
+    pub fn refresh_rate(&self) -> u32 {
+        self.refresh_rate
+    }
+
+    /// Time the output takes to show a frame in its current mode
+    fn refresh_period(&self) -> Option<Duration> {
+        (self.refresh_rate > 0).then(|| Duration::from_secs(1000) / self.refresh_rate)
+    }
+
+    /// Follow the refresh rate of the output's current mode, returns whether
+    /// it changed
+    pub fn set_refresh_rate(&mut self, info: &OutputInfo) -> bool {
+        let refresh_rate = refresh_rate(info);
+        if self.refresh_rate == refresh_rate {
+            return false;
+        }
+        self.refresh_rate = refresh_rate;
+        tracing::debug!(
+            "{} refreshes at {:.2} Hz",
+            self.name,
+            refresh_rate as f64 / 1000.0
+        );
+        true
+    }
+
+    /// The compositor asked for the next frame, see `frame_pending`
+    pub fn frame_done(&mut self) {
+        self.frame_pending = false;
+    }
+
+    /// Turn drawing off while the output is off, returns whether it changed
+    pub fn set_powered(&mut self, powered: bool) -> bool {
+        if self.powered == powered {
+            return false;
+        }
+        self.powered = powered;
+        if powered {
+            // The callback of the last frame before the output went dark may
+            // never come
+            self.frame_pending = false;
+        }
+        true
+    }
+
+    /// Time between animation ticks, None while nothing on the layer changes
+    /// on its own
+    ///
+    /// Ticks come no faster than the models change, see
+    /// `Render::update_interval`, nor than the framerate, a framerate of 0
+    /// leaves the pace to the tickrate. A framerate above the output's
+    /// refresh rate is capped to it, the frames in between would never show.
+    /// Mirrors show the source's state and frozen layers keep theirs.
+    fn tick_interval(&self, wallpaper: &Pipelines) -> Option<Duration> {
+        if self.mirror_of.is_some() || self.is_frozen() {
+            return None;
+        }
+        let models = wallpaper.update_interval()?;
+        let tick = self.tickrate.interval()?;
+        let frame = match self.framerate {
+            Rate::Every(interval) => interval.max(self.refresh_period().unwrap_or_default()),
+            Rate::Compositor | Rate::Never => Duration::ZERO,
+        };
+        Some(models.max(tick).max(frame))
+    }
+
+    /// When the layer should be drawn next, None if it waits to be damaged
+    /// or for a frame callback
+    ///
+    /// The main loop sleeps until the earliest of these, see
+    /// `Client::next_deadline`.
+    pub fn next_frame(&self) -> Option<Instant> {
+        if !self.configured || !self.powered || self.frame_pending {
+            return None;
+        }
+        if let Some(retry_at) = self.retry_at {
+            return Some(retry_at);
         }
+        let wallpaper = self.wallpaper.lock().unwrap();
+        if wallpaper.is_empty() {
+            return None;
+        }
+        if self.damaged || (self.mirror_of.is_none() && self.overview.is_fading()) {
+            return Some(Instant::now());
+        }
+        self.tick_interval(&wallpaper)?;
+        Some(self.next_tick)
     }
 
     pub fn configure(&mut self, adapter: &Adapter, device: &Device) {
         self.configured = true;
         self.damaged = true;
         let capability = self.surface.get_capabilities(adapter);
+
+        // Render through an sRGB view so blending happens in linear space and
+        // the hardware encodes the result, regardless of the swapchain format.
+        // Any view format other than the models' goes through a render target,
+        // see `render_target::FORMAT`
+        let format = capability
+            .formats
+            .iter()
+            .copied()
+            .find(|format| *format == TextureFormat::Bgra8UnormSrgb)
+            .or_else(|| capability.formats.iter().copied().find(|f| f.is_srgb()))
+            .unwrap_or(capability.formats[0]);
+        self.view_format = format.add_srgb_suffix();
+        let alpha_mode = self.choose_alpha_mode(&capability.alpha_modes);
+
+        // Copying frames out for screenshots needs the surface to be readable
+        let usage =
+            TextureUsages::RENDER_ATTACHMENT | (capability.usages & TextureUsages::COPY_SRC);
         let config = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: capability.formats[0],
-            view_formats: capability.formats,
-            alpha_mode: CompositeAlphaMode::Auto,
-            width: self.width,
-            height: self.height,
+            usage,
+            format,
+            view_formats: vec![self.view_format],
+            alpha_mode,
+            width: self.pixel_width(),
+            height: self.pixel_height(),
             desired_maximum_frame_latency: 2,
             present_mode: PresentMode::Mailbox,
         };
 
         // Configure the surface with the new configuration
         self.surface.configure(device, &config);
+        self.surface_config = Some(config);
+        self.layer.wl_surface().set_buffer_scale(self.scale);
+        // Recreated for the new size and format on the next draw
+        self.render_target = None;
     }
 
-    pub fn set_size(&mut self, width: u32, height: u32) {
-        if self.width == width && self.height == height {
-            println!("No size change for layer {}", self.name);
+    /// Create a new wgpu surface for the layer, e.g. after the GPU was lost
+    ///
+    /// The layer has to be configured again before it can draw.
+    pub fn recreate_surface(&mut self, instance: &Instance) {
+        let Some(backend) = self.layer.wl_surface().backend().upgrade() else {
             return;
+        };
+        self.surface = create_surface(instance, &backend, &self.layer);
+        self.surface_config = None;
+        self.render_target = None;
+    }
+
+    /// Pick how the compositor should treat the alpha channel
+    ///
+    /// Layers blend with `ALPHA_BLENDING`, whose alpha component is "over",
+    /// so drawing onto black cleared to `background_alpha` leaves premultiplied
+    /// colors in the surface. Opaque wallpapers ask for an opaque surface so
+    /// the compositor can skip blending.
+    fn choose_alpha_mode(&self, supported: &[CompositeAlphaMode]) -> CompositeAlphaMode {
+        let preferred = if self.is_transparent() {
+            CompositeAlphaMode::PreMultiplied
+        } else {
+            CompositeAlphaMode::Opaque
+        };
+        if supported.contains(&preferred) {
+            return preferred;
+        }
+
+        if self.is_transparent() {
+            tracing::warn!(
+                "Surface for {} does not support premultiplied alpha, the wallpaper will be opaque",
+                self.name
+            );
+        }
+        CompositeAlphaMode::Auto
+    }
+
+    /// Alpha the surface is cleared to before drawing
+    pub fn background_alpha(&self) -> f32 {
+        self.background_alpha
+    }
+
+    /// Whether the wallpaper lets the desktop behind it show through
+    pub fn is_transparent(&self) -> bool {
+        self.background_alpha < 1.0
+    }
+
+    /// Update the background alpha, returns whether the surface has to be
+    /// configured again because it switched between opaque and transparent
+    pub fn set_background_alpha(&mut self, alpha: f32) -> bool {
+        let was_transparent = self.is_transparent();
+        self.background_alpha = alpha.clamp(0.0, 1.0);
+        self.damaged = true;
+        was_transparent != self.is_transparent()
+    }
+
+    /// Update the logical size, returns whether it changed
+    pub fn set_size(&mut self, width: u32, height: u32) -> bool {
+        if self.width == width && self.height == height {
+            tracing::trace!("No size change for layer {}", self.name);
+            return false;
         }
         self.width = width;
         self.height = height;
         self.damaged = true;
+        true
     }
 
-    /// Set the frames per update rate based on the wallpaper's framerate
-    /// This controls how often the wallpaper is redrawn
-    pub fn set_framerate(&mut self, framerate: i32) {
-        // Default system refresh rate assumed to be 60 Hz
-        const SYSTEM_FPS: u32 = 60;
-
-        if framerate < 0 {
-            // Any negative value: Use compositor-driven timing
-            // This means we'll redraw every time the compositor requests a frame
-            self.frames_per_update = 0; // Special value - will trigger on frame callbacks
-            println!("Layer {} set to compositor-driven framerate", self.name);
-        } else if framerate == 0 {
-            // If framerate is 0, only redraw on demand (never automatically)
-            self.frames_per_update = u32::MAX;
-            println!(
-                "Layer {} set to static mode (no automatic updates)",
-                self.name
-            );
-        } else if framerate >= SYSTEM_FPS as i32 {
-            // If framerate is >= system rate, redraw every frame
-            self.frames_per_update = 1;
-            println!(
-                "Layer {} set to {} FPS (redraw every frame)",
-                self.name, framerate
-            );
-        } else {
-            // Calculate how many system frames should pass before we redraw
-            // For example: system fps = 60, wallpaper framerate = 30 => redraw every 2 frames
-            self.frames_per_update = SYSTEM_FPS / framerate as u32;
-            println!(
-                "Layer {} set to {} FPS (redraw every {} frames)",
-                self.name, framerate, self.frames_per_update
-            );
+    /// Update the buffer scale, returns whether it changed
+    pub fn set_scale(&mut self, scale: i32) -> bool {
+        let scale = scale.max(1);
+        if self.scale == scale {
+            return false;
         }
+        self.scale = scale;
+        self.damaged = true;
+        true
     }
 
-    /// Set the ticks per update rate based on the wallpaper's tickrate
-    /// This controls how often animations and logic are updated
-    pub fn set_tickrate(&mut self, tickrate: i32) {
-        // Default system update rate assumed to be 60 Hz
-        const SYSTEM_TPS: u32 = 60;
-
-        if tickrate < 0 {
-            // Any negative value: Use compositor-driven timing for animation updates
-            // This typically means update animations on every frame callback
-            self.ticks_per_update = 0; // Special value - will update on each frame callback
-            println!(
-                "Layer {} set to compositor-driven animation rate",
-                self.name
-            );
-        } else if tickrate == 0 {
-            // If tickrate is 0, never update animations automatically
-            self.ticks_per_update = u32::MAX;
-            println!(
-                "Layer {} set to static animation mode (no updates)",
-                self.name
-            );
-        } else if tickrate >= SYSTEM_TPS as i32 {
-            // If tickrate is >= system rate, update every frame
-            self.ticks_per_update = 1;
-            println!(
-                "Layer {} set to {} TPS (update every frame)",
-                self.name, tickrate
-            );
-        } else {
-            // Calculate how many system frames should pass before we update animations
-            // For example: system tps = 60, wallpaper tickrate = 15 => update every 4 frames
-            self.ticks_per_update = SYSTEM_TPS / tickrate as u32;
-            println!(
-                "Layer {} set to {} TPS (update every {} frames)",
-                self.name, tickrate, self.ticks_per_update
-            );
+    /// Width of the surface in physical pixels
+    pub fn pixel_width(&self) -> u32 {
+        self.width * self.scale as u32
+    }
+
+    /// Height of the surface in physical pixels
+    pub fn pixel_height(&self) -> u32 {
+        self.height * self.scale as u32
+    }
+
+    /// Update the render scale, clamped to `RENDER_SCALE_RANGE`, returns
+    /// whether it changed
+    pub fn set_render_scale(&mut self, scale: f32) -> bool {
+        let scale = scale.clamp(RENDER_SCALE_RANGE.0, RENDER_SCALE_RANGE.1);
+        if self.render_scale == scale {
+            return false;
         }
+        self.render_scale = scale;
+        self.damaged = true;
+        true
     }
 
-    pub fn draw(&mut self, qh: &QueueHandle<Client>, device: &Device, queue: &Queue) {
-        // Increment frame counter for rendering
-        self.frame_counter = (self.frame_counter + 1) % 6000; // Avoid overflow, max ~1 minute at 100fps
+    /// Resolution the wallpaper is drawn at relative to the surface
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
 
-        // Increment tick counter for animations
-        self.tick_counter = (self.tick_counter + 1) % 6000; // Avoid overflow, max ~1 minute at 100fps
+    /// Update the display adjustment, returns whether it changed
+    pub fn set_adjustment(&mut self, adjustment: DisplayAdjustment) -> bool {
+        if self.adjustment == adjustment {
+            return false;
+        }
+        self.adjustment = adjustment;
+        self.damaged = true;
+        true
+    }
 
-        // Handle special cases for compositor-driven timing (frames_per_update = 0)
-        let should_redraw = if self.frames_per_update == 0 {
-            // For compositor-driven timing, we'll decide on redraw through
-            // the frame() callback from CompositorHandler instead of counter
-            false
-        } else {
-            // Regular timing - check frame counter against update interval
-            self.frame_counter % self.frames_per_update == 0
-        };
+    /// Color adjustment applied when presenting the wallpaper
+    pub fn adjustment(&self) -> DisplayAdjustment {
+        self.adjustment
+    }
 
-        // Similarly for animation updates
-        let update_animations = if self.ticks_per_update == 0 {
-            // For compositor-driven animation updates
-            true // Always update on frame callback
-        } else if self.ticks_per_update == u32::MAX {
-            // No animation updates
-            false
-        } else {
-            // Regular timing - check tick counter
-            self.tick_counter % self.ticks_per_update == 0
-        };
+    /// Width the models draw at in pixels, the surface width times the render scale
+    pub fn render_width(&self) -> u32 {
+        ((self.pixel_width() as f32 * self.render_scale).round() as u32).max(1)
+    }
 
-        // Mark as damaged if we should redraw or if animations were updated
-        if should_redraw || (update_animations && self.ticks_per_update < u32::MAX) {
+    /// Height the models draw at in pixels, the surface height times the render scale
+    pub fn render_height(&self) -> u32 {
+        ((self.pixel_height() as f32 * self.render_scale).round() as u32).max(1)
+    }
+
+    /// Let models that render at output resolution catch up with the
+    /// current pixel size, called after resizes and when pipelines change
+    ///
+    /// Mirrors leave the models at the size of their source.
+    pub fn resize_models(&mut self, device: &Device, queue: &Queue) {
+        if !self.configured || self.mirror_of.is_some() {
+            return;
+        }
+        let (width, height) = (self.render_width(), self.render_height());
+        let scale = self.scale as f32 * self.render_scale;
+        let mut wallpaper = self.wallpaper.lock().unwrap();
+        for model in wallpaper.iter_mut() {
+            model.resize(device, queue, width, height, scale);
+        }
+        wallpaper.resize_backdrop(device, width, height);
+        self.damaged = true;
+    }
+
+    /// Set how often the wallpaper is redrawn from its manifest's framerate,
+    /// negative to follow the compositor and 0 for only when damaged
+    pub fn set_framerate(&mut self, framerate: i32) {
+        self.framerate = Rate::new(framerate);
+        tracing::debug!("Layer {} redraws {:?}", self.name, self.framerate);
+    }
+
+    /// Set how often animations advance from the manifest's tickrate,
+    /// negative to follow the compositor and 0 for never
+    pub fn set_tickrate(&mut self, tickrate: i32) {
+        self.tickrate = Rate::new(tickrate);
+        // A new wallpaper joins the shared clock on its first tick
+        self.animation_time = None;
+        self.next_tick = Instant::now();
+        tracing::debug!("Layer {} ticks {:?}", self.name, self.tickrate);
+    }
+
+    /// Run the animations `phase` seconds behind the shared clock, from the
+    /// manifest
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase;
+    }
+
+    /// Whether the layer should be drawn now, see `next_frame`
+    pub fn is_due(&self) -> bool {
+        self.next_frame().is_some_and(|next| next <= Instant::now())
+    }
+
+    /// Stop or resume animating, e.g. while the user is idle
+    pub fn set_paused(&mut self, paused: bool) {
+        let was_frozen = self.is_frozen();
+        self.paused = paused;
+        self.unfreeze(was_frozen);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Show a single still frame regardless of the wallpaper's framerate
+    pub fn set_still(&mut self, still: bool) {
+        let was_frozen = self.is_frozen();
+        self.still = still;
+        self.unfreeze(was_frozen);
+    }
+
+    /// Whether animations stand still, paused or by choice
+    fn is_frozen(&self) -> bool {
+        self.paused || self.still
+    }
+
+    fn unfreeze(&mut self, was_frozen: bool) {
+        if was_frozen && !self.is_frozen() {
+            // Animations catch up with the shared clock, so they are back in
+            // step with the other outputs
+            self.next_tick = Instant::now();
             self.damaged = true;
         }
+    }
 
-        if !self.damaged || self.wallpaper.is_empty() {
-            return;
+    /// Draw a frame right away and return a copy of it
+    pub fn capture(
+        &mut self,
+        qh: &QueueHandle<Client>,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<RgbaImage, String> {
+        if !self.configured {
+            return Err(format!("{} is not configured yet", self.name));
+        }
+        self.capturing = true;
+        self.damaged = true;
+        self.draw(qh, device, queue);
+        self.capturing = false;
+        self.captured
+            .take()
+            .unwrap_or_else(|| Err(format!("Nothing was drawn on {}", self.name)))
+    }
+
+    /// Draw the wallpaper if it is due or damaged, returns whether a frame was presented
+    pub fn draw(&mut self, qh: &QueueHandle<Client>, device: &Device, queue: &Queue) -> bool {
+        let now = Instant::now();
+        // Locked through a clone so the layer itself stays mutable while drawing
+        let wallpaper = self.wallpaper.clone();
+        let mut wallpaper = wallpaper.lock().unwrap();
+
+        let tick = self
+            .tick_interval(&wallpaper)
+            .filter(|_| now >= self.next_tick);
+        let update_animations = tick.is_some();
+        if update_animations {
+            self.damaged = true;
+        }
+
+        // Fade the overview, mirrors show the one of their source
+        if self.configured && self.mirror_of.is_none() && !wallpaper.is_empty() {
+            let models = wallpaper.len();
+            let size = (
+                self.render_width(),
+                self.render_height(),
+                self.scale as f32 * self.render_scale,
+            );
+            if self.overview.update(device, queue, &mut wallpaper, size) {
+                self.damaged = true;
+            }
+            if wallpaper.len() != models {
+                self.profiler = Profiler::new(device, queue, wallpaper.len());
+            }
+        }
+
+        if !self.damaged || wallpaper.is_empty() {
+            return false;
         }
 
         self.damaged = false;
+        self.retry_at = None;
+        let _span = tracing::trace_span!("draw", output = %self.name).entered();
+        let started = Instant::now();
 
         // Get a texture from the surface to render to
         let surface_texture = match self.surface.get_current_texture() {
             Ok(texture) => texture,
+            Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                // The swapchain no longer matches the surface, rebuild it and
+                // draw again on the next frame
+                tracing::warn!("Surface for {} was lost, reconfiguring it", self.name);
+                if let Some(config) = &self.surface_config {
+                    self.surface.configure(device, config);
+                }
+                self.damaged = true;
+                self.retry_at = Some(Instant::now() + RETRY_DELAY);
+                self.stats.record_dropped();
+                return false;
+            }
+            Err(SurfaceError::Timeout) => {
+                self.damaged = true;
+                self.retry_at = Some(Instant::now() + RETRY_DELAY);
+                self.stats.record_dropped();
+                return false;
+            }
             Err(e) => {
-                eprintln!("Failed to acquire next swapchain texture: {e:?}");
-                return;
+                tracing::error!(
+                    "Failed to acquire next swapchain texture for {}: {e}",
+                    self.name
+                );
+                self.stats.record_dropped();
+                return false;
             }
         };
 
         // Create a view of the texture that we'll render to
         let texture_view = surface_texture
             .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+            .create_view(&wgpu::TextureViewDescriptor {
+                format: Some(self.view_format),
+                ..Default::default()
+            });
+
+        // A validation error of the frame is caught here instead of taking
+        // the device down, see `drop_failed_layer`. wgpu keeps one stack of
+        // scopes per device for every thread, so only the render thread
+        // pushes them, and errors of loads running meanwhile land here too.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        // Write timestamps between the models, unless the last ones are still being read
+        let timestamps = self.profiler.begin_frame(device).cloned();
 
         // Create a command encoder to record commands
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Texture Renderer Encoder"),
         });
 
+        // Draw into an intermediate texture when rendering at another
+        // resolution, when an effect reads the layers below it, when the
+        // colors are adjusted or when the surface has another format than
+        // the models' pipelines, it is scaled onto the surface afterwards
+        let backdrop = wallpaper.backdrop().cloned();
+        if self.render_scale == 1.0
+            && backdrop.is_none()
+            && self.adjustment.is_identity()
+            && self.view_format == render_target::FORMAT
+        {
+            self.render_target = None;
+        } else if self.render_target.is_none() {
+            self.render_target = Some(RenderTarget::new(
+                device,
+                self.render_width(),
+                self.render_height(),
+                self.view_format,
+            ));
+        }
+        let target_view = self
+            .render_target
+            .as_ref()
+            .map_or(&texture_view, RenderTarget::view);
+
         // Create the render pass
         {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Texture Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
+            // Premultiplied black, see `choose_alpha_mode`
+            let clear = wgpu::LoadOp::Clear(wgpu::Color {
+                a: self.background_alpha as f64,
+                ..wgpu::Color::BLACK
+            });
+            // Ended early for work outside of it, see below
+            let mut render_pass = begin_pass(&mut encoder, target_view, clear).forget_lifetime();
+
+            // Advance the animations to the shared clock at the tick that is
+            // due, the same on every output ticking at this rate
+            let time = tick.map_or(Duration::ZERO, |interval| {
+                clock::time(clock::tick_before(now, interval), self.phase)
             });
+            let dt = time.saturating_sub(self.animation_time.unwrap_or_default());
 
-            // Calculate real elapsed time since last animation update
-            let now = Instant::now();
-            let dt = now.duration_since(self.last_animation_update);
+            if let Some(queries) = &timestamps {
+                render_pass.write_timestamp(queries, 0);
+            }
 
             // Update and render all pipeline objects
-            for renderer in self.wallpaper.iter_mut() {
+            for (index, renderer) in wallpaper.iter_mut().enumerate() {
+                let model_started = Instant::now();
                 // Update animated textures and other objects that need pre-render updates
                 if update_animations {
                     // First call pre_render to do any necessary setup
@@ -330,11 +761,11 @@ impl WallpaperLayer {
                         .as_any()
                         .downcast_ref::<crate::renderer::models::effect::EffectModel>(
                     ) {
-                        // Call the effect's update_time method if it's animated
+                        // Call the effect's set_time method if it's animated
                         if effect.is_animated() {
                             // Get and display the effect name more frequently
                             if effect.current_time < 0.5 || (effect.current_time % 5.0 < 0.1) {
-                                println!("Rendering effect layer: {}", self.name);
+                                tracing::trace!("Rendering effect layer: {}", self.name);
                             }
 
                             // Here we need to use a mutable reference, so we'll have to downcast again
@@ -344,41 +775,227 @@ impl WallpaperLayer {
                             ) {
                                 // Always update effect time to ensure animations work
                                 // This ensures the shader gets time updates even if animations are disabled
-                                effect_mut.update_time(dt, queue);
-                                
-                                // Force damage to ensure continuous redraw for wave effect debugging
-                                if self.name.contains("effect-test") && self.frame_counter % 5 == 0 {
-                                    self.damaged = true;
-                                    println!("Forcing redraw for wave effect test");
-                                }
+                                effect_mut.set_time(time, queue);
                             }
                         }
                     }
                 }
 
-                render_pass.set_pipeline(&renderer.pipeline());
-                render_pass.set_bind_group(0, Some(&*renderer.bindgroup()), &[]);
-                render_pass.draw(0..6, 0..1); // Draw full-screen quad (6 vertices)
+                // Effects that read the layers below them sample a copy of
+                // what was drawn so far, and multi-pass effects render into
+                // their own textures, both between render passes
+                let capture = match (&self.render_target, &backdrop) {
+                    (Some(target), Some(backdrop)) if renderer.reads_backdrop() => {
+                        Some((target, backdrop))
+                    }
+                    _ => None,
+                };
+                if capture.is_some() || renderer.has_passes() {
+                    drop(render_pass);
+                    if let Some((target, backdrop)) = capture {
+                        target.capture(&mut encoder, backdrop);
+                    }
+                    renderer.encode_passes(&mut encoder);
+                    render_pass =
+                        begin_pass(&mut encoder, target_view, wgpu::LoadOp::Load).forget_lifetime();
+                }
+
+                renderer.draw(&mut render_pass);
+                self.profiler.record_cpu(index, model_started.elapsed());
+                if let Some(queries) = &timestamps {
+                    render_pass.write_timestamp(queries, index as u32 + 1);
+                }
             }
 
             // Update the last animation time if animations were updated
-            if update_animations {
-                self.last_animation_update = now;
+            if let Some(interval) = tick {
+                self.animation_time = Some(time);
+                self.next_tick = clock::next_tick(now, interval);
             }
         }
 
+        if let Some(target) = &self.render_target {
+            target.write_adjustment(queue, &self.adjustment);
+            target.blit(&mut encoder, &texture_view);
+        }
+        if timestamps.is_some() {
+            self.profiler.resolve(&mut encoder);
+        }
+        let screenshot = std::mem::take(&mut self.capturing)
+            .then(|| Screenshot::copy(device, &mut encoder, &surface_texture.texture));
+
         // Submit the commands to the GPU queue
         queue.submit(Some(encoder.finish()));
+        let error = pollster::block_on(device.pop_error_scope());
+        self.profiler.end_frame();
 
         // Present the rendered image to the screen
         surface_texture.present();
-
-        self.layer
-            .wl_surface()
-            .damage_buffer(0, 0, self.width as i32, self.height as i32);
+        if let Some(screenshot) = screenshot {
+            self.captured = Some(screenshot.and_then(|screenshot| screenshot.read(device)));
+        }
+        if let Some(error) = error {
+            self.drop_failed_layer(&mut wallpaper, device, queue, error);
+        }
+        let interval = self.get_recommended_update_interval();
+        // Redraws of a damaged still wallpaper are not late frames
+        self.stats.record_frame(started, tick.and(interval));
+        self.profiler.check_budget(
+            &self.name,
+            interval.unwrap_or(DEFAULT_FRAME_BUDGET),
+            wallpaper.labels(),
+        );
+
+        self.layer.wl_surface().damage_buffer(
+            0,
+            0,
+            self.pixel_width() as i32,
+            self.pixel_height() as i32,
+        );
         self.layer
             .wl_surface()
             .frame(qh, self.layer.wl_surface().clone());
         self.layer.commit();
+        self.frame_pending = true;
+        true
+    }
+}
+
+impl WallpaperLayer {
+    /// Drop the model a validation error of the last frame came from, so a
+    /// broken layer leaves the other layers and outputs drawing
+    ///
+    /// wgpu names the resources involved by their labels, which end with
+    /// the name of the manifest layer for most models. An error naming no
+    /// layer is only reported.
+    fn drop_failed_layer(
+        &mut self,
+        wallpaper: &mut Pipelines,
+        device: &Device,
+        queue: &Queue,
+        error: wgpu::Error,
+    ) {
+        let message = error.to_string();
+        // The longest name, a layer "sky" must not take the blame for
+        // "night_sky". Resources are labelled per pipelines, see
+        // `Pipelines::gpu_label`, so a layer of the same name on another
+        // output or in a load is not blamed either.
+        let label = wallpaper
+            .labels()
+            .filter(|label| message.contains(&format!("{}'", wallpaper.gpu_label(label))))
+            .max_by_key(|label| label.len())
+            .map(str::to_string);
+        let Some(label) = label else {
+            tracing::error!(
+                "A frame on {} or a load running meanwhile failed on the GPU: {message}",
+                self.name
+            );
+            self.stats.record_gpu_error(message);
+            return;
+        };
+
+        tracing::error!(
+            "Dropped layer {label} on {}, it failed on the GPU: {message}",
+            self.name
+        );
+        wallpaper.remove_model(&label);
+        self.profiler = Profiler::new(device, queue, wallpaper.len());
+        self.stats.record_gpu_error(format!("{label} dropped: {message}"));
+        self.damaged = true;
+    }
+}
+
+impl Drop for WallpaperLayer {
+    fn drop(&mut self) {
+        if let Some(power) = &self.power {
+            power.destroy();
+        }
+    }
+}
+
+/// Begin a pass drawing the models into `view`
+fn begin_pass<'a>(
+    encoder: &'a mut CommandEncoder,
+    view: &TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Texture Render Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
+
+/// Name identifying an output across reconnects, like `DP-1`
+///
+/// The name comes from wl_output version 4 or xdg-output. Compositors that
+/// support neither only tell make and model, which two identical monitors
+/// share, so the global id is appended to keep the names apart.
+fn output_name(info: &OutputInfo) -> String {
+    if let Some(name) = &info.name {
+        return name.clone();
+    }
+    let make_model = format!("{} {}", info.make, info.model);
+    let make_model = make_model.trim();
+    if make_model.is_empty() {
+        format!("output-{}", info.id)
+    } else {
+        format!("{make_model}-{}", info.id)
+    }
+}
+
+/// Refresh rate of an output's current mode in mHz, 0 if unknown
+fn refresh_rate(info: &OutputInfo) -> u32 {
+    info.modes
+        .iter()
+        .find(|mode| mode.current)
+        .map_or(0, |mode| mode.refresh_rate.max(0) as u32)
+}
+
+/// Size of an output in pixels as it is oriented, from its current mode
+pub fn output_pixels(info: &OutputInfo) -> (u32, u32) {
+    let (width, height) = info
+        .modes
+        .iter()
+        .find(|mode| mode.current)
+        .map(|mode| mode.dimensions)
+        .unwrap_or_default();
+    let (width, height) = (width.max(0) as u32, height.max(0) as u32);
+    match info.transform {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+            (height, width)
+        }
+        _ => (width, height),
+    }
+}
+
+/// Create a wgpu surface drawing into the layer's Wayland surface
+fn create_surface(
+    instance: &Instance,
+    backend: &Backend,
+    layer: &LayerSurface,
+) -> Surface<'static> {
+    // SAFETY: the display outlives the surface, layers are dropped before
+    // their connection
+    unsafe {
+        instance
+            .create_surface_unsafe(SurfaceTargetUnsafe::RawHandle {
+                raw_display_handle: RawDisplayHandle::Wayland(WaylandDisplayHandle::new(
+                    NonNull::new(backend.display_ptr() as *mut _).unwrap(),
+                )),
+                raw_window_handle: RawWindowHandle::Wayland(WaylandWindowHandle::new(
+                    NonNull::new(layer.wl_surface().id().as_ptr() as *mut _).unwrap(),
+                )),
+            })
+            .unwrap()
     }
 }
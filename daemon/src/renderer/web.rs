@@ -0,0 +1,452 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::process::CommandExt,
+    },
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+};
+
+use base64::Engine;
+use common::manifest::WebContent;
+use image::RgbaImage;
+use serde_json::{json, Value};
+
+use super::pipeline::PointerInput;
+
+/// Browsers looked up in `PATH` when `WLRS_BROWSER` is not set, any build
+/// of Chromium with the new headless mode works
+const BROWSERS: [&str; 6] = [
+    "chromium",
+    "chromium-browser",
+    "google-chrome-stable",
+    "google-chrome",
+    "brave",
+    "microsoft-edge-stable",
+];
+
+/// A page rendered offscreen by a headless browser
+///
+/// The browser is driven over the DevTools protocol on a pair of pipes, see
+/// `--remote-debugging-pipe`, and streams frames whenever the page changes.
+/// Each view runs its own browser with a throwaway profile, stopped when the
+/// view is dropped.
+#[derive(Debug)]
+pub struct WebView {
+    url: String,
+    content: WebContent,
+    connection: Arc<Connection>,
+    state: Arc<Mutex<ViewState>>,
+    browser: Child,
+    profile: PathBuf,
+}
+
+#[derive(Debug, Default)]
+struct ViewState {
+    /// Latest frame of the page
+    frame: Option<Arc<RgbaImage>>,
+    /// Incremented with every frame
+    serial: u64,
+    /// Size of the viewport in logical pixels, and the scale of the output
+    viewport: Option<(u32, u32, f32)>,
+}
+
+/// Writing end of the DevTools pipe
+#[derive(Debug)]
+struct Connection {
+    pipe: Mutex<File>,
+    next_id: AtomicU64,
+    /// Session of the page, set once the browser attached to it
+    session: OnceLock<String>,
+}
+
+impl Connection {
+    /// Send a command, to the page's session if `to_page`, returning its id
+    fn send(&self, method: &str, params: Value, to_page: bool) -> io::Result<u64> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut message = json!({ "id": id, "method": method, "params": params });
+        if to_page {
+            let session = self.session.get().ok_or(io::ErrorKind::NotConnected)?;
+            message["sessionId"] = session.as_str().into();
+        }
+        let mut pipe = self.pipe.lock().unwrap();
+        // Messages are separated by a NUL byte
+        pipe.write_all(format!("{message}\0").as_bytes())?;
+        Ok(id)
+    }
+
+    /// Send a command to the page, if it is attached already
+    fn send_to_page(&self, method: &str, params: Value) {
+        if self.session.get().is_some() {
+            if let Err(e) = self.send(method, params, true) {
+                tracing::debug!("Failed to send {method} to the browser: {e}");
+            }
+        }
+    }
+}
+
+impl WebView {
+    /// Start a browser showing `url`
+    pub fn open(url: &str, content: &WebContent) -> Result<Self, String> {
+        let executable = find_browser()
+            .ok_or("No browser found, install Chromium or set WLRS_BROWSER to one")?;
+
+        static VIEWS: AtomicU64 = AtomicU64::new(0);
+        let profile = env::temp_dir().join(format!(
+            "wlrs-web-{}-{}",
+            std::process::id(),
+            VIEWS.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        // The browser reads commands from fd 3 and writes replies to fd 4
+        let (commands_read, commands_write) = pipe().map_err(|e| e.to_string())?;
+        let (replies_read, replies_write) = pipe().map_err(|e| e.to_string())?;
+        let (commands_fd, replies_fd) = (commands_read.as_raw_fd(), replies_write.as_raw_fd());
+
+        let mut command = Command::new(&executable);
+        command
+            .args([
+                "--headless=new",
+                "--remote-debugging-pipe",
+                "--no-first-run",
+                "--no-default-browser-check",
+                "--disable-extensions",
+                "--hide-scrollbars",
+                "--mute-audio",
+            ])
+            .arg(format!("--user-data-dir={}", profile.display()))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0);
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                // Move the replies end out of the way if it sits on fd 3
+                let replies_fd = if replies_fd == 3 {
+                    libc::dup(replies_fd)
+                } else {
+                    replies_fd
+                };
+                if libc::dup2(commands_fd, 3) < 0 || libc::dup2(replies_fd, 4) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // dup2 keeps close-on-exec when an end already was fd 3 or 4
+                for fd in [3, 4] {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        let browser = command
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {e}", executable.display()))?;
+        drop((commands_read, replies_write));
+        tracing::info!("Started {} for {url}", executable.display());
+
+        let connection = Arc::new(Connection {
+            pipe: Mutex::new(File::from(commands_write)),
+            next_id: AtomicU64::new(0),
+            session: OnceLock::new(),
+        });
+        let state = Arc::new(Mutex::new(ViewState::default()));
+
+        let reader = BufReader::new(File::from(replies_read));
+        let session = Session {
+            url: url.to_string(),
+            content: content.clone(),
+            connection: connection.clone(),
+            state: state.clone(),
+        };
+        thread::Builder::new()
+            .name("web".to_string())
+            .spawn(move || session.run(reader))
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            url: url.to_string(),
+            content: content.clone(),
+            connection,
+            state,
+            browser,
+            profile,
+        })
+    }
+
+    /// Latest frame if it is newer than `serial`, along with its serial
+    pub fn frame_since(&self, serial: u64) -> Option<(u64, Arc<RgbaImage>)> {
+        let state = self.state.lock().unwrap();
+        if state.serial == serial {
+            return None;
+        }
+        state.frame.clone().map(|frame| (state.serial, frame))
+    }
+
+    /// Lay the page out for an output of `width` by `height` pixels
+    pub fn resize(&self, width: u32, height: u32, scale: f32) {
+        let viewport = (
+            (width as f32 / scale).round() as u32,
+            (height as f32 / scale).round() as u32,
+            scale,
+        );
+        let mut state = self.state.lock().unwrap();
+        if state.viewport == Some(viewport) {
+            return;
+        }
+        state.viewport = Some(viewport);
+        drop(state);
+        self.connection
+            .send_to_page("Emulation.setDeviceMetricsOverride", metrics(viewport));
+    }
+
+    /// Forward pointer input over the wallpaper, if the manifest allows it
+    pub fn pointer(&self, input: &PointerInput) {
+        if !self.content.input {
+            return;
+        }
+        let (kind, x, y, button, clicks) = match *input {
+            PointerInput::Motion { x, y } => ("mouseMoved", x, y, None, 0),
+            PointerInput::Press { x, y, button } => ("mousePressed", x, y, Some(button), 1),
+            PointerInput::Release { x, y, button } => ("mouseReleased", x, y, Some(button), 1),
+            PointerInput::Scroll { x, y, dx, dy } => {
+                self.connection.send_to_page(
+                    "Input.dispatchMouseEvent",
+                    json!({ "type": "mouseWheel", "x": x, "y": y, "deltaX": dx, "deltaY": dy }),
+                );
+                return;
+            }
+        };
+        // Linux input event codes, see `linux/input-event-codes.h`
+        let button = match button {
+            None => "none",
+            Some(0x110) => "left",
+            Some(0x111) => "right",
+            Some(0x112) => "middle",
+            Some(0x113) => "back",
+            Some(0x114) => "forward",
+            Some(_) => return,
+        };
+        self.connection.send_to_page(
+            "Input.dispatchMouseEvent",
+            json!({ "type": kind, "x": x, "y": y, "button": button, "clickCount": clicks }),
+        );
+    }
+}
+
+impl Drop for WebView {
+    fn drop(&mut self) {
+        tracing::info!("Stopping the browser of {}", self.url);
+        // The browser forks renderer and GPU processes into its group
+        unsafe {
+            libc::kill(-(self.browser.id() as i32), libc::SIGTERM);
+        }
+        let _ = self.browser.wait();
+        if let Err(e) = fs::remove_dir_all(&self.profile) {
+            tracing::debug!("Failed to remove {}: {e}", self.profile.display());
+        }
+    }
+}
+
+/// Reading end of the DevTools pipe, run on a thread of its own
+struct Session {
+    url: String,
+    content: WebContent,
+    connection: Arc<Connection>,
+    state: Arc<Mutex<ViewState>>,
+}
+
+impl Session {
+    fn run(self, mut reader: BufReader<File>) {
+        if let Err(e) = self.attach(&mut reader) {
+            tracing::error!("Failed to open {} in the browser: {e}", self.url);
+            return;
+        }
+        loop {
+            match read_message(&mut reader) {
+                Ok(Some(message)) => self.handle(&message),
+                // The browser was stopped along with the view
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::warn!("Lost the browser of {}: {e}", self.url);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Open a page, lay it out and start streaming its frames
+    fn attach(&self, reader: &mut BufReader<File>) -> Result<(), String> {
+        let call = |reader: &mut BufReader<File>, method: &str, params: Value| {
+            let id = self
+                .connection
+                .send(method, params, false)
+                .map_err(|e| e.to_string())?;
+            wait_for(reader, id)
+        };
+        // Navigated once the background is set, so the page does not flash white
+        let target = call(
+            reader,
+            "Target.createTarget",
+            json!({ "url": "about:blank" }),
+        )?;
+        let target = target
+            .get("targetId")
+            .cloned()
+            .ok_or("no target id in the reply")?;
+        let session = call(
+            reader,
+            "Target.attachToTarget",
+            json!({ "targetId": target, "flatten": true }),
+        )?;
+        let session = session
+            .get("sessionId")
+            .and_then(Value::as_str)
+            .ok_or("no session id in the reply")?;
+        let _ = self.connection.session.set(session.to_string());
+
+        let viewport = self.state.lock().unwrap().viewport;
+        if let Some(viewport) = viewport {
+            self.connection
+                .send_to_page("Emulation.setDeviceMetricsOverride", metrics(viewport));
+        }
+        if self.content.transparent {
+            self.connection.send_to_page(
+                "Emulation.setDefaultBackgroundColorOverride",
+                json!({ "color": { "r": 0, "g": 0, "b": 0, "a": 0 } }),
+            );
+        }
+        self.connection
+            .send_to_page("Page.navigate", json!({ "url": self.url }));
+        // PNG keeps the transparency, JPEG is much cheaper to encode
+        let format = if self.content.transparent {
+            "png"
+        } else {
+            "jpeg"
+        };
+        self.connection.send_to_page(
+            "Page.startScreencast",
+            json!({ "format": format, "quality": 90 }),
+        );
+        Ok(())
+    }
+
+    fn handle(&self, message: &Value) {
+        if let Some(error) = message.pointer("/error/message").and_then(Value::as_str) {
+            tracing::warn!("Browser of {} refused a command: {error}", self.url);
+            return;
+        }
+        match message.get("method").and_then(Value::as_str) {
+            Some("Page.screencastFrame") => {
+                let params = message.get("params").cloned().unwrap_or_default();
+                // Frames stop until the previous one is acknowledged
+                if let Some(frame_session) = params.get("sessionId").cloned() {
+                    self.connection.send_to_page(
+                        "Page.screencastFrameAck",
+                        json!({ "sessionId": frame_session }),
+                    );
+                }
+                match params
+                    .get("data")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| "no data".to_string())
+                    .and_then(decode_frame)
+                {
+                    Ok(frame) => {
+                        let mut state = self.state.lock().unwrap();
+                        state.frame = Some(Arc::new(frame));
+                        state.serial += 1;
+                    }
+                    Err(e) => tracing::warn!("Failed to decode a frame of {}: {e}", self.url),
+                }
+            }
+            Some("Target.targetCrashed") => {
+                tracing::error!("The page {} crashed", self.url);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Device metrics of a viewport, see `WebView::resize`
+fn metrics((width, height, scale): (u32, u32, f32)) -> Value {
+    json!({
+        "width": width,
+        "height": height,
+        "deviceScaleFactor": scale,
+        "mobile": false,
+    })
+}
+
+fn decode_frame(data: &str) -> Result<RgbaImage, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| e.to_string())?;
+    image::load_from_memory(&bytes)
+        .map(|image| image.to_rgba8())
+        .map_err(|e| e.to_string())
+}
+
+/// Read the next message, None once the browser closed the pipe
+fn read_message(reader: &mut BufReader<File>) -> Result<Option<Value>, String> {
+    let mut message = Vec::new();
+    let read = reader
+        .read_until(b'\0', &mut message)
+        .map_err(|e| e.to_string())?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if message.last() == Some(&b'\0') {
+        message.pop();
+    }
+    serde_json::from_slice(&message)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// Read messages until the reply to command `id`, returning its result
+fn wait_for(reader: &mut BufReader<File>, id: u64) -> Result<Value, String> {
+    loop {
+        let message = read_message(reader)?.ok_or("the browser exited")?;
+        if message.get("id").and_then(Value::as_u64) != Some(id) {
+            continue;
+        }
+        if let Some(error) = message.pointer("/error/message").and_then(Value::as_str) {
+            return Err(error.to_string());
+        }
+        return Ok(message.get("result").cloned().unwrap_or_default());
+    }
+}
+
+/// Browser named by `WLRS_BROWSER`, or the first known one in `PATH`
+fn find_browser() -> Option<PathBuf> {
+    if let Some(browser) = env::var_os("WLRS_BROWSER") {
+        return Some(browser.into());
+    }
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path).find_map(|dir| {
+        BROWSERS
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// A pipe whose ends are closed on exec, the browser gets duplicates
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: pipe2 returned two new descriptors
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
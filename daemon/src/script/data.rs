@@ -0,0 +1,342 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, Read},
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex, Once, OnceLock},
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+use crate::config::FetchConfig;
+
+/// Window the `max_per_minute` limit counts fetches in
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Sources no script asked for in this long are dropped, e.g. those of a
+/// wallpaper that was replaced
+const FORGET_AFTER: Duration = Duration::from_secs(10 * 60);
+
+static SOURCES: OnceLock<DataSources> = OnceLock::new();
+
+/// Set the limits fetches of scripts are held to, before any script runs
+pub fn init(config: FetchConfig) {
+    if SOURCES.set(DataSources::new(config)).is_err() {
+        tracing::warn!("Data sources were used before their limits were set");
+    }
+}
+
+/// The data sources shared by every script of the daemon
+pub fn sources() -> &'static DataSources {
+    SOURCES.get_or_init(|| DataSources::new(FetchConfig::default()))
+}
+
+/// JSON documents fetched from the web for scripts
+///
+/// Scripts only ever read the latest copy, a background thread refetches
+/// each document as often as the scripts ask for, within the configured
+/// limits. Copies are kept on disk so restarts neither wait for the network
+/// nor spend the rate limit.
+pub struct DataSources {
+    config: FetchConfig,
+    agent: ureq::Agent,
+    sources: Mutex<HashMap<String, Source>>,
+    /// Signalled when a new source is added
+    wake: Condvar,
+    thread: Once,
+}
+
+/// The latest state of a source, as seen by a script
+#[derive(Debug, Clone, Default)]
+pub struct Fetched {
+    /// The document, none until the first fetch succeeded
+    pub data: Option<Arc<Value>>,
+    /// Why the last fetch failed, none once one succeeds
+    pub error: Option<String>,
+}
+
+struct Source {
+    fetched: Fetched,
+    /// Time between fetches, the shortest any script asked for
+    every: Duration,
+    /// When to fetch next
+    due: Instant,
+    /// When a script last asked for the document
+    used: Instant,
+}
+
+impl DataSources {
+    fn new(config: FetchConfig) -> Self {
+        let hosts = config.hosts.clone();
+        let local = config.local;
+        // Every connection, redirects included, goes through the resolver,
+        // so it is where hosts and addresses are checked
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(config.timeout))
+            .redirects(3)
+            .user_agent(concat!("wlrs/", env!("CARGO_PKG_VERSION")))
+            .resolver(move |addr: &str| resolve(addr, &hosts, local))
+            .build();
+
+        Self {
+            config,
+            agent,
+            sources: Mutex::new(HashMap::new()),
+            wake: Condvar::new(),
+            thread: Once::new(),
+        }
+    }
+
+    /// Get the latest copy of the JSON document at `url`, fetching it every
+    /// `every` from now on
+    ///
+    /// Fails for URLs scripts may not fetch; network and parse errors are
+    /// reported in the result instead, along with the last good copy.
+    pub fn get(&'static self, url: &str, every: Option<Duration>) -> Result<Fetched, String> {
+        let every = every
+            .unwrap_or_default()
+            .max(Duration::from_secs(self.config.min_interval));
+        let now = Instant::now();
+
+        let mut sources = self.sources.lock().unwrap();
+        if let Some(source) = sources.get_mut(url) {
+            source.used = now;
+            source.every = source.every.min(every);
+            return Ok(source.fetched.clone());
+        }
+
+        self.check(url)?;
+        let (data, due) = match load_cached(url) {
+            Some((data, age)) => (Some(Arc::new(data)), now + every.saturating_sub(age)),
+            None => (None, now),
+        };
+        let fetched = Fetched { data, error: None };
+        sources.insert(
+            url.to_string(),
+            Source {
+                fetched: fetched.clone(),
+                every,
+                due,
+                used: now,
+            },
+        );
+        drop(sources);
+
+        self.thread.call_once(|| {
+            let spawned = std::thread::Builder::new()
+                .name("fetch".to_string())
+                .spawn(move || self.run());
+            if let Err(e) = spawned {
+                tracing::error!("Failed to start fetching data for scripts: {e}");
+            }
+        });
+        self.wake.notify_one();
+        Ok(fetched)
+    }
+
+    /// Whether scripts may fetch `url` at all
+    fn check(&self, url: &str) -> Result<(), String> {
+        let request = self.agent.get(url);
+        let parsed = request
+            .request_url()
+            .map_err(|e| format!("Invalid URL {url}: {e}"))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!(
+                "Only http and https URLs can be fetched, not {url}"
+            ));
+        }
+        if !host_allowed(&self.config.hosts, parsed.host()) {
+            return Err(format!(
+                "{} is not one of the hosts scripts may fetch from",
+                parsed.host()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fetch due sources one at a time, forever
+    fn run(&self) {
+        // When the fetches of the last minute started
+        let mut recent = VecDeque::new();
+        let mut sources = self.sources.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            sources.retain(|url, source| {
+                let keep = now.duration_since(source.used) < FORGET_AFTER;
+                if !keep {
+                    tracing::debug!("No script uses {url} anymore");
+                }
+                keep
+            });
+            while recent
+                .front()
+                .is_some_and(|start| now.duration_since(*start) >= MINUTE)
+            {
+                recent.pop_front();
+            }
+
+            let due = sources
+                .iter()
+                .filter(|(_, source)| source.due <= now)
+                .min_by_key(|(_, source)| source.due)
+                .map(|(url, _)| url.clone());
+            let wait = match due {
+                Some(url) if recent.len() < self.config.max_per_minute as usize => {
+                    recent.push_back(now);
+                    drop(sources);
+                    tracing::debug!("Fetching {url} for scripts");
+                    let result = self.fetch(&url);
+                    sources = self.sources.lock().unwrap();
+
+                    if let Some(source) = sources.get_mut(&url) {
+                        source.due = Instant::now() + source.every;
+                        match result {
+                            Ok(data) => {
+                                source.fetched.data = Some(Arc::new(data));
+                                source.fetched.error = None;
+                            }
+                            Err(e) => {
+                                if source.fetched.error.as_ref() != Some(&e) {
+                                    tracing::warn!("Failed to fetch {url}: {e}");
+                                }
+                                source.fetched.error = Some(e);
+                            }
+                        }
+                    }
+                    continue;
+                }
+                // Rate limited, until the oldest fetch leaves the window
+                Some(_) => recent.front().map_or(MINUTE, |start| {
+                    MINUTE.saturating_sub(now.duration_since(*start))
+                }),
+                None => sources
+                    .values()
+                    .map(|source| source.due.saturating_duration_since(now))
+                    .min()
+                    .unwrap_or(FORGET_AFTER),
+            };
+            sources = self.wake.wait_timeout(sources, wait).unwrap().0;
+        }
+    }
+
+    fn fetch(&self, url: &str) -> Result<Value, String> {
+        let response = self
+            .agent
+            .get(url)
+            .set("Accept", "application/json")
+            .call()
+            .map_err(|e| e.to_string())?;
+
+        let mut body = String::new();
+        response
+            .into_reader()
+            .take(self.config.max_size + 1)
+            .read_to_string(&mut body)
+            .map_err(|e| e.to_string())?;
+        if body.len() as u64 > self.config.max_size {
+            return Err(format!(
+                "Response is larger than {} bytes",
+                self.config.max_size
+            ));
+        }
+
+        let data = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+        store_cached(url, &body);
+        Ok(data)
+    }
+}
+
+/// Resolve `addr`, a host and port, refusing hosts that are not allowed and,
+/// unless `local`, addresses of this machine and the local network
+fn resolve(addr: &str, hosts: &[String], local: bool) -> io::Result<Vec<SocketAddr>> {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    if !host_allowed(hosts, host) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{host} is not one of the hosts scripts may fetch from"),
+        ));
+    }
+
+    let addrs: Vec<_> = addr
+        .to_socket_addrs()?
+        .filter(|addr| local || !is_local(addr.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{host} is on the local network"),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Whether `host` is one of `hosts` or a subdomain of one, any host is
+/// allowed if there are none
+fn host_allowed(hosts: &[String], host: &str) -> bool {
+    hosts.is_empty()
+        || hosts.iter().any(|allowed| {
+            host.eq_ignore_ascii_case(allowed)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", allowed.to_ascii_lowercase()))
+        })
+}
+
+fn is_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // Shared address space of carrier-grade NAT, 100.64.0.0/10
+                || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_local(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local fc00::/7 and link local fe80::/10
+                    || ip.segments()[0] & 0xfe00 == 0xfc00
+                    || ip.segments()[0] & 0xffc0 == 0xfe80
+            }
+        },
+    }
+}
+
+fn cache_path(url: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.cache_dir().join("wlrs").join("data"))
+        .unwrap_or_else(|| PathBuf::from("/tmp/wlrs/data"))
+        .join(format!("{:016x}.json", hasher.finish()))
+}
+
+/// The copy of a document kept on disk and how old it is
+fn load_cached(url: &str) -> Option<(Value, Duration)> {
+    let path = cache_path(url);
+    let age = fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok()?
+        .elapsed()
+        .unwrap_or_default();
+    let data = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+    Some((data, age))
+}
+
+fn store_cached(url: &str, body: &str) {
+    let path = cache_path(url);
+    let stored = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, body));
+    if let Err(e) = stored {
+        tracing::debug!("Failed to keep a copy of {url} in {}: {e}", path.display());
+    }
+}
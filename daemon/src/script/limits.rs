@@ -0,0 +1,146 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use mlua::{Function, HookTriggers, Lua, VmState};
+
+use crate::config::ScriptsConfig;
+
+/// Instructions a script runs between two looks at its budget
+const CHECK_INTERVAL: u32 = 1000;
+
+static CONFIG: OnceLock<ScriptsConfig> = OnceLock::new();
+
+/// Set how much scripts may take, before any wallpaper is loaded
+pub fn init(config: ScriptsConfig) {
+    if CONFIG.set(config).is_err() {
+        tracing::warn!("A script ran before the script limits were configured");
+    }
+}
+
+fn config() -> &'static ScriptsConfig {
+    CONFIG.get_or_init(ScriptsConfig::default)
+}
+
+/// Why a call into a script failed
+#[derive(Debug)]
+pub enum ScriptError {
+    /// The script went over its budget and must not run again, says what
+    /// it went over
+    OverBudget(String),
+    /// The script raised an error, it may do better on the next tick
+    Lua(mlua::Error),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OverBudget(reason) => write!(f, "the script {reason}"),
+            Self::Lua(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<mlua::Error> for ScriptError {
+    fn from(e: mlua::Error) -> Self {
+        Self::Lua(e)
+    }
+}
+
+/// The call running in a script's Lua state, as the hook sees it
+#[derive(Debug, Default)]
+struct Call {
+    /// When the call has to be done by, None between calls
+    deadline: Option<Instant>,
+    instructions: u64,
+    /// What the call went over, once the hook stopped it
+    exceeded: Option<String>,
+}
+
+/// Time, instructions and memory a script's Lua state may use for each
+/// call into it, like the update of a tick
+///
+/// A hook runs every `CHECK_INTERVAL` instructions and raises an error in
+/// the script once it is over, which unwinds it back to `run`. It raises the
+/// error again every time it runs after that, and `sandbox` keeps `pcall`
+/// from catching it, so the script cannot go on. LuaJIT does not call hooks
+/// from compiled code, the JIT is off in `sandbox`.
+#[derive(Debug)]
+pub struct Budget {
+    call: Arc<Mutex<Call>>,
+    time: Duration,
+}
+
+impl Budget {
+    /// Watch the calls into `lua`
+    pub fn install(lua: &Lua) -> Self {
+        let config = config();
+        let time = Duration::from_millis(config.time_budget);
+        let max_instructions = config.max_instructions;
+        let max_memory = config.max_memory as usize * 1024 * 1024;
+        if let Err(e) = lua.set_memory_limit(max_memory) {
+            // Checked by the hook instead, after the fact
+            tracing::debug!("Cannot limit the memory of scripts: {e}");
+        }
+
+        let call = Arc::new(Mutex::new(Call::default()));
+        let watched = call.clone();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(CHECK_INTERVAL),
+            move |lua, _debug| {
+                let mut call = watched.lock().unwrap();
+                if let Some(exceeded) = &call.exceeded {
+                    return Err(mlua::Error::runtime(exceeded.clone()));
+                }
+                call.instructions += CHECK_INTERVAL as u64;
+                let late = call
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() > deadline);
+                let exceeded = if late {
+                    format!("took longer than {} ms", time.as_millis())
+                } else if call.instructions > max_instructions {
+                    format!("ran more than {max_instructions} instructions")
+                } else if lua.used_memory() > max_memory {
+                    format!("used more than {} MiB", max_memory / 1024 / 1024)
+                } else {
+                    return Ok(VmState::Continue);
+                };
+                call.exceeded = Some(exceeded.clone());
+                Err(mlua::Error::runtime(exceeded))
+            },
+        );
+        Self { call, time }
+    }
+
+    /// A Lua function returning what the running call went over, nil while
+    /// it is within the budget
+    pub fn exceeded(&self, lua: &Lua) -> mlua::Result<Function> {
+        let call = self.call.clone();
+        lua.create_function(move |_, ()| Ok(call.lock().unwrap().exceeded.clone()))
+    }
+
+    /// Run `f`, which calls into the script, within the budget
+    pub fn run<R>(&self, f: impl FnOnce() -> mlua::Result<R>) -> Result<R, ScriptError> {
+        *self.call.lock().unwrap() = Call {
+            deadline: Some(Instant::now() + self.time),
+            ..Call::default()
+        };
+        let result = f();
+        let exceeded = {
+            let mut call = self.call.lock().unwrap();
+            call.deadline = None;
+            call.exceeded.take()
+        };
+        match (result, exceeded) {
+            // Whatever the script made of the hook's error
+            (_, Some(reason)) => Err(ScriptError::OverBudget(reason)),
+            (Err(mlua::Error::MemoryError(_)), None) => Err(ScriptError::OverBudget(format!(
+                "used more than {} MiB",
+                config().max_memory
+            ))),
+            (result, None) => result.map_err(ScriptError::Lua),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Services the daemon offers to wallpaper scripts
+
+use std::time::Duration;
+
+use common::manifest::Permission;
+use mlua::{Lua, LuaOptions, StdLib, Table, Value};
+use serde_json::Value as JsonValue;
+
+use crate::{metrics, weather};
+
+use limits::Budget;
+
+pub mod data;
+pub mod limits;
+pub mod particles;
+pub mod thread;
+
+/// Globals of the base library that load code from files or bytecode,
+/// which can break out of the sandbox
+const UNSAFE_GLOBALS: [&str; 4] = ["dofile", "loadfile", "load", "loadstring"];
+
+/// The hook of `Budget` only runs on the main thread, mlua turns it off
+/// when a coroutine runs into it
+const UNBUDGETED_GLOBALS: [&str; 1] = ["coroutine"];
+
+/// Read-only `io` for scripts with the `file-read` permission, the library
+/// as loaded can also write files and run commands
+const READ_ONLY_IO: &str = r#"
+local open = io.open
+io.open = function(path, mode)
+    mode = mode or "r"
+    if mode ~= "r" and mode ~= "rb" then
+        error("files can only be opened for reading", 2)
+    end
+    return open(path, mode)
+end
+io.popen, io.output, io.write, io.tmpfile = nil, nil, nil, nil
+io.stdout, io.stderr = nil, nil
+"#;
+
+/// `pcall` and `xpcall` letting the error of a script over its `Budget`
+/// through, a script catching it would run on forever. Called with
+/// `Budget::exceeded`.
+const RETHROW_BUDGET: &str = r#"
+local over_budget = ...
+local raw_pcall, raw_xpcall, error = pcall, xpcall, error
+local function rethrow(ok, ...)
+    if not ok then
+        local reason = over_budget()
+        if reason then
+            error(reason, 0)
+        end
+    end
+    return ok, ...
+end
+pcall = function(...) return rethrow(raw_pcall(...)) end
+xpcall = function(...) return rethrow(raw_xpcall(...)) end
+"#;
+
+/// Leaves scripts to the interpreter, which calls the hook of `Budget`,
+/// and takes the library away again
+const JIT_OFF: &str = "jit.off() jit = nil";
+
+/// A Lua state for a wallpaper's script that can do what `permissions`
+/// allow and nothing else
+///
+/// Scripts get the base, table, string, math and bit libraries
+/// without the functions that load code, and the `wlrs` table. `file-read`
+/// adds `io` for reading, `network` lets `wlrs.fetch` through. `os`,
+/// `package`, `debug`, `ffi`, `jit` and `coroutine` are never there, a
+/// script cannot run commands or native code, or run past its budget.
+///
+/// Calls into the script should go through the state's `Budget`.
+pub fn sandbox(permissions: &[Permission]) -> mlua::Result<(Lua, Budget)> {
+    // LuaJIT loads coroutine together with base, it is taken away below
+    let mut libraries = StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::BIT | StdLib::JIT;
+    if permissions.contains(&Permission::FileRead) {
+        libraries |= StdLib::IO;
+    }
+    let lua = Lua::new_with(libraries, LuaOptions::default())?;
+
+    lua.load(JIT_OFF).set_name("jit off").exec()?;
+    let globals = lua.globals();
+    for name in UNSAFE_GLOBALS.into_iter().chain(UNBUDGETED_GLOBALS) {
+        globals.raw_set(name, Value::Nil)?;
+    }
+    // Makes bytecode, which LuaJIT does not check
+    globals
+        .get::<Table>("string")?
+        .raw_set("dump", Value::Nil)?;
+    if permissions.contains(&Permission::FileRead) {
+        lua.load(READ_ONLY_IO).set_name("read-only io").exec()?;
+    }
+    register(&lua, permissions)?;
+    let budget = Budget::install(&lua);
+    lua.load(RETHROW_BUDGET)
+        .set_name("rethrow budget")
+        .call::<()>(budget.exceeded(&lua)?)?;
+    Ok((lua, budget))
+}
+
+/// Add the `wlrs` table with the daemon's services to a script's globals
+///
+/// `wlrs.fetch(url [, every])` returns the JSON document at `url` as a Lua
+/// table, or nil until it first arrived, followed by the error of the last
+/// failed fetch. The document is fetched again in the background every
+/// `every` seconds, or as often as the config allows, for as long as the
+/// script keeps asking for it. JSON nulls become nil. Without the `network`
+/// permission it raises an error.
+///
+/// `wlrs.weather()` returns the weather outside, like `"rain"`, as the
+/// `when.weather` conditions of layers see it.
+///
+/// `wlrs.metrics()` returns the load of the system as a table with `cpu`
+/// and `memory` from 0 to 1, `net_down` and `net_up` in bytes per second and
+/// `battery` from 0 to 1, nil without a battery.
+///
+/// `wlrs.now_playing()` returns the track a media player plays as a table
+/// with `title`, `artist`, `album`, `art_url`, `length` in seconds and
+/// `playing`, false while paused, or nil without a player. It is only there
+/// in daemons built with the `mpris` feature.
+fn register(lua: &Lua, permissions: &[Permission]) -> mlua::Result<()> {
+    let network = permissions.contains(&Permission::Network);
+    let wlrs = lua.create_table()?;
+    wlrs.set(
+        "fetch",
+        lua.create_function(move |lua, (url, every): (String, Option<f64>)| {
+            if !network {
+                return Err(mlua::Error::runtime(
+                    "wlrs.fetch needs the network permission, see `needs` in the manifest",
+                ));
+            }
+            let every = every.and_then(|every| Duration::try_from_secs_f64(every).ok());
+            let fetched = data::sources()
+                .get(&url, every)
+                .map_err(mlua::Error::runtime)?;
+            let data = match &fetched.data {
+                Some(data) => to_lua(lua, data)?,
+                None => Value::Nil,
+            };
+            Ok((data, fetched.error))
+        })?,
+    )?;
+    wlrs.set(
+        "weather",
+        lua.create_function(|_, ()| Ok(weather::current().name()))?,
+    )?;
+    wlrs.set(
+        "metrics",
+        lua.create_function(|lua, ()| {
+            let metrics = metrics::current();
+            let table = lua.create_table()?;
+            table.set("cpu", metrics.cpu)?;
+            table.set("memory", metrics.memory)?;
+            table.set("net_down", metrics.net_down)?;
+            table.set("net_up", metrics.net_up)?;
+            table.set(
+                "battery",
+                (metrics.battery >= 0.0).then_some(metrics.battery),
+            )?;
+            Ok(table)
+        })?,
+    )?;
+    #[cfg(feature = "mpris")]
+    wlrs.set(
+        "now_playing",
+        lua.create_function(|lua, ()| {
+            let Some(track) = crate::mpris::current() else {
+                return Ok(None);
+            };
+            let table = lua.create_table()?;
+            table.set("title", track.title)?;
+            table.set("artist", track.artist)?;
+            table.set("album", track.album)?;
+            table.set("art_url", track.art_url)?;
+            table.set("length", track.length.map(|length| length.as_secs_f64()))?;
+            table.set("playing", track.playing)?;
+            Ok(Some(table))
+        })?,
+    )?;
+    lua.globals().set("wlrs", wlrs)
+}
+
+fn to_lua(lua: &Lua, json: &JsonValue) -> mlua::Result<Value> {
+    Ok(match json {
+        JsonValue::Null => Value::Nil,
+        JsonValue::Bool(value) => Value::Boolean(*value),
+        JsonValue::Number(value) => Value::Number(value.as_f64().unwrap_or_default()),
+        JsonValue::String(value) => Value::String(lua.create_string(value)?),
+        JsonValue::Array(items) => {
+            let table = lua.create_table_with_capacity(items.len(), 0)?;
+            for (index, item) in items.iter().enumerate() {
+                table.raw_set(index + 1, to_lua(lua, item)?)?;
+            }
+            Value::Table(table)
+        }
+        JsonValue::Object(fields) => {
+            let table = lua.create_table_with_capacity(0, fields.len())?;
+            for (key, value) in fields {
+                table.raw_set(key.as_str(), to_lua(lua, value)?)?;
+            }
+            Value::Table(table)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use limits::ScriptError;
+
+    /// Run `script` in a sandbox without permissions, within its budget
+    fn run(script: &str) -> Result<(), ScriptError> {
+        let (lua, budget) = sandbox(&[]).unwrap();
+        budget.run(|| lua.load(script).exec())
+    }
+
+    #[test]
+    fn test_budget_cannot_be_caught() {
+        for script in [
+            "while true do pcall(function() while true do end end) end",
+            "while true do xpcall(function() while true do end end, function() end) end",
+        ] {
+            assert!(
+                matches!(run(script), Err(ScriptError::OverBudget(_))),
+                "{script}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_no_coroutines() {
+        let (lua, _) = sandbox(&[]).unwrap();
+        assert!(lua.globals().get::<Value>("coroutine").unwrap().is_nil());
+    }
+
+    #[test]
+    fn test_pcall_still_catches_errors() {
+        let (lua, budget) = sandbox(&[]).unwrap();
+        let caught: (bool, String) = budget
+            .run(|| lua.load(r#"return pcall(error, "broken", 0)"#).eval())
+            .unwrap();
+        assert_eq!(caught, (false, "broken".to_string()));
+        let returned: (bool, i32) = budget
+            .run(|| {
+                lua.load("return xpcall(function(a, b) return a + b end, print, 1, 2)")
+                    .eval()
+            })
+            .unwrap();
+        assert_eq!(returned, (true, 3));
+    }
+}
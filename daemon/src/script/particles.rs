@@ -0,0 +1,169 @@
+use mlua::{Lua, MetaMethod, Table, UserData, UserDataMethods};
+
+/// A single particle in the particle system
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Particle {
+    pub position: [f32; 2], // x, y normalized coords (-1.0 to 1.0)
+    pub velocity: [f32; 2], // movement direction and speed
+    pub color: [f32; 4],    // rgba color
+    pub size: f32,          // particle size
+    pub rotation: f32,      // rotation in radians
+    pub life: f32,          // remaining lifetime (0.0 to 1.0)
+    pub alive: u32,         // 1 if alive, 0 if dead (for GPU filtering)
+    pub texture: u32,       // which of the layer's textures it draws
+    pub frame: f32,         // frame of the sprite sheet, wrapped around
+    _padding: [u32; 2],
+}
+
+// Make Particle compatible with GPU buffers
+unsafe impl bytemuck::Pod for Particle {}
+unsafe impl bytemuck::Zeroable for Particle {}
+
+impl Particle {
+    pub fn new(x: f32, y: f32, size: f32) -> Self {
+        Self {
+            position: [x, y],
+            velocity: [0.0, 0.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+            size,
+            rotation: 0.0,
+            life: 1.0,
+            alive: 1,
+            texture: 0,
+            frame: 0.0,
+            _padding: [0; 2],
+        }
+    }
+
+    /// A free slot of the particle array
+    pub fn dead() -> Self {
+        Self {
+            alive: 0,
+            ..Self::new(0.0, 0.0, 0.1)
+        }
+    }
+}
+
+/// Functions calling the methods of `particles`, see `register`
+const HELPERS: &str = r#"
+function emit_particle(args) particles:emit(args) end
+function update_particle(index, args) particles:set(index, args) end
+function get_particle(index) return particles:get(index) end
+"#;
+
+/// The particle array of a layer, as its script sees it in the `particles`
+/// global during a call
+///
+/// The script works on the array the renderer uploads from, see
+/// `ParticleScript::particles`, nothing is copied in or out. Indices start
+/// at 0:
+///
+/// - `particles:emit{x, y, vx, vy, size, life, r, g, b, a, rotation,
+///   texture, frame}` brings the first dead particle back to life, nothing
+///   happens while all are alive
+/// - `particles:set(index, fields)` sets some fields of a particle, with
+///   `alive` as a boolean, `texture` as the index of one of the layer's
+///   textures from 0 and `frame` as the frame of its sprite sheet
+/// - `particles:get(index)` returns them all as a table, empty for an index
+///   past the end
+/// - `#particles` is the size of the array, alive or not
+#[derive(Debug)]
+pub struct Particles(pub Vec<Particle>);
+
+impl UserData for Particles {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::Len, |_, this, ()| Ok(this.0.len()));
+        methods.add_method_mut("emit", |_, this, args: Table| {
+            let Some(p) = this.0.iter_mut().find(|p| p.alive == 0) else {
+                return Ok(());
+            };
+            p.position = [get_or(&args, "x", 0.0)?, get_or(&args, "y", 0.0)?];
+            p.velocity = [get_or(&args, "vx", 0.0)?, get_or(&args, "vy", 0.0)?];
+            p.color = [
+                get_or(&args, "r", 1.0)?,
+                get_or(&args, "g", 1.0)?,
+                get_or(&args, "b", 1.0)?,
+                get_or(&args, "a", 1.0)?,
+            ];
+            p.size = get_or(&args, "size", 0.05)?;
+            p.rotation = get_or(&args, "rotation", 0.0)?;
+            p.life = get_or(&args, "life", 1.0)?;
+            p.texture = args.get::<Option<u32>>("texture")?.unwrap_or(0);
+            p.frame = get_or(&args, "frame", 0.0)?;
+            p.alive = 1;
+            Ok(())
+        });
+        methods.add_method_mut("set", |_, this, (index, args): (usize, Table)| {
+            let Some(p) = this.0.get_mut(index) else {
+                return Ok(());
+            };
+            update(&args, "x", &mut p.position[0])?;
+            update(&args, "y", &mut p.position[1])?;
+            update(&args, "vx", &mut p.velocity[0])?;
+            update(&args, "vy", &mut p.velocity[1])?;
+            update(&args, "size", &mut p.size)?;
+            update(&args, "life", &mut p.life)?;
+            update(&args, "r", &mut p.color[0])?;
+            update(&args, "g", &mut p.color[1])?;
+            update(&args, "b", &mut p.color[2])?;
+            update(&args, "a", &mut p.color[3])?;
+            update(&args, "rotation", &mut p.rotation)?;
+            update(&args, "frame", &mut p.frame)?;
+            if let Some(texture) = args.get::<Option<u32>>("texture")? {
+                p.texture = texture;
+            }
+            if let Some(alive) = args.get::<Option<bool>>("alive")? {
+                p.alive = alive as u32;
+            }
+            Ok(())
+        });
+        methods.add_method("get", |lua, this, index: usize| {
+            let table = lua.create_table()?;
+            if let Some(p) = this.0.get(index) {
+                table.set("x", p.position[0])?;
+                table.set("y", p.position[1])?;
+                table.set("vx", p.velocity[0])?;
+                table.set("vy", p.velocity[1])?;
+                table.set("size", p.size)?;
+                table.set("life", p.life)?;
+                table.set("r", p.color[0])?;
+                table.set("g", p.color[1])?;
+                table.set("b", p.color[2])?;
+                table.set("a", p.color[3])?;
+                table.set("rotation", p.rotation)?;
+                table.set("texture", p.texture)?;
+                table.set("frame", p.frame)?;
+                table.set("alive", p.alive == 1)?;
+            }
+            Ok(table)
+        });
+    }
+}
+
+/// Add the functions particle scripts work with to their globals
+///
+/// `emit_particle(fields)`, `update_particle(index, fields)` and
+/// `get_particle(index)` call the methods of `particles`, see `Particles`.
+/// `random(min, max)` returns a number between the two.
+pub fn register(lua: &Lua) -> mlua::Result<()> {
+    lua.globals().set(
+        "random",
+        lua.create_function(|_, (min, max): (f32, f32)| {
+            Ok(min + (max - min) * rand::random::<f32>())
+        })?,
+    )?;
+    lua.load(HELPERS).set_name("particle helpers").exec()
+}
+
+fn get_or(table: &Table, key: &str, default: f32) -> mlua::Result<f32> {
+    Ok(table.get::<Option<f32>>(key)?.unwrap_or(default))
+}
+
+/// Set `field` to the number at `key`, if there is one
+fn update(table: &Table, key: &str, field: &mut f32) -> mlua::Result<()> {
+    if let Some(value) = table.get::<Option<f32>>(key)? {
+        *field = value;
+    }
+    Ok(())
+}
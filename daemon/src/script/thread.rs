@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, MutexGuard, OnceLock,
+    },
+    thread,
+};
+
+use common::manifest::Permission;
+use mlua::{Function, IntoLuaMulti, Lua};
+
+use super::{
+    limits::{Budget, ScriptError},
+    particles::{self, Particle, Particles},
+    sandbox,
+};
+
+static COMMANDS: OnceLock<Sender<Command>> = OnceLock::new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// What the renderer asks of the scripting thread
+enum Command {
+    /// Start the script `source` of a particle layer, named `name` in
+    /// errors, working on `particles` and sending why it stopped to
+    /// `warnings`
+    Load {
+        id: u64,
+        name: String,
+        source: String,
+        permissions: Vec<Permission>,
+        particles: Arc<Mutex<Particles>>,
+        warnings: Sender<String>,
+    },
+    /// Advance the script by `dt` seconds
+    Tick { id: u64, dt: f32 },
+    /// The layer is gone
+    Unload { id: u64 },
+}
+
+/// The script of a particle layer, running on the scripting thread
+///
+/// The Lua states of all scripts live on that one thread, so a slow script
+/// holds up the other scripts but never the drawing: the renderer only
+/// sends ticks and uploads the particles whenever the script is not busy
+/// with them. The script is unloaded when this is dropped.
+#[derive(Debug)]
+pub struct ParticleScript {
+    id: u64,
+    /// Locked by the scripting thread for each call into the script
+    particles: Arc<Mutex<Particles>>,
+    warnings: Receiver<String>,
+}
+
+impl ParticleScript {
+    /// Start the script `source`, with what `permissions` allow
+    ///
+    /// The script is compiled and run once, it defines `update(dt)`, called
+    /// on every tick with the seconds since the last one, and can define
+    /// `init()`, called once before the first. See `Particles` for what it
+    /// can do with the particles.
+    pub fn load(
+        name: String,
+        source: String,
+        permissions: Vec<Permission>,
+        max_particles: u32,
+    ) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let particles = vec![Particle::dead(); max_particles as usize];
+        let particles = Arc::new(Mutex::new(Particles(particles)));
+        let (sender, warnings) = mpsc::channel();
+        send(Command::Load {
+            id,
+            name,
+            source,
+            permissions,
+            particles: particles.clone(),
+            warnings: sender,
+        });
+        Self {
+            id,
+            particles,
+            warnings,
+        }
+    }
+
+    /// Advance the script by `dt` seconds, without waiting for it
+    pub fn tick(&self, dt: f32) {
+        send(Command::Tick { id: self.id, dt });
+    }
+
+    /// The particles as the script left them, None while it is running
+    pub fn particles(&self) -> Option<MutexGuard<'_, Particles>> {
+        self.particles.try_lock().ok()
+    }
+
+    /// Why the script failed to load or was stopped, once
+    pub fn warning(&self) -> Option<String> {
+        self.warnings.try_recv().ok()
+    }
+}
+
+impl Drop for ParticleScript {
+    fn drop(&mut self) {
+        send(Command::Unload { id: self.id });
+    }
+}
+
+fn send(command: Command) {
+    let commands = COMMANDS.get_or_init(|| {
+        let (sender, commands) = mpsc::channel();
+        let spawned = thread::Builder::new()
+            .name("scripts".to_string())
+            .spawn(move || run(commands));
+        if let Err(e) = spawned {
+            // The scripts never tick, their particles stay where they are
+            tracing::error!("Failed to start the script thread: {e}");
+        }
+        sender
+    });
+    let _ = commands.send(command);
+}
+
+fn run(commands: Receiver<Command>) {
+    // With the channel each script's warnings go to
+    let mut scripts: HashMap<u64, (Running, Sender<String>)> = HashMap::new();
+    while let Ok(command) = commands.recv() {
+        // Everything queued is taken at once, so a script that fell behind
+        // runs one longer tick instead of catching up on each
+        let mut ticks: HashMap<u64, f32> = HashMap::new();
+        for command in std::iter::once(command).chain(commands.try_iter()) {
+            match command {
+                Command::Load {
+                    id,
+                    name,
+                    source,
+                    permissions,
+                    particles,
+                    warnings,
+                } => match Running::new(name.clone(), &source, &permissions, particles) {
+                    Ok(script) => {
+                        scripts.insert(id, (script, warnings));
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to load the script {name}: {e}");
+                        let _ = warnings.send(format!("Script failed to load: {e}"));
+                    }
+                },
+                Command::Tick { id, dt } => *ticks.entry(id).or_default() += dt,
+                Command::Unload { id } => {
+                    scripts.remove(&id);
+                    ticks.remove(&id);
+                }
+            }
+        }
+        for (id, dt) in ticks {
+            let Some((script, warnings)) = scripts.get(&id) else {
+                continue;
+            };
+            if let Err(warning) = script.tick(dt) {
+                let _ = warnings.send(warning);
+                scripts.remove(&id);
+            }
+        }
+    }
+}
+
+/// A script loaded on the scripting thread
+///
+/// Its Lua state lives as long as the layer, so globals the script sets
+/// are still there on the next tick.
+struct Running {
+    name: String,
+    lua: Lua,
+    budget: Budget,
+    /// The script's `update(dt)`, None if it has none
+    update: Option<Function>,
+    particles: Arc<Mutex<Particles>>,
+}
+
+impl Running {
+    /// Compile the script and run it, then its `init()` if it has one
+    fn new(
+        name: String,
+        source: &str,
+        permissions: &[Permission],
+        particles: Arc<Mutex<Particles>>,
+    ) -> Result<Self, ScriptError> {
+        let (lua, budget) = sandbox(permissions)?;
+        particles::register(&lua)?;
+        let max_particles = particles.lock().unwrap().0.len();
+        lua.globals().set("max_particles", max_particles)?;
+        let chunk = lua.load(source).set_name(name.as_str()).into_function()?;
+        let mut script = Self {
+            name,
+            lua,
+            budget,
+            update: None,
+            particles,
+        };
+
+        script.call(&chunk, ())?;
+        let globals = script.lua.globals();
+        if let Some(init) = globals.get::<Option<Function>>("init")? {
+            script.call(&init, ())?;
+        }
+        script.update = globals.get::<Option<Function>>("update")?;
+        Ok(script)
+    }
+
+    /// Call `function` of the script within the budget, with the layer's
+    /// particles in the `particles` global for the time of the call
+    fn call(&self, function: &Function, args: impl IntoLuaMulti) -> Result<(), ScriptError> {
+        let mut particles = self.particles.lock().unwrap();
+        self.budget.run(|| {
+            self.lua.scope(|scope| {
+                let particles = scope.create_userdata_ref_mut(&mut *particles)?;
+                self.lua.globals().set("particles", particles)?;
+                function.call::<()>(args)
+            })
+        })
+    }
+
+    /// Run the script's `update` for a tick of `dt` seconds, the error says
+    /// why it was stopped
+    fn tick(&self, dt: f32) -> Result<(), String> {
+        let Some(update) = &self.update else {
+            return Ok(());
+        };
+        match self.call(update, dt) {
+            Ok(()) => Ok(()),
+            Err(ScriptError::OverBudget(reason)) => {
+                tracing::warn!("Stopped the particle script {}: it {reason}", self.name);
+                Err(format!("Script stopped, it {reason}"))
+            }
+            Err(e) => {
+                tracing::error!("Error running Lua script {}: {e}", self.name);
+                Ok(())
+            }
+        }
+    }
+}
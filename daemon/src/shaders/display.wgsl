@@ -0,0 +1,69 @@
+// Display adjustment shader: presents a render target with brightness,
+// contrast, gamma and color temperature applied
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    let pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0),
+    );
+
+    let tex_coords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
+    out.tex_coords = tex_coords[in_vertex_index];
+    return out;
+}
+
+struct Adjustment {
+    brightness: f32,
+    contrast: f32,
+    // Reciprocal of the gamma, the exponent applied to the color
+    inverse_gamma: f32,
+    padding: f32,
+    // Multiplier of each channel for the color temperature
+    white_point: vec3<f32>,
+};
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var<uniform> adjustment: Adjustment;
+
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+    let low = srgb / 12.92;
+    let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_source, s_source, in.tex_coords);
+    // Adjust the sRGB-encoded color so the settings act like a monitor's
+    var rgb = linear_to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    rgb = rgb * adjustment.white_point;
+    rgb = (rgb - 0.5) * adjustment.contrast + 0.5;
+    rgb = clamp(rgb * adjustment.brightness, vec3<f32>(0.0), vec3<f32>(1.0));
+    rgb = pow(rgb, vec3<f32>(adjustment.inverse_gamma));
+    return vec4<f32>(srgb_to_linear(rgb), color.a);
+}
@@ -0,0 +1,95 @@
+// Fire effect shader: flames rising from the bottom, drawn without an image
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0),
+    );
+
+    let tex_coords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+    );
+
+    out.clip_position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
+    out.tex_coords = tex_coords[in_vertex_index];
+    return out;
+}
+
+struct FireParams {
+    // Height the flames reach, as a fraction of the screen
+    height: f32,
+    // Speed multiplier of the flicker
+    speed: f32,
+    // Layer opacity
+    strength: f32,
+    // Runs at five times real time, see EffectModel::update_time
+    time: f32,
+    // Size multiplier of the flame tongues
+    scale: f32,
+};
+
+@group(0) @binding(2)
+var<uniform> params: FireParams;
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+// Value noise, smoothly interpolated between random lattice points
+fn noise(p: vec2<f32>) -> f32 {
+    let cell = floor(p);
+    let local = fract(p);
+    let u = local * local * (3.0 - 2.0 * local);
+    return mix(
+        mix(hash(cell), hash(cell + vec2<f32>(1.0, 0.0)), u.x),
+        mix(hash(cell + vec2<f32>(0.0, 1.0)), hash(cell + vec2<f32>(1.0, 1.0)), u.x),
+        u.y
+    );
+}
+
+fn fbm(p: vec2<f32>) -> f32 {
+    var value = 0.0;
+    var amplitude = 0.5;
+    var q = p;
+    for (var octave = 0; octave < 5; octave++) {
+        value += noise(q) * amplitude;
+        q *= 2.0;
+        amplitude *= 0.5;
+    }
+    return value;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Width over height, see ripple.effect.wgsl
+    let aspect = abs(dpdy(in.tex_coords.y) / dpdx(in.tex_coords.x));
+    let seconds = params.time / 5.0;
+    // Height above the bottom of the screen
+    let rise = 1.0 - in.tex_coords.y;
+
+    let p = vec2<f32>(in.tex_coords.x * aspect, rise) * 4.0 / max(params.scale, 0.01);
+    let flicker = fbm(p + vec2<f32>(0.0, -seconds * params.speed * 1.5));
+    let heat = clamp(flicker * 1.6 - rise / max(params.height, 0.01), 0.0, 1.0);
+
+    // Dark red at the edges through orange to pale yellow at the core
+    let color = mix(
+        mix(vec3<f32>(0.5, 0.05, 0.0), vec3<f32>(1.0, 0.45, 0.0), smoothstep(0.0, 0.5, heat)),
+        vec3<f32>(1.0, 0.95, 0.6),
+        smoothstep(0.5, 1.0, heat)
+    );
+    return vec4<f32>(color, smoothstep(0.0, 0.3, heat) * params.strength);
+}
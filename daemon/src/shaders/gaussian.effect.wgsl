@@ -1,5 +1,6 @@
-// Improved Gaussian Blur Shader
-// Performs a weighted Gaussian blur based on radius parameter
+// Separable Gaussian Blur Shader
+// Blurs horizontally into a texture (fs_horizontal), then vertically onto the
+// layers below (fs_main), see GAUSSIAN_EFFECT_PASSES
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
@@ -47,60 +48,48 @@ struct BlurParams {
 @group(0) @binding(1) var texture_sampler: sampler;
 @group(0) @binding(2) var<uniform> params: BlurParams;
 
-// Fragment shader for Gaussian blur
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // Get texture dimensions
-    let texture_size = vec2<f32>(
-        f32(textureDimensions(input_texture).x), 
-        f32(textureDimensions(input_texture).y)
-    );
-    
+// Gaussian weights for 5 samples (optimized for better blur quality)
+const WEIGHTS = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+// Blur the input along one axis, `direction` being a unit vector
+fn blur(tex_coords: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
     // Get radius from uniforms and apply strength multiplier
-    let blur_radius = max(params.radius, 0.1) * params.strength;  
-    let blur_direction = vec2<f32>(1.0, 1.0);  // Blur in both directions
-    
+    let blur_radius = max(params.radius, 0.1) * params.strength;
+
     // Calculate pixel size
-    let pixel_size = 1.0 / texture_size;
-    
-    // Gaussian weights for 5 samples (optimized for better blur quality)
-    let weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
-    
+    let pixel_size = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    let step = pixel_size * direction * blur_radius;
+
     // Sample center texel
-    var result = textureSample(input_texture, texture_sampler, in.tex_coords) * weights[0];
-    var total_weight = weights[0];
-    
-    // First pass - horizontal blur
-    for (var i = 1; i < 5; i++) {
-        let weight = weights[i];
-        let offset = pixel_size * vec2<f32>(blur_direction.x, 0.0) * f32(i) * blur_radius;
-        
-        // Sample in positive direction
-        result += textureSample(input_texture, texture_sampler, in.tex_coords + offset) * weight;
-        // Sample in negative direction
-        result += textureSample(input_texture, texture_sampler, in.tex_coords - offset) * weight;
-        
-        total_weight += 2.0 * weight;
-    }
-    
-    // Reset for vertical pass
-    var temp = result / total_weight;
-    result = temp * weights[0];
-    total_weight = weights[0];
-    
-    // Second pass - vertical blur
+    var result = textureSample(input_texture, texture_sampler, tex_coords) * WEIGHTS[0];
+    var total_weight = WEIGHTS[0];
+
     for (var i = 1; i < 5; i++) {
-        let weight = weights[i];
-        let offset = pixel_size * vec2<f32>(0.0, blur_direction.y) * f32(i) * blur_radius;
-        
+        let weight = WEIGHTS[i];
+        let offset = step * f32(i);
+
         // Sample in positive direction
-        result += textureSample(input_texture, texture_sampler, in.tex_coords + offset) * weight;
+        result += textureSample(input_texture, texture_sampler, tex_coords + offset) * weight;
         // Sample in negative direction
-        result += textureSample(input_texture, texture_sampler, in.tex_coords - offset) * weight;
-        
+        result += textureSample(input_texture, texture_sampler, tex_coords - offset) * weight;
+
         total_weight += 2.0 * weight;
     }
-    
+
+    return result / total_weight;
+}
+
+// First pass - horizontal blur into the intermediate texture
+@fragment
+fn fs_horizontal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.tex_coords, vec2<f32>(1.0, 0.0));
+}
+
+// Second pass - vertical blur of the first pass onto the layers below
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let result = blur(in.tex_coords, vec2<f32>(0.0, 1.0));
+
     // Enhanced time-based effects
     // Create a stronger pulsing effect
     let pulse_amount = params.strength * 0.3; // Increased from 0.1 to 0.3 (0-30% pulse)
@@ -114,7 +103,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let dir_shift = sin(params.time * 0.7) * 0.2 + 0.8; // 0.6-1.0 range
     
     // Normalize the result and apply enhanced time-based effects
-    var final_color = (result / total_weight) * pulse;
+    var final_color = result * pulse;
     
     // Apply color tinting
     final_color.r *= r_tint;
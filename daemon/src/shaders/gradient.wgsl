@@ -0,0 +1,102 @@
+// Gradient shader - Render a linear, radial or conic gradient over the screen
+
+const MAX_STOPS: u32 = 8u;
+const PI: f32 = 3.14159265358979;
+
+const KIND_LINEAR: u32 = 0u;
+const KIND_RADIAL: u32 = 1u;
+const KIND_CONIC: u32 = 2u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// Full-screen rectangle vertex shader
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Create a rectangle that covers the entire viewport (two triangles)
+    var positions = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+
+    out.clip_position = vec4<f32>(positions[in_vertex_index], 0.0, 1.0);
+    return out;
+}
+
+struct GradientUniform {
+    // sRGB-encoded stop colors with straight alpha
+    colors: array<vec4<f32>, 8>,
+    // Stop positions, four per vector
+    positions: array<vec4<f32>, 2>,
+    center: vec2<f32>,
+    screen_size: vec2<f32>,
+    // Radians, 0 points up and increases clockwise
+    angle: f32,
+    kind: u32,
+    stop_count: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> gradient: GradientUniform;
+
+fn stop_position(index: u32) -> f32 {
+    return gradient.positions[index / 4u][index % 4u];
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+// Position along the gradient for a pixel, 0 at the first stop and 1 at the last
+fn gradient_position(pixel: vec2<f32>) -> f32 {
+    let size = gradient.screen_size;
+    let center = gradient.center * size;
+    // Pixel coordinates grow downwards, so "up" is negative y
+    let direction = vec2<f32>(sin(gradient.angle), -cos(gradient.angle));
+
+    switch gradient.kind {
+        case KIND_RADIAL: {
+            // Reach 1 at the farthest corner, like CSS's default radial size
+            let corners = max(center, size - center);
+            return distance(pixel, center) / length(corners);
+        }
+        case KIND_CONIC: {
+            let offset = pixel - center;
+            let angle = atan2(offset.x, -offset.y) - gradient.angle;
+            return fract(angle / (2.0 * PI));
+        }
+        default: {
+            // The gradient line spans the screen so the corners get the end colors
+            let span = abs(size.x * direction.x) + abs(size.y * direction.y);
+            return dot(pixel - size * 0.5, direction) / span + 0.5;
+        }
+    }
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let t = gradient_position(in.clip_position.xy);
+
+    var color = gradient.colors[0];
+    for (var i = 1u; i < min(gradient.stop_count, MAX_STOPS); i++) {
+        let start = stop_position(i - 1u);
+        let end = stop_position(i);
+        let factor = clamp((t - start) / max(end - start, 1e-5), 0.0, 1.0);
+        color = select(color, mix(gradient.colors[i - 1u], gradient.colors[i], factor), t >= start);
+    }
+
+    // Interpolate in sRGB like CSS, the surface expects linear output
+    return vec4<f32>(srgb_to_linear(color.rgb), color.a);
+}
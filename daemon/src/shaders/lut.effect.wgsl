@@ -0,0 +1,80 @@
+// Color grading shader: looks colors up in a 3D table, blending between a
+// day and a night table
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0),
+    );
+
+    let tex_coords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+    );
+
+    out.clip_position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
+    out.tex_coords = tex_coords[in_vertex_index];
+    return out;
+}
+
+struct LutParams {
+    // How much of the night table shows, 0 to 1
+    night: f32,
+    // Layer opacity
+    strength: f32,
+    padding: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+@group(0) @binding(2)
+var<uniform> params: LutParams;
+@group(0) @binding(3)
+var t_day: texture_3d<f32>;
+@group(0) @binding(4)
+var t_night: texture_3d<f32>;
+@group(0) @binding(5)
+var s_lut: sampler;
+
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+    let low = srgb / 12.92;
+    let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+// Sample the table at the centers of its first and last entries for 0 and 1
+fn lookup(lut: texture_3d<f32>, color: vec3<f32>) -> vec3<f32> {
+    let size = f32(textureDimensions(lut).x);
+    let coords = color * ((size - 1.0) / size) + 0.5 / size;
+    return textureSampleLevel(lut, s_lut, coords, 0.0).rgb;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.tex_coords);
+    // Tables map sRGB-encoded colors, the texture decodes to linear
+    let encoded = linear_to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    let graded = mix(lookup(t_day, encoded), lookup(t_night, encoded), params.night);
+    return vec4<f32>(srgb_to_linear(graded), color.a * params.strength);
+}
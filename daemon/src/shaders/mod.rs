@@ -1,6 +1,8 @@
 //! Shader module containing compiled shader code as constants
 //! This module provides easy access to all shader code used in the application
 
+use crate::renderer::models::effect::EffectPass;
+
 pub const TEXTURE_SHADER: wgpu::ShaderModuleDescriptor<'static> =
     wgpu::include_wgsl!("./texture.wgsl");
 pub const COLOR_SHADER: wgpu::ShaderModuleDescriptor<'static> =
@@ -11,5 +13,38 @@ pub const GLITCH_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
     wgpu::include_wgsl!("./glitch.effect.wgsl");
 pub const GAUSSIAN_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
     wgpu::include_wgsl!("./gaussian.effect.wgsl");
+/// Separable blur, horizontally into a texture and vertically onto the layers below
+pub const GAUSSIAN_EFFECT_PASSES: &[EffectPass] =
+    &[EffectPass::new("fs_horizontal"), EffectPass::new("fs_main")];
+pub const RIPPLE_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./ripple.effect.wgsl");
+pub const SNOW_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./snow.effect.wgsl");
+pub const RAIN_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./rain.effect.wgsl");
+pub const FIRE_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./fire.effect.wgsl");
+pub const PLASMA_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./plasma.effect.wgsl");
+pub const STARFIELD_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./starfield.effect.wgsl");
+pub const LUT_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./lut.effect.wgsl");
+pub const OVERVIEW_EFFECT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./overview.effect.wgsl");
+/// Separable blur like `GAUSSIAN_EFFECT_PASSES`, dimming in the last pass
+pub const OVERVIEW_EFFECT_PASSES: &[EffectPass] = GAUSSIAN_EFFECT_PASSES;
 pub const PARTICLE_SHADER: wgpu::ShaderModuleDescriptor<'static> =
     wgpu::include_wgsl!("./particle.wgsl");
+pub const PARTICLE_COMPACT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./particle_compact.wgsl");
+pub const MIPMAP_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./mipmap.wgsl");
+pub const NOISE_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./noise.wgsl");
+pub const DISPLAY_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./display.wgsl");
+pub const TEXT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./text.wgsl");
+pub const GRADIENT_SHADER: wgpu::ShaderModuleDescriptor<'static> =
+    wgpu::include_wgsl!("./gradient.wgsl");
@@ -0,0 +1,212 @@
+// Noise generator: renders a tileable noise texture for effects when their
+// wallpaper loads, see asset::noise. The channels hold the noise at 4, 8 and
+// 16 features per tile, alpha the sum of those and a finer octave
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    let pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0),
+    );
+
+    let tex_coords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
+    out.tex_coords = tex_coords[in_vertex_index];
+    return out;
+}
+
+struct NoiseParams {
+    // 0 for Perlin, 1 for simplex, 2 for Worley noise
+    kind: u32,
+    seed: u32,
+    padding: vec2<u32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: NoiseParams;
+
+const TAU: f32 = 6.283185307179586;
+
+// PCG hash
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Hash of a lattice cell, wrapped so the pattern repeats every `period` cells
+fn hash_cell(cell: vec2<i32>, period: i32) -> u32 {
+    let wrapped = ((cell % period) + period) % period;
+    return hash(u32(wrapped.x) + hash(u32(wrapped.y) + hash(params.seed)));
+}
+
+// Two random numbers from 0 to 1 out of one hash
+fn random2(h: u32) -> vec2<f32> {
+    return vec2<f32>(f32(h & 0xffffu), f32(h >> 16u)) / 65535.0;
+}
+
+fn gradient(cell: vec2<i32>, period: i32) -> vec2<f32> {
+    let angle = f32(hash_cell(cell, period)) / 4294967295.0 * TAU;
+    return vec2<f32>(cos(angle), sin(angle));
+}
+
+// Gradient noise repeating every `period` cells, from 0 to 1
+fn perlin(p: vec2<f32>, period: i32) -> f32 {
+    let cell = vec2<i32>(floor(p));
+    let f = fract(p);
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let a = dot(gradient(cell, period), f);
+    let b = dot(gradient(cell + vec2<i32>(1, 0), period), f - vec2<f32>(1.0, 0.0));
+    let c = dot(gradient(cell + vec2<i32>(0, 1), period), f - vec2<f32>(0.0, 1.0));
+    let d = dot(gradient(cell + vec2<i32>(1, 1), period), f - vec2<f32>(1.0, 1.0));
+    // At most sqrt(0.5) away from 0
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y) * 0.7071 + 0.5;
+}
+
+// Distance to the nearest of one random point per cell, repeating every
+// `period` cells, from 0 to 1
+fn worley(p: vec2<f32>, period: i32) -> f32 {
+    let cell = vec2<i32>(floor(p));
+    let f = fract(p);
+    var nearest = 1.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2<i32>(x, y);
+            let point = vec2<f32>(offset) + random2(hash_cell(cell + offset, period));
+            nearest = min(nearest, length(point - f));
+        }
+    }
+    return nearest;
+}
+
+fn mod289(x: vec4<f32>) -> vec4<f32> {
+    return x - floor(x * (1.0 / 289.0)) * 289.0;
+}
+
+fn permute(x: vec4<f32>) -> vec4<f32> {
+    return mod289(((x * 34.0) + 10.0) * x);
+}
+
+fn taylor_inv_sqrt(r: vec4<f32>) -> vec4<f32> {
+    return 1.79284291400159 - 0.85373472095314 * r;
+}
+
+fn grad4(j: f32, ip: vec4<f32>) -> vec4<f32> {
+    var p = vec4<f32>(floor(fract(vec3<f32>(j) * ip.xyz) * 7.0) * ip.z - 1.0, 0.0);
+    p.w = 1.5 - dot(abs(p.xyz), vec3<f32>(1.0));
+    let s = select(vec4<f32>(0.0), vec4<f32>(1.0), p < vec4<f32>(0.0));
+    return vec4<f32>(p.xyz + (s.xyz * 2.0 - 1.0) * s.w, p.w);
+}
+
+// 4D simplex noise after Ashima Arts and Stefan Gustavson, from -1 to 1
+fn simplex4(v: vec4<f32>) -> f32 {
+    let C = vec4<f32>(
+        0.138196601125011, // (5 - sqrt(5)) / 20
+        0.276393202250021, // 2 * C.x
+        0.414589803375032, // 3 * C.x
+        -0.447213595499958, // -1 + 4 * C.x
+    );
+    let F4 = 0.309016994374947451; // (sqrt(5) - 1) / 4
+
+    // First corner
+    var i = floor(v + dot(v, vec4<f32>(F4)));
+    let x0 = v - i + dot(i, C.xxxx);
+
+    // Rank the coordinates to find the other corners
+    let is_x = step(x0.yzw, x0.xxx);
+    let is_yz = step(x0.zww, x0.yyz);
+    var i0 = vec4<f32>(is_x.x + is_x.y + is_x.z, 1.0 - is_x);
+    i0.y += is_yz.x + is_yz.y;
+    i0.z += 1.0 - is_yz.x;
+    i0.w += 1.0 - is_yz.y;
+    i0.z += is_yz.z;
+    i0.w += 1.0 - is_yz.z;
+
+    let i3 = clamp(i0, vec4<f32>(0.0), vec4<f32>(1.0));
+    let i2 = clamp(i0 - 1.0, vec4<f32>(0.0), vec4<f32>(1.0));
+    let i1 = clamp(i0 - 2.0, vec4<f32>(0.0), vec4<f32>(1.0));
+
+    let x1 = x0 - i1 + C.xxxx;
+    let x2 = x0 - i2 + C.yyyy;
+    let x3 = x0 - i3 + C.zzzz;
+    let x4 = x0 + C.wwww;
+
+    // Gradients of the corners
+    i = mod289(i);
+    let j0 = permute(permute(permute(permute(vec4<f32>(i.w)) + i.z) + i.y) + i.x).x;
+    let j1 = permute(
+        permute(
+            permute(
+                permute(i.w + vec4<f32>(i1.w, i2.w, i3.w, 1.0)) + i.z
+                    + vec4<f32>(i1.z, i2.z, i3.z, 1.0),
+            ) + i.y + vec4<f32>(i1.y, i2.y, i3.y, 1.0),
+        ) + i.x + vec4<f32>(i1.x, i2.x, i3.x, 1.0),
+    );
+
+    let ip = vec4<f32>(1.0 / 294.0, 1.0 / 49.0, 1.0 / 7.0, 0.0);
+    var p0 = grad4(j0, ip);
+    var p1 = grad4(j1.x, ip);
+    var p2 = grad4(j1.y, ip);
+    var p3 = grad4(j1.z, ip);
+    var p4 = grad4(j1.w, ip);
+    let norm = taylor_inv_sqrt(vec4<f32>(dot(p0, p0), dot(p1, p1), dot(p2, p2), dot(p3, p3)));
+    p0 *= norm.x;
+    p1 *= norm.y;
+    p2 *= norm.z;
+    p3 *= norm.w;
+    p4 *= taylor_inv_sqrt(vec4<f32>(dot(p4, p4))).x;
+
+    // Mix the contributions of the five corners
+    var m0 = max(0.6 - vec3<f32>(dot(x0, x0), dot(x1, x1), dot(x2, x2)), vec3<f32>(0.0));
+    var m1 = max(0.6 - vec2<f32>(dot(x3, x3), dot(x4, x4)), vec2<f32>(0.0));
+    m0 = m0 * m0;
+    m1 = m1 * m1;
+    return 49.0 * (dot(m0 * m0, vec3<f32>(dot(p0, x0), dot(p1, x1), dot(p2, x2)))
+        + dot(m1 * m1, vec2<f32>(dot(p3, x3), dot(p4, x4))));
+}
+
+// Simplex noise tiling every `period` features, sampled on a torus in 4D
+// since the simplex grid does not line up with the texture edges
+fn simplex(uv: vec2<f32>, period: i32) -> f32 {
+    let angles = uv * TAU;
+    let radius = f32(period) / TAU;
+    let torus = vec4<f32>(cos(angles.x), sin(angles.x), cos(angles.y), sin(angles.y)) * radius;
+    let offset = vec4<f32>(random2(hash(params.seed)), random2(hash(params.seed + 1u))) * 256.0;
+    return simplex4(torus + offset) * 0.5 + 0.5;
+}
+
+fn noise(uv: vec2<f32>, period: i32) -> f32 {
+    switch params.kind {
+        case 1u: {
+            return simplex(uv, period);
+        }
+        case 2u: {
+            return worley(uv * f32(period), period);
+        }
+        default: {
+            return perlin(uv * f32(period), period);
+        }
+    }
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coarse = noise(in.tex_coords, 4);
+    let medium = noise(in.tex_coords, 8);
+    let fine = noise(in.tex_coords, 16);
+    let fbm = (coarse + 0.5 * medium + 0.25 * fine + 0.125 * noise(in.tex_coords, 32)) / 1.875;
+    return vec4<f32>(coarse, medium, fine, clamp(fbm, 0.0, 1.0));
+}
@@ -0,0 +1,73 @@
+// Overview shader: blurs and dims the wallpaper while the compositor shows an
+// overview or launcher over it. Blurs horizontally into a texture
+// (fs_horizontal), then vertically onto the wallpaper (fs_main), see
+// OVERVIEW_EFFECT_PASSES
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0)
+    );
+
+    var texcoords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0)
+    );
+
+    var output: VertexOutput;
+    output.position = vec4<f32>(positions[vertex_index], 0.0, 1.0);
+    output.tex_coords = texcoords[vertex_index];
+    return output;
+}
+
+struct OverviewParams {
+    // Distance between blur samples in pixels when fully shown
+    radius: f32,
+    // How much darker the wallpaper gets when fully shown, 0 to 1
+    dim: f32,
+    // How far the overview faded in, 0 to 1
+    strength: f32,
+    time: f32,
+};
+
+@group(0) @binding(0) var input_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+@group(0) @binding(2) var<uniform> params: OverviewParams;
+
+const WEIGHTS = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+// Blur the input along one axis, widening as the overview fades in
+fn blur(tex_coords: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    let pixel_size = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    let step = pixel_size * direction * params.radius * params.strength;
+
+    var result = textureSample(input_texture, texture_sampler, tex_coords) * WEIGHTS[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = step * f32(i);
+        result += textureSample(input_texture, texture_sampler, tex_coords + offset) * WEIGHTS[i];
+        result += textureSample(input_texture, texture_sampler, tex_coords - offset) * WEIGHTS[i];
+    }
+    return result;
+}
+
+@fragment
+fn fs_horizontal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.tex_coords, vec2<f32>(1.0, 0.0));
+}
+
+// The blurred wallpaper replaces the sharp one as the overview fades in
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = blur(in.tex_coords, vec2<f32>(0.0, 1.0));
+    let dimmed = color.rgb * (1.0 - params.dim * params.strength);
+    return vec4<f32>(dimmed, color.a * params.strength);
+}
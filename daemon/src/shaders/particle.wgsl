@@ -7,6 +7,16 @@ struct Particle {
     rotation: f32,
     life: f32,
     alive: u32,
+    texture: u32,
+    frame: f32,
+};
+
+// The layer's textures side by side in one, each a grid of frames
+struct Sheet {
+    textures: u32,
+    columns: u32,
+    rows: u32,
+    _padding: u32,
 };
 
 @group(0) @binding(0)
@@ -15,6 +25,8 @@ var particle_texture: texture_2d<f32>;
 var particle_sampler: sampler;
 @group(0) @binding(2)
 var<storage, read> particles: array<Particle>;
+@group(0) @binding(3)
+var<uniform> sheet: Sheet;
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
@@ -84,12 +96,21 @@ fn vs_main(
     
     // Set position and pass through color and texture coordinates
     output.position = vec4<f32>(final_position, 0.0, 1.0);
-    output.tex_coords = uv;
+    output.tex_coords = sprite_uv(particle, uv);
     output.color = particle.color * vec4<f32>(1.0, 1.0, 1.0, particle.life); // Fade out based on life
     
     return output;
 }
 
+// Where `uv` of the particle's frame is in the texture holding all sheets
+fn sprite_uv(particle: Particle, uv: vec2<f32>) -> vec2<f32> {
+    let frame = u32(max(particle.frame, 0.0)) % (sheet.columns * sheet.rows);
+    let cell = vec2<f32>(f32(frame % sheet.columns), f32(frame / sheet.columns));
+    let in_sheet = (cell + uv) / vec2<f32>(f32(sheet.columns), f32(sheet.rows));
+    let texture = f32(min(particle.texture, sheet.textures - 1u));
+    return vec2<f32>((texture + in_sheet.x) / f32(sheet.textures), in_sheet.y);
+}
+
 @fragment
 fn fs_main(
     @location(0) tex_coords: vec2<f32>,
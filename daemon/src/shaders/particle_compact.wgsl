@@ -0,0 +1,42 @@
+// Compaction of the particles: copies the alive ones to the front of the
+// array the particle shader draws from, and counts them into the instances
+// of its indirect draw, so dead particles cost nothing to draw.
+// The order of the alive particles is not kept.
+struct Particle {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    color: vec4<f32>,
+    size: f32,
+    rotation: f32,
+    life: f32,
+    alive: u32,
+    texture: u32,
+    frame: f32,
+};
+
+// Laid out like the arguments of `draw_indirect`
+struct DrawArgs {
+    vertex_count: u32,
+    instance_count: atomic<u32>,
+    first_vertex: u32,
+    first_instance: u32,
+};
+
+@group(0) @binding(0)
+var<storage, read> particles: array<Particle>;
+@group(0) @binding(1)
+var<storage, read_write> alive: array<Particle>;
+@group(0) @binding(2)
+var<storage, read_write> draw: DrawArgs;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= arrayLength(&particles)) {
+        return;
+    }
+    let particle = particles[id.x];
+    if (particle.alive == 0u) {
+        return;
+    }
+    alive[atomicAdd(&draw.instance_count, 1u)] = particle;
+}
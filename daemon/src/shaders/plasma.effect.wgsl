@@ -0,0 +1,62 @@
+// Plasma effect shader: flowing rainbow bands, drawn without an image
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0),
+    );
+
+    let tex_coords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+    );
+
+    out.clip_position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
+    out.tex_coords = tex_coords[in_vertex_index];
+    return out;
+}
+
+struct PlasmaParams {
+    // Size multiplier of the bands
+    scale: f32,
+    // Speed multiplier of the flow
+    speed: f32,
+    // Layer opacity
+    strength: f32,
+    // Runs at five times real time, see EffectModel::update_time
+    time: f32,
+};
+
+@group(0) @binding(2)
+var<uniform> params: PlasmaParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Width over height, see ripple.effect.wgsl
+    let aspect = abs(dpdy(in.tex_coords.y) / dpdx(in.tex_coords.x));
+    let p = (in.tex_coords - 0.5) * vec2<f32>(aspect, 1.0) * 8.0 / max(params.scale, 0.01);
+    let t = params.time / 5.0 * params.speed;
+
+    // Sum of sines moving in different directions
+    var value = sin(p.x + t);
+    value += sin((p.y + t) * 0.5);
+    value += sin((p.x + p.y + t) * 0.5);
+    let center = p + vec2<f32>(sin(t / 3.0), cos(t / 2.0)) * 4.0;
+    value += sin(sqrt(dot(center, center) + 1.0) + t);
+
+    let color = sin(value * 3.1415927 + vec3<f32>(0.0, 2.0943951, 4.1887902)) * 0.5 + 0.5;
+    return vec4<f32>(color, params.strength);
+}
@@ -0,0 +1,84 @@
+// Rain effect shader: slanted streaks falling, drawn without an image
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0),
+    );
+
+    let tex_coords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+    );
+
+    out.clip_position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
+    out.tex_coords = tex_coords[in_vertex_index];
+    return out;
+}
+
+struct RainParams {
+    // Fraction of the grid cells holding a drop, 0 to 1
+    density: f32,
+    // Fall speed multiplier
+    speed: f32,
+    // Layer opacity
+    strength: f32,
+    // Runs at five times real time, see EffectModel::update_time
+    time: f32,
+    // Slant of the streaks, horizontal movement per unit of fall
+    angle: f32,
+    // Streak length multiplier
+    length: f32,
+};
+
+@group(0) @binding(2)
+var<uniform> params: RainParams;
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Width over height, see ripple.effect.wgsl
+    let aspect = abs(dpdy(in.tex_coords.y) / dpdx(in.tex_coords.x));
+    var uv = in.tex_coords * vec2<f32>(aspect, 1.0);
+    uv.x += uv.y * params.angle;
+    let seconds = params.time / 5.0;
+
+    var alpha = 0.0;
+    for (var layer = 0; layer < 3; layer++) {
+        let depth = f32(layer);
+        // Tall cells, each holding at most one streak
+        let scale = vec2<f32>(80.0 - depth * 20.0, 4.0 - depth);
+        let p = vec2<f32>(uv.x + depth * 0.37, uv.y - seconds * params.speed * (1.6 - depth * 0.4));
+
+        let cell = floor(p * scale);
+        let local = fract(p * scale);
+        let random = hash(cell + depth * 17.3);
+        if (random < params.density) {
+            let x = hash(cell + 2.9) * 0.6 + 0.2;
+            let top = hash(cell + 5.7) * 0.5;
+            let size = 0.3 * params.length;
+            let across = 1.0 - smoothstep(0.02, 0.08, abs(local.x - x));
+            // Brightest at the bottom of the streak
+            let along = smoothstep(top, top + size, local.y) * step(local.y, top + size);
+            alpha = max(alpha, across * along * (0.5 - depth * 0.12));
+        }
+    }
+
+    return vec4<f32>(0.75, 0.8, 0.9, alpha * params.strength);
+}
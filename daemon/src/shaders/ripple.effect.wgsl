@@ -0,0 +1,74 @@
+// Ripple effect shader: rings spreading from a point distort the image
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0),
+    );
+
+    let tex_coords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+    );
+
+    out.clip_position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
+    out.tex_coords = tex_coords[in_vertex_index];
+    return out;
+}
+
+struct RippleParams {
+    // Distortion of the rings, as a fraction of the screen
+    amplitude: f32,
+    // Rings per screen height
+    frequency: f32,
+    // Layer opacity
+    strength: f32,
+    // Runs at five times real time, see EffectModel::update_time
+    time: f32,
+    // Speed multiplier of the rings
+    speed: f32,
+    // Where the rings start, 0 to 1 across the screen
+    center_x: f32,
+    center_y: f32,
+    // How fast the rings fade with distance
+    damping: f32,
+};
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(0) @binding(2)
+var<uniform> params: RippleParams;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.tex_coords;
+    // Texture coordinates change by one over the width per pixel horizontally
+    // and one over the height vertically, so this is width over height
+    let aspect = abs(dpdy(uv.y) / dpdx(uv.x));
+
+    let seconds = params.time / 5.0;
+    let offset = (uv - vec2<f32>(params.center_x, params.center_y)) * vec2<f32>(aspect, 1.0);
+    let distance = length(offset);
+    let phase = distance * params.frequency * 6.2831853 - seconds * params.speed * 6.0;
+    let wave = sin(phase) * params.amplitude * exp(-distance * params.damping);
+
+    // Push the image along the direction away from the center
+    let direction = offset / max(distance, 0.0001) / vec2<f32>(aspect, 1.0);
+    let color = textureSample(t_diffuse, s_diffuse, uv + direction * wave);
+    return vec4<f32>(color.rgb, color.a * params.strength);
+}
@@ -0,0 +1,83 @@
+// Snow effect shader: layers of flakes drifting down, drawn without an image
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0),
+    );
+
+    let tex_coords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+    );
+
+    out.clip_position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
+    out.tex_coords = tex_coords[in_vertex_index];
+    return out;
+}
+
+struct SnowParams {
+    // Fraction of the grid cells holding a flake, 0 to 1
+    density: f32,
+    // Fall speed multiplier
+    speed: f32,
+    // Layer opacity
+    strength: f32,
+    // Runs at five times real time, see EffectModel::update_time
+    time: f32,
+    // Flake size multiplier
+    size: f32,
+    // Sideways drift, negative to the left
+    wind: f32,
+};
+
+@group(0) @binding(2)
+var<uniform> params: SnowParams;
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Width over height, see ripple.effect.wgsl
+    let aspect = abs(dpdy(in.tex_coords.y) / dpdx(in.tex_coords.x));
+    let uv = in.tex_coords * vec2<f32>(aspect, 1.0);
+    let seconds = params.time / 5.0;
+
+    var alpha = 0.0;
+    // Far layers have more, smaller and slower flakes
+    for (var layer = 0; layer < 4; layer++) {
+        let depth = f32(layer);
+        let scale = 24.0 - depth * 4.0;
+        let fall = seconds * params.speed * (0.04 + depth * 0.02);
+        var p = uv + vec2<f32>(-seconds * params.wind * 0.05, -fall) + depth * 7.31;
+        // Let every row of flakes sway a little
+        p.x += sin(p.y * 3.0 + seconds + depth) * 0.02;
+
+        let cell = floor(p * scale);
+        let local = fract(p * scale);
+        let random = hash(cell + depth * 13.7);
+        if (random < params.density) {
+            let center = vec2<f32>(hash(cell + 1.3), hash(cell + 4.1)) * 0.6 + 0.2;
+            let radius = (0.05 + depth * 0.03) * params.size;
+            let flake = 1.0 - smoothstep(radius * 0.5, radius, length(local - center));
+            alpha = max(alpha, flake * (0.5 + depth * 0.15));
+        }
+    }
+
+    return vec4<f32>(1.0, 1.0, 1.0, alpha * params.strength);
+}
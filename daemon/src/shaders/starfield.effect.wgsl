@@ -0,0 +1,80 @@
+// Starfield effect shader: stars flying towards the viewer, drawn without an image
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Full-screen triangle
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let pos = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(3.0, -1.0),
+        vec2<f32>(-1.0, 3.0),
+    );
+
+    let tex_coords = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(2.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+    );
+
+    out.clip_position = vec4<f32>(pos[in_vertex_index], 0.0, 1.0);
+    out.tex_coords = tex_coords[in_vertex_index];
+    return out;
+}
+
+struct StarfieldParams {
+    // Fraction of the grid cells holding a star, 0 to 1
+    density: f32,
+    // Flight speed multiplier
+    speed: f32,
+    // Layer opacity
+    strength: f32,
+    // Runs at five times real time, see EffectModel::update_time
+    time: f32,
+    // How much the stars twinkle, 0 to 1
+    twinkle: f32,
+};
+
+@group(0) @binding(2)
+var<uniform> params: StarfieldParams;
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Width over height, see ripple.effect.wgsl
+    let aspect = abs(dpdy(in.tex_coords.y) / dpdx(in.tex_coords.x));
+    let p = (in.tex_coords - 0.5) * vec2<f32>(aspect, 1.0);
+    let seconds = params.time / 5.0;
+
+    var brightness = 0.0;
+    // Each layer zooms in and fades out as it gets close, then starts far away again
+    for (var layer = 0; layer < 4; layer++) {
+        let offset = f32(layer) / 4.0;
+        let depth = fract(offset + seconds * params.speed * 0.05);
+        let zoom = mix(30.0, 1.0, depth);
+        let fade = smoothstep(0.0, 0.3, depth) * (1.0 - smoothstep(0.85, 1.0, depth));
+
+        let q = p * zoom + offset * 91.7;
+        let cell = floor(q);
+        let local = fract(q) - 0.5;
+        let random = hash(cell);
+        if (random < params.density * 0.3) {
+            let center = (vec2<f32>(hash(cell + 3.1), hash(cell + 7.7)) - 0.5) * 0.7;
+            let star = 1.0 - smoothstep(0.0, 0.08, length(local - center));
+            let twinkle = 1.0 - params.twinkle * (0.5 + 0.5 * sin(seconds * 4.0 + random * 100.0));
+            brightness = max(brightness, star * fade * twinkle);
+        }
+    }
+
+    return vec4<f32>(1.0, 1.0, 1.0, brightness * params.strength);
+}
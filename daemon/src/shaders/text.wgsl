@@ -0,0 +1,59 @@
+// Text shader, draws one instanced quad per glyph from the glyph atlas
+
+struct TextUniform {
+    screen_size: vec2<f32>,
+    color: vec4<f32>,
+};
+
+struct GlyphInput {
+    // Top left corner and size in pixels
+    @location(0) rect: vec4<f32>,
+    // Top left and bottom right corners in the atlas
+    @location(1) uv: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var t_atlas: texture_2d<f32>;
+@group(0) @binding(1)
+var s_atlas: sampler;
+@group(0) @binding(2)
+var<uniform> text: TextUniform;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+    glyph: GlyphInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[in_vertex_index];
+
+    // Pixel coordinates grow downwards, clip space grows upwards
+    let pixel = glyph.rect.xy + corner * glyph.rect.zw;
+    let ndc = vec2<f32>(
+        pixel.x / text.screen_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / text.screen_size.y * 2.0,
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.uv = mix(glyph.uv.xy, glyph.uv.zw, corner);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_atlas, s_atlas, in.uv).r;
+    return vec4<f32>(text.color.rgb, text.color.a * coverage);
+}
@@ -52,3 +52,46 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     return textureSample(t_diffuse, s_diffuse, in.tex_coords);
 }
 
+
+// 采样不带 alpha 的纹理，alpha 通道内容未定义，视为不透明
+@fragment
+fn fs_opaque(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSample(t_diffuse, s_diffuse, in.tex_coords).rgb, 1.0);
+}
+
+// Tiling: the texture pixel for pixel from the top left corner, an instance
+// of the quad for each tile
+struct Tiles {
+    // Size of a tile in clip space
+    size: vec2<f32>,
+    columns: u32,
+    _padding: u32,
+};
+
+@group(0) @binding(2)
+var<uniform> tiles: Tiles;
+
+@vertex
+fn vs_tile(
+    @builtin(vertex_index) in_vertex_index: u32,
+    @builtin(instance_index) instance: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Corners of the tile from its top left, y pointing down
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+    );
+
+    let corner = corners[in_vertex_index];
+    let tile = vec2<f32>(f32(instance % tiles.columns), f32(instance / tiles.columns));
+    let offset = (tile + corner) * tiles.size;
+    out.clip_position = vec4<f32>(-1.0 + offset.x, 1.0 - offset.y, 0.0, 1.0);
+    out.tex_coords = corner;
+    return out;
+}
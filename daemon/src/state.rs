@@ -0,0 +1,323 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use common::{manifest::Permission, types::WallpaperUsage, wallpaper::Wallpaper};
+use rand::distributions::{Distribution, WeightedIndex};
+use serde::{Deserialize, Serialize};
+
+/// Number of recently set wallpapers a random pick avoids
+const HISTORY_LEN: usize = 5;
+
+/// How much more likely a favorite is to be picked at random
+const FAVORITE_WEIGHT: u32 = 3;
+
+/// Wallpaper usage and history persisted by the daemon
+///
+/// Stored as TOML in `$XDG_STATE_HOME/wlrs/state.toml` and written back after
+/// every change, so a crash loses at most the change in flight.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// Recently set wallpapers, oldest first and without duplicates
+    #[serde(default)]
+    history: Vec<String>,
+    #[serde(default)]
+    wallpapers: BTreeMap<String, WallpaperUsage>,
+    /// Path of the wallpaper last set on each output, restored by `--replace`
+    #[serde(default)]
+    outputs: BTreeMap<String, String>,
+    /// Variant of the wallpaper of each output, for the ones not in their
+    /// default look
+    #[serde(default)]
+    variants: BTreeMap<String, String>,
+    /// Parameters the user overrode for each wallpaper, by `layer.parameter`
+    #[serde(default)]
+    settings: BTreeMap<String, BTreeMap<String, toml::Value>>,
+    /// Permissions the user granted to the scripts of each wallpaper
+    #[serde(default)]
+    permissions: BTreeMap<String, Grant>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+/// Permissions granted to the scripts of a wallpaper as they were then
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Grant {
+    pub permissions: Vec<Permission>,
+    /// Hash of the scripts, see `Wallpaper::hash_scripts`
+    pub scripts: String,
+}
+
+impl State {
+    /// Load the state file, starting empty if it is missing or unreadable
+    pub fn load() -> Self {
+        let path = state_path();
+        let mut state = match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring invalid state file {}: {e}", path.display());
+                State::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => State::default(),
+            Err(e) => {
+                tracing::warn!("Failed to read state file {}: {e}", path.display());
+                State::default()
+            }
+        };
+        state.path = path;
+        state
+    }
+
+    /// Write the state file, replacing it atomically
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string(self).map_err(io::Error::other)?;
+        let temporary = self.path.with_extension("toml.tmp");
+        fs::write(&temporary, content)?;
+        fs::rename(&temporary, &self.path)
+    }
+
+    /// Save, logging instead of failing since usage data is not critical
+    fn persist(&self) {
+        if let Err(e) = self.save() {
+            tracing::error!("Failed to save state to {}: {e}", self.path.display());
+        }
+    }
+
+    /// Usage of a wallpaper, all defaults if it was never used
+    pub fn usage(&self, name: &str) -> WallpaperUsage {
+        self.wallpapers.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Every wallpaper the daemon has data about
+    pub fn wallpapers(&self) -> impl Iterator<Item = (&str, &WallpaperUsage)> {
+        self.wallpapers
+            .iter()
+            .map(|(name, usage)| (name.as_str(), usage))
+    }
+
+    /// Mark or unmark a wallpaper as favorite
+    pub fn set_favorite(&mut self, name: &str, favorite: bool) {
+        self.wallpapers
+            .entry(name.to_string())
+            .or_default()
+            .favorite = favorite;
+        self.persist();
+    }
+
+    /// Settings of the user for a wallpaper, empty if there are none
+    pub fn settings(&self, name: &str) -> BTreeMap<String, toml::Value> {
+        self.settings.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Replace the settings of a wallpaper
+    pub fn set_settings(&mut self, name: &str, settings: BTreeMap<String, toml::Value>) {
+        if settings.is_empty() {
+            self.settings.remove(name);
+        } else {
+            self.settings.insert(name.to_string(), settings);
+        }
+        self.persist();
+    }
+
+    /// Permissions granted to the wallpaper's scripts, none if they changed
+    /// since, e.g. with an update, or could not be read
+    pub fn granted(&self, wallpaper: &Wallpaper) -> Vec<Permission> {
+        let Some(grant) = self.permissions.get(wallpaper.name()) else {
+            return Vec::new();
+        };
+        match wallpaper.hash_scripts() {
+            Ok(scripts) if scripts == grant.scripts => grant.permissions.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether permissions were granted to other scripts of the wallpaper
+    pub fn grant_is_stale(&self, wallpaper: &Wallpaper) -> bool {
+        self.permissions.contains_key(wallpaper.name()) && self.granted(wallpaper).is_empty()
+    }
+
+    /// Grant the wallpaper's scripts as they are now all the permissions
+    /// it needs, or revoke them
+    pub fn set_granted(&mut self, wallpaper: &Wallpaper, grant: bool) -> io::Result<()> {
+        if grant && !wallpaper.manifest.needs.is_empty() {
+            let grant = Grant {
+                permissions: wallpaper.manifest.needs.clone(),
+                scripts: wallpaper.hash_scripts()?,
+            };
+            self.permissions.insert(wallpaper.name().to_string(), grant);
+        } else {
+            self.permissions.remove(wallpaper.name());
+        }
+        self.persist();
+        Ok(())
+    }
+
+    /// Count a use of the wallpaper and remember when, where and in which
+    /// variant it was set
+    pub fn record_use(
+        &mut self,
+        name: &str,
+        path: &str,
+        variant: Option<&str>,
+        outputs: &[String],
+    ) {
+        for output in outputs {
+            self.outputs.insert(output.clone(), path.to_string());
+            match variant {
+                Some(variant) => self.variants.insert(output.clone(), variant.to_string()),
+                None => self.variants.remove(output),
+            };
+        }
+
+        let usage = self.wallpapers.entry(name.to_string()).or_default();
+        usage.use_count += 1;
+        usage.last_used = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_secs());
+
+        self.history.retain(|recent| recent != name);
+        self.history.push(name.to_string());
+        let excess = self.history.len().saturating_sub(HISTORY_LEN);
+        self.history.drain(..excess);
+        self.persist();
+    }
+
+    /// Path and variant of the wallpaper last set on each output
+    pub fn outputs(&self) -> impl Iterator<Item = (&str, &str, Option<&str>)> {
+        self.outputs.iter().map(|(output, path)| {
+            let variant = self.variants.get(output).map(String::as_str);
+            (output.as_str(), path.as_str(), variant)
+        })
+    }
+
+    /// The wallpaper that was set last
+    pub fn current(&self) -> Option<&str> {
+        self.history.last().map(String::as_str)
+    }
+
+    /// Pick one of `candidates` at random
+    ///
+    /// Favorites are more likely to be picked, and recently set wallpapers are
+    /// skipped as long as that leaves something to choose from.
+    pub fn choose_random<'a>(&self, candidates: &'a [String]) -> Option<&'a String> {
+        let avoided = HISTORY_LEN.min(candidates.len().saturating_sub(1));
+        let recent = &self.history[self.history.len().saturating_sub(avoided)..];
+        let mut fresh: Vec<&String> = candidates
+            .iter()
+            .filter(|name| !recent.contains(name))
+            .collect();
+        if fresh.is_empty() {
+            fresh = candidates.iter().collect();
+        }
+
+        let weights = fresh.iter().map(|name| {
+            if self.usage(name).favorite {
+                FAVORITE_WEIGHT
+            } else {
+                1
+            }
+        });
+        let index = WeightedIndex::new(weights).ok()?;
+        Some(fresh[index.sample(&mut rand::thread_rng())])
+    }
+}
+
+/// State file of the daemon, one per namespace, see `ipc::set_namespace`
+fn state_path() -> PathBuf {
+    let file = match common::ipc::namespace() {
+        Some(namespace) => format!("state-{namespace}.toml"),
+        None => "state.toml".to_string(),
+    };
+    directories::BaseDirs::new()
+        .map(|dirs| {
+            dirs.state_dir()
+                .unwrap_or_else(|| dirs.data_local_dir())
+                .join("wlrs")
+                .join(&file)
+        })
+        .unwrap_or_else(|| PathBuf::from("/tmp/wlrs").join(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty state saved in `dir`
+    fn state(dir: &tempfile::TempDir) -> State {
+        State {
+            path: dir.path().join("state.toml"),
+            ..Default::default()
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_record_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = state(&dir);
+        state.record_use("forest", "/walls/forest", Some("night"), &names(&["DP-1"]));
+        state.record_use("sea", "/walls/sea", None, &names(&["DP-1", "HDMI-A-1"]));
+        state.record_use(
+            "forest",
+            "/walls/forest",
+            Some("night"),
+            &names(&["HDMI-A-1"]),
+        );
+
+        assert_eq!(state.usage("forest").use_count, 2);
+        assert!(state.usage("forest").last_used.is_some());
+        assert_eq!(state.usage("sea").use_count, 1);
+        assert_eq!(state.current(), Some("forest"));
+        assert_eq!(
+            state.outputs().collect::<Vec<_>>(),
+            [
+                ("DP-1", "/walls/sea", None),
+                ("HDMI-A-1", "/walls/forest", Some("night")),
+            ]
+        );
+
+        // The history keeps the latest uses, each wallpaper once
+        for name in ["a", "b", "c", "d", "sea", "e"] {
+            state.record_use(name, name, None, &[]);
+        }
+        assert_eq!(state.history, names(&["b", "c", "d", "sea", "e"]));
+
+        // Every use is saved right away
+        let saved: State = toml::from_str(&fs::read_to_string(&state.path).unwrap()).unwrap();
+        assert_eq!(saved.history, state.history);
+        assert_eq!(saved.usage("forest").use_count, 2);
+    }
+
+    #[test]
+    fn test_choose_random() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = state(&dir);
+        assert_eq!(state.choose_random(&[]), None);
+
+        let candidates = names(&["a", "b", "c", "d", "e", "f", "g"]);
+        for name in ["a", "b", "c", "d", "e", "f"] {
+            state.record_use(name, name, None, &[]);
+        }
+        // The five last set are avoided, a wallpaper set before them is not
+        for _ in 0..50 {
+            let picked = state.choose_random(&candidates).unwrap();
+            assert!(["a", "g"].contains(&picked.as_str()), "picked {picked}");
+        }
+
+        // With fewer candidates only the one set longest ago is left
+        let few = names(&["d", "e", "f"]);
+        for _ in 0..50 {
+            assert_eq!(state.choose_random(&few).unwrap(), "d");
+        }
+        assert_eq!(state.choose_random(&names(&["f"])).unwrap(), "f");
+    }
+}
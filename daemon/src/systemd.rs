@@ -0,0 +1,58 @@
+//! Integration with systemd user services: readiness notification and
+//! socket activation, without linking against libsystemd.
+
+use std::{
+    env, io,
+    os::{
+        fd::{FromRawFd, RawFd},
+        linux::net::SocketAddrExt,
+        unix::net::{SocketAddr, UnixDatagram, UnixListener},
+    },
+};
+
+/// First file descriptor passed by socket activation, see sd_listen_fds(3)
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Send a status like `READY=1` to the service manager
+///
+/// Returns `Ok(false)` if the daemon was not started by systemd with
+/// `Type=notify`.
+pub fn notify(state: &str) -> io::Result<bool> {
+    let Some(socket) = env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+
+    // A leading '@' denotes a socket in the abstract namespace
+    let addr = match socket.as_encoded_bytes() {
+        [b'@', name @ ..] => SocketAddr::from_abstract_name(name)?,
+        _ => SocketAddr::from_pathname(&socket)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(true)
+}
+
+/// Take the IPC listener passed by socket activation, if there is one
+///
+/// The environment variables are removed so child processes do not try to
+/// use the socket as well.
+pub fn take_listener() -> Option<UnixListener> {
+    let pid = env::var("LISTEN_PID").ok()?.parse::<u32>().ok()?;
+    let fds = env::var("LISTEN_FDS").ok()?.parse::<RawFd>().ok()?;
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    if pid != std::process::id() || fds < 1 {
+        return None;
+    }
+    if fds > 1 {
+        tracing::warn!("Received {fds} sockets from systemd, only the first one is used");
+    }
+
+    // SAFETY: systemd passes the listening socket as fd 3 and nothing else in
+    // the daemon owns it
+    unsafe {
+        libc::fcntl(LISTEN_FDS_START, libc::F_SETFD, libc::FD_CLOEXEC);
+        Some(UnixListener::from_raw_fd(LISTEN_FDS_START))
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    time::{Duration, Instant},
+};
+
+/// When the main loop has to wake up without an event, the earliest of the
+/// next frame, capture or slide and the next attempt to get a lost GPU back,
+/// None to sleep until an event arrives
+pub fn next_wake(frame: Option<Instant>, recovery: Option<Instant>) -> Option<Instant> {
+    frame.into_iter().chain(recovery).min()
+}
+
+/// Milliseconds the main loop blocks in `epoll_wait`
+///
+/// Deadlines wake it through the `Timer`, so it waits for an event without
+/// a timeout, and a daemon with nothing animating does not wake up at all.
+/// Events that were dispatched without reading the socket may have drawn
+/// something, the loop comes right back to flush it.
+pub fn wait_timeout(dispatched_without_read: bool) -> i32 {
+    if dispatched_without_read {
+        0
+    } else {
+        -1
+    }
+}
+
+/// A timerfd the main loop waits on next to the Wayland and IPC sockets,
+/// armed for the earliest deadline of the layers, see `Client::next_deadline`
+///
+/// Deadlines are absolute on the monotonic clock, so a layer's frames stay
+/// on schedule however long the loop took to get back to waiting.
+pub struct Timer {
+    fd: OwnedFd,
+    /// Deadline the timer is armed for, to skip rearming it with the same one
+    armed: Option<Instant>,
+}
+
+impl Timer {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::timerfd_create(
+                libc::CLOCK_MONOTONIC,
+                libc::TFD_CLOEXEC | libc::TFD_NONBLOCK,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            armed: None,
+        })
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    /// Fire at `deadline`, right away if it passed, or never for None
+    pub fn set(&mut self, deadline: Option<Instant>) -> io::Result<()> {
+        if deadline == self.armed {
+            return Ok(());
+        }
+        let value = match deadline {
+            Some(deadline) => {
+                // Read after `now`, so the timer never fires before `deadline`
+                let now = Instant::now();
+                let mut clock = libc::timespec {
+                    tv_sec: 0,
+                    tv_nsec: 0,
+                };
+                if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut clock) } < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // A zero value disarms the timer, a passed deadline still has
+                // to fire
+                let at = Duration::new(clock.tv_sec as u64, clock.tv_nsec as u32)
+                    + deadline.saturating_duration_since(now)
+                    + Duration::from_nanos(1);
+                libc::timespec {
+                    tv_sec: at.as_secs() as _,
+                    tv_nsec: at.subsec_nanos() as _,
+                }
+            }
+            None => libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+        };
+        let spec = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: value,
+        };
+        let result = unsafe {
+            libc::timerfd_settime(
+                self.fd.as_raw_fd(),
+                libc::TFD_TIMER_ABSTIME,
+                &spec,
+                std::ptr::null_mut(),
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.armed = deadline;
+        Ok(())
+    }
+
+    /// Reset the timer after the main loop woke up from it
+    pub fn clear(&mut self) {
+        let mut expirations = 0u64;
+        unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                (&mut expirations as *mut u64).cast(),
+                8,
+            );
+        }
+        self.armed = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epoll::{ControlOptions, Event, Events};
+
+    /// Whether `timer` fires within `timeout` milliseconds
+    fn fires(timer: &Timer, timeout: i32) -> bool {
+        let ep = epoll::create(true).unwrap();
+        epoll::ctl(
+            ep,
+            ControlOptions::EPOLL_CTL_ADD,
+            timer.fd(),
+            Event::new(Events::EPOLLIN, 0),
+        )
+        .unwrap();
+        let mut events = [Event::new(Events::empty(), 0)];
+        let fired = epoll::wait(ep, timeout, &mut events).unwrap() == 1;
+        // SAFETY: `ep` was opened above and is not used after this
+        unsafe { libc::close(ep) };
+        fired
+    }
+
+    #[test]
+    fn test_idle_sleeps() {
+        // Nothing animating and no lost GPU, nothing wakes the loop
+        assert_eq!(next_wake(None, None), None);
+        assert_eq!(wait_timeout(false), -1);
+        assert_eq!(wait_timeout(true), 0);
+
+        let mut timer = Timer::new().unwrap();
+        timer.set(None).unwrap();
+        assert!(!fires(&timer, 100));
+    }
+
+    #[test]
+    fn test_next_wake() {
+        let now = Instant::now();
+        let later = now + Duration::from_millis(20);
+        assert_eq!(next_wake(Some(later), None), Some(later));
+        assert_eq!(next_wake(None, Some(later)), Some(later));
+        assert_eq!(next_wake(Some(later), Some(now)), Some(now));
+
+        let mut timer = Timer::new().unwrap();
+        timer.set(next_wake(Some(later), None)).unwrap();
+        assert!(fires(&timer, 1000));
+        assert!(Instant::now() >= later);
+    }
+}
@@ -1,40 +1,70 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
 use common::{
-    types::{Response, SetCurrentWallpaper, WallpaperInfo, WallpaperSet},
+    colorscheme::ColorScheme,
+    manifest::{parse_setting_value, Permission},
+    types::{
+        CaptureFrame, ColorSchemeSet, ColorsExtracted, DescribeWallpaper, DisplayAdjusted,
+        DisplayAdjustment, ExtractColors, FavoriteSet, FrameCaptured, GetWallpaperSettings,
+        GetWallpaperStats, ListWallpapers, LoadWallpaper, MirrorOutput, OutputMirrored,
+        OutputStats, OverviewModeSet, ParamDescription, PermissionsSet, PlanWallpaper,
+        RandomWallpaperSet, RenderScaleSet, RenderStats, Response, SetColorScheme,
+        SetCurrentWallpaper, SetDisplayAdjustment, SetFavorite, SetOverviewMode, SetPermissions,
+        SetRandomWallpaper, SetRenderScale, SetWallpaperSetting, WallpaperDescription,
+        WallpaperDetails, WallpaperInfo, WallpaperList, WallpaperLoaded, WallpaperPlan,
+        WallpaperSet, WallpaperSettingSet, WallpaperSettings, WallpaperStats,
+    },
     wallpaper::Wallpaper,
 };
 
-use crate::renderer::client::Client;
-
-/// Handle a request to set the current wallpaper
-pub fn handle_set_wallpaper(req: &SetCurrentWallpaper, client: &mut Client) -> Response {
-    // Try to find the requested wallpaper
-    let wallpaper_info = find_wallpaper_by_name(&req.name);
+use wayland_client::QueueHandle;
 
-    // If wallpaper not found, return error
-    if wallpaper_info.is_none() {
-        return Response::WallpaperSet(WallpaperSet {
-            name: req.name.clone(),
-            success: false,
-            error: Some("Wallpaper not found".to_string()),
-        });
-    }
+use crate::{
+    asset::thumbnail, colors, config::expand_home, library, renderer::client::Client, state::State,
+};
 
-    // Try to load the wallpaper
-    let wallpaper_info = wallpaper_info.unwrap();
-    let wallpaper_result = Wallpaper::load(&wallpaper_info.path);
+/// Sends the response to a request, for handlers that answer later
+pub type Reply = Box<dyn FnOnce(Response) + Send>;
 
-    if let Err(e) = wallpaper_result {
-        return Response::WallpaperSet(WallpaperSet {
-            name: req.name.clone(),
-            success: false,
-            error: Some(format!("Failed to load wallpaper: {e}")),
-        });
-    }
-
-    let wallpaper = wallpaper_result.unwrap();
+/// Handle a request to set the current wallpaper
+pub fn handle_set_wallpaper(
+    req: &SetCurrentWallpaper,
+    client: &mut Client,
+    state: &mut State,
+) -> Response {
+    // Try to find the requested wallpaper, in the variant asked for
+    let wallpaper = find_wallpaper(&req.name)
+        .and_then(|wallpaper| customize(wallpaper, req.variant.as_deref(), state));
+    let wallpaper = match wallpaper {
+        Ok(wallpaper) => wallpaper,
+        Err(e) => {
+            // Outputs showing nothing yet get the error wallpaper, the others
+            // keep theirs
+            let outputs: Vec<String> = client
+                .wallpapers
+                .iter()
+                .map(|layer| layer.name.clone())
+                .filter(|output| req.monitor.as_ref().is_none_or(|monitor| monitor == output))
+                .collect();
+            for output in outputs {
+                client.show_error(&output, &format!("{}: {e}", req.name));
+            }
+            return Response::WallpaperSet(WallpaperSet {
+                name: req.name.clone(),
+                success: false,
+                error: Some(e),
+            });
+        }
+    };
+    let name = wallpaper.name().to_string();
+    let path = wallpaper.path.to_string_lossy().to_string();
 
     // If a specific monitor is requested, set only that monitor
-    if let Some(ref monitor_name) = req.monitor {
+    let outputs: Vec<String> = if let Some(ref monitor_name) = req.monitor {
         // Check if the monitor exists
         let found = client
             .wallpapers
@@ -48,123 +78,779 @@ pub fn handle_set_wallpaper(req: &SetCurrentWallpaper, client: &mut Client) -> R
             });
         }
 
-        // Set the wallpaper for the specified monitor
-        for layer in client.wallpapers.iter_mut() {
-            if layer.name == *monitor_name {
-                layer.wallpaper = crate::renderer::pipeline::Pipelines::from(
-                    wallpaper.clone(),
-                    &client.device,
-                    &client.queue,
-                    client.bindgroup_layout_manager.clone(),
-                    client.pipeline_manager.clone(),
+        // Queue the wallpaper for the specified monitor
+        client.assign(monitor_name, wallpaper);
+        vec![monitor_name.clone()]
+    } else {
+        // Queue the wallpaper for all monitors
+        let outputs: Vec<String> = client
+            .wallpapers
+            .iter()
+            .map(|layer| layer.name.clone())
+            .collect();
+        for output in &outputs {
+            tracing::info!("Setting wallpaper for monitor: {output}");
+            client.assign(output, wallpaper.clone());
+        }
+        outputs
+    };
+    state.record_use(&name, &path, req.variant.as_deref(), &outputs);
+
+    Response::WallpaperSet(WallpaperSet {
+        name: req.name.clone(),
+        success: true,
+        error: None,
+    })
+}
+
+/// Handle a request to work out how a wallpaper would be drawn, for
+/// `wlrs set-wallpaper --dry-run`, nothing on screen changes
+pub fn handle_plan_wallpaper(req: &PlanWallpaper, client: &Client, state: &State) -> Response {
+    let fail = |error: String| {
+        Response::WallpaperPlan(WallpaperPlan {
+            name: req.name.clone(),
+            framerate: 0,
+            tickrate: 0,
+            warnings: Vec::new(),
+            outputs: Vec::new(),
+            success: false,
+            error: Some(error),
+        })
+    };
+    let wallpaper = match find_wallpaper(&req.name)
+        .and_then(|wallpaper| customize(wallpaper, req.variant.as_deref(), state))
+    {
+        Ok(wallpaper) => wallpaper,
+        Err(e) => return fail(e),
+    };
+
+    let outputs: Vec<&str> = client
+        .wallpapers
+        .iter()
+        .map(|layer| layer.name.as_str())
+        .filter(|output| {
+            req.monitor
+                .as_deref()
+                .is_none_or(|monitor| monitor == *output)
+        })
+        .collect();
+    if let (Some(monitor), true) = (&req.monitor, outputs.is_empty()) {
+        return fail(format!("Monitor '{monitor}' not found"));
+    }
+
+    let outputs = match outputs
+        .into_iter()
+        .map(|output| client.plan(&wallpaper, output))
+        .collect()
+    {
+        Ok(outputs) => outputs,
+        Err(e) => return fail(e),
+    };
+    Response::WallpaperPlan(WallpaperPlan {
+        name: wallpaper.name().to_string(),
+        framerate: wallpaper.framerate(),
+        tickrate: wallpaper.tickrate(),
+        outputs,
+        warnings: wallpaper.warnings,
+        success: true,
+        error: None,
+    })
+}
+
+/// The wallpaper in its variant `variant`, with the settings of the user
+/// applied over the manifest and the permissions they granted its scripts
+pub fn customize(
+    wallpaper: Wallpaper,
+    variant: Option<&str>,
+    state: &State,
+) -> Result<Wallpaper, String> {
+    let wallpaper = match variant {
+        Some(variant) => wallpaper.with_variant(variant),
+        None => Ok(wallpaper),
+    };
+    wallpaper
+        .and_then(|wallpaper| {
+            let settings = state.settings(wallpaper.name());
+            wallpaper.with_settings(&settings)
+        })
+        .map(|wallpaper| {
+            let granted = state.granted(&wallpaper);
+            let wallpaper = wallpaper.with_permissions(&granted);
+            if wallpaper.permissions.len() < wallpaper.manifest.needs.len() {
+                tracing::warn!(
+                    "{} runs without some of the permissions it needs, grant them with \
+                     `wlrs permissions {} --grant`",
+                    wallpaper.name(),
+                    wallpaper.name()
                 );
-                // Set the framerate and tickrate based on the wallpaper's manifest
-                layer.set_framerate(wallpaper.framerate());
-                layer.set_tickrate(wallpaper.tickrate());
-                layer.damaged = true;
-                break;
             }
-        }
+            wallpaper
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// Handle a request to preload a wallpaper, by name or by the path of its directory
+///
+/// Answers once the wallpaper is queued, its assets are decoded in the background.
+pub fn handle_load_wallpaper(req: &LoadWallpaper, client: &mut Client) -> Response {
+    let wallpaper = if Path::new(&req.path).is_dir() {
+        Wallpaper::load(&req.path).map_err(|e| format!("Failed to load wallpaper: {e}"))
     } else {
-        // Set the wallpaper for all monitors
-        for layer in client.wallpapers.iter_mut() {
-            layer.wallpaper = crate::renderer::pipeline::Pipelines::from(
-                wallpaper.clone(),
-                &client.device,
-                &client.queue,
-                client.bindgroup_layout_manager.clone(),
-                client.pipeline_manager.clone(),
-            );
-            // Set the framerate and tickrate based on the wallpaper's manifest
-            layer.set_framerate(wallpaper.framerate());
-            layer.set_tickrate(wallpaper.tickrate());
-            layer.damaged = true;
-            println!("Setting wallpaper for monitor: {}", layer.name);
-
-            println!("tickrate: {}", wallpaper.tickrate());
+        find_wallpaper(&req.path)
+    };
+
+    match wallpaper {
+        Ok(wallpaper) if req.strict && !wallpaper.warnings.is_empty() => {
+            Response::WallpaperLoaded(WallpaperLoaded {
+                name: wallpaper.name().to_string(),
+                success: false,
+                error: Some("The manifest has warnings, refused in strict mode".to_string()),
+                warnings: wallpaper.warnings,
+            })
+        }
+        Ok(wallpaper) => {
+            let name = wallpaper.name().to_string();
+            let warnings = wallpaper.warnings.clone();
+            client.preload(wallpaper);
+            Response::WallpaperLoaded(WallpaperLoaded {
+                name,
+                success: true,
+                error: None,
+                warnings,
+            })
         }
+        Err(e) => Response::WallpaperLoaded(WallpaperLoaded {
+            name: Path::new(&req.path)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            success: false,
+            error: Some(e),
+            warnings: Vec::new(),
+        }),
     }
+}
 
-    Response::WallpaperSet(WallpaperSet {
+/// Handle a request to set a randomly picked wallpaper
+pub fn handle_set_random_wallpaper(
+    req: &SetRandomWallpaper,
+    client: &mut Client,
+    state: &mut State,
+) -> Response {
+    let current = req.exclude_current.then(|| state.current()).flatten();
+    let candidates: Vec<String> = load_available_wallpapers()
+        .into_iter()
+        .filter(|wallpaper| wallpaper.manifest.has_tags(&req.tags))
+        .map(|wallpaper| wallpaper.manifest.name.clone())
+        .filter(|name| !req.exclude.contains(name) && current != Some(name.as_str()))
+        .collect();
+
+    let Some(name) = state.choose_random(&candidates).cloned() else {
+        return Response::RandomWallpaperSet(RandomWallpaperSet {
+            name: None,
+            success: false,
+            error: Some("No wallpaper matches".to_string()),
+        });
+    };
+
+    let request = SetCurrentWallpaper {
+        name,
+        monitor: req.monitor.clone(),
+        variant: None,
+    };
+    match handle_set_wallpaper(&request, client, state) {
+        Response::WallpaperSet(set) => Response::RandomWallpaperSet(RandomWallpaperSet {
+            name: Some(set.name),
+            success: set.success,
+            error: set.error,
+        }),
+        _ => unreachable!("handle_set_wallpaper always answers with WallpaperSet"),
+    }
+}
+
+/// Handle a request to list the available wallpapers
+pub fn handle_list_wallpapers(req: &ListWallpapers, client: &Client, state: &State) -> Response {
+    let available = load_available_wallpapers();
+    let used = used_paths(&available);
+    let wallpapers = available
+        .iter()
+        .filter(|wallpaper| matches_list_filters(req, wallpaper))
+        .filter(|wallpaper| !req.favorites || state.usage(wallpaper.name()).favorite)
+        .map(|wallpaper| {
+            let details = req
+                .details
+                .then(|| wallpaper_details(wallpaper, client, state));
+            WallpaperInfo {
+                name: wallpaper.manifest.name.clone(),
+                path: wallpaper.path.to_string_lossy().to_string(),
+                shadowed_by: shadowed_by(&used, wallpaper),
+                details,
+            }
+        })
+        .collect();
+
+    Response::WallpaperList(WallpaperList { wallpapers })
+}
+
+/// Whether a wallpaper passes every filter of a list request
+fn matches_list_filters(req: &ListWallpapers, wallpaper: &Wallpaper) -> bool {
+    let manifest = &wallpaper.manifest;
+    req.filter
+        .as_deref()
+        .is_none_or(|filter| manifest.matches_query(filter))
+        && manifest.has_tags(&req.tags)
+        && req
+            .animated
+            .is_none_or(|animated| manifest.is_animated() == animated)
+}
+
+/// Collect the manifest metadata of a wallpaper, queueing its thumbnail to
+/// be rendered if there is none yet
+fn wallpaper_details(wallpaper: &Wallpaper, client: &Client, state: &State) -> WallpaperDetails {
+    let thumbnail = thumbnail::cached(wallpaper);
+    if thumbnail.is_none() {
+        let name = wallpaper.name().to_string();
+        client.loader.thumbnail(wallpaper.clone(), move |result| {
+            if let Err(e) = result {
+                tracing::warn!("No thumbnail for {name}: {e}");
+            }
+        });
+    }
+
+    WallpaperDetails {
+        author: wallpaper.author().to_string(),
+        version: wallpaper.version().to_string(),
+        description: wallpaper.description().to_string(),
+        tags: wallpaper.manifest.tags.clone(),
+        variants: wallpaper
+            .manifest
+            .variants
+            .iter()
+            .map(|variant| variant.name.clone())
+            .collect(),
+        animated: wallpaper.manifest.is_animated(),
+        preview: wallpaper
+            .preview_path()
+            .map(|path| path.to_string_lossy().to_string()),
+        thumbnail: thumbnail.map(|path| path.to_string_lossy().to_string()),
+        usage: state.usage(wallpaper.name()),
+    }
+}
+
+/// Handle a request to mark a wallpaper as favorite or to unmark it
+pub fn handle_set_favorite(req: &SetFavorite, state: &mut State) -> Response {
+    let wallpaper = match find_wallpaper(&req.name) {
+        Ok(wallpaper) => wallpaper,
+        Err(e) => {
+            return Response::FavoriteSet(FavoriteSet {
+                name: req.name.clone(),
+                success: false,
+                error: Some(e),
+            });
+        }
+    };
+
+    state.set_favorite(wallpaper.name(), req.favorite);
+    Response::FavoriteSet(FavoriteSet {
         name: req.name.clone(),
         success: true,
         error: None,
     })
 }
 
-/// Find all available wallpapers in standard directories
-pub fn find_available_wallpapers() -> Vec<WallpaperInfo> {
-    use common::wallpaper::WallpaperDirectory;
-    use std::path::PathBuf;
-
-    let mut all_wallpapers = Vec::new();
-
-    // Define the standard directories where wallpapers can be located
-    let possible_paths = vec![
-        // User-specific wallpapers in data directory
-        directories::BaseDirs::new()
-            .map(|dirs| dirs.data_dir().join("wlrs").join("wallpapers"))
-            .unwrap_or_else(|| PathBuf::from("/tmp/wlrs/wallpapers")),
-        // Example wallpapers in the project directory (for development)
-        PathBuf::from("examples/wallpapers"),
-    ];
-
-    // Check each directory for wallpapers
-    for path in possible_paths {
-        if !path.exists() || !path.is_dir() {
-            continue;
-        }
-
-        // Create a wallpaper directory handler
-        let wallpaper_dir = WallpaperDirectory::new(&path);
-
-        // List all wallpapers in the directory
-        match wallpaper_dir.list_wallpapers() {
-            Ok(names) => {
-                for name in names {
-                    // Attempt to load each wallpaper to get its details
-                    if let Ok(wallpaper) = wallpaper_dir.load_wallpaper(&name) {
-                        all_wallpapers.push(WallpaperInfo {
-                            name: wallpaper.manifest.name.clone(),
-                            path: wallpaper.path.to_string_lossy().to_string(),
-                        });
+/// Handle a request to change settings of a wallpaper
+///
+/// The settings are tried on the wallpaper together before any is stored,
+/// and outputs showing the wallpaper are rebuilt with them.
+pub fn handle_set_wallpaper_setting(
+    req: &SetWallpaperSetting,
+    client: &mut Client,
+    state: &mut State,
+) -> Response {
+    let fail = |error: String| {
+        Response::WallpaperSettingSet(WallpaperSettingSet {
+            name: req.name.clone(),
+            success: false,
+            error: Some(error),
+        })
+    };
+    let wallpaper = match find_wallpaper(&req.name) {
+        Ok(wallpaper) => wallpaper,
+        Err(e) => return fail(e),
+    };
+    let name = wallpaper.name().to_string();
+
+    let mut settings = state.settings(&name);
+    for (key, value) in &req.changes {
+        match value {
+            Some(value) => {
+                settings.insert(key.clone(), parse_setting_value(value));
+            }
+            None => {
+                settings.remove(key);
+            }
+        }
+    }
+    if let Err(e) = wallpaper.clone().with_settings(&settings) {
+        return fail(e.to_string());
+    }
+    state.set_settings(&name, settings);
+
+    let outputs: Vec<(String, Option<String>)> = client
+        .assignments
+        .iter()
+        .filter(|(_, wallpaper)| wallpaper.name() == name)
+        .map(|(output, wallpaper)| (output.clone(), wallpaper.variant.clone()))
+        .collect();
+    for (output, variant) in outputs {
+        match customize(wallpaper.clone(), variant.as_deref(), state) {
+            Ok(wallpaper) => client.assign(&output, wallpaper),
+            Err(e) => tracing::warn!("Failed to rebuild {name} on {output}: {e}"),
+        }
+    }
+
+    Response::WallpaperSettingSet(WallpaperSettingSet {
+        name,
+        success: true,
+        error: None,
+    })
+}
+
+/// Handle a request to grant or revoke the permissions of a wallpaper,
+/// outputs showing it are rebuilt with the new ones
+pub fn handle_set_permissions(
+    req: &SetPermissions,
+    client: &mut Client,
+    state: &mut State,
+) -> Response {
+    let wallpaper = match find_wallpaper(&req.name) {
+        Ok(wallpaper) => wallpaper,
+        Err(e) => {
+            return Response::PermissionsSet(PermissionsSet {
+                name: req.name.clone(),
+                needs: Vec::new(),
+                granted: Vec::new(),
+                stale: false,
+                success: false,
+                error: Some(e),
+            });
+        }
+    };
+    let name = wallpaper.name().to_string();
+    let mut error = None;
+
+    if let Some(grant) = req.grant {
+        match state.set_granted(&wallpaper, grant) {
+            Ok(()) => {
+                tracing::info!(
+                    "{} the permissions of {name}",
+                    if grant { "Granted" } else { "Revoked" }
+                );
+                let outputs: Vec<(String, Option<String>)> = client
+                    .assignments
+                    .iter()
+                    .filter(|(_, wallpaper)| wallpaper.name() == name)
+                    .map(|(output, wallpaper)| (output.clone(), wallpaper.variant.clone()))
+                    .collect();
+                for (output, variant) in outputs {
+                    match customize(wallpaper.clone(), variant.as_deref(), state) {
+                        Ok(wallpaper) => client.assign(&output, wallpaper),
+                        Err(e) => tracing::warn!("Failed to rebuild {name} on {output}: {e}"),
                     }
                 }
             }
-            Err(_) => continue, // Skip directories that cannot be read
+            Err(e) => error = Some(format!("Failed to read the scripts of {name}: {e}")),
         }
     }
 
-    all_wallpapers
+    let as_strings = |permissions: &[Permission]| {
+        permissions
+            .iter()
+            .map(|permission| permission.as_str().to_string())
+            .collect()
+    };
+    Response::PermissionsSet(PermissionsSet {
+        needs: as_strings(&wallpaper.manifest.needs),
+        granted: as_strings(&state.granted(&wallpaper)),
+        stale: state.grant_is_stale(&wallpaper),
+        success: error.is_none(),
+        error,
+        name,
+    })
+}
+
+/// Handle a request for the settings stored for a wallpaper
+pub fn handle_get_wallpaper_settings(req: &GetWallpaperSettings, state: &State) -> Response {
+    let name = find_wallpaper(&req.name)
+        .map(|wallpaper| wallpaper.name().to_string())
+        .unwrap_or_else(|_| req.name.clone());
+    let settings = state
+        .settings(&name)
+        .into_iter()
+        .map(|(key, value)| (key, value.to_string()))
+        .collect();
+    Response::WallpaperSettings(WallpaperSettings { name, settings })
+}
+
+/// Handle a request for the parameters a wallpaper declares, along with the
+/// values the user set for them
+pub fn handle_describe_wallpaper(req: &DescribeWallpaper, state: &State) -> Response {
+    let wallpaper = match find_wallpaper(&req.name) {
+        Ok(wallpaper) => wallpaper,
+        Err(e) => {
+            return Response::WallpaperDescription(WallpaperDescription {
+                name: req.name.clone(),
+                params: Vec::new(),
+                variants: Vec::new(),
+                success: false,
+                error: Some(e),
+            });
+        }
+    };
+    let settings = state.settings(wallpaper.name());
+    let params = wallpaper
+        .manifest
+        .params
+        .iter()
+        .map(|(name, spec)| ParamDescription {
+            name: name.clone(),
+            kind: spec.kind.as_str().to_string(),
+            label: spec.label.clone().unwrap_or_else(|| name.clone()),
+            description: spec.description.clone(),
+            min: spec.min,
+            max: spec.max,
+            step: spec.step,
+            default: spec.default.as_ref().map(ToString::to_string),
+            options: spec.options.clone(),
+            value: settings.get(name).map(ToString::to_string),
+        })
+        .collect();
+
+    Response::WallpaperDescription(WallpaperDescription {
+        name: wallpaper.name().to_string(),
+        params,
+        variants: wallpaper
+            .manifest
+            .variants
+            .iter()
+            .map(|variant| variant.name.clone())
+            .collect(),
+        success: true,
+        error: None,
+    })
+}
+
+/// Handle a request for the usage recorded for one or all wallpapers
+pub fn handle_get_wallpaper_stats(req: &GetWallpaperStats, state: &State) -> Response {
+    let wallpapers = match &req.name {
+        Some(name) => vec![(name.clone(), state.usage(name))],
+        None => state
+            .wallpapers()
+            .map(|(name, usage)| (name.to_string(), usage.clone()))
+            .collect(),
+    };
+
+    Response::WallpaperStats(WallpaperStats { wallpapers })
 }
 
-/// Find a wallpaper by name
-pub fn find_wallpaper_by_name(name: &str) -> Option<WallpaperInfo> {
-    // Get all available wallpapers
-    let wallpapers = find_available_wallpapers();
+/// Handle a request for render statistics of every output
+pub fn handle_get_stats(client: &Client) -> Response {
+    let outputs = client
+        .wallpapers
+        .iter()
+        .map(|layer| {
+            let mut models = BTreeMap::new();
+            for model in layer.wallpaper.lock().unwrap().iter() {
+                *models.entry(model.kind().to_string()).or_insert(0) += 1;
+            }
+            OutputStats {
+                output_name: layer.name.clone(),
+                wallpaper: client
+                    .assignment(&layer.name)
+                    .map(|wallpaper| wallpaper.name().to_string()),
+                fps: layer.stats.fps(),
+                refresh_rate: layer.refresh_rate(),
+                frame_time_avg_us: layer.stats.average_frame_time().as_micros() as u64,
+                frame_time_p99_us: layer.stats.percentile_frame_time(0.99).as_micros() as u64,
+                frames: layer.stats.frames(),
+                dropped_frames: layer.stats.dropped(),
+                gpu_errors: layer.stats.gpu_errors().map(str::to_string).collect(),
+                models: models.into_iter().collect(),
+            }
+        })
+        .collect();
+
+    let cache = client.asset_cache.lock().unwrap().stats();
+    Response::RenderStats(RenderStats {
+        outputs,
+        texture_bytes: cache.bytes,
+        texture_budget: cache.budget,
+    })
+}
 
-    // Find the wallpaper with the matching name
-    wallpapers.into_iter().find(|wp| wp.name == name)
+/// Handle a request to show the wallpaper of one output on others
+pub fn handle_mirror_output(req: &MirrorOutput, client: &mut Client) -> Response {
+    let result = client.mirror(&req.source, &req.targets);
+    Response::OutputMirrored(OutputMirrored {
+        success: result.is_ok(),
+        error: result.err(),
+    })
+}
+
+/// Handle a request to render outputs at another resolution
+pub fn handle_set_render_scale(req: &SetRenderScale, client: &mut Client) -> Response {
+    let result = client.set_render_scale(req.output.as_deref(), req.scale);
+    Response::RenderScaleSet(RenderScaleSet {
+        success: result.is_ok(),
+        error: result.err(),
+    })
+}
+
+/// Handle a request to change the brightness, contrast, gamma or color
+/// temperature of outputs
+pub fn handle_set_display_adjustment(req: &SetDisplayAdjustment, client: &mut Client) -> Response {
+    let result = client.adjust_display(req.output.as_deref(), |adjustment| {
+        if req.reset {
+            *adjustment = DisplayAdjustment::default();
+        }
+        if let Some(brightness) = req.brightness {
+            adjustment.brightness = brightness;
+        }
+        if let Some(contrast) = req.contrast {
+            adjustment.contrast = contrast;
+        }
+        if let Some(gamma) = req.gamma {
+            adjustment.gamma = gamma;
+        }
+        if let Some(temperature) = req.temperature {
+            adjustment.temperature = temperature;
+        }
+    });
+    Response::DisplayAdjusted(DisplayAdjusted {
+        success: result.is_ok(),
+        error: result.err(),
+    })
+}
+
+/// Handle a request to blur and dim outputs for the compositor's overview
+pub fn handle_set_overview_mode(req: &SetOverviewMode, client: &mut Client) -> Response {
+    let result = client.set_overview(req.output.as_deref(), req.enabled);
+    Response::OverviewModeSet(OverviewModeSet {
+        success: result.is_ok(),
+        error: result.err(),
+    })
+}
+
+/// Handle a request to recolor wallpapers with a color scheme
+pub fn handle_set_color_scheme(req: &SetColorScheme) -> Response {
+    let result = if req.colors.is_empty() {
+        colors::set(None);
+        Ok(())
+    } else {
+        ColorScheme::from_list(&req.colors)
+            .map(|scheme| colors::set(Some(scheme)))
+            .map_err(|e| e.to_string())
+    };
+    Response::ColorSchemeSet(ColorSchemeSet {
+        success: result.is_ok(),
+        error: result.err(),
+    })
+}
+
+/// Handle a request to compute a color scheme from the wallpaper of an output
+///
+/// The palette comes from the wallpaper's thumbnail, which leaves out text
+/// and shader effects but is cached and cheap to read. A loader thread
+/// answers, as the thumbnail may have to be rendered first.
+pub fn handle_extract_colors(req: &ExtractColors, client: &Client, reply: Reply) {
+    let wallpaper = match &req.output {
+        Some(output) => client.assignment(output),
+        None => client
+            .wallpapers
+            .iter()
+            .find_map(|layer| client.assignment(&layer.name)),
+    };
+    let Some(wallpaper) = wallpaper else {
+        return reply(colors_extracted(
+            None,
+            Err("No wallpaper is shown there".to_string()),
+        ));
+    };
+
+    let name = wallpaper.name().to_string();
+    client.loader.thumbnail(wallpaper.clone(), move |path| {
+        let result = path.and_then(|path| {
+            let image = image::open(&path).map_err(|e| e.to_string())?.to_rgb8();
+            let pixels: Vec<[u8; 3]> = image.pixels().map(|pixel| pixel.0).collect();
+            ColorScheme::from_pixels(&pixels).ok_or_else(|| "The wallpaper is empty".to_string())
+        });
+        reply(colors_extracted(Some(name), result));
+    });
+}
+
+fn colors_extracted(wallpaper: Option<String>, result: Result<ColorScheme, String>) -> Response {
+    Response::ColorsExtracted(match result {
+        Ok(scheme) => ColorsExtracted {
+            wallpaper,
+            colors: scheme.to_list(),
+            success: true,
+            error: None,
+        },
+        Err(e) => ColorsExtracted {
+            wallpaper,
+            colors: Vec::new(),
+            success: false,
+            error: Some(e),
+        },
+    })
+}
+
+/// Handle a request to save the frame an output shows as a PNG image
+///
+/// The frame is drawn right away, so animated wallpapers are captured in
+/// their current state.
+pub fn handle_capture_frame(
+    req: &CaptureFrame,
+    client: &mut Client,
+    qh: &QueueHandle<Client>,
+) -> Response {
+    let layer = match &req.output {
+        Some(output) => client
+            .wallpapers
+            .iter_mut()
+            .find(|layer| layer.name == *output),
+        None => client
+            .wallpapers
+            .iter_mut()
+            .find(|layer| layer.configured && !layer.wallpaper.lock().unwrap().is_empty()),
+    };
+    let output = layer.as_ref().map(|layer| layer.name.clone());
+    let result = layer
+        .ok_or_else(|| match &req.output {
+            Some(output) => format!("Output {output} not found"),
+            None => "No output shows a wallpaper".to_string(),
+        })
+        .and_then(|layer| layer.capture(qh, &client.device, &client.queue))
+        .and_then(|frame| {
+            frame
+                .save_with_format(&req.path, image::ImageFormat::Png)
+                .map_err(|e| format!("Failed to write {}: {e}", req.path))
+        });
+
+    Response::FrameCaptured(FrameCaptured {
+        output,
+        success: result.is_ok(),
+        error: result.err(),
+    })
+}
+
+/// Find all available wallpapers in standard directories
+pub fn find_available_wallpapers() -> Vec<WallpaperInfo> {
+    let wallpapers = load_available_wallpapers();
+    let used = used_paths(&wallpapers);
+    wallpapers
+        .iter()
+        .map(|wallpaper| WallpaperInfo {
+            name: wallpaper.manifest.name.clone(),
+            path: wallpaper.path.to_string_lossy().to_string(),
+            shadowed_by: shadowed_by(&used, wallpaper),
+            details: None,
+        })
+        .collect()
+}
+
+/// Directory of the wallpaper each name stands for, the first one found
+fn used_paths(wallpapers: &[Arc<Wallpaper>]) -> HashMap<&str, &Path> {
+    let mut used = HashMap::new();
+    for wallpaper in wallpapers {
+        used.entry(wallpaper.name())
+            .or_insert(wallpaper.path.as_path());
+    }
+    used
+}
+
+/// Directory of the wallpaper used instead of `wallpaper` when set by name
+fn shadowed_by(used: &HashMap<&str, &Path>, wallpaper: &Wallpaper) -> Option<String> {
+    used.get(wallpaper.name())
+        .filter(|path| **path != wallpaper.path)
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// Load every valid wallpaper from the standard directories
+///
+/// Wallpapers come in the order of the search paths, see `library::wallpapers`.
+pub fn load_available_wallpapers() -> Vec<Arc<Wallpaper>> {
+    library::wallpapers()
+}
+
+/// Find a wallpaper by name, by the path of its directory, or by
+/// `dir/name` with `dir` the end of the directory it is in
+///
+/// Of several wallpapers with the same name the one in the earliest search
+/// path is used, a path or `dir/name` has to match a single wallpaper.
+pub fn find_wallpaper(reference: &str) -> Result<Wallpaper, String> {
+    let wallpapers = load_available_wallpapers();
+    if let Some(wallpaper) = wallpapers
+        .iter()
+        .find(|wallpaper| wallpaper.name() == reference)
+    {
+        return Ok(Wallpaper::clone(wallpaper));
+    }
+    if !reference.contains('/') {
+        return Err("Wallpaper not found".to_string());
+    }
+
+    let reference_path = expand_home(PathBuf::from(reference));
+    let matches: Vec<Arc<Wallpaper>> = wallpapers
+        .into_iter()
+        .filter(|wallpaper| matches_reference(wallpaper, &reference_path))
+        .collect();
+    match matches.len() {
+        0 => Err("Wallpaper not found".to_string()),
+        1 => Ok(Wallpaper::clone(&matches[0])),
+        _ => Err(format!(
+            "'{reference}' matches several wallpapers, use one of their paths: {}",
+            matches
+                .iter()
+                .map(|wallpaper| wallpaper.path.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Whether `reference` is the end of the wallpaper's path, or the end of
+/// the directory it is in followed by its manifest name
+fn matches_reference(wallpaper: &Wallpaper, reference: &Path) -> bool {
+    if wallpaper.path.ends_with(reference) {
+        return true;
+    }
+    let (Some(dir), Some(name)) = (reference.parent(), reference.file_name()) else {
+        return false;
+    };
+    name == wallpaper.name()
+        && wallpaper
+            .path
+            .parent()
+            .is_some_and(|parent| parent.ends_with(dir))
 }
 
 /// Ensure that the wallpaper directory exists
 pub fn ensure_wallpaper_directory() {
     use std::fs;
-    use std::path::PathBuf;
 
     // Get the user-specific wallpapers directory
-    let user_wallpaper_dir = directories::BaseDirs::new()
-        .map(|dirs| dirs.data_dir().join("wlrs").join("wallpapers"))
-        .unwrap_or_else(|| PathBuf::from("/tmp/wlrs/wallpapers"));
+    let user_wallpaper_dir = library::install_dir();
 
     // Create the directory if it doesn't exist
     if !user_wallpaper_dir.exists() {
-        println!(
+        tracing::info!(
             "Creating user wallpaper directory: {}",
             user_wallpaper_dir.display()
         );
         if let Err(e) = fs::create_dir_all(&user_wallpaper_dir) {
-            eprintln!("Failed to create user wallpaper directory: {e}");
+            tracing::error!("Failed to create user wallpaper directory: {e}");
         }
     }
 }
@@ -0,0 +1,43 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    sync::OnceLock,
+};
+
+/// Event file the main loop waits on next to the Wayland and IPC sockets
+static EVENT: OnceLock<OwnedFd> = OnceLock::new();
+
+/// Create the event file, before any thread may call `wake`
+pub fn init() -> io::Result<RawFd> {
+    let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = EVENT.get_or_init(|| unsafe { OwnedFd::from_raw_fd(fd) });
+    Ok(fd.as_raw_fd())
+}
+
+/// Interrupt the main loop's wait, for threads whose results it picks up,
+/// like finished loads or a new color scheme
+///
+/// The main loop sleeps until the next frame is due, indefinitely when
+/// nothing is animated, so changes it would otherwise only notice on its
+/// next iteration have to wake it.
+pub fn wake() {
+    if let Some(fd) = EVENT.get() {
+        let one = 1u64;
+        unsafe {
+            libc::write(fd.as_raw_fd(), (&one as *const u64).cast(), 8);
+        }
+    }
+}
+
+/// Reset the event file after the main loop woke up from it
+pub fn clear() {
+    if let Some(fd) = EVENT.get() {
+        let mut count = 0u64;
+        unsafe {
+            libc::read(fd.as_raw_fd(), (&mut count as *mut u64).cast(), 8);
+        }
+    }
+}
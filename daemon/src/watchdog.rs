@@ -0,0 +1,124 @@
+use std::{
+    collections::VecDeque,
+    env,
+    ffi::OsString,
+    io,
+    os::unix::process::{CommandExt, ExitStatusExt},
+    process::{Command, ExitStatus},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Crashes within `CRASH_WINDOW` after which the supervisor gives up, a
+/// renderer that cannot stay up would only flicker the screen
+const MAX_CRASHES: usize = 5;
+const CRASH_WINDOW: Duration = Duration::from_secs(60);
+
+/// Pause before starting the renderer again
+const RESTART_DELAY: Duration = Duration::from_millis(200);
+
+/// Exit code of a daemon that does not start because another one is
+/// running, the supervisor stops too rather than starting it again
+pub const ALREADY_RUNNING: i32 = 3;
+
+/// Run the daemon as a child process and start it again when it crashes,
+/// e.g. on a segfault in the GPU driver, returning the exit code for the
+/// supervisor
+///
+/// The renderer writes the wallpaper of every output to the state file as
+/// they change, so a restarted one shows them again with `--restore`. A
+/// renderer that exits cleanly, after `wlrs stop` or when it was replaced,
+/// that was terminated on purpose or that found another daemon running ends
+/// the supervisor too.
+pub fn supervise() -> i32 {
+    let executable = match env::current_exe() {
+        Ok(executable) => executable,
+        Err(e) => {
+            tracing::error!("Failed to find the daemon executable: {e}");
+            return 1;
+        }
+    };
+    let args: Vec<OsString> = env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--watchdog")
+        .collect();
+    // Started with `--restore` already, clap refuses the flag twice
+    let restores = args.iter().any(|arg| arg == "--restore");
+
+    let mut crashes = VecDeque::new();
+    let mut restore = false;
+    loop {
+        let mut command = Command::new(&executable);
+        command.args(&args);
+        if restore && !restores {
+            command.arg("--restore");
+        }
+        // SAFETY: prctl is async-signal-safe
+        unsafe {
+            command.pre_exec(|| {
+                // Do not outlive the supervisor when it is stopped
+                if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let status = match command.status() {
+            Ok(status) => status,
+            Err(e) => {
+                tracing::error!("Failed to start the renderer: {e}");
+                return 1;
+            }
+        };
+        if !crashed(status) {
+            return status.code().unwrap_or(0);
+        }
+
+        let now = Instant::now();
+        crashes.push_back(now);
+        while crashes
+            .front()
+            .is_some_and(|&crash| now.duration_since(crash) > CRASH_WINDOW)
+        {
+            crashes.pop_front();
+        }
+        if crashes.len() > MAX_CRASHES {
+            tracing::error!(
+                "The renderer crashed {} times within {}s, giving up",
+                crashes.len(),
+                CRASH_WINDOW.as_secs()
+            );
+            return status.code().unwrap_or(1);
+        }
+        tracing::error!("The renderer stopped with {status}, restarting it");
+        thread::sleep(RESTART_DELAY);
+        restore = true;
+    }
+}
+
+/// Whether the renderer died of a bug rather than being stopped or finding
+/// another daemon running
+fn crashed(status: ExitStatus) -> bool {
+    match status.signal() {
+        Some(signal) => !matches!(signal, libc::SIGTERM | libc::SIGINT | libc::SIGHUP),
+        None => !matches!(status.code(), Some(0 | ALREADY_RUNNING)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crashed() {
+        let exited = |code: i32| ExitStatus::from_raw(code << 8);
+        assert!(!crashed(exited(0)));
+        assert!(!crashed(exited(ALREADY_RUNNING)));
+        assert!(crashed(exited(1)));
+        assert!(crashed(exited(101)));
+
+        assert!(!crashed(ExitStatus::from_raw(libc::SIGTERM)));
+        assert!(crashed(ExitStatus::from_raw(libc::SIGSEGV)));
+        assert!(crashed(ExitStatus::from_raw(libc::SIGABRT)));
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    io::Read,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use common::manifest::Weather;
+use serde_json::Value;
+
+use crate::config::{WeatherConfig, WeatherProvider};
+
+/// Wait before looking up the weather again after a failure
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Largest response accepted from a provider
+const MAX_RESPONSE_SIZE: u64 = 256 * 1024;
+
+static CURRENT: Mutex<Weather> = Mutex::new(Weather::Clear);
+static CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Start looking up the weather in the background, as often as configured
+pub fn start(config: WeatherConfig) {
+    if config.provider == WeatherProvider::OpenWeatherMap && config.api_key.is_none() {
+        tracing::error!("The openweathermap weather provider needs an api_key");
+        return;
+    }
+
+    let spawned = thread::Builder::new()
+        .name("weather".to_string())
+        .spawn(move || {
+            let agent = ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .user_agent(concat!("wlrs/", env!("CARGO_PKG_VERSION")))
+                .build();
+            let interval = Duration::from_secs(config.interval.max(1) * 60);
+            loop {
+                let wait = match look_up(&agent, &config) {
+                    Ok(weather) => {
+                        let mut current = CURRENT.lock().unwrap();
+                        if *current != weather {
+                            tracing::info!("The weather is {} now", weather.name());
+                            *current = weather;
+                            CHANGED.store(true, Ordering::Release);
+                            crate::wake::wake();
+                        }
+                        interval
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to look up the weather: {e}");
+                        RETRY_INTERVAL.min(interval)
+                    }
+                };
+                thread::sleep(wait);
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::error!("Failed to start looking up the weather: {e}");
+    }
+}
+
+/// The weather outside, clear until it was looked up
+pub fn current() -> Weather {
+    *CURRENT.lock().unwrap()
+}
+
+/// Whether the weather changed since the last call
+pub fn take_change() -> bool {
+    CHANGED.swap(false, Ordering::Acquire)
+}
+
+fn look_up(agent: &ureq::Agent, config: &WeatherConfig) -> Result<Weather, String> {
+    let (url, code) = match config.provider {
+        WeatherProvider::OpenMeteo => (
+            format!(
+                "https://api.open-meteo.com/v1/forecast?latitude={}&longitude={}&current=weather_code",
+                config.latitude, config.longitude
+            ),
+            "/current/weather_code",
+        ),
+        WeatherProvider::OpenWeatherMap => (
+            format!(
+                "https://api.openweathermap.org/data/2.5/weather?lat={}&lon={}&appid={}",
+                config.latitude,
+                config.longitude,
+                config.api_key.as_deref().unwrap_or_default()
+            ),
+            "/weather/0/id",
+        ),
+    };
+
+    let mut body = String::new();
+    agent
+        .get(&url)
+        .call()
+        .map_err(|e| redact(&e.to_string(), config))?
+        .into_reader()
+        .take(MAX_RESPONSE_SIZE)
+        .read_to_string(&mut body)
+        .map_err(|e| e.to_string())?;
+    let response: Value = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+    let code = response
+        .pointer(code)
+        .and_then(Value::as_f64)
+        .ok_or_else(|| format!("The response has no {code}"))? as u32;
+
+    match config.provider {
+        WeatherProvider::OpenMeteo => from_wmo_code(code),
+        WeatherProvider::OpenWeatherMap => from_openweathermap_id(code),
+    }
+    .ok_or_else(|| format!("Unknown weather code {code}"))
+}
+
+/// Hide the API key from an error, ureq names the URL it failed to fetch
+fn redact(error: &str, config: &WeatherConfig) -> String {
+    match config.api_key.as_deref() {
+        Some(key) if !key.is_empty() => error.replace(key, "<api_key>"),
+        _ => error.to_string(),
+    }
+}
+
+/// Weather of a WMO 4677 code, as reported by Open-Meteo
+fn from_wmo_code(code: u32) -> Option<Weather> {
+    Some(match code {
+        0 | 1 => Weather::Clear,
+        2 | 3 => Weather::Cloudy,
+        45 | 48 => Weather::Fog,
+        51..=67 | 80..=82 => Weather::Rain,
+        71..=77 | 85 | 86 => Weather::Snow,
+        95..=99 => Weather::Storm,
+        _ => return None,
+    })
+}
+
+/// Weather of an OpenWeatherMap condition id
+fn from_openweathermap_id(id: u32) -> Option<Weather> {
+    Some(match id {
+        200..=299 | 781 => Weather::Storm,
+        300..=399 | 500..=599 => Weather::Rain,
+        600..=699 => Weather::Snow,
+        700..=799 => Weather::Fog,
+        800 | 801 => Weather::Clear,
+        802..=804 => Weather::Cloudy,
+        _ => return None,
+    })
+}
@@ -0,0 +1,149 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use basis_universal::{
+    BasisTextureFormat, ColorSpace, Compressor, CompressorParams, TranscodeParameters, Transcoder,
+    TranscoderTextureFormat,
+};
+use common::{
+    baked::{baked_path, BakedTexture, BlockFormat},
+    manifest::{LayerContent, WallpaperManifest},
+};
+use image::RgbaImage;
+
+/// An image that was baked, with what its texture takes on the GPU
+pub struct Baked {
+    pub width: u32,
+    pub height: u32,
+    /// Size of the image decoded to RGBA with its mip chain
+    pub rgba_bytes: u64,
+    /// Size of the baked texture
+    pub baked_bytes: u64,
+}
+
+/// Images of a wallpaper worth baking, relative to its directory
+///
+/// Those are the still images of layers sampled with mipmaps, GIFs are
+/// animated and streamed from disk instead.
+pub fn images(manifest: &WallpaperManifest) -> Vec<String> {
+    let images: BTreeSet<_> = manifest
+        .layers
+        .iter()
+        .filter(|layer| layer.sampling.uses_mipmaps())
+        .filter_map(|layer| match &layer.content {
+            LayerContent::Image(path) => Some(path.clone()),
+            _ => None,
+        })
+        .filter(|path| {
+            !Path::new(path)
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"))
+        })
+        .collect();
+    images.into_iter().collect()
+}
+
+/// Compress an image into BC7 and ASTC blocks next to it, see `common::baked`
+///
+/// Images whose size is not a multiple of 4 are padded with their last row
+/// and column, GPUs only take whole blocks.
+pub fn bake(path: &Path) -> Result<Baked, String> {
+    let source = fs::read(path).map_err(|e| e.to_string())?;
+    let image = image::load_from_memory(&source)
+        .map_err(|e| e.to_string())?
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Err("The image is empty".to_string());
+    }
+    let image = pad(&image);
+
+    let basis = compress(&image)?;
+    let mut transcoder = Transcoder::new();
+    transcoder
+        .prepare_transcoding(&basis)
+        .map_err(|()| "Failed to read the encoded texture".to_string())?;
+    let mut baked = Vec::new();
+    for format in BlockFormat::ALL {
+        let texture = transcode(&transcoder, &basis, format, image.width(), image.height())?;
+        texture
+            .write(&baked_path(path, format), &source)
+            .map_err(|e| e.to_string())?;
+        baked.push(texture);
+    }
+
+    // Both formats take the same space, a GPU only loads one of them
+    let baked = &baked[0];
+    let rgba_bytes = (0..baked.levels.len())
+        .map(|level| {
+            let (width, height) = baked.level_size(level);
+            width as u64 * height as u64 * 4
+        })
+        .sum();
+    Ok(Baked {
+        width,
+        height,
+        rgba_bytes,
+        baked_bytes: baked.levels.iter().map(|level| level.len() as u64).sum(),
+    })
+}
+
+/// Extend an image to whole 4×4 blocks by repeating its last row and column
+fn pad(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    RgbaImage::from_fn(width.div_ceil(4) * 4, height.div_ceil(4) * 4, |x, y| {
+        *image.get_pixel(x.min(width - 1), y.min(height - 1))
+    })
+}
+
+/// Encode an image and its mip chain as UASTC, which transcodes to both
+/// block formats with little loss
+fn compress(image: &RgbaImage) -> Result<Vec<u8>, String> {
+    let mut params = CompressorParams::new();
+    params.set_basis_format(BasisTextureFormat::UASTC4x4);
+    params.set_color_space(ColorSpace::Srgb);
+    params.set_generate_mipmaps(true);
+    params.set_mipmap_smallest_dimension(1);
+    params
+        .source_image_mut(0)
+        .init(image.as_raw(), image.width(), image.height(), 4);
+
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut compressor = Compressor::new(threads as u32);
+    // SAFETY: the parameters hold one RGBA image of the size given and
+    // otherwise keep their defaults
+    unsafe {
+        if !compressor.init(&params) {
+            return Err("Failed to set up the texture encoder".to_string());
+        }
+        compressor
+            .process()
+            .map_err(|e| format!("Failed to encode the texture: {e:?}"))?;
+    }
+    Ok(compressor.basis_file().to_vec())
+}
+
+/// Transcode every mip level of the encoded texture into `format`
+fn transcode(
+    transcoder: &Transcoder,
+    basis: &[u8],
+    format: BlockFormat,
+    width: u32,
+    height: u32,
+) -> Result<BakedTexture, String> {
+    let target = match format {
+        BlockFormat::Bc7 => TranscoderTextureFormat::BC7_RGBA,
+        BlockFormat::Astc => TranscoderTextureFormat::ASTC_4x4_RGBA,
+    };
+    let levels = (0..transcoder.image_level_count(basis, 0))
+        .map(|level| {
+            let parameters = TranscodeParameters {
+                level_index: level,
+                ..Default::default()
+            };
+            transcoder
+                .transcode_image_level(basis, target, parameters)
+                .map_err(|e| format!("Failed to transcode mip level {level}: {e:?}"))
+        })
+        .collect::<Result<_, _>>()?;
+    BakedTexture::new(format, width, height, levels).map_err(|e| e.to_string())
+}
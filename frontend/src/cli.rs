@@ -1,28 +1,111 @@
-use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
 
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+/// Control the wlrs wallpaper daemon
+// Named after the installed binary rather than the package, for completions
+// and man pages
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(name = "wlrs", author, version, about, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Seconds to wait for the daemon to answer, 0 to wait forever
+    #[arg(
+        long,
+        global = true,
+        value_name = "SECONDS",
+        default_value_t = common::ipc::DEFAULT_TIMEOUT.as_secs()
+    )]
+    pub timeout: u64,
+
+    /// Talk to the daemon started with the same `--namespace`, e.g. in a
+    /// nested compositor. `WLRS_SOCKET` overrides the socket path entirely
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        value_parser = common::ipc::parse_namespace
+    )]
+    pub namespace: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Check if the daemon is running
     Ping(PingArgs),
-    /// Load an installed wallpaper
+    /// Decode a wallpaper in the background so setting it later is instant
     LoadWallpaper(LoadWallpaperArgs),
     /// List all available wallpapers
+    #[command(visible_alias = "list")]
     ListWallpapers(ListWallpapersArgs),
     /// Install a wallpaper from a directory
     InstallWallpaper(InstallWallpaperArgs),
+    /// Search the wallpapers offered by the configured repositories
+    Browse(BrowseArgs),
+    /// Download a wallpaper from a repository and install it
+    Fetch(FetchArgs),
+    /// Convert a Wallpaper Engine project into a wallpaper and install it
+    ImportWe(ImportWeArgs),
+    /// Install the wallpaper GNOME or KDE Plasma is set to, a slideshow as a playlist
+    ImportDesktop(ImportDesktopArgs),
+    /// Show the directories wallpapers are looked up in, in order of precedence
+    Dirs(DirsArgs),
+    /// Check the files of a wallpaper against the hashes in its manifest
+    Verify(VerifyArgs),
+    /// Upgrade manifests written for an older wlrs to the current format, in place
+    MigrateManifest(MigrateManifestArgs),
+    /// Pre-compress the images of a wallpaper into GPU textures stored next to them
+    Bake(BakeArgs),
     /// Set the current wallpaper by name
     SetWallpaper(SetWallpaperArgs),
+    /// Set a randomly picked wallpaper, preferring favorites
+    Random(RandomArgs),
+    /// Mark a wallpaper as favorite
+    Fav(FavArgs),
+    /// Override parameters of a wallpaper's layers, or show the overrides
+    ConfigWallpaper(ConfigWallpaperArgs),
+    /// Show the parameters a wallpaper declares for config-wallpaper, and its variants
+    Describe(DescribeArgs),
+    /// Show, grant or revoke what a wallpaper's scripts may do
+    Permissions(PermissionsArgs),
     /// Gracefully stop the daemon
     Stop(StopArgs),
+    /// Start a new daemon that takes over the wallpapers of the running one
+    Restart(RestartArgs),
     /// Query active wallpapers on all monitors
     Query(QueryArgs),
+    /// Inspect or clear the daemon's decoded asset cache
+    Cache(CacheArgs),
+    /// Install a systemd user service that starts the daemon with the session
+    InstallService(InstallServiceArgs),
+    /// Change which messages the daemon logs
+    LogLevel(LogLevelArgs),
+    /// Show frame rate, frame times and memory use of every output
+    Stats(StatsArgs),
+    /// Show the wallpaper of one output on others, in sync
+    Mirror(MirrorArgs),
+    /// Render at a fraction or multiple of the monitor resolution
+    RenderScale(RenderScaleArgs),
+    /// Dim, warm or otherwise adjust the colors of the wallpaper
+    Adjust(AdjustArgs),
+    /// Blur and dim the wallpaper while the compositor shows an overview or launcher
+    OverviewMode(OverviewModeArgs),
+    /// Recolor wallpapers with a pywal or matugen scheme, or extract one from the wallpaper
+    Colors(ColorsArgs),
+    /// Save the frame a monitor shows as a PNG, e.g. for a matching lockscreen
+    Screenshot(ScreenshotArgs),
+    /// Lock the session with swaylock showing the current frame of every monitor
+    Lock(LockArgs),
+    /// Turn animations off for a still wallpaper, e.g. for accessibility or screen recording
+    Motion(MotionArgs),
+    /// Browse, set and tweak wallpapers in an interactive terminal UI
+    Tui(TuiArgs),
+    /// Print the completion script for a shell
+    Completions(CompletionsArgs),
+    /// Print the man page, or write one for every command into a directory
+    Manpage(ManpageArgs),
 }
 
 #[derive(Args, Debug)]
@@ -49,13 +132,40 @@ pub struct SetFramerateArgs {
 
 #[derive(Args, Debug)]
 pub struct LoadWallpaperArgs {
-    /// Path to the wallpaper directory
+    /// Name of the wallpaper or path to its directory
     #[arg(required = true)]
     pub path: String,
+
+    /// Refuse the wallpaper if its manifest has warnings, like misspelled fields
+    #[arg(long)]
+    pub strict: bool,
 }
 
 #[derive(Args, Debug)]
-pub struct ListWallpapersArgs {}
+pub struct ListWallpapersArgs {
+    /// Only list wallpapers whose name, author, description or tags contain this text
+    pub filter: Option<String>,
+
+    /// Only list wallpapers with this tag (can be repeated)
+    #[arg(short, long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// Only list animated wallpapers
+    #[arg(long, conflicts_with = "static")]
+    pub animated: bool,
+
+    /// Only list static wallpapers
+    #[arg(long = "static")]
+    pub r#static: bool,
+
+    /// Only list wallpapers marked as favorite
+    #[arg(short, long)]
+    pub favorites: bool,
+
+    /// Show author, version, description and thumbnail of each wallpaper
+    #[arg(short, long)]
+    pub long: bool,
+}
 
 #[derive(Args, Debug)]
 pub struct InstallWallpaperArgs {
@@ -66,21 +176,398 @@ pub struct InstallWallpaperArgs {
     /// Custom name for the wallpaper (defaults to directory name)
     #[arg(short, long)]
     pub name: Option<String>,
+
+    /// Grant the permissions its scripts need without asking
+    #[arg(long)]
+    pub allow: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct SetWallpaperArgs {
-    /// Name of the wallpaper
+    /// Name of the wallpaper, its path or `dir/name` if several have the name
     #[arg(required = true)]
     pub name: String,
 
     /// Target monitor to set the wallpaper for (sets for all monitors if not specified)
     #[arg(short, long)]
     pub monitor: Option<String>,
+
+    /// Variant of the wallpaper to show, as listed by `wlrs list --long`
+    #[arg(short, long)]
+    pub variant: Option<String>,
+
+    /// Show how the wallpaper would be drawn instead of setting it: its
+    /// layers, shaders, parameters and estimated GPU memory
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct RandomArgs {
+    /// Only pick wallpapers with this tag (can be repeated)
+    #[arg(short, long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// Never pick this wallpaper, `current` for the one set last (can be repeated)
+    #[arg(short, long, value_name = "NAME")]
+    pub exclude: Vec<String>,
+
+    /// Target monitor to set the wallpaper for (sets for all monitors if not specified)
+    #[arg(short, long)]
+    pub monitor: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct FavArgs {
+    /// Name of the wallpaper, its path or `dir/name` if several have the name
+    #[arg(required = true)]
+    pub name: String,
+
+    /// Remove the wallpaper from the favorites instead
+    #[arg(short, long)]
+    pub remove: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigWallpaperArgs {
+    /// Name of the wallpaper, its path or `dir/name` if several have the name
+    #[arg(required = true)]
+    pub name: String,
+
+    /// Settings as `layer.parameter=value`, e.g. `snow.density=0.8` or
+    /// `clouds.opacity=0.5`, or `parameter=value` for parameters the
+    /// manifest declares, the value read as TOML
+    pub settings: Vec<String>,
+
+    /// Go back to the manifest's value of `layer.parameter`
+    #[arg(short, long, value_name = "KEY")]
+    pub unset: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct DescribeArgs {
+    /// Name of the wallpaper, its path or `dir/name` if several have the name
+    #[arg(required = true)]
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct PermissionsArgs {
+    /// Name of the wallpaper, its path or `dir/name` if several have the name
+    #[arg(required = true)]
+    pub name: String,
+
+    /// Grant all the permissions the wallpaper needs
+    #[arg(long, conflicts_with = "revoke")]
+    pub grant: bool,
+
+    /// Revoke the permissions granted to the wallpaper
+    #[arg(long)]
+    pub revoke: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct StopArgs {}
 
+#[derive(Args, Debug)]
+pub struct DirsArgs {}
+
+#[derive(Args, Debug)]
+pub struct BrowseArgs {
+    /// Only list wallpapers whose name, author, description or tags contain this text
+    pub filter: Option<String>,
+
+    /// Only list wallpapers with this tag (can be repeated)
+    #[arg(short, long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// Repository to search instead of the configured ones (can be repeated)
+    #[arg(short, long = "source", value_name = "URL")]
+    pub sources: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct FetchArgs {
+    /// Name of the wallpaper in the repository
+    #[arg(required = true)]
+    pub name: String,
+
+    /// Repository to fetch from instead of the configured ones (can be repeated)
+    #[arg(short, long = "source", value_name = "URL")]
+    pub sources: Vec<String>,
+
+    /// Install it under another directory name
+    #[arg(long = "as", value_name = "NAME")]
+    pub install_name: Option<String>,
+
+    /// Grant the permissions its scripts need without asking
+    #[arg(long)]
+    pub allow: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportWeArgs {
+    /// Directory of the project, the one with its project.json
+    #[arg(required = true)]
+    pub path: PathBuf,
+
+    /// Name of the installed wallpaper directory (defaults to the title)
+    #[arg(short, long)]
+    pub name: Option<String>,
+
+    /// Write the wallpaper to this directory instead of installing it
+    #[arg(short, long, conflicts_with = "name")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportDesktopArgs {
+    /// Desktop to read the settings of (detected if not specified)
+    #[arg(short, long)]
+    pub desktop: Option<Desktop>,
+
+    /// Write the wallpapers into this directory instead of installing them
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Desktop {
+    Gnome,
+    Kde,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Path to the wallpaper directory
+    #[arg(required = true)]
+    pub path: PathBuf,
+
+    /// Print an [assets] table with the hashes of every file instead, to
+    /// paste into the manifest
+    #[arg(long)]
+    pub print: bool,
+
+    /// Fail on warnings about the manifest too, like misspelled fields
+    #[arg(long)]
+    pub strict: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateManifestArgs {
+    /// Wallpaper directories or manifest files (can be repeated)
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct BakeArgs {
+    /// Name of an installed wallpaper or path to a wallpaper directory
+    #[arg(required = true)]
+    pub wallpaper: String,
+}
+
+#[derive(Args, Debug)]
+pub struct RestartArgs {
+    /// Path to the daemon binary (defaults to wlrs-daemon next to this binary)
+    #[arg(short, long)]
+    pub daemon: Option<String>,
+}
+
 #[derive(Args, Debug)]
 pub struct QueryArgs {}
+
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Args, Debug)]
+pub struct InstallServiceArgs {
+    /// Also install a socket unit so the daemon starts on the first request
+    #[arg(short, long)]
+    pub socket: bool,
+
+    /// Path to the daemon binary (defaults to wlrs-daemon next to this binary)
+    #[arg(short, long)]
+    pub daemon: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct LogLevelArgs {
+    /// A level (error, warn, info, debug or trace), or directives like `info,daemon::renderer=trace`
+    #[arg(required = true)]
+    pub filter: String,
+}
+
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    /// Refresh the statistics every second until interrupted
+    #[arg(short, long)]
+    pub watch: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct MirrorArgs {
+    /// Output whose wallpaper is shown, like DP-1
+    #[arg(required = true)]
+    pub source: String,
+
+    /// Outputs that show it until they are given a wallpaper of their own
+    #[arg(required = true)]
+    pub targets: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct RenderScaleArgs {
+    /// Factor applied to width and height between 0.25 and 2, e.g. 0.5 for heavy effects
+    /// on a 4K monitor or 1 to render at the monitor resolution again
+    #[arg(required = true)]
+    pub scale: f32,
+
+    /// Target monitor (sets for all monitors if not specified)
+    #[arg(short, long)]
+    pub monitor: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct AdjustArgs {
+    /// Color temperature in Kelvin, 6500 is neutral and lower is warmer
+    #[arg(long = "temp")]
+    pub temperature: Option<u32>,
+
+    /// Brightness multiplier, 1 is unchanged
+    #[arg(long)]
+    pub brightness: Option<f32>,
+
+    /// Contrast multiplier, 1 is unchanged
+    #[arg(long)]
+    pub contrast: Option<f32>,
+
+    /// Gamma, above 1 brightens the midtones
+    #[arg(long)]
+    pub gamma: Option<f32>,
+
+    /// Start from the neutral adjustment instead of the current one
+    #[arg(long)]
+    pub reset: bool,
+
+    /// Target monitor (adjusts all monitors if not specified)
+    #[arg(short, long)]
+    pub monitor: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct OverviewModeArgs {
+    /// Whether the overview shows
+    #[arg(value_enum)]
+    pub state: OverviewState,
+
+    /// Target monitor (changes all monitors if not specified)
+    #[arg(short, long)]
+    pub monitor: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OverviewState {
+    On,
+    Off,
+    Toggle,
+}
+
+#[derive(Args, Debug)]
+pub struct ColorsArgs {
+    #[command(subcommand)]
+    pub command: ColorsCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ColorsCommand {
+    /// Use the scheme in a pywal colors.json or `matugen --json hex` output
+    Set {
+        /// Path to the JSON file
+        file: String,
+
+        /// Use the light variant of matugen schemes
+        #[arg(long)]
+        light: bool,
+    },
+    /// Go back to the scheme file named in the daemon's config
+    Reset,
+    /// Compute a scheme from the wallpaper shown on a monitor
+    Extract {
+        /// Monitor to take the wallpaper of (the first one if not specified)
+        #[arg(short, long)]
+        monitor: Option<String>,
+
+        /// Print the scheme as pywal's colors.json
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct ScreenshotArgs {
+    /// Where to write the PNG image
+    #[arg(short, long, required = true)]
+    pub output: PathBuf,
+
+    /// Monitor to capture (the first one showing a wallpaper if not specified)
+    #[arg(short, long)]
+    pub monitor: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct LockArgs {
+    /// Locker to run, it is passed `-i <monitor>:<image>` for every monitor
+    #[arg(long, default_value = "swaylock")]
+    pub locker: String,
+
+    /// Only save the frames and print `<monitor> <image>` lines, for other lockers
+    #[arg(long)]
+    pub capture_only: bool,
+
+    /// Further arguments for the locker, after `--`
+    #[arg(last = true)]
+    pub args: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct MotionArgs {
+    /// Whether wallpapers animate
+    #[arg(value_enum)]
+    pub state: MotionState,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum MotionState {
+    On,
+    Off,
+    Toggle,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Show cache size and hit rate
+    Stats,
+    /// Drop all cached assets
+    Clear,
+}
+
+#[derive(Args, Debug)]
+pub struct TuiArgs {}
+
+#[derive(Args, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to complete in
+    #[arg(value_enum)]
+    pub shell: clap_complete::Shell,
+}
+
+#[derive(Args, Debug)]
+pub struct ManpageArgs {
+    /// Directory to write wlrs.1 and a page per subcommand to, like wlrs-set-wallpaper.1
+    #[arg(short, long)]
+    pub dir: Option<PathBuf>,
+}
@@ -0,0 +1,70 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+use common::desktop::{gnome_wallpaper, plasma_wallpapers, DesktopWallpaper};
+
+use crate::cli::Desktop;
+
+/// File Plasma keeps the settings of its desktops in
+const PLASMA_CONFIG: &str = "plasma-org.kde.plasma.desktop-appletsrc";
+
+/// The wallpapers `desktop` is set to, the running desktop if `None`
+pub fn wallpapers(desktop: Option<Desktop>) -> Result<Vec<DesktopWallpaper>, String> {
+    match desktop.unwrap_or_else(running_desktop) {
+        Desktop::Gnome => {
+            let dark = gsettings("org.gnome.desktop.interface", "color-scheme")
+                .is_ok_and(|scheme| scheme.contains("prefer-dark"));
+            let uri = if dark {
+                gsettings("org.gnome.desktop.background", "picture-uri-dark")?
+            } else {
+                gsettings("org.gnome.desktop.background", "picture-uri")?
+            };
+            let options = gsettings("org.gnome.desktop.background", "picture-options")?;
+            let color = gsettings("org.gnome.desktop.background", "primary-color")?;
+            let wallpaper = gnome_wallpaper(&uri, &options, &color).map_err(|e| e.to_string())?;
+            Ok(vec![wallpaper])
+        }
+        Desktop::Kde => {
+            let path = plasma_config();
+            let config = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+            Ok(plasma_wallpapers(&config))
+        }
+    }
+}
+
+/// The desktop from `XDG_CURRENT_DESKTOP`, else Plasma if it has a config
+fn running_desktop() -> Desktop {
+    let current = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    if current.split(':').any(|desktop| desktop == "KDE") {
+        Desktop::Kde
+    } else if current.split(':').any(|desktop| desktop == "GNOME") {
+        Desktop::Gnome
+    } else if plasma_config().exists() {
+        Desktop::Kde
+    } else {
+        Desktop::Gnome
+    }
+}
+
+fn plasma_config() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from(".config"))
+        .join(PLASMA_CONFIG)
+}
+
+/// The value of a GNOME setting, as `gsettings get` prints it
+fn gsettings(schema: &str, key: &str) -> Result<String, String> {
+    let output = Command::new("gsettings")
+        .args(["get", schema, key])
+        .output()
+        .map_err(|e| format!("Failed to run gsettings: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "gsettings could not read {schema} {key}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -1,19 +1,51 @@
+mod bake;
 mod cli;
+mod desktop;
+mod repository;
+mod tui;
 
-use clap::Parser;
-use std::{fs, path::Path};
+use clap::{CommandFactory, Parser};
+use std::{
+    env, fs,
+    io::{self, BufRead, IsTerminal, Write},
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt},
+        process::CommandExt,
+    },
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use common::{
-    ipc::{IpcError, IpcSocket, Stream},
+    colorscheme::{ColorScheme, SchemeMode},
+    desktop::Playlist,
+    ipc::{self, IpcError, IpcSocket, Stream},
+    manifest::{self, WallpaperManifest, SCHEMA_VERSION},
     types::{
-        Checkhealth, GetInstallDirectory, ListWallpapers, LoadWallpaper,
-        QueryActiveWallpapers, SetCurrentWallpaper, StopServer,
+        CaptureFrame, Checkhealth, ClearCache, DescribeWallpaper, ExtractColors, GetCacheStats,
+        GetInstallDirectory, GetStats, GetWallpaperSettings, InstallDirectory, LayerTiming,
+        ListWallpapers, LoadWallpaper, MirrorOutput, ParamDescription, PlanWallpaper,
+        QueryActiveWallpapers, RenderStats, SetColorScheme, SetCurrentWallpaper,
+        SetDisplayAdjustment, SetFavorite, SetLogLevel, SetMotion, SetOverviewMode, SetPermissions,
+        SetRandomWallpaper, SetRenderScale, SetWallpaperSetting, StopServer, WallpaperPlan,
     },
+    wallpaper::{hash_assets, Wallpaper, WallpaperError},
+    wallpaper_engine::{self, Conversion},
 };
 use fs_extra::dir::{copy, CopyOptions};
 
+/// How often `wlrs stats --watch` refreshes
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long `wlrs restart` waits for the new daemon to take over
+const RESTART_TIMEOUT: Duration = Duration::from_secs(15);
+
 fn main() -> Result<(), IpcError> {
     let cli = cli::Cli::parse();
+    ipc::set_timeout((cli.timeout > 0).then(|| Duration::from_secs(cli.timeout)));
+    ipc::set_namespace(cli.namespace.clone());
 
     match cli.command {
         cli::Commands::Ping(_) => {
@@ -42,57 +74,43 @@ fn main() -> Result<(), IpcError> {
             // Try to connect to the daemon
             match IpcSocket::<Stream>::connect() {
                 Ok(mut client) => {
-                    // Check if this is a wallpaper name (without path separators) or a path
-                    if !args.path.contains('/') && !args.path.contains('\\') {
-                        // This looks like just a name, use SetCurrentWallpaper
-                        println!("Loading wallpaper by name: {}", args.path);
-                        let request = SetCurrentWallpaper {
-                            name: args.path,
-                            monitor: None,
-                        };
-
-                        match client.request(request) {
-                            Ok(response) => {
-                                if response.success {
-                                    println!("Wallpaper '{}' loaded successfully", response.name);
-                                } else {
-                                    eprintln!(
-                                        "Failed to load wallpaper: {}",
-                                        response
-                                            .error
-                                            .unwrap_or_else(|| "Unknown error".to_string())
-                                    );
-                                }
-                                Ok(())
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to load wallpaper: {e:?}");
-                                Err(e)
-                            }
-                        }
+                    // The daemon may run in another directory, send it absolute paths
+                    let path = Path::new(&args.path);
+                    let path = if path.is_dir() {
+                        std::path::absolute(path)
+                            .map(|path| path.to_string_lossy().to_string())
+                            .unwrap_or(args.path)
                     } else {
-                        // This is a path, use LoadWallpaper
-                        println!("Loading wallpaper from path: {}", args.path);
-                        let request = LoadWallpaper { path: args.path };
+                        args.path
+                    };
+                    let request = LoadWallpaper {
+                        path,
+                        strict: args.strict,
+                    };
 
-                        match client.request(request) {
-                            Ok(response) => {
-                                if response.success {
-                                    println!("Wallpaper '{}' loaded successfully", response.name);
-                                } else {
-                                    eprintln!(
-                                        "Failed to load wallpaper: {}",
-                                        response
-                                            .error
-                                            .unwrap_or_else(|| "Unknown error".to_string())
-                                    );
-                                }
-                                Ok(())
+                    match client.request(request) {
+                        Ok(response) => {
+                            for warning in &response.warnings {
+                                eprintln!("Warning: {warning}");
                             }
-                            Err(e) => {
-                                eprintln!("Failed to load wallpaper: {e:?}");
-                                Err(e)
+                            if response.success {
+                                println!(
+                                    "Preloading wallpaper '{}', setting it will be instant",
+                                    response.name
+                                );
+                            } else {
+                                eprintln!(
+                                    "Failed to load wallpaper: {}",
+                                    response
+                                        .error
+                                        .unwrap_or_else(|| "Unknown error".to_string())
+                                );
                             }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to load wallpaper: {e:?}");
+                            Err(e)
                         }
                     }
                 }
@@ -102,16 +120,35 @@ fn main() -> Result<(), IpcError> {
                 }
             }
         }
-        cli::Commands::ListWallpapers(_) => {
+        cli::Commands::ListWallpapers(args) => {
             // Try to connect to the daemon
             match IpcSocket::<Stream>::connect() {
                 Ok(mut client) => {
                     // Send list wallpapers request
-                    let request = ListWallpapers;
+                    let animated = match (args.animated, args.r#static) {
+                        (true, _) => Some(true),
+                        (_, true) => Some(false),
+                        _ => None,
+                    };
+                    let filtered = args.filter.is_some()
+                        || !args.tags.is_empty()
+                        || animated.is_some()
+                        || args.favorites;
+                    let request = ListWallpapers {
+                        details: args.long,
+                        filter: args.filter,
+                        tags: args.tags,
+                        animated,
+                        favorites: args.favorites,
+                    };
                     match client.request(request) {
                         Ok(list) => {
                             if list.wallpapers.is_empty() {
-                                println!("No wallpapers installed");
+                                if filtered {
+                                    println!("No wallpapers match the filters");
+                                } else {
+                                    println!("No wallpapers installed");
+                                }
                             } else {
                                 println!("Available wallpapers:");
                                 println!("{}", "-".repeat(60));
@@ -127,6 +164,48 @@ fn main() -> Result<(), IpcError> {
 
                                     println!("Directory: {dir_name}");
                                     println!("Path: {}", wallpaper.path);
+                                    if let Some(shadowed_by) = wallpaper.shadowed_by {
+                                        println!(
+                                            "Shadowed by: {shadowed_by} (set this one by its path)"
+                                        );
+                                    }
+                                    if let Some(details) = wallpaper.details {
+                                        if !details.author.is_empty() {
+                                            println!("Author: {}", details.author);
+                                        }
+                                        println!("Version: {}", details.version);
+                                        if !details.description.is_empty() {
+                                            println!("Description: {}", details.description);
+                                        }
+                                        if !details.tags.is_empty() {
+                                            println!("Tags: {}", details.tags.join(", "));
+                                        }
+                                        if !details.variants.is_empty() {
+                                            println!("Variants: {}", details.variants.join(", "));
+                                        }
+                                        let kind = if details.animated {
+                                            "animated"
+                                        } else {
+                                            "static"
+                                        };
+                                        println!("Type: {kind}");
+                                        if details.usage.favorite {
+                                            println!("Favorite: yes");
+                                        }
+                                        if let Some(last_used) = details.usage.last_used {
+                                            println!(
+                                                "Used: {} time(s), last {}",
+                                                details.usage.use_count,
+                                                format_ago(last_used)
+                                            );
+                                        }
+                                        if let Some(preview) = details.preview {
+                                            println!("Preview: {preview}");
+                                        }
+                                        if let Some(thumbnail) = details.thumbnail {
+                                            println!("Thumbnail: {thumbnail}");
+                                        }
+                                    }
                                     println!("{}", "-".repeat(60));
                                 }
                                 println!("Total: {len} wallpaper(s)");
@@ -165,6 +244,15 @@ fn main() -> Result<(), IpcError> {
                         return Ok(());
                     }
 
+                    // Refuse a damaged download before it replaces an installed copy
+                    if let Err(e) = WallpaperManifest::from_file(&manifest_path)
+                        .map_err(WallpaperError::from)
+                        .and_then(|manifest| Wallpaper::verify_integrity(source_path, &manifest))
+                    {
+                        eprintln!("The wallpaper cannot be installed: {e}");
+                        return Ok(());
+                    }
+
                     // Get the installation directory from the server
                     let request = GetInstallDirectory;
                     match client.request(request) {
@@ -227,13 +315,13 @@ fn main() -> Result<(), IpcError> {
                                     );
 
                                     if copied_dir != target_dir && args.name.is_some() {
-                                        fs::rename(copied_dir, target_dir).unwrap_or_else(|e| {
+                                        fs::rename(copied_dir, &target_dir).unwrap_or_else(|e| {
                                             eprintln!("Failed to rename wallpaper directory: {e}");
                                             std::process::exit(1);
                                         });
                                     }
 
-                                    Ok(())
+                                    approve_permissions(&target_dir, args.allow)
                                 }
                                 Err(e) => {
                                     eprintln!("Failed to copy wallpaper directory: {e}");
@@ -253,6 +341,36 @@ fn main() -> Result<(), IpcError> {
                 }
             }
         }
+        cli::Commands::SetWallpaper(args) if args.dry_run => match IpcSocket::<Stream>::connect() {
+            Ok(mut client) => {
+                let request = PlanWallpaper {
+                    name: args.name,
+                    monitor: args.monitor,
+                    variant: args.variant,
+                };
+                match client.request(request) {
+                    Ok(plan) if plan.success => {
+                        print_plan(&plan);
+                        Ok(())
+                    }
+                    Ok(plan) => {
+                        eprintln!(
+                            "Failed to load wallpaper: {}",
+                            plan.error.unwrap_or_else(|| "Unknown error".to_string())
+                        );
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to plan wallpaper: {e:?}");
+                        Err(e)
+                    }
+                }
+            }
+            Err(_) => {
+                eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                Err(IpcError::ConnectionClosed)
+            }
+        },
         cli::Commands::SetWallpaper(args) => {
             // Try to connect to the daemon
             match IpcSocket::<Stream>::connect() {
@@ -261,6 +379,7 @@ fn main() -> Result<(), IpcError> {
                     let request = SetCurrentWallpaper {
                         name: args.name,
                         monitor: args.monitor,
+                        variant: args.variant,
                     };
                     match client.request(request) {
                         Ok(status) => {
@@ -286,6 +405,227 @@ fn main() -> Result<(), IpcError> {
                 }
             }
         }
+        cli::Commands::Random(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    let (current, exclude): (Vec<_>, Vec<_>) =
+                        args.exclude.into_iter().partition(|name| name == "current");
+                    let request = SetRandomWallpaper {
+                        tags: args.tags,
+                        exclude,
+                        exclude_current: !current.is_empty(),
+                        monitor: args.monitor,
+                    };
+                    match client.request(request) {
+                        Ok(status) => {
+                            if let (true, Some(name)) = (status.success, status.name) {
+                                println!("Current wallpaper set to '{name}'");
+                            } else {
+                                eprintln!(
+                                    "Failed to set a random wallpaper: {}",
+                                    status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                );
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to set a random wallpaper: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::Fav(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    let request = SetFavorite {
+                        name: args.name,
+                        favorite: !args.remove,
+                    };
+                    match client.request(request) {
+                        Ok(status) => {
+                            if !status.success {
+                                eprintln!(
+                                    "Failed to update favorites: {}",
+                                    status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                );
+                            } else if args.remove {
+                                println!("Removed '{}' from favorites", status.name);
+                            } else {
+                                println!("Added '{}' to favorites", status.name);
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to update favorites: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::ConfigWallpaper(args) => {
+            let mut changes = Vec::new();
+            for setting in &args.settings {
+                let Some((key, value)) = setting.split_once('=') else {
+                    eprintln!("Settings are written as layer.parameter=value, not '{setting}'");
+                    std::process::exit(1);
+                };
+                changes.push((key.trim().to_string(), Some(value.to_string())));
+            }
+            changes.extend(args.unset.iter().map(|key| (key.clone(), None)));
+
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) if changes.is_empty() => {
+                    match client.request(GetWallpaperSettings { name: args.name }) {
+                        Ok(settings) => {
+                            if settings.settings.is_empty() {
+                                println!("'{}' uses the values of its manifest", settings.name);
+                            }
+                            for (key, value) in settings.settings {
+                                println!("{key} = {value}");
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to get the settings: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Ok(mut client) => {
+                    let request = SetWallpaperSetting {
+                        name: args.name,
+                        changes: changes.clone(),
+                    };
+                    let status = client
+                        .request(request)
+                        .inspect_err(|e| eprintln!("Failed to change the settings: {e:?}"))?;
+                    if !status.success {
+                        eprintln!(
+                            "Failed to change the settings: {}",
+                            status.error.unwrap_or_else(|| "Unknown error".to_string())
+                        );
+                        std::process::exit(1);
+                    }
+                    for (key, value) in changes {
+                        match value {
+                            Some(value) => println!("Set {key} of '{}' to {value}", status.name),
+                            None => println!("Reset {key} of '{}'", status.name),
+                        }
+                    }
+                    Ok(())
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::Describe(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => match client.request(DescribeWallpaper { name: args.name }) {
+                    Ok(description) => {
+                        if !description.success {
+                            eprintln!(
+                                "Failed to describe the wallpaper: {}",
+                                description
+                                    .error
+                                    .unwrap_or_else(|| "Unknown error".to_string())
+                            );
+                            std::process::exit(1);
+                        }
+                        if description.params.is_empty() {
+                            println!("'{}' declares no parameters", description.name);
+                        }
+                        for param in &description.params {
+                            println!("{}: {}", param.name, describe_param(param));
+                            if !param.description.is_empty() {
+                                println!("    {}", param.description);
+                            }
+                            if let Some(value) = &param.value {
+                                println!("    set to {value}");
+                            }
+                        }
+                        if !description.variants.is_empty() {
+                            println!("Variants: {}", description.variants.join(", "));
+                        }
+                        Ok(())
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to describe the wallpaper: {e:?}");
+                        Err(e)
+                    }
+                },
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::Permissions(args) => {
+            let grant = match (args.grant, args.revoke) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            };
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    match client.request(SetPermissions {
+                        name: args.name,
+                        grant,
+                    }) {
+                        Ok(status) => {
+                            if !status.success {
+                                eprintln!(
+                                    "Failed to update the permissions: {}",
+                                    status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                );
+                                std::process::exit(1);
+                            }
+                            if status.needs.is_empty() {
+                                println!("The scripts of '{}' need no permissions", status.name);
+                                return Ok(());
+                            }
+                            println!("The scripts of '{}' need:", status.name);
+                            for need in &status.needs {
+                                let state = if status.granted.contains(need) {
+                                    "granted"
+                                } else {
+                                    "not granted"
+                                };
+                                println!("  {need:<10} {state}");
+                            }
+                            if status.stale {
+                                println!("They were granted to older scripts, grant them again");
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to update the permissions: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
         cli::Commands::Query(_) => {
             // Try to connect to the daemon
             match IpcSocket::<Stream>::connect() {
@@ -301,11 +641,45 @@ fn main() -> Result<(), IpcError> {
                                     println!("Active wallpapers:");
                                     for wallpaper in result.wallpapers {
                                         println!("  Monitor: {}", wallpaper.output_name);
+                                        let description =
+                                            wallpaper.description.clone().unwrap_or_else(|| {
+                                                format!("{} {}", wallpaper.make, wallpaper.model)
+                                            });
+                                        if !description.trim().is_empty() {
+                                            println!("    Description: {}", description.trim());
+                                        }
                                         println!("    Name: {}", wallpaper.name);
                                         println!(
                                             "    Size: {}x{}",
                                             wallpaper.width, wallpaper.height
                                         );
+                                        println!("    Position: {},{}", wallpaper.x, wallpaper.y);
+                                        if let Some(source) = &wallpaper.mirror_of {
+                                            println!("    Mirroring: {source}");
+                                        }
+                                        if wallpaper.render_scale != 1.0 {
+                                            println!(
+                                                "    Render scale: {}x",
+                                                wallpaper.render_scale
+                                            );
+                                        }
+                                        if wallpaper.overview {
+                                            println!("    Overview mode: on");
+                                        }
+                                        let adjustment = &wallpaper.adjustment;
+                                        if !adjustment.is_identity() {
+                                            println!(
+                                                "    Adjustment: {}K, brightness {}, contrast {}, gamma {}",
+                                                adjustment.temperature,
+                                                adjustment.brightness,
+                                                adjustment.contrast,
+                                                adjustment.gamma
+                                            );
+                                        }
+                                        print_layer_timings(
+                                            wallpaper.frame_budget_us,
+                                            &wallpaper.layers,
+                                        );
                                         println!();
                                     }
                                 }
@@ -329,32 +703,1418 @@ fn main() -> Result<(), IpcError> {
                 }
             }
         }
-        cli::Commands::Stop(_) => {
+        cli::Commands::Cache(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => match args.command {
+                    cli::CacheCommand::Stats => match client.request(GetCacheStats) {
+                        Ok(stats) => {
+                            let lookups = stats.hits + stats.misses;
+                            let hit_rate = if lookups > 0 {
+                                stats.hits as f64 / lookups as f64 * 100.0
+                            } else {
+                                0.0
+                            };
+                            println!("Asset cache:");
+                            println!("  Entries:   {}", stats.entries);
+                            println!(
+                                "  Size:      {} / {}",
+                                format_bytes(stats.bytes),
+                                format_bytes(stats.budget)
+                            );
+                            println!(
+                                "  Hits:      {} ({hit_rate:.1}% of {lookups} lookups)",
+                                stats.hits
+                            );
+                            println!("  Misses:    {}", stats.misses);
+                            println!("  Evictions: {}", stats.evictions);
+                            match stats.gpu_budget {
+                                Some(budget) => println!(
+                                    "GPU memory: {} / {}",
+                                    format_bytes(stats.gpu_bytes),
+                                    format_bytes(budget)
+                                ),
+                                None => println!(
+                                    "GPU memory: {} (no budget)",
+                                    format_bytes(stats.gpu_bytes)
+                                ),
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to get cache stats: {e:?}");
+                            Err(e)
+                        }
+                    },
+                    cli::CacheCommand::Clear => match client.request(ClearCache) {
+                        Ok(cleared) => {
+                            println!(
+                                "Cleared {} cached asset(s), released {}",
+                                cleared.entries,
+                                format_bytes(cleared.bytes)
+                            );
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to clear cache: {e:?}");
+                            Err(e)
+                        }
+                    },
+                },
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::InstallService(args) => {
+            let daemon = daemon_path(args.daemon)?;
+            if !daemon.exists() {
+                eprintln!(
+                    "Daemon binary '{}' not found, pass its path with --daemon",
+                    daemon.display()
+                );
+                return Ok(());
+            }
+
+            let unit_dir = env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+                .map(|config| config.join("systemd").join("user"))
+                .unwrap_or_else(|| PathBuf::from(".config/systemd/user"));
+            fs::create_dir_all(&unit_dir).map_err(IpcError::Io)?;
+
+            // Namespaced daemons get units of their own
+            let name = match &cli.namespace {
+                Some(namespace) => format!("wlrs-{namespace}"),
+                None => "wlrs".to_string(),
+            };
+            let service = unit_dir.join(format!("{name}.service"));
+            fs::write(&service, service_unit(&daemon, cli.namespace.as_deref()))
+                .map_err(IpcError::Io)?;
+            println!("Installed {}", service.display());
+
+            let unit = if args.socket {
+                let socket = unit_dir.join(format!("{name}.socket"));
+                fs::write(&socket, socket_unit(&IpcSocket::<Stream>::socket_file()))
+                    .map_err(IpcError::Io)?;
+                println!("Installed {}", socket.display());
+                format!("{name}.socket")
+            } else {
+                format!("{name}.service")
+            };
+
+            println!("Enable it with:");
+            println!("  systemctl --user daemon-reload");
+            println!("  systemctl --user enable --now {unit}");
+            Ok(())
+        }
+        cli::Commands::Restart(args) => {
+            let daemon = daemon_path(args.daemon)?;
+            if !daemon.exists() {
+                eprintln!(
+                    "Daemon binary '{}' not found, pass its path with --daemon",
+                    daemon.display()
+                );
+                return Ok(());
+            }
+
+            // Detach the daemon from this terminal, it outlives the command
+            let mut child = Command::new(&daemon);
+            child.arg("--replace");
+            if let Some(namespace) = &cli.namespace {
+                child.arg("--namespace").arg(namespace);
+            }
+            let mut child = child
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .process_group(0)
+                .spawn()
+                .map_err(IpcError::Io)?;
+
+            // The new daemon writes its pid to the lock file once it owns the socket
+            let deadline = Instant::now() + RESTART_TIMEOUT;
+            let lock_file = IpcSocket::<Stream>::lock_file();
+            loop {
+                let owner = fs::read_to_string(&lock_file).unwrap_or_default();
+                if owner.trim().parse() == Ok(child.id()) {
+                    println!("Daemon restarted (pid {})", child.id());
+                    return Ok(());
+                }
+                if let Ok(Some(status)) = child.try_wait() {
+                    eprintln!("The new daemon exited before taking over ({status})");
+                    return Err(IpcError::ConnectionClosed);
+                }
+                if Instant::now() >= deadline {
+                    eprintln!(
+                        "The new daemon (pid {}) did not take over within {RESTART_TIMEOUT:?}",
+                        child.id()
+                    );
+                    return Err(IpcError::ConnectionClosed);
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+        cli::Commands::LogLevel(args) => {
             // Try to connect to the daemon
             match IpcSocket::<Stream>::connect() {
                 Ok(mut client) => {
-                    // Send stop server request
-                    let request = StopServer;
+                    let request = SetLogLevel {
+                        filter: args.filter.clone(),
+                    };
                     match client.request(request) {
                         Ok(status) => {
                             if status.success {
-                                println!("Daemon is shutting down gracefully");
+                                println!("Daemon log level set to '{}'", args.filter);
                             } else {
-                                eprintln!("Failed to stop daemon");
+                                eprintln!(
+                                    "Failed to set log level: {}",
+                                    status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                );
                             }
                             Ok(())
                         }
                         Err(e) => {
-                            eprintln!("Failed to stop daemon: {e:?}");
+                            eprintln!("Failed to set log level: {e:?}");
                             Err(e)
                         }
                     }
                 }
                 Err(_) => {
-                    println!("Daemon is not running");
-                    Ok(())
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
                 }
             }
         }
+        cli::Commands::Stats(args) => loop {
+            // Every request needs its own connection, so reconnect on each refresh
+            let stats = match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => match client.request(GetStats) {
+                    Ok(stats) => stats,
+                    Err(e) => {
+                        eprintln!("Failed to get stats: {e:?}");
+                        return Err(e);
+                    }
+                },
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    return Err(IpcError::ConnectionClosed);
+                }
+            };
+
+            if args.watch {
+                // Clear the terminal so the statistics update in place
+                print!("\x1b[2J\x1b[H");
+            }
+            print_stats(&stats);
+            if !args.watch {
+                return Ok(());
+            }
+            thread::sleep(STATS_INTERVAL);
+        },
+        cli::Commands::Mirror(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    let request = MirrorOutput {
+                        source: args.source.clone(),
+                        targets: args.targets.clone(),
+                    };
+                    match client.request(request) {
+                        Ok(status) => {
+                            if status.success {
+                                println!(
+                                    "Mirroring {} on {}",
+                                    args.source,
+                                    args.targets.join(", ")
+                                );
+                            } else {
+                                eprintln!(
+                                    "Failed to mirror {}: {}",
+                                    args.source,
+                                    status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                );
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to mirror {}: {e:?}", args.source);
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::RenderScale(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    let request = SetRenderScale {
+                        output: args.monitor.clone(),
+                        scale: args.scale,
+                    };
+                    match client.request(request) {
+                        Ok(status) => {
+                            if status.success {
+                                match &args.monitor {
+                                    Some(monitor) => {
+                                        println!("Rendering {monitor} at {}x", args.scale)
+                                    }
+                                    None => println!("Rendering all monitors at {}x", args.scale),
+                                }
+                            } else {
+                                eprintln!(
+                                    "Failed to set render scale: {}",
+                                    status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                );
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to set render scale: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::Adjust(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    let request = SetDisplayAdjustment {
+                        output: args.monitor.clone(),
+                        brightness: args.brightness,
+                        contrast: args.contrast,
+                        gamma: args.gamma,
+                        temperature: args.temperature,
+                        reset: args.reset,
+                    };
+                    match client.request(request) {
+                        Ok(status) => {
+                            if status.success {
+                                match &args.monitor {
+                                    Some(monitor) => println!("Adjusted {monitor}"),
+                                    None => println!("Adjusted all monitors"),
+                                }
+                            } else {
+                                eprintln!(
+                                    "Failed to adjust display: {}",
+                                    status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                );
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to adjust display: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::OverviewMode(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    let request = SetOverviewMode {
+                        output: args.monitor.clone(),
+                        enabled: match args.state {
+                            cli::OverviewState::On => Some(true),
+                            cli::OverviewState::Off => Some(false),
+                            cli::OverviewState::Toggle => None,
+                        },
+                    };
+                    match client.request(request) {
+                        Ok(status) => {
+                            if !status.success {
+                                eprintln!(
+                                    "Failed to set overview mode: {}",
+                                    status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                );
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to set overview mode: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::Motion(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    let request = SetMotion {
+                        enabled: match args.state {
+                            cli::MotionState::On => Some(true),
+                            cli::MotionState::Off => Some(false),
+                            cli::MotionState::Toggle => None,
+                        },
+                    };
+                    match client.request(request) {
+                        Ok(status) => {
+                            if status.enabled {
+                                println!("Wallpapers animate");
+                            } else {
+                                println!("Wallpapers stand still");
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to set motion: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::Completions(args) => {
+            let mut command = cli::Cli::command();
+            clap_complete::generate(args.shell, &mut command, "wlrs", &mut io::stdout());
+            Ok(())
+        }
+        cli::Commands::Manpage(args) => {
+            let command = cli::Cli::command();
+            let result = match &args.dir {
+                Some(dir) => {
+                    fs::create_dir_all(dir).and_then(|_| clap_mangen::generate_to(command, dir))
+                }
+                None => clap_mangen::Man::new(command).render(&mut io::stdout()),
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to write the man pages: {e}");
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        cli::Commands::Tui(_) => {
+            if let Err(e) = tui::run() {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        cli::Commands::Colors(args) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => match args.command {
+                    cli::ColorsCommand::Set { file, light } => {
+                        let mode = if light {
+                            SchemeMode::Light
+                        } else {
+                            SchemeMode::Dark
+                        };
+                        let scheme = match fs::read_to_string(&file)
+                            .map_err(|e| e.to_string())
+                            .and_then(|text| {
+                                ColorScheme::from_json(&text, mode).map_err(|e| e.to_string())
+                            }) {
+                            Ok(scheme) => scheme,
+                            Err(e) => {
+                                eprintln!("Failed to read {file}: {e}");
+                                return Ok(());
+                            }
+                        };
+                        let request = SetColorScheme {
+                            colors: scheme.to_list(),
+                        };
+                        match client.request(request) {
+                            Ok(status) => {
+                                if !status.success {
+                                    eprintln!(
+                                        "Failed to set color scheme: {}",
+                                        status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                    );
+                                }
+                                Ok(())
+                            }
+                            Err(e) => {
+                                eprintln!("Failed to set color scheme: {e:?}");
+                                Err(e)
+                            }
+                        }
+                    }
+                    cli::ColorsCommand::Reset => match client.request(SetColorScheme::default()) {
+                        Ok(_) => Ok(()),
+                        Err(e) => {
+                            eprintln!("Failed to reset color scheme: {e:?}");
+                            Err(e)
+                        }
+                    },
+                    cli::ColorsCommand::Extract { monitor, json } => {
+                        match client.request(ExtractColors { output: monitor }) {
+                            Ok(extracted) => {
+                                let scheme = match ColorScheme::from_list(&extracted.colors) {
+                                    Ok(scheme) if extracted.success => scheme,
+                                    _ => {
+                                        eprintln!(
+                                            "Failed to extract colors: {}",
+                                            extracted
+                                                .error
+                                                .unwrap_or_else(|| "Unknown error".to_string())
+                                        );
+                                        return Ok(());
+                                    }
+                                };
+                                if json {
+                                    println!("{}", scheme.to_json());
+                                } else {
+                                    if let Some(wallpaper) = extracted.wallpaper {
+                                        println!("Colors of {wallpaper}:");
+                                    }
+                                    println!("  background {}", scheme.background);
+                                    println!("  foreground {}", scheme.foreground);
+                                    for (index, color) in scheme.colors.iter().enumerate() {
+                                        println!("  color{index:<4} {color}");
+                                    }
+                                }
+                                Ok(())
+                            }
+                            Err(e) => {
+                                eprintln!("Failed to extract colors: {e:?}");
+                                Err(e)
+                            }
+                        }
+                    }
+                },
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::Screenshot(args) => {
+            // The daemon writes the file, relative to its own working directory otherwise
+            let path = match std::path::absolute(&args.output) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("Invalid path {}: {e}", args.output.display());
+                    return Ok(());
+                }
+            };
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    let request = CaptureFrame {
+                        output: args.monitor,
+                        path: path.to_string_lossy().to_string(),
+                    };
+                    match client.request(request) {
+                        Ok(status) => {
+                            if status.success {
+                                match status.output {
+                                    Some(output) => {
+                                        println!("Saved {output} to {}", path.display())
+                                    }
+                                    None => println!("Saved {}", path.display()),
+                                }
+                            } else {
+                                eprintln!(
+                                    "Failed to capture frame: {}",
+                                    status.error.unwrap_or_else(|| "Unknown error".to_string())
+                                );
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to capture frame: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::Lock(args) => {
+            let frames = capture_outputs(&lock_image_dir())?;
+            if args.capture_only {
+                for (output, path) in &frames {
+                    println!("{output} {}", path.display());
+                }
+                return Ok(());
+            }
+
+            // Replace this process, so whoever locks sees the locker's exit status
+            let mut locker = Command::new(&args.locker);
+            for (output, path) in &frames {
+                locker.arg("-i").arg(format!("{output}:{}", path.display()));
+            }
+            let e = locker.args(&args.args).exec();
+            eprintln!("Failed to run {}: {e}", args.locker);
+            Err(IpcError::Io(e))
+        }
+        cli::Commands::Dirs(_) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => match client.request(GetInstallDirectory) {
+                    Ok(dirs) => {
+                        for (index, path) in dirs.search_paths.iter().enumerate() {
+                            let exists = Path::new(path).is_dir();
+                            println!(
+                                "{}. {path}{}{}",
+                                index + 1,
+                                if *path == dirs.path { " (install)" } else { "" },
+                                if exists { "" } else { " (missing)" }
+                            );
+                        }
+                        Ok(())
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to get the wallpaper directories: {e:?}");
+                        Err(e)
+                    }
+                },
+                Err(_) => {
+                    eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+                    Err(IpcError::ConnectionClosed)
+                }
+            }
+        }
+        cli::Commands::Browse(args) => {
+            let sources = if args.sources.is_empty() {
+                install_directory()?.repositories
+            } else {
+                args.sources
+            };
+            if sources.is_empty() {
+                eprintln!(
+                    "No repositories configured, add them to the daemon's config or pass --source"
+                );
+                return Ok(());
+            }
+
+            let agent = repository::agent();
+            let mut found = 0;
+            for source in &sources {
+                let repository = match repository::load(&agent, source) {
+                    Ok(repository) => repository,
+                    Err(e) => {
+                        eprintln!("{e}");
+                        continue;
+                    }
+                };
+                for entry in repository.entries.iter().filter(|entry| {
+                    args.filter
+                        .as_deref()
+                        .is_none_or(|filter| entry.matches_query(filter))
+                        && entry.has_tags(&args.tags)
+                }) {
+                    println!("Name: {}", entry.name);
+                    println!("Version: {}", entry.version);
+                    if !entry.author.is_empty() {
+                        println!("Author: {}", entry.author);
+                    }
+                    if !entry.description.is_empty() {
+                        println!("Description: {}", entry.description);
+                    }
+                    if !entry.tags.is_empty() {
+                        println!("Tags: {}", entry.tags.join(", "));
+                    }
+                    println!("Repository: {}", repository.source);
+                    println!("{}", "-".repeat(60));
+                    found += 1;
+                }
+            }
+            if found == 0 {
+                println!("No wallpapers match");
+            } else {
+                println!("Total: {found} wallpaper(s), install one with 'wlrs fetch <name>'");
+            }
+            Ok(())
+        }
+        cli::Commands::Fetch(args) => {
+            let install = install_directory()?;
+            let sources = if args.sources.is_empty() {
+                install.repositories
+            } else {
+                args.sources
+            };
+
+            // The first repository offering the wallpaper wins, like the search paths
+            let agent = repository::agent();
+            let entry = sources
+                .iter()
+                .find_map(|source| match repository::load(&agent, source) {
+                    Ok(repository) => repository
+                        .entries
+                        .into_iter()
+                        .find(|entry| entry.name == args.name),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        None
+                    }
+                });
+            let Some(entry) = entry else {
+                eprintln!("No repository offers '{}'", args.name);
+                std::process::exit(1);
+            };
+
+            let name = args.install_name.unwrap_or_else(|| entry.name.clone());
+            if name.is_empty() || name.starts_with('.') || name.contains('/') {
+                eprintln!("'{name}' cannot be the name of a wallpaper directory");
+                std::process::exit(1);
+            }
+            match repository::install(&agent, &entry, Path::new(&install.path), &name) {
+                Ok(path) => {
+                    println!(
+                        "Wallpaper '{}' {} installed successfully to '{}'",
+                        entry.name,
+                        entry.version,
+                        path.display()
+                    );
+                    approve_permissions(&path, args.allow)
+                }
+                Err(e) => {
+                    eprintln!("Failed to fetch '{}': {e}", entry.name);
+                    std::process::exit(1);
+                }
+            }
+        }
+        cli::Commands::ImportWe(args) => {
+            let conversion = match wallpaper_engine::convert(&args.path) {
+                Ok(conversion) => conversion,
+                Err(e) => {
+                    eprintln!("Failed to import {}: {e}", args.path.display());
+                    std::process::exit(1);
+                }
+            };
+            let target = match args.output {
+                Some(output) => output,
+                None => {
+                    let install = install_directory()?;
+                    let name = args
+                        .name
+                        .unwrap_or_else(|| directory_name(&conversion.manifest.name));
+                    Path::new(&install.path).join(name)
+                }
+            };
+            if target.exists() {
+                eprintln!(
+                    "'{}' already exists, remove it or pick another --name",
+                    target.display()
+                );
+                std::process::exit(1);
+            }
+
+            if !conversion.unsupported.is_empty() {
+                println!("Not converted:");
+                for line in &conversion.unsupported {
+                    println!("  {line}");
+                }
+            }
+            if let Err(e) = write_conversion(conversion, &target) {
+                eprintln!("Failed to write {}: {e}", target.display());
+                let _ = fs::remove_dir_all(&target);
+                std::process::exit(1);
+            }
+            println!("Wallpaper written to '{}'", target.display());
+            Ok(())
+        }
+        cli::Commands::ImportDesktop(args) => {
+            let wallpapers = match desktop::wallpapers(args.desktop) {
+                Ok(wallpapers) if wallpapers.is_empty() => {
+                    eprintln!("No wallpaper found in the desktop settings");
+                    std::process::exit(1);
+                }
+                Ok(wallpapers) => wallpapers,
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            };
+            let target_dir = match args.output {
+                Some(output) => output,
+                None => PathBuf::from(install_directory()?.path),
+            };
+
+            let mut failed = false;
+            for wallpaper in &wallpapers {
+                let import = match common::desktop::convert(wallpaper) {
+                    Ok(import) => import,
+                    Err(e) => {
+                        eprintln!("{e}");
+                        failed = true;
+                        continue;
+                    }
+                };
+                for conversion in import.wallpapers {
+                    let target = target_dir.join(directory_name(&conversion.manifest.name));
+                    if target.exists() {
+                        println!("'{}' already exists, skipped", target.display());
+                        continue;
+                    }
+                    if let Err(e) = write_conversion(conversion, &target) {
+                        eprintln!("Failed to write {}: {e}", target.display());
+                        let _ = fs::remove_dir_all(&target);
+                        failed = true;
+                        continue;
+                    }
+                    println!("Wallpaper written to '{}'", target.display());
+                }
+                if let Some(Playlist { tag, interval }) = import.playlist {
+                    println!("The slideshow is the playlist tagged '{tag}', play it with:");
+                    println!("  wlrs random --tag {tag}");
+                    println!("or as the idle slideshow, in the [idle] section of the config:");
+                    println!("  action = \"slideshow\"");
+                    println!("  slideshow_interval = {interval}");
+                    println!("  slideshow_tags = [\"{tag}\"]");
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        cli::Commands::Verify(args) => {
+            if args.print {
+                match hash_assets(&args.path) {
+                    Ok(hashes) => {
+                        println!("[assets]");
+                        for (asset, hash) in hashes {
+                            println!("{asset:?} = {hash:?}");
+                        }
+                        Ok(())
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to hash {}: {e}", args.path.display());
+                        Err(IpcError::Io(e))
+                    }
+                }
+            } else {
+                let manifest_path = args.path.join("manifest.toml");
+                let manifest = match WallpaperManifest::from_file_with_warnings(&manifest_path) {
+                    Ok((manifest, warnings)) => {
+                        for warning in &warnings {
+                            eprintln!("Warning: {warning}");
+                        }
+                        if args.strict && !warnings.is_empty() {
+                            std::process::exit(1);
+                        }
+                        manifest
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to read the manifest: {e}");
+                        std::process::exit(1);
+                    }
+                };
+                if manifest.assets.is_empty() {
+                    println!(
+                        "'{}' lists no asset hashes, 'wlrs verify --print' writes them",
+                        manifest.name
+                    );
+                    return Ok(());
+                }
+                match Wallpaper::verify_integrity(&args.path, &manifest) {
+                    Ok(()) => {
+                        println!(
+                            "All {} asset(s) of '{}' match their hashes",
+                            manifest.assets.len(),
+                            manifest.name
+                        );
+                        Ok(())
+                    }
+                    Err(e) => {
+                        eprintln!("{e}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        cli::Commands::MigrateManifest(args) => {
+            let mut failed = false;
+            for path in args.paths {
+                let path = if path.is_dir() {
+                    path.join("manifest.toml")
+                } else {
+                    path
+                };
+                match migrate_manifest(&path) {
+                    Ok(Some(from)) => println!(
+                        "{}: migrated from schema version {from} to {SCHEMA_VERSION}",
+                        path.display()
+                    ),
+                    Ok(None) => println!("{}: already up to date", path.display()),
+                    Err(e) => {
+                        eprintln!("{}: {e}", path.display());
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        cli::Commands::Bake(args) => {
+            let dir = wallpaper_dir(&args.wallpaper)?;
+            let manifest = match WallpaperManifest::from_file(dir.join("manifest.toml")) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    eprintln!("Failed to read the manifest: {e}");
+                    std::process::exit(1);
+                }
+            };
+            let images = bake::images(&manifest);
+            if images.is_empty() {
+                println!("'{}' has no still images to bake", manifest.name);
+                return Ok(());
+            }
+
+            let mut failed = false;
+            for image in images {
+                match bake::bake(&dir.join(&image)) {
+                    Ok(baked) => println!(
+                        "{image}: {}x{}, {} on the GPU instead of {}",
+                        baked.width,
+                        baked.height,
+                        format_bytes(baked.baked_bytes),
+                        format_bytes(baked.rgba_bytes)
+                    ),
+                    Err(e) => {
+                        eprintln!("{image}: {e}");
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        cli::Commands::Stop(_) => {
+            // Try to connect to the daemon
+            match IpcSocket::<Stream>::connect() {
+                Ok(mut client) => {
+                    // Send stop server request
+                    let request = StopServer;
+                    match client.request(request) {
+                        Ok(status) => {
+                            if status.success {
+                                println!("Daemon is shutting down gracefully");
+                            } else if let Some(error) = status.error {
+                                eprintln!("Failed to stop daemon: {error}");
+                            } else {
+                                eprintln!("Failed to stop daemon");
+                            }
+                            Ok(())
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to stop daemon: {e:?}");
+                            Err(e)
+                        }
+                    }
+                }
+                Err(_) => {
+                    println!("Daemon is not running");
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+/// Rewrite the manifest at `path` in the current schema, returns the
+/// version it had or `None` if it already was current
+///
+/// The new manifest is written next to the old one and renamed over it, so
+/// a failed write leaves the old one intact.
+fn migrate_manifest(path: &Path) -> Result<Option<i64>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    match manifest::migrate_text(&text).map_err(|e| e.to_string())? {
+        Some((from, migrated)) => {
+            let partial = path.with_extension("toml.partial");
+            fs::write(&partial, migrated)
+                .and_then(|()| fs::rename(&partial, path))
+                .map_err(|e| {
+                    let _ = fs::remove_file(&partial);
+                    e.to_string()
+                })?;
+            Ok(Some(from))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Copy the files of a converted wallpaper into `target` and write its
+/// manifest, with the hashes of the files
+fn write_conversion(mut conversion: Conversion, target: &Path) -> Result<(), String> {
+    for (relative, source) in &conversion.files {
+        if !manifest::is_inside(relative) {
+            return Err(format!("{relative} is not a path inside the wallpaper"));
+        }
+        let path = target.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::copy(source, &path).map_err(|e| format!("{relative}: {e}"))?;
+    }
+    fs::create_dir_all(target).map_err(|e| e.to_string())?;
+    conversion.manifest.assets = hash_assets(target).map_err(|e| e.to_string())?;
+    conversion
+        .manifest
+        .to_file(target.join("manifest.toml"))
+        .map_err(|e| e.to_string())
+}
+
+/// Directory name for a wallpaper title, like `northern-lights` for
+/// `Northern Lights!`
+fn directory_name(title: &str) -> String {
+    let name = title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if name.is_empty() {
+        "imported".to_string()
+    } else {
+        name
+    }
+}
+
+/// Directory of a wallpaper given by path, or by name among those the
+/// daemon knows
+fn wallpaper_dir(wallpaper: &str) -> Result<PathBuf, IpcError> {
+    let path = Path::new(wallpaper);
+    if path.join("manifest.toml").is_file() {
+        return Ok(path.to_path_buf());
+    }
+    let Ok(mut client) = IpcSocket::<Stream>::connect() else {
+        eprintln!("'{wallpaper}' is not a wallpaper directory and the daemon is not running to look it up");
+        std::process::exit(1);
+    };
+    let list = client.request(ListWallpapers::default())?;
+    match list
+        .wallpapers
+        .into_iter()
+        .find(|info| info.name == wallpaper && info.shadowed_by.is_none())
+    {
+        Some(info) => Ok(PathBuf::from(info.path)),
+        None => {
+            eprintln!("Wallpaper '{wallpaper}' not found");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Ask the daemon where wallpapers are installed and fetched from
+/// Ask the user to grant what the scripts of the wallpaper just installed
+/// to `path` need, unless `allow` already does
+///
+/// Without a terminal to ask on they are not granted.
+fn approve_permissions(path: &Path, allow: bool) -> Result<(), IpcError> {
+    let Ok(manifest) = WallpaperManifest::from_file(path.join("manifest.toml")) else {
+        return Ok(());
+    };
+    if manifest.needs.is_empty() {
+        return Ok(());
+    }
+    println!("'{}' asks to:", manifest.name);
+    for permission in &manifest.needs {
+        println!("  {}", permission.description());
+    }
+    if !allow && !confirm("Allow them?") {
+        println!(
+            "They run without, grant them later with 'wlrs permissions \"{}\" --grant'",
+            manifest.name
+        );
+        return Ok(());
+    }
+
+    let mut client = IpcSocket::<Stream>::connect()?;
+    let status = client.request(SetPermissions {
+        name: path.display().to_string(),
+        grant: Some(true),
+    })?;
+    if status.success {
+        println!("Granted {}", status.granted.join(", "));
+    } else {
+        eprintln!(
+            "Failed to grant the permissions: {}",
+            status.error.unwrap_or_else(|| "Unknown error".to_string())
+        );
+    }
+    Ok(())
+}
+
+/// Ask a yes or no question on the terminal, no without one
+fn confirm(question: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+    print!("{question} [y/N] ");
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn install_directory() -> Result<InstallDirectory, IpcError> {
+    let Ok(mut client) = IpcSocket::<Stream>::connect() else {
+        eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+        return Err(IpcError::ConnectionClosed);
+    };
+    client.request(GetInstallDirectory)
+}
+
+/// Directory `wlrs lock` saves the frames to, private to the user
+fn lock_image_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+        .join("wlrs-lock")
+}
+
+/// Create `dir` so only the user can enter it, or make sure it already is
+///
+/// Without a runtime dir it is in the shared temporary directory, where
+/// another user could have made it first to read or swap the frames.
+fn private_dir(dir: &Path) -> io::Result<()> {
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir()
+        || metadata.uid() != IpcSocket::<Stream>::getuid()
+        || metadata.mode() & 0o777 != 0o700
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not a directory private to this user", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Save the current frame of every output showing a wallpaper into `dir`
+///
+/// Outputs that cannot be captured are skipped, the locker shows its own
+/// background there.
+fn capture_outputs(dir: &Path) -> Result<Vec<(String, PathBuf)>, IpcError> {
+    let Ok(mut client) = IpcSocket::<Stream>::connect() else {
+        eprintln!("Daemon is not running. Start it first with 'wlrs start'");
+        return Err(IpcError::ConnectionClosed);
+    };
+    let active = client.request(QueryActiveWallpapers)?;
+    private_dir(dir).map_err(IpcError::Io)?;
+
+    let mut frames = Vec::new();
+    for wallpaper in active.wallpapers {
+        // Every request needs its own connection
+        let path = dir.join(format!("{}.png", wallpaper.output_name));
+        let request = CaptureFrame {
+            output: Some(wallpaper.output_name.clone()),
+            path: path.to_string_lossy().to_string(),
+        };
+        let status = IpcSocket::<Stream>::connect()?.request(request)?;
+        if status.success {
+            frames.push((wallpaper.output_name, path));
+        } else {
+            eprintln!(
+                "Failed to capture {}: {}",
+                wallpaper.output_name,
+                status.error.unwrap_or_else(|| "Unknown error".to_string())
+            );
+        }
+    }
+    Ok(frames)
+}
+
+/// Print how long each layer takes to draw, marking the ones that blow the
+/// frame budget, and what went wrong with them
+fn print_layer_timings(frame_budget_us: u64, layers: &[LayerTiming]) {
+    if layers.is_empty() {
+        return;
+    }
+    println!(
+        "    Layers (frame budget {:.2} ms):",
+        frame_budget_us as f64 / 1000.0
+    );
+    for layer in layers {
+        let gpu = match layer.gpu_us {
+            Some(gpu_us) => format!("{:.2} ms", gpu_us as f64 / 1000.0),
+            None => "n/a".to_string(),
+        };
+        let warning = if layer.over_budget {
+            "  <- over budget"
+        } else {
+            ""
+        };
+        println!(
+            "      {}: {:.2} ms CPU, {gpu} GPU{warning}",
+            layer.name,
+            layer.cpu_us as f64 / 1000.0
+        );
+        if let Some(warning) = &layer.warning {
+            println!("        Warning: {warning}");
+        }
+    }
+}
+
+/// Print render statistics of every output
+fn print_stats(stats: &RenderStats) {
+    println!(
+        "Cached textures: {} / {}",
+        format_bytes(stats.texture_bytes),
+        format_bytes(stats.texture_budget)
+    );
+    if stats.outputs.is_empty() {
+        println!("No outputs");
+    }
+
+    for output in &stats.outputs {
+        println!();
+        match &output.wallpaper {
+            Some(wallpaper) => println!("{}: {wallpaper}", output.output_name),
+            None => println!("{}: no wallpaper", output.output_name),
+        }
+        if output.refresh_rate > 0 {
+            println!(
+                "  FPS:        {} ({:.2} Hz output)",
+                output.fps,
+                output.refresh_rate as f64 / 1000.0
+            );
+        } else {
+            println!("  FPS:        {}", output.fps);
+        }
+        println!(
+            "  Frame time: {:.2} ms average, {:.2} ms 99th percentile",
+            output.frame_time_avg_us as f64 / 1000.0,
+            output.frame_time_p99_us as f64 / 1000.0
+        );
+        println!(
+            "  Frames:     {} ({} dropped)",
+            output.frames, output.dropped_frames
+        );
+        for error in &output.gpu_errors {
+            println!("  GPU error:  {error}");
+        }
+        let models: Vec<String> = output
+            .models
+            .iter()
+            .map(|(kind, count)| format!("{count} {kind}"))
+            .collect();
+        if models.is_empty() {
+            println!("  Models:     none");
+        } else {
+            println!("  Models:     {}", models.join(", "));
+        }
+    }
+}
+
+/// Print how a wallpaper would be drawn on each output
+fn print_plan(plan: &WallpaperPlan) {
+    println!(
+        "{}: {} FPS, {} ticks per second",
+        plan.name,
+        describe_rate(plan.framerate),
+        describe_rate(plan.tickrate)
+    );
+    for warning in &plan.warnings {
+        println!("Warning: {warning}");
+    }
+    if plan.outputs.is_empty() {
+        println!("No outputs");
+    }
+
+    for output in &plan.outputs {
+        println!();
+        println!("{} ({}x{}):", output.output, output.width, output.height);
+        for layer in &output.layers {
+            let kind = match &layer.shader {
+                Some(shader) => format!("{} {shader}", layer.kind),
+                None => layer.kind.clone(),
+            };
+            let size = layer
+                .size
+                .map(|(width, height)| format!(", {width}x{height}"))
+                .unwrap_or_default();
+            println!(
+                "  {}: {kind}{size}, {}",
+                layer.name,
+                format_bytes(layer.texture_bytes)
+            );
+            if let Some(source) = &layer.source {
+                println!("    from {source}");
+            }
+            for (key, value) in &layer.params {
+                println!("    {key} = {value}");
+            }
+            if let Some(error) = &layer.error {
+                println!("    error: {error}");
+            }
+        }
+        if !output.hidden.is_empty() {
+            println!("  Hidden here: {}", output.hidden.join(", "));
+        }
+        println!(
+            "  Estimated texture memory: {}",
+            format_bytes(output.texture_bytes)
+        );
+    }
+}
+
+/// Describe a framerate or tickrate of a manifest
+fn describe_rate(rate: i32) -> String {
+    match rate {
+        0 => "no".to_string(),
+        rate if rate < 0 => "compositor".to_string(),
+        rate => rate.to_string(),
+    }
+}
+
+/// Format a byte count for display, e.g. `12.3 MiB`
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[0])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Describe a Unix timestamp relative to now, e.g. `3 hours ago`
+fn format_ago(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(timestamp);
+    let seconds = now.saturating_sub(timestamp);
+    let (value, unit) = match seconds {
+        0..60 => return "just now".to_string(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    let plural = if value == 1 { "" } else { "s" };
+    format!("{value} {unit}{plural} ago")
+}
+
+/// The daemon binary given on the command line, or the one next to this binary
+fn daemon_path(explicit: Option<String>) -> Result<PathBuf, IpcError> {
+    match explicit {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => env::current_exe()
+            .map(|exe| exe.with_file_name("wlrs-daemon"))
+            .map_err(IpcError::Io),
+    }
+}
+
+/// Unit file for running the daemon as part of the graphical session
+fn service_unit(daemon: &Path, namespace: Option<&str>) -> String {
+    let namespace = namespace
+        .map(|namespace| format!(" --namespace {namespace}"))
+        .unwrap_or_default();
+    format!(
+        "[Unit]
+Description=wlrs wallpaper daemon
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+Type=notify
+ExecStart={} --systemd{namespace}
+Restart=on-failure
+
+[Install]
+WantedBy=graphical-session.target
+",
+        systemd_quote(&daemon.to_string_lossy())
+    )
+}
+
+/// Quote the program of an `ExecStart=` line, which systemd splits at
+/// spaces and where it expands `%` specifiers, but not `$` variables
+fn systemd_quote(program: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in program.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Unit file for starting the daemon when the IPC socket is first used
+///
+/// The socket path depends on `WAYLAND_DISPLAY`, so it is fixed to the
+/// display of the session the unit was installed from.
+fn socket_unit(socket_file: &str) -> String {
+    format!(
+        "[Unit]
+Description=wlrs wallpaper daemon socket
+PartOf=graphical-session.target
+
+[Socket]
+ListenStream={socket_file}
+RemoveOnStop=yes
+
+[Install]
+WantedBy=graphical-session.target
+"
+    )
+}
+
+/// One line summing up a declared parameter, like
+/// `Blur radius, float from 0 to 20 in steps of 0.5, 3.5 by default`
+fn describe_param(param: &ParamDescription) -> String {
+    let mut line = format!("{}, {}", param.label, param.kind);
+    match (param.min, param.max) {
+        (Some(min), Some(max)) => line.push_str(&format!(" from {min} to {max}")),
+        (Some(min), None) => line.push_str(&format!(" from {min}")),
+        (None, Some(max)) => line.push_str(&format!(" up to {max}")),
+        (None, None) => {}
+    }
+    if let Some(step) = param.step {
+        line.push_str(&format!(" in steps of {step}"));
+    }
+    if !param.options.is_empty() {
+        line.push_str(&format!(" of {}", param.options.join(", ")));
+    }
+    if let Some(default) = &param.default {
+        line.push_str(&format!(", {default} by default"));
     }
+    line
 }
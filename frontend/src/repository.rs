@@ -0,0 +1,142 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use common::{
+    manifest::WallpaperManifest,
+    repository::{index_url, parse_index, RepositoryEntry},
+    wallpaper::{hash_bytes, Wallpaper},
+};
+
+/// Largest file downloaded, so a broken server cannot fill the disk
+const MAX_DOWNLOAD: u64 = 512 * 1024 * 1024;
+
+/// How long connecting to a repository and each read from it may take
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wallpapers offered by a repository, along with where it is
+pub struct Repository {
+    pub source: String,
+    pub entries: Vec<RepositoryEntry>,
+}
+
+pub fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(TIMEOUT)
+        .timeout_read(TIMEOUT)
+        .user_agent(concat!("wlrs/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// Download the index of the repository at `source`
+pub fn load(agent: &ureq::Agent, source: &str) -> Result<Repository, String> {
+    let url = index_url(source);
+    let index = download(agent, &url)?;
+    let text = String::from_utf8(index).map_err(|_| format!("{url} is not UTF-8"))?;
+    let entries = parse_index(&text, &url).map_err(|e| format!("{url}: {e}"))?;
+    Ok(Repository {
+        source: source.to_string(),
+        entries,
+    })
+}
+
+/// Download a wallpaper and install it into `install_dir` as `name`,
+/// replacing an installed one of that name
+///
+/// The wallpaper is assembled next to its final place and only moved there
+/// once every file matches its hash, so a failed download leaves the
+/// installed copy alone.
+pub fn install(
+    agent: &ureq::Agent,
+    entry: &RepositoryEntry,
+    install_dir: &Path,
+    name: &str,
+) -> Result<PathBuf, String> {
+    let manifest_text = download(agent, &entry.manifest)?;
+    if hash_bytes(&manifest_text) != entry.hash {
+        return Err(format!(
+            "The manifest of {} does not match the hash in the index",
+            entry.name
+        ));
+    }
+    let manifest = std::str::from_utf8(&manifest_text)
+        .map_err(|_| "The manifest is not UTF-8".to_string())
+        .and_then(|text| WallpaperManifest::parse(text).map_err(|e| e.to_string()))?;
+    if manifest.assets.is_empty() {
+        return Err(format!(
+            "{} lists no files in [assets], so they cannot be downloaded and checked",
+            entry.name
+        ));
+    }
+
+    fs::create_dir_all(install_dir).map_err(|e| e.to_string())?;
+    let staging = install_dir.join(format!(".{name}.part"));
+    let result = assemble(agent, entry, &manifest, &manifest_text, &staging);
+    if let Err(e) = result {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    let target = install_dir.join(name);
+    if target.exists() {
+        fs::remove_dir_all(&target)
+            .map_err(|e| format!("Failed to remove {}: {e}", target.display()))?;
+    }
+    fs::rename(&staging, &target)
+        .map_err(|e| format!("Failed to move the wallpaper to {}: {e}", target.display()))?;
+    Ok(target)
+}
+
+/// Download every file of the `[assets]` table into `dir`, then the manifest
+fn assemble(
+    agent: &ureq::Agent,
+    entry: &RepositoryEntry,
+    manifest: &WallpaperManifest,
+    manifest_text: &[u8],
+    dir: &Path,
+) -> Result<(), String> {
+    if dir.exists() {
+        fs::remove_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    for (asset, hash) in &manifest.assets {
+        let data = download(agent, &entry.asset_url(asset))?;
+        if hash_bytes(&data) != *hash {
+            return Err(format!("{asset} does not match its hash in the manifest"));
+        }
+        let path = dir.join(asset);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(&path, data).map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    }
+    fs::write(dir.join("manifest.toml"), manifest_text).map_err(|e| e.to_string())?;
+
+    // Also checks that the layers only use files that were downloaded
+    Wallpaper::load(dir)
+        .map(|_| ())
+        .map_err(|e| format!("The downloaded wallpaper is broken: {e}"))
+}
+
+/// Fetch a document over HTTPS, refusing anything larger than `MAX_DOWNLOAD`
+fn download(agent: &ureq::Agent, url: &str) -> Result<Vec<u8>, String> {
+    if !url.starts_with("https://") {
+        return Err(format!("Refusing to download {url}, only HTTPS is allowed"));
+    }
+    let response = agent
+        .get(url)
+        .call()
+        .map_err(|e| format!("Failed to download {url}: {e}"))?;
+    let mut data = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_DOWNLOAD + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Failed to download {url}: {e}"))?;
+    if data.len() as u64 > MAX_DOWNLOAD {
+        return Err(format!("{url} is larger than {MAX_DOWNLOAD} bytes"));
+    }
+    Ok(data)
+}
@@ -0,0 +1,668 @@
+use std::{collections::BTreeSet, io};
+
+use common::{
+    ipc::{IpcSocket, Stream},
+    types::{
+        ActiveWallpaperInfo, DescribeWallpaper, IntoRequest, ListWallpapers, ParamDescription,
+        QueryActiveWallpapers, Response, SetCurrentWallpaper, SetFavorite, SetRandomWallpaper,
+        SetWallpaperSetting, WallpaperInfo,
+    },
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+/// Keys of the wallpaper list, shown at the bottom
+const BROWSE_KEYS: &str = "enter set  m monitor  v variant  f favorite  t playlist  r random  \
+                           p params  / search  q quit";
+
+/// Keys of the parameter editor, shown at the bottom
+const PARAM_KEYS: &str = "←/→ change  enter type a value  u reset  esc back";
+
+/// Run the terminal UI until the user quits
+pub fn run() -> Result<(), String> {
+    let mut app = App::new()?;
+    let mut terminal = ratatui::try_init().map_err(|e| e.to_string())?;
+    let result = app.run(&mut terminal).map_err(|e| e.to_string());
+    ratatui::restore();
+    result
+}
+
+/// Send one request to the daemon, which answers one per connection
+fn request<R: IntoRequest>(request: R) -> Result<R::Response, String>
+where
+    R::Response: TryFrom<Response, Error = ()>,
+{
+    IpcSocket::<Stream>::connect()
+        .map_err(|_| "Daemon is not running. Start it first with 'wlrs start'".to_string())?
+        .request(request)
+        .map_err(|e| format!("{e:?}"))
+}
+
+/// What the keys act on
+enum Mode {
+    Browse,
+    /// Typing a search, the wallpaper list follows every key
+    Search,
+    /// Changing the declared parameters of the selected wallpaper
+    Params {
+        name: String,
+        params: Vec<ParamDescription>,
+        selected: ListState,
+        /// Value being typed for the selected parameter
+        input: Option<String>,
+    },
+}
+
+struct App {
+    /// Every installed wallpaper, with details
+    wallpapers: Vec<WallpaperInfo>,
+    active: Vec<ActiveWallpaperInfo>,
+    /// Indices into `wallpapers` of the ones the search and playlist let
+    /// through
+    shown: Vec<usize>,
+    list: ListState,
+    search: String,
+    /// Tag the list is limited to, wlrs' take on a playlist
+    playlist: Option<String>,
+    /// Output wallpapers are set on, all if None
+    monitor: Option<String>,
+    /// Variant of the selected wallpaper to set, its default look if None
+    variant: Option<String>,
+    mode: Mode,
+    status: String,
+    quit: bool,
+}
+
+impl App {
+    fn new() -> Result<Self, String> {
+        let mut app = Self {
+            wallpapers: Vec::new(),
+            active: Vec::new(),
+            shown: Vec::new(),
+            list: ListState::default(),
+            search: String::new(),
+            playlist: None,
+            monitor: None,
+            variant: None,
+            mode: Mode::Browse,
+            status: String::new(),
+            quit: false,
+        };
+        app.reload()?;
+        app.list.select((!app.shown.is_empty()).then_some(0));
+        Ok(app)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetch the wallpapers and what the monitors show from the daemon
+    fn reload(&mut self) -> Result<(), String> {
+        let selected = self.selected().map(|wallpaper| wallpaper.path.clone());
+        self.wallpapers = request(ListWallpapers {
+            details: true,
+            filter: None,
+            tags: Vec::new(),
+            animated: None,
+            favorites: false,
+        })?
+        .wallpapers;
+        self.wallpapers
+            .retain(|wallpaper| wallpaper.shadowed_by.is_none());
+        let active = request(QueryActiveWallpapers)?;
+        if !active.success {
+            return Err(active.error.unwrap_or_else(|| "Unknown error".to_string()));
+        }
+        self.active = active.wallpapers;
+        self.refilter();
+        if let Some(index) = selected.and_then(|path| {
+            self.shown
+                .iter()
+                .position(|&index| self.wallpapers[index].path == path)
+        }) {
+            self.list.select(Some(index));
+        }
+        Ok(())
+    }
+
+    /// Apply the search and the playlist to the list
+    fn refilter(&mut self) {
+        let search = self.search.to_lowercase();
+        self.shown =
+            self.wallpapers
+                .iter()
+                .enumerate()
+                .filter(|(_, wallpaper)| {
+                    let details = wallpaper.details.as_ref();
+                    let in_playlist = self.playlist.as_ref().is_none_or(|tag| {
+                        details.is_some_and(|details| details.tags.contains(tag))
+                    });
+                    let found = search.is_empty()
+                        || wallpaper.name.to_lowercase().contains(&search)
+                        || details.is_some_and(|details| {
+                            details.author.to_lowercase().contains(&search)
+                                || details.description.to_lowercase().contains(&search)
+                                || details.tags.iter().any(|tag| tag.contains(&search))
+                        });
+                    in_playlist && found
+                })
+                .map(|(index, _)| index)
+                .collect();
+        let selected = self.list.selected().unwrap_or(0);
+        self.list.select(if self.shown.is_empty() {
+            None
+        } else {
+            Some(selected.min(self.shown.len() - 1))
+        });
+    }
+
+    fn selected(&self) -> Option<&WallpaperInfo> {
+        self.list
+            .selected()
+            .and_then(|index| self.shown.get(index))
+            .map(|&index| &self.wallpapers[index])
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        match self.mode {
+            Mode::Browse => self.browse_key(key.code),
+            Mode::Search => match key.code {
+                KeyCode::Enter | KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    self.search.pop();
+                    self.refilter();
+                }
+                KeyCode::Char(c) => {
+                    self.search.push(c);
+                    self.refilter();
+                }
+                _ => {}
+            },
+            Mode::Params { .. } => self.param_key(key.code),
+        }
+    }
+
+    fn browse_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.list.select_next();
+                self.variant = None;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.list.select_previous();
+                self.variant = None;
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.list.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.list.select_last(),
+            KeyCode::Char('/') => {
+                self.search.clear();
+                self.refilter();
+                self.mode = Mode::Search;
+            }
+            KeyCode::Enter => self.set_selected(),
+            KeyCode::Char('m') => {
+                let outputs: Vec<String> = self
+                    .active
+                    .iter()
+                    .map(|active| active.output_name.clone())
+                    .collect();
+                self.monitor = cycle(&outputs, self.monitor.as_ref());
+            }
+            KeyCode::Char('v') => {
+                let variants = self
+                    .selected()
+                    .and_then(|wallpaper| wallpaper.details.as_ref())
+                    .map(|details| details.variants.clone())
+                    .unwrap_or_default();
+                self.variant = cycle(&variants, self.variant.as_ref());
+            }
+            KeyCode::Char('t') => {
+                let tags: BTreeSet<String> = self
+                    .wallpapers
+                    .iter()
+                    .filter_map(|wallpaper| wallpaper.details.as_ref())
+                    .flat_map(|details| details.tags.iter().cloned())
+                    .collect();
+                let tags: Vec<String> = tags.into_iter().collect();
+                self.playlist = cycle(&tags, self.playlist.as_ref());
+                self.refilter();
+            }
+            KeyCode::Char('f') => self.toggle_favorite(),
+            KeyCode::Char('r') => self.set_random(),
+            KeyCode::Char('p') => self.open_params(),
+            KeyCode::Char('R') => self.refresh(),
+            _ => {}
+        }
+    }
+
+    /// Fetch everything again, keeping the error as the status
+    fn refresh(&mut self) {
+        if let Err(e) = self.reload() {
+            self.status = e;
+        }
+    }
+
+    fn set_selected(&mut self) {
+        let Some(wallpaper) = self.selected() else {
+            return;
+        };
+        let name = wallpaper.name.clone();
+        let result = request(SetCurrentWallpaper {
+            name: name.clone(),
+            monitor: self.monitor.clone(),
+            variant: self.variant.clone(),
+        });
+        self.status = match result {
+            Ok(set) if set.success => format!("Set {name} on {}", self.target()),
+            Ok(set) => set.error.unwrap_or_else(|| "Unknown error".to_string()),
+            Err(e) => e,
+        };
+        self.refresh_active();
+    }
+
+    fn set_random(&mut self) {
+        let result = request(SetRandomWallpaper {
+            tags: self.playlist.iter().cloned().collect(),
+            exclude_current: true,
+            monitor: self.monitor.clone(),
+            ..Default::default()
+        });
+        self.status = match result {
+            Ok(set) if set.success => {
+                format!("Set {} on {}", set.name.unwrap_or_default(), self.target())
+            }
+            Ok(set) => set.error.unwrap_or_else(|| "Unknown error".to_string()),
+            Err(e) => e,
+        };
+        self.refresh_active();
+    }
+
+    /// Fetch what the monitors show, after setting a wallpaper
+    fn refresh_active(&mut self) {
+        if let Ok(active) = request(QueryActiveWallpapers) {
+            self.active = active.wallpapers;
+        }
+    }
+
+    fn toggle_favorite(&mut self) {
+        let Some(wallpaper) = self.selected() else {
+            return;
+        };
+        let favorite = !wallpaper
+            .details
+            .as_ref()
+            .is_some_and(|details| details.usage.favorite);
+        let result = request(SetFavorite {
+            name: wallpaper.path.clone(),
+            favorite,
+        });
+        match result {
+            Ok(set) if set.success => self.refresh(),
+            Ok(set) => self.status = set.error.unwrap_or_else(|| "Unknown error".to_string()),
+            Err(e) => self.status = e,
+        }
+    }
+
+    fn open_params(&mut self) {
+        let Some(wallpaper) = self.selected() else {
+            return;
+        };
+        let name = wallpaper.path.clone();
+        match request(DescribeWallpaper { name: name.clone() }) {
+            Ok(description) if description.success && description.params.is_empty() => {
+                self.status = format!("{} declares no parameters", description.name);
+            }
+            Ok(description) if description.success => {
+                self.mode = Mode::Params {
+                    name,
+                    params: description.params,
+                    selected: ListState::default().with_selected(Some(0)),
+                    input: None,
+                };
+            }
+            Ok(description) => {
+                self.status = description
+                    .error
+                    .unwrap_or_else(|| "Unknown error".to_string())
+            }
+            Err(e) => self.status = e,
+        }
+    }
+
+    fn param_key(&mut self, code: KeyCode) {
+        let Mode::Params {
+            name,
+            params,
+            selected,
+            input,
+        } = &mut self.mode
+        else {
+            return;
+        };
+        let Some(param) = selected.selected().and_then(|index| params.get(index)) else {
+            return;
+        };
+
+        if let Some(text) = input {
+            match code {
+                KeyCode::Esc => *input = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Enter => {
+                    let value = Some(text.clone());
+                    *input = None;
+                    let (name, key) = (name.clone(), param.name.clone());
+                    self.change_param(name, key, value);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Browse,
+            KeyCode::Down | KeyCode::Char('j') => selected.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => selected.select_previous(),
+            KeyCode::Enter => {
+                *input = Some(
+                    param
+                        .value
+                        .clone()
+                        .or(param.default.clone())
+                        .unwrap_or_default(),
+                );
+            }
+            KeyCode::Char('u') => {
+                let (name, key) = (name.clone(), param.name.clone());
+                self.change_param(name, key, None);
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                if let Some(value) = step_param(param, false) {
+                    let (name, key) = (name.clone(), param.name.clone());
+                    self.change_param(name, key, Some(value));
+                }
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                if let Some(value) = step_param(param, true) {
+                    let (name, key) = (name.clone(), param.name.clone());
+                    self.change_param(name, key, Some(value));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Store a setting in the daemon, which rebuilds the wallpaper with it,
+    /// and show the parameters as they are now
+    fn change_param(&mut self, name: String, key: String, value: Option<String>) {
+        let result = request(SetWallpaperSetting {
+            name: name.clone(),
+            changes: vec![(key.clone(), value.clone())],
+        });
+        self.status = match result {
+            Ok(set) if set.success => match &value {
+                Some(value) => format!("Set {key} to {value}"),
+                None => format!("Reset {key}"),
+            },
+            Ok(set) => set.error.unwrap_or_else(|| "Unknown error".to_string()),
+            Err(e) => e,
+        };
+        if let (Ok(description), Mode::Params { params, .. }) =
+            (request(DescribeWallpaper { name }), &mut self.mode)
+        {
+            *params = description.params;
+        }
+    }
+
+    /// Where wallpapers are set, for messages
+    fn target(&self) -> &str {
+        self.monitor.as_deref().unwrap_or("all monitors")
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, main, footer] = Layout::vertical([
+            Constraint::Length(self.active.len().max(1) as u16 + 2),
+            Constraint::Min(0),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+        let [list, details] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        self.draw_active(frame, header);
+        self.draw_list(frame, list);
+        if let Mode::Params { .. } = self.mode {
+            self.draw_params(frame, details);
+        } else {
+            self.draw_details(frame, details);
+        }
+
+        let keys = match &self.mode {
+            Mode::Browse => BROWSE_KEYS.to_string(),
+            Mode::Search => format!("/{}", self.search),
+            Mode::Params {
+                input: Some(text), ..
+            } => format!("value: {text}"),
+            Mode::Params { .. } => PARAM_KEYS.to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(self.status.as_str()),
+                Line::from(keys).dim(),
+            ]),
+            footer,
+        );
+    }
+
+    fn draw_active(&self, frame: &mut Frame, area: Rect) {
+        let mut lines: Vec<Line> = self
+            .active
+            .iter()
+            .map(|active| {
+                let shows = match &active.mirror_of {
+                    Some(source) => format!("mirrors {source}"),
+                    None => active.name.clone(),
+                };
+                Line::from(vec![
+                    Span::from(format!("{:<12}", active.output_name)).bold(),
+                    Span::from(format!("{}x{}  ", active.width, active.height)).dim(),
+                    Span::from(shows),
+                ])
+            })
+            .collect();
+        if lines.is_empty() {
+            lines.push(Line::from("No monitors").dim());
+        }
+        let title = format!(" Monitors, setting on {} ", self.target());
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .shown
+            .iter()
+            .map(|&index| {
+                let wallpaper = &self.wallpapers[index];
+                let details = wallpaper.details.as_ref();
+                let mut line = Line::from(wallpaper.name.as_str());
+                if details.is_some_and(|details| details.usage.favorite) {
+                    line.push_span(Span::from(" ★").yellow());
+                }
+                if self
+                    .active
+                    .iter()
+                    .any(|active| active.name == wallpaper.name)
+                {
+                    line.push_span(Span::from(" ●").green());
+                }
+                ListItem::new(line)
+            })
+            .collect();
+        let title = match &self.playlist {
+            Some(tag) => format!(" Wallpapers tagged {tag} "),
+            None => " Wallpapers ".to_string(),
+        };
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().reversed())
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Details ");
+        let Some(wallpaper) = self.selected() else {
+            frame.render_widget(Paragraph::new("No wallpapers").block(block), area);
+            return;
+        };
+        let mut lines = vec![Line::from(wallpaper.name.as_str()).bold()];
+        if let Some(details) = &wallpaper.details {
+            let field = |label: &str, value: String| {
+                Line::from(vec![
+                    Span::from(format!("{label:<10}")).dim(),
+                    Span::from(value),
+                ])
+            };
+            if !details.author.is_empty() {
+                lines.push(field("Author", details.author.clone()));
+            }
+            lines.push(field("Version", details.version.clone()));
+            if !details.tags.is_empty() {
+                lines.push(field("Tags", details.tags.join(", ")));
+            }
+            if !details.variants.is_empty() {
+                let variant = self.variant.as_deref().unwrap_or("default");
+                lines.push(field(
+                    "Variant",
+                    format!("{variant} of {}", details.variants.join(", ")),
+                ));
+            }
+            lines.push(field(
+                "Animated",
+                if details.animated { "yes" } else { "no" }.to_string(),
+            ));
+            lines.push(field("Used", format!("{} times", details.usage.use_count)));
+            lines.push(field("Path", wallpaper.path.clone()));
+            if !details.description.is_empty() {
+                lines.push(Line::default());
+                lines.push(Line::from(details.description.as_str()));
+            }
+        }
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: false })
+                .block(block),
+            area,
+        );
+    }
+
+    fn draw_params(&mut self, frame: &mut Frame, area: Rect) {
+        let Mode::Params {
+            params, selected, ..
+        } = &mut self.mode
+        else {
+            return;
+        };
+        let items: Vec<ListItem> = params
+            .iter()
+            .map(|param| {
+                let value = match (&param.value, &param.default) {
+                    (Some(value), _) => Span::from(value.clone()).bold(),
+                    (None, Some(default)) => Span::from(default.clone()),
+                    (None, None) => Span::from("unset").dim(),
+                };
+                let mut lines = vec![Line::from(vec![
+                    Span::from(format!("{}: ", param.label)),
+                    value,
+                ])];
+                if !param.description.is_empty() {
+                    lines.push(Line::from(format!("  {}", param.description)).dim());
+                }
+                ListItem::new(lines)
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(" Parameters "))
+            .highlight_style(Style::new().reversed())
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, area, selected);
+    }
+}
+
+/// The item after `current` in `items`, None after the last one so cycling
+/// goes back to "any"
+fn cycle(items: &[String], current: Option<&String>) -> Option<String> {
+    match current.and_then(|current| items.iter().position(|item| item == current)) {
+        Some(index) => items.get(index + 1).cloned(),
+        None => items.first().cloned(),
+    }
+}
+
+/// The value of `param` one step up or down, as TOML, None for parameters
+/// that have to be typed
+fn step_param(param: &ParamDescription, up: bool) -> Option<String> {
+    let current = param.value.as_ref().or(param.default.as_ref());
+    match param.kind.as_str() {
+        "float" | "int" => {
+            let value: f64 = current.and_then(|value| value.parse().ok()).unwrap_or(0.0);
+            let step = param
+                .step
+                .unwrap_or(match (param.kind.as_str(), param.min, param.max) {
+                    ("int", _, _) => 1.0,
+                    (_, Some(min), Some(max)) => (max - min) / 20.0,
+                    _ => 0.1,
+                });
+            let mut value = if up { value + step } else { value - step };
+            if let Some(min) = param.min {
+                value = value.max(min);
+            }
+            if let Some(max) = param.max {
+                value = value.min(max);
+            }
+            Some(if param.kind == "int" {
+                format!("{}", value.round() as i64)
+            } else {
+                // Keep the TOML a float, and drop rounding noise of the steps
+                format!("{:?}", (value * 1e6).round() / 1e6)
+            })
+        }
+        "bool" => Some((current.map(String::as_str) != Some("true")).to_string()),
+        "choice" => {
+            let current = current.map(|value| value.trim_matches('"'));
+            let index = param
+                .options
+                .iter()
+                .position(|option| Some(option.as_str()) == current);
+            let count = param.options.len();
+            let next = match (index, up) {
+                (Some(index), true) => (index + 1) % count,
+                (Some(index), false) => (index + count - 1) % count,
+                (None, _) => 0,
+            };
+            param.options.get(next).map(|option| format!("{option:?}"))
+        }
+        _ => None,
+    }
+}